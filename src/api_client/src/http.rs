@@ -0,0 +1,189 @@
+// Copyright 2021 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! The subset of HTTP/1.1 spoken by the Firecracker API: requests with an optional JSON body,
+//! and responses whose body length is given by `Content-Length`.
+
+use std::io::{Read, Write};
+
+use super::{Error, Result};
+
+const HEADERS_END: &[u8] = b"\r\n\r\n";
+// Responses with longer headers are considered invalid.
+const MAX_HEADERS_LEN: usize = 8192;
+
+/// A response of the API server.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Response {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+/// Writes a request to `stream` and reads back the response.
+pub(crate) fn send_request<S: Read + Write>(
+    stream: &mut S,
+    method: &str,
+    path: &str,
+    body: Option<&[u8]>,
+) -> Result<Response> {
+    let mut request = format!("{} {} HTTP/1.1\r\nHost: localhost\r\n", method, path).into_bytes();
+    if let Some(body) = body {
+        request.extend_from_slice(
+            format!(
+                "Content-Type: application/json\r\nContent-Length: {}\r\n",
+                body.len()
+            )
+            .as_bytes(),
+        );
+    }
+    request.extend_from_slice(b"\r\n");
+    if let Some(body) = body {
+        request.extend_from_slice(body);
+    }
+    stream.write_all(&request).map_err(Error::Io)?;
+    stream.flush().map_err(Error::Io)?;
+
+    read_response(stream)
+}
+
+fn read_response<S: Read>(stream: &mut S) -> Result<Response> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    let headers_len = loop {
+        if let Some(pos) = find(&buf, HEADERS_END) {
+            break pos + HEADERS_END.len();
+        }
+        if buf.len() > MAX_HEADERS_LEN {
+            return Err(Error::InvalidResponse(
+                "The response headers are too long.".to_string(),
+            ));
+        }
+        let len = stream.read(&mut chunk).map_err(Error::Io)?;
+        if len == 0 {
+            return Err(Error::InvalidResponse(
+                "The connection was closed before the response was complete.".to_string(),
+            ));
+        }
+        buf.extend_from_slice(&chunk[..len]);
+    };
+
+    let headers = std::str::from_utf8(&buf[..headers_len])
+        .map_err(|_| Error::InvalidResponse("The response headers are not UTF-8.".to_string()))?;
+    let mut lines = headers.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|status_line| status_line.split_whitespace().nth(1))
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or_else(|| Error::InvalidResponse("Invalid status line.".to_string()))?;
+    let mut content_length = 0;
+    for line in lines {
+        let mut header = line.splitn(2, ':');
+        let name = header.next().unwrap_or("").trim();
+        if name.eq_ignore_ascii_case("Content-Length") {
+            content_length = header
+                .next()
+                .and_then(|value| value.trim().parse::<usize>().ok())
+                .ok_or_else(|| Error::InvalidResponse("Invalid Content-Length.".to_string()))?;
+        }
+    }
+
+    let mut body = buf.split_off(headers_len);
+    if body.len() < content_length {
+        let mut rest = vec![0u8; content_length - body.len()];
+        stream.read_exact(&mut rest).map_err(Error::Io)?;
+        body.extend_from_slice(&rest);
+    }
+    body.truncate(content_length);
+
+    Ok(Response { status, body })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    // A stream whose reads come from `input`, and whose writes are recorded.
+    struct MockStream {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for MockStream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            // Hand out small reads, to exercise the reassembly of the response.
+            let len = std::cmp::min(buf.len(), 7);
+            self.input.read(&mut buf[..len])
+        }
+    }
+
+    impl Write for MockStream {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn mock_stream(input: &[u8]) -> MockStream {
+        MockStream {
+            input: Cursor::new(input.to_vec()),
+            output: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_send_request() {
+        let mut stream = mock_stream(
+            b"HTTP/1.1 200 \r\nServer: Firecracker API\r\ncontent-length: 13\r\n\r\n{\"id\":\"vm0\"}\n",
+        );
+        let response = send_request(&mut stream, "PUT", "/drives/rootfs", Some(b"{}")).unwrap();
+        assert_eq!(
+            response,
+            Response {
+                status: 200,
+                body: b"{\"id\":\"vm0\"}\n".to_vec(),
+            }
+        );
+        assert_eq!(
+            String::from_utf8(stream.output).unwrap(),
+            "PUT /drives/rootfs HTTP/1.1\r\nHost: localhost\r\n\
+             Content-Type: application/json\r\nContent-Length: 2\r\n\r\n{}"
+        );
+
+        let mut stream = mock_stream(b"HTTP/1.1 204 \r\nServer: Firecracker API\r\n\r\n");
+        let response = send_request(&mut stream, "GET", "/", None).unwrap();
+        assert_eq!(response.status, 204);
+        assert!(response.body.is_empty());
+        assert_eq!(
+            String::from_utf8(stream.output).unwrap(),
+            "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_invalid_response() {
+        let responses: [&[u8]; 4] = [
+            b"",
+            b"HTTP/1.1 OK\r\n\r\n",
+            b"HTTP/1.1 200 \r\nContent-Length: foo\r\n\r\n",
+            b"HTTP/1.1 200 \r\nContent-Length: 10\r\n\r\n{}",
+        ];
+        for response in responses.iter() {
+            assert!(send_request(&mut mock_stream(response), "GET", "/", None).is_err());
+        }
+        let long_headers = vec![b'a'; MAX_HEADERS_LEN + 10];
+        match send_request(&mut mock_stream(&long_headers), "GET", "/", None) {
+            Err(Error::InvalidResponse(_)) => (),
+            _ => unreachable!(),
+        }
+    }
+}
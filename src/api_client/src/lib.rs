@@ -0,0 +1,539 @@
+// Copyright 2021 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+#![deny(missing_docs)]
+//! A typed client for the Firecracker API, served over a Unix socket.
+//!
+//! The requests and the responses are the `vmm_config` types which the API server parses the
+//! requests into, so that they can't drift from what Firecracker accepts.
+//!
+//! ```no_run
+//! use api_client::Client;
+//! use vmm::vmm_config::boot_source::BootSourceConfig;
+//!
+//! let mut client = Client::connect("/tmp/firecracker.socket").unwrap();
+//! client
+//!     .put_boot_source(&BootSourceConfig {
+//!         kernel_image_path: "./vmlinux.bin".to_string(),
+//!         ..Default::default()
+//!     })
+//!     .unwrap();
+//! client.start_instance().unwrap();
+//! ```
+
+mod http;
+
+use std::fmt;
+use std::io;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use vmm::resources::VmmConfig;
+use vmm::vmm_config::balloon::{
+    BalloonDeviceConfig, BalloonStats, BalloonUpdateConfig, BalloonUpdateStatsConfig,
+};
+use vmm::vmm_config::boot_source::BootSourceConfig;
+use vmm::vmm_config::drive::{BlockDeviceConfig, BlockDeviceUpdateConfig};
+use vmm::vmm_config::instance_info::InstanceInfo;
+use vmm::vmm_config::logger::LoggerConfig;
+use vmm::vmm_config::machine_config::VmConfig;
+use vmm::vmm_config::metrics::MetricsConfig;
+use vmm::vmm_config::mmds::MmdsConfig;
+use vmm::vmm_config::net::{
+    NetworkInterfaceConfig, NetworkInterfaceStats, NetworkInterfaceUpdateConfig,
+};
+use vmm::vmm_config::snapshot::{CreateSnapshotParams, LoadSnapshotParams, Vm, VmState};
+use vmm::vmm_config::vsock::VsockDeviceConfig;
+
+/// Errors returned by the API client.
+#[derive(Debug)]
+pub enum Error {
+    /// Connecting to, or communicating over, the API socket failed.
+    Io(io::Error),
+    /// The API server answered with something other than a valid HTTP response.
+    InvalidResponse(String),
+    /// A request body couldn't be serialized, or a response body deserialized.
+    Json(serde_json::Error),
+    /// The API server rejected the request.
+    Fault {
+        /// The HTTP status code of the response.
+        status: u16,
+        /// The fault message of the response.
+        message: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::Error::*;
+        match self {
+            Io(err) => write!(f, "Cannot communicate with the API server: {}", err),
+            InvalidResponse(msg) => write!(f, "Invalid response from the API server: {}", msg),
+            Json(err) => write!(f, "Invalid JSON body: {}", err),
+            Fault { status, message } => {
+                write!(f, "The API server answered {}: {}", status, message)
+            }
+        }
+    }
+}
+
+/// Shorthand result type for the API client.
+pub type Result<T> = std::result::Result<T, Error>;
+
+// The body of the error responses.
+#[derive(Deserialize)]
+struct Fault {
+    fault_message: String,
+}
+
+// The names of the members from this enum must precisely correspond to the values of
+// "action_type" accepted by `PUT /actions`.
+#[derive(Serialize)]
+enum ActionType {
+    FlushMetrics,
+    InstanceStart,
+    #[cfg(target_arch = "x86_64")]
+    SendCtrlAltDel,
+    #[cfg(target_arch = "x86_64")]
+    Shutdown,
+}
+
+#[derive(Serialize)]
+struct ActionBody {
+    action_type: ActionType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timeout_ms: Option<u64>,
+}
+
+/// A client of the Firecracker API, holding a connection to the API socket.
+pub struct Client {
+    stream: UnixStream,
+}
+
+impl Client {
+    /// Connects to the API socket at `path`.
+    pub fn connect<P: AsRef<Path>>(path: P) -> Result<Client> {
+        UnixStream::connect(path)
+            .map(Client::new)
+            .map_err(Error::Io)
+    }
+
+    /// Uses `stream`, connected to the API socket, for sending the requests.
+    pub fn new(stream: UnixStream) -> Client {
+        Client { stream }
+    }
+
+    /// Returns general information about the microVM.
+    pub fn get_instance_info(&mut self) -> Result<InstanceInfo> {
+        self.get("/")
+    }
+
+    /// Boots the microVM.
+    pub fn start_instance(&mut self) -> Result<()> {
+        self.action(ActionType::InstanceStart)
+    }
+
+    /// Flushes the metrics.
+    pub fn flush_metrics(&mut self) -> Result<()> {
+        self.action(ActionType::FlushMetrics)
+    }
+
+    /// Sends CTRL+ALT+DEL to the guest.
+    #[cfg(target_arch = "x86_64")]
+    pub fn send_ctrl_alt_del(&mut self) -> Result<()> {
+        self.action(ActionType::SendCtrlAltDel)
+    }
+
+    /// Asks the guest to power off, and has the microVM stopped once it did, or once
+    /// `timeout_ms` milliseconds passed. Without a timeout, the server default is used.
+    #[cfg(target_arch = "x86_64")]
+    pub fn shutdown(&mut self, timeout_ms: Option<u64>) -> Result<()> {
+        self.put(
+            "/actions",
+            &ActionBody {
+                action_type: ActionType::Shutdown,
+                timeout_ms,
+            },
+        )
+    }
+
+    /// Returns the balloon device configuration.
+    pub fn get_balloon(&mut self) -> Result<BalloonDeviceConfig> {
+        self.get("/balloon")
+    }
+
+    /// Creates or updates the balloon device. Pre-boot only.
+    pub fn put_balloon(&mut self, config: &BalloonDeviceConfig) -> Result<()> {
+        self.put("/balloon", config)
+    }
+
+    /// Updates the target size of the balloon. Post-boot only.
+    pub fn patch_balloon(&mut self, config: &BalloonUpdateConfig) -> Result<()> {
+        self.patch("/balloon", config)
+    }
+
+    /// Returns the latest balloon device statistics.
+    pub fn get_balloon_stats(&mut self) -> Result<BalloonStats> {
+        self.get("/balloon/statistics")
+    }
+
+    /// Updates the balloon statistics polling interval. Post-boot only.
+    pub fn patch_balloon_stats(&mut self, config: &BalloonUpdateStatsConfig) -> Result<()> {
+        self.patch("/balloon/statistics", config)
+    }
+
+    /// Returns the boot source configuration.
+    pub fn get_boot_source(&mut self) -> Result<BootSourceConfig> {
+        self.get("/boot-source")
+    }
+
+    /// Configures the boot source. Pre-boot only.
+    pub fn put_boot_source(&mut self, config: &BootSourceConfig) -> Result<()> {
+        self.put("/boot-source", config)
+    }
+
+    /// Returns the configuration of all the block devices.
+    pub fn get_drives(&mut self) -> Result<Vec<BlockDeviceConfig>> {
+        self.get("/drives")
+    }
+
+    /// Returns the configuration of the block device `drive_id`.
+    pub fn get_drive(&mut self, drive_id: &str) -> Result<BlockDeviceConfig> {
+        self.get(&format!("/drives/{}", drive_id))
+    }
+
+    /// Creates or updates a block device. Pre-boot only.
+    pub fn put_drive(&mut self, config: &BlockDeviceConfig) -> Result<()> {
+        self.put(&format!("/drives/{}", config.drive_id), config)
+    }
+
+    /// Updates the backing file or the rate limiter of a block device. Post-boot only.
+    pub fn patch_drive(&mut self, config: &BlockDeviceUpdateConfig) -> Result<()> {
+        self.patch(&format!("/drives/{}", config.drive_id), config)
+    }
+
+    /// Returns the logger configuration.
+    pub fn get_logger(&mut self) -> Result<LoggerConfig> {
+        self.get("/logger")
+    }
+
+    /// Initializes the logger. Pre-boot only.
+    pub fn put_logger(&mut self, config: &LoggerConfig) -> Result<()> {
+        self.put("/logger", config)
+    }
+
+    /// Returns the vCPU and memory configuration.
+    pub fn get_machine_config(&mut self) -> Result<VmConfig> {
+        let mut value: Value = self.get("/machine-config")?;
+        // A missing CPU template is reported as `Uninitialized`.
+        if value["cpu_template"] == "Uninitialized" {
+            if let Some(fields) = value.as_object_mut() {
+                fields.remove("cpu_template");
+            }
+        }
+        serde_json::from_value(value).map_err(Error::Json)
+    }
+
+    /// Sets the vCPU and memory configuration. Pre-boot only.
+    pub fn put_machine_config(&mut self, config: &VmConfig) -> Result<()> {
+        self.put("/machine-config", config)
+    }
+
+    /// Updates the given fields of the vCPU and memory configuration. Pre-boot only.
+    pub fn patch_machine_config(&mut self, config: &VmConfig) -> Result<()> {
+        self.patch("/machine-config", config)
+    }
+
+    /// Returns the metrics system configuration.
+    pub fn get_metrics(&mut self) -> Result<MetricsConfig> {
+        self.get("/metrics")
+    }
+
+    /// Initializes the metrics system. Pre-boot only.
+    pub fn put_metrics(&mut self, config: &MetricsConfig) -> Result<()> {
+        self.put("/metrics", config)
+    }
+
+    /// Returns the contents of the MMDS data store dedicated to `iface_id`, or of the shared
+    /// one.
+    pub fn get_mmds(&mut self, iface_id: Option<&str>) -> Result<Value> {
+        self.get(&mmds_path(iface_id))
+    }
+
+    /// Replaces the contents of the MMDS data store selected as in `get_mmds`.
+    pub fn put_mmds(&mut self, iface_id: Option<&str>, data: &Value) -> Result<()> {
+        self.put(&mmds_path(iface_id), data)
+    }
+
+    /// Merges `data` into the MMDS data store selected as in `get_mmds`.
+    pub fn patch_mmds(&mut self, iface_id: Option<&str>, data: &Value) -> Result<()> {
+        self.patch(&mmds_path(iface_id), data)
+    }
+
+    /// Configures the MMDS. Pre-boot only.
+    pub fn put_mmds_config(&mut self, config: &MmdsConfig) -> Result<()> {
+        self.put("/mmds/config", config)
+    }
+
+    /// Returns the configuration of all the network interfaces.
+    pub fn get_network_interfaces(&mut self) -> Result<Vec<NetworkInterfaceConfig>> {
+        self.get("/network-interfaces")
+    }
+
+    /// Returns the configuration of the network interface `iface_id`.
+    pub fn get_network_interface(&mut self, iface_id: &str) -> Result<NetworkInterfaceConfig> {
+        self.get(&format!("/network-interfaces/{}", iface_id))
+    }
+
+    /// Returns the traffic statistics of the network interface `iface_id`.
+    pub fn get_network_interface_stats(&mut self, iface_id: &str) -> Result<NetworkInterfaceStats> {
+        self.get(&format!("/network-interfaces/{}/stats", iface_id))
+    }
+
+    /// Creates a network interface. After boot, the interface is hot-plugged.
+    pub fn put_network_interface(&mut self, config: &NetworkInterfaceConfig) -> Result<()> {
+        self.put(&format!("/network-interfaces/{}", config.iface_id), config)
+    }
+
+    /// Updates the TAP device, the link state or the rate limiters of a network interface.
+    pub fn patch_network_interface(&mut self, config: &NetworkInterfaceUpdateConfig) -> Result<()> {
+        self.patch(&format!("/network-interfaces/{}", config.iface_id), config)
+    }
+
+    /// Removes the network interface `iface_id`. After boot, the interface is hot-unplugged.
+    pub fn delete_network_interface(&mut self, iface_id: &str) -> Result<()> {
+        self.delete(&format!("/network-interfaces/{}", iface_id))
+    }
+
+    /// Creates a snapshot of the paused microVM.
+    pub fn create_snapshot(&mut self, params: &CreateSnapshotParams) -> Result<()> {
+        self.put("/snapshot/create", params)
+    }
+
+    /// Loads a snapshot. Pre-boot only.
+    pub fn load_snapshot(&mut self, params: &LoadSnapshotParams) -> Result<()> {
+        self.put("/snapshot/load", params)
+    }
+
+    /// Pauses the microVM.
+    pub fn pause(&mut self) -> Result<()> {
+        self.patch(
+            "/vm",
+            &Vm {
+                state: VmState::Paused,
+            },
+        )
+    }
+
+    /// Resumes the microVM.
+    pub fn resume(&mut self) -> Result<()> {
+        self.patch(
+            "/vm",
+            &Vm {
+                state: VmState::Resumed,
+            },
+        )
+    }
+
+    /// Returns the full microVM configuration.
+    pub fn get_vm_config(&mut self) -> Result<VmmConfig> {
+        self.get("/vm/config")
+    }
+
+    /// Replaces the full microVM configuration, and boots the microVM afterwards if `boot` is
+    /// set. Pre-boot only.
+    pub fn put_vm_config(&mut self, config: &VmmConfig, boot: bool) -> Result<()> {
+        let path = if boot {
+            "/vm/config?boot=true"
+        } else {
+            "/vm/config"
+        };
+        self.put(path, config)
+    }
+
+    /// Returns the configuration of all the vsock devices.
+    pub fn get_vsock_devices(&mut self) -> Result<Vec<VsockDeviceConfig>> {
+        self.get("/vsock")
+    }
+
+    /// Returns the configuration of the vsock device `vsock_id`.
+    pub fn get_vsock_device(&mut self, vsock_id: &str) -> Result<VsockDeviceConfig> {
+        self.get(&format!("/vsock/{}", vsock_id))
+    }
+
+    /// Creates or updates a vsock device. Pre-boot only.
+    pub fn put_vsock_device(&mut self, config: &VsockDeviceConfig) -> Result<()> {
+        self.put("/vsock", config)
+    }
+
+    fn action(&mut self, action_type: ActionType) -> Result<()> {
+        self.put(
+            "/actions",
+            &ActionBody {
+                action_type,
+                timeout_ms: None,
+            },
+        )
+    }
+
+    fn get<T: DeserializeOwned>(&mut self, path: &str) -> Result<T> {
+        let body = self.request("GET", path, None)?;
+        serde_json::from_slice(&body).map_err(Error::Json)
+    }
+
+    fn put<T: Serialize>(&mut self, path: &str, body: &T) -> Result<()> {
+        let body = serde_json::to_vec(body).map_err(Error::Json)?;
+        self.request("PUT", path, Some(&body)).map(|_| ())
+    }
+
+    fn patch<T: Serialize>(&mut self, path: &str, body: &T) -> Result<()> {
+        let body = serde_json::to_vec(body).map_err(Error::Json)?;
+        self.request("PATCH", path, Some(&body)).map(|_| ())
+    }
+
+    fn delete(&mut self, path: &str) -> Result<()> {
+        self.request("DELETE", path, None).map(|_| ())
+    }
+
+    // Sends a request, and returns the body of the response if it is successful.
+    fn request(&mut self, method: &str, path: &str, body: Option<&[u8]>) -> Result<Vec<u8>> {
+        let response = http::send_request(&mut self.stream, method, path, body)?;
+        if (200..300).contains(&response.status) {
+            return Ok(response.body);
+        }
+        let message = serde_json::from_slice::<Fault>(&response.body)
+            .map(|fault| fault.fault_message)
+            .unwrap_or_else(|_| String::from_utf8_lossy(&response.body).into_owned());
+        Err(Error::Fault {
+            status: response.status,
+            message,
+        })
+    }
+}
+
+fn mmds_path(iface_id: Option<&str>) -> String {
+    match iface_id {
+        Some(iface_id) => format!("/mmds/interfaces/{}", iface_id),
+        None => "/mmds".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::thread;
+
+    // Runs `client_fn` against a server which records the request and answers `response`.
+    fn check_request<F>(response: &str, client_fn: F) -> String
+    where
+        F: FnOnce(&mut Client),
+    {
+        let response = response.to_string();
+        let (client_stream, mut server_stream) = UnixStream::pair().unwrap();
+        let server = thread::spawn(move || {
+            let mut request = vec![0u8; 4096];
+            let len = server_stream.read(&mut request).unwrap();
+            server_stream.write_all(response.as_bytes()).unwrap();
+            String::from_utf8(request[..len].to_vec()).unwrap()
+        });
+        client_fn(&mut Client::new(client_stream));
+        server.join().unwrap()
+    }
+
+    #[test]
+    fn test_put_drive() {
+        let config = BlockDeviceConfig {
+            drive_id: "rootfs".to_string(),
+            path_on_host: "/foo/bar".to_string(),
+            is_root_device: true,
+            partuuid: None,
+            cache_type: Default::default(),
+            is_read_only: false,
+            rate_limiter: None,
+        };
+        let request = check_request("HTTP/1.1 204 \r\n\r\n", |client| {
+            client.put_drive(&config).unwrap();
+        });
+        let mut parts = request.splitn(2, "\r\n\r\n");
+        assert!(parts
+            .next()
+            .unwrap()
+            .starts_with("PUT /drives/rootfs HTTP/1.1\r\n"));
+        assert_eq!(
+            serde_json::from_str::<BlockDeviceConfig>(parts.next().unwrap()).unwrap(),
+            config
+        );
+    }
+
+    #[test]
+    fn test_get_machine_config() {
+        let body = "{ \"vcpu_count\": 2, \"mem_size_mib\": 256, \"ht_enabled\": false, \
+                    \"cpu_template\": \"Uninitialized\", \"track_dirty_pages\": false }";
+        let response = format!(
+            "HTTP/1.1 200 \r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let request = check_request(&response, |client| {
+            let vm_config = client.get_machine_config().unwrap();
+            assert_eq!(vm_config.vcpu_count, Some(2));
+            assert_eq!(vm_config.mem_size_mib, Some(256));
+            assert!(vm_config.cpu_template.is_none());
+        });
+        assert_eq!(
+            request,
+            "GET /machine-config HTTP/1.1\r\nHost: localhost\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_fault() {
+        let body = "{\"fault_message\": \"The requested operation is not supported.\"}";
+        let response = format!(
+            "HTTP/1.1 400 \r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let request = check_request(&response, |client| match client.pause() {
+            Err(Error::Fault { status, message }) => {
+                assert_eq!(status, 400);
+                assert_eq!(message, "The requested operation is not supported.");
+            }
+            _ => unreachable!(),
+        });
+        assert!(request.starts_with("PATCH /vm HTTP/1.1\r\n"));
+        assert!(request.ends_with("{\"state\":\"Paused\"}"));
+    }
+
+    #[test]
+    fn test_actions() {
+        let request = check_request("HTTP/1.1 204 \r\n\r\n", |client| {
+            client.flush_metrics().unwrap();
+        });
+        assert!(request.ends_with("{\"action_type\":\"FlushMetrics\"}"));
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            let request = check_request("HTTP/1.1 204 \r\n\r\n", |client| {
+                client.shutdown(Some(500)).unwrap();
+            });
+            assert!(request.starts_with("PUT /actions HTTP/1.1\r\n"));
+            assert!(request.ends_with("{\"action_type\":\"Shutdown\",\"timeout_ms\":500}"));
+        }
+    }
+
+    #[test]
+    fn test_put_vm_config() {
+        let request = check_request("HTTP/1.1 204 \r\n\r\n", |client| {
+            client.put_vm_config(&VmmConfig::default(), true).unwrap();
+        });
+        assert!(request.starts_with("PUT /vm/config?boot=true HTTP/1.1\r\n"));
+        assert!(request.ends_with(
+            "{\"drives\":[],\"mmds-configs\":[],\"network-interfaces\":[],\"vsock-devices\":[]}"
+        ));
+    }
+}
@@ -0,0 +1,112 @@
+// Copyright 2021 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Restricts which clients may use the API, and what they may do with it.
+
+use std::fs;
+use std::os::unix::fs::FileTypeExt;
+use std::path::PathBuf;
+
+use micro_http::PeerCredentials;
+
+/// The access control settings of the API server.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AccessControl {
+    /// The user IDs of the clients allowed to use the API.
+    pub allowed_uids: Vec<u32>,
+    /// The group IDs of the clients allowed to use the API.
+    pub allowed_gids: Vec<u32>,
+    /// The path of an additional socket, on which only `GET` requests are served.
+    pub read_only_socket: Option<PathBuf>,
+}
+
+impl AccessControl {
+    /// Returns `true` if the client with the given credentials may use the API: either its
+    /// user or its group has to be allowed. Without allow-lists, all clients are allowed.
+    pub fn allows(&self, peer_credentials: Option<PeerCredentials>) -> bool {
+        if self.allowed_uids.is_empty() && self.allowed_gids.is_empty() {
+            return true;
+        }
+        // Clients whose credentials are unknown can't be checked.
+        peer_credentials.map_or(false, |peer| {
+            self.allowed_uids.contains(&peer.uid) || self.allowed_gids.contains(&peer.gid)
+        })
+    }
+
+    /// Removes the read-only socket left behind by a previous run, if any. Other files are
+    /// left in place, in which case binding the socket fails.
+    pub fn remove_stale_read_only_socket(&self) {
+        if let Some(path) = self.read_only_socket.as_ref() {
+            let is_socket = fs::symlink_metadata(path)
+                .map(|metadata| metadata.file_type().is_socket())
+                .unwrap_or(false);
+            if is_socket {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+
+    use utils::tempfile::TempFile;
+
+    #[test]
+    fn test_allows() {
+        let peer = PeerCredentials {
+            pid: 1,
+            uid: 1000,
+            gid: 100,
+        };
+
+        let mut access_control = AccessControl::default();
+        assert!(access_control.allows(Some(peer)));
+        assert!(access_control.allows(None));
+
+        access_control.allowed_uids = vec![0, 1000];
+        assert!(access_control.allows(Some(peer)));
+        assert!(!access_control.allows(Some(PeerCredentials { uid: 1001, ..peer })));
+        assert!(!access_control.allows(None));
+
+        access_control.allowed_gids = vec![100];
+        assert!(access_control.allows(Some(PeerCredentials { uid: 1001, ..peer })));
+        assert!(!access_control.allows(Some(PeerCredentials {
+            uid: 1001,
+            gid: 101,
+            ..peer
+        })));
+
+        access_control.allowed_uids.clear();
+        assert!(!access_control.allows(Some(PeerCredentials { gid: 101, ..peer })));
+    }
+
+    #[test]
+    fn test_remove_stale_read_only_socket() {
+        let mut tmp_socket = TempFile::new().unwrap();
+        tmp_socket.remove().unwrap();
+        let path = tmp_socket.as_path().to_path_buf();
+        let access_control = AccessControl {
+            read_only_socket: Some(path.clone()),
+            ..Default::default()
+        };
+
+        // Nothing to remove.
+        access_control.remove_stale_read_only_socket();
+
+        drop(UnixListener::bind(&path).unwrap());
+        access_control.remove_stale_read_only_socket();
+        assert!(!path.exists());
+
+        // Other files are kept.
+        let tmp_file = TempFile::new().unwrap();
+        let access_control = AccessControl {
+            read_only_socket: Some(tmp_file.as_path().to_path_buf()),
+            ..Default::default()
+        };
+        access_control.remove_stale_read_only_socket();
+        assert!(tmp_file.as_path().exists());
+    }
+}
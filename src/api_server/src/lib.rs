@@ -6,25 +6,31 @@
 //! and responding to the user.
 //! It is constructed on top of an HTTP Server that uses Unix Domain Sockets and `EPOLL` to
 //! handle multiple connections on the same thread.
+mod access;
+mod operation;
 mod parsed_request;
 mod request;
 
 use serde_json::json;
+use std::collections::VecDeque;
+use std::os::unix::io::AsRawFd;
 use std::path::PathBuf;
-use std::sync::{mpsc, Arc, Mutex};
+use std::sync::mpsc::{self, TryRecvError};
 use std::{fmt, io};
 
+pub use crate::access::AccessControl;
+use crate::operation::Operations;
 use crate::parsed_request::ParsedRequest;
 use logger::{
-    debug, error, info, update_metric_with_elapsed_time, IncMetric, StoreMetric, METRICS,
+    debug, error, info, update_metric_with_elapsed_time, warn, IncMetric, StoreMetric, EVENTS,
+    MAX_EVENT_SUBSCRIBERS, METRICS,
 };
 pub use micro_http::{
-    Body, HttpServer, Method, Request, RequestError, Response, ServerError, ServerRequest,
-    ServerResponse, StatusCode, Version,
+    Body, HttpServer, MediaType, Method, PeerCredentials, Request, RequestError, Response,
+    ServerError, ServerRequest, ServerResponse, StatusCode, Version,
 };
-use mmds::data_store;
-use mmds::data_store::Mmds;
 use seccomp::{BpfProgram, SeccompFilter};
+use utils::epoll::{ControlOperation, Epoll, EpollEvent, EventSet};
 use utils::eventfd::EventFd;
 use vmm::rpc_interface::{VmmAction, VmmActionError, VmmData};
 use vmm::vmm_config::instance_info::InstanceInfo;
@@ -65,10 +71,34 @@ impl fmt::Debug for Error {
 
 type Result<T> = std::result::Result<T, Error>;
 
+/// The preference (RFC 7240) asking for a VMM action to be run asynchronously.
+const RESPOND_ASYNC: &str = "respond-async";
+/// Maximum number of requests waiting for the running operation, across all the connections.
+const MAX_WAITING_REQUESTS: usize = 64;
+
+// Returns `true` if `vmm_action` may be run asynchronously. Only the snapshot operations take
+// long enough to need it.
+fn supports_async(vmm_action: &VmmAction) -> bool {
+    matches!(
+        vmm_action,
+        VmmAction::CreateSnapshot(_) | VmmAction::LoadSnapshot(_)
+    )
+}
+
+// Returns `true` if the request carries the `Prefer: respond-async` header.
+fn prefers_async(request: &Request) -> bool {
+    request
+        .headers
+        .custom_entry("Prefer")
+        .map_or(false, |prefer| {
+            prefer
+                .split(',')
+                .any(|preference| preference.trim().eq_ignore_ascii_case(RESPOND_ASYNC))
+        })
+}
+
 /// Structure associated with the API server implementation.
 pub struct ApiServer {
-    /// MMDS info directly accessible from the API thread.
-    mmds_info: Arc<Mutex<Mmds>>,
     /// Firecracker instance info exposed through API.
     instance_info: InstanceInfo,
     /// Sender which allows passing messages to the VMM.
@@ -78,9 +108,19 @@ pub struct ApiServer {
     /// FD on which we notify the VMM that we have sent at least one
     /// `VmmRequest`.
     to_vmm_fd: EventFd,
+    /// FD on which the VMM notifies us that it has sent at least one
+    /// `ApiResponse`.
+    from_vmm_fd: EventFd,
     /// If this flag is set, the process encountered a fatal error
     /// and it is going to exit once it sends any pending API response.
     vmm_fatal_error: bool,
+    /// The VMM actions requested asynchronously.
+    operations: Operations,
+    /// The requests answered once the running operation is over, along with the time
+    /// they were received at.
+    waiting_requests: VecDeque<(ServerRequest, u64)>,
+    /// Which clients may use the API, and through which sockets.
+    access_control: AccessControl,
 }
 
 impl ApiServer {
@@ -88,22 +128,31 @@ impl ApiServer {
     ///
     /// Returns the newly formed `ApiServer`.
     pub fn new(
-        mmds_info: Arc<Mutex<Mmds>>,
         instance_info: InstanceInfo,
         api_request_sender: mpsc::Sender<ApiRequest>,
         vmm_response_receiver: mpsc::Receiver<ApiResponse>,
         to_vmm_fd: EventFd,
+        from_vmm_fd: EventFd,
     ) -> Self {
         ApiServer {
-            mmds_info,
             instance_info,
             api_request_sender,
             vmm_response_receiver,
             to_vmm_fd,
+            from_vmm_fd,
             vmm_fatal_error: false,
+            operations: Operations::default(),
+            waiting_requests: VecDeque::new(),
+            access_control: AccessControl::default(),
         }
     }
 
+    /// Restricts the clients of the API server, and optionally adds a read-only socket.
+    /// Takes effect when the server is started by `bind_and_run`.
+    pub fn set_access_control(&mut self, access_control: AccessControl) {
+        self.access_control = access_control;
+    }
+
     /// Starts the HTTP Server by binding to the socket path provided as
     /// an argument.
     ///
@@ -118,7 +167,6 @@ impl ApiServer {
     ///
     /// ```
     /// use api_server::ApiServer;
-    /// use mmds::MMDS;
     /// use seccomp::SeccompFilter;
     /// use std::{
     ///     convert::TryInto, io::Read, io::Write, os::unix::net::UnixStream, path::PathBuf,
@@ -139,19 +187,19 @@ impl ApiServer {
     /// };
     ///
     /// let to_vmm_fd = EventFd::new(libc::EFD_NONBLOCK).unwrap();
+    /// let from_vmm_fd = EventFd::new(libc::EFD_NONBLOCK).unwrap();
     /// let (api_request_sender, _from_api) = channel();
     /// let (to_api, vmm_response_receiver) = channel();
-    /// let mmds_info = MMDS.clone();
     ///
     /// thread::Builder::new()
     ///     .name("fc_api_test".to_owned())
     ///     .spawn(move || {
     ///         ApiServer::new(
-    ///             mmds_info,
     ///             instance_info,
     ///             api_request_sender,
     ///             vmm_response_receiver,
     ///             to_vmm_fd,
+    ///             from_vmm_fd,
     ///         )
     ///         .bind_and_run(
     ///             PathBuf::from(api_thread_path_to_socket),
@@ -177,10 +225,30 @@ impl ApiServer {
         start_time_cpu_us: Option<u64>,
         seccomp_filter: BpfProgram,
     ) -> Result<()> {
+        // A read-only socket left behind by a previous run would prevent binding it again.
+        self.access_control.remove_stale_read_only_socket();
         let mut server = HttpServer::new(path).unwrap_or_else(|e| {
             error!("Error creating the HTTP server: {}", e);
             std::process::exit(i32::from(vmm::FC_EXIT_CODE_GENERIC_ERROR));
         });
+        if let Some(read_only_path) = self.access_control.read_only_socket.as_ref() {
+            server
+                .add_read_only_socket(read_only_path)
+                .unwrap_or_else(|e| {
+                    error!("Error creating the read-only HTTP server socket: {}", e);
+                    std::process::exit(i32::from(vmm::FC_EXIT_CODE_GENERIC_ERROR));
+                });
+        }
+        // The clients which are not allowed to use the API are disconnected as soon as they
+        // connect, so they can't take up the connection slots of the server.
+        let access_control = self.access_control.clone();
+        server.set_peer_filter(move |peer_credentials| {
+            let allowed = access_control.allows(peer_credentials);
+            if !allowed {
+                warn!("{}", ApiServer::access_denied_message(peer_credentials));
+            }
+            allowed
+        });
 
         if let Some(start_time) = start_time_us {
             let delta_us = utils::time::get_time_us(utils::time::ClockType::Monotonic) - start_time;
@@ -199,6 +267,19 @@ impl ApiServer {
                 .store(delta_us as usize);
         }
 
+        // Besides the clients, wait for the VMM, which may finish the running operation, and
+        // for the event stream subscribers to hang up.
+        let epoll = Epoll::new().map_err(Error::Io)?;
+        for fd in &[server.epoll().as_raw_fd(), self.from_vmm_fd.as_raw_fd()] {
+            epoll
+                .ctl(
+                    ControlOperation::Add,
+                    *fd,
+                    EpollEvent::new(EventSet::IN, *fd as u64),
+                )
+                .map_err(Error::Io)?;
+        }
+
         // Load seccomp filters on the API thread.
         // Execution panics if filters cannot be loaded, use --seccomp-level=0 if skipping filters
         // altogether is the desired behaviour.
@@ -210,47 +291,166 @@ impl ApiServer {
         }
 
         server.start_server().expect("Cannot start HTTP server");
+        let mut events = vec![EpollEvent::default(); 2 + MAX_EVENT_SUBSCRIBERS];
         loop {
-            match server.requests() {
-                Ok(request_vec) => {
-                    for server_request in request_vec {
-                        let request_processing_start_us =
-                            utils::time::get_time_us(utils::time::ClockType::Monotonic);
-                        server
-                            .respond(
-                                // Use `self.handle_request()` as the processing callback.
-                                server_request.process(|request| {
-                                    self.handle_request(request, request_processing_start_us)
-                                }),
-                            )
-                            .or_else(|e| {
-                                error!("API Server encountered an error on response: {}", e);
-                                Ok(())
-                            })?;
-                        let delta_us = utils::time::get_time_us(utils::time::ClockType::Monotonic)
-                            - request_processing_start_us;
-                        debug!("Total previous API call duration: {} us.", delta_us);
-                        if self.vmm_fatal_error {
-                            // Flush the remaining outgoing responses
-                            // and proceed to exit
-                            server.flush_outgoing_writes();
-                            error!(
-                                "Fatal error with exit code: {}",
-                                FC_EXIT_CODE_BAD_CONFIGURATION
-                            );
-                            unsafe {
-                                libc::_exit(i32::from(FC_EXIT_CODE_BAD_CONFIGURATION));
-                            }
+            let event_count = match epoll.wait(events.len(), -1, &mut events[..]) {
+                Ok(event_count) => event_count,
+                Err(e) if e.raw_os_error() == Some(libc::EINTR) => 0,
+                Err(e) => {
+                    error!("API Server error on waiting for events. Error: {}", e);
+                    continue;
+                }
+            };
+            for event in events.iter().take(event_count) {
+                if event.fd() == self.from_vmm_fd.as_raw_fd() {
+                    let _ = self.from_vmm_fd.read();
+                    self.poll_running_operation();
+                    continue;
+                }
+                if event.fd() != server.epoll().as_raw_fd() {
+                    ApiServer::unsubscribe_from_events(&epoll, event.fd());
+                    continue;
+                }
+                match server.requests() {
+                    Ok(request_vec) => {
+                        for server_request in request_vec {
+                            self.handle_server_request(&mut server, &epoll, server_request);
+                            self.exit_on_fatal_error(&mut server);
                         }
                     }
-                }
-                Err(e) => {
-                    error!(
-                        "API Server error on retrieving incoming request. Error: {}",
-                        e
-                    );
+                    Err(e) => {
+                        error!(
+                            "API Server error on retrieving incoming request. Error: {}",
+                            e
+                        );
+                    }
                 }
             }
+            self.answer_waiting_requests(&mut server, &epoll);
+            self.exit_on_fatal_error(&mut server);
+        }
+    }
+
+    // Answers `server_request`, unless it has to wait for the running operation.
+    fn handle_server_request(
+        &mut self,
+        server: &mut HttpServer,
+        epoll: &Epoll,
+        server_request: ServerRequest,
+    ) {
+        let request_processing_start_us =
+            utils::time::get_time_us(utils::time::ClockType::Monotonic);
+        // The responses are sent in order, so the requests following a waiting one on the
+        // same connection wait as well.
+        if (self.operations.running().is_some()
+            && ParsedRequest::reads_vmm_state(server_request.inner()))
+            || self
+                .waiting_requests
+                .iter()
+                .any(|(waiting_request, _)| waiting_request.id() == server_request.id())
+        {
+            if self.waiting_requests.len() < MAX_WAITING_REQUESTS {
+                self.waiting_requests
+                    .push_back((server_request, request_processing_start_us));
+                return;
+            }
+
+            // The requests waiting on the same connection are rejected as well, so that the
+            // responses are still sent in order.
+            let id = server_request.id();
+            let (rejected_requests, waiting_requests) = self
+                .waiting_requests
+                .drain(..)
+                .partition::<VecDeque<_>, _>(|(waiting_request, _)| waiting_request.id() == id);
+            self.waiting_requests = waiting_requests;
+            warn!(
+                "Rejecting {} requests, as too many requests wait for the running operation.",
+                rejected_requests.len() + 1
+            );
+            for (rejected_request, start_us) in rejected_requests.into_iter().chain(
+                std::iter::once((server_request, request_processing_start_us)),
+            ) {
+                let server_response =
+                    rejected_request.process(|_| ApiServer::too_many_waiting_requests());
+                ApiServer::respond(server, epoll, server_response, start_us);
+            }
+            return;
+        }
+        let server_response =
+            self.process_server_request(server_request, request_processing_start_us);
+        ApiServer::respond(server, epoll, server_response, request_processing_start_us);
+    }
+
+    // Answers `server_request` with `self.handle_request()`, unless it came on the read-only
+    // socket and is not one of the requests served there.
+    fn process_server_request(
+        &mut self,
+        server_request: ServerRequest,
+        request_processing_start_us: u64,
+    ) -> ServerResponse {
+        if server_request.is_read_only()
+            && !ParsedRequest::allowed_read_only(server_request.inner())
+        {
+            return server_request.process(|request| {
+                let message = format!(
+                    "{} {} is not allowed on the read-only socket.",
+                    String::from_utf8_lossy(request.method().raw()),
+                    request.uri().get_abs_path()
+                );
+                warn!("{}", message);
+                ApiServer::json_response(
+                    StatusCode::Forbidden,
+                    ApiServer::json_fault_message(message),
+                )
+            });
+        }
+        server_request.process(|request| self.handle_request(request, request_processing_start_us))
+    }
+
+    // Answers the requests which waited for the operation, in the order they were received,
+    // until another operation is started.
+    fn answer_waiting_requests(&mut self, server: &mut HttpServer, epoll: &Epoll) {
+        while self.operations.running().is_none() {
+            let (server_request, request_processing_start_us) =
+                match self.waiting_requests.pop_front() {
+                    Some(waiting_request) => waiting_request,
+                    None => break,
+                };
+            let server_response =
+                self.process_server_request(server_request, request_processing_start_us);
+            ApiServer::respond(server, epoll, server_response, request_processing_start_us);
+        }
+    }
+
+    fn respond(
+        server: &mut HttpServer,
+        epoll: &Epoll,
+        server_response: ServerResponse,
+        request_processing_start_us: u64,
+    ) {
+        if server_response.is_streaming() {
+            // The connection is handed over to the event stream.
+            ApiServer::subscribe_to_events(server, epoll, server_response);
+        } else if let Err(e) = server.respond(server_response) {
+            error!("API Server encountered an error on response: {}", e);
+        }
+        let delta_us = utils::time::get_time_us(utils::time::ClockType::Monotonic)
+            - request_processing_start_us;
+        debug!("Total previous API call duration: {} us.", delta_us);
+    }
+
+    fn exit_on_fatal_error(&self, server: &mut HttpServer) {
+        if self.vmm_fatal_error {
+            // Flush the remaining outgoing responses
+            // and proceed to exit
+            server.flush_outgoing_writes();
+            error!(
+                "Fatal error with exit code: {}",
+                FC_EXIT_CODE_BAD_CONFIGURATION
+            );
+            unsafe {
+                libc::_exit(i32::from(FC_EXIT_CODE_BAD_CONFIGURATION));
+            }
         }
     }
 
@@ -260,14 +460,32 @@ impl ApiServer {
         request: &Request,
         request_processing_start_us: u64,
     ) -> Response {
+        // The VMM may have finished the running operation since the last request.
+        self.poll_running_operation();
+
         match ParsedRequest::try_from_request(request) {
             Ok(ParsedRequest::Sync(vmm_action)) => {
-                self.serve_vmm_action_request(vmm_action, request_processing_start_us)
+                if let Some(operation) = self.operations.running() {
+                    // The VMM is busy with the operation, and would only apply the change once
+                    // it's done. The reads of the VMM state wait for it in `bind_and_run`.
+                    return ApiServer::json_response(
+                        StatusCode::Conflict,
+                        ApiServer::json_fault_message(format!(
+                            "Operation {} is still running.",
+                            operation.status().id
+                        )),
+                    );
+                }
+                // The preference is ignored by the other requests, which are served right away.
+                if prefers_async(request) && supports_async(&vmm_action) {
+                    self.start_operation(vmm_action, request)
+                } else {
+                    self.serve_vmm_action_request(vmm_action, request_processing_start_us)
+                }
             }
+            Ok(ParsedRequest::GetEvents) => ApiServer::get_events(),
             Ok(ParsedRequest::GetInstanceInfo) => self.get_instance_info(),
-            Ok(ParsedRequest::GetMMDS) => self.get_mmds(),
-            Ok(ParsedRequest::PatchMMDS(value)) => self.patch_mmds(value),
-            Ok(ParsedRequest::PutMMDS(value)) => self.put_mmds(value),
+            Ok(ParsedRequest::GetOperation(id)) => self.get_operation(id),
             Err(e) => {
                 error!("{}", e);
                 e.into()
@@ -299,16 +517,8 @@ impl ApiServer {
             _ => None,
         };
 
-        let vmm_new_state = match *vmm_action {
-            VmmAction::StartMicroVm => "Running".to_string(),
-            VmmAction::Pause => "Paused".to_string(),
-            VmmAction::Resume => "Running".to_string(),
-            _ => self.instance_info.state.clone(),
-        };
-        self.api_request_sender
-            .send(vmm_action)
-            .expect("Failed to send VMM message");
-        self.to_vmm_fd.write(1).expect("Cannot update send VMM fd");
+        let vmm_new_state = self.vmm_new_state(&vmm_action);
+        self.send_to_vmm(vmm_action);
         let vmm_outcome = *(self.vmm_response_receiver.recv().expect("VMM disconnected"));
         self.check_for_fatal_error(&vmm_outcome);
         let response = ParsedRequest::convert_to_response(&vmm_outcome);
@@ -324,6 +534,94 @@ impl ApiServer {
         response
     }
 
+    /// Sends `vmm_action` to the VMM without waiting for the outcome, and answers with the ID
+    /// of the operation tracking it.
+    fn start_operation(&mut self, vmm_action: Box<VmmAction>, request: &Request) -> Response {
+        let progress = match *vmm_action {
+            VmmAction::CreateSnapshot(ref params) => Some(params.progress.clone()),
+            _ => None,
+        };
+        let vmm_new_state = self.vmm_new_state(&vmm_action);
+        self.send_to_vmm(vmm_action);
+
+        let request_description = format!(
+            "{} {}",
+            String::from_utf8_lossy(request.method().raw()),
+            request.uri().get_abs_path()
+        );
+        let id = self
+            .operations
+            .start(request_description, progress, vmm_new_state);
+        info!("Started asynchronous operation {}.", id);
+
+        let mut response = ApiServer::json_response(
+            StatusCode::Accepted,
+            json!({ "operation_id": id }).to_string(),
+        );
+        response.add_custom_header("Location", &format!("/operations/{}", id));
+        response.add_custom_header("Preference-Applied", RESPOND_ASYNC);
+        response
+    }
+
+    /// Records the outcome of the running operation, if the VMM has sent it.
+    fn poll_running_operation(&mut self) {
+        if self.operations.running().is_none() {
+            return;
+        }
+        let vmm_outcome = match self.vmm_response_receiver.try_recv() {
+            Ok(vmm_outcome) => *vmm_outcome,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => panic!("VMM disconnected"),
+        };
+        self.check_for_fatal_error(&vmm_outcome);
+        if let Some(operation) = self.operations.finish(&vmm_outcome) {
+            if vmm_outcome.is_ok() {
+                self.instance_info.state = operation.vmm_new_state.clone();
+            }
+            info!("Asynchronous operation {} finished.", operation.status().id);
+        }
+    }
+
+    fn get_operation(&self, id: Option<u64>) -> Response {
+        let body = match id {
+            Some(id) => match self.operations.status(id) {
+                Some(status) => serde_json::to_string(&status),
+                None => {
+                    return ApiServer::json_response(
+                        StatusCode::NotFound,
+                        ApiServer::json_fault_message(format!("Unknown operation {}.", id)),
+                    )
+                }
+            },
+            None => serde_json::to_string(&self.operations.statuses()),
+        };
+        match body {
+            Ok(body) => ApiServer::json_response(StatusCode::OK, body),
+            Err(e) => ApiServer::json_response(
+                StatusCode::InternalServerError,
+                ApiServer::json_fault_message(e.to_string()),
+            ),
+        }
+    }
+
+    fn send_to_vmm(&mut self, vmm_action: Box<VmmAction>) {
+        self.api_request_sender
+            .send(vmm_action)
+            .expect("Failed to send VMM message");
+        self.to_vmm_fd.write(1).expect("Cannot update send VMM fd");
+    }
+
+    // Returns the instance state after `vmm_action` succeeds.
+    fn vmm_new_state(&self, vmm_action: &VmmAction) -> String {
+        match *vmm_action {
+            VmmAction::StartMicroVm => "Running".to_string(),
+            VmmAction::SetFullVmConfig(_, true) => "Running".to_string(),
+            VmmAction::Pause => "Paused".to_string(),
+            VmmAction::Resume => "Running".to_string(),
+            _ => self.instance_info.state.clone(),
+        }
+    }
+
     fn check_for_fatal_error(&mut self, response: &std::result::Result<VmmData, VmmActionError>) {
         // Errors considered as fatal are added here
         if let Err(VmmActionError::LoadSnapshot(_)) = response {
@@ -348,51 +646,85 @@ impl ApiServer {
         }
     }
 
-    fn get_mmds(&self) -> Response {
-        ApiServer::json_response(
-            StatusCode::OK,
-            self.mmds_info
-                .lock()
-                .expect("Failed to acquire lock on MMDS info")
-                .get_data_str(),
-        )
-    }
-
-    fn patch_mmds(&self, value: serde_json::Value) -> Response {
-        let mmds_response = self
-            .mmds_info
-            .lock()
-            .expect("Failed to acquire lock on MMDS info")
-            .patch_data(value);
-
-        match mmds_response {
-            Ok(_) => Response::new(Version::Http11, StatusCode::NoContent),
-            Err(e) => match e {
-                data_store::Error::NotFound => unreachable!(),
-                data_store::Error::UnsupportedValueType => unreachable!(),
-                data_store::Error::NotInitialized => ApiServer::json_response(
-                    StatusCode::BadRequest,
-                    ApiServer::json_fault_message(e.to_string()),
-                ),
-            },
-        }
+    // The response to a request which can't wait for the running operation.
+    fn too_many_waiting_requests() -> Response {
+        let mut response = ApiServer::json_response(
+            StatusCode::ServiceUnavailable,
+            ApiServer::json_fault_message(
+                "Too many requests are waiting for the running operation.",
+            ),
+        );
+        response.add_custom_header("Retry-After", "1");
+        response
     }
 
-    fn put_mmds(&self, value: serde_json::Value) -> Response {
-        let mmds_response = self
-            .mmds_info
-            .lock()
-            .expect("Failed to acquire lock on MMDS info")
-            .put_data(value);
-        match mmds_response {
-            Ok(_) => Response::new(Version::Http11, StatusCode::NoContent),
-            Err(e) => ApiServer::json_response(
-                StatusCode::BadRequest,
-                ApiServer::json_fault_message(e.to_string()),
+    // Describes a client which is not allowed to use the API.
+    fn access_denied_message(peer_credentials: Option<PeerCredentials>) -> String {
+        match peer_credentials {
+            Some(peer) => format!(
+                "Access denied to the client with UID {} and GID {}.",
+                peer.uid, peer.gid
             ),
+            None => "Access denied to the client with unknown credentials.".to_string(),
         }
     }
 
+    // Starts the event stream, unless there are too many subscribers already.
+    fn get_events() -> Response {
+        if EVENTS.subscriber_count() >= MAX_EVENT_SUBSCRIBERS {
+            return ApiServer::json_response(
+                StatusCode::ServiceUnavailable,
+                ApiServer::json_fault_message(format!(
+                    "The maximum number of event subscribers ({}) has been reached.",
+                    MAX_EVENT_SUBSCRIBERS
+                )),
+            );
+        }
+        let mut response = Response::new(Version::Http11, StatusCode::OK);
+        response.set_content_type(MediaType::EventStream);
+        response.add_custom_header("Cache-Control", "no-cache");
+        response.set_streaming();
+        response
+    }
+
+    // Sends the headers of the event stream and registers its connection as a subscriber.
+    // The connection is watched for hang ups, so that it doesn't hold a subscriber slot
+    // until the next event.
+    fn subscribe_to_events(
+        server: &mut HttpServer,
+        epoll: &Epoll,
+        server_response: ServerResponse,
+    ) {
+        match server.detach(server_response) {
+            Ok(Some(stream)) => {
+                // The stream is watched before subscribing it, since failing to write an
+                // event closes it.
+                let fd = stream.as_raw_fd();
+                if let Err(e) = epoll.ctl(
+                    ControlOperation::Add,
+                    fd,
+                    EpollEvent::new(EventSet::READ_HANG_UP, fd as u64),
+                ) {
+                    error!("Failed to watch the event stream: {}", e);
+                    return;
+                }
+                if let Err(e) = EVENTS.subscribe(fd as u64, Box::new(stream)) {
+                    error!("Failed to subscribe to the events: {}", e);
+                }
+            }
+            Ok(None) => (),
+            Err(e) => error!("API Server encountered an error on response: {}", e),
+        }
+    }
+
+    // Drops the event stream subscriber on `fd`, which hung up.
+    fn unsubscribe_from_events(epoll: &Epoll, fd: i32) {
+        if let Err(e) = epoll.ctl(ControlOperation::Delete, fd, EpollEvent::default()) {
+            error!("Failed to stop watching the event stream: {}", e);
+        }
+        EVENTS.unsubscribe(fd as u64);
+    }
+
     /// An HTTP response which also includes a body.
     pub(crate) fn json_response<T: Into<String>>(status: StatusCode, body: T) -> Response {
         let mut response = Response::new(Version::Http11, status);
@@ -409,19 +741,19 @@ impl ApiServer {
 mod tests {
     use std::convert::TryInto;
     use std::io::{Read, Write};
-    use std::os::unix::net::UnixStream;
+    use std::os::unix::net::{UnixListener, UnixStream};
     use std::sync::mpsc::channel;
     use std::thread;
     use std::time::Duration;
 
     use super::*;
     use micro_http::HttpConnection;
-    use mmds::MMDS;
     use utils::tempfile::TempFile;
     use utils::time::ClockType;
     use vmm::builder::StartMicrovmError;
     use vmm::rpc_interface::VmmActionError;
     use vmm::vmm_config::instance_info::InstanceInfo;
+    use vmm::vmm_config::mmds::MmdsStoreError;
     use vmm::vmm_config::snapshot::CreateSnapshotParams;
 
     #[test]
@@ -462,16 +794,16 @@ mod tests {
         };
 
         let to_vmm_fd = EventFd::new(libc::EFD_NONBLOCK).unwrap();
+        let from_vmm_fd = EventFd::new(libc::EFD_NONBLOCK).unwrap();
         let (api_request_sender, _from_api) = channel();
         let (to_api, vmm_response_receiver) = channel();
-        let mmds_info = MMDS.clone();
 
         let mut api_server = ApiServer::new(
-            mmds_info,
             instance_info,
             api_request_sender,
             vmm_response_receiver,
             to_vmm_fd,
+            from_vmm_fd,
         );
         to_api
             .send(Box::new(Err(VmmActionError::StartMicrovm(
@@ -499,6 +831,7 @@ mod tests {
                 snapshot_path: PathBuf::new(),
                 mem_file_path: PathBuf::new(),
                 version: None,
+                progress: Default::default(),
             })),
             start_time_us,
         );
@@ -513,6 +846,7 @@ mod tests {
                 snapshot_path: PathBuf::new(),
                 mem_file_path: PathBuf::new(),
                 version: None,
+                progress: Default::default(),
             })),
             start_time_us,
         );
@@ -522,114 +856,53 @@ mod tests {
     }
 
     #[test]
-    fn test_get_instance_info() {
-        let instance_info = InstanceInfo {
-            state: "Not started".to_string(),
-            id: "test_get_instance_info".to_string(),
-            vmm_version: "version 0.1.0".to_string(),
-            app_name: "app name".to_string(),
-        };
-        let to_vmm_fd = EventFd::new(libc::EFD_NONBLOCK).unwrap();
-        let (api_request_sender, _from_api) = channel();
-        let (_to_api, vmm_response_receiver) = channel();
-        let mmds_info = MMDS.clone();
-
-        let api_server = ApiServer::new(
-            mmds_info,
-            instance_info,
-            api_request_sender,
-            vmm_response_receiver,
-            to_vmm_fd,
+    fn test_access_denied_message() {
+        assert_eq!(
+            ApiServer::access_denied_message(Some(PeerCredentials {
+                pid: 1,
+                uid: 1000,
+                gid: 100,
+            })),
+            "Access denied to the client with UID 1000 and GID 100."
         );
-
-        let response = api_server.get_instance_info();
-        assert_eq!(response.status(), StatusCode::OK);
-    }
-
-    #[test]
-    fn test_get_mmds() {
-        let instance_info = InstanceInfo {
-            state: "Not started".to_string(),
-            id: "test_get_mmds".to_string(),
-            vmm_version: "version 0.1.0".to_string(),
-            app_name: "app name".to_string(),
-        };
-
-        let to_vmm_fd = EventFd::new(libc::EFD_NONBLOCK).unwrap();
-        let (api_request_sender, _from_api) = channel();
-        let (_to_api, vmm_response_receiver) = channel();
-        let mmds_info = MMDS.clone();
-
-        let api_server = ApiServer::new(
-            mmds_info,
-            instance_info,
-            api_request_sender,
-            vmm_response_receiver,
-            to_vmm_fd,
+        assert_eq!(
+            ApiServer::access_denied_message(None),
+            "Access denied to the client with unknown credentials."
         );
-
-        let response = api_server.get_mmds();
-        assert_eq!(response.status(), StatusCode::OK);
     }
 
     #[test]
-    fn test_put_mmds() {
-        let instance_info = InstanceInfo {
-            state: "Not started".to_string(),
-            id: "test_put_mmds".to_string(),
-            vmm_version: "version 0.1.0".to_string(),
-            app_name: "app name".to_string(),
-        };
-
-        let to_vmm_fd = EventFd::new(libc::EFD_NONBLOCK).unwrap();
-        let (api_request_sender, _from_api) = channel();
-        let (_to_api, vmm_response_receiver) = channel();
-        let mmds_info = MMDS.clone();
-
-        let api_server = ApiServer::new(
-            mmds_info,
-            instance_info,
-            api_request_sender,
-            vmm_response_receiver,
-            to_vmm_fd,
-        );
-        let response = api_server.put_mmds(serde_json::Value::String("string".to_string()));
-        assert_eq!(response.status(), StatusCode::NoContent);
+    fn test_get_events() {
+        let response = ApiServer::get_events();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.is_streaming());
+        assert_eq!(response.content_type(), MediaType::EventStream);
+        assert_eq!(response.custom_header("Cache-Control"), Some("no-cache"));
     }
 
     #[test]
-    fn test_patch_mmds() {
+    fn test_get_instance_info() {
         let instance_info = InstanceInfo {
             state: "Not started".to_string(),
-            id: "test_patch_mmds".to_string(),
+            id: "test_get_instance_info".to_string(),
             vmm_version: "version 0.1.0".to_string(),
             app_name: "app name".to_string(),
         };
-
         let to_vmm_fd = EventFd::new(libc::EFD_NONBLOCK).unwrap();
+        let from_vmm_fd = EventFd::new(libc::EFD_NONBLOCK).unwrap();
         let (api_request_sender, _from_api) = channel();
         let (_to_api, vmm_response_receiver) = channel();
-        let mmds_info = Arc::new(Mutex::new(Mmds::default()));
 
         let api_server = ApiServer::new(
-            mmds_info,
             instance_info,
             api_request_sender,
             vmm_response_receiver,
             to_vmm_fd,
+            from_vmm_fd,
         );
 
-        // MMDS data store is not yet initialized.
-        let response = api_server.patch_mmds(serde_json::Value::Bool(true));
-        assert_eq!(response.status(), StatusCode::BadRequest);
-
-        let response = api_server.put_mmds(serde_json::Value::String("string".to_string()));
-        assert_eq!(response.status(), StatusCode::NoContent);
-
-        let response = api_server.patch_mmds(serde_json::Value::String(
-            "{ \"key\" : \"value\" }".to_string(),
-        ));
-        assert_eq!(response.status(), StatusCode::NoContent);
+        let response = api_server.get_instance_info();
+        assert_eq!(response.status(), StatusCode::OK);
     }
 
     #[test]
@@ -642,22 +915,17 @@ mod tests {
         };
 
         let to_vmm_fd = EventFd::new(libc::EFD_NONBLOCK).unwrap();
+        let from_vmm_fd = EventFd::new(libc::EFD_NONBLOCK).unwrap();
         let (api_request_sender, _from_api) = channel();
         let (to_api, vmm_response_receiver) = channel();
-        let mmds_info = MMDS.clone();
 
         let mut api_server = ApiServer::new(
-            mmds_info,
             instance_info,
             api_request_sender,
             vmm_response_receiver,
             to_vmm_fd,
+            from_vmm_fd,
         );
-        to_api
-            .send(Box::new(Err(VmmActionError::StartMicrovm(
-                StartMicrovmError::MissingKernelConfig,
-            ))))
-            .unwrap();
 
         // Test an Actions request.
         let (mut sender, receiver) = UnixStream::pair().unwrap();
@@ -685,6 +953,9 @@ mod tests {
         assert_eq!(response.status(), StatusCode::OK);
 
         // Test a Get Mmds request.
+        to_api
+            .send(Box::new(Ok(VmmData::MmdsValue(json!({})))))
+            .unwrap();
         sender.write_all(b"GET /mmds HTTP/1.1\r\n\r\n").unwrap();
         assert!(connection.try_read().is_ok());
         let req = connection.pop_parsed_request().unwrap();
@@ -692,6 +963,7 @@ mod tests {
         assert_eq!(response.status(), StatusCode::OK);
 
         // Test a Put Mmds request.
+        to_api.send(Box::new(Ok(VmmData::Empty))).unwrap();
         sender
             .write_all(
                 b"PUT /mmds HTTP/1.1\r\n\
@@ -705,9 +977,14 @@ mod tests {
         assert_eq!(response.status(), StatusCode::NoContent);
 
         // Test a Patch Mmds request.
+        to_api
+            .send(Box::new(Err(VmmActionError::Mmds(
+                MmdsStoreError::NoDedicatedStore(String::from("eth0")),
+            ))))
+            .unwrap();
         sender
             .write_all(
-                b"PATCH /mmds HTTP/1.1\r\n\
+                b"PATCH /mmds/interfaces/eth0 HTTP/1.1\r\n\
                 Content-Type: application/json\r\n\
                 Content-Length: 2\r\n\r\n{}",
             )
@@ -715,7 +992,7 @@ mod tests {
         assert!(connection.try_read().is_ok());
         let req = connection.pop_parsed_request().unwrap();
         let response = api_server.handle_request(&req, 0);
-        assert_eq!(response.status(), StatusCode::NoContent);
+        assert_eq!(response.status(), StatusCode::BadRequest);
 
         // Test erroneous request.
         sender
@@ -731,6 +1008,265 @@ mod tests {
         assert_eq!(response.status(), StatusCode::BadRequest);
     }
 
+    #[test]
+    fn test_async_operations() {
+        let instance_info = InstanceInfo {
+            state: "Running".to_string(),
+            id: "test_async_operations".to_string(),
+            vmm_version: "version 0.1.0".to_string(),
+            app_name: "app name".to_string(),
+        };
+
+        let to_vmm_fd = EventFd::new(libc::EFD_NONBLOCK).unwrap();
+        let from_vmm_fd = EventFd::new(libc::EFD_NONBLOCK).unwrap();
+        let (api_request_sender, from_api) = channel();
+        let (to_api, vmm_response_receiver) = channel();
+
+        let mut api_server = ApiServer::new(
+            instance_info,
+            api_request_sender,
+            vmm_response_receiver,
+            to_vmm_fd,
+            from_vmm_fd,
+        );
+
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        let mut send_request = |request: &[u8]| {
+            sender.write_all(request).unwrap();
+            assert!(connection.try_read().is_ok());
+            connection.pop_parsed_request().unwrap()
+        };
+
+        // Only the snapshot operations run asynchronously, the other requests are served right
+        // away.
+        let req = send_request(
+            b"PATCH /vm HTTP/1.1\r\n\
+            Prefer: respond-async\r\n\
+            Content-Type: application/json\r\n\
+            Content-Length: 18\r\n\r\n{\"state\":\"Paused\"}",
+        );
+        to_api.send(Box::new(Ok(VmmData::Empty))).unwrap();
+        let response = api_server.handle_request(&req, 0);
+        assert_eq!(response.status(), StatusCode::NoContent);
+        assert_eq!(response.custom_header("Preference-Applied"), None);
+        assert_eq!(*from_api.try_recv().unwrap(), VmmAction::Pause);
+        assert_eq!(api_server.instance_info.state, "Paused");
+
+        // Create a snapshot asynchronously.
+        let req = send_request(
+            b"PUT /snapshot/create HTTP/1.1\r\n\
+            Prefer: respond-async\r\n\
+            Content-Type: application/json\r\n\
+            Content-Length: 41\r\n\r\n{\"snapshot_path\":\"a\",\"mem_file_path\":\"b\"}",
+        );
+        let response = api_server.handle_request(&req, 0);
+        assert_eq!(response.status(), StatusCode::Accepted);
+        assert_eq!(response.custom_header("Location"), Some("/operations/1"));
+        assert_eq!(
+            response.custom_header("Preference-Applied"),
+            Some(RESPOND_ASYNC)
+        );
+        match *from_api.try_recv().unwrap() {
+            VmmAction::CreateSnapshot(_) => (),
+            _ => panic!("Test failed."),
+        }
+
+        // Until the VMM answers, the operation is running, and the changes are refused.
+        let req = send_request(b"GET /operations/1 HTTP/1.1\r\n\r\n");
+        let response = api_server.handle_request(&req, 0);
+        assert_eq!(response.status(), StatusCode::OK);
+        let status: serde_json::Value =
+            serde_json::from_slice(response.body().unwrap().raw()).unwrap();
+        assert_eq!(status["state"], "Running");
+
+        let req = send_request(b"PUT /mmds HTTP/1.1\r\nContent-Length: 2\r\n\r\n{}");
+        let response = api_server.handle_request(&req, 0);
+        assert_eq!(response.status(), StatusCode::Conflict);
+        assert!(from_api.try_recv().is_err());
+
+        let req = send_request(b"GET / HTTP/1.1\r\n\r\n");
+        let response = api_server.handle_request(&req, 0);
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // The outcome is picked up on the next request.
+        to_api.send(Box::new(Ok(VmmData::Empty))).unwrap();
+        let req = send_request(b"GET /operations/1 HTTP/1.1\r\n\r\n");
+        let response = api_server.handle_request(&req, 0);
+        let status: serde_json::Value =
+            serde_json::from_slice(response.body().unwrap().raw()).unwrap();
+        assert_eq!(status["state"], "Succeeded");
+        assert_eq!(status["request"], "PUT /snapshot/create");
+        assert_eq!(api_server.instance_info.state, "Paused");
+
+        // A failed snapshot creation.
+        let req = send_request(
+            b"PUT /snapshot/create HTTP/1.1\r\n\
+            Prefer: wait=10, respond-async\r\n\
+            Content-Type: application/json\r\n\
+            Content-Length: 41\r\n\r\n{\"snapshot_path\":\"a\",\"mem_file_path\":\"b\"}",
+        );
+        let response = api_server.handle_request(&req, 0);
+        assert_eq!(response.status(), StatusCode::Accepted);
+        match *from_api.try_recv().unwrap() {
+            VmmAction::CreateSnapshot(params) => params.progress.add(10),
+            _ => panic!("Test failed."),
+        }
+        let req = send_request(b"GET /operations/2 HTTP/1.1\r\n\r\n");
+        let response = api_server.handle_request(&req, 0);
+        let status: serde_json::Value =
+            serde_json::from_slice(response.body().unwrap().raw()).unwrap();
+        assert_eq!(status["bytes_written"], 10);
+
+        to_api
+            .send(Box::new(Err(VmmActionError::OperationNotSupportedPreBoot)))
+            .unwrap();
+        let req = send_request(b"GET /operations HTTP/1.1\r\n\r\n");
+        let response = api_server.handle_request(&req, 0);
+        let statuses: serde_json::Value =
+            serde_json::from_slice(response.body().unwrap().raw()).unwrap();
+        assert_eq!(statuses.as_array().unwrap().len(), 2);
+        assert_eq!(statuses[1]["state"], "Failed");
+        assert_eq!(
+            statuses[1]["fault_message"],
+            VmmActionError::OperationNotSupportedPreBoot.to_string()
+        );
+
+        let req = send_request(b"GET /operations/3 HTTP/1.1\r\n\r\n");
+        let response = api_server.handle_request(&req, 0);
+        assert_eq!(response.status(), StatusCode::NotFound);
+    }
+
+    #[test]
+    fn test_bind_and_run_with_operation() {
+        let mut tmp_socket = TempFile::new().unwrap();
+        tmp_socket.remove().unwrap();
+        let path_to_socket = tmp_socket.as_path().to_path_buf();
+        let api_thread_path_to_socket = path_to_socket.clone();
+
+        let instance_info = InstanceInfo {
+            state: "Not started".to_string(),
+            id: "test_bind_and_run_with_operation".to_string(),
+            vmm_version: "version 0.1.0".to_string(),
+            app_name: "app name".to_string(),
+        };
+
+        let to_vmm_fd = EventFd::new(libc::EFD_NONBLOCK).unwrap();
+        let from_vmm_fd = EventFd::new(libc::EFD_NONBLOCK).unwrap();
+        let to_api_fd = from_vmm_fd.try_clone().unwrap();
+        let (api_request_sender, from_api) = channel();
+        let (to_api, vmm_response_receiver) = channel();
+
+        thread::Builder::new()
+            .name("fc_api_test".to_owned())
+            .spawn(move || {
+                ApiServer::new(
+                    instance_info,
+                    api_request_sender,
+                    vmm_response_receiver,
+                    to_vmm_fd,
+                    from_vmm_fd,
+                )
+                .bind_and_run(
+                    api_thread_path_to_socket,
+                    Some(1),
+                    Some(1),
+                    SeccompFilter::empty().try_into().unwrap(),
+                )
+                .unwrap();
+            })
+            .unwrap();
+
+        // Wait for the server to set itself up.
+        thread::sleep(Duration::new(0, 10_000_000));
+        let mut buf: [u8; 100] = [0; 100];
+
+        // Create a snapshot asynchronously.
+        let mut sock = UnixStream::connect(&path_to_socket).unwrap();
+        assert!(sock
+            .write_all(
+                b"PUT /snapshot/create HTTP/1.1\r\n\
+                Prefer: respond-async\r\n\
+                Content-Type: application/json\r\n\
+                Content-Length: 41\r\n\r\n{\"snapshot_path\":\"a\",\"mem_file_path\":\"b\"}"
+            )
+            .is_ok());
+        assert!(sock.read(&mut buf[..]).unwrap() > 0);
+        assert!(buf.starts_with(b"HTTP/1.1 202"));
+        match *from_api.recv().unwrap() {
+            VmmAction::CreateSnapshot(_) => (),
+            _ => panic!("Test failed."),
+        }
+
+        // A read of the VMM state waits for the operation.
+        let mut waiting_sock = UnixStream::connect(&path_to_socket).unwrap();
+        waiting_sock
+            .set_read_timeout(Some(Duration::from_millis(50)))
+            .unwrap();
+        assert!(waiting_sock
+            .write_all(b"GET /machine-config HTTP/1.1\r\n\r\n")
+            .is_ok());
+        assert!(waiting_sock.read(&mut buf[..]).is_err());
+
+        // Only a limited number of requests can wait.
+        let mut busy_sock = UnixStream::connect(&path_to_socket).unwrap();
+        busy_sock
+            .set_read_timeout(Some(Duration::from_millis(50)))
+            .unwrap();
+        assert!(busy_sock
+            .write_all(
+                b"GET /machine-config HTTP/1.1\r\n\r\n"
+                    .repeat(MAX_WAITING_REQUESTS - 1)
+                    .as_slice()
+            )
+            .is_ok());
+        assert!(busy_sock.read(&mut buf[..]).is_err());
+        let mut rejected_sock = UnixStream::connect(&path_to_socket).unwrap();
+        assert!(rejected_sock
+            .write_all(b"GET /machine-config HTTP/1.1\r\n\r\n")
+            .is_ok());
+        assert!(rejected_sock.read(&mut buf[..]).unwrap() > 0);
+        assert!(buf.starts_with(b"HTTP/1.1 503"));
+
+        // The requests waiting on the same connection are rejected along with the new one.
+        assert!(busy_sock
+            .write_all(b"GET /machine-config HTTP/1.1\r\n\r\n")
+            .is_ok());
+        busy_sock.set_read_timeout(None).unwrap();
+        let mut responses: Vec<u8> = Vec::new();
+        while responses
+            .windows(12)
+            .filter(|window| *window == b"HTTP/1.1 503")
+            .count()
+            < MAX_WAITING_REQUESTS
+        {
+            let count = busy_sock.read(&mut buf[..]).unwrap();
+            assert!(count > 0);
+            responses.extend_from_slice(&buf[..count]);
+        }
+
+        // The operation can still be followed meanwhile.
+        assert!(sock
+            .write_all(b"GET /operations/1 HTTP/1.1\r\n\r\n")
+            .is_ok());
+        assert!(sock.read(&mut buf[..]).unwrap() > 0);
+        assert!(buf.starts_with(b"HTTP/1.1 200"));
+
+        // Once the VMM is done, the waiting request is served.
+        to_api.send(Box::new(Ok(VmmData::Empty))).unwrap();
+        to_api_fd.write(1).unwrap();
+        assert_eq!(*from_api.recv().unwrap(), VmmAction::GetVmConfiguration);
+        to_api
+            .send(Box::new(Ok(VmmData::MachineConfiguration(
+                Default::default(),
+            ))))
+            .unwrap();
+        to_api_fd.write(1).unwrap();
+        waiting_sock.set_read_timeout(None).unwrap();
+        assert!(waiting_sock.read(&mut buf[..]).unwrap() > 0);
+        assert!(buf.starts_with(b"HTTP/1.1 200"));
+    }
+
     #[test]
     fn test_bind_and_run() {
         let mut tmp_socket = TempFile::new().unwrap();
@@ -746,19 +1282,19 @@ mod tests {
         };
 
         let to_vmm_fd = EventFd::new(libc::EFD_NONBLOCK).unwrap();
+        let from_vmm_fd = EventFd::new(libc::EFD_NONBLOCK).unwrap();
         let (api_request_sender, _from_api) = channel();
         let (_to_api, vmm_response_receiver) = channel();
-        let mmds_info = MMDS.clone();
 
         thread::Builder::new()
             .name("fc_api_test".to_owned())
             .spawn(move || {
                 ApiServer::new(
-                    mmds_info,
                     instance_info,
                     api_request_sender,
                     vmm_response_receiver,
                     to_vmm_fd,
+                    from_vmm_fd,
                 )
                 .bind_and_run(
                     PathBuf::from(api_thread_path_to_socket),
@@ -784,4 +1320,87 @@ mod tests {
         let mut buf: [u8; 100] = [0; 100];
         assert!(sock.read(&mut buf[..]).unwrap() > 0);
     }
+
+    #[test]
+    fn test_bind_and_run_with_access_control() {
+        let mut tmp_socket = TempFile::new().unwrap();
+        tmp_socket.remove().unwrap();
+        let mut tmp_read_only_socket = TempFile::new().unwrap();
+        tmp_read_only_socket.remove().unwrap();
+        let path_to_socket = tmp_socket.as_path().to_path_buf();
+        let path_to_read_only_socket = tmp_read_only_socket.as_path().to_path_buf();
+        let api_thread_path_to_socket = path_to_socket.clone();
+        let access_control = AccessControl {
+            // Only the group of this process is allowed.
+            allowed_uids: vec![],
+            allowed_gids: vec![unsafe { libc::getegid() }],
+            read_only_socket: Some(path_to_read_only_socket.clone()),
+        };
+        // The socket left behind by a previous run is replaced.
+        drop(UnixListener::bind(&path_to_read_only_socket).unwrap());
+
+        let instance_info = InstanceInfo {
+            state: "Not started".to_string(),
+            id: "test_handle_request".to_string(),
+            vmm_version: "version 0.1.0".to_string(),
+            app_name: "app name".to_string(),
+        };
+
+        let to_vmm_fd = EventFd::new(libc::EFD_NONBLOCK).unwrap();
+        let from_vmm_fd = EventFd::new(libc::EFD_NONBLOCK).unwrap();
+        let (api_request_sender, _from_api) = channel();
+        let (_to_api, vmm_response_receiver) = channel();
+
+        thread::Builder::new()
+            .name("fc_api_test".to_owned())
+            .spawn(move || {
+                let mut api_server = ApiServer::new(
+                    instance_info,
+                    api_request_sender,
+                    vmm_response_receiver,
+                    to_vmm_fd,
+                    from_vmm_fd,
+                );
+                api_server.set_access_control(access_control);
+                api_server
+                    .bind_and_run(
+                        api_thread_path_to_socket,
+                        Some(1),
+                        Some(1),
+                        SeccompFilter::empty().try_into().unwrap(),
+                    )
+                    .unwrap();
+            })
+            .unwrap();
+
+        // Wait for the server to set itself up.
+        thread::sleep(Duration::new(0, 10_000_000));
+
+        // The client is allowed through its group.
+        let mut sock = UnixStream::connect(path_to_socket).unwrap();
+        assert!(sock.write_all(b"GET / HTTP/1.1\r\n\r\n").is_ok());
+        let mut buf: [u8; 100] = [0; 100];
+        assert!(sock.read(&mut buf[..]).unwrap() > 0);
+        assert!(buf.starts_with(b"HTTP/1.1 200"));
+
+        // Only the requests reporting the state and statistics of the microVM are served on
+        // the read-only socket.
+        let mut sock = UnixStream::connect(path_to_read_only_socket).unwrap();
+        for request in [
+            "PUT /actions HTTP/1.1\r\nContent-Length: 2\r\n\r\n{}",
+            "GET /mmds HTTP/1.1\r\n\r\n",
+            "GET /vm/config HTTP/1.1\r\n\r\n",
+        ]
+        .iter()
+        {
+            assert!(sock.write_all(request.as_bytes()).is_ok());
+            let mut buf: [u8; 100] = [0; 100];
+            assert!(sock.read(&mut buf[..]).unwrap() > 0);
+            assert!(buf.starts_with(b"HTTP/1.1 403"), "{}", request);
+        }
+        assert!(sock.write_all(b"GET / HTTP/1.1\r\n\r\n").is_ok());
+        let mut buf: [u8; 100] = [0; 100];
+        assert!(sock.read(&mut buf[..]).unwrap() > 0);
+        assert!(buf.starts_with(b"HTTP/1.1 200"));
+    }
 }
@@ -0,0 +1,223 @@
+// Copyright 2021 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Tracks the VMM actions run asynchronously, on behalf of requests which carry the
+//! `Prefer: respond-async` header.
+
+use std::collections::VecDeque;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::parsed_request::ParsedRequest;
+use vmm::rpc_interface::{VmmActionError, VmmData};
+use vmm::vmm_config::snapshot::SnapshotProgress;
+
+/// The maximum number of finished operations kept around for status queries. The oldest
+/// ones are forgotten first.
+const MAX_FINISHED_OPERATIONS: usize = 32;
+
+/// The state of an asynchronous operation.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) enum OperationState {
+    /// The VMM hasn't answered yet.
+    Running,
+    /// The VMM action succeeded.
+    Succeeded,
+    /// The VMM action failed.
+    Failed,
+}
+
+/// The status of an asynchronous operation, as reported by `GET /operations/{id}`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct OperationStatus {
+    /// The operation ID.
+    pub id: u64,
+    /// The request which started the operation, e.g. `PUT /snapshot/create`.
+    pub request: String,
+    /// The operation state.
+    pub state: OperationState,
+    /// For snapshot creation, the bytes written to the snapshot files so far.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes_written: Option<u64>,
+    /// The data returned by a successful operation, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    /// The error message of a failed operation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fault_message: Option<String>,
+}
+
+/// A VMM action sent to the VMM without waiting for its outcome.
+pub(crate) struct Operation {
+    status: OperationStatus,
+    progress: Option<SnapshotProgress>,
+    /// The instance state to switch to if the action succeeds.
+    pub vmm_new_state: String,
+}
+
+impl Operation {
+    /// Returns the current status of the operation.
+    pub fn status(&self) -> OperationStatus {
+        let mut status = self.status.clone();
+        if let Some(progress) = self.progress.as_ref() {
+            status.bytes_written = Some(progress.bytes_written());
+        }
+        status
+    }
+
+    // Records the outcome of the VMM action.
+    fn finish(&mut self, outcome: &std::result::Result<VmmData, VmmActionError>) {
+        match outcome {
+            Ok(_) => {
+                self.status.state = OperationState::Succeeded;
+                // The result is reported the same way a synchronous request would return it.
+                self.status.result = ParsedRequest::convert_to_response(outcome)
+                    .body()
+                    .and_then(|body| serde_json::from_slice(body.raw()).ok());
+            }
+            Err(err) => {
+                self.status.state = OperationState::Failed;
+                self.status.fault_message = Some(err.to_string());
+            }
+        }
+    }
+}
+
+/// The asynchronous operations started through the API. At most one of them is running at
+/// any time, since the VMM answers the actions in order.
+#[derive(Default)]
+pub(crate) struct Operations {
+    next_id: u64,
+    running: Option<Operation>,
+    finished: VecDeque<Operation>,
+}
+
+impl Operations {
+    /// Registers a new running operation, and returns its ID.
+    pub fn start(
+        &mut self,
+        request: String,
+        progress: Option<SnapshotProgress>,
+        vmm_new_state: String,
+    ) -> u64 {
+        self.next_id += 1;
+        self.running = Some(Operation {
+            status: OperationStatus {
+                id: self.next_id,
+                request,
+                state: OperationState::Running,
+                bytes_written: None,
+                result: None,
+                fault_message: None,
+            },
+            progress,
+            vmm_new_state,
+        });
+        self.next_id
+    }
+
+    /// Returns the running operation, if any.
+    pub fn running(&self) -> Option<&Operation> {
+        self.running.as_ref()
+    }
+
+    /// Records the outcome of the running operation, and returns it.
+    pub fn finish(
+        &mut self,
+        outcome: &std::result::Result<VmmData, VmmActionError>,
+    ) -> Option<&Operation> {
+        let mut operation = self.running.take()?;
+        operation.finish(outcome);
+        if self.finished.len() == MAX_FINISHED_OPERATIONS {
+            self.finished.pop_front();
+        }
+        self.finished.push_back(operation);
+        self.finished.back()
+    }
+
+    /// Returns the status of the operation with the given ID.
+    pub fn status(&self, id: u64) -> Option<OperationStatus> {
+        self.iter()
+            .find(|operation| operation.status.id == id)
+            .map(Operation::status)
+    }
+
+    /// Returns the status of all the known operations, oldest first.
+    pub fn statuses(&self) -> Vec<OperationStatus> {
+        self.iter().map(Operation::status).collect()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &Operation> {
+        self.finished.iter().chain(self.running.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operations() {
+        let mut operations = Operations::default();
+        assert!(operations.running().is_none());
+        assert!(operations.finish(&Ok(VmmData::Empty)).is_none());
+
+        let progress = SnapshotProgress::default();
+        let id = operations.start(
+            "PUT /snapshot/create".to_string(),
+            Some(progress.clone()),
+            "Paused".to_string(),
+        );
+        assert_eq!(id, 1);
+        assert_eq!(operations.running().unwrap().vmm_new_state, "Paused");
+        progress.add(4096);
+        let status = operations.status(id).unwrap();
+        assert_eq!(status.state, OperationState::Running);
+        assert_eq!(status.bytes_written, Some(4096));
+        assert_eq!(
+            serde_json::to_value(&status).unwrap(),
+            serde_json::json!({
+                "id": 1,
+                "request": "PUT /snapshot/create",
+                "state": "Running",
+                "bytes_written": 4096
+            })
+        );
+
+        operations.finish(&Ok(VmmData::Empty)).unwrap();
+        assert!(operations.running().is_none());
+        let status = operations.status(id).unwrap();
+        assert_eq!(status.state, OperationState::Succeeded);
+        assert_eq!(status.result, None);
+
+        let id = operations.start("GET /mmds".to_string(), None, "Running".to_string());
+        operations
+            .finish(&Ok(VmmData::MmdsValue(serde_json::json!({"foo": "bar"}))))
+            .unwrap();
+        let status = operations.status(id).unwrap();
+        assert_eq!(status.bytes_written, None);
+        assert_eq!(status.result, Some(serde_json::json!({"foo": "bar"})));
+
+        let id = operations.start("PUT /snapshot/load".to_string(), None, String::new());
+        operations
+            .finish(&Err(VmmActionError::OperationNotSupportedPostBoot))
+            .unwrap();
+        let status = operations.status(id).unwrap();
+        assert_eq!(status.state, OperationState::Failed);
+        assert_eq!(
+            status.fault_message,
+            Some(VmmActionError::OperationNotSupportedPostBoot.to_string())
+        );
+        assert_eq!(operations.statuses().len(), 3);
+        assert!(operations.status(4).is_none());
+
+        // Only the latest finished operations are kept.
+        for _ in 0..MAX_FINISHED_OPERATIONS {
+            operations.start(String::new(), None, String::new());
+            operations.finish(&Ok(VmmData::Empty));
+        }
+        assert_eq!(operations.statuses().len(), MAX_FINISHED_OPERATIONS);
+        assert!(operations.status(1).is_none());
+    }
+}
@@ -1,35 +1,130 @@
 // Copyright 2019 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-use serde_json::Value;
-
 use super::VmmData;
 use crate::request::actions::parse_put_actions;
 use crate::request::balloon::{parse_get_balloon, parse_patch_balloon, parse_put_balloon};
-use crate::request::boot_source::parse_put_boot_source;
-use crate::request::drive::{parse_patch_drive, parse_put_drive};
+use crate::request::boot_source::{parse_get_boot_source, parse_put_boot_source};
+use crate::request::drive::{parse_get_drive, parse_patch_drive, parse_put_drive};
+use crate::request::events::parse_get_events;
 use crate::request::instance_info::parse_get_instance_info;
-use crate::request::logger::parse_put_logger;
+use crate::request::logger::{parse_get_logger, parse_put_logger};
 use crate::request::machine_configuration::{
     parse_get_machine_config, parse_patch_machine_config, parse_put_machine_config,
 };
-use crate::request::metrics::parse_put_metrics;
+use crate::request::metrics::{parse_get_metrics, parse_put_metrics};
 use crate::request::mmds::{parse_get_mmds, parse_patch_mmds, parse_put_mmds};
-use crate::request::net::{parse_patch_net, parse_put_net};
+use crate::request::net::{parse_delete_net, parse_get_net, parse_patch_net, parse_put_net};
+use crate::request::operation::parse_get_operation;
 use crate::request::snapshot::parse_patch_vm_state;
 use crate::request::snapshot::parse_put_snapshot;
-use crate::request::vsock::parse_put_vsock;
+use crate::request::vm_config::{parse_get_vm_config, parse_put_vm_config};
+use crate::request::vsock::{parse_get_vsock, parse_put_vsock};
 use crate::ApiServer;
-use micro_http::{Body, Method, Request, Response, StatusCode, Version};
+use micro_http::{Body, Method, ReadSource, Request, Response, StatusCode, Version};
 
 use logger::{error, info};
 use vmm::rpc_interface::{VmmAction, VmmActionError};
+use vmm::vmm_config::mmds::{MmdsError, MmdsStoreError};
+
+// Response bodies larger than this are sent in chunks of this size, instead of being copied
+// whole to the connection.
+const BODY_CHUNK_SIZE: usize = 16 * 1024;
+
+/// The routes served by the API, as documented in the swagger spec. Path parameters are
+/// written in braces and match any single path segment.
+pub(crate) const ROUTES: &[(Method, &str)] = &[
+    (Method::Get, "/"),
+    (Method::Get, "/balloon"),
+    (Method::Get, "/balloon/statistics"),
+    (Method::Get, "/boot-source"),
+    (Method::Get, "/drives"),
+    (Method::Get, "/drives/{drive_id}"),
+    (Method::Get, "/events"),
+    (Method::Get, "/logger"),
+    (Method::Get, "/machine-config"),
+    (Method::Get, "/metrics"),
+    (Method::Get, "/mmds"),
+    (Method::Get, "/mmds/interfaces/{iface_id}"),
+    (Method::Get, "/network-interfaces"),
+    (Method::Get, "/network-interfaces/{iface_id}"),
+    (Method::Get, "/network-interfaces/{iface_id}/stats"),
+    (Method::Get, "/operations"),
+    (Method::Get, "/operations/{operation_id}"),
+    (Method::Get, "/vm/config"),
+    (Method::Get, "/vsock"),
+    (Method::Get, "/vsock/{vsock_id}"),
+    (Method::Put, "/actions"),
+    (Method::Put, "/balloon"),
+    (Method::Put, "/boot-source"),
+    (Method::Put, "/drives/{drive_id}"),
+    (Method::Put, "/logger"),
+    (Method::Put, "/machine-config"),
+    (Method::Put, "/metrics"),
+    (Method::Put, "/mmds"),
+    (Method::Put, "/mmds/config"),
+    (Method::Put, "/mmds/interfaces/{iface_id}"),
+    (Method::Put, "/network-interfaces/{iface_id}"),
+    (Method::Put, "/snapshot/create"),
+    (Method::Put, "/snapshot/load"),
+    (Method::Put, "/vm/config"),
+    (Method::Put, "/vsock"),
+    (Method::Patch, "/balloon"),
+    (Method::Patch, "/balloon/statistics"),
+    (Method::Patch, "/drives/{drive_id}"),
+    (Method::Patch, "/machine-config"),
+    (Method::Patch, "/mmds"),
+    (Method::Patch, "/mmds/interfaces/{iface_id}"),
+    (Method::Patch, "/network-interfaces/{iface_id}"),
+    (Method::Patch, "/vm"),
+    (Method::Delete, "/network-interfaces/{iface_id}"),
+];
+
+/// The routes served on the read-only API socket. They only report the state and statistics of
+/// the microVM, unlike the configuration and the MMDS contents, which may hold secrets.
+pub(crate) const READ_ONLY_ROUTES: &[(Method, &str)] = &[
+    (Method::Get, "/"),
+    (Method::Get, "/balloon/statistics"),
+    (Method::Get, "/events"),
+    (Method::Get, "/network-interfaces/{iface_id}/stats"),
+];
+
+// Returns `true` if `request` matches one of the `routes`. The query string is ignored.
+fn request_matches(routes: &[(Method, &str)], request: &Request) -> bool {
+    let path_tokens: Vec<&str> = request
+        .uri()
+        .get_abs_path()
+        .split('?')
+        .next()
+        .unwrap_or("")
+        .trim_start_matches('/')
+        .split_terminator('/')
+        .collect();
+    routes
+        .iter()
+        .any(|(method, route)| *method == request.method() && route_matches(route, &path_tokens))
+}
+
+// Returns `true` if the request path, split in tokens, matches the `route` template.
+fn route_matches(route: &str, path_tokens: &[&str]) -> bool {
+    let route_tokens: Vec<&str> = route
+        .trim_start_matches('/')
+        .split_terminator('/')
+        .collect();
+    route_tokens.len() == path_tokens.len()
+        && route_tokens
+            .iter()
+            .zip(path_tokens)
+            .all(|(route_token, path_token)| {
+                route_token == path_token
+                    || (route_token.starts_with('{') && !path_token.is_empty())
+            })
+}
 
 pub(crate) enum ParsedRequest {
+    GetEvents,
     GetInstanceInfo,
-    GetMMDS,
-    PatchMMDS(Value),
-    PutMMDS(Value),
+    GetOperation(Option<u64>),
     Sync(Box<VmmAction>),
 }
 
@@ -55,11 +150,30 @@ impl ParsedRequest {
             path_tokens[0]
         };
 
+        // Only the routes in `ROUTES` are served, whatever the parsers below would accept.
+        if !request_matches(ROUTES, request) {
+            return Err(Error::InvalidPathMethod(
+                request_uri.clone(),
+                request.method(),
+            ));
+        }
+
         match (request.method(), path, request.body.as_ref()) {
             (Method::Get, "", None) => parse_get_instance_info(),
             (Method::Get, "balloon", None) => parse_get_balloon(path_tokens.get(1)),
+            (Method::Get, "boot-source", None) => parse_get_boot_source(),
+            (Method::Get, "drives", None) => parse_get_drive(path_tokens.get(1)),
+            (Method::Get, "events", None) => parse_get_events(),
+            (Method::Get, "logger", None) => parse_get_logger(),
             (Method::Get, "machine-config", None) => parse_get_machine_config(),
-            (Method::Get, "mmds", None) => parse_get_mmds(),
+            (Method::Get, "metrics", None) => parse_get_metrics(),
+            (Method::Get, "mmds", None) => parse_get_mmds(path_tokens.get(1), path_tokens.get(2)),
+            (Method::Get, "network-interfaces", None) => {
+                parse_get_net(path_tokens.get(1), path_tokens.get(2))
+            }
+            (Method::Get, "operations", None) => parse_get_operation(path_tokens.get(1)),
+            (Method::Get, "vm", None) => parse_get_vm_config(path_tokens.get(1)),
+            (Method::Get, "vsock", None) => parse_get_vsock(path_tokens.get(1)),
             (Method::Get, _, Some(_)) => method_to_error(Method::Get),
             (Method::Put, "actions", Some(body)) => parse_put_actions(body),
             (Method::Put, "balloon", Some(body)) => parse_put_balloon(body),
@@ -68,22 +182,29 @@ impl ParsedRequest {
             (Method::Put, "logger", Some(body)) => parse_put_logger(body),
             (Method::Put, "machine-config", Some(body)) => parse_put_machine_config(body),
             (Method::Put, "metrics", Some(body)) => parse_put_metrics(body),
-            (Method::Put, "mmds", Some(body)) => parse_put_mmds(body, path_tokens.get(1)),
+            (Method::Put, "mmds", Some(body)) => {
+                parse_put_mmds(body, path_tokens.get(1), path_tokens.get(2))
+            }
             (Method::Put, "network-interfaces", Some(body)) => {
                 parse_put_net(body, path_tokens.get(1))
             }
             (Method::Put, "snapshot", Some(body)) => parse_put_snapshot(body, path_tokens.get(1)),
+            (Method::Put, "vm", Some(body)) => parse_put_vm_config(body, path_tokens.get(1)),
             (Method::Put, "vsock", Some(body)) => parse_put_vsock(body),
             (Method::Put, _, None) => method_to_error(Method::Put),
             (Method::Patch, "balloon", Some(body)) => parse_patch_balloon(body, path_tokens.get(1)),
             (Method::Patch, "drives", Some(body)) => parse_patch_drive(body, path_tokens.get(1)),
             (Method::Patch, "machine-config", Some(body)) => parse_patch_machine_config(body),
-            (Method::Patch, "mmds", Some(body)) => parse_patch_mmds(body),
+            (Method::Patch, "mmds", Some(body)) => {
+                parse_patch_mmds(body, path_tokens.get(1), path_tokens.get(2))
+            }
             (Method::Patch, "network-interfaces", Some(body)) => {
                 parse_patch_net(body, path_tokens.get(1))
             }
             (Method::Patch, "vm", Some(body)) => parse_patch_vm_state(body),
             (Method::Patch, _, None) => method_to_error(Method::Patch),
+            (Method::Delete, "network-interfaces", None) => parse_delete_net(path_tokens.get(1)),
+            (Method::Delete, _, Some(_)) => method_to_error(Method::Delete),
             (method, unknown_uri, _) => {
                 Err(Error::InvalidPathMethod(unknown_uri.to_string(), method))
             }
@@ -117,13 +238,36 @@ impl ParsedRequest {
                     response.set_body(Body::new(serde_json::to_string(stats).unwrap()));
                     response
                 }
+                VmmData::NetworkInterfaceStats(stats) => {
+                    info!("The request was executed successfully. Status code: 200 OK.");
+                    let mut response = Response::new(Version::Http11, StatusCode::OK);
+                    response.set_body(Body::new(serde_json::to_string(stats).unwrap()));
+                    response
+                }
+                VmmData::MmdsValue(value) | VmmData::ResourceConfig(value) => {
+                    info!("The request was executed successfully. Status code: 200 OK.");
+                    ParsedRequest::document_response(value.to_string())
+                }
+                VmmData::FullVmConfig(vmm_config) => {
+                    info!("The request was executed successfully. Status code: 200 OK.");
+                    ParsedRequest::document_response(serde_json::to_string(vmm_config).unwrap())
+                }
             },
             Err(vmm_action_error) => {
+                let (status_code, status_description) = match vmm_action_error {
+                    VmmActionError::Mmds(MmdsStoreError::DataStore(
+                        MmdsError::DataStoreLimitExceeded(_),
+                    )) => (StatusCode::PayloadTooLarge, "413 Payload Too Large"),
+                    VmmActionError::ResourceNotConfigured(_) => {
+                        (StatusCode::NotFound, "404 Not Found")
+                    }
+                    _ => (StatusCode::BadRequest, "400 Bad Request"),
+                };
                 error!(
-                    "Received Error. Status code: 400 Bad Request. Message: {}",
-                    vmm_action_error
+                    "Received Error. Status code: {}. Message: {}",
+                    status_description, vmm_action_error
                 );
-                let mut response = Response::new(Version::Http11, StatusCode::BadRequest);
+                let mut response = Response::new(Version::Http11, status_code);
                 response.set_body(Body::new(ApiServer::json_fault_message(
                     vmm_action_error.to_string(),
                 )));
@@ -132,6 +276,40 @@ impl ParsedRequest {
         }
     }
 
+    // Answers with a JSON document of arbitrary size, such as the MMDS contents.
+    fn document_response(body: String) -> Response {
+        let mut response = Response::new(Version::Http11, StatusCode::OK);
+        if body.len() > BODY_CHUNK_SIZE {
+            response.set_body_source(Box::new(ReadSource::new(
+                std::io::Cursor::new(body.into_bytes()),
+                BODY_CHUNK_SIZE,
+            )));
+        } else {
+            response.set_body(Body::new(body));
+        }
+        response
+    }
+
+    /// Returns `true` if `request` only reads the state of the VMM, which makes it safe to
+    /// answer once the running operation is over, instead of rejecting it.
+    pub(crate) fn reads_vmm_state(request: &Request) -> bool {
+        let path = request
+            .uri()
+            .get_abs_path()
+            .trim_start_matches('/')
+            .split('/')
+            .next()
+            .unwrap_or("");
+        request.method() == Method::Get
+            && request.body.is_none()
+            && !matches!(path, "" | "events" | "operations")
+    }
+
+    /// Returns `true` if `request` may be served on the read-only socket.
+    pub(crate) fn allowed_read_only(request: &Request) -> bool {
+        request_matches(READ_ONLY_ROUTES, request)
+    }
+
     /// Helper function to avoid boiler-plate code.
     pub(crate) fn new_sync(vmm_action: VmmAction) -> ParsedRequest {
         ParsedRequest::Sync(Box::new(vmm_action))
@@ -150,12 +328,15 @@ fn log_received_api_request(api_description: String) {
 ///
 /// # Arguments
 ///
-/// * `method` - one of `GET`, `PATCH`, `PUT`
+/// * `method` - one of `GET`, `PATCH`, `PUT`, `DELETE`
 /// * `path` - path of the API request
 /// * `body` - body of the API request
 fn describe(method: Method, path: &str, body: Option<&Body>) -> String {
     match (path, body) {
         ("/mmds", Some(_)) | (_, None) => format!("{:?} request on {:?}", method, path),
+        (path, Some(_)) if path.starts_with("/mmds/interfaces/") => {
+            format!("{:?} request on {:?}", method, path)
+        }
         (_, Some(value)) => format!(
             "{:?} request on {:?} with body {:?}",
             method,
@@ -182,6 +363,10 @@ pub(crate) fn method_to_error(method: Method) -> Result<ParsedRequest, Error> {
             StatusCode::BadRequest,
             "Empty PATCH request.".to_string(),
         )),
+        Method::Delete => Err(Error::Generic(
+            StatusCode::BadRequest,
+            "DELETE request cannot have a body.".to_string(),
+        )),
     }
 }
 
@@ -195,6 +380,8 @@ pub(crate) enum Error {
     InvalidID,
     // The HTTP method & request path combination is not valid.
     InvalidPathMethod(String, Method),
+    // The request path has an unknown segment, given as the second field.
+    UnrecognizedPath(Method, String),
     // An error occurred when deserializing the json body of a request.
     SerdeJson(serde_json::Error),
 }
@@ -214,6 +401,12 @@ impl std::fmt::Display for Error {
                 std::str::from_utf8(method.raw()).expect("Cannot convert from UTF-8"),
                 path
             ),
+            Error::UnrecognizedPath(ref method, ref path) => write!(
+                f,
+                "Unrecognized {} request path `{}`.",
+                std::str::from_utf8(method.raw()).expect("Cannot convert from UTF-8"),
+                path
+            ),
             Error::SerdeJson(ref e) => write!(
                 f,
                 "An error occurred when deserializing the json body of a request: {}.",
@@ -232,6 +425,7 @@ impl Into<Response> for Error {
             Error::EmptyID
             | Error::InvalidID
             | Error::InvalidPathMethod(_, _)
+            | Error::UnrecognizedPath(_, _)
             | Error::SerdeJson(_) => ApiServer::json_response(StatusCode::BadRequest, msg),
         }
     }
@@ -260,11 +454,13 @@ pub(crate) mod tests {
     use std::os::unix::net::UnixStream;
     use std::str::FromStr;
 
-    use micro_http::HttpConnection;
+    use micro_http::{BodySource, HttpConnection};
     use vmm::builder::StartMicrovmError;
+    use vmm::resources::{ConfiguredResource, VmmConfig};
     use vmm::rpc_interface::VmmActionError;
     use vmm::vmm_config::balloon::BalloonStats;
     use vmm::vmm_config::machine_config::VmConfig;
+    use vmm::vmm_config::net::NetworkInterfaceStats;
 
     impl PartialEq for ParsedRequest {
         fn eq(&self, other: &ParsedRequest) -> bool {
@@ -272,13 +468,10 @@ pub(crate) mod tests {
                 (&ParsedRequest::Sync(ref sync_req), &ParsedRequest::Sync(ref other_sync_req)) => {
                     sync_req == other_sync_req
                 }
+                (&ParsedRequest::GetEvents, &ParsedRequest::GetEvents) => true,
                 (&ParsedRequest::GetInstanceInfo, &ParsedRequest::GetInstanceInfo) => true,
-                (&ParsedRequest::GetMMDS, &ParsedRequest::GetMMDS) => true,
-                (&ParsedRequest::PutMMDS(ref val), &ParsedRequest::PutMMDS(ref other_val)) => {
-                    val == other_val
-                }
-                (&ParsedRequest::PatchMMDS(ref val), &ParsedRequest::PatchMMDS(ref other_val)) => {
-                    val == other_val
+                (&ParsedRequest::GetOperation(id), &ParsedRequest::GetOperation(other_id)) => {
+                    id == other_id
                 }
                 _ => false,
             }
@@ -456,6 +649,14 @@ pub(crate) mod tests {
         let expected_response = http_response(&body, 400);
         assert_eq!(buf.into_inner(), expected_response.as_bytes());
 
+        // Unrecognized path error.
+        let mut buf = Cursor::new(vec![0]);
+        let response: Response = Error::UnrecognizedPath(Method::Get, "path".to_string()).into();
+        assert!(response.write_all(&mut buf).is_ok());
+        let body = ApiServer::json_fault_message("Unrecognized GET request path `path`.");
+        let expected_response = http_response(&body, 400);
+        assert_eq!(buf.into_inner(), expected_response.as_bytes());
+
         // Serde error.
         let mut buf = Cursor::new(vec![0]);
         let serde_error = serde_json::Value::from_str("").unwrap_err();
@@ -479,6 +680,14 @@ pub(crate) mod tests {
             describe(Method::Put, "/mmds", None),
             "Put request on \"/mmds\""
         );
+        assert_eq!(
+            describe(
+                Method::Put,
+                "/mmds/interfaces/eth0",
+                Some(&Body::new("body"))
+            ),
+            "Put request on \"/mmds/interfaces/eth0\""
+        );
         assert_eq!(
             describe(Method::Put, "path", Some(&Body::new("body"))),
             "Put request on \"path\" with body \"body\""
@@ -514,6 +723,56 @@ pub(crate) mod tests {
         let expected_response = http_response(&serde_json::to_string(&stats).unwrap(), 200);
         assert_eq!(buf.into_inner(), expected_response.as_bytes());
 
+        // With Network Interface Stats Vmm data.
+        let mut stats = NetworkInterfaceStats::default();
+        stats.iface_id = String::from("eth0");
+        stats.rx_bytes = 1;
+        let mut buf = Cursor::new(vec![0]);
+        let response =
+            ParsedRequest::convert_to_response(&Ok(VmmData::NetworkInterfaceStats(stats.clone())));
+        assert!(response.write_all(&mut buf).is_ok());
+        let expected_response = http_response(&serde_json::to_string(&stats).unwrap(), 200);
+        assert_eq!(buf.into_inner(), expected_response.as_bytes());
+
+        // With MMDS contents Vmm data.
+        let mut buf = Cursor::new(vec![0]);
+        let response = ParsedRequest::convert_to_response(&Ok(VmmData::MmdsValue(
+            serde_json::json!({"foo": "bar"}),
+        )));
+        assert!(response.write_all(&mut buf).is_ok());
+        let expected_response = http_response("{\"foo\":\"bar\"}", 200);
+        assert_eq!(buf.into_inner(), expected_response.as_bytes());
+
+        // Large documents are sent in chunks.
+        let value = serde_json::json!({ "foo": "a".repeat(BODY_CHUNK_SIZE) });
+        let mut response =
+            ParsedRequest::convert_to_response(&Ok(VmmData::MmdsValue(value.clone())));
+        assert!(response.is_chunked());
+        let mut source = response.take_body_source().unwrap();
+        let mut body = Vec::new();
+        while let Some(chunk) = source.next_chunk().unwrap() {
+            assert!(chunk.len() <= BODY_CHUNK_SIZE);
+            body.extend_from_slice(&chunk);
+        }
+        assert_eq!(body, value.to_string().into_bytes());
+
+        // With the configuration of a single resource.
+        let mut buf = Cursor::new(vec![0]);
+        let response = ParsedRequest::convert_to_response(&Ok(VmmData::ResourceConfig(
+            serde_json::json!({"metrics_path": "metrics"}),
+        )));
+        assert!(response.write_all(&mut buf).is_ok());
+        let expected_response = http_response("{\"metrics_path\":\"metrics\"}", 200);
+        assert_eq!(buf.into_inner(), expected_response.as_bytes());
+
+        // With the full microVM configuration.
+        let vmm_config = VmmConfig::default();
+        let expected_response = http_response(&serde_json::to_string(&vmm_config).unwrap(), 200);
+        let mut buf = Cursor::new(vec![0]);
+        let response = ParsedRequest::convert_to_response(&Ok(VmmData::FullVmConfig(vmm_config)));
+        assert!(response.write_all(&mut buf).is_ok());
+        assert_eq!(buf.into_inner(), expected_response.as_bytes());
+
         // Error.
         let error = VmmActionError::StartMicrovm(StartMicrovmError::MissingKernelConfig);
         let mut buf = Cursor::new(vec![0]);
@@ -523,6 +782,28 @@ pub(crate) mod tests {
 
         let expected_response = http_response(&json, 400);
         assert_eq!(buf.into_inner(), expected_response.as_bytes());
+
+        // Exceeding the MMDS data store size limit.
+        let error = VmmActionError::Mmds(MmdsStoreError::DataStore(
+            MmdsError::DataStoreLimitExceeded(51200),
+        ));
+        let mut buf = Cursor::new(vec![0]);
+        let json = ApiServer::json_fault_message(error.to_string());
+        let response = ParsedRequest::convert_to_response(&Err(error));
+        response.write_all(&mut buf).unwrap();
+
+        let expected_response = http_response(&json, 413);
+        assert_eq!(buf.into_inner(), expected_response.as_bytes());
+
+        // Reading back a resource which was not configured.
+        let error = VmmActionError::ResourceNotConfigured(ConfiguredResource::Logger);
+        let mut buf = Cursor::new(vec![0]);
+        let json = ApiServer::json_fault_message(error.to_string());
+        let response = ParsedRequest::convert_to_response(&Err(error));
+        response.write_all(&mut buf).unwrap();
+
+        let expected_response = http_response(&json, 404);
+        assert_eq!(buf.into_inner(), expected_response.as_bytes());
     }
 
     #[test]
@@ -573,6 +854,152 @@ pub(crate) mod tests {
         assert!(ParsedRequest::try_from_request(&req).is_ok());
     }
 
+    #[test]
+    fn test_try_from_get_net() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request("GET", "/network-interfaces/eth0", None).as_bytes())
+            .unwrap();
+        assert!(connection.try_read().is_ok());
+        let req = connection.pop_parsed_request().unwrap();
+        assert!(ParsedRequest::try_from_request(&req).is_ok());
+
+        sender
+            .write_all(http_request("GET", "/network-interfaces/eth0/stats", None).as_bytes())
+            .unwrap();
+        assert!(connection.try_read().is_ok());
+        let req = connection.pop_parsed_request().unwrap();
+        assert!(ParsedRequest::try_from_request(&req).is_ok());
+    }
+
+    #[test]
+    fn test_try_from_get_boot_source() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request("GET", "/boot-source", None).as_bytes())
+            .unwrap();
+        assert!(connection.try_read().is_ok());
+        let req = connection.pop_parsed_request().unwrap();
+        assert!(ParsedRequest::try_from_request(&req).is_ok());
+    }
+
+    #[test]
+    fn test_try_from_get_drives() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request("GET", "/drives/rootfs", None).as_bytes())
+            .unwrap();
+        assert!(connection.try_read().is_ok());
+        let req = connection.pop_parsed_request().unwrap();
+        assert!(ParsedRequest::try_from_request(&req).is_ok());
+    }
+
+    #[test]
+    fn test_try_from_get_logger() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request("GET", "/logger", None).as_bytes())
+            .unwrap();
+        assert!(connection.try_read().is_ok());
+        let req = connection.pop_parsed_request().unwrap();
+        assert!(ParsedRequest::try_from_request(&req).is_ok());
+    }
+
+    #[test]
+    fn test_try_from_get_metrics() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request("GET", "/metrics", None).as_bytes())
+            .unwrap();
+        assert!(connection.try_read().is_ok());
+        let req = connection.pop_parsed_request().unwrap();
+        assert!(ParsedRequest::try_from_request(&req).is_ok());
+    }
+
+    #[test]
+    fn test_try_from_get_vm_config() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request("GET", "/vm/config", None).as_bytes())
+            .unwrap();
+        assert!(connection.try_read().is_ok());
+        let req = connection.pop_parsed_request().unwrap();
+        assert!(ParsedRequest::try_from_request(&req).is_ok());
+    }
+
+    #[test]
+    fn test_try_from_get_vsock() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request("GET", "/vsock", None).as_bytes())
+            .unwrap();
+        assert!(connection.try_read().is_ok());
+        let req = connection.pop_parsed_request().unwrap();
+        assert!(ParsedRequest::try_from_request(&req).is_ok());
+    }
+
+    #[test]
+    fn test_try_from_get_events() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request("GET", "/events", None).as_bytes())
+            .unwrap();
+        assert!(connection.try_read().is_ok());
+        let req = connection.pop_parsed_request().unwrap();
+        assert!(ParsedRequest::try_from_request(&req).unwrap() == ParsedRequest::GetEvents);
+    }
+
+    #[test]
+    fn test_try_from_get_operations() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request("GET", "/operations/1", None).as_bytes())
+            .unwrap();
+        assert!(connection.try_read().is_ok());
+        let req = connection.pop_parsed_request().unwrap();
+        assert!(
+            ParsedRequest::try_from_request(&req).unwrap() == ParsedRequest::GetOperation(Some(1))
+        );
+    }
+
+    #[test]
+    fn test_reads_vmm_state() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        let mut reads_vmm_state = |method: &str, endpoint: &str, body: Option<&str>| {
+            sender
+                .write_all(http_request(method, endpoint, body).as_bytes())
+                .unwrap();
+            assert!(connection.try_read().is_ok());
+            ParsedRequest::reads_vmm_state(&connection.pop_parsed_request().unwrap())
+        };
+
+        assert!(reads_vmm_state("GET", "/mmds", None));
+        assert!(reads_vmm_state("GET", "/machine-config", None));
+        assert!(reads_vmm_state("GET", "/vm/config", None));
+        assert!(reads_vmm_state("GET", "/balloon/statistics", None));
+        // The API server answers these by itself.
+        assert!(!reads_vmm_state("GET", "/", None));
+        assert!(!reads_vmm_state("GET", "/events", None));
+        assert!(!reads_vmm_state("GET", "/operations/1", None));
+        // Changes.
+        assert!(!reads_vmm_state("PUT", "/mmds", Some("{}")));
+        assert!(!reads_vmm_state(
+            "PATCH",
+            "/vm",
+            Some("{\"state\":\"Paused\"}")
+        ));
+    }
+
     #[test]
     fn test_try_from_get_mmds() {
         let (mut sender, receiver) = UnixStream::pair().unwrap();
@@ -583,6 +1010,16 @@ pub(crate) mod tests {
         assert!(connection.try_read().is_ok());
         let req = connection.pop_parsed_request().unwrap();
         assert!(ParsedRequest::try_from_request(&req).is_ok());
+
+        sender
+            .write_all(http_request("GET", "/mmds/interfaces/eth0", None).as_bytes())
+            .unwrap();
+        assert!(connection.try_read().is_ok());
+        let req = connection.pop_parsed_request().unwrap();
+        assert!(
+            ParsedRequest::try_from_request(&req).unwrap()
+                == ParsedRequest::new_sync(VmmAction::GetMmds(Some(String::from("eth0"))))
+        );
     }
 
     #[test]
@@ -837,6 +1274,22 @@ pub(crate) mod tests {
         assert!(ParsedRequest::try_from_request(&req).is_ok());
     }
 
+    #[test]
+    fn test_try_from_put_vm_config() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        let body = "{ \
+            \"boot-source\": { \"kernel_image_path\": \"string\" }, \
+            \"drives\": [] \
+        }";
+        sender
+            .write_all(http_request("PUT", "/vm/config?boot=true", Some(&body)).as_bytes())
+            .unwrap();
+        assert!(connection.try_read().is_ok());
+        let req = connection.pop_parsed_request().unwrap();
+        assert!(ParsedRequest::try_from_request(&req).is_ok());
+    }
+
     #[test]
     fn test_try_from_patch_balloon() {
         let (mut sender, receiver) = UnixStream::pair().unwrap();
@@ -933,4 +1386,174 @@ pub(crate) mod tests {
         let req = connection.pop_parsed_request().unwrap();
         assert!(ParsedRequest::try_from_request(&req).is_ok());
     }
+
+    #[test]
+    fn test_try_from_delete_netif() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request("DELETE", "/network-interfaces/string", None).as_bytes())
+            .unwrap();
+        assert!(connection.try_read().is_ok());
+        let req = connection.pop_parsed_request().unwrap();
+        assert!(ParsedRequest::try_from_request(&req).is_ok());
+
+        // DELETE requests carry no body.
+        sender
+            .write_all(http_request("DELETE", "/network-interfaces/string", Some("{}")).as_bytes())
+            .unwrap();
+        assert!(connection.try_read().is_ok());
+        let req = connection.pop_parsed_request().unwrap();
+        assert!(ParsedRequest::try_from_request(&req).is_err());
+    }
+
+    // Returns the `(method, path)` pairs documented in the swagger spec.
+    fn swagger_routes() -> Vec<(String, String)> {
+        let spec = include_str!("../swagger/firecracker.yaml");
+        let mut routes = Vec::new();
+        let mut path = None;
+        for line in spec.lines().filter(|line| !line.trim().is_empty()) {
+            // Paths are the keys of the top level `paths` object, and methods their keys.
+            if !line.starts_with(' ') {
+                path = None;
+            } else if line.starts_with("  /") && line.ends_with(':') {
+                path = Some(line.trim().trim_end_matches(':').to_string());
+            } else if let Some(path) = path.as_ref() {
+                let key = line.trim_end().trim_end_matches(':');
+                if ["    get", "    put", "    patch", "    delete"].contains(&key) {
+                    routes.push((key.trim().to_uppercase(), path.clone()));
+                }
+            }
+        }
+        routes
+    }
+
+    // Returns `true` if `try_from_request` routes the request, even if it then fails to parse
+    // its body.
+    fn is_routed(method: &str, path: &str) -> bool {
+        // Replace the path parameters with valid IDs.
+        let mut uri = path.replace("{operation_id}", "1");
+        while let (Some(start), Some(end)) = (uri.find('{'), uri.find('}')) {
+            uri.replace_range(start..=end, "id0");
+        }
+        let body = match method {
+            "PUT" | "PATCH" => Some("{}"),
+            _ => None,
+        };
+
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request(method, &uri, body).as_bytes())
+            .unwrap();
+        assert!(connection.try_read().is_ok());
+        let req = connection.pop_parsed_request().unwrap();
+        match ParsedRequest::try_from_request(&req) {
+            Err(Error::InvalidPathMethod(_, _)) | Err(Error::UnrecognizedPath(_, _)) => false,
+            _ => true,
+        }
+    }
+
+    #[test]
+    fn test_routes_match_swagger() {
+        let spec_routes = swagger_routes();
+        assert!(!spec_routes.is_empty());
+
+        for (method, path) in ROUTES {
+            let method = std::str::from_utf8(method.raw()).unwrap();
+            assert!(
+                spec_routes.contains(&(method.to_string(), path.to_string())),
+                "{} {} is not documented in the swagger spec.",
+                method,
+                path
+            );
+            assert!(
+                is_routed(method, path),
+                "{} {} is not served.",
+                method,
+                path
+            );
+        }
+        for (method, path) in spec_routes.iter() {
+            assert!(
+                is_routed(method, path),
+                "{} {} is documented in the swagger spec, but not served.",
+                method,
+                path
+            );
+        }
+        assert_eq!(spec_routes.len(), ROUTES.len());
+    }
+
+    #[test]
+    fn test_read_only_routes() {
+        // The read-only routes are a subset of the served ones.
+        for route in READ_ONLY_ROUTES {
+            assert!(ROUTES.contains(route));
+        }
+
+        for (method, uri, allowed) in [
+            ("GET", "/", true),
+            ("GET", "/balloon/statistics", true),
+            ("GET", "/events", true),
+            ("GET", "/network-interfaces/eth0/stats", true),
+            ("GET", "/network-interfaces/eth0", false),
+            ("GET", "/mmds", false),
+            ("GET", "/mmds?wait-for-change", false),
+            ("GET", "/vm/config", false),
+            ("PATCH", "/balloon/statistics", false),
+            ("PUT", "/actions", false),
+        ]
+        .iter()
+        {
+            let (mut sender, receiver) = UnixStream::pair().unwrap();
+            let mut connection = HttpConnection::new(receiver);
+            let body = match *method {
+                "PUT" | "PATCH" => Some("{}"),
+                _ => None,
+            };
+            sender
+                .write_all(http_request(method, uri, body).as_bytes())
+                .unwrap();
+            assert!(connection.try_read().is_ok());
+            let req = connection.pop_parsed_request().unwrap();
+            assert_eq!(
+                ParsedRequest::allowed_read_only(&req),
+                *allowed,
+                "{} {}",
+                method,
+                uri
+            );
+        }
+    }
+
+    #[test]
+    fn test_unknown_routes() {
+        for (method, uri) in [
+            ("GET", "/drives/id0/id1"),
+            ("GET", "/vm"),
+            ("GET", "/unknown"),
+            ("PUT", "/boot-source/id0"),
+            ("PATCH", "/boot-source"),
+            ("DELETE", "/network-interfaces"),
+        ]
+        .iter()
+        {
+            let (mut sender, receiver) = UnixStream::pair().unwrap();
+            let mut connection = HttpConnection::new(receiver);
+            let body = match *method {
+                "PUT" | "PATCH" => Some("{}"),
+                _ => None,
+            };
+            sender
+                .write_all(http_request(method, uri, body).as_bytes())
+                .unwrap();
+            assert!(connection.try_read().is_ok());
+            let req = connection.pop_parsed_request().unwrap();
+            match ParsedRequest::try_from_request(&req) {
+                Err(Error::InvalidPathMethod(path, _)) => assert_eq!(path, *uri),
+                _ => panic!("{} {} should not be served.", method, uri),
+            }
+        }
+    }
 }
@@ -3,9 +3,7 @@
 
 use super::super::VmmAction;
 use crate::parsed_request::{Error, ParsedRequest};
-use crate::request::Body;
-#[cfg(target_arch = "aarch64")]
-use crate::request::StatusCode;
+use crate::request::{Body, StatusCode};
 use logger::{IncMetric, METRICS};
 
 use serde::{Deserialize, Serialize};
@@ -18,14 +16,21 @@ enum ActionType {
     FlushMetrics,
     InstanceStart,
     SendCtrlAltDel,
+    Shutdown,
 }
 
+// How long the guest is given to power off when the `Shutdown` request does not say.
+#[cfg(target_arch = "x86_64")]
+const DEFAULT_SHUTDOWN_TIMEOUT_MS: u64 = 10_000;
+
 // The model of the json body from a sync request. We use Serde to transform each associated
 // json body into this.
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 struct ActionBody {
     action_type: ActionType,
+    #[serde(default)]
+    timeout_ms: Option<u64>,
 }
 
 pub(crate) fn parse_put_actions(body: &Body) -> Result<ParsedRequest, Error> {
@@ -35,6 +40,15 @@ pub(crate) fn parse_put_actions(body: &Body) -> Result<ParsedRequest, Error> {
         Error::SerdeJson(e)
     })?;
 
+    if action_body.timeout_ms.is_some() && !matches!(action_body.action_type, ActionType::Shutdown)
+    {
+        METRICS.put_api_requests.actions_fails.inc();
+        return Err(Error::Generic(
+            StatusCode::BadRequest,
+            "The timeout_ms field is only supported by the Shutdown action.".to_string(),
+        ));
+    }
+
     match action_body.action_type {
         ActionType::FlushMetrics => Ok(ParsedRequest::new_sync(VmmAction::FlushMetrics)),
         ActionType::InstanceStart => Ok(ParsedRequest::new_sync(VmmAction::StartMicroVm)),
@@ -49,6 +63,23 @@ pub(crate) fn parse_put_actions(body: &Body) -> Result<ParsedRequest, Error> {
             #[cfg(target_arch = "x86_64")]
             Ok(ParsedRequest::new_sync(VmmAction::SendCtrlAltDel))
         }
+        ActionType::Shutdown => {
+            // Asking an aarch64 guest to power off needs a power button device, which is not
+            // emulated yet.
+            #[cfg(target_arch = "aarch64")]
+            return Err(Error::Generic(
+                StatusCode::BadRequest,
+                "Shutdown is not supported on aarch64 yet, as there is no power button device."
+                    .to_string(),
+            ));
+
+            #[cfg(target_arch = "x86_64")]
+            Ok(ParsedRequest::new_sync(VmmAction::Shutdown(
+                action_body
+                    .timeout_ms
+                    .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT_MS),
+            )))
+        }
     }
 }
 
@@ -103,5 +134,52 @@ mod tests {
             assert!(result.is_ok());
             assert!(result.unwrap().eq(&req));
         }
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            let json = r#"{
+                "action_type": "Shutdown"
+            }"#;
+
+            let req: ParsedRequest =
+                ParsedRequest::new_sync(VmmAction::Shutdown(DEFAULT_SHUTDOWN_TIMEOUT_MS));
+            let result = parse_put_actions(&Body::new(json));
+            assert!(result.is_ok());
+            assert!(result.unwrap().eq(&req));
+        }
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            let json = r#"{
+                "action_type": "Shutdown",
+                "timeout_ms": 500
+            }"#;
+
+            let req: ParsedRequest = ParsedRequest::new_sync(VmmAction::Shutdown(500));
+            let result = parse_put_actions(&Body::new(json));
+            assert!(result.is_ok());
+            assert!(result.unwrap().eq(&req));
+        }
+
+        #[cfg(target_arch = "aarch64")]
+        {
+            let json = r#"{
+                "action_type": "Shutdown",
+                "timeout_ms": 500
+            }"#;
+
+            let result = parse_put_actions(&Body::new(json));
+            assert!(result.is_err());
+        }
+
+        {
+            let json = r#"{
+                "action_type": "FlushMetrics",
+                "timeout_ms": 500
+            }"#;
+
+            let result = parse_put_actions(&Body::new(json));
+            assert!(result.is_err());
+        }
     }
 }
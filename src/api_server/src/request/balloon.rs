@@ -4,7 +4,7 @@
 use super::super::VmmAction;
 use crate::parsed_request::{Error, ParsedRequest};
 use crate::request::Body;
-use micro_http::StatusCode;
+use micro_http::Method;
 use vmm::vmm_config::balloon::{
     BalloonDeviceConfig, BalloonUpdateConfig, BalloonUpdateStatsConfig,
 };
@@ -13,10 +13,7 @@ pub(crate) fn parse_get_balloon(path_second_token: Option<&&str>) -> Result<Pars
     match path_second_token {
         Some(stats_path) => match *stats_path {
             "statistics" => Ok(ParsedRequest::new_sync(VmmAction::GetBalloonStats)),
-            _ => Err(Error::Generic(
-                StatusCode::BadRequest,
-                format!("Unrecognized GET request path `{}`.", *stats_path),
-            )),
+            _ => Err(Error::UnrecognizedPath(Method::Get, stats_path.to_string())),
         },
         None => Ok(ParsedRequest::new_sync(VmmAction::GetBalloonConfig)),
     }
@@ -38,9 +35,9 @@ pub(crate) fn parse_patch_balloon(
                 serde_json::from_slice::<BalloonUpdateStatsConfig>(body.raw())
                     .map_err(Error::SerdeJson)?,
             ))),
-            _ => Err(Error::Generic(
-                StatusCode::BadRequest,
-                format!("Unrecognized PATCH request path `{}`.", *config_path),
+            _ => Err(Error::UnrecognizedPath(
+                Method::Patch,
+                config_path.to_string(),
             )),
         },
         None => Ok(ParsedRequest::new_sync(VmmAction::UpdateBalloon(
@@ -165,5 +162,43 @@ mod tests {
                 "stats_polling_interval_s": 0
             }"#;
         assert!(parse_put_balloon(&Body::new(body)).is_ok());
+
+        let body = r#"{
+                "amount_mb": 1000,
+                "deflate_on_oom": true,
+                "stats_polling_interval_s": 0,
+                "free_page_reporting": true
+            }"#;
+        assert!(parse_put_balloon(&Body::new(body)).is_ok());
+
+        let body = r#"{
+                "amount_mb": 0,
+                "deflate_on_oom": true,
+                "stats_polling_interval_s": 1,
+                "auto_size": {
+                    "min_mb": 0,
+                    "max_mb": 512,
+                    "step_mb": 32,
+                    "low_available_mb": 128,
+                    "high_available_mb": 256
+                }
+            }"#;
+        assert!(parse_put_balloon(&Body::new(body)).is_ok());
+
+        // Unknown fields in the automatic sizing policy are rejected.
+        let body = r#"{
+                "amount_mb": 0,
+                "deflate_on_oom": true,
+                "stats_polling_interval_s": 1,
+                "auto_size": {
+                    "min_mb": 0,
+                    "max_mb": 512,
+                    "step_mb": 32,
+                    "low_available_mb": 128,
+                    "high_available_mb": 256,
+                    "foo": 1
+                }
+            }"#;
+        assert!(parse_put_balloon(&Body::new(body)).is_err());
     }
 }
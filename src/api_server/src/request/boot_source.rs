@@ -5,8 +5,15 @@ use super::super::VmmAction;
 use crate::parsed_request::{Error, ParsedRequest};
 use crate::request::Body;
 use logger::{IncMetric, METRICS};
+use vmm::resources::ConfiguredResource;
 use vmm::vmm_config::boot_source::BootSourceConfig;
 
+pub(crate) fn parse_get_boot_source() -> Result<ParsedRequest, Error> {
+    Ok(ParsedRequest::new_sync(VmmAction::GetResourceConfig(
+        ConfiguredResource::BootSource,
+    )))
+}
+
 pub(crate) fn parse_put_boot_source(body: &Body) -> Result<ParsedRequest, Error> {
     METRICS.put_api_requests.boot_source_count.inc();
     Ok(ParsedRequest::new_sync(VmmAction::ConfigureBootSource(
@@ -20,6 +27,15 @@ pub(crate) fn parse_put_boot_source(body: &Body) -> Result<ParsedRequest, Error>
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::parsed_request::tests::vmm_action_from_request;
+
+    #[test]
+    fn test_parse_get_boot_source_request() {
+        assert_eq!(
+            vmm_action_from_request(parse_get_boot_source().unwrap()),
+            VmmAction::GetResourceConfig(ConfiguredResource::BootSource)
+        );
+    }
 
     #[test]
     fn test_parse_boot_request() {
@@ -5,8 +5,19 @@ use super::super::VmmAction;
 use crate::parsed_request::{checked_id, Error, ParsedRequest};
 use crate::request::{Body, StatusCode};
 use logger::{IncMetric, METRICS};
+use vmm::resources::ConfiguredResource;
 use vmm::vmm_config::drive::{BlockDeviceConfig, BlockDeviceUpdateConfig};
 
+pub(crate) fn parse_get_drive(id_from_path: Option<&&str>) -> Result<ParsedRequest, Error> {
+    let id = match id_from_path {
+        Some(id) => Some(checked_id(id)?.to_string()),
+        None => None,
+    };
+    Ok(ParsedRequest::new_sync(VmmAction::GetResourceConfig(
+        ConfiguredResource::Drives(id),
+    )))
+}
+
 pub(crate) fn parse_put_drive(
     body: &Body,
     id_from_path: Option<&&str>,
@@ -88,6 +99,19 @@ mod tests {
     use super::*;
     use crate::parsed_request::tests::vmm_action_from_request;
 
+    #[test]
+    fn test_parse_get_drive_request() {
+        assert_eq!(
+            vmm_action_from_request(parse_get_drive(None).unwrap()),
+            VmmAction::GetResourceConfig(ConfiguredResource::Drives(None))
+        );
+        assert_eq!(
+            vmm_action_from_request(parse_get_drive(Some(&"rootfs")).unwrap()),
+            VmmAction::GetResourceConfig(ConfiguredResource::Drives(Some("rootfs".to_string())))
+        );
+        assert!(parse_get_drive(Some(&"root-fs")).is_err());
+    }
+
     #[test]
     fn test_parse_patch_drive_request() {
         assert!(parse_patch_drive(&Body::new("invalid_payload"), None).is_err());
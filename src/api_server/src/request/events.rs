@@ -0,0 +1,21 @@
+// Copyright 2021 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::parsed_request::{Error, ParsedRequest};
+
+pub(crate) fn parse_get_events() -> Result<ParsedRequest, Error> {
+    Ok(ParsedRequest::GetEvents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_get_events_request() {
+        match parse_get_events() {
+            Ok(ParsedRequest::GetEvents) => {}
+            _ => panic!("Test failed."),
+        }
+    }
+}
@@ -5,8 +5,15 @@ use super::super::VmmAction;
 use crate::parsed_request::{Error, ParsedRequest};
 use crate::request::Body;
 use logger::{IncMetric, METRICS};
+use vmm::resources::ConfiguredResource;
 use vmm::vmm_config::logger::LoggerConfig;
 
+pub(crate) fn parse_get_logger() -> Result<ParsedRequest, Error> {
+    Ok(ParsedRequest::new_sync(VmmAction::GetResourceConfig(
+        ConfiguredResource::Logger,
+    )))
+}
+
 pub(crate) fn parse_put_logger(body: &Body) -> Result<ParsedRequest, Error> {
     METRICS.put_api_requests.logger_count.inc();
     Ok(ParsedRequest::new_sync(VmmAction::ConfigureLogger(
@@ -25,6 +32,14 @@ mod tests {
     use crate::parsed_request::tests::vmm_action_from_request;
     use vmm::vmm_config::logger::LoggerLevel;
 
+    #[test]
+    fn test_parse_get_logger_request() {
+        assert_eq!(
+            vmm_action_from_request(parse_get_logger().unwrap()),
+            VmmAction::GetResourceConfig(ConfiguredResource::Logger)
+        );
+    }
+
     #[test]
     fn test_parse_put_logger_request() {
         let mut body = r#"{
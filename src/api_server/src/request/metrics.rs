@@ -5,8 +5,15 @@ use super::super::VmmAction;
 use crate::parsed_request::{Error, ParsedRequest};
 use crate::request::Body;
 use logger::{IncMetric, METRICS};
+use vmm::resources::ConfiguredResource;
 use vmm::vmm_config::metrics::MetricsConfig;
 
+pub(crate) fn parse_get_metrics() -> Result<ParsedRequest, Error> {
+    Ok(ParsedRequest::new_sync(VmmAction::GetResourceConfig(
+        ConfiguredResource::Metrics,
+    )))
+}
+
 pub(crate) fn parse_put_metrics(body: &Body) -> Result<ParsedRequest, Error> {
     METRICS.put_api_requests.metrics_count.inc();
     Ok(ParsedRequest::new_sync(VmmAction::ConfigureMetrics(
@@ -24,6 +31,14 @@ mod tests {
     use super::*;
     use crate::parsed_request::tests::vmm_action_from_request;
 
+    #[test]
+    fn test_parse_get_metrics_request() {
+        assert_eq!(
+            vmm_action_from_request(parse_get_metrics().unwrap()),
+            VmmAction::GetResourceConfig(ConfiguredResource::Metrics)
+        );
+    }
+
     #[test]
     fn test_parse_put_metrics_request() {
         let body = r#"{
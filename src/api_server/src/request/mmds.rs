@@ -1,40 +1,62 @@
 // Copyright 2019 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::parsed_request::{Error, ParsedRequest};
+use crate::parsed_request::{checked_id, Error, ParsedRequest};
 use crate::request::Body;
-use micro_http::StatusCode;
-use vmm::rpc_interface::VmmAction::SetMmdsConfiguration;
+use micro_http::Method;
+use vmm::rpc_interface::VmmAction::{GetMmds, PatchMmds, PutMmds, SetMmdsConfiguration};
 use vmm::vmm_config::mmds::MmdsConfig;
 
-pub(crate) fn parse_get_mmds() -> Result<ParsedRequest, Error> {
-    Ok(ParsedRequest::GetMMDS)
+// Selects the MMDS data store targeted by a request: the shared one for `/mmds`, or the one
+// dedicated to a network interface for `/mmds/interfaces/{iface_id}`.
+fn parse_mmds_path(
+    method: Method,
+    path_second_token: Option<&&str>,
+    path_third_token: Option<&&str>,
+) -> Result<Option<String>, Error> {
+    match (path_second_token, path_third_token) {
+        (None, _) => Ok(None),
+        (Some(&"interfaces"), Some(iface_id)) => Ok(Some(checked_id(iface_id)?.to_string())),
+        (Some(&"interfaces"), None) => Err(Error::EmptyID),
+        (Some(path), _) => Err(Error::UnrecognizedPath(method, path.to_string())),
+    }
+}
+
+pub(crate) fn parse_get_mmds(
+    path_second_token: Option<&&str>,
+    path_third_token: Option<&&str>,
+) -> Result<ParsedRequest, Error> {
+    let iface_id = parse_mmds_path(Method::Get, path_second_token, path_third_token)?;
+    Ok(ParsedRequest::new_sync(GetMmds(iface_id)))
 }
 
 pub(crate) fn parse_put_mmds(
     body: &Body,
     path_second_token: Option<&&str>,
+    path_third_token: Option<&&str>,
 ) -> Result<ParsedRequest, Error> {
-    match path_second_token {
-        Some(config_path) => match *config_path {
-            "config" => Ok(ParsedRequest::new_sync(SetMmdsConfiguration(
-                serde_json::from_slice::<MmdsConfig>(body.raw()).map_err(Error::SerdeJson)?,
-            ))),
-            _ => Err(Error::Generic(
-                StatusCode::BadRequest,
-                format!("Unrecognized PUT request path `{}`.", *config_path),
-            )),
-        },
-        None => Ok(ParsedRequest::PutMMDS(
-            serde_json::from_slice(body.raw()).map_err(Error::SerdeJson)?,
-        )),
+    if path_second_token == Some(&"config") {
+        return Ok(ParsedRequest::new_sync(SetMmdsConfiguration(
+            serde_json::from_slice::<MmdsConfig>(body.raw()).map_err(Error::SerdeJson)?,
+        )));
     }
+    let iface_id = parse_mmds_path(Method::Put, path_second_token, path_third_token)?;
+    Ok(ParsedRequest::new_sync(PutMmds(
+        iface_id,
+        serde_json::from_slice(body.raw()).map_err(Error::SerdeJson)?,
+    )))
 }
 
-pub(crate) fn parse_patch_mmds(body: &Body) -> Result<ParsedRequest, Error> {
-    Ok(ParsedRequest::PatchMMDS(
+pub(crate) fn parse_patch_mmds(
+    body: &Body,
+    path_second_token: Option<&&str>,
+    path_third_token: Option<&&str>,
+) -> Result<ParsedRequest, Error> {
+    let iface_id = parse_mmds_path(Method::Patch, path_second_token, path_third_token)?;
+    Ok(ParsedRequest::new_sync(PatchMmds(
+        iface_id,
         serde_json::from_slice(body.raw()).map_err(Error::SerdeJson)?,
-    ))
+    )))
 }
 
 #[cfg(test)]
@@ -43,7 +65,14 @@ mod tests {
 
     #[test]
     fn test_parse_get_mmds_request() {
-        assert!(parse_get_mmds().is_ok());
+        assert!(parse_get_mmds(None, None).unwrap() == ParsedRequest::new_sync(GetMmds(None)));
+        assert!(
+            parse_get_mmds(Some(&"interfaces"), Some(&"eth0")).unwrap()
+                == ParsedRequest::new_sync(GetMmds(Some("eth0".to_string())))
+        );
+        assert!(parse_get_mmds(Some(&"interfaces"), None).is_err());
+        assert!(parse_get_mmds(Some(&"interfaces"), Some(&"eth-0")).is_err());
+        assert!(parse_get_mmds(Some(&"config"), None).is_err());
     }
 
     #[test]
@@ -51,31 +80,84 @@ mod tests {
         let body = r#"{
                 "foo": "bar"
               }"#;
-        assert!(parse_put_mmds(&Body::new(body), None).is_ok());
+        assert!(parse_put_mmds(&Body::new(body), None, None).is_ok());
         let invalid_body = "invalid_body";
-        assert!(parse_put_mmds(&Body::new(invalid_body), None).is_err());
+        assert!(parse_put_mmds(&Body::new(invalid_body), None, None).is_err());
+
+        let interfaces = "interfaces";
+        let iface_id = "eth0";
+        assert!(
+            parse_put_mmds(&Body::new(body), Some(&interfaces), Some(&iface_id)).unwrap()
+                == ParsedRequest::new_sync(PutMmds(
+                    Some(iface_id.to_string()),
+                    serde_json::json!({"foo": "bar"})
+                ))
+        );
+        assert!(parse_put_mmds(&Body::new(body), Some(&interfaces), None).is_err());
 
         let body = r#"{
                 "ipv4_address": "169.254.170.2"
               }"#;
         let path = "config";
-        assert!(parse_put_mmds(&Body::new(body), Some(&path)).is_ok());
+        assert!(parse_put_mmds(&Body::new(body), Some(&path), None).is_ok());
 
         let body = r#"{
                 "ipv4_address": ""
               }"#;
-        assert!(parse_put_mmds(&Body::new(body), Some(&path)).is_err());
+        assert!(parse_put_mmds(&Body::new(body), Some(&path), None).is_err());
+
+        let body = r#"{
+                "ipv4_address": "169.254.170.2",
+                "token_mode": "required"
+              }"#;
+        assert!(parse_put_mmds(&Body::new(body), Some(&path), None).is_ok());
+
+        let body = r#"{
+                "token_mode": "mandatory"
+              }"#;
+        assert!(parse_put_mmds(&Body::new(body), Some(&path), None).is_err());
+
+        let body = r#"{
+                "ipv4_address": "169.254.170.2",
+                "iface_id": "eth0"
+              }"#;
+        match parse_put_mmds(&Body::new(body), Some(&path), None) {
+            Ok(ParsedRequest::Sync(action)) => match *action {
+                SetMmdsConfiguration(config) => assert_eq!(config.iface_id(), Some("eth0")),
+                _ => panic!("Test failed."),
+            },
+            _ => panic!("Test failed."),
+        }
+
+        let ipv6_body = r#"{
+                "ipv6_address": "fe80::a9fe:a9fe",
+                "vsock_port": 52
+              }"#;
+        match parse_put_mmds(&Body::new(ipv6_body), Some(&path), None) {
+            Ok(ParsedRequest::Sync(action)) => match *action {
+                SetMmdsConfiguration(config) => {
+                    assert_eq!(config.ipv6_addr(), Some("fe80::a9fe:a9fe".parse().unwrap()));
+                    assert_eq!(config.vsock_port(), Some(52));
+                }
+                _ => panic!("Test failed."),
+            },
+            _ => panic!("Test failed."),
+        }
+        let ipv6_body = r#"{
+                "ipv6_address": "169.254.170.2"
+              }"#;
+        assert!(parse_put_mmds(&Body::new(ipv6_body), Some(&path), None).is_err());
 
         // Equivalent to reset the mmds configuration.
         let empty_body = r#"{}"#;
-        assert!(parse_put_mmds(&Body::new(empty_body), Some(&path)).is_ok());
+        assert!(parse_put_mmds(&Body::new(empty_body), Some(&path), None).is_ok());
 
         let invalid_config_body = r#"{
                 "invalid_config": "invalid_value"
               }"#;
-        assert!(parse_put_mmds(&Body::new(invalid_config_body), Some(&path)).is_err());
-        assert!(parse_put_mmds(&Body::new(body), Some(&"invalid_path")).is_err());
-        assert!(parse_put_mmds(&Body::new(invalid_body), Some(&path)).is_err());
+        assert!(parse_put_mmds(&Body::new(invalid_config_body), Some(&path), None).is_err());
+        assert!(parse_put_mmds(&Body::new(body), Some(&"invalid_path"), None).is_err());
+        assert!(parse_put_mmds(&Body::new(invalid_body), Some(&path), None).is_err());
     }
 
     #[test]
@@ -83,7 +165,9 @@ mod tests {
         let body = r#"{
                 "foo": "bar"
               }"#;
-        assert!(parse_patch_mmds(&Body::new(body)).is_ok());
-        assert!(parse_patch_mmds(&Body::new("invalid_body")).is_err());
+        assert!(parse_patch_mmds(&Body::new(body), None, None).is_ok());
+        assert!(parse_patch_mmds(&Body::new(body), Some(&"interfaces"), Some(&"eth0")).is_ok());
+        assert!(parse_patch_mmds(&Body::new(body), Some(&"config"), None).is_err());
+        assert!(parse_patch_mmds(&Body::new("invalid_body"), None, None).is_err());
     }
 }
@@ -5,13 +5,16 @@ pub mod actions;
 pub mod balloon;
 pub mod boot_source;
 pub mod drive;
+pub mod events;
 pub mod instance_info;
 pub mod logger;
 pub mod machine_configuration;
 pub mod metrics;
 pub mod mmds;
 pub mod net;
+pub mod operation;
 pub mod snapshot;
+pub mod vm_config;
 pub mod vsock;
 pub use micro_http::{
     Body, HttpServer, Method, Request, RequestError, Response, StatusCode, Version,
@@ -3,10 +3,42 @@
 
 use super::super::VmmAction;
 use crate::parsed_request::{checked_id, Error, ParsedRequest};
-use crate::request::{Body, StatusCode};
+use crate::request::{Body, Method, StatusCode};
 use logger::{IncMetric, METRICS};
+use vmm::resources::ConfiguredResource;
 use vmm::vmm_config::net::{NetworkInterfaceConfig, NetworkInterfaceUpdateConfig};
 
+pub(crate) fn parse_get_net(
+    id_from_path: Option<&&str>,
+    path_third_token: Option<&&str>,
+) -> Result<ParsedRequest, Error> {
+    METRICS.get_api_requests.network_count.inc();
+    let id = match id_from_path {
+        Some(id) => checked_id(id)?.to_string(),
+        None => {
+            return Ok(ParsedRequest::new_sync(VmmAction::GetResourceConfig(
+                ConfiguredResource::NetworkInterfaces(None),
+            )))
+        }
+    };
+
+    match path_third_token {
+        None => Ok(ParsedRequest::new_sync(VmmAction::GetResourceConfig(
+            ConfiguredResource::NetworkInterfaces(Some(id)),
+        ))),
+        Some(&"stats") => Ok(ParsedRequest::new_sync(
+            VmmAction::GetNetworkInterfaceStats(id),
+        )),
+        Some(unknown_path) => {
+            METRICS.get_api_requests.network_fails.inc();
+            Err(Error::UnrecognizedPath(
+                Method::Get,
+                unknown_path.to_string(),
+            ))
+        }
+    }
+}
+
 pub(crate) fn parse_put_net(
     body: &Body,
     id_from_path: Option<&&str>,
@@ -64,11 +96,47 @@ pub(crate) fn parse_patch_net(
     )))
 }
 
+pub(crate) fn parse_delete_net(id_from_path: Option<&&str>) -> Result<ParsedRequest, Error> {
+    METRICS.delete_api_requests.network_count.inc();
+    let id = if let Some(id) = id_from_path {
+        checked_id(id)?
+    } else {
+        METRICS.delete_api_requests.network_fails.inc();
+        return Err(Error::EmptyID);
+    };
+
+    Ok(ParsedRequest::new_sync(VmmAction::RemoveNetworkDevice(
+        id.to_string(),
+    )))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::parsed_request::tests::vmm_action_from_request;
 
+    #[test]
+    fn test_parse_get_net_request() {
+        // The `id_from_path` cannot be invalid.
+        assert!(parse_get_net(Some(&"foo-bar"), None).is_err());
+
+        assert_eq!(
+            vmm_action_from_request(parse_get_net(None, None).unwrap()),
+            VmmAction::GetResourceConfig(ConfiguredResource::NetworkInterfaces(None))
+        );
+        assert_eq!(
+            vmm_action_from_request(parse_get_net(Some(&"foo"), None).unwrap()),
+            VmmAction::GetResourceConfig(ConfiguredResource::NetworkInterfaces(Some(
+                "foo".to_string()
+            )))
+        );
+        match vmm_action_from_request(parse_get_net(Some(&"foo"), Some(&"stats")).unwrap()) {
+            VmmAction::GetNetworkInterfaceStats(id) => assert_eq!(id, "foo"),
+            _ => panic!("Test failed."),
+        }
+        assert!(parse_get_net(Some(&"foo"), Some(&"bar")).is_err());
+    }
+
     #[test]
     fn test_parse_put_net_request() {
         let body = r#"{
@@ -149,5 +217,39 @@ mod tests {
             }
         }"#;
         assert!(parse_patch_net(&Body::new(body), Some(&"foo")).is_err());
+
+        // 5. Re-pointing the interface to another tap.
+        let body = r#"{
+                "iface_id": "foo",
+                "host_dev_name": "bar"
+        }"#;
+        match vmm_action_from_request(parse_patch_net(&Body::new(body), Some(&"foo")).unwrap()) {
+            VmmAction::UpdateNetworkInterface(netif) => {
+                assert_eq!(netif.host_dev_name, Some("bar".to_string()))
+            }
+            _ => panic!("Test failed."),
+        }
+
+        // 6. Toggling the link state.
+        let body = r#"{
+                "iface_id": "foo",
+                "link_up": false
+        }"#;
+        match vmm_action_from_request(parse_patch_net(&Body::new(body), Some(&"foo")).unwrap()) {
+            VmmAction::UpdateNetworkInterface(netif) => assert_eq!(netif.link_up, Some(false)),
+            _ => panic!("Test failed."),
+        }
+    }
+
+    #[test]
+    fn test_parse_delete_net_request() {
+        // The `id_from_path` cannot be None or invalid.
+        assert!(parse_delete_net(None).is_err());
+        assert!(parse_delete_net(Some(&"foo-bar")).is_err());
+
+        match vmm_action_from_request(parse_delete_net(Some(&"foo")).unwrap()) {
+            VmmAction::RemoveNetworkDevice(id) => assert_eq!(id, "foo"),
+            _ => panic!("Test failed."),
+        }
     }
 }
@@ -0,0 +1,39 @@
+// Copyright 2021 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::parsed_request::{Error, ParsedRequest};
+use crate::request::StatusCode;
+
+pub(crate) fn parse_get_operation(id_from_path: Option<&&str>) -> Result<ParsedRequest, Error> {
+    match id_from_path {
+        Some(id) => id
+            .parse::<u64>()
+            .map(|id| ParsedRequest::GetOperation(Some(id)))
+            .map_err(|_| {
+                Error::Generic(
+                    StatusCode::BadRequest,
+                    format!("Invalid operation ID `{}`.", id),
+                )
+            }),
+        None => Ok(ParsedRequest::GetOperation(None)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_get_operation_request() {
+        match parse_get_operation(None) {
+            Ok(ParsedRequest::GetOperation(None)) => (),
+            _ => panic!("Test failed."),
+        }
+        match parse_get_operation(Some(&"12")) {
+            Ok(ParsedRequest::GetOperation(Some(12))) => (),
+            _ => panic!("Test failed."),
+        }
+        assert!(parse_get_operation(Some(&"foo")).is_err());
+        assert!(parse_get_operation(Some(&"-1")).is_err());
+    }
+}
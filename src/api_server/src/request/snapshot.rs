@@ -47,6 +47,7 @@ pub(crate) fn parse_patch_vm_state(body: &Body) -> Result<ParsedRequest, Error>
 mod tests {
     use super::*;
     use crate::parsed_request::tests::vmm_action_from_request;
+    use vmm::vmm_config::snapshot::VsockReattachConfig;
 
     #[test]
     fn test_parse_put_snapshot() {
@@ -65,6 +66,7 @@ mod tests {
             snapshot_path: PathBuf::from("foo"),
             mem_file_path: PathBuf::from("bar"),
             version: Some(String::from("0.23.0")),
+            progress: Default::default(),
         };
 
         match vmm_action_from_request(
@@ -84,6 +86,7 @@ mod tests {
             snapshot_path: PathBuf::from("foo"),
             mem_file_path: PathBuf::from("bar"),
             version: None,
+            progress: Default::default(),
         };
 
         match vmm_action_from_request(
@@ -110,6 +113,8 @@ mod tests {
             mem_file_path: PathBuf::from("bar"),
             enable_diff_snapshots: false,
             resume_vm: false,
+            vsock_reattach: Vec::new(),
+            discard_mmds_data: false,
         };
         match vmm_action_from_request(parse_put_snapshot(&Body::new(body), Some(&"load")).unwrap())
         {
@@ -128,6 +133,8 @@ mod tests {
             mem_file_path: PathBuf::from("bar"),
             enable_diff_snapshots: true,
             resume_vm: false,
+            vsock_reattach: Vec::new(),
+            discard_mmds_data: false,
         };
 
         match vmm_action_from_request(parse_put_snapshot(&Body::new(body), Some(&"load")).unwrap())
@@ -147,6 +154,8 @@ mod tests {
             mem_file_path: PathBuf::from("bar"),
             enable_diff_snapshots: false,
             resume_vm: true,
+            vsock_reattach: Vec::new(),
+            discard_mmds_data: false,
         };
 
         match vmm_action_from_request(parse_put_snapshot(&Body::new(body), Some(&"load")).unwrap())
@@ -155,6 +164,65 @@ mod tests {
             _ => panic!("Test failed."),
         }
 
+        body = r#"{
+                "snapshot_path": "foo",
+                "mem_file_path": "bar",
+                "vsock_reattach": [
+                    { "vsock_id": "vsock0", "host_port": 1024, "guest_port": 52, "uds_path": "baz" },
+                    { "host_port": 1025, "uds_path": "qux" }
+                ]
+              }"#;
+
+        expected_cfg = LoadSnapshotParams {
+            snapshot_path: PathBuf::from("foo"),
+            mem_file_path: PathBuf::from("bar"),
+            enable_diff_snapshots: false,
+            resume_vm: false,
+            vsock_reattach: vec![
+                VsockReattachConfig {
+                    vsock_id: Some("vsock0".to_string()),
+                    host_port: 1024,
+                    guest_port: Some(52),
+                    uds_path: "baz".to_string(),
+                },
+                VsockReattachConfig {
+                    vsock_id: None,
+                    host_port: 1025,
+                    guest_port: None,
+                    uds_path: "qux".to_string(),
+                },
+            ],
+            discard_mmds_data: false,
+        };
+
+        match vmm_action_from_request(parse_put_snapshot(&Body::new(body), Some(&"load")).unwrap())
+        {
+            VmmAction::LoadSnapshot(cfg) => assert_eq!(cfg, expected_cfg),
+            _ => panic!("Test failed."),
+        }
+
+        let body_discard_mmds = r#"{
+                "snapshot_path": "foo",
+                "mem_file_path": "bar",
+                "discard_mmds_data": true
+              }"#;
+
+        expected_cfg = LoadSnapshotParams {
+            snapshot_path: PathBuf::from("foo"),
+            mem_file_path: PathBuf::from("bar"),
+            enable_diff_snapshots: false,
+            resume_vm: false,
+            vsock_reattach: Vec::new(),
+            discard_mmds_data: true,
+        };
+
+        match vmm_action_from_request(
+            parse_put_snapshot(&Body::new(body_discard_mmds), Some(&"load")).unwrap(),
+        ) {
+            VmmAction::LoadSnapshot(cfg) => assert_eq!(cfg, expected_cfg),
+            _ => panic!("Test failed."),
+        }
+
         assert!(parse_put_snapshot(&Body::new(body), Some(&"invalid")).is_err());
         assert!(parse_put_snapshot(&Body::new(body), None).is_err());
     }
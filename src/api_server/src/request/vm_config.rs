@@ -0,0 +1,105 @@
+// Copyright 2021 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use super::super::VmmAction;
+use crate::parsed_request::{Error, ParsedRequest};
+use crate::request::{Body, Method, StatusCode};
+use vmm::resources::VmmConfig;
+
+pub(crate) fn parse_get_vm_config(
+    path_second_token: Option<&&str>,
+) -> Result<ParsedRequest, Error> {
+    match path_second_token {
+        Some(&"config") => Ok(ParsedRequest::new_sync(VmmAction::GetFullVmConfig)),
+        Some(unknown_path) => Err(Error::UnrecognizedPath(
+            Method::Get,
+            unknown_path.to_string(),
+        )),
+        None => Err(Error::Generic(
+            StatusCode::BadRequest,
+            "Missing VM resource in GET request path.".to_string(),
+        )),
+    }
+}
+
+pub(crate) fn parse_put_vm_config(
+    body: &Body,
+    path_second_token: Option<&&str>,
+) -> Result<ParsedRequest, Error> {
+    // The microVM is booted after applying the configuration if asked to with `?boot=true`.
+    let mut path_and_query = path_second_token.unwrap_or(&"").splitn(2, '?');
+    let path = path_and_query.next().unwrap_or("");
+    let boot = match path_and_query.next() {
+        None | Some("boot=false") => false,
+        Some("boot=true") => true,
+        Some(query) => {
+            return Err(Error::Generic(
+                StatusCode::BadRequest,
+                format!("Unrecognized PUT request query `{}`.", query),
+            ))
+        }
+    };
+
+    match path {
+        "config" => Ok(ParsedRequest::new_sync(VmmAction::SetFullVmConfig(
+            serde_json::from_slice::<VmmConfig>(body.raw()).map_err(Error::SerdeJson)?,
+            boot,
+        ))),
+        "" => Err(Error::Generic(
+            StatusCode::BadRequest,
+            "Missing VM resource in PUT request path.".to_string(),
+        )),
+        unknown_path => Err(Error::UnrecognizedPath(
+            Method::Put,
+            unknown_path.to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsed_request::tests::vmm_action_from_request;
+
+    #[test]
+    fn test_parse_get_vm_config_request() {
+        assert_eq!(
+            vmm_action_from_request(parse_get_vm_config(Some(&"config")).unwrap()),
+            VmmAction::GetFullVmConfig
+        );
+        assert!(parse_get_vm_config(Some(&"foo")).is_err());
+        assert!(parse_get_vm_config(None).is_err());
+    }
+
+    #[test]
+    fn test_parse_put_vm_config_request() {
+        let body = r#"{
+                "boot-source": {
+                    "kernel_image_path": "/foo/bar"
+                },
+                "drives": []
+            }"#;
+        let vmm_config = serde_json::from_str::<VmmConfig>(body).unwrap();
+        assert_eq!(
+            vmm_action_from_request(
+                parse_put_vm_config(&Body::new(body), Some(&"config")).unwrap()
+            ),
+            VmmAction::SetFullVmConfig(vmm_config, false)
+        );
+
+        let body = r#"{ "drives": [] }"#;
+        let vmm_config = serde_json::from_str::<VmmConfig>(body).unwrap();
+        assert_eq!(
+            vmm_action_from_request(
+                parse_put_vm_config(&Body::new(body), Some(&"config?boot=true")).unwrap()
+            ),
+            VmmAction::SetFullVmConfig(vmm_config, true)
+        );
+
+        assert!(parse_put_vm_config(&Body::new(body), Some(&"config?boot=1")).is_err());
+        assert!(parse_put_vm_config(&Body::new(body), Some(&"foo")).is_err());
+        assert!(parse_put_vm_config(&Body::new(body), None).is_err());
+        // The drives are mandatory.
+        assert!(parse_put_vm_config(&Body::new("{}"), Some(&"config")).is_err());
+    }
+}
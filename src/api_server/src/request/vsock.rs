@@ -2,10 +2,21 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::super::VmmAction;
-use crate::parsed_request::{Error, ParsedRequest};
+use crate::parsed_request::{checked_id, Error, ParsedRequest};
 use crate::request::Body;
+use vmm::resources::ConfiguredResource;
 use vmm::vmm_config::vsock::VsockDeviceConfig;
 
+pub(crate) fn parse_get_vsock(id_from_path: Option<&&str>) -> Result<ParsedRequest, Error> {
+    let id = match id_from_path {
+        Some(id) => Some(checked_id(id)?.to_string()),
+        None => None,
+    };
+    Ok(ParsedRequest::new_sync(VmmAction::GetResourceConfig(
+        ConfiguredResource::Vsock(id),
+    )))
+}
+
 pub(crate) fn parse_put_vsock(body: &Body) -> Result<ParsedRequest, Error> {
     Ok(ParsedRequest::new_sync(VmmAction::SetVsockDevice(
         serde_json::from_slice::<VsockDeviceConfig>(body.raw()).map_err(Error::SerdeJson)?,
@@ -15,6 +26,20 @@ pub(crate) fn parse_put_vsock(body: &Body) -> Result<ParsedRequest, Error> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::parsed_request::tests::vmm_action_from_request;
+
+    #[test]
+    fn test_parse_get_vsock_request() {
+        assert_eq!(
+            vmm_action_from_request(parse_get_vsock(None).unwrap()),
+            VmmAction::GetResourceConfig(ConfiguredResource::Vsock(None))
+        );
+        assert_eq!(
+            vmm_action_from_request(parse_get_vsock(Some(&"foo")).unwrap()),
+            VmmAction::GetResourceConfig(ConfiguredResource::Vsock(Some("foo".to_string())))
+        );
+        assert!(parse_get_vsock(Some(&"foo-bar")).is_err());
+    }
 
     #[test]
     fn test_parse_put_vsock_request() {
@@ -25,6 +50,17 @@ mod tests {
               }"#;
         assert!(parse_put_vsock(&Body::new(body)).is_ok());
 
+        let body = r#"{
+                "vsock_id": "foo",
+                "guest_cid": 42,
+                "uds_path": "vsock.sock",
+                "buf_alloc": 131072,
+                "max_connections": 16,
+                "conn_request_timeout_ms": 500,
+                "conn_shutdown_timeout_ms": 1000
+              }"#;
+        assert!(parse_put_vsock(&Body::new(body)).is_ok());
+
         let body = r#"{
                 "vsock_id": "foo",
                 "guest_cid": 42,
@@ -12,7 +12,7 @@ use std::collections::btree_map::BTreeMap;
 use std::fmt;
 use std::io;
 use std::result;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLock};
 
 use crate::virtio::AsAny;
 
@@ -73,26 +73,34 @@ impl PartialOrd for BusRange {
     }
 }
 
+type DeviceMap = BTreeMap<BusRange, Arc<Mutex<dyn BusDevice>>>;
+
 /// A device container for routing reads and writes over some address space.
 ///
 /// This doesn't have any restrictions on what kind of device or address space this applies to. The
 /// only restriction is that no two devices can overlap in this address space.
+///
+/// Clones of a `Bus` share the same address space, so devices inserted or removed through one
+/// clone (e.g. at runtime, from the API thread) are seen by all the others (e.g. the vCPU threads).
 #[derive(Clone, Default)]
 pub struct Bus {
-    devices: BTreeMap<BusRange, Arc<Mutex<dyn BusDevice>>>,
+    devices: Arc<RwLock<DeviceMap>>,
 }
 
 impl Bus {
     /// Constructs an a bus with an empty address space.
     pub fn new() -> Bus {
         Bus {
-            devices: BTreeMap::new(),
+            devices: Arc::new(RwLock::new(BTreeMap::new())),
         }
     }
 
-    fn first_before(&self, addr: u64) -> Option<(BusRange, &Mutex<dyn BusDevice>)> {
+    fn first_before(
+        devices: &DeviceMap,
+        addr: u64,
+    ) -> Option<(BusRange, &Arc<Mutex<dyn BusDevice>>)> {
         // for when we switch to rustc 1.17: self.devices.range(..addr).iter().rev().next()
-        for (range, dev) in self.devices.iter().rev() {
+        for (range, dev) in devices.iter().rev() {
             if range.0 <= addr {
                 return Some((*range, dev));
             }
@@ -100,8 +108,8 @@ impl Bus {
         None
     }
 
-    pub fn get_device(&self, addr: u64) -> Option<(u64, &Mutex<dyn BusDevice>)> {
-        if let Some((BusRange(start, len), dev)) = self.first_before(addr) {
+    fn device_at(devices: &DeviceMap, addr: u64) -> Option<(u64, &Arc<Mutex<dyn BusDevice>>)> {
+        if let Some((BusRange(start, len), dev)) = Self::first_before(devices, addr) {
             let offset = addr - start;
             if offset < len {
                 return Some((offset, dev));
@@ -110,14 +118,23 @@ impl Bus {
         None
     }
 
+    pub fn get_device(&self, addr: u64) -> Option<(u64, Arc<Mutex<dyn BusDevice>>)> {
+        // The bus lock is released before returning, so that accessing the device doesn't
+        // block concurrent bus updates.
+        let devices = self.devices.read().expect("Poisoned lock");
+        Self::device_at(&devices, addr).map(|(offset, dev)| (offset, dev.clone()))
+    }
+
     /// Puts the given device at the given address space.
     pub fn insert(&mut self, device: Arc<Mutex<dyn BusDevice>>, base: u64, len: u64) -> Result<()> {
         if len == 0 {
             return Err(Error::Overlap);
         }
 
+        let mut devices = self.devices.write().expect("Poisoned lock");
+
         // Reject all cases where the new device's base is within an old device's range.
-        if self.get_device(base).is_some() {
+        if Self::device_at(&devices, base).is_some() {
             return Err(Error::Overlap);
         }
 
@@ -125,7 +142,7 @@ impl Bus {
         // range of another device. To catch that case, we search for a device with a range before
         // the new device's range's end. If there is no existing device in that range that starts
         // after the new device, then there will be no overlap.
-        if let Some((BusRange(start, _), _)) = self.first_before(base + len - 1) {
+        if let Some((BusRange(start, _), _)) = Self::first_before(&devices, base + len - 1) {
             // Such a device only conflicts with the new device if it also starts after the new
             // device because of our initial `get_device` check above.
             if start >= base {
@@ -133,13 +150,21 @@ impl Bus {
             }
         }
 
-        if self.devices.insert(BusRange(base, len), device).is_some() {
+        if devices.insert(BusRange(base, len), device).is_some() {
             return Err(Error::Overlap);
         }
 
         Ok(())
     }
 
+    /// Removes the device placed at `base`, returning it if there was one.
+    pub fn remove(&mut self, base: u64) -> Option<Arc<Mutex<dyn BusDevice>>> {
+        self.devices
+            .write()
+            .expect("Poisoned lock")
+            .remove(&BusRange(base, 0))
+    }
+
     /// Reads data from the device that owns the range containing `addr` and puts it into `data`.
     ///
     /// Returns true on success, otherwise `data` is untouched.
@@ -253,6 +278,28 @@ mod tests {
         assert!(bus.write(0x15, &values));
     }
 
+    #[test]
+    fn bus_remove() {
+        let mut bus = Bus::new();
+        let bus_clone = bus.clone();
+        let dummy = Arc::new(Mutex::new(DummyDevice));
+        assert!(bus.remove(0x10).is_none());
+
+        // Devices inserted through one handle are visible through its clones.
+        assert!(bus.insert(dummy.clone(), 0x10, 0x10).is_ok());
+        assert!(bus_clone.read(0x10, &mut [0, 0, 0, 0]));
+
+        // Only the device base address identifies it.
+        assert!(bus.remove(0x11).is_none());
+        assert!(bus.remove(0x10).is_some());
+        assert!(!bus.read(0x10, &mut [0, 0, 0, 0]));
+        assert!(!bus_clone.read(0x10, &mut [0, 0, 0, 0]));
+
+        // The freed range can be reused.
+        assert!(bus.insert(dummy, 0x10, 0x10).is_ok());
+        assert!(bus_clone.write(0x10, &[0, 0, 0, 0]));
+    }
+
     #[test]
     fn busrange_cmp_and_clone() {
         assert_eq!(BusRange(0x10, 2), BusRange(0x10, 3));
@@ -0,0 +1,316 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Automatic balloon sizing, driven by the statistics periodically reported by the guest.
+//!
+//! On every statistics period, the available memory reported by the driver is compared
+//! against two watermarks. The balloon is deflated when the available memory drops below
+//! the low watermark, or when the guest started swapping since the previous period, and is
+//! inflated when the available memory exceeds the high watermark. The gap between the two
+//! watermarks provides the hysteresis that keeps the balloon from oscillating.
+
+use std::cmp::min;
+
+use serde::{Deserialize, Serialize};
+
+use super::device::BalloonStats;
+use super::Error as BalloonError;
+
+/// Bounds and thresholds of the automatic balloon sizing policy.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct AutoSizeConfig {
+    /// The balloon is never deflated below this size, in MB.
+    pub min_mb: u32,
+    /// The balloon is never inflated above this size, in MB.
+    pub max_mb: u32,
+    /// The amount by which the balloon is resized in a single statistics period, in MB.
+    pub step_mb: u32,
+    /// The balloon is deflated when the guest available memory drops below this value, in MB.
+    pub low_available_mb: u32,
+    /// The balloon is inflated when the guest available memory exceeds this value, in MB.
+    pub high_available_mb: u32,
+}
+
+impl AutoSizeConfig {
+    /// Checks that the bounds and the watermarks are consistent.
+    pub fn validate(&self) -> Result<(), BalloonError> {
+        if self.step_mb == 0
+            || self.min_mb > self.max_mb
+            || self.low_available_mb >= self.high_available_mb
+        {
+            return Err(BalloonError::InvalidAutoSizeConfig);
+        }
+        Ok(())
+    }
+}
+
+/// The resizing action taken by the policy.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoSizeAction {
+    Hold,
+    Inflate,
+    Deflate,
+}
+
+/// The reason behind the latest policy decision.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoSizeReason {
+    /// The driver did not report the available (or free) memory.
+    NoStats,
+    /// The available memory is between the two watermarks.
+    WithinWatermarks,
+    /// The available memory is above the high watermark.
+    HighMemory,
+    /// The available memory is below the low watermark.
+    LowMemory,
+    /// The guest swapped pages in or out since the previous period.
+    SwapActivity,
+    /// A resize was needed, but the balloon already reached its bounds.
+    AtLimit,
+}
+
+/// The latest decision of the policy, as reported through the statistics endpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct AutoSizeDecision {
+    pub action: AutoSizeAction,
+    pub reason: AutoSizeReason,
+    /// The balloon target size before the decision, in MB.
+    pub previous_mb: u32,
+    /// The balloon target size after the decision, in MB.
+    pub target_mb: u32,
+}
+
+/// State of the automatic balloon sizing policy.
+#[derive(Clone, Debug)]
+pub(crate) struct AutoSizePolicy {
+    pub(crate) config: AutoSizeConfig,
+    // Swap counters from the previous sample, used to detect swap activity.
+    pub(crate) last_swap: Option<u64>,
+    // Whether the driver reported new statistics since the previous decision.
+    pub(crate) fresh_stats: bool,
+    pub(crate) last_decision: Option<AutoSizeDecision>,
+}
+
+impl AutoSizePolicy {
+    pub(crate) fn new(config: AutoSizeConfig) -> Self {
+        AutoSizePolicy {
+            config,
+            last_swap: None,
+            fresh_stats: false,
+            last_decision: None,
+        }
+    }
+
+    /// Computes the new balloon target size, in MB. Returns `None` when the driver did not
+    /// report any statistics since the previous decision.
+    ///
+    /// `current_mb` is the current target size and `limit_mb` is the guest memory size,
+    /// which the balloon can never exceed.
+    pub(crate) fn decide(
+        &mut self,
+        stats: &BalloonStats,
+        current_mb: u32,
+        limit_mb: u32,
+    ) -> Option<AutoSizeDecision> {
+        if !self.fresh_stats {
+            return None;
+        }
+        self.fresh_stats = false;
+
+        let swap = match (stats.swap_in, stats.swap_out) {
+            (None, None) => None,
+            (swap_in, swap_out) => Some(swap_in.unwrap_or(0) + swap_out.unwrap_or(0)),
+        };
+        let swapping = match (self.last_swap, swap) {
+            (Some(last), Some(swap)) => swap > last,
+            _ => false,
+        };
+        self.last_swap = swap;
+
+        let (action, reason, target_mb) = match stats
+            .available_memory
+            .or(stats.free_memory)
+            .map(|b| b >> 20)
+        {
+            None => (AutoSizeAction::Hold, AutoSizeReason::NoStats, current_mb),
+            Some(available_mb) if swapping || available_mb < self.low_available_mb() => {
+                let reason = if swapping {
+                    AutoSizeReason::SwapActivity
+                } else {
+                    AutoSizeReason::LowMemory
+                };
+                let floor = min(self.config.min_mb, current_mb);
+                let target_mb = current_mb.saturating_sub(self.config.step_mb).max(floor);
+                (AutoSizeAction::Deflate, reason, target_mb)
+            }
+            Some(available_mb) if available_mb > self.high_available_mb() => {
+                // Never take more than the excess over the high watermark, so that the
+                // next sample does not immediately fall below it.
+                let excess_mb = available_mb - self.high_available_mb();
+                let step_mb = min(u64::from(self.config.step_mb), excess_mb) as u32;
+                let ceiling = min(self.config.max_mb, limit_mb).max(current_mb);
+                let target_mb = current_mb.saturating_add(step_mb).min(ceiling);
+                (
+                    AutoSizeAction::Inflate,
+                    AutoSizeReason::HighMemory,
+                    target_mb,
+                )
+            }
+            Some(_) => (
+                AutoSizeAction::Hold,
+                AutoSizeReason::WithinWatermarks,
+                current_mb,
+            ),
+        };
+
+        let decision = if action != AutoSizeAction::Hold && target_mb == current_mb {
+            AutoSizeDecision {
+                action: AutoSizeAction::Hold,
+                reason: AutoSizeReason::AtLimit,
+                previous_mb: current_mb,
+                target_mb,
+            }
+        } else {
+            AutoSizeDecision {
+                action,
+                reason,
+                previous_mb: current_mb,
+                target_mb,
+            }
+        };
+        self.last_decision = Some(decision);
+        Some(decision)
+    }
+
+    fn low_available_mb(&self) -> u64 {
+        u64::from(self.config.low_available_mb)
+    }
+
+    fn high_available_mb(&self) -> u64 {
+        u64::from(self.config.high_available_mb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MB: u64 = 1 << 20;
+
+    fn policy() -> AutoSizePolicy {
+        AutoSizePolicy::new(AutoSizeConfig {
+            min_mb: 16,
+            max_mb: 64,
+            step_mb: 8,
+            low_available_mb: 32,
+            high_available_mb: 64,
+        })
+    }
+
+    fn stats(available_mb: u64, swap_in: u64) -> BalloonStats {
+        BalloonStats {
+            available_memory: Some(available_mb * MB),
+            swap_in: Some(swap_in),
+            ..Default::default()
+        }
+    }
+
+    fn decide(
+        policy: &mut AutoSizePolicy,
+        stats: &BalloonStats,
+        current_mb: u32,
+    ) -> AutoSizeDecision {
+        policy.fresh_stats = true;
+        policy.decide(stats, current_mb, 128).unwrap()
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = policy().config;
+        assert!(config.validate().is_ok());
+
+        let mut bad = config;
+        bad.step_mb = 0;
+        assert!(bad.validate().is_err());
+
+        let mut bad = config;
+        bad.min_mb = bad.max_mb + 1;
+        assert!(bad.validate().is_err());
+
+        let mut bad = config;
+        bad.low_available_mb = bad.high_available_mb;
+        assert!(bad.validate().is_err());
+    }
+
+    #[test]
+    fn test_stale_stats() {
+        let mut policy = policy();
+        assert!(policy.decide(&stats(100, 0), 16, 128).is_none());
+        assert!(decide(&mut policy, &stats(100, 0), 16).target_mb > 16);
+        // The same sample is never used twice.
+        assert!(policy.decide(&stats(100, 0), 24, 128).is_none());
+    }
+
+    #[test]
+    fn test_decisions() {
+        let mut policy = policy();
+
+        // No memory statistics.
+        let d = decide(&mut policy, &BalloonStats::default(), 20);
+        assert_eq!(d.action, AutoSizeAction::Hold);
+        assert_eq!(d.reason, AutoSizeReason::NoStats);
+
+        // Between the watermarks.
+        let d = decide(&mut policy, &stats(48, 0), 20);
+        assert_eq!(d.action, AutoSizeAction::Hold);
+        assert_eq!(d.reason, AutoSizeReason::WithinWatermarks);
+        assert_eq!(d.target_mb, 20);
+
+        // Above the high watermark: inflate by one step.
+        let d = decide(&mut policy, &stats(100, 0), 20);
+        assert_eq!(d.action, AutoSizeAction::Inflate);
+        assert_eq!(d.reason, AutoSizeReason::HighMemory);
+        assert_eq!((d.previous_mb, d.target_mb), (20, 28));
+
+        // Only inflate by the excess over the high watermark.
+        let d = decide(&mut policy, &stats(67, 0), 20);
+        assert_eq!(d.target_mb, 23);
+
+        // Never above `max_mb`.
+        let d = decide(&mut policy, &stats(100, 0), 60);
+        assert_eq!(d.target_mb, 64);
+        let d = decide(&mut policy, &stats(100, 0), 64);
+        assert_eq!(d.action, AutoSizeAction::Hold);
+        assert_eq!(d.reason, AutoSizeReason::AtLimit);
+
+        // Below the low watermark: deflate by one step, never below `min_mb`.
+        let d = decide(&mut policy, &stats(10, 0), 30);
+        assert_eq!(d.action, AutoSizeAction::Deflate);
+        assert_eq!(d.reason, AutoSizeReason::LowMemory);
+        assert_eq!(d.target_mb, 22);
+        let d = decide(&mut policy, &stats(10, 0), 20);
+        assert_eq!(d.target_mb, 16);
+        let d = decide(&mut policy, &stats(10, 0), 16);
+        assert_eq!(d.reason, AutoSizeReason::AtLimit);
+
+        // Swap activity deflates the balloon even with plenty of available memory.
+        let d = decide(&mut policy, &stats(100, 5), 40);
+        assert_eq!(d.action, AutoSizeAction::Deflate);
+        assert_eq!(d.reason, AutoSizeReason::SwapActivity);
+        assert_eq!(d.target_mb, 32);
+        // Once the swap counters settle, inflation resumes.
+        let d = decide(&mut policy, &stats(100, 5), 32);
+        assert_eq!(d.action, AutoSizeAction::Inflate);
+    }
+
+    #[test]
+    fn test_guest_memory_limit() {
+        let mut policy = policy();
+        policy.fresh_stats = true;
+        let d = policy.decide(&stats(100, 0), 20, 24).unwrap();
+        assert_eq!(d.target_mb, 24);
+    }
+}
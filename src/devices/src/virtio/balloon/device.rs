@@ -1,7 +1,7 @@
 // Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::cmp;
 use std::io::Write;
 use std::result::Result;
@@ -11,17 +11,21 @@ use std::time::Duration;
 
 use ::timerfd::{ClockId, SetTimeFlags, TimerFd, TimerState};
 
-use ::logger::{error, IncMetric, METRICS};
+use ::logger::{error, info, IncMetric, VmEvent, EVENTS, METRICS};
 use ::utils::eventfd::EventFd;
 use ::virtio_gen::virtio_blk::*;
-use ::vm_memory::{Address, ByteValued, Bytes, GuestAddress, GuestMemoryMmap};
+use ::vm_memory::{
+    Address, ByteValued, Bytes, GuestAddress, GuestMemory, GuestMemoryMmap, GuestMemoryRegion,
+};
 
 use super::*;
 use super::{
     super::{
-        ActivateResult, DeviceState, Queue, VirtioDevice, TYPE_BALLOON, VIRTIO_MMIO_INT_VRING,
+        ActivateResult, DeviceState, Queue, VirtioDevice, TYPE_BALLOON, VIRTIO_MMIO_INT_CONFIG,
+        VIRTIO_MMIO_INT_VRING,
     },
-    utils::{compact_page_frame_numbers, remove_range},
+    auto_size::{AutoSizeAction, AutoSizeConfig, AutoSizeDecision, AutoSizePolicy},
+    utils::{compact_page_frame_numbers, remove_range, PageFrameRanges},
     BALLOON_DEV_ID,
 };
 
@@ -79,10 +83,12 @@ pub struct BalloonConfig {
     pub amount_mb: u32,
     pub deflate_on_oom: bool,
     pub stats_polling_interval_s: u16,
+    pub free_page_reporting: bool,
+    pub auto_size: Option<AutoSizeConfig>,
 }
 
 // BalloonStats holds statistics returned from the stats_queue.
-#[derive(Clone, Default, Debug, PartialEq, Serialize)]
+#[derive(Clone, Default, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct BalloonStats {
     pub target_pages: u32,
@@ -109,6 +115,8 @@ pub struct BalloonStats {
     pub hugetlb_allocations: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub hugetlb_failures: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_size: Option<AutoSizeDecision>,
 }
 
 impl BalloonStats {
@@ -157,8 +165,12 @@ pub struct Balloon {
     // it is acknowledged after the stats queue is processed.
     pub(crate) stats_desc_index: Option<u16>,
     pub(crate) latest_stats: BalloonStats,
+    // The automatic sizing policy, if enabled.
+    pub(crate) auto_size: Option<AutoSizePolicy>,
     // A buffer used as pfn accumulator during descriptor processing.
     pub(crate) pfn_buffer: [u32; MAX_PAGE_COMPACT_BUFFER],
+    // The pages currently held by the balloon, which are left out of snapshots.
+    pub(crate) inflated_ranges: PageFrameRanges,
 }
 
 impl Balloon {
@@ -166,6 +178,7 @@ impl Balloon {
         amount_mb: u32,
         deflate_on_oom: bool,
         stats_polling_interval_s: u16,
+        free_page_reporting: bool,
         restored: bool,
     ) -> Result<Balloon, BalloonError> {
         let mut avail_features = 1u64 << VIRTIO_F_VERSION_1;
@@ -178,19 +191,25 @@ impl Balloon {
             avail_features |= 1u64 << VIRTIO_BALLOON_F_STATS_VQ;
         }
 
+        if free_page_reporting {
+            avail_features |= 1u64 << VIRTIO_BALLOON_F_FREE_PAGE_REPORTING;
+        }
+
         let queue_evts = [
             EventFd::new(libc::EFD_NONBLOCK).map_err(BalloonError::EventFd)?,
             EventFd::new(libc::EFD_NONBLOCK).map_err(BalloonError::EventFd)?,
             EventFd::new(libc::EFD_NONBLOCK).map_err(BalloonError::EventFd)?,
+            EventFd::new(libc::EFD_NONBLOCK).map_err(BalloonError::EventFd)?,
         ];
 
-        let mut queues: Vec<Queue> = QUEUE_SIZES.iter().map(|&s| Queue::new(s)).collect();
-
-        // The VirtIO specification states that the statistics queue should
-        // not be present at all if the statistics are not enabled.
-        if stats_polling_interval_s == 0 {
-            let _ = queues.remove(STATS_INDEX);
-        }
+        // The VirtIO specification states that the statistics and free page
+        // reporting queues should not be present at all if the respective
+        // features are not enabled.
+        let num_queues = Self::num_queues(stats_polling_interval_s > 0, free_page_reporting);
+        let queues: Vec<Queue> = QUEUE_SIZES[..num_queues]
+            .iter()
+            .map(|&s| Queue::new(s))
+            .collect();
 
         let stats_timer =
             TimerFd::new_custom(ClockId::Monotonic, true, true).map_err(BalloonError::Timer)?;
@@ -213,7 +232,9 @@ impl Balloon {
             stats_timer,
             stats_desc_index: None,
             latest_stats: BalloonStats::default(),
+            auto_size: None,
             pfn_buffer: [0u32; MAX_PAGE_COMPACT_BUFFER],
+            inflated_ranges: PageFrameRanges::default(),
         })
     }
 
@@ -238,6 +259,13 @@ impl Balloon {
         self.process_stats_queue()
     }
 
+    pub(crate) fn process_reporting_queue_event(&mut self) -> Result<(), BalloonError> {
+        self.queue_evts[self.reporting_index()]
+            .read()
+            .map_err(BalloonError::EventFd)?;
+        self.process_reporting_queue()
+    }
+
     pub(crate) fn process_stats_timer_event(&mut self) -> Result<(), BalloonError> {
         let mem = mem_of_active_device!(self.device_state);
         self.stats_timer.read();
@@ -248,10 +276,44 @@ impl Balloon {
             self.queues[STATS_INDEX]
                 .add_used(&mem, index, 0)
                 .map_err(BalloonError::Queue)?;
-            self.signal_used_queue()
-        } else {
-            Ok(())
+            self.signal_used_queue()?;
+        }
+
+        self.process_auto_size()
+    }
+
+    /// Runs the automatic sizing policy on the latest statistics and, if needed, updates
+    /// the balloon target size.
+    pub(crate) fn process_auto_size(&mut self) -> Result<(), BalloonError> {
+        let mem = mem_of_active_device!(self.device_state);
+        let mem_size_mb = mem.map_and_fold(0, |(_, region)| region.len(), |a, b| a + b) >> 20;
+        let current_mb = self.size_mb();
+        let stats = &self.latest_stats;
+        let decision = match self.auto_size.as_mut() {
+            Some(policy) => match policy.decide(stats, current_mb, mem_size_mb as u32) {
+                Some(decision) => decision,
+                None => return Ok(()),
+            },
+            None => return Ok(()),
+        };
+
+        match decision.action {
+            AutoSizeAction::Inflate => METRICS.balloon.auto_size_inflate_count.inc(),
+            AutoSizeAction::Deflate => METRICS.balloon.auto_size_deflate_count.inc(),
+            AutoSizeAction::Hold => return Ok(()),
         }
+        info!(
+            "balloon: automatic sizing {:?} from {} MB to {} MB ({:?})",
+            decision.action, decision.previous_mb, decision.target_mb, decision.reason
+        );
+        self.config_space.num_pages = mb_to_pages(decision.target_mb)?;
+
+        // Let the driver know about the new target size.
+        self.interrupt_status
+            .fetch_or(VIRTIO_MMIO_INT_CONFIG as usize, Ordering::SeqCst);
+        self.interrupt_evt
+            .write(1)
+            .map_err(BalloonError::InterruptError)
     }
 
     pub(crate) fn process_inflate(&mut self) -> Result<(), BalloonError> {
@@ -326,12 +388,15 @@ impl Balloon {
                 let guest_addr =
                     GuestAddress((page_frame_number as u64) << VIRTIO_BALLOON_PFN_SHIFT);
 
-                if let Err(e) = remove_range(
+                match remove_range(
                     &mem,
                     (guest_addr, u64::from(range_len) << VIRTIO_BALLOON_PFN_SHIFT),
                     self.restored,
                 ) {
-                    error!("Error removing memory range: {:?}", e);
+                    Ok(()) => self
+                        .inflated_ranges
+                        .insert(u64::from(page_frame_number), u64::from(range_len)),
+                    Err(e) => error!("Error removing memory range: {:?}", e),
                 }
             }
         }
@@ -352,6 +417,25 @@ impl Balloon {
         let mut needs_interrupt = false;
 
         while let Some(head) = queue.pop(&mem) {
+            let len = head.len as usize;
+            if !head.is_write_only()
+                && len % SIZE_OF_U32 == 0
+                && len <= MAX_PAGES_IN_DESC * SIZE_OF_U32
+            {
+                // The deflated pages are given back to the guest, so they have to be
+                // included in snapshots again.
+                for index in (0..len).step_by(SIZE_OF_U32) {
+                    let addr = head
+                        .addr
+                        .checked_add(index as u64)
+                        .ok_or(BalloonError::MalformedDescriptor)?;
+                    let page_frame_number = mem
+                        .read_obj::<u32>(addr)
+                        .map_err(|_| BalloonError::MalformedDescriptor)?;
+                    self.inflated_ranges.remove(u64::from(page_frame_number), 1);
+                }
+            }
+
             queue
                 .add_used(&mem, head.index, 0)
                 .map_err(BalloonError::Queue)?;
@@ -365,6 +449,45 @@ impl Balloon {
         }
     }
 
+    pub(crate) fn process_reporting_queue(&mut self) -> Result<(), BalloonError> {
+        let mem = mem_of_active_device!(self.device_state);
+        let reporting_index = self.reporting_index();
+        let mut needs_interrupt = false;
+
+        while let Some(head) = self.queues[reporting_index].pop(&mem) {
+            METRICS.balloon.free_page_report_count.inc();
+            let head_index = head.index;
+
+            // Each descriptor of the chain holds a range of free guest memory.
+            let mut next_desc = Some(head);
+            while let Some(desc) = next_desc {
+                if let Err(e) = remove_range(&mem, (desc.addr, u64::from(desc.len)), self.restored)
+                {
+                    error!("Error removing reported memory range: {:?}", e);
+                    METRICS.balloon.free_page_report_fails.inc();
+                } else {
+                    METRICS
+                        .balloon
+                        .free_page_report_pages
+                        .add((desc.len >> VIRTIO_BALLOON_PFN_SHIFT) as usize);
+                }
+                next_desc = desc.next_descriptor();
+            }
+
+            // The reported pages are handed back to the guest once the descriptor is used.
+            self.queues[reporting_index]
+                .add_used(&mem, head_index, 0)
+                .map_err(BalloonError::Queue)?;
+            needs_interrupt = true;
+        }
+
+        if needs_interrupt {
+            self.signal_used_queue()
+        } else {
+            Ok(())
+        }
+    }
+
     pub(crate) fn process_stats_queue(&mut self) -> std::result::Result<(), BalloonError> {
         let mem = mem_of_active_device!(self.device_state);
         METRICS.balloon.stats_updates_count.inc();
@@ -397,6 +520,9 @@ impl Balloon {
             }
 
             self.stats_desc_index = Some(head.index);
+            if let Some(policy) = self.auto_size.as_mut() {
+                policy.fresh_stats = true;
+            }
         }
 
         Ok(())
@@ -417,6 +543,9 @@ impl Balloon {
     pub fn process_virtio_queues(&mut self) {
         let _ = self.process_inflate();
         let _ = self.process_deflate_queue();
+        if self.free_page_reporting() {
+            let _ = self.process_reporting_queue();
+        }
     }
 
     pub fn id(&self) -> &str {
@@ -467,6 +596,23 @@ impl Balloon {
         self.avail_features & (1u64 << VIRTIO_BALLOON_F_DEFLATE_ON_OOM) != 0
     }
 
+    pub fn free_page_reporting(&self) -> bool {
+        self.avail_features & (1u64 << VIRTIO_BALLOON_F_FREE_PAGE_REPORTING) != 0
+    }
+
+    /// The guest memory ranges currently held by the balloon, in ascending order.
+    pub fn inflated_ranges(&self) -> Vec<(GuestAddress, u64)> {
+        self.inflated_ranges
+            .iter()
+            .map(|(page_frame_number, range_len)| {
+                (
+                    GuestAddress(page_frame_number << VIRTIO_BALLOON_PFN_SHIFT),
+                    range_len << VIRTIO_BALLOON_PFN_SHIFT,
+                )
+            })
+            .collect()
+    }
+
     pub fn stats_polling_interval_s(&self) -> u16 {
         self.stats_polling_interval_s
     }
@@ -477,6 +623,10 @@ impl Balloon {
             self.latest_stats.actual_pages = self.config_space.actual_pages;
             self.latest_stats.target_mb = pages_to_mb(self.latest_stats.target_pages);
             self.latest_stats.actual_mb = pages_to_mb(self.latest_stats.actual_pages);
+            self.latest_stats.auto_size = self
+                .auto_size
+                .as_ref()
+                .and_then(|policy| policy.last_decision);
             Some(&self.latest_stats)
         } else {
             None
@@ -488,12 +638,53 @@ impl Balloon {
             amount_mb: self.size_mb(),
             deflate_on_oom: self.deflate_on_oom(),
             stats_polling_interval_s: self.stats_polling_interval_s(),
+            free_page_reporting: self.free_page_reporting(),
+            auto_size: self.auto_size(),
+        }
+    }
+
+    /// Enables or disables the automatic sizing policy. The policy is driven by the
+    /// statistics, so they must be enabled.
+    pub fn set_auto_size(&mut self, config: Option<AutoSizeConfig>) -> Result<(), BalloonError> {
+        if let Some(config) = config {
+            if !self.stats_enabled() {
+                return Err(BalloonError::AutoSizeStatsDisabled);
+            }
+            config.validate()?;
         }
+        self.auto_size = config.map(AutoSizePolicy::new);
+        Ok(())
+    }
+
+    pub fn auto_size(&self) -> Option<AutoSizeConfig> {
+        self.auto_size.as_ref().map(|policy| policy.config)
     }
 
     pub(crate) fn stats_enabled(&self) -> bool {
         self.stats_polling_interval_s > 0
     }
+
+    /// The number of virtio queues used with the given features.
+    pub(crate) fn num_queues(stats_enabled: bool, free_page_reporting: bool) -> usize {
+        let mut num_queues = NUM_QUEUES;
+        if !stats_enabled {
+            num_queues -= 1;
+        }
+        if !free_page_reporting {
+            num_queues -= 1;
+        }
+        num_queues
+    }
+
+    /// The index of the free page reporting queue, which comes right after the statistics
+    /// queue, if present.
+    pub(crate) fn reporting_index(&self) -> usize {
+        if self.stats_enabled() {
+            STATS_INDEX + 1
+        } else {
+            STATS_INDEX
+        }
+    }
 }
 
 impl VirtioDevice for Balloon {
@@ -551,6 +742,7 @@ impl VirtioDevice for Balloon {
     }
 
     fn write_config(&mut self, offset: u64, data: &[u8]) {
+        let prev_actual_pages = self.config_space.actual_pages;
         let data_len = data.len() as u64;
         let config_space_bytes = self.config_space.as_mut_slice();
         let config_len = config_space_bytes.len() as u64;
@@ -559,6 +751,21 @@ impl VirtioDevice for Balloon {
             return;
         }
         config_space_bytes[offset as usize..(offset + data_len) as usize].copy_from_slice(data);
+
+        // The driver reports the balloon size after each inflation or deflation round.
+        if self.config_space.actual_pages != prev_actual_pages
+            && self.config_space.actual_pages == self.config_space.num_pages
+        {
+            EVENTS.emit(VmEvent::BalloonTargetReached {
+                target_mb: self.size_mb(),
+            });
+        }
+
+        // An empty balloon holds no pages. This also covers a driver that was
+        // reinitialized without deflating the balloon first.
+        if self.config_space.actual_pages == 0 {
+            self.inflated_ranges.clear();
+        }
     }
 
     fn is_activated(&self) -> bool {
@@ -643,6 +850,7 @@ pub(crate) mod tests {
             disk_caches: Some(0),
             hugetlb_allocations: Some(0),
             hugetlb_failures: Some(0),
+            auto_size: None,
         };
 
         let mut stat = BalloonStat {
@@ -686,36 +894,55 @@ pub(crate) mod tests {
         // Test all feature combinations.
         for deflate_on_oom in vec![true, false].iter() {
             for stats_interval in vec![0, 1].iter() {
-                let mut balloon = Balloon::new(0, *deflate_on_oom, *stats_interval, false).unwrap();
-                assert_eq!(balloon.device_type(), TYPE_BALLOON);
-
-                let features: u64 = (1u64 << VIRTIO_F_VERSION_1)
-                    | ((if *deflate_on_oom { 1 } else { 0 }) << VIRTIO_BALLOON_F_DEFLATE_ON_OOM)
-                    | ((*stats_interval as u64) << VIRTIO_BALLOON_F_STATS_VQ);
-
-                assert_eq!(balloon.avail_features_by_page(0), features as u32);
-                assert_eq!(balloon.avail_features_by_page(1), (features >> 32) as u32);
-                for i in 2..10 {
-                    assert_eq!(balloon.avail_features_by_page(i), 0u32);
-                }
+                for free_page_reporting in vec![true, false].iter() {
+                    let mut balloon = Balloon::new(
+                        0,
+                        *deflate_on_oom,
+                        *stats_interval,
+                        *free_page_reporting,
+                        false,
+                    )
+                    .unwrap();
+                    assert_eq!(balloon.device_type(), TYPE_BALLOON);
+
+                    let features: u64 = (1u64 << VIRTIO_F_VERSION_1)
+                        | ((if *deflate_on_oom { 1 } else { 0 })
+                            << VIRTIO_BALLOON_F_DEFLATE_ON_OOM)
+                        | ((*stats_interval as u64) << VIRTIO_BALLOON_F_STATS_VQ)
+                        | ((if *free_page_reporting { 1 } else { 0 })
+                            << VIRTIO_BALLOON_F_FREE_PAGE_REPORTING);
+
+                    assert_eq!(balloon.avail_features_by_page(0), features as u32);
+                    assert_eq!(balloon.avail_features_by_page(1), (features >> 32) as u32);
+                    for i in 2..10 {
+                        assert_eq!(balloon.avail_features_by_page(i), 0u32);
+                    }
 
-                for i in 0..10 {
-                    balloon.ack_features_by_page(i, u32::MAX);
+                    for i in 0..10 {
+                        balloon.ack_features_by_page(i, u32::MAX);
+                    }
+                    // Only present features should be acknowledged.
+                    assert_eq!(balloon.acked_features, features);
+                    // Only the queues of enabled features should be present.
+                    assert_eq!(
+                        balloon.queues().len(),
+                        2 + *stats_interval as usize + *free_page_reporting as usize
+                    );
                 }
-                // Only present features should be acknowledged.
-                assert_eq!(balloon.acked_features, features);
             }
         }
     }
 
     #[test]
     fn test_virtio_read_config() {
-        let balloon = Balloon::new(0x10, true, 0, false).unwrap();
+        let balloon = Balloon::new(0x10, true, 0, false, false).unwrap();
 
         let cfg = BalloonConfig {
             amount_mb: 16,
             deflate_on_oom: true,
             stats_polling_interval_s: 0,
+            free_page_reporting: false,
+            auto_size: None,
         };
         assert_eq!(balloon.config(), cfg);
 
@@ -741,7 +968,7 @@ pub(crate) mod tests {
 
     #[test]
     fn test_virtio_write_config() {
-        let mut balloon = Balloon::new(0, true, 0, false).unwrap();
+        let mut balloon = Balloon::new(0, true, 0, false, false).unwrap();
 
         let expected_config_space: [u8; CONFIG_SPACE_SIZE] =
             [0x00, 0x50, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
@@ -761,7 +988,7 @@ pub(crate) mod tests {
 
     #[test]
     fn test_invalid_request() {
-        let mut balloon = Balloon::new(0, true, 0, false).unwrap();
+        let mut balloon = Balloon::new(0, true, 0, false, false).unwrap();
         let mem = default_mem();
         // Only initialize the inflate queue to demonstrate invalid request handling.
         let infq = VirtQueue::new(GuestAddress(0), &mem, 16);
@@ -820,7 +1047,7 @@ pub(crate) mod tests {
 
     #[test]
     fn test_inflate() {
-        let mut balloon = Balloon::new(0, true, 0, false).unwrap();
+        let mut balloon = Balloon::new(0, true, 0, false, false).unwrap();
         let mem = default_mem();
         let infq = VirtQueue::new(GuestAddress(0), &mem, 16);
         balloon.set_queue(INFLATE_INDEX, infq.create_queue());
@@ -877,12 +1104,17 @@ pub(crate) mod tests {
             for i in 0..0x1000 {
                 assert_eq!(mem.read_obj::<u8>(GuestAddress((1 << 12) + i)).unwrap(), 0);
             }
+            // The page is tracked as held by the balloon.
+            assert_eq!(
+                balloon.inflated_ranges(),
+                vec![(GuestAddress(1 << 12), 0x1000)]
+            );
         }
     }
 
     #[test]
     fn test_deflate() {
-        let mut balloon = Balloon::new(0, true, 0, false).unwrap();
+        let mut balloon = Balloon::new(0, true, 0, false, false).unwrap();
         let mem = default_mem();
         let defq = VirtQueue::new(GuestAddress(0), &mem, 16);
         balloon.set_queue(DEFLATE_INDEX, defq.create_queue());
@@ -894,7 +1126,11 @@ pub(crate) mod tests {
             balloon.queue_evts[DEFLATE_INDEX].as_raw_fd() as u64,
         );
 
+        // Will write the page frame number of the deflated page at this
+        // arbitrary address in memory.
         let page_addr = 0x10;
+        mem.write_obj::<u32>(0x1, GuestAddress(page_addr)).unwrap();
+        balloon.inflated_ranges.insert(0, 4);
 
         // Error case: forgot to trigger deflate event queue.
         {
@@ -917,12 +1153,22 @@ pub(crate) mod tests {
                 invoke_handler_for_queue_event(&mut balloon, DEFLATE_INDEX)
             );
             check_request_completion(&defq, 1);
+
+            // The deflated page is no longer tracked as held by the balloon.
+            assert_eq!(
+                balloon.inflated_ranges(),
+                vec![(GuestAddress(0), 0x1000), (GuestAddress(2 << 12), 0x2000)]
+            );
         }
+
+        // The tracked pages are dropped when the driver reports an empty balloon.
+        balloon.write_config(4, &[0u8; 4]);
+        assert!(balloon.inflated_ranges().is_empty());
     }
 
     #[test]
     fn test_stats() {
-        let mut balloon = Balloon::new(0, true, 1, false).unwrap();
+        let mut balloon = Balloon::new(0, true, 1, false, false).unwrap();
         let mem = default_mem();
         let statsq = VirtQueue::new(GuestAddress(0), &mem, 16);
         balloon.set_queue(STATS_INDEX, statsq.create_queue());
@@ -1005,9 +1251,141 @@ pub(crate) mod tests {
         }
     }
 
+    #[test]
+    fn test_free_page_reporting() {
+        for stats_interval in vec![0, 1].iter() {
+            let mut balloon = Balloon::new(0, true, *stats_interval, true, false).unwrap();
+            let reporting_index = balloon.reporting_index();
+            assert_eq!(reporting_index, STATS_INDEX + *stats_interval as usize);
+            let mem = default_mem();
+            let repq = VirtQueue::new(GuestAddress(0), &mem, 16);
+            balloon.set_queue(reporting_index, repq.create_queue());
+            balloon.activate(mem.clone()).unwrap();
+
+            let mut event_manager = EventManager::new().unwrap();
+            let queue_evt = EpollEvent::new(
+                EventSet::IN,
+                balloon.queue_evts[reporting_index].as_raw_fd() as u64,
+            );
+
+            // Fill two pages with non-zero bytes.
+            let range_addr = 0x8000;
+            let range_len = 0x2000;
+            for i in 0..range_len {
+                mem.write_obj::<u8>(1, GuestAddress(range_addr + i))
+                    .unwrap();
+            }
+
+            // Error case: forgot to trigger the reporting event queue.
+            {
+                set_request(&repq, 0, range_addr, range_len as u32, VIRTQ_DESC_F_WRITE);
+                check_metric_after_block!(
+                    METRICS.balloon.event_fails,
+                    1,
+                    balloon.process(&queue_evt, &mut event_manager)
+                );
+                // Verify that nothing got processed.
+                assert_eq!(repq.used.idx.get(), 0);
+            }
+
+            // Happy case: the reported range is released, and the descriptor is returned.
+            {
+                check_metric_after_block!(METRICS.balloon.free_page_report_pages, 2, {
+                    check_metric_after_block!(
+                        METRICS.balloon.free_page_report_count,
+                        1,
+                        invoke_handler_for_queue_event(&mut balloon, reporting_index)
+                    );
+                });
+                check_request_completion(&repq, 0);
+
+                // Check that the pages were zeroed.
+                for i in 0..range_len {
+                    assert_eq!(mem.read_obj::<u8>(GuestAddress(range_addr + i)).unwrap(), 0);
+                }
+            }
+
+            // A range outside of guest memory is skipped, but the descriptor is still returned.
+            {
+                set_request(&repq, 1, u64::MAX - 0x1000, 0x1000, VIRTQ_DESC_F_WRITE);
+                check_metric_after_block!(
+                    METRICS.balloon.free_page_report_fails,
+                    1,
+                    invoke_handler_for_queue_event(&mut balloon, reporting_index)
+                );
+                check_request_completion(&repq, 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_auto_size() {
+        let auto_size = AutoSizeConfig {
+            min_mb: 2,
+            max_mb: 16,
+            step_mb: 4,
+            low_available_mb: 32,
+            high_available_mb: 64,
+        };
+
+        // Automatic sizing needs the statistics.
+        let mut balloon = Balloon::new(10, true, 0, false, false).unwrap();
+        assert_eq!(
+            format!("{:?}", balloon.set_auto_size(Some(auto_size))),
+            "Err(AutoSizeStatsDisabled)"
+        );
+
+        let mut balloon = Balloon::new(10, true, 1, false, false).unwrap();
+        let mut invalid = auto_size;
+        invalid.low_available_mb = invalid.high_available_mb;
+        assert_eq!(
+            format!("{:?}", balloon.set_auto_size(Some(invalid))),
+            "Err(InvalidAutoSizeConfig)"
+        );
+        balloon.set_auto_size(Some(auto_size)).unwrap();
+        assert_eq!(balloon.config().auto_size, Some(auto_size));
+        balloon.activate(default_mem()).unwrap();
+
+        // Nothing happens until the driver reports statistics.
+        balloon.process_auto_size().unwrap();
+        assert_eq!(balloon.size_mb(), 10);
+        assert!(balloon.latest_stats().unwrap().auto_size.is_none());
+
+        // Low available memory deflates the balloon and notifies the driver.
+        balloon.latest_stats.available_memory = Some(16 << 20);
+        balloon.auto_size.as_mut().unwrap().fresh_stats = true;
+        check_metric_after_block!(
+            METRICS.balloon.auto_size_deflate_count,
+            1,
+            balloon.process_auto_size().unwrap()
+        );
+        assert_eq!(balloon.size_mb(), 6);
+        assert_ne!(
+            balloon.interrupt_status().load(Ordering::SeqCst) & VIRTIO_MMIO_INT_CONFIG as usize,
+            0
+        );
+        assert_eq!(balloon.interrupt_evt().read().unwrap(), 1);
+        let decision = balloon.latest_stats().unwrap().auto_size.unwrap();
+        assert_eq!(decision.action, AutoSizeAction::Deflate);
+        assert_eq!((decision.previous_mb, decision.target_mb), (10, 6));
+
+        // The balloon cannot grow past the guest memory size, which is below 1 MB here.
+        balloon.latest_stats.available_memory = Some(128 << 20);
+        balloon.auto_size.as_mut().unwrap().fresh_stats = true;
+        check_metric_after_block!(
+            METRICS.balloon.auto_size_inflate_count,
+            0,
+            balloon.process_auto_size().unwrap()
+        );
+        assert_eq!(balloon.size_mb(), 6);
+
+        balloon.set_auto_size(None).unwrap();
+        assert!(balloon.latest_stats().unwrap().auto_size.is_none());
+    }
+
     #[test]
     fn test_process_balloon_queues() {
-        let mut balloon = Balloon::new(0x10, true, 0, false).unwrap();
+        let mut balloon = Balloon::new(0x10, true, 0, false, false).unwrap();
         let mem = default_mem();
         balloon.activate(mem).unwrap();
         balloon.process_virtio_queues()
@@ -1015,14 +1393,14 @@ pub(crate) mod tests {
 
     #[test]
     fn test_update_stats_interval() {
-        let mut balloon = Balloon::new(0, true, 0, false).unwrap();
+        let mut balloon = Balloon::new(0, true, 0, false, false).unwrap();
         assert_eq!(
             format!("{:?}", balloon.update_stats_polling_interval(1)),
             "Err(StatisticsStateChange)"
         );
         assert!(balloon.update_stats_polling_interval(0).is_ok());
 
-        let mut balloon = Balloon::new(0, true, 1, false).unwrap();
+        let mut balloon = Balloon::new(0, true, 1, false, false).unwrap();
         assert_eq!(
             format!("{:?}", balloon.update_stats_polling_interval(0)),
             "Err(StatisticsStateChange)"
@@ -1033,7 +1411,7 @@ pub(crate) mod tests {
 
     #[test]
     fn test_num_pages() {
-        let mut balloon = Balloon::new(0, true, 0, false).unwrap();
+        let mut balloon = Balloon::new(0, true, 0, false, false).unwrap();
         // Assert that we can't update an inactive device.
         assert!(balloon.update_size(1).is_err());
         // Switch the state to active.
@@ -63,6 +63,7 @@ impl Subscriber for Balloon {
             let virtq_inflate_ev_fd = self.queue_evts[INFLATE_INDEX].as_raw_fd();
             let virtq_deflate_ev_fd = self.queue_evts[DEFLATE_INDEX].as_raw_fd();
             let virtq_stats_ev_fd = self.queue_evts[STATS_INDEX].as_raw_fd();
+            let virtq_reporting_ev_fd = self.queue_evts[self.reporting_index()].as_raw_fd();
             let stats_timer_fd = self.stats_timer.as_raw_fd();
             let activate_fd = self.activate_evt.as_raw_fd();
 
@@ -74,6 +75,11 @@ impl Subscriber for Balloon {
                 _ if source == virtq_deflate_ev_fd => self
                     .process_deflate_queue_event()
                     .unwrap_or_else(report_balloon_event_fail),
+                // The reporting queue takes the index of the statistics queue, when the
+                // statistics are disabled, so check for it first.
+                _ if source == virtq_reporting_ev_fd && self.free_page_reporting() => self
+                    .process_reporting_queue_event()
+                    .unwrap_or_else(report_balloon_event_fail),
                 _ if source == virtq_stats_ev_fd => self
                     .process_stats_queue_event()
                     .unwrap_or_else(report_balloon_event_fail),
@@ -118,6 +124,12 @@ impl Subscriber for Balloon {
                     EpollEvent::new(EventSet::IN, self.stats_timer.as_raw_fd() as u64),
                 ]);
             }
+            if self.free_page_reporting() {
+                events.push(EpollEvent::new(
+                    EventSet::IN,
+                    self.queue_evts[self.reporting_index()].as_raw_fd() as u64,
+                ));
+            }
             events
         } else {
             vec![EpollEvent::new(
@@ -140,7 +152,7 @@ pub mod tests {
     #[test]
     fn test_event_handler() {
         let mut event_manager = EventManager::new().unwrap();
-        let mut balloon = Balloon::new(0, true, 10, false).unwrap();
+        let mut balloon = Balloon::new(0, true, 10, false, false).unwrap();
         let mem = default_mem();
         let infq = VirtQueue::new(GuestAddress(0), &mem, 16);
         balloon.set_queue(INFLATE_INDEX, infq.create_queue());
@@ -1,6 +1,7 @@
 // Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod auto_size;
 pub mod device;
 pub mod event_handler;
 pub mod persist;
@@ -9,6 +10,7 @@ mod utils;
 
 use vm_memory::GuestMemoryError;
 
+pub use self::auto_size::{AutoSizeConfig, AutoSizeDecision};
 pub use self::device::Balloon;
 pub use self::device::BalloonConfig;
 pub use self::device::BalloonStats;
@@ -19,8 +21,8 @@ pub use self::event_handler::*;
 pub const BALLOON_DEV_ID: &str = "balloon";
 pub const CONFIG_SPACE_SIZE: usize = 8;
 pub const QUEUE_SIZE: u16 = 256;
-pub const NUM_QUEUES: usize = 3;
-pub const QUEUE_SIZES: &[u16] = &[QUEUE_SIZE, QUEUE_SIZE, QUEUE_SIZE];
+pub const NUM_QUEUES: usize = 4;
+pub const QUEUE_SIZES: &[u16] = &[QUEUE_SIZE, QUEUE_SIZE, QUEUE_SIZE, QUEUE_SIZE];
 // Number of 4K pages in a MB.
 pub const MB_TO_4K_PAGES: u32 = 256;
 // The maximum number of pages that can be received in a single descriptor.
@@ -36,10 +38,13 @@ pub const INFLATE_INDEX: usize = 0;
 pub const DEFLATE_INDEX: usize = 1;
 // The index of the deflate queue from Balloon device queues/queues_evts vector.
 pub const STATS_INDEX: usize = 2;
+// The free page reporting queue follows the statistics queue, or takes its index when the
+// statistics are disabled. See `Balloon::reporting_index()`.
 
 // The feature bitmap for virtio balloon.
 const VIRTIO_BALLOON_F_STATS_VQ: u32 = 1; // Enable statistics.
 const VIRTIO_BALLOON_F_DEFLATE_ON_OOM: u32 = 2; // Deflate balloon on OOM.
+const VIRTIO_BALLOON_F_FREE_PAGE_REPORTING: u32 = 5; // Report free pages to the device.
 
 // The statistics tags.
 const VIRTIO_BALLOON_S_SWAP_IN: u16 = 0;
@@ -61,6 +66,8 @@ pub enum Error {
     DeviceNotFound,
     /// Device not activated yet.
     DeviceNotActive,
+    /// Automatic sizing requires the statistics to be enabled.
+    AutoSizeStatsDisabled,
     /// EventFd error.
     EventFd(std::io::Error),
     /// Failed to signal the virtio used queue.
@@ -69,6 +76,8 @@ pub enum Error {
     GuestMemory(GuestMemoryError),
     /// Received error while sending an interrupt.
     InterruptError(std::io::Error),
+    /// The automatic sizing bounds or watermarks are inconsistent.
+    InvalidAutoSizeConfig,
     /// Guest gave us a malformed descriptor.
     MalformedDescriptor,
     /// Guest gave us a malformed payload.
@@ -8,6 +8,7 @@ use std::sync::Arc;
 use std::time::Duration;
 use timerfd::{SetTimeFlags, TimerState};
 
+use logger::error;
 use snapshot::Persist;
 use versionize::{VersionMap, Versionize, VersionizeResult};
 use versionize_derive::Versionize;
@@ -17,6 +18,7 @@ use vm_memory::GuestMemoryMmap;
 use super::*;
 
 use crate::virtio::balloon::device::{BalloonStats, ConfigSpace};
+use crate::virtio::balloon::utils::remove_range;
 use crate::virtio::persist::VirtioDeviceState;
 use crate::virtio::{DeviceState, TYPE_BALLOON};
 
@@ -74,10 +76,50 @@ impl BalloonStatsState {
             disk_caches: self.disk_caches,
             hugetlb_allocations: self.hugetlb_allocations,
             hugetlb_failures: self.hugetlb_failures,
+            auto_size: None,
         }
     }
 }
 
+#[derive(Clone, Versionize)]
+// NOTICE: Any changes to this structure require a snapshot version bump.
+pub struct BalloonAutoSizeState {
+    min_mb: u32,
+    max_mb: u32,
+    step_mb: u32,
+    low_available_mb: u32,
+    high_available_mb: u32,
+}
+
+impl BalloonAutoSizeState {
+    fn from_config(config: &AutoSizeConfig) -> Self {
+        Self {
+            min_mb: config.min_mb,
+            max_mb: config.max_mb,
+            step_mb: config.step_mb,
+            low_available_mb: config.low_available_mb,
+            high_available_mb: config.high_available_mb,
+        }
+    }
+
+    fn create_config(&self) -> AutoSizeConfig {
+        AutoSizeConfig {
+            min_mb: self.min_mb,
+            max_mb: self.max_mb,
+            step_mb: self.step_mb,
+            low_available_mb: self.low_available_mb,
+            high_available_mb: self.high_available_mb,
+        }
+    }
+}
+
+#[derive(Clone, Versionize)]
+// NOTICE: Any changes to this structure require a snapshot version bump.
+pub struct BalloonPageRangeState {
+    start_pfn: u64,
+    num_pages: u64,
+}
+
 #[derive(Clone, Versionize)]
 // NOTICE: Any changes to this structure require a snapshot version bump.
 pub struct BalloonState {
@@ -86,6 +128,26 @@ pub struct BalloonState {
     latest_stats: BalloonStatsState,
     config_space: BalloonConfigSpaceState,
     virtio_state: VirtioDeviceState,
+    #[version(start = 2, default_fn = "def_free_page_reporting")]
+    free_page_reporting: bool,
+    #[version(start = 2, default_fn = "def_auto_size")]
+    auto_size: Option<BalloonAutoSizeState>,
+    #[version(start = 2, default_fn = "def_inflated_ranges")]
+    inflated_ranges: Vec<BalloonPageRangeState>,
+}
+
+impl BalloonState {
+    fn def_free_page_reporting(_source_version: u16) -> bool {
+        false
+    }
+
+    fn def_auto_size(_source_version: u16) -> Option<BalloonAutoSizeState> {
+        None
+    }
+
+    fn def_inflated_ranges(_source_version: u16) -> Vec<BalloonPageRangeState> {
+        Vec::new()
+    }
 }
 
 pub struct BalloonConstructorArgs {
@@ -107,6 +169,19 @@ impl Persist<'_> for Balloon {
                 actual_pages: self.config_space.actual_pages,
             },
             virtio_state: VirtioDeviceState::from_device(self),
+            free_page_reporting: self.free_page_reporting(),
+            auto_size: self
+                .auto_size()
+                .as_ref()
+                .map(BalloonAutoSizeState::from_config),
+            inflated_ranges: self
+                .inflated_ranges
+                .iter()
+                .map(|(start_pfn, num_pages)| BalloonPageRangeState {
+                    start_pfn,
+                    num_pages,
+                })
+                .collect(),
         }
     }
 
@@ -116,14 +191,20 @@ impl Persist<'_> for Balloon {
     ) -> std::result::Result<Self, Self::Error> {
         // We can safely create the balloon with arbitrary flags and
         // num_pages because we will overwrite them after.
-        let mut balloon = Balloon::new(0, false, state.stats_polling_interval_s, true)?;
+        let mut balloon = Balloon::new(
+            0,
+            false,
+            state.stats_polling_interval_s,
+            state.free_page_reporting,
+            true,
+        )?;
 
-        let mut num_queues = NUM_QUEUES;
-        // As per the virtio 1.1 specification, the statistics queue
-        // should not exist if the statistics are not enabled.
-        if state.stats_polling_interval_s == 0 {
-            num_queues -= 1;
-        }
+        // As per the virtio 1.1 specification, the statistics and free page
+        // reporting queues should not exist if the features are not enabled.
+        let num_queues = Balloon::num_queues(
+            state.stats_polling_interval_s > 0,
+            state.free_page_reporting,
+        );
         balloon.queues = state
             .virtio_state
             .build_queues_checked(&constructor_args.mem, TYPE_BALLOON, num_queues, QUEUE_SIZE)
@@ -136,8 +217,27 @@ impl Persist<'_> for Balloon {
             num_pages: state.config_space.num_pages,
             actual_pages: state.config_space.actual_pages,
         };
+        balloon.set_auto_size(
+            state
+                .auto_size
+                .as_ref()
+                .map(BalloonAutoSizeState::create_config),
+        )?;
+
+        for range in state.inflated_ranges.iter() {
+            balloon
+                .inflated_ranges
+                .insert(range.start_pfn, range.num_pages);
+        }
 
         if state.virtio_state.activated {
+            // The pages held by the balloon are not saved in the memory file, so they are
+            // replaced with zero pages, regardless of what a base snapshot may contain.
+            for (guest_addr, range_len) in balloon.inflated_ranges() {
+                if let Err(e) = remove_range(&constructor_args.mem, (guest_addr, range_len), true) {
+                    error!("Error removing memory range: {:?}", e);
+                }
+            }
             balloon.device_state = DeviceState::Activated(constructor_args.mem);
 
             // Restart timer if needed.
@@ -164,6 +264,7 @@ mod tests {
 
     use crate::virtio::test_utils::default_mem;
     use std::sync::atomic::Ordering;
+    use vm_memory::{Bytes, GuestAddress};
 
     #[test]
     fn test_persistence() {
@@ -172,7 +273,7 @@ mod tests {
         let version_map = VersionMap::new();
 
         // Create and save the balloon device.
-        let balloon = Balloon::new(0x42, false, 2, false).unwrap();
+        let balloon = Balloon::new(0x42, false, 2, false, false).unwrap();
 
         <Balloon as Persist>::save(&balloon)
             .serialize(&mut mem.as_mut_slice(), &version_map, 1)
@@ -204,5 +305,90 @@ mod tests {
         );
         assert_eq!(restored_balloon.stats_desc_index, balloon.stats_desc_index);
         assert_eq!(restored_balloon.latest_stats, balloon.latest_stats);
+        assert!(!restored_balloon.free_page_reporting());
+        assert!(restored_balloon.auto_size().is_none());
+    }
+
+    #[test]
+    fn test_persistence_v2() {
+        let guest_mem = default_mem();
+        let mut mem = vec![0; 4096];
+        let mut version_map = VersionMap::new();
+        version_map
+            .new_version()
+            .set_type_version(BalloonState::type_id(), 2);
+
+        let mut balloon = Balloon::new(0x42, false, 1, true, false).unwrap();
+        let auto_size = AutoSizeConfig {
+            min_mb: 1,
+            max_mb: 2,
+            step_mb: 1,
+            low_available_mb: 1,
+            high_available_mb: 2,
+        };
+        balloon.set_auto_size(Some(auto_size)).unwrap();
+        balloon.inflated_ranges.insert(1, 2);
+        <Balloon as Persist>::save(&balloon)
+            .serialize(&mut mem.as_mut_slice(), &version_map, 2)
+            .unwrap();
+
+        let restored_balloon = Balloon::restore(
+            BalloonConstructorArgs { mem: guest_mem },
+            &BalloonState::deserialize(&mut mem.as_slice(), &version_map, 2).unwrap(),
+        )
+        .unwrap();
+        assert!(restored_balloon.free_page_reporting());
+        assert_eq!(restored_balloon.queues().len(), 4);
+        assert_eq!(restored_balloon.auto_size(), Some(auto_size));
+        assert_eq!(
+            restored_balloon.inflated_ranges(),
+            vec![(GuestAddress(0x1000), 0x2000)]
+        );
+        assert_eq!(restored_balloon.avail_features, balloon.avail_features);
+
+        // Older versions don't support free page reporting.
+        <Balloon as Persist>::save(&Balloon::new(0x42, false, 0, false, false).unwrap())
+            .serialize(&mut mem.as_mut_slice(), &version_map, 1)
+            .unwrap();
+        let state = BalloonState::deserialize(&mut mem.as_slice(), &version_map, 1).unwrap();
+        assert!(!state.free_page_reporting);
+        assert!(state.auto_size.is_none());
+    }
+
+    #[test]
+    fn test_persistence_inflated_ranges() {
+        let guest_mem = default_mem();
+        let mut mem = vec![0; 4096];
+        let mut version_map = VersionMap::new();
+        version_map
+            .new_version()
+            .set_type_version(BalloonState::type_id(), 2);
+
+        let mut balloon = Balloon::new(0, false, 0, false, false).unwrap();
+        balloon.activate(guest_mem.clone()).unwrap();
+        balloon.inflated_ranges.insert(1, 1);
+        <Balloon as Persist>::save(&balloon)
+            .serialize(&mut mem.as_mut_slice(), &version_map, 2)
+            .unwrap();
+
+        // Whatever the memory holds for the inflated page, the restored balloon
+        // replaces it with a zero page.
+        let ones = vec![1u8; 0x2000];
+        guest_mem.write_slice(&ones, GuestAddress(0)).unwrap();
+        let _restored_balloon = Balloon::restore(
+            BalloonConstructorArgs {
+                mem: guest_mem.clone(),
+            },
+            &BalloonState::deserialize(&mut mem.as_slice(), &version_map, 2).unwrap(),
+        )
+        .unwrap();
+
+        let mut page = vec![0u8; 0x1000];
+        guest_mem.read_slice(&mut page, GuestAddress(0)).unwrap();
+        assert_eq!(page, vec![1u8; 0x1000]);
+        guest_mem
+            .read_slice(&mut page, GuestAddress(0x1000))
+            .unwrap();
+        assert_eq!(page, vec![0u8; 0x1000]);
     }
 }
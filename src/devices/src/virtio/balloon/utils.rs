@@ -1,6 +1,7 @@
 // Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
+use std::collections::BTreeMap;
 use std::io;
 
 use super::{RemoveRegionError, MAX_PAGE_COMPACT_BUFFER};
@@ -64,6 +65,84 @@ pub(crate) fn compact_page_frame_numbers(v: &mut [u32]) -> Vec<(u32, u32)> {
     result
 }
 
+/// A set of page frame numbers, stored as disjoint ranges of consecutive pages.
+/// Adjacent ranges are always merged.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct PageFrameRanges {
+    // Maps the first page frame number of each range to the one following its last page.
+    ranges: BTreeMap<u64, u64>,
+}
+
+impl PageFrameRanges {
+    /// Adds the `len` pages starting at `start` to the set.
+    pub(crate) fn insert(&mut self, start: u64, len: u64) {
+        if len == 0 {
+            return;
+        }
+        let mut start = start;
+        let mut end = start + len;
+
+        // Absorb the range that overlaps or touches the new one from the left.
+        if let Some((&prev_start, &prev_end)) = self.ranges.range(..=start).next_back() {
+            if prev_end >= start {
+                start = prev_start;
+                end = end.max(prev_end);
+            }
+        }
+        // Absorb all the ranges that overlap or touch the new one from the right.
+        let absorbed: Vec<(u64, u64)> = self
+            .ranges
+            .range(start..=end)
+            .map(|(&s, &e)| (s, e))
+            .collect();
+        for (s, e) in absorbed {
+            self.ranges.remove(&s);
+            end = end.max(e);
+        }
+
+        self.ranges.insert(start, end);
+    }
+
+    /// Removes the `len` pages starting at `start` from the set.
+    pub(crate) fn remove(&mut self, start: u64, len: u64) {
+        if len == 0 {
+            return;
+        }
+        let end = start + len;
+
+        let overlapping: Vec<(u64, u64)> = self
+            .ranges
+            .range(..end)
+            .rev()
+            .take_while(|&(_, &e)| e > start)
+            .map(|(&s, &e)| (s, e))
+            .collect();
+        for (s, e) in overlapping {
+            self.ranges.remove(&s);
+            if s < start {
+                self.ranges.insert(s, start);
+            }
+            if e > end {
+                self.ranges.insert(end, e);
+            }
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.ranges.clear();
+    }
+
+    /// The total number of pages in the set.
+    pub(crate) fn num_pages(&self) -> u64 {
+        self.ranges.iter().map(|(s, e)| e - s).sum()
+    }
+
+    /// Iterates over the (start_page_frame_number, range_length) pairs, in ascending order.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.ranges.iter().map(|(&s, &e)| (s, e - s))
+    }
+}
+
 pub(crate) fn remove_range(
     guest_memory: &GuestMemoryMmap,
     range: (GuestAddress, u64),
@@ -183,6 +262,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_page_frame_ranges() {
+        let mut ranges = PageFrameRanges::default();
+        ranges.insert(10, 0);
+        assert_eq!(ranges.num_pages(), 0);
+
+        // Disjoint ranges.
+        ranges.insert(10, 5);
+        ranges.insert(20, 5);
+        assert_eq!(ranges.iter().collect::<Vec<_>>(), vec![(10, 5), (20, 5)]);
+
+        // Adjacent and overlapping ranges are merged.
+        ranges.insert(15, 2);
+        ranges.insert(5, 6);
+        assert_eq!(ranges.iter().collect::<Vec<_>>(), vec![(5, 12), (20, 5)]);
+        ranges.insert(16, 4);
+        assert_eq!(ranges.iter().collect::<Vec<_>>(), vec![(5, 20)]);
+        ranges.insert(0, 100);
+        assert_eq!(ranges.iter().collect::<Vec<_>>(), vec![(0, 100)]);
+        assert_eq!(ranges.num_pages(), 100);
+
+        // Removing pages splits ranges.
+        ranges.remove(10, 10);
+        assert_eq!(ranges.iter().collect::<Vec<_>>(), vec![(0, 10), (20, 80)]);
+        ranges.remove(5, 20);
+        assert_eq!(ranges.iter().collect::<Vec<_>>(), vec![(0, 5), (25, 75)]);
+        ranges.remove(99, 10);
+        ranges.remove(0, 1);
+        assert_eq!(ranges.iter().collect::<Vec<_>>(), vec![(1, 4), (25, 74)]);
+        assert_eq!(ranges.num_pages(), 78);
+
+        // Removing pages which are not in the set does nothing.
+        ranges.remove(10, 10);
+        assert_eq!(ranges.num_pages(), 78);
+
+        ranges.clear();
+        assert_eq!(ranges.iter().count(), 0);
+    }
+
     #[test]
     fn test_remove_range() {
         let page_size: usize = 0x1000;
@@ -151,19 +151,18 @@ impl DiskProperties {
     pub fn cache_type(&self) -> CacheType {
         self.cache_type
     }
-}
 
-impl Drop for DiskProperties {
-    fn drop(&mut self) {
+    // Writes the data cached on the host out to the physical media, for writeback disks.
+    fn flush_cache(&mut self) {
         match self.cache_type {
             CacheType::Writeback => {
                 // flush() first to force any cached data out.
                 if self.file.flush().is_err() {
-                    error!("Failed to flush block data on drop.");
+                    error!("Failed to flush block data.");
                 }
                 // Sync data out to physical media on host.
                 if self.file.sync_all().is_err() {
-                    error!("Failed to sync block data on drop.")
+                    error!("Failed to sync block data.")
                 }
                 METRICS.block.flush_count.inc();
             }
@@ -174,6 +173,12 @@ impl Drop for DiskProperties {
     }
 }
 
+impl Drop for DiskProperties {
+    fn drop(&mut self) {
+        self.flush_cache();
+    }
+}
+
 /// Virtio device for exposing block level read/write operations on a host file.
 pub struct Block {
     // Host file and properties.
@@ -409,6 +414,12 @@ impl Block {
         Ok(())
     }
 
+    /// Writes the data cached on the host out to the backing file, as done when the device
+    /// is dropped.
+    pub fn flush_backend(&mut self) {
+        self.disk.flush_cache();
+    }
+
     /// Updates the parameters for the rate limiter
     pub fn update_rate_limiter(&mut self, bytes: BucketUpdate, ops: BucketUpdate) {
         self.rate_limiter.update_buckets(bytes, ops);
@@ -437,6 +448,16 @@ impl Block {
     pub fn cache_type(&self) -> CacheType {
         self.disk.cache_type()
     }
+
+    /// Provides the path of the host file backing this block device.
+    pub fn file_path(&self) -> &String {
+        self.disk.file_path()
+    }
+
+    /// Provides the rate limiter of this block device.
+    pub fn rate_limiter(&self) -> &RateLimiter {
+        &self.rate_limiter
+    }
 }
 
 impl VirtioDevice for Block {
@@ -1014,6 +1035,24 @@ pub(crate) mod tests {
         }
     }
 
+    #[test]
+    fn test_flush_backend() {
+        let f = TempFile::new().unwrap();
+        f.as_file().set_len(SECTOR_SIZE).unwrap();
+        let path = String::from(f.as_path().to_str().unwrap());
+
+        // Flushing an unsafe disk is a noop.
+        let mut block = default_block();
+        let flush_count = METRICS.block.flush_count.count();
+        block.flush_backend();
+        assert_eq!(METRICS.block.flush_count.count(), flush_count);
+
+        // The cached data of writeback disks is synced to the backing file.
+        block.disk = DiskProperties::new(path, false, CacheType::Writeback).unwrap();
+        block.flush_backend();
+        assert!(METRICS.block.flush_count.count() > flush_count);
+    }
+
     #[test]
     fn test_get_device_id() {
         let mut block = default_block();
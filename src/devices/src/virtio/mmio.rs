@@ -79,6 +79,12 @@ impl MmioTransport {
         self.device.clone()
     }
 
+    /// Whether a guest driver is driving the device, i.e. it completed the initialization
+    /// sequence and neither reset the device nor marked it as failed since.
+    pub fn is_driver_active(&self) -> bool {
+        self.check_device_status(device_status::DRIVER_OK, device_status::FAILED)
+    }
+
     fn check_device_status(&self, set: u32, clr: u32) -> bool {
         self.device_status & (set | clr) == set
     }
@@ -845,6 +851,23 @@ pub(crate) mod tests {
         assert!(d.locked_device().is_activated());
     }
 
+    #[test]
+    fn test_bus_device_driver_active() {
+        let m = GuestMemoryMmap::from_ranges(&[(GuestAddress(0), 0x1000)]).unwrap();
+        let mut d = MmioTransport::new(m, Arc::new(Mutex::new(DummyDevice::new())));
+        let mut buf = vec![0; 4];
+        assert!(!d.is_driver_active());
+
+        activate_device(&mut d);
+        assert!(d.is_driver_active());
+
+        // The driver lets go of the device by resetting it. The device doesn't support reset,
+        // so it is left marked as failed instead.
+        write_le_u32(&mut buf[..], 0x0);
+        d.write(0x70, &buf[..]);
+        assert!(!d.is_driver_active());
+    }
+
     #[test]
     fn test_get_avail_features() {
         let dummy_dev = DummyDevice::new();
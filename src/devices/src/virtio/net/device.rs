@@ -8,35 +8,56 @@
 use crate::virtio::net::tap::Tap;
 #[cfg(test)]
 use crate::virtio::net::test_utils::Mocks;
+use crate::virtio::net::unix::UnixSocket;
 use crate::virtio::net::Error;
 use crate::virtio::net::Result;
-use crate::virtio::net::{MAX_BUFFER_SIZE, QUEUE_SIZE, QUEUE_SIZES, RX_INDEX, TX_INDEX};
+use crate::virtio::net::{HostEndpoint, NetBackend};
+use crate::virtio::net::{
+    CTRL_INDEX, MAX_BUFFER_SIZE, QUEUE_SIZE, QUEUE_SIZES, RX_INDEX, TX_INDEX,
+};
 use crate::virtio::{
-    ActivateResult, DeviceState, Queue, VirtioDevice, TYPE_NET, VIRTIO_MMIO_INT_VRING,
+    ActivateResult, DeviceState, Queue, VirtioDevice, TYPE_NET, VIRTIO_MMIO_INT_CONFIG,
+    VIRTIO_MMIO_INT_VRING,
 };
 use crate::{report_net_event_fail, Error as DeviceError};
 
-use dumbo::pdu::ethernet::EthernetFrame;
+use dumbo::pdu::arp::{EthIPv4ArpFrame, ETH_IPV4_FRAME_LEN};
+use dumbo::pdu::ethernet::{EthernetFrame, ETHERTYPE_ARP, ETHERTYPE_IPV4, PAYLOAD_OFFSET};
+use dumbo::pdu::ipv4::IPv4Packet;
 use libc::EAGAIN;
-use logger::{error, warn, IncMetric, METRICS};
+use logger::{error, warn, IncMetric, NetInterfaceMetrics, METRICS};
+use mmds::data_store::Mmds;
 use mmds::ns::MmdsNetworkStack;
 use rate_limiter::{BucketUpdate, RateLimiter, TokenType};
-#[cfg(not(test))]
 use std::io;
 use std::io::{Read, Write};
+use std::net::Ipv4Addr;
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use std::{cmp, mem, result};
+use timerfd::{ClockId, SetTimeFlags, TimerFd, TimerState};
 use utils::eventfd::EventFd;
 use utils::net::mac::{MacAddr, MAC_ADDR_LEN};
 use virtio_gen::virtio_net::{
-    virtio_net_hdr_v1, VIRTIO_F_VERSION_1, VIRTIO_NET_F_CSUM, VIRTIO_NET_F_GUEST_CSUM,
-    VIRTIO_NET_F_GUEST_TSO4, VIRTIO_NET_F_GUEST_UFO, VIRTIO_NET_F_HOST_TSO4, VIRTIO_NET_F_HOST_UFO,
-    VIRTIO_NET_F_MAC,
+    virtio_net_hdr_v1, VIRTIO_F_VERSION_1, VIRTIO_NET_CTRL_ANNOUNCE, VIRTIO_NET_CTRL_ANNOUNCE_ACK,
+    VIRTIO_NET_ERR, VIRTIO_NET_F_CTRL_VQ, VIRTIO_NET_F_GUEST_ANNOUNCE, VIRTIO_NET_F_MAC,
+    VIRTIO_NET_F_STATUS, VIRTIO_NET_OK, VIRTIO_NET_S_ANNOUNCE, VIRTIO_NET_S_LINK_UP,
 };
 use vm_memory::{ByteValued, Bytes, GuestAddress, GuestMemoryError, GuestMemoryMmap};
 
+// See https://tools.ietf.org/html/rfc903.
+const ETHERTYPE_RARP: u16 = 0x8035;
+const OPER_RARP_REQUEST: u16 = 0x0003;
+// Length of the frames sent on behalf of the guest to announce its presence.
+const ANNOUNCE_FRAME_LEN: usize = PAYLOAD_OFFSET + ETH_IPV4_FRAME_LEN;
+// Number of frames sent on behalf of the guest for each announcement, since any of them may be
+// lost while the network converges.
+const ANNOUNCE_FRAME_COUNT: u8 = 5;
+// Interval between the frames of an announcement.
+const ANNOUNCE_INTERVAL_MS: u64 = 100;
+
 enum FrontendError {
     AddUsed,
     DescriptorChainTooSmall,
@@ -45,6 +66,23 @@ enum FrontendError {
     ReadOnlyDescriptor,
 }
 
+// Opens the TAP interface named `tap_if_name` and configures it as a net device backend.
+pub(crate) fn open_tap(tap_if_name: &str) -> Result<Tap> {
+    let tap = Tap::open_named(tap_if_name).map_err(Error::TapOpen)?;
+
+    // Set offload flags to match the virtio features advertised by the tap backend.
+    tap.set_offload(
+        net_gen::TUN_F_CSUM | net_gen::TUN_F_UFO | net_gen::TUN_F_TSO4 | net_gen::TUN_F_TSO6,
+    )
+    .map_err(Error::TapSetOffload)?;
+
+    let vnet_hdr_size = vnet_hdr_len() as i32;
+    tap.set_vnet_hdr_size(vnet_hdr_size)
+        .map_err(Error::TapSetVnetHdrSize)?;
+
+    Ok(tap)
+}
+
 pub(crate) fn vnet_hdr_len() -> usize {
     mem::size_of::<virtio_net_hdr_v1>()
 }
@@ -67,6 +105,77 @@ fn frame_bytes_from_buf_mut(buf: &mut [u8]) -> Result<&mut [u8]> {
     }
 }
 
+// Returns the IPv4 address used by the sender of `frame`, if it is sent from `guest_mac` and
+// carries one.
+fn guest_ipv4_from_frame(frame: &[u8], guest_mac: MacAddr) -> Option<Ipv4Addr> {
+    let eth_frame = EthernetFrame::from_bytes(frame).ok()?;
+    if eth_frame.src_mac() != guest_mac {
+        return None;
+    }
+    let addr = match eth_frame.ethertype() {
+        ETHERTYPE_ARP => {
+            EthIPv4ArpFrame::request_from_bytes(eth_frame.payload().get(..ETH_IPV4_FRAME_LEN)?)
+                .ok()?
+                .spa()
+        }
+        ETHERTYPE_IPV4 => IPv4Packet::from_bytes(eth_frame.payload(), false)
+            .ok()?
+            .source_address(),
+        _ => return None,
+    };
+    if addr.is_unspecified() {
+        None
+    } else {
+        Some(addr)
+    }
+}
+
+// Writes to `buf` a broadcast frame announcing `guest_mac` to the network: a gratuitous ARP
+// request when the guest IPv4 address is known, or a RARP request otherwise.
+fn write_announce_frame(
+    buf: &mut [u8],
+    guest_mac: MacAddr,
+    guest_ipv4: Option<Ipv4Addr>,
+) -> Result<()> {
+    let broadcast_mac = MacAddr::from_bytes_unchecked(&[0xff; MAC_ADDR_LEN]);
+    let ethertype = if guest_ipv4.is_some() {
+        ETHERTYPE_ARP
+    } else {
+        ETHERTYPE_RARP
+    };
+    let mut eth_frame = EthernetFrame::write_incomplete(buf, broadcast_mac, guest_mac, ethertype)
+        .map_err(|_| Error::AnnounceFrame)?;
+    let arp_buf = eth_frame
+        .inner_mut()
+        .payload_mut()
+        .get_mut(..ETH_IPV4_FRAME_LEN)
+        .ok_or(Error::AnnounceFrame)?;
+    match guest_ipv4 {
+        Some(addr) => {
+            EthIPv4ArpFrame::write_request(
+                arp_buf,
+                guest_mac,
+                addr,
+                MacAddr::from_bytes_unchecked(&[0; MAC_ADDR_LEN]),
+                addr,
+            )
+            .map_err(|_| Error::AnnounceFrame)?;
+        }
+        None => {
+            EthIPv4ArpFrame::write_request(
+                arp_buf,
+                guest_mac,
+                Ipv4Addr::UNSPECIFIED,
+                guest_mac,
+                Ipv4Addr::UNSPECIFIED,
+            )
+            .map_err(|_| Error::AnnounceFrame)?
+            .set_operation(OPER_RARP_REQUEST);
+        }
+    }
+    Ok(())
+}
+
 // This initializes to all 0 the VNET hdr part of a buf.
 fn init_vnet_hdr(buf: &mut [u8]) {
     // The buffer should be larger than vnet_hdr_len.
@@ -77,14 +186,17 @@ fn init_vnet_hdr(buf: &mut [u8]) {
 }
 
 #[derive(Clone, Copy)]
+#[repr(C)]
 pub struct ConfigSpace {
     pub guest_mac: [u8; MAC_ADDR_LEN],
+    pub status: u16,
 }
 
 impl Default for ConfigSpace {
     fn default() -> ConfigSpace {
         ConfigSpace {
             guest_mac: [0; MAC_ADDR_LEN],
+            status: VIRTIO_NET_S_LINK_UP as u16,
         }
     }
 }
@@ -94,7 +206,7 @@ unsafe impl ByteValued for ConfigSpace {}
 pub struct Net {
     pub(crate) id: String,
 
-    pub(crate) tap: Tap,
+    pub(crate) backend: Box<dyn NetBackend>,
 
     pub(crate) avail_features: u64,
     pub(crate) acked_features: u64,
@@ -119,6 +231,13 @@ pub struct Net {
 
     pub(crate) config_space: ConfigSpace,
     pub(crate) guest_mac: Option<MacAddr>,
+    // First IPv4 address seen in the frames sent by the guest.
+    pub(crate) guest_ipv4: Option<Ipv4Addr>,
+    // Paces the frames sent on behalf of the guest to announce it.
+    pub(crate) announce_timer: TimerFd,
+    announce_frames_left: u8,
+
+    pub(crate) metrics: Arc<NetInterfaceMetrics>,
 
     pub(crate) device_state: DeviceState,
     pub(crate) activate_evt: EventFd,
@@ -130,34 +249,20 @@ pub struct Net {
 }
 
 impl Net {
-    /// Create a new virtio network device with the given TAP interface.
-    pub fn new_with_tap(
+    /// Create a new virtio network device with the given backend.
+    pub fn new(
         id: String,
-        tap_if_name: String,
+        backend: Box<dyn NetBackend>,
         guest_mac: Option<&MacAddr>,
         rx_rate_limiter: RateLimiter,
         tx_rate_limiter: RateLimiter,
-        allow_mmds_requests: bool,
+        mmds: Option<Arc<Mutex<Mmds>>>,
     ) -> Result<Self> {
-        let tap = Tap::open_named(&tap_if_name).map_err(Error::TapOpen)?;
-
-        // Set offload flags to match the virtio features below.
-        tap.set_offload(
-            net_gen::TUN_F_CSUM | net_gen::TUN_F_UFO | net_gen::TUN_F_TSO4 | net_gen::TUN_F_TSO6,
-        )
-        .map_err(Error::TapSetOffload)?;
-
-        let vnet_hdr_size = vnet_hdr_len() as i32;
-        tap.set_vnet_hdr_size(vnet_hdr_size)
-            .map_err(Error::TapSetVnetHdrSize)?;
-
-        let mut avail_features = 1 << VIRTIO_NET_F_GUEST_CSUM
-            | 1 << VIRTIO_NET_F_CSUM
-            | 1 << VIRTIO_NET_F_GUEST_TSO4
-            | 1 << VIRTIO_NET_F_GUEST_UFO
-            | 1 << VIRTIO_NET_F_HOST_TSO4
-            | 1 << VIRTIO_NET_F_HOST_UFO
-            | 1 << VIRTIO_F_VERSION_1;
+        let mut avail_features = backend.offload_features()
+            | 1 << VIRTIO_F_VERSION_1
+            | 1 << VIRTIO_NET_F_STATUS
+            | 1 << VIRTIO_NET_F_CTRL_VQ
+            | 1 << VIRTIO_NET_F_GUEST_ANNOUNCE;
 
         let mut config_space = ConfigSpace::default();
         if let Some(mac) = guest_mac {
@@ -174,14 +279,12 @@ impl Net {
 
         let queues = QUEUE_SIZES.iter().map(|&s| Queue::new(s)).collect();
 
-        let mmds_ns = if allow_mmds_requests {
-            Some(MmdsNetworkStack::new_with_defaults(None))
-        } else {
-            None
-        };
+        // Guest requests to the MMDS are only intercepted when a data store is provided.
+        let mmds_ns = mmds.map(|mmds| MmdsNetworkStack::new_with_defaults(None, mmds));
+        let metrics = METRICS.net_interfaces.register(&id);
         Ok(Net {
             id,
-            tap,
+            backend,
             avail_features,
             acked_features: 0u64,
             queues,
@@ -201,27 +304,204 @@ impl Net {
             config_space,
             mmds_ns,
             guest_mac: guest_mac.copied(),
+            guest_ipv4: None,
+            announce_timer: TimerFd::new_custom(ClockId::Monotonic, true, true)
+                .map_err(Error::EventFd)?,
+            announce_frames_left: 0,
+            metrics,
 
             #[cfg(test)]
             mocks: Mocks::default(),
         })
     }
 
+    /// Create a new virtio network device with the given TAP interface.
+    pub fn new_with_tap(
+        id: String,
+        tap_if_name: String,
+        guest_mac: Option<&MacAddr>,
+        rx_rate_limiter: RateLimiter,
+        tx_rate_limiter: RateLimiter,
+        mmds: Option<Arc<Mutex<Mmds>>>,
+    ) -> Result<Self> {
+        let tap = open_tap(&tap_if_name)?;
+
+        Self::new(
+            id,
+            Box::new(tap),
+            guest_mac,
+            rx_rate_limiter,
+            tx_rate_limiter,
+            mmds,
+        )
+    }
+
+    /// Create a new virtio network device which exchanges frames with the peer listening on
+    /// the Unix socket at `socket_path`.
+    pub fn new_with_unix_socket(
+        id: String,
+        socket_path: String,
+        guest_mac: Option<&MacAddr>,
+        rx_rate_limiter: RateLimiter,
+        tx_rate_limiter: RateLimiter,
+        mmds: Option<Arc<Mutex<Mmds>>>,
+    ) -> Result<Self> {
+        let socket = UnixSocket::connect(&socket_path).map_err(Error::UnixSocketConnect)?;
+
+        Self::new(
+            id,
+            Box::new(socket),
+            guest_mac,
+            rx_rate_limiter,
+            tx_rate_limiter,
+            mmds,
+        )
+    }
+
+    /// Replaces the host side of this device with `backend`, which must offer the same offload
+    /// features as the current one, since the guest driver has already negotiated them.
+    /// Returns the previous backend.
+    pub(crate) fn replace_backend(
+        &mut self,
+        backend: Box<dyn NetBackend>,
+    ) -> Result<Box<dyn NetBackend>> {
+        if backend.offload_features() != self.backend.offload_features() {
+            return Err(Error::BackendFeaturesMismatch);
+        }
+        Ok(mem::replace(&mut self.backend, backend))
+    }
+
+    /// Provides the metrics of this interface.
+    pub fn metrics(&self) -> &NetInterfaceMetrics {
+        &self.metrics
+    }
+
     /// Provides the ID of this net device.
     pub fn id(&self) -> &String {
         &self.id
     }
 
+    /// Provides the host endpoint this net device is attached to.
+    pub fn host_endpoint(&self) -> HostEndpoint {
+        self.backend.endpoint()
+    }
+
     /// Provides the MAC of this net device.
     pub fn guest_mac(&self) -> Option<&MacAddr> {
         self.guest_mac.as_ref()
     }
 
+    /// Provides the rate limiter applied to the frames received by the guest.
+    pub fn rx_rate_limiter(&self) -> &RateLimiter {
+        &self.rx_rate_limiter
+    }
+
+    /// Provides the rate limiter applied to the frames sent by the guest.
+    pub fn tx_rate_limiter(&self) -> &RateLimiter {
+        &self.tx_rate_limiter
+    }
+
+    /// Provides a reference to the `MmdsNetworkStack`.
+    pub fn mmds_ns(&self) -> Option<&MmdsNetworkStack> {
+        self.mmds_ns.as_ref()
+    }
+
     /// Provides a mutable reference to the `MmdsNetworkStack`.
     pub fn mmds_ns_mut(&mut self) -> Option<&mut MmdsNetworkStack> {
         self.mmds_ns.as_mut()
     }
 
+    /// Provides the link state reported to the guest.
+    pub fn link_up(&self) -> bool {
+        self.config_space.status & VIRTIO_NET_S_LINK_UP as u16 != 0
+    }
+
+    /// Sets the link state reported to the guest and notifies the guest driver of the change.
+    /// While the link is down, frames sent by the guest are dropped and frames coming from the
+    /// host are left in the backend.
+    pub fn set_link_up(&mut self, link_up: bool) -> Result<()> {
+        if link_up == self.link_up() {
+            return Ok(());
+        }
+        let old_status = self.config_space.status;
+        if link_up {
+            self.config_space.status |= VIRTIO_NET_S_LINK_UP as u16;
+        } else {
+            self.config_space.status &= !(VIRTIO_NET_S_LINK_UP as u16);
+        }
+
+        if self.is_activated() {
+            if let Err(e) = self.signal_config_change() {
+                // The guest driver was not told, so keep reporting the previous state.
+                self.config_space.status = old_status;
+                return Err(e);
+            }
+            if link_up {
+                // The backend fd is edge triggered, so pick up the frames that arrived meanwhile.
+                self.process_tap_rx_event();
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes the guest known to the network segment it is attached to, e.g. after it was
+    /// restored on another host. A driver which negotiated `VIRTIO_NET_F_GUEST_ANNOUNCE` is asked
+    /// to send the announcement itself. Otherwise, gratuitous ARP requests (or RARP requests, if
+    /// the guest IPv4 address is unknown) are sent on its behalf, `ANNOUNCE_FRAME_COUNT` times.
+    pub fn announce(&mut self) -> Result<()> {
+        if !self.is_activated() || !self.link_up() {
+            return Ok(());
+        }
+        if self.acked_features & (1 << VIRTIO_NET_F_GUEST_ANNOUNCE) != 0 {
+            self.config_space.status |= VIRTIO_NET_S_ANNOUNCE as u16;
+            return self.signal_config_change();
+        }
+        // There is nothing to announce before the guest has a MAC address.
+        if self.guest_mac.is_none() {
+            return Ok(());
+        }
+
+        self.announce_frames_left = ANNOUNCE_FRAME_COUNT;
+        self.send_announce_frame()
+    }
+
+    // Sends one of the frames of an announcement, and schedules the next one.
+    fn send_announce_frame(&mut self) -> Result<()> {
+        let guest_mac = match self.guest_mac {
+            Some(mac) if self.announce_frames_left > 0 && self.link_up() => mac,
+            _ => {
+                self.announce_frames_left = 0;
+                return Ok(());
+            }
+        };
+        self.announce_frames_left -= 1;
+        if self.announce_frames_left > 0 {
+            self.announce_timer.set_state(
+                TimerState::Oneshot(Duration::from_millis(ANNOUNCE_INTERVAL_MS)),
+                SetTimeFlags::Default,
+            );
+        }
+
+        let mut buf = vec![0u8; vnet_hdr_len() + ANNOUNCE_FRAME_LEN];
+        write_announce_frame(
+            frame_bytes_from_buf_mut(&mut buf)?,
+            guest_mac,
+            self.guest_ipv4,
+        )?;
+        Self::write_to_backend(self.backend.as_mut(), &buf, &self.metrics).map_err(Error::IO)?;
+        Ok(())
+    }
+
+    fn signal_config_change(&mut self) -> Result<()> {
+        self.interrupt_status
+            .fetch_or(VIRTIO_MMIO_INT_CONFIG as usize, Ordering::SeqCst);
+        self.interrupt_evt.write(1).map_err(|e| {
+            error!("Failed to signal config change: {:?}", e);
+            METRICS.net.event_fails.inc();
+            Error::EventFd(e)
+        })
+    }
+
     fn signal_used_queue(&mut self) -> result::Result<(), DeviceError> {
         self.interrupt_status
             .fetch_or(VIRTIO_MMIO_INT_VRING as usize, Ordering::SeqCst);
@@ -251,6 +531,7 @@ impl Net {
         // budget and rate limiting is in effect.
         if !self.rx_rate_limiter.consume(1, TokenType::Ops) {
             METRICS.net.rx_rate_limiter_throttled.inc();
+            self.metrics.rx_rate_limiter_throttled.inc();
             return false;
         }
         // If limiter.consume() fails it means there is no more TokenType::Bytes
@@ -262,6 +543,7 @@ impl Net {
             // revert the OPS consume()
             self.rx_rate_limiter.manual_replenish(1, TokenType::Ops);
             METRICS.net.rx_rate_limiter_throttled.inc();
+            self.metrics.rx_rate_limiter_throttled.inc();
             return false;
         }
 
@@ -321,6 +603,7 @@ impl Net {
                         _ => &METRICS.net.rx_fails,
                     }
                     .inc();
+                    self.metrics.rx_fails.inc();
                     result = Err(FrontendError::GuestMemory(e));
                     break;
                 }
@@ -331,6 +614,7 @@ impl Net {
         if result.is_ok() && !frame_slice.is_empty() {
             warn!("Receiving buffer is too small to hold frame of current size");
             METRICS.net.rx_fails.inc();
+            self.metrics.rx_fails.inc();
             result = Err(FrontendError::DescriptorChainTooSmall);
         }
 
@@ -345,6 +629,8 @@ impl Net {
         if result.is_ok() {
             METRICS.net.rx_bytes_count.add(frame_len);
             METRICS.net.rx_packets_count.inc();
+            self.metrics.rx_bytes_count.add(frame_len);
+            self.metrics.rx_packets_count.inc();
         }
         result
     }
@@ -377,19 +663,22 @@ impl Net {
         mmds_ns: Option<&mut MmdsNetworkStack>,
         rate_limiter: &mut RateLimiter,
         frame_buf: &[u8],
-        tap: &mut Tap,
+        backend: &mut dyn NetBackend,
         guest_mac: Option<MacAddr>,
+        metrics: &NetInterfaceMetrics,
     ) -> Result<bool> {
         let checked_frame = |frame_buf| {
             frame_bytes_from_buf(frame_buf).map_err(|e| {
                 error!("VNET header missing in the TX frame.");
                 METRICS.net.tx_malformed_frames.inc();
+                metrics.tx_dropped.inc();
                 e
             })
         };
         if let Some(ns) = mmds_ns {
             if ns.detour_frame(checked_frame(frame_buf)?) {
                 METRICS.mmds.rx_accepted.inc();
+                metrics.tx_mmds_frames.inc();
 
                 // MMDS frames are not accounted by the rate limiter.
                 rate_limiter.manual_replenish(frame_buf.len() as u64, TokenType::Bytes);
@@ -400,7 +689,7 @@ impl Net {
             }
         }
 
-        // This frame goes to the TAP.
+        // This frame goes to the backend.
 
         // Check for guest MAC spoofing.
         if let Some(mac) = guest_mac {
@@ -411,18 +700,34 @@ impl Net {
             });
         }
 
-        match tap.write(frame_buf) {
-            Ok(_) => {
+        if let Err(e) = Self::write_to_backend(backend, frame_buf, metrics) {
+            error!("Failed to write to tap: {:?}", e);
+        }
+        Ok(false)
+    }
+
+    // Writes `frame_buf` to the backend, accounting for it in both the global and the
+    // interface metrics.
+    fn write_to_backend(
+        backend: &mut dyn NetBackend,
+        frame_buf: &[u8],
+        metrics: &NetInterfaceMetrics,
+    ) -> io::Result<usize> {
+        match backend.write(frame_buf) {
+            Ok(len) => {
                 METRICS.net.tx_bytes_count.add(frame_buf.len());
                 METRICS.net.tx_packets_count.inc();
                 METRICS.net.tx_count.inc();
+                metrics.tx_bytes_count.add(frame_buf.len());
+                metrics.tx_packets_count.inc();
+                Ok(len)
             }
             Err(e) => {
-                error!("Failed to write to tap: {:?}", e);
                 METRICS.net.tap_write_fails.inc();
+                metrics.tap_write_fails.inc();
+                Err(e)
             }
-        };
-        Ok(false)
+        }
     }
 
     // We currently prioritize packets from the MMDS over regular network packets.
@@ -434,6 +739,7 @@ impl Net {
                 let len = len.get();
                 METRICS.mmds.tx_frames.inc();
                 METRICS.mmds.tx_bytes.add(len);
+                self.metrics.rx_mmds_frames.inc();
                 init_vnet_hdr(&mut self.rx_frame_buf);
                 return Ok(vnet_hdr_len() + len);
             }
@@ -443,6 +749,11 @@ impl Net {
     }
 
     fn process_rx(&mut self) -> result::Result<(), DeviceError> {
+        // Nothing reaches the guest while the link is down.
+        if !self.link_up() {
+            return Ok(());
+        }
+
         // Read as many frames as possible.
         loop {
             match self.read_from_mmds_or_tap() {
@@ -459,9 +770,17 @@ impl Net {
                     // unexpected.
                     match e.raw_os_error() {
                         Some(err) if err == EAGAIN => (),
+                        // The peer of a Unix socket backend hung up: there is nothing left to
+                        // receive, and the link stays silent until the backend is replaced.
+                        _ if e.kind() == io::ErrorKind::ConnectionReset => {
+                            warn!("The net backend peer hung up: {:?}", e);
+                            METRICS.net.tap_read_fails.inc();
+                            self.metrics.tap_read_fails.inc();
+                        }
                         _ => {
                             error!("Failed to read tap: {:?}", e);
                             METRICS.net.tap_read_fails.inc();
+                            self.metrics.tap_read_fails.inc();
                             return Err(DeviceError::FailedReadTap);
                         }
                     };
@@ -511,6 +830,7 @@ impl Net {
         // with the MMDS network stack.
         let mut process_rx_for_mmds = false;
         let mut raise_irq = false;
+        let link_up = self.link_up();
         let tx_queue = &mut self.queues[TX_INDEX];
 
         while let Some(head) = tx_queue.pop(mem) {
@@ -521,6 +841,7 @@ impl Net {
                 // avail ring, for later processing.
                 tx_queue.undo_pop();
                 METRICS.net.tx_rate_limiter_throttled.inc();
+                self.metrics.tx_rate_limiter_throttled.inc();
                 break;
             }
 
@@ -551,6 +872,7 @@ impl Net {
                 // avail ring, for later processing.
                 tx_queue.undo_pop();
                 METRICS.net.tx_rate_limiter_throttled.inc();
+                self.metrics.tx_rate_limiter_throttled.inc();
                 break;
             }
 
@@ -583,14 +905,34 @@ impl Net {
                 }
             }
 
-            let frame_consumed_by_mmds = Self::write_to_mmds_or_tap(
-                self.mmds_ns.as_mut(),
-                &mut self.tx_rate_limiter,
-                &self.tx_frame_buf[..read_count],
-                &mut self.tap,
-                self.guest_mac,
-            )
-            .unwrap_or_else(|_| false);
+            // The guest address is only needed to announce the guest on its behalf, and learning
+            // it from the first frame carrying one keeps the TX path cheap afterwards.
+            let guest_announces = self.acked_features & (1 << VIRTIO_NET_F_GUEST_ANNOUNCE) != 0;
+            if let (Some(guest_mac), None, false) =
+                (self.guest_mac, self.guest_ipv4, guest_announces)
+            {
+                if let Some(addr) = frame_bytes_from_buf(&self.tx_frame_buf[..read_count])
+                    .ok()
+                    .and_then(|frame| guest_ipv4_from_frame(frame, guest_mac))
+                {
+                    self.guest_ipv4 = Some(addr);
+                }
+            }
+
+            // Frames sent while the link is down are dropped.
+            if !link_up {
+                self.metrics.tx_dropped.inc();
+            }
+            let frame_consumed_by_mmds = link_up
+                && Self::write_to_mmds_or_tap(
+                    self.mmds_ns.as_mut(),
+                    &mut self.tx_rate_limiter,
+                    &self.tx_frame_buf[..read_count],
+                    self.backend.as_mut(),
+                    self.guest_mac,
+                    &self.metrics,
+                )
+                .unwrap_or_else(|_| false);
             if frame_consumed_by_mmds && !self.rx_deferred_frame {
                 // MMDS consumed this frame/request, let's also try to process the response.
                 process_rx_for_mmds = true;
@@ -616,6 +958,62 @@ impl Net {
         }
     }
 
+    fn process_ctrl(&mut self) -> result::Result<(), DeviceError> {
+        let mem = match self.device_state {
+            DeviceState::Activated(ref mem) => mem,
+            // This should never happen, it's been already validated in the event handler.
+            DeviceState::Inactive => unreachable!(),
+        };
+        // Devices restored from older snapshots don't have a control queue.
+        let ctrl_queue = match self.queues.get_mut(CTRL_INDEX) {
+            Some(queue) => queue,
+            None => return Ok(()),
+        };
+
+        let mut raise_irq = false;
+        while let Some(head) = ctrl_queue.pop(mem) {
+            let head_index = head.index;
+            // The command header holds the class and the command, followed by its arguments in
+            // device readable descriptors, and by a device writable byte for the acknowledgement.
+            let mut hdr = [0u8; 2];
+            let hdr_read = !head.is_write_only() && mem.read_slice(&mut hdr, head.addr).is_ok();
+            let mut ack_addr = None;
+            let mut next_desc = Some(head);
+            while let Some(desc) = next_desc {
+                if desc.is_write_only() {
+                    ack_addr = Some(desc.addr);
+                }
+                next_desc = desc.next_descriptor();
+            }
+
+            let ack = match (hdr_read, u32::from(hdr[0]), u32::from(hdr[1])) {
+                (true, VIRTIO_NET_CTRL_ANNOUNCE, VIRTIO_NET_CTRL_ANNOUNCE_ACK) => {
+                    self.config_space.status &= !(VIRTIO_NET_S_ANNOUNCE as u16);
+                    VIRTIO_NET_OK as u8
+                }
+                _ => {
+                    warn!("Unsupported net control command: {:?}", hdr);
+                    METRICS.net.event_fails.inc();
+                    VIRTIO_NET_ERR as u8
+                }
+            };
+            let used_len = match ack_addr {
+                Some(addr) if mem.write_obj(ack, addr).is_ok() => 1,
+                _ => 0,
+            };
+
+            ctrl_queue
+                .add_used(mem, head_index, used_len)
+                .map_err(DeviceError::QueueError)?;
+            raise_irq = true;
+        }
+
+        if raise_irq {
+            self.signal_used_queue()?;
+        }
+        Ok(())
+    }
+
     /// Updates the parameters for the rate limiters
     pub fn patch_rate_limiters(
         &mut self,
@@ -630,7 +1028,7 @@ impl Net {
 
     #[cfg(not(test))]
     fn read_tap(&mut self) -> io::Result<usize> {
-        self.tap.read(&mut self.rx_frame_buf)
+        self.backend.read(&mut self.rx_frame_buf)
     }
 
     pub fn process_rx_queue_event(&mut self) {
@@ -646,6 +1044,7 @@ impl Net {
                 self.resume_rx().unwrap_or_else(report_net_event_fail);
             } else {
                 METRICS.net.rx_rate_limiter_throttled.inc();
+                self.metrics.rx_rate_limiter_throttled.inc();
             }
         }
     }
@@ -670,6 +1069,7 @@ impl Net {
         // While limiter is blocked, don't process any more incoming.
         if self.rx_rate_limiter.is_blocked() {
             METRICS.net.rx_rate_limiter_throttled.inc();
+            self.metrics.rx_rate_limiter_throttled.inc();
             return;
         }
 
@@ -695,6 +1095,16 @@ impl Net {
             self.process_tx().unwrap_or_else(report_net_event_fail);
         } else {
             METRICS.net.tx_rate_limiter_throttled.inc();
+            self.metrics.tx_rate_limiter_throttled.inc();
+        }
+    }
+
+    pub fn process_ctrl_queue_event(&mut self) {
+        if let Err(e) = self.queue_evts[CTRL_INDEX].read() {
+            error!("Failed to get ctrl queue event: {:?}", e);
+            METRICS.net.event_fails.inc();
+        } else {
+            self.process_ctrl().unwrap_or_else(report_net_event_fail);
         }
     }
 
@@ -731,10 +1141,51 @@ impl Net {
         }
     }
 
+    pub fn process_announce_timer_event(&mut self) {
+        // Consume the expired timer.
+        self.announce_timer.read();
+        self.send_announce_frame()
+            .unwrap_or_else(report_net_event_fail);
+    }
+
+    /// Answers the MMDS requests which were waiting for a data store change and delivers the
+    /// responses to the guest.
+    pub fn process_mmds_update(&mut self) {
+        let has_pending = match self.mmds_ns.as_mut() {
+            Some(ns) => {
+                ns.process_pending_requests();
+                true
+            }
+            None => false,
+        };
+        if has_pending && self.is_activated() && !self.rx_deferred_frame {
+            self.process_rx().unwrap_or_else(report_net_event_fail);
+        }
+    }
+
+    /// Sends the frames the guest already queued for transmission, ahead of the microVM
+    /// stopping.
+    pub fn flush_tx(&mut self) {
+        if self.is_activated() {
+            self.process_tx().unwrap_or_else(report_net_event_fail);
+            // Send what the backend may still hold of the last frame.
+            if let Err(e) = self.backend.flush() {
+                error!("Failed to write to tap: {:?}", e);
+            }
+        }
+    }
+
     /// Process device virtio queue(s).
     pub fn process_virtio_queues(&mut self) {
         let _ = self.resume_rx();
         let _ = self.process_tx();
+        let _ = self.process_ctrl();
+    }
+}
+
+impl Drop for Net {
+    fn drop(&mut self) {
+        METRICS.net_interfaces.unregister(&self.id, &self.metrics);
     }
 }
 
@@ -795,7 +1246,8 @@ impl VirtioDevice for Net {
     fn write_config(&mut self, offset: u64, data: &[u8]) {
         let data_len = data.len() as u64;
         let config_space_bytes = self.config_space.as_mut_slice();
-        let config_len = config_space_bytes.len() as u64;
+        // Only the MAC address is writable by the driver.
+        let config_len = MAC_ADDR_LEN as u64;
         if offset + data_len > config_len {
             error!("Failed to write config space");
             METRICS.net.cfg_fails.inc();
@@ -834,6 +1286,7 @@ pub mod tests {
         frame_bytes_from_buf, frame_bytes_from_buf_mut, init_vnet_hdr, vnet_hdr_len,
     };
     use std::net::Ipv4Addr;
+    use std::os::unix::io::AsRawFd;
     use std::sync::atomic::Ordering;
     use std::time::Duration;
     use std::{io, mem, thread};
@@ -841,25 +1294,45 @@ pub mod tests {
     use crate::check_metric_after_block;
     use crate::virtio::net::test_utils::test::TestHelper;
     use crate::virtio::net::test_utils::{
-        check_used_queue_signal, default_net, if_index, inject_tap_tx_frame, set_mac, NetEvent,
-        NetQueue, ReadTapMock, TapTrafficSimulator,
+        check_used_queue_signal, default_net, if_index, inject_tap_tx_frame, set_mac, tap_if_name,
+        unix_socket_pair, NetEvent, NetQueue, ReadTapMock, TapTrafficSimulator,
     };
-    use crate::virtio::net::QUEUE_SIZES;
+    use crate::virtio::net::{UnixSocketType, QUEUE_SIZES};
     use crate::virtio::{
-        Net, VirtioDevice, MAX_BUFFER_SIZE, RX_INDEX, TX_INDEX, TYPE_NET, VIRTIO_MMIO_INT_VRING,
-        VIRTQ_DESC_F_WRITE,
+        Net, VirtioDevice, CTRL_INDEX, MAX_BUFFER_SIZE, RX_INDEX, TX_INDEX, TYPE_NET,
+        VIRTIO_MMIO_INT_CONFIG, VIRTIO_MMIO_INT_VRING, VIRTQ_DESC_F_WRITE,
     };
     use dumbo::pdu::arp::{EthIPv4ArpFrame, ETH_IPV4_FRAME_LEN};
     use dumbo::pdu::ethernet::ETHERTYPE_ARP;
     use logger::{IncMetric, METRICS};
+    use polly::event_manager::EventManager;
     use rate_limiter::{RateLimiter, TokenBucket, TokenType};
     use virtio_gen::virtio_net::{
         virtio_net_hdr_v1, VIRTIO_F_VERSION_1, VIRTIO_NET_F_CSUM, VIRTIO_NET_F_GUEST_CSUM,
         VIRTIO_NET_F_GUEST_TSO4, VIRTIO_NET_F_GUEST_UFO, VIRTIO_NET_F_HOST_TSO4,
-        VIRTIO_NET_F_HOST_UFO, VIRTIO_NET_F_MAC,
+        VIRTIO_NET_F_HOST_UFO, VIRTIO_NET_F_MAC, VIRTIO_NET_F_STATUS,
     };
     use vm_memory::{Address, GuestMemory};
 
+    // Writes the frame `payload` to the tx queue, from the guest with `guest_mac`.
+    fn guest_tx_frame(th: &mut TestHelper, guest_mac: MacAddr, ethertype: u16, payload: &[u8]) {
+        let mut frame = vec![0u8; vnet_hdr_len() + PAYLOAD_OFFSET + payload.len()];
+        {
+            let mut eth_frame = EthernetFrame::write_incomplete(
+                frame_bytes_from_buf_mut(&mut frame).unwrap(),
+                MacAddr::parse_str("22:22:22:22:22:22").unwrap(),
+                guest_mac,
+                ethertype,
+            )
+            .unwrap();
+            eth_frame.inner_mut().payload_mut()[..payload.len()].copy_from_slice(payload);
+        }
+        let desc_idx = th.txq.used.idx.get();
+        th.add_desc_chain(NetQueue::Tx, 0, &[(desc_idx, frame.len() as u32, 0)]);
+        th.txq.dtable[desc_idx as usize].set_data(&frame);
+        th.event_manager.run_with_timeout(100).unwrap();
+    }
+
     impl Net {
         pub fn read_tap(&mut self) -> io::Result<usize> {
             match &self.mocks.read_tap {
@@ -871,7 +1344,7 @@ pub mod tests {
                     io::ErrorKind::Other,
                     "Read tap synthetically failed.",
                 )),
-                ReadTapMock::TapFrame => self.tap.read(&mut self.rx_frame_buf),
+                ReadTapMock::TapFrame => self.backend.read(&mut self.rx_frame_buf),
             }
         }
     }
@@ -927,6 +1400,9 @@ pub mod tests {
             | 1 << VIRTIO_NET_F_GUEST_UFO
             | 1 << VIRTIO_NET_F_HOST_TSO4
             | 1 << VIRTIO_NET_F_HOST_UFO
+            | 1 << VIRTIO_NET_F_STATUS
+            | 1 << VIRTIO_NET_F_CTRL_VQ
+            | 1 << VIRTIO_NET_F_GUEST_ANNOUNCE
             | 1 << VIRTIO_F_VERSION_1;
 
         assert_eq!(net.avail_features_by_page(0), features as u32);
@@ -989,6 +1465,243 @@ pub mod tests {
         new_config_read = [0u8; 6];
         net.read_config(0, &mut new_config_read);
         assert_eq!(new_config, new_config_read);
+
+        // The link status is read-only.
+        net.write_config(MAC_ADDR_LEN as u64, &[0, 0]);
+        assert!(net.link_up());
+    }
+
+    #[test]
+    fn test_link_state() {
+        let mut th = TestHelper::default();
+        th.activate_net();
+        th.net().mocks.set_read_tap(ReadTapMock::TapFrame);
+
+        let mut status = [0u8; 2];
+        th.net().read_config(MAC_ADDR_LEN as u64, &mut status);
+        assert_eq!(status, [1, 0]);
+
+        // Taking the link down raises a config change interrupt.
+        th.net().set_link_up(false).unwrap();
+        assert!(!th.net().link_up());
+        th.net().read_config(MAC_ADDR_LEN as u64, &mut status);
+        assert_eq!(status, [0, 0]);
+        assert_eq!(
+            th.net().interrupt_status().load(Ordering::SeqCst),
+            VIRTIO_MMIO_INT_CONFIG as usize
+        );
+        check_used_queue_signal(&th.net(), 1);
+
+        // Frames from the host don't reach the guest while the link is down.
+        th.add_desc_chain(NetQueue::Rx, 0, &[(0, 1000, VIRTQ_DESC_F_WRITE)]);
+        let frame = inject_tap_tx_frame(&th.net(), 1000);
+        th.event_manager.run_with_timeout(100).unwrap();
+        assert_eq!(th.rxq.used.idx.get(), 0);
+
+        // They are delivered once the link is back up.
+        th.net().set_link_up(true).unwrap();
+        assert!(th.net().link_up());
+        assert_eq!(th.rxq.used.idx.get(), 1);
+        th.rxq.check_used_elem(0, 0, frame.len() as u32);
+        th.rxq.dtable[0].check_data(&frame);
+    }
+
+    #[test]
+    fn test_interface_metrics() {
+        let mut th = TestHelper::default();
+        let id = th.net().id().clone();
+        assert!(Arc::ptr_eq(
+            &METRICS.net_interfaces.get(&id).unwrap(),
+            &th.net().metrics
+        ));
+        th.activate_net();
+
+        // Frames sent while the link is down are dropped.
+        th.net().set_link_up(false).unwrap();
+        th.add_desc_chain(NetQueue::Tx, 0, &[(0, 1000, 0)]);
+        th.event_manager.run_with_timeout(100).unwrap();
+        assert_eq!(th.txq.used.idx.get(), 1);
+        assert_eq!(th.net().metrics().tx_dropped.count(), 1);
+        assert_eq!(th.net().metrics().tx_packets_count.count(), 0);
+
+        // The metrics are no longer flushed once the device is gone.
+        drop(th);
+        assert!(METRICS.net_interfaces.get(&id).is_none());
+    }
+
+    #[test]
+    fn test_ctrl_queue() {
+        let mut th = TestHelper::default();
+        th.activate_net();
+        th.net().config_space.status |= VIRTIO_NET_S_ANNOUNCE as u16;
+
+        // An unsupported command is rejected.
+        th.add_desc_chain(NetQueue::Ctrl, 0, &[(0, 2, 0), (1, 1, VIRTQ_DESC_F_WRITE)]);
+        th.ctrlq.dtable[0].set_data(&[0, 0]);
+        th.event_manager.run_with_timeout(100).unwrap();
+        assert_eq!(th.ctrlq.used.idx.get(), 1);
+        th.ctrlq.check_used_elem(0, 0, 1);
+        th.ctrlq.dtable[1].check_data(&[VIRTIO_NET_ERR as u8]);
+        assert_ne!(
+            th.net().config_space.status & VIRTIO_NET_S_ANNOUNCE as u16,
+            0
+        );
+
+        // Acknowledging the announcement clears the status bit.
+        th.add_desc_chain(NetQueue::Ctrl, 0, &[(2, 2, 0), (3, 1, VIRTQ_DESC_F_WRITE)]);
+        th.ctrlq.dtable[2].set_data(&[
+            VIRTIO_NET_CTRL_ANNOUNCE as u8,
+            VIRTIO_NET_CTRL_ANNOUNCE_ACK as u8,
+        ]);
+        check_metric_after_block!(
+            &METRICS.net.event_fails,
+            0,
+            th.simulate_event(NetEvent::CtrlQueue)
+        );
+        assert_eq!(th.ctrlq.used.idx.get(), 2);
+        th.ctrlq.check_used_elem(1, 2, 1);
+        th.ctrlq.dtable[3].check_data(&[VIRTIO_NET_OK as u8]);
+        assert_eq!(
+            th.net().config_space.status & VIRTIO_NET_S_ANNOUNCE as u16,
+            0
+        );
+        assert!(th.net().link_up());
+    }
+
+    #[test]
+    fn test_announce_guest_announce() {
+        let mut th = TestHelper::default();
+        // Nothing is announced before activation.
+        th.net().announce().unwrap();
+        assert_eq!(
+            th.net().config_space.status & VIRTIO_NET_S_ANNOUNCE as u16,
+            0
+        );
+
+        th.net().acked_features = 1 << VIRTIO_NET_F_GUEST_ANNOUNCE;
+        th.activate_net();
+        th.net().announce().unwrap();
+        assert_ne!(
+            th.net().config_space.status & VIRTIO_NET_S_ANNOUNCE as u16,
+            0
+        );
+        assert_eq!(
+            th.net().interrupt_status().load(Ordering::SeqCst),
+            VIRTIO_MMIO_INT_CONFIG as usize
+        );
+    }
+
+    #[test]
+    fn test_announce_fallback() {
+        let (backend, mut peer) = unix_socket_pair(UnixSocketType::SeqPacket);
+        let guest_mac = MacAddr::parse_str("11:22:33:44:55:66").unwrap();
+        let net = Net::new(
+            "unix-net".to_string(),
+            Box::new(backend),
+            Some(&guest_mac),
+            RateLimiter::default(),
+            RateLimiter::default(),
+            None,
+        )
+        .unwrap();
+        let mut th = TestHelper::with_net(net);
+        th.activate_net();
+        let mut peer_buf = [0u8; MAX_BUFFER_SIZE];
+
+        // The guest IPv4 address is unknown, so a RARP request is sent.
+        th.net().announce().unwrap();
+        let len = peer.read(&mut peer_buf).unwrap();
+        assert_eq!(len, ANNOUNCE_FRAME_LEN);
+        // The frame is accounted for as sent by the interface.
+        assert_eq!(th.net().metrics.tx_packets_count.count(), 1);
+        assert_eq!(
+            th.net().metrics.tx_bytes_count.count(),
+            vnet_hdr_len() + ANNOUNCE_FRAME_LEN
+        );
+        let eth_frame = EthernetFrame::from_bytes(&peer_buf[..len]).unwrap();
+        assert_eq!(eth_frame.src_mac(), guest_mac);
+        assert_eq!(eth_frame.dst_mac().get_bytes(), [0xff; MAC_ADDR_LEN]);
+        assert_eq!(eth_frame.ethertype(), ETHERTYPE_RARP);
+        // The operation is the only field telling RARP and ARP requests apart.
+        assert_eq!(eth_frame.payload()[6..8], OPER_RARP_REQUEST.to_be_bytes());
+
+        // The request is repeated, paced by the announce timer.
+        for _ in 1..ANNOUNCE_FRAME_COUNT {
+            assert_eq!(th.event_manager.run_with_timeout(1000).unwrap(), 1);
+            let len = peer.read(&mut peer_buf).unwrap();
+            let eth_frame = EthernetFrame::from_bytes(&peer_buf[..len]).unwrap();
+            assert_eq!(eth_frame.ethertype(), ETHERTYPE_RARP);
+        }
+        assert_eq!(th.net().announce_frames_left, 0);
+        assert_eq!(th.net().announce_timer.get_state(), TimerState::Disarmed);
+
+        // The guest address is learnt from the frames it sends.
+        let guest_ip = Ipv4Addr::new(10, 0, 0, 2);
+        let mut arp_buf = [0u8; ETH_IPV4_FRAME_LEN];
+        EthIPv4ArpFrame::write_request(
+            &mut arp_buf[..],
+            guest_mac,
+            guest_ip,
+            MacAddr::from_bytes_unchecked(&[0; MAC_ADDR_LEN]),
+            Ipv4Addr::new(10, 0, 0, 1),
+        )
+        .unwrap();
+        guest_tx_frame(&mut th, guest_mac, ETHERTYPE_ARP, &arp_buf);
+        assert_eq!(th.net().guest_ipv4, Some(guest_ip));
+        peer.read(&mut peer_buf).unwrap();
+
+        // Frames sent from another MAC address don't change it.
+        let other_mac = MacAddr::parse_str("11:22:33:44:55:77").unwrap();
+        EthIPv4ArpFrame::write_request(
+            &mut arp_buf[..],
+            other_mac,
+            Ipv4Addr::new(10, 0, 0, 3),
+            MacAddr::from_bytes_unchecked(&[0; MAC_ADDR_LEN]),
+            Ipv4Addr::new(10, 0, 0, 1),
+        )
+        .unwrap();
+        guest_tx_frame(&mut th, other_mac, ETHERTYPE_ARP, &arp_buf);
+        assert_eq!(th.net().guest_ipv4, Some(guest_ip));
+        peer.read(&mut peer_buf).unwrap();
+
+        // Once learnt, the address is no longer looked up in the frames the guest sends.
+        EthIPv4ArpFrame::write_request(
+            &mut arp_buf[..],
+            guest_mac,
+            Ipv4Addr::new(10, 0, 0, 4),
+            MacAddr::from_bytes_unchecked(&[0; MAC_ADDR_LEN]),
+            Ipv4Addr::new(10, 0, 0, 1),
+        )
+        .unwrap();
+        guest_tx_frame(&mut th, guest_mac, ETHERTYPE_ARP, &arp_buf);
+        assert_eq!(th.net().guest_ipv4, Some(guest_ip));
+        peer.read(&mut peer_buf).unwrap();
+
+        // Now a gratuitous ARP request is sent.
+        th.net().announce().unwrap();
+        let len = peer.read(&mut peer_buf).unwrap();
+        let eth_frame = EthernetFrame::from_bytes(&peer_buf[..len]).unwrap();
+        assert_eq!(eth_frame.ethertype(), ETHERTYPE_ARP);
+        let arp_frame =
+            EthIPv4ArpFrame::request_from_bytes(&eth_frame.payload()[..ETH_IPV4_FRAME_LEN])
+                .unwrap();
+        assert_eq!(arp_frame.sha(), guest_mac);
+        assert_eq!(arp_frame.spa(), guest_ip);
+        assert_eq!(arp_frame.tpa(), guest_ip);
+
+        // Nothing is sent while the link is down, including the rest of an ongoing announcement.
+        th.net().set_link_up(false).unwrap();
+        check_metric_after_block!(
+            &METRICS.net.tx_packets_count,
+            0,
+            th.simulate_event(NetEvent::AnnounceTimer)
+        );
+        assert_eq!(th.net().announce_frames_left, 0);
+        check_metric_after_block!(
+            &METRICS.net.tx_packets_count,
+            0,
+            th.net().announce().unwrap()
+        );
     }
 
     #[test]
@@ -1197,7 +1910,7 @@ pub mod tests {
     fn test_tx_missing_queue_signal() {
         let mut th = TestHelper::default();
         th.activate_net();
-        let tap_traffic_simulator = TapTrafficSimulator::new(if_index(&th.net().tap));
+        let tap_traffic_simulator = TapTrafficSimulator::new(if_index(&tap_if_name(&th.net())));
 
         th.add_desc_chain(NetQueue::Tx, 0, &[(0, 4096, 0)]);
         th.net().queue_evts[TX_INDEX].read().unwrap();
@@ -1217,7 +1930,7 @@ pub mod tests {
     fn test_tx_writeable_descriptor() {
         let mut th = TestHelper::default();
         th.activate_net();
-        let tap_traffic_simulator = TapTrafficSimulator::new(if_index(&th.net().tap));
+        let tap_traffic_simulator = TapTrafficSimulator::new(if_index(&tap_if_name(&th.net())));
 
         let desc_list = [(0, 100, 0), (1, 100, VIRTQ_DESC_F_WRITE), (2, 500, 0)];
         th.add_desc_chain(NetQueue::Tx, 0, &desc_list);
@@ -1236,7 +1949,7 @@ pub mod tests {
     fn test_tx_short_frame() {
         let mut th = TestHelper::default();
         th.activate_net();
-        let tap_traffic_simulator = TapTrafficSimulator::new(if_index(&th.net().tap));
+        let tap_traffic_simulator = TapTrafficSimulator::new(if_index(&tap_if_name(&th.net())));
 
         // Send an invalid frame (too small, VNET header missing).
         th.add_desc_chain(NetQueue::Tx, 0, &[(0, 1, 0)]);
@@ -1258,7 +1971,7 @@ pub mod tests {
     fn test_tx_partial_read() {
         let mut th = TestHelper::default();
         th.activate_net();
-        let tap_traffic_simulator = TapTrafficSimulator::new(if_index(&th.net().tap));
+        let tap_traffic_simulator = TapTrafficSimulator::new(if_index(&tap_if_name(&th.net())));
 
         // The descriptor chain is created so that the last descriptor doesn't fit in the
         // guest memory.
@@ -1286,7 +1999,7 @@ pub mod tests {
     fn test_tx_retry() {
         let mut th = TestHelper::default();
         th.activate_net();
-        let tap_traffic_simulator = TapTrafficSimulator::new(if_index(&th.net().tap));
+        let tap_traffic_simulator = TapTrafficSimulator::new(if_index(&tap_if_name(&th.net())));
 
         // Add invalid descriptor chain - writeable descriptor.
         th.add_desc_chain(
@@ -1326,7 +2039,7 @@ pub mod tests {
     fn test_tx_complex_descriptor() {
         let mut th = TestHelper::default();
         th.activate_net();
-        let tap_traffic_simulator = TapTrafficSimulator::new(if_index(&th.net().tap));
+        let tap_traffic_simulator = TapTrafficSimulator::new(if_index(&tap_if_name(&th.net())));
 
         // Add gaps between the descriptor ids in order to ensure that we follow
         // the `next` field.
@@ -1354,7 +2067,7 @@ pub mod tests {
     fn test_tx_multiple_frame() {
         let mut th = TestHelper::default();
         th.activate_net();
-        let tap_traffic_simulator = TapTrafficSimulator::new(if_index(&th.net().tap));
+        let tap_traffic_simulator = TapTrafficSimulator::new(if_index(&tap_if_name(&th.net())));
 
         // Write the first frame to the Tx queue
         let desc_list = [(0, 50, 0), (1, 100, 0), (2, 150, 0)];
@@ -1386,6 +2099,24 @@ pub mod tests {
         assert_eq!(&buf[..600], &frame_2[..600]);
     }
 
+    #[test]
+    fn test_flush_tx() {
+        let mut th = TestHelper::default();
+        th.activate_net();
+        let tap_traffic_simulator = TapTrafficSimulator::new(if_index(&tap_if_name(&th.net())));
+
+        // The frame is sent without the event manager noticing the queue event.
+        let desc_list = [(0, 50, 0), (1, 100, 0)];
+        th.add_desc_chain(NetQueue::Tx, 0, &desc_list);
+        let frame = th.write_tx_frame(&desc_list, 150);
+        th.net().flush_tx();
+
+        assert_eq!(th.txq.used.idx.get(), 1);
+        let mut buf = vec![0; 150];
+        assert!(tap_traffic_simulator.pop_rx_packet(&mut buf[vnet_hdr_len()..]));
+        assert_eq!(&buf[..150], &frame[..150]);
+    }
+
     fn create_arp_request(
         src_mac: MacAddr,
         src_ip: Ipv4Addr,
@@ -1438,18 +2169,143 @@ pub mod tests {
                 net.mmds_ns.as_mut(),
                 &mut net.tx_rate_limiter,
                 &frame_buf[..frame_len],
-                &mut net.tap,
+                net.backend.as_mut(),
                 Some(src_mac),
+                &net.metrics,
             )
             .unwrap())
         );
 
+        assert_eq!(net.metrics.tx_mmds_frames.count(), 1);
+
         // Validate that MMDS has a response and we can retrieve it.
         check_metric_after_block!(
             &METRICS.mmds.tx_frames,
             1,
             net.read_from_mmds_or_tap().unwrap()
         );
+        assert_eq!(net.metrics.rx_mmds_frames.count(), 1);
+    }
+
+    #[test]
+    fn test_unix_socket_backend() {
+        let (backend, mut peer) = unix_socket_pair(UnixSocketType::SeqPacket);
+        let guest_mac = MacAddr::parse_str("11:22:33:44:55:66").unwrap();
+        let mut net = Net::new(
+            "unix-net".to_string(),
+            Box::new(backend),
+            Some(&guest_mac),
+            RateLimiter::default(),
+            RateLimiter::default(),
+            None,
+        )
+        .unwrap();
+        net.mocks.set_read_tap(ReadTapMock::TapFrame);
+
+        // No offloads are offered to the guest.
+        assert_eq!(
+            net.avail_features(),
+            1 << VIRTIO_F_VERSION_1
+                | 1 << VIRTIO_NET_F_MAC
+                | 1 << VIRTIO_NET_F_STATUS
+                | 1 << VIRTIO_NET_F_CTRL_VQ
+                | 1 << VIRTIO_NET_F_GUEST_ANNOUNCE
+        );
+        match net.host_endpoint() {
+            HostEndpoint::UnixSocket(_) => (),
+            _ => panic!("Expected HostEndpoint::UnixSocket"),
+        }
+
+        // TX frames reach the peer without the VNET header.
+        let dst_mac = MacAddr::parse_str("22:22:22:22:22:22").unwrap();
+        let (frame_buf, frame_len) = create_arp_request(
+            guest_mac,
+            Ipv4Addr::new(10, 1, 2, 3),
+            dst_mac,
+            Ipv4Addr::new(10, 1, 1, 1),
+        );
+        check_metric_after_block!(
+            &METRICS.net.tx_packets_count,
+            1,
+            assert!(!Net::write_to_mmds_or_tap(
+                net.mmds_ns.as_mut(),
+                &mut net.tx_rate_limiter,
+                &frame_buf[..frame_len],
+                net.backend.as_mut(),
+                Some(guest_mac),
+                &net.metrics,
+            )
+            .unwrap())
+        );
+        assert_eq!(net.metrics.tx_packets_count.count(), 1);
+        assert_eq!(net.metrics.tx_bytes_count.count(), frame_len);
+        let mut peer_buf = [0u8; MAX_BUFFER_SIZE];
+        let count = peer.read(&mut peer_buf).unwrap();
+        assert_eq!(&peer_buf[..count], &frame_buf[vnet_hdr_len()..frame_len]);
+
+        // RX frames from the peer get a VNET header.
+        peer.write_all(&peer_buf[..count]).unwrap();
+        assert_eq!(net.read_from_mmds_or_tap().unwrap(), frame_len);
+        assert_eq!(
+            &net.rx_frame_buf[vnet_hdr_len()..frame_len],
+            &frame_buf[vnet_hdr_len()..frame_len]
+        );
+
+        // Once the peer hangs up, the RX loop stops without handing empty frames to the guest.
+        drop(peer);
+        check_metric_after_block!(
+            &METRICS.net.tap_read_fails,
+            1,
+            assert!(net.process_rx().is_ok())
+        );
+        assert_eq!(net.metrics.rx_packets_count.count(), 0);
+    }
+
+    #[test]
+    fn test_switch_backend() {
+        let (backend, _peer) = unix_socket_pair(UnixSocketType::SeqPacket);
+        let guest_mac = MacAddr::parse_str("11:22:33:44:55:66").unwrap();
+        let net = Net::new(
+            "unix-net".to_string(),
+            Box::new(backend),
+            Some(&guest_mac),
+            RateLimiter::default(),
+            RateLimiter::default(),
+            None,
+        )
+        .unwrap();
+        let mut th = TestHelper::with_net(net);
+        th.activate_net();
+        let net = th.net.clone();
+        let old_fd = th.net().backend.as_raw_fd();
+
+        // When the event registration can't be moved, the device keeps the previous backend.
+        let (backend, _new_peer) = unix_socket_pair(UnixSocketType::SeqPacket);
+        let mut other_event_manager = EventManager::new().unwrap();
+        match net
+            .lock()
+            .unwrap()
+            .switch_backend(Box::new(backend), &mut other_event_manager)
+        {
+            Err(Error::EventManager(_)) => (),
+            _ => panic!("Expected Error::EventManager"),
+        }
+        assert_eq!(th.net().backend.as_raw_fd(), old_fd);
+        assert!(th.event_manager.subscriber(old_fd).is_ok());
+
+        // Otherwise, the new backend is polled instead of the previous one.
+        let (backend, mut new_peer) = unix_socket_pair(UnixSocketType::SeqPacket);
+        let new_fd = backend.as_raw_fd();
+        let old_backend = net
+            .lock()
+            .unwrap()
+            .switch_backend(Box::new(backend), &mut th.event_manager)
+            .unwrap();
+        assert_eq!(old_backend.as_raw_fd(), old_fd);
+        assert_eq!(th.net().backend.as_raw_fd(), new_fd);
+        assert!(th.event_manager.subscriber(old_fd).is_err());
+        new_peer.write_all(&[0u8; 64]).unwrap();
+        assert_eq!(th.event_manager.run_with_timeout(100).unwrap(), 1);
     }
 
     #[test]
@@ -1472,8 +2328,9 @@ pub mod tests {
                 net.mmds_ns.as_mut(),
                 &mut net.tx_rate_limiter,
                 &frame_buf[..frame_len],
-                &mut net.tap,
+                net.backend.as_mut(),
                 Some(guest_mac),
+                &net.metrics,
             )
         );
 
@@ -1485,8 +2342,9 @@ pub mod tests {
                 net.mmds_ns.as_mut(),
                 &mut net.tx_rate_limiter,
                 &frame_buf[..frame_len],
-                &mut net.tap,
+                net.backend.as_mut(),
                 Some(not_guest_mac),
+                &net.metrics,
             )
         );
     }
@@ -1826,11 +2684,12 @@ pub mod tests {
         th.activate_net();
         let net = th.net.lock().unwrap();
 
-        // Test queues count (TX and RX).
+        // Test queues count (RX, TX and control).
         let queues = net.queues();
         assert_eq!(queues.len(), QUEUE_SIZES.len());
         assert_eq!(queues[RX_INDEX].size, th.rxq.size());
         assert_eq!(queues[TX_INDEX].size, th.txq.size());
+        assert_eq!(queues[CTRL_INDEX].size, th.ctrlq.size());
 
         // Test corresponding queues events.
         assert_eq!(net.queue_events().len(), QUEUE_SIZES.len());
@@ -1,14 +1,16 @@
 // Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-use std::os::unix::io::AsRawFd;
+use std::io::{self, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
 
 use logger::{debug, error, warn, IncMetric, METRICS};
 use polly::event_manager::{EventManager, Subscriber};
 use utils::epoll::{EpollEvent, EventSet};
 
-use crate::virtio::net::device::Net;
-use crate::virtio::{VirtioDevice, RX_INDEX, TX_INDEX};
+use crate::virtio::net::device::{open_tap, Net};
+use crate::virtio::net::{Error, NetBackend, Result};
+use crate::virtio::{VirtioDevice, CTRL_INDEX, RX_INDEX, TX_INDEX};
 
 impl Net {
     fn process_activate_event(&self, event_manager: &mut EventManager) {
@@ -41,6 +43,105 @@ impl Net {
             error!("Failed to unregister net activate evt: {:?}", e);
         });
     }
+
+    // The events the backend is polled for.
+    fn backend_events(&self) -> EventSet {
+        let mut events = EventSet::IN | EventSet::EDGE_TRIGGERED;
+        if self.backend.buffers_writes() {
+            events |= EventSet::OUT;
+        }
+        events
+    }
+
+    fn process_backend_event(&mut self, event_set: EventSet) {
+        if event_set.contains(EventSet::OUT) {
+            // The backend can take the rest of a partially sent frame.
+            match self.backend.flush() {
+                Err(e) if e.kind() != io::ErrorKind::WouldBlock => {
+                    error!("Failed to write to tap: {:?}", e);
+                    METRICS.net.tap_write_fails.inc();
+                    self.metrics.tap_write_fails.inc();
+                }
+                _ => (),
+            }
+        }
+        if event_set.contains(EventSet::IN) {
+            self.process_tap_rx_event();
+        }
+    }
+
+    /// Re-points the device to the TAP interface named `tap_if_name`. Returns the previous
+    /// backend, see `switch_backend()`.
+    pub fn update_tap(
+        &mut self,
+        tap_if_name: &str,
+        event_manager: &mut EventManager,
+    ) -> Result<Box<dyn NetBackend>> {
+        let tap = open_tap(tap_if_name)?;
+        self.switch_backend(Box::new(tap), event_manager)
+    }
+
+    /// Replaces the host side of the device with `backend`. If the device is active, the event
+    /// registration of the previous backend is moved over to the new one. On error, the device
+    /// is left using the previous backend. Returns the previous backend, so that the switch can
+    /// be reverted.
+    pub fn switch_backend(
+        &mut self,
+        backend: Box<dyn NetBackend>,
+        event_manager: &mut EventManager,
+    ) -> Result<Box<dyn NetBackend>> {
+        let old_backend = self.replace_backend(backend)?;
+        if !self.is_activated() {
+            // The backend is only polled once the device is activated.
+            return Ok(old_backend);
+        }
+
+        if let Err(e) = self.move_backend_events(old_backend.as_raw_fd(), event_manager) {
+            self.backend = old_backend;
+            return Err(e);
+        }
+        // The new backend is edge triggered, so pick up anything that is already pending.
+        self.process_tap_rx_event();
+        Ok(old_backend)
+    }
+
+    fn move_backend_events(&self, old_fd: RawFd, event_manager: &mut EventManager) -> Result<()> {
+        let self_subscriber = event_manager
+            .subscriber(old_fd)
+            .map_err(Error::EventManager)?;
+        // Register the new backend first, so that a failure leaves the previous one polled.
+        let backend_fd = self.backend.as_raw_fd();
+        event_manager
+            .register(
+                backend_fd,
+                EpollEvent::new(self.backend_events(), backend_fd as u64),
+                self_subscriber,
+            )
+            .map_err(Error::EventManager)?;
+        if let Err(e) = event_manager.unregister(old_fd) {
+            let _ = event_manager.unregister(backend_fd);
+            return Err(Error::EventManager(e));
+        }
+        Ok(())
+    }
+
+    /// Stops polling all the event sources of the device, ahead of its removal.
+    pub fn unregister_events(&self, event_manager: &mut EventManager) {
+        let fds = [
+            self.activate_evt.as_raw_fd(),
+            self.queue_evts[RX_INDEX].as_raw_fd(),
+            self.queue_evts[TX_INDEX].as_raw_fd(),
+            self.queue_evts[CTRL_INDEX].as_raw_fd(),
+            self.rx_rate_limiter.as_raw_fd(),
+            self.tx_rate_limiter.as_raw_fd(),
+            self.announce_timer.as_raw_fd(),
+            self.backend.as_raw_fd(),
+        ];
+        // Depending on whether the device was activated, only some of these are registered.
+        for fd in fds.iter() {
+            let _ = event_manager.unregister(*fd);
+        }
+    }
 }
 
 impl Subscriber for Net {
@@ -50,7 +151,7 @@ impl Subscriber for Net {
 
         // TODO: also check for errors. Pending high level discussions on how we want
         // to handle errors in devices.
-        let supported_events = EventSet::IN;
+        let supported_events = EventSet::IN | EventSet::OUT;
         if !supported_events.contains(event_set) {
             warn!(
                 "Received unknown event: {:?} from source: {:?}",
@@ -62,18 +163,22 @@ impl Subscriber for Net {
         if self.is_activated() {
             let virtq_rx_ev_fd = self.queue_evts[RX_INDEX].as_raw_fd();
             let virtq_tx_ev_fd = self.queue_evts[TX_INDEX].as_raw_fd();
+            let virtq_ctrl_ev_fd = self.queue_evts[CTRL_INDEX].as_raw_fd();
             let rx_rate_limiter_fd = self.rx_rate_limiter.as_raw_fd();
             let tx_rate_limiter_fd = self.tx_rate_limiter.as_raw_fd();
-            let tap_fd = self.tap.as_raw_fd();
+            let announce_timer_fd = self.announce_timer.as_raw_fd();
+            let backend_fd = self.backend.as_raw_fd();
             let activate_fd = self.activate_evt.as_raw_fd();
 
             // Looks better than C style if/else if/else.
             match source {
                 _ if source == virtq_rx_ev_fd => self.process_rx_queue_event(),
-                _ if source == tap_fd => self.process_tap_rx_event(),
+                _ if source == backend_fd => self.process_backend_event(event_set),
                 _ if source == virtq_tx_ev_fd => self.process_tx_queue_event(),
+                _ if source == virtq_ctrl_ev_fd => self.process_ctrl_queue_event(),
                 _ if source == rx_rate_limiter_fd => self.process_rx_rate_limiter_event(),
                 _ if source == tx_rate_limiter_fd => self.process_tx_rate_limiter_event(),
+                _ if source == announce_timer_fd => self.process_announce_timer_event(),
                 _ if activate_fd == source => self.process_activate_event(evmgr),
                 _ => {
                     warn!("Net: Spurious event received: {:?}", source);
@@ -97,12 +202,11 @@ impl Subscriber for Net {
             vec![
                 EpollEvent::new(EventSet::IN, self.queue_evts[RX_INDEX].as_raw_fd() as u64),
                 EpollEvent::new(EventSet::IN, self.queue_evts[TX_INDEX].as_raw_fd() as u64),
+                EpollEvent::new(EventSet::IN, self.queue_evts[CTRL_INDEX].as_raw_fd() as u64),
                 EpollEvent::new(EventSet::IN, self.rx_rate_limiter.as_raw_fd() as u64),
                 EpollEvent::new(EventSet::IN, self.tx_rate_limiter.as_raw_fd() as u64),
-                EpollEvent::new(
-                    EventSet::IN | EventSet::EDGE_TRIGGERED,
-                    self.tap.as_raw_fd() as u64,
-                ),
+                EpollEvent::new(EventSet::IN, self.announce_timer.as_raw_fd() as u64),
+                EpollEvent::new(self.backend_events(), self.backend.as_raw_fd() as u64),
             ]
         } else {
             vec![EpollEvent::new(
@@ -1,26 +1,32 @@
 // Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
+use std::io::{Read, Write};
+use std::os::unix::io::AsRawFd;
 use std::{io, result};
 
 pub const MAX_BUFFER_SIZE: usize = 65562;
 pub const QUEUE_SIZE: u16 = 256;
-pub const NUM_QUEUES: usize = 2;
+pub const NUM_QUEUES: usize = 3;
 pub const QUEUE_SIZES: &[u16] = &[QUEUE_SIZE; NUM_QUEUES];
 // The index of the rx queue from Net device queues/queues_evts vector.
 pub const RX_INDEX: usize = 0;
 // The index of the tx queue from Net device queues/queues_evts vector.
 pub const TX_INDEX: usize = 1;
+// The index of the control queue from Net device queues/queues_evts vector.
+pub const CTRL_INDEX: usize = 2;
 
 pub mod device;
 pub mod event_handler;
 pub mod persist;
 mod tap;
 pub mod test_utils;
+mod unix;
 
 pub use self::device::Net;
 pub use self::event_handler::*;
-pub use tap::Error as TapError;
+pub use tap::{Error as TapError, Tap};
+pub use unix::{Error as UnixSocketError, SocketType as UnixSocketType, UnixSocket};
 
 #[derive(Debug)]
 pub enum Error {
@@ -32,12 +38,48 @@ pub enum Error {
     TapSetVnetHdrSize(TapError),
     /// Enabling tap interface failed.
     TapEnable(TapError),
+    /// Connecting to the Unix socket backend failed.
+    UnixSocketConnect(UnixSocketError),
+    /// The new backend doesn't offer the offload features negotiated with the guest.
+    BackendFeaturesMismatch,
     /// EventFd error.
     EventFd(io::Error),
+    /// Updating the device's event registration failed.
+    EventManager(polly::event_manager::Error),
     /// IO error.
     IO(io::Error),
+    /// Building the frame announcing the guest failed.
+    AnnounceFrame,
     /// The VNET header is missing from the frame.
     VnetHeaderMissing,
 }
 
 pub type Result<T> = result::Result<T, Error>;
+
+/// Identifies the host endpoint a net device backend is attached to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum HostEndpoint {
+    /// A TAP interface, identified by its name.
+    Tap(String),
+    /// A Unix socket, identified by its filesystem path.
+    UnixSocket(String),
+}
+
+/// The host side of a net device: a non-blocking, epoll-able channel that carries one
+/// Ethernet frame per `read()` / `write()` call. Frames exchanged with the device model are
+/// always prefixed by a VNET header, so backends which don't use one on the wire must add/strip
+/// it themselves.
+pub trait NetBackend: Read + Write + AsRawFd + Send {
+    /// Virtio-net offload features (checksum, TSO, UFO) the backend is able to service, on top
+    /// of the ones always offered by the device.
+    fn offload_features(&self) -> u64;
+
+    /// Describes the host endpoint of the backend, so it can be reopened on restore.
+    fn endpoint(&self) -> HostEndpoint;
+
+    /// Whether `write()` can keep part of a frame for later, in which case the backend is also
+    /// polled for writability, and `flush()` sends the rest.
+    fn buffers_writes(&self) -> bool {
+        false
+    }
+}
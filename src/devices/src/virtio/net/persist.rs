@@ -4,27 +4,43 @@
 //! Defines the structures needed for saving/restoring net devices.
 
 use std::io;
+use std::net::Ipv4Addr;
 use std::sync::atomic::AtomicUsize;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
-use mmds::{ns::MmdsNetworkStack, persist::MmdsNetworkStackState};
+use mmds::{data_store::Mmds, ns::MmdsNetworkStack, persist::MmdsNetworkStackState};
 use rate_limiter::{persist::RateLimiterState, RateLimiter};
 use snapshot::Persist;
 use utils::net::mac::{MacAddr, MAC_ADDR_LEN};
-use versionize::{VersionMap, Versionize, VersionizeResult};
+use versionize::{VersionMap, Versionize, VersionizeError, VersionizeResult};
 use versionize_derive::Versionize;
+use virtio_gen::virtio_net::{
+    VIRTIO_NET_F_CTRL_VQ, VIRTIO_NET_F_GUEST_ANNOUNCE, VIRTIO_NET_S_LINK_UP,
+};
 use vm_memory::GuestMemoryMmap;
 
 use super::device::{ConfigSpace, Net};
-use super::{NUM_QUEUES, QUEUE_SIZE};
+use super::{HostEndpoint, NUM_QUEUES, QUEUE_SIZE};
 
 use crate::virtio::persist::{Error as VirtioStateError, VirtioDeviceState};
 use crate::virtio::{DeviceState, TYPE_NET};
 
+// Features older versions don't implement.
+const V1_UNSUPPORTED_FEATURES: u64 = 1 << VIRTIO_NET_F_CTRL_VQ | 1 << VIRTIO_NET_F_GUEST_ANNOUNCE;
+
 #[derive(Clone, Versionize)]
 // NOTICE: Any changes to this structure require a snapshot version bump.
 pub struct NetConfigSpaceState {
     guest_mac: [u8; MAC_ADDR_LEN],
+    #[version(start = 2, default_fn = "default_status")]
+    status: u16,
+}
+
+impl NetConfigSpaceState {
+    fn default_status(_source_version: u16) -> u16 {
+        // Devices saved by older versions always had their link up.
+        VIRTIO_NET_S_LINK_UP as u16
+    }
 }
 
 #[derive(Clone, Versionize)]
@@ -32,15 +48,50 @@ pub struct NetConfigSpaceState {
 pub struct NetState {
     id: String,
     tap_if_name: String,
+    #[version(start = 2, ser_fn = "unix_socket_path_ser")]
+    unix_socket_path: Option<String>,
     rx_rate_limiter_state: RateLimiterState,
     tx_rate_limiter_state: RateLimiterState,
     mmds_ns: Option<MmdsNetworkStackState>,
     config_space: NetConfigSpaceState,
     virtio_state: VirtioDeviceState,
+    #[version(start = 2, ser_fn = "guest_ipv4_ser")]
+    guest_ipv4: Option<u32>,
+}
+
+impl NetState {
+    fn guest_ipv4_ser(&mut self, _target_version: u16) -> VersionizeResult<()> {
+        // Older versions implement neither the control queue nor guest announcements. Once the
+        // driver negotiated them, they can't be taken back from it.
+        if self.virtio_state.acked_features & V1_UNSUPPORTED_FEATURES != 0 {
+            return Err(VersionizeError::Semantic(
+                "Target version does not implement the net control queue and guest \
+                 announcements, which the guest driver negotiated."
+                    .to_owned(),
+            ));
+        }
+
+        // The driver only uses the control queue when it negotiated the features above.
+        self.virtio_state.queues.truncate(NUM_QUEUES - 1);
+        self.virtio_state.avail_features &= !V1_UNSUPPORTED_FEATURES;
+        Ok(())
+    }
+
+    fn unix_socket_path_ser(&mut self, _target_version: u16) -> VersionizeResult<()> {
+        if self.unix_socket_path.is_some() {
+            return Err(VersionizeError::Semantic(
+                "Target version does not implement the Unix socket net backend.".to_owned(),
+            ));
+        }
+
+        Ok(())
+    }
 }
 
 pub struct NetConstructorArgs {
     pub mem: GuestMemoryMmap,
+    /// The data store serving the guest MMDS requests, if the device allows them.
+    pub mmds: Arc<Mutex<Mmds>>,
 }
 
 #[derive(Debug)]
@@ -56,16 +107,23 @@ impl Persist<'_> for Net {
     type Error = Error;
 
     fn save(&self) -> Self::State {
+        let (tap_if_name, unix_socket_path) = match self.backend.endpoint() {
+            HostEndpoint::Tap(if_name) => (if_name, None),
+            HostEndpoint::UnixSocket(path) => (String::new(), Some(path)),
+        };
         NetState {
             id: self.id().clone(),
-            tap_if_name: self.tap.if_name_as_str().to_string(),
+            tap_if_name,
+            unix_socket_path,
             rx_rate_limiter_state: self.rx_rate_limiter.save(),
             tx_rate_limiter_state: self.tx_rate_limiter.save(),
             mmds_ns: self.mmds_ns.as_ref().map(|mmds| mmds.save()),
             config_space: NetConfigSpaceState {
                 guest_mac: self.config_space.guest_mac,
+                status: self.config_space.status,
             },
             virtio_state: VirtioDeviceState::from_device(self),
+            guest_ipv4: self.guest_ipv4.map(u32::from),
         }
     }
 
@@ -78,36 +136,57 @@ impl Persist<'_> for Net {
             .map_err(Error::CreateRateLimiter)?;
         let tx_rate_limiter = RateLimiter::restore((), &state.tx_rate_limiter_state)
             .map_err(Error::CreateRateLimiter)?;
-        let mut net = Net::new_with_tap(
-            state.id.clone(),
-            state.tap_if_name.clone(),
-            None,
-            rx_rate_limiter,
-            tx_rate_limiter,
-            state.mmds_ns.is_some(),
-        )
+        let mmds = state
+            .mmds_ns
+            .as_ref()
+            .map(|_| constructor_args.mmds.clone());
+        let mut net = match state.unix_socket_path {
+            Some(ref path) => Net::new_with_unix_socket(
+                state.id.clone(),
+                path.clone(),
+                None,
+                rx_rate_limiter,
+                tx_rate_limiter,
+                mmds.clone(),
+            ),
+            None => Net::new_with_tap(
+                state.id.clone(),
+                state.tap_if_name.clone(),
+                None,
+                rx_rate_limiter,
+                tx_rate_limiter,
+                mmds.clone(),
+            ),
+        }
         .map_err(Error::CreateNet)?;
 
         // Safe to unwrap because MmdsNetworkStack::restore() cannot fail.
-        net.mmds_ns = state
-            .mmds_ns
-            .as_ref()
-            .map(|mmds_state| MmdsNetworkStack::restore((), &mmds_state).unwrap());
+        net.mmds_ns = state.mmds_ns.as_ref().map(|mmds_state| {
+            MmdsNetworkStack::restore(constructor_args.mmds.clone(), &mmds_state).unwrap()
+        });
 
+        // States of older versions don't hold the control queue.
+        let num_queues = if state.virtio_state.queues.len() == NUM_QUEUES - 1 {
+            NUM_QUEUES - 1
+        } else {
+            NUM_QUEUES
+        };
         net.queues = state
             .virtio_state
-            .build_queues_checked(&constructor_args.mem, TYPE_NET, NUM_QUEUES, QUEUE_SIZE)
+            .build_queues_checked(&constructor_args.mem, TYPE_NET, num_queues, QUEUE_SIZE)
             .map_err(Error::VirtioState)?;
         net.interrupt_status = Arc::new(AtomicUsize::new(state.virtio_state.interrupt_status));
         net.avail_features = state.virtio_state.avail_features;
         net.acked_features = state.virtio_state.acked_features;
         net.config_space = ConfigSpace {
             guest_mac: state.config_space.guest_mac,
+            status: state.config_space.status,
         };
 
         net.guest_mac = Some(MacAddr::from_bytes_unchecked(
             &state.config_space.guest_mac[..MAC_ADDR_LEN],
         ));
+        net.guest_ipv4 = state.guest_ipv4.map(Ipv4Addr::from);
 
         if state.virtio_state.activated {
             net.device_state = DeviceState::Activated(constructor_args.mem);
@@ -122,7 +201,9 @@ mod tests {
     use super::*;
     use crate::virtio::device::VirtioDevice;
 
-    use crate::virtio::net::test_utils::{default_guest_memory, default_net};
+    use crate::virtio::net::test_utils::{
+        default_guest_memory, default_net, tap_if_name as tap_if_name_of,
+    };
     use std::sync::atomic::Ordering;
 
     #[test]
@@ -131,6 +212,7 @@ mod tests {
         let mut mem = vec![0; 4096];
         let version_map = VersionMap::new();
 
+        let mmds = Arc::new(Mutex::new(Mmds::default()));
         let id;
         let tap_if_name;
         let allow_mmds_requests;
@@ -146,7 +228,7 @@ mod tests {
 
             // Save some fields that we want to check later.
             id = net.id.clone();
-            tap_if_name = net.tap.if_name_as_str().to_string();
+            tap_if_name = tap_if_name_of(&net);
             allow_mmds_requests = net.mmds_ns.is_some();
             virtio_state = VirtioDeviceState::from_device(&net);
         }
@@ -154,14 +236,21 @@ mod tests {
         // Deserialize and restore the net device.
         {
             let restored_net = Net::restore(
-                NetConstructorArgs { mem: guest_mem },
+                NetConstructorArgs {
+                    mem: guest_mem,
+                    mmds: mmds.clone(),
+                },
                 &NetState::deserialize(&mut mem.as_slice(), &version_map, 1).unwrap(),
             )
             .unwrap();
 
-            // Test that virtio specific fields are the same.
+            // Test that virtio specific fields are the same, except for the features the
+            // target version doesn't implement.
             assert_eq!(restored_net.device_type(), TYPE_NET);
-            assert_eq!(restored_net.avail_features(), virtio_state.avail_features);
+            assert_eq!(
+                restored_net.avail_features(),
+                virtio_state.avail_features & !V1_UNSUPPORTED_FEATURES
+            );
             assert_eq!(restored_net.acked_features(), virtio_state.acked_features);
             assert_eq!(
                 restored_net.interrupt_status().load(Ordering::Relaxed),
@@ -171,10 +260,152 @@ mod tests {
 
             // Test that net specific fields are the same.
             assert_eq!(&restored_net.id, &id);
-            assert_eq!(&restored_net.tap.if_name_as_str(), &tap_if_name);
+            assert_eq!(tap_if_name_of(&restored_net), tap_if_name);
             assert_eq!(restored_net.mmds_ns.is_some(), allow_mmds_requests);
+            assert!(Arc::ptr_eq(
+                restored_net.mmds_ns.as_ref().unwrap().mmds(),
+                &mmds
+            ));
             assert_eq!(restored_net.rx_rate_limiter, RateLimiter::default());
             assert_eq!(restored_net.tx_rate_limiter, RateLimiter::default());
         }
     }
+
+    #[test]
+    fn test_unix_socket_persistence() {
+        let tmp_file = utils::tempfile::TempFile::new().unwrap();
+        let path = tmp_file.as_path().to_str().unwrap().to_string();
+        drop(tmp_file);
+        let _peer = std::os::unix::net::UnixDatagram::bind(&path).unwrap();
+
+        let net = Net::new_with_unix_socket(
+            "unix-net".to_string(),
+            path.clone(),
+            None,
+            RateLimiter::default(),
+            RateLimiter::default(),
+            None,
+        )
+        .unwrap();
+        let mut mem = vec![0; 4096];
+
+        // Older versions can't describe a Unix socket backend.
+        let version_map = VersionMap::new();
+        assert!(<Net as Persist>::save(&net)
+            .serialize(&mut mem.as_mut_slice(), &version_map, 1)
+            .is_err());
+
+        let mut version_map = VersionMap::new();
+        version_map
+            .new_version()
+            .set_type_version(NetState::type_id(), 2);
+        <Net as Persist>::save(&net)
+            .serialize(&mut mem.as_mut_slice(), &version_map, 2)
+            .unwrap();
+
+        let restored_net = Net::restore(
+            NetConstructorArgs {
+                mem: default_guest_memory(),
+                mmds: Arc::default(),
+            },
+            &NetState::deserialize(&mut mem.as_slice(), &version_map, 2).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            restored_net.host_endpoint(),
+            HostEndpoint::UnixSocket(path.clone())
+        );
+        assert_eq!(restored_net.avail_features(), net.avail_features());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_link_state_persistence() {
+        let mut net = default_net();
+        net.set_link_up(false).unwrap();
+        let mut mem = vec![0; 4096];
+
+        let mut version_map = VersionMap::new();
+        version_map
+            .new_version()
+            .set_type_version(NetConfigSpaceState::type_id(), 2);
+        <Net as Persist>::save(&net)
+            .serialize(&mut mem.as_mut_slice(), &version_map, 2)
+            .unwrap();
+        let restored_net = Net::restore(
+            NetConstructorArgs {
+                mem: default_guest_memory(),
+                mmds: Arc::default(),
+            },
+            &NetState::deserialize(&mut mem.as_slice(), &version_map, 2).unwrap(),
+        )
+        .unwrap();
+        assert!(!restored_net.link_up());
+
+        // Older versions don't save the link state, so it comes back up.
+        <Net as Persist>::save(&net)
+            .serialize(&mut mem.as_mut_slice(), &version_map, 1)
+            .unwrap();
+        let restored_net = Net::restore(
+            NetConstructorArgs {
+                mem: default_guest_memory(),
+                mmds: Arc::default(),
+            },
+            &NetState::deserialize(&mut mem.as_slice(), &version_map, 1).unwrap(),
+        )
+        .unwrap();
+        assert!(restored_net.link_up());
+    }
+
+    #[test]
+    fn test_announce_state_persistence() {
+        let mut net = default_net();
+        net.guest_ipv4 = Some(Ipv4Addr::new(10, 0, 0, 2));
+        net.acked_features = V1_UNSUPPORTED_FEATURES;
+        let mut mem = vec![0; 4096];
+
+        let mut version_map = VersionMap::new();
+        version_map
+            .new_version()
+            .set_type_version(NetState::type_id(), 2);
+        <Net as Persist>::save(&net)
+            .serialize(&mut mem.as_mut_slice(), &version_map, 2)
+            .unwrap();
+        let restored_net = Net::restore(
+            NetConstructorArgs {
+                mem: default_guest_memory(),
+                mmds: Arc::default(),
+            },
+            &NetState::deserialize(&mut mem.as_slice(), &version_map, 2).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(restored_net.guest_ipv4, net.guest_ipv4);
+        assert_eq!(restored_net.queues().len(), NUM_QUEUES);
+        assert_eq!(restored_net.acked_features(), V1_UNSUPPORTED_FEATURES);
+
+        // Older versions have neither the control queue nor guest announcements, so the device
+        // can't be saved for them once the driver negotiated these.
+        assert!(<Net as Persist>::save(&net)
+            .serialize(&mut mem.as_mut_slice(), &version_map, 1)
+            .is_err());
+
+        // When only offered, the features are not advertised to the guest in older versions.
+        net.acked_features = 0;
+        <Net as Persist>::save(&net)
+            .serialize(&mut mem.as_mut_slice(), &version_map, 1)
+            .unwrap();
+        let restored_net = Net::restore(
+            NetConstructorArgs {
+                mem: default_guest_memory(),
+                mmds: Arc::default(),
+            },
+            &NetState::deserialize(&mut mem.as_slice(), &version_map, 1).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(restored_net.guest_ipv4, None);
+        assert_eq!(restored_net.queues().len(), NUM_QUEUES - 1);
+        assert_eq!(restored_net.acked_features(), 0);
+        assert_eq!(restored_net.avail_features() & V1_UNSUPPORTED_FEATURES, 0);
+    }
 }
@@ -7,11 +7,14 @@ use std::os::unix::ffi::OsStrExt;
 use std::os::unix::io::{AsRawFd, FromRawFd};
 use std::process::Command;
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::{mem, result};
 
 #[cfg(test)]
 use crate::virtio::net::device::vnet_hdr_len;
-use crate::virtio::net::tap::{Error, IfReqBuilder, Tap};
+use crate::virtio::net::tap::{build_terminated_if_name, Error, IfReqBuilder};
+use crate::virtio::net::unix::{SocketType, UnixSocket};
+use crate::virtio::net::HostEndpoint;
 use crate::virtio::test_utils::VirtQueue;
 use crate::virtio::{Net, Queue, QueueError};
 
@@ -38,14 +41,46 @@ pub fn default_net() -> Net {
         Some(&guest_mac),
         RateLimiter::default(),
         RateLimiter::default(),
-        true,
+        Some(Arc::default()),
     )
     .unwrap();
-    enable(&net.tap);
+    enable(&tap_if_name(&net));
 
     net
 }
 
+// Returns the name of the tap backing `net`.
+pub fn tap_if_name(net: &Net) -> String {
+    match net.host_endpoint() {
+        HostEndpoint::Tap(if_name) => if_name,
+        _ => panic!("The net device is not backed by a tap"),
+    }
+}
+
+// Returns a Unix socket backend of the given type and the peer end of its connection.
+pub fn unix_socket_pair(socket_type: SocketType) -> (UnixSocket, File) {
+    let mut fds = [0; 2];
+    // This is safe since we check the return value.
+    let ret = unsafe {
+        libc::socketpair(
+            libc::AF_UNIX,
+            socket_type.to_libc() | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+            0,
+            fds.as_mut_ptr(),
+        )
+    };
+    if ret < 0 {
+        panic!("Unable to create socket pair");
+    }
+
+    // This is safe; nothing else will use or hold onto the raw socket fds.
+    let (backend, peer) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+    (
+        UnixSocket::from_connected(backend, "socketpair".to_string(), socket_type),
+        peer,
+    )
+}
+
 pub enum ReadTapMock {
     Failure,
     MockFrame(Vec<u8>),
@@ -85,10 +120,13 @@ impl Default for Mocks {
 pub enum NetQueue {
     Rx,
     Tx,
+    Ctrl,
 }
 
 pub enum NetEvent {
+    AnnounceTimer,
     Custom(i32),
+    CtrlQueue,
     RxQueue,
     RxRateLimiter,
     Tap,
@@ -198,10 +236,10 @@ pub fn virtqueues(mem: &GuestMemoryMmap) -> (VirtQueue, VirtQueue) {
     (rxq, txq)
 }
 
-pub fn if_index(tap: &Tap) -> i32 {
+pub fn if_index(if_name: &str) -> i32 {
     let sock = create_socket();
     let ifreq = IfReqBuilder::new()
-        .if_name(&tap.if_name)
+        .if_name(&build_terminated_if_name(if_name).unwrap())
         .execute(&sock, c_ulong::from(net_gen::sockios::SIOCGIFINDEX))
         .unwrap();
 
@@ -209,20 +247,20 @@ pub fn if_index(tap: &Tap) -> i32 {
 }
 
 /// Enable the tap interface.
-pub fn enable(tap: &Tap) {
+pub fn enable(if_name: &str) {
     // Disable IPv6 router advertisment requests
     Command::new("sh")
         .arg("-c")
         .arg(format!(
             "echo 0 > /proc/sys/net/ipv6/conf/{}/accept_ra",
-            tap.if_name_as_str()
+            if_name
         ))
         .output()
         .unwrap();
 
     let sock = create_socket();
     IfReqBuilder::new()
-        .if_name(&tap.if_name)
+        .if_name(&build_terminated_if_name(if_name).unwrap())
         .flags(
             (net_gen::net_device_flags_IFF_UP
                 | net_gen::net_device_flags_IFF_RUNNING
@@ -242,7 +280,7 @@ pub fn check_used_queue_signal(net: &Net, count: u64) {
 #[cfg(test)]
 pub(crate) fn inject_tap_tx_frame(net: &Net, len: usize) -> Vec<u8> {
     assert!(len >= vnet_hdr_len());
-    let tap_traffic_simulator = TapTrafficSimulator::new(if_index(&net.tap));
+    let tap_traffic_simulator = TapTrafficSimulator::new(if_index(&tap_if_name(net)));
     let mut frame = utils::rand::rand_alphanumerics(len - vnet_hdr_len())
         .as_bytes()
         .to_vec();
@@ -285,10 +323,11 @@ pub fn set_mac(net: &mut Net, mac: MacAddr) {
 }
 
 // Assigns "guest virtio driver" activated queues to the net device.
-pub fn assign_queues(net: &mut Net, rxq: Queue, txq: Queue) {
+pub fn assign_queues(net: &mut Net, rxq: Queue, txq: Queue, ctrlq: Queue) {
     net.queues.clear();
     net.queues.push(rxq);
     net.queues.push(txq);
+    net.queues.push(ctrlq);
 }
 
 #[cfg(test)]
@@ -301,7 +340,7 @@ pub mod test {
     };
     use crate::virtio::test_utils::{VirtQueue, VirtqDesc};
     use crate::virtio::{
-        Net, VirtioDevice, MAX_BUFFER_SIZE, RX_INDEX, TX_INDEX, VIRTQ_DESC_F_NEXT,
+        Net, VirtioDevice, CTRL_INDEX, MAX_BUFFER_SIZE, RX_INDEX, TX_INDEX, VIRTQ_DESC_F_NEXT,
         VIRTQ_DESC_F_WRITE,
     };
     use logger::{IncMetric, METRICS};
@@ -321,14 +360,18 @@ pub mod test {
         pub mem: GuestMemoryMmap,
         pub rxq: VirtQueue<'a>,
         pub txq: VirtQueue<'a>,
+        pub ctrlq: VirtQueue<'a>,
     }
 
     impl<'a> TestHelper<'a> {
         const QUEUE_SIZE: u16 = 16;
 
         pub fn default() -> TestHelper<'a> {
+            Self::with_net(default_net())
+        }
+
+        pub fn with_net(mut net: Net) -> TestHelper<'a> {
             let mut event_manager = EventManager::new().unwrap();
-            let mut net = default_net();
             let mem = GuestMemoryMmap::from_ranges(&[(GuestAddress(0), MAX_BUFFER_SIZE)]).unwrap();
             // transmute mem_ref lifetime to 'a
             let mem_ref = unsafe { mem::transmute::<&GuestMemoryMmap, &'a GuestMemoryMmap>(&mem) };
@@ -339,7 +382,17 @@ pub mod test {
                 mem_ref,
                 Self::QUEUE_SIZE,
             );
-            assign_queues(&mut net, rxq.create_queue(), txq.create_queue());
+            let ctrlq = VirtQueue::new(
+                txq.end().unchecked_align_up(VirtqDesc::ALIGNMENT),
+                mem_ref,
+                Self::QUEUE_SIZE,
+            );
+            assign_queues(
+                &mut net,
+                rxq.create_queue(),
+                txq.create_queue(),
+                ctrlq.create_queue(),
+            );
 
             let net = Arc::new(Mutex::new(net));
             event_manager.add_subscriber(net.clone()).unwrap();
@@ -350,6 +403,7 @@ pub mod test {
                 mem,
                 rxq,
                 txq,
+                ctrlq,
             }
         }
 
@@ -366,10 +420,12 @@ pub mod test {
 
         pub fn simulate_event(&mut self, event: NetEvent) {
             let event_fd = match event {
+                NetEvent::AnnounceTimer => self.net().announce_timer.as_raw_fd(),
                 NetEvent::Custom(event_fd) => event_fd,
+                NetEvent::CtrlQueue => self.net().queue_evts[CTRL_INDEX].as_raw_fd(),
                 NetEvent::RxQueue => self.net().queue_evts[RX_INDEX].as_raw_fd(),
                 NetEvent::RxRateLimiter => self.net().rx_rate_limiter.as_raw_fd(),
-                NetEvent::Tap => self.net().tap.as_raw_fd(),
+                NetEvent::Tap => self.net().backend.as_raw_fd(),
                 NetEvent::TxQueue => self.net().queue_evts[TX_INDEX].as_raw_fd(),
                 NetEvent::TxRateLimiter => self.net().tx_rate_limiter.as_raw_fd(),
             };
@@ -380,7 +436,7 @@ pub mod test {
         }
 
         pub fn data_addr(&self) -> u64 {
-            self.txq.end().raw_value()
+            self.ctrlq.end().raw_value()
         }
 
         pub fn add_desc_chain(
@@ -394,6 +450,7 @@ pub mod test {
             let (queue, event_fd) = match queue {
                 NetQueue::Rx => (&self.rxq, &net.queue_evts[RX_INDEX]),
                 NetQueue::Tx => (&self.txq, &net.queue_evts[TX_INDEX]),
+                NetQueue::Ctrl => (&self.ctrlq, &net.queue_evts[CTRL_INDEX]),
             };
 
             // Create the descriptor chain.
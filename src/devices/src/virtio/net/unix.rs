@@ -0,0 +1,572 @@
+// Copyright 2021 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! A net device backend which exchanges Ethernet frames with a userspace peer (e.g. a software
+//! switch) over a connected `SOCK_SEQPACKET`, `SOCK_STREAM` or `SOCK_DGRAM` Unix socket.
+//!
+//! `SOCK_SEQPACKET` and `SOCK_DGRAM` sockets preserve message boundaries, so each message
+//! carries exactly one Ethernet frame, with no additional framing. Over a `SOCK_STREAM` socket,
+//! each frame is prefixed by its length, as a 4 byte big endian integer, which is the framing
+//! QEMU uses for its `stream` and `socket` netdevs. There is no VNET header on the wire.
+
+use std::fs::File;
+use std::io::{Error as IoError, ErrorKind, Read, Result as IoResult, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::{cmp, mem};
+
+use logger::warn;
+
+use super::device::vnet_hdr_len;
+use super::{HostEndpoint, NetBackend};
+
+// Size of the length prefixing each frame sent over a `SOCK_STREAM` socket.
+const FRAME_LEN_SIZE: usize = 4;
+// How much is read from a `SOCK_STREAM` socket at once.
+const STREAM_READ_SIZE: usize = 4096;
+
+/// List of errors the Unix socket backend can throw.
+#[derive(Debug)]
+pub enum Error {
+    /// Unable to connect to the peer socket.
+    Connect(IoError),
+    /// Unable to create the socket.
+    CreateSocket(IoError),
+    /// The socket path is too long.
+    InvalidPath,
+}
+
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+/// The type of a Unix socket backend.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SocketType {
+    /// `SOCK_SEQPACKET`, one frame per message.
+    SeqPacket,
+    /// `SOCK_STREAM`, length prefixed frames.
+    Stream,
+    /// `SOCK_DGRAM`, one frame per datagram.
+    Dgram,
+}
+
+impl SocketType {
+    pub(crate) fn to_libc(self) -> libc::c_int {
+        match self {
+            SocketType::SeqPacket => libc::SOCK_SEQPACKET,
+            SocketType::Stream => libc::SOCK_STREAM,
+            SocketType::Dgram => libc::SOCK_DGRAM,
+        }
+    }
+}
+
+/// Handle for a connected Unix socket, used as a net device backend.
+#[derive(Debug)]
+pub struct UnixSocket {
+    socket: File,
+    path: String,
+    socket_type: SocketType,
+    // Bytes received from a `SOCK_STREAM` socket, which don't make up a whole frame yet.
+    rx_buf: Vec<u8>,
+    // How much is left to skip of a frame too large for the device.
+    rx_discard: usize,
+    // The remainder of a frame partially sent over a `SOCK_STREAM` socket.
+    tx_pending: Vec<u8>,
+}
+
+fn build_sockaddr_un(path: &str) -> Result<libc::sockaddr_un> {
+    // This is safe since `sockaddr_un` is a plain C struct for which all zeroes is valid.
+    let mut addr: libc::sockaddr_un = unsafe { mem::zeroed() };
+    addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+
+    // Leave room for the null terminator.
+    let path = path.as_bytes();
+    if path.is_empty() || path.len() >= addr.sun_path.len() {
+        return Err(Error::InvalidPath);
+    }
+    for (dst, src) in addr.sun_path.iter_mut().zip(path) {
+        *dst = *src as libc::c_char;
+    }
+
+    Ok(addr)
+}
+
+impl UnixSocket {
+    /// Connects to the peer listening at `path`. The socket types are tried in turn, starting
+    /// with `SOCK_SEQPACKET`, then `SOCK_STREAM` and `SOCK_DGRAM`, until one matches the peer's.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the filesystem path of the peer socket.
+    pub fn connect(path: &str) -> Result<UnixSocket> {
+        let addr = build_sockaddr_un(path)?;
+        for socket_type in &[SocketType::SeqPacket, SocketType::Stream] {
+            match Self::connect_with_type(&addr, *socket_type) {
+                // The peer socket is of another type.
+                Err(Error::Connect(ref e)) if e.raw_os_error() == Some(libc::EPROTOTYPE) => (),
+                result => {
+                    return result
+                        .map(|socket| Self::from_connected(socket, path.to_string(), *socket_type))
+                }
+            }
+        }
+        Self::connect_with_type(&addr, SocketType::Dgram)
+            .map(|socket| Self::from_connected(socket, path.to_string(), SocketType::Dgram))
+    }
+
+    fn connect_with_type(addr: &libc::sockaddr_un, socket_type: SocketType) -> Result<File> {
+        // This is safe since we check the return value.
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_UNIX,
+                socket_type.to_libc() | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+                0,
+            )
+        };
+        if fd < 0 {
+            return Err(Error::CreateSocket(IoError::last_os_error()));
+        }
+        // We just checked that the fd is valid.
+        let socket = unsafe { File::from_raw_fd(fd) };
+
+        // This is safe since `addr` is a valid `sockaddr_un` and we check the return value.
+        let ret = unsafe {
+            libc::connect(
+                socket.as_raw_fd(),
+                addr as *const libc::sockaddr_un as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_un>() as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            return Err(Error::Connect(IoError::last_os_error()));
+        }
+
+        Ok(socket)
+    }
+
+    /// Creates a backend from an already connected socket. The socket must be non-blocking.
+    ///
+    /// # Arguments
+    ///
+    /// * `socket` - the connected socket.
+    /// * `path` - a description of the peer, reported by `endpoint()`.
+    /// * `socket_type` - the type of the socket.
+    pub fn from_connected(socket: File, path: String, socket_type: SocketType) -> UnixSocket {
+        UnixSocket {
+            socket,
+            path,
+            socket_type,
+            rx_buf: Vec::new(),
+            rx_discard: 0,
+            tx_pending: Vec::new(),
+        }
+    }
+
+    /// Returns the path of the peer socket.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the type of the socket.
+    pub fn socket_type(&self) -> SocketType {
+        self.socket_type
+    }
+
+    fn hung_up_error() -> IoError {
+        IoError::new(
+            ErrorKind::ConnectionReset,
+            "The peer of the Unix socket hung up.",
+        )
+    }
+
+    // Receives the next length prefixed frame from a `SOCK_STREAM` socket into `buf`, which can
+    // take frames of up to `buf.len()` bytes. Frames are only returned once received whole, and
+    // larger ones are dropped.
+    fn read_stream_frame(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        loop {
+            if self.rx_discard > 0 {
+                let count = cmp::min(self.rx_discard, self.rx_buf.len());
+                self.rx_buf.drain(..count);
+                self.rx_discard -= count;
+            }
+            if self.rx_discard == 0 && self.rx_buf.len() >= FRAME_LEN_SIZE {
+                let mut frame_len = [0u8; FRAME_LEN_SIZE];
+                frame_len.copy_from_slice(&self.rx_buf[..FRAME_LEN_SIZE]);
+                let frame_len = u32::from_be_bytes(frame_len) as usize;
+                if frame_len > buf.len() {
+                    warn!(
+                        "Dropping a {} bytes frame received from the Unix socket.",
+                        frame_len
+                    );
+                    self.rx_buf.drain(..FRAME_LEN_SIZE);
+                    self.rx_discard = frame_len;
+                    continue;
+                }
+                if self.rx_buf.len() >= FRAME_LEN_SIZE + frame_len {
+                    buf[..frame_len]
+                        .copy_from_slice(&self.rx_buf[FRAME_LEN_SIZE..FRAME_LEN_SIZE + frame_len]);
+                    self.rx_buf.drain(..FRAME_LEN_SIZE + frame_len);
+                    return Ok(frame_len);
+                }
+            }
+
+            // The next frame is not whole yet.
+            let len = self.rx_buf.len();
+            self.rx_buf.resize(len + STREAM_READ_SIZE, 0);
+            let result = self.socket.read(&mut self.rx_buf[len..]);
+            self.rx_buf.truncate(len + *result.as_ref().unwrap_or(&0));
+            match result? {
+                0 => return Err(Self::hung_up_error()),
+                _ => continue,
+            }
+        }
+    }
+
+    // Sends whatever is left of the frame partially sent over a `SOCK_STREAM` socket. Fails with
+    // `ErrorKind::WouldBlock` if the socket can't take all of it yet.
+    fn send_pending(&mut self) -> IoResult<()> {
+        while !self.tx_pending.is_empty() {
+            match self.socket.write(&self.tx_pending)? {
+                0 => return Err(IoError::from(ErrorKind::WriteZero)),
+                count => {
+                    self.tx_pending.drain(..count);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Sends a frame over a `SOCK_STREAM` socket, prefixed by its length. If the socket only
+    // takes part of it, the rest is kept and sent before the next frame, or once the socket is
+    // writable again, through `flush()`.
+    fn write_stream_frame(&mut self, frame: &[u8]) -> IoResult<()> {
+        // The frames can't be interleaved, so a frame is dropped while the previous one is not
+        // fully sent.
+        self.send_pending()?;
+
+        let mut data = Vec::with_capacity(FRAME_LEN_SIZE + frame.len());
+        data.extend_from_slice(&(frame.len() as u32).to_be_bytes());
+        data.extend_from_slice(frame);
+        let count = self.socket.write(&data)?;
+        data.drain(..count);
+        self.tx_pending = data;
+        Ok(())
+    }
+}
+
+impl Read for UnixSocket {
+    /// Receives a single frame, prefixed by a zeroed VNET header.
+    ///
+    /// Fails with `ErrorKind::ConnectionReset` once the peer of a `SOCK_SEQPACKET` or
+    /// `SOCK_STREAM` socket hung up. Empty datagrams carry no frame, and are skipped.
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        let hdr_len = cmp::min(vnet_hdr_len(), buf.len());
+        for byte in &mut buf[..hdr_len] {
+            *byte = 0;
+        }
+        if self.socket_type == SocketType::Stream {
+            return self
+                .read_stream_frame(&mut buf[hdr_len..])
+                .map(|count| hdr_len + count);
+        }
+        loop {
+            match self.socket.read(&mut buf[hdr_len..])? {
+                0 if self.socket_type == SocketType::SeqPacket => return Err(Self::hung_up_error()),
+                0 => continue,
+                count => return Ok(hdr_len + count),
+            }
+        }
+    }
+}
+
+impl Write for UnixSocket {
+    /// Sends a single frame, stripping its VNET header.
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        let hdr_len = cmp::min(vnet_hdr_len(), buf.len());
+        if self.socket_type == SocketType::Stream {
+            return self.write_stream_frame(&buf[hdr_len..]).map(|_| buf.len());
+        }
+        let count = self.socket.write(&buf[hdr_len..])?;
+        // A frame is a single message, so it is either sent whole or not at all.
+        if count < buf.len() - hdr_len {
+            return Err(IoError::new(
+                ErrorKind::WriteZero,
+                "The frame was only partially sent.",
+            ));
+        }
+        Ok(buf.len())
+    }
+
+    /// Sends the rest of a frame partially sent over a `SOCK_STREAM` socket.
+    fn flush(&mut self) -> IoResult<()> {
+        self.send_pending()
+    }
+}
+
+impl AsRawFd for UnixSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+}
+
+impl NetBackend for UnixSocket {
+    fn offload_features(&self) -> u64 {
+        // The peer receives raw Ethernet frames, so the guest must not leave checksums or
+        // segmentation to the host.
+        0
+    }
+
+    fn endpoint(&self) -> HostEndpoint {
+        HostEndpoint::UnixSocket(self.path.clone())
+    }
+
+    fn buffers_writes(&self) -> bool {
+        self.socket_type == SocketType::Stream
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::virtio::net::test_utils::unix_socket_pair;
+
+    #[test]
+    fn test_connect() {
+        // Empty and overlong paths are rejected.
+        match UnixSocket::connect("") {
+            Err(Error::InvalidPath) => (),
+            _ => panic!("Expected Error::InvalidPath"),
+        };
+        match UnixSocket::connect(&"a".repeat(108)) {
+            Err(Error::InvalidPath) => (),
+            _ => panic!("Expected Error::InvalidPath"),
+        };
+
+        // Nobody is listening on this path.
+        let tmp_file = utils::tempfile::TempFile::new().unwrap();
+        let path = tmp_file.as_path().to_str().unwrap().to_string();
+        drop(tmp_file);
+        match UnixSocket::connect(&path) {
+            Err(Error::Connect(_)) => (),
+            _ => panic!("Expected Error::Connect"),
+        };
+    }
+
+    #[test]
+    fn test_connect_seqpacket_and_dgram() {
+        let tmp_file = utils::tempfile::TempFile::new().unwrap();
+        let path = tmp_file.as_path().to_str().unwrap().to_string();
+        drop(tmp_file);
+
+        // A datagram peer: the SOCK_SEQPACKET attempt fails and we fall back to SOCK_DGRAM.
+        let peer = std::os::unix::net::UnixDatagram::bind(&path).unwrap();
+        let mut backend = UnixSocket::connect(&path).unwrap();
+        assert_eq!(backend.path(), path);
+        assert_eq!(backend.endpoint(), HostEndpoint::UnixSocket(path.clone()));
+
+        let mut frame = vec![0u8; vnet_hdr_len()];
+        frame.extend_from_slice(b"datagram frame");
+        assert_eq!(backend.write(&frame).unwrap(), frame.len());
+        let mut buf = [0u8; 100];
+        let count = peer.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..count], b"datagram frame");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_connect_stream() {
+        let tmp_file = utils::tempfile::TempFile::new().unwrap();
+        let path = tmp_file.as_path().to_str().unwrap().to_string();
+        drop(tmp_file);
+
+        // A stream peer, like QEMU's `stream` netdev in server mode.
+        let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        let mut backend = UnixSocket::connect(&path).unwrap();
+        assert_eq!(backend.socket_type(), SocketType::Stream);
+        assert!(backend.buffers_writes());
+        let (mut peer, _) = listener.accept().unwrap();
+
+        let mut frame = vec![0u8; vnet_hdr_len()];
+        frame.extend_from_slice(b"stream frame");
+        assert_eq!(backend.write(&frame).unwrap(), frame.len());
+        let mut buf = [0u8; 16];
+        peer.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf[..FRAME_LEN_SIZE], &12u32.to_be_bytes());
+        assert_eq!(&buf[FRAME_LEN_SIZE..], b"stream frame");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_write() {
+        let (mut backend, mut peer) = unix_socket_pair(SocketType::SeqPacket);
+        assert_eq!(backend.socket_type(), SocketType::SeqPacket);
+        assert!(!backend.buffers_writes());
+        assert_eq!(backend.offload_features(), 0);
+        assert_eq!(backend.as_raw_fd(), backend.socket.as_raw_fd());
+
+        // Nothing to read yet.
+        let mut buf = [0xffu8; 1000];
+        assert_eq!(
+            backend.read(&mut buf).unwrap_err().raw_os_error(),
+            Some(libc::EAGAIN)
+        );
+
+        // Frames coming from the peer are prefixed by a zeroed VNET header.
+        let payload = utils::rand::rand_alphanumerics(500);
+        peer.write_all(payload.as_bytes()).unwrap();
+        let count = backend.read(&mut buf).unwrap();
+        assert_eq!(count, vnet_hdr_len() + payload.len());
+        assert_eq!(&buf[..vnet_hdr_len()], vec![0u8; vnet_hdr_len()].as_slice());
+        assert_eq!(&buf[vnet_hdr_len()..count], payload.as_bytes());
+
+        // Frames going to the peer have their VNET header stripped.
+        let mut frame = vec![0x42u8; vnet_hdr_len()];
+        frame.extend_from_slice(payload.as_bytes());
+        assert_eq!(backend.write(&frame).unwrap(), frame.len());
+        let count = peer.read(&mut buf).unwrap();
+        assert_eq!(&buf[..count], payload.as_bytes());
+
+        // Message boundaries are preserved.
+        peer.write_all(b"first").unwrap();
+        peer.write_all(b"second").unwrap();
+        let count = backend.read(&mut buf).unwrap();
+        assert_eq!(&buf[vnet_hdr_len()..count], b"first");
+        let count = backend.read(&mut buf).unwrap();
+        assert_eq!(&buf[vnet_hdr_len()..count], b"second");
+
+        // Once the peer hangs up, reads fail instead of returning empty frames.
+        drop(peer);
+        assert_eq!(
+            backend.read(&mut buf).unwrap_err().kind(),
+            ErrorKind::ConnectionReset
+        );
+    }
+
+    // Returns the length prefixed frames sent by the backend, as the peer would receive them.
+    fn stream_frames(mut data: &[u8]) -> Vec<Vec<u8>> {
+        let mut frames = Vec::new();
+        while !data.is_empty() {
+            let mut frame_len = [0u8; FRAME_LEN_SIZE];
+            frame_len.copy_from_slice(&data[..FRAME_LEN_SIZE]);
+            let frame_len = u32::from_be_bytes(frame_len) as usize;
+            frames.push(data[FRAME_LEN_SIZE..FRAME_LEN_SIZE + frame_len].to_vec());
+            data = &data[FRAME_LEN_SIZE + frame_len..];
+        }
+        frames
+    }
+
+    // Reads all the data the peer can currently receive.
+    fn read_available(peer: &mut File, data: &mut Vec<u8>) {
+        let mut buf = [0u8; 65536];
+        loop {
+            match peer.read(&mut buf) {
+                Ok(count) => data.extend_from_slice(&buf[..count]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return,
+                Err(e) => panic!("Unexpected error: {:?}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn test_stream_read() {
+        let (mut backend, mut peer) = unix_socket_pair(SocketType::Stream);
+        let mut buf = [0xffu8; 1000];
+        let hdr_len = vnet_hdr_len();
+
+        // A frame is only returned once received whole.
+        peer.write_all(&5u32.to_be_bytes()[..2]).unwrap();
+        assert_eq!(
+            backend.read(&mut buf).unwrap_err().kind(),
+            ErrorKind::WouldBlock
+        );
+        peer.write_all(&5u32.to_be_bytes()[2..]).unwrap();
+        peer.write_all(b"fir").unwrap();
+        assert_eq!(
+            backend.read(&mut buf).unwrap_err().kind(),
+            ErrorKind::WouldBlock
+        );
+        peer.write_all(b"st").unwrap();
+        let count = backend.read(&mut buf).unwrap();
+        assert_eq!(count, hdr_len + 5);
+        assert_eq!(&buf[..hdr_len], vec![0u8; hdr_len].as_slice());
+        assert_eq!(&buf[hdr_len..count], b"first");
+
+        // Frames received at once are returned one by one, and frames which don't fit in the
+        // buffer are dropped.
+        let mut data = Vec::new();
+        data.extend_from_slice(&6u32.to_be_bytes());
+        data.extend_from_slice(b"second");
+        data.extend_from_slice(&2000u32.to_be_bytes());
+        data.extend_from_slice(&[0x42u8; 2000]);
+        data.extend_from_slice(&5u32.to_be_bytes());
+        data.extend_from_slice(b"third");
+        peer.write_all(&data).unwrap();
+        let count = backend.read(&mut buf).unwrap();
+        assert_eq!(&buf[hdr_len..count], b"second");
+        let count = backend.read(&mut buf).unwrap();
+        assert_eq!(&buf[hdr_len..count], b"third");
+        assert_eq!(
+            backend.read(&mut buf).unwrap_err().kind(),
+            ErrorKind::WouldBlock
+        );
+
+        // Once the peer hangs up, reads fail instead of returning empty frames.
+        drop(peer);
+        assert_eq!(
+            backend.read(&mut buf).unwrap_err().kind(),
+            ErrorKind::ConnectionReset
+        );
+    }
+
+    #[test]
+    fn test_stream_write() {
+        let (mut backend, mut peer) = unix_socket_pair(SocketType::Stream);
+        let mut frame = vec![0x42u8; vnet_hdr_len()];
+        frame.extend_from_slice(&[0xa5u8; 60000]);
+
+        // Fill the socket until a frame is only partially sent.
+        let mut data = Vec::new();
+        let mut sent_frames = 0;
+        while backend.tx_pending.is_empty() {
+            match backend.write(&frame) {
+                Ok(count) => {
+                    assert_eq!(count, frame.len());
+                    sent_frames += 1;
+                }
+                // The socket filled up right after a whole frame, so make some room.
+                Err(e) => {
+                    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+                    let mut buf = [0u8; 4096];
+                    let count = peer.read(&mut buf).unwrap();
+                    data.extend_from_slice(&buf[..count]);
+                }
+            }
+        }
+        assert!(backend.tx_pending.len() < FRAME_LEN_SIZE + 60000);
+
+        // While the rest of that frame can't be sent, the following frames are dropped.
+        assert_eq!(
+            backend.write(&frame).unwrap_err().kind(),
+            ErrorKind::WouldBlock
+        );
+        assert_eq!(backend.flush().unwrap_err().kind(), ErrorKind::WouldBlock);
+
+        // Once the peer made room, the rest is sent.
+        loop {
+            read_available(&mut peer, &mut data);
+            match backend.flush() {
+                Ok(()) => break,
+                Err(e) => assert_eq!(e.kind(), ErrorKind::WouldBlock),
+            }
+        }
+        assert!(backend.tx_pending.is_empty());
+        read_available(&mut peer, &mut data);
+
+        // The peer got every frame which was accepted, whole and without the VNET header.
+        let frames = stream_frames(&data);
+        assert_eq!(frames.len(), sent_frames);
+        for received in frames {
+            assert_eq!(received.as_slice(), &frame[vnet_hdr_len()..]);
+        }
+    }
+}
@@ -84,14 +84,16 @@ use std::os::unix::io::{AsRawFd, RawFd};
 use std::time::{Duration, Instant};
 
 use logger::{debug, error, info, warn, IncMetric, METRICS};
+use snapshot::Persist;
 use utils::epoll::EventSet;
 
 use super::super::defs::uapi;
 use super::super::packet::VsockPacket;
+use super::super::persist::VsockConnectionState;
 use super::super::{Result as VsockResult, VsockChannel, VsockEpollListener, VsockError};
 use super::defs;
 use super::txbuf::TxBuf;
-use super::{ConnState, Error, PendingRx, PendingRxSet, Result};
+use super::{ConnParams, ConnState, Error, PendingRx, PendingRxSet, Result};
 
 /// A self-managing connection object, that handles communication between a guest-side AF_VSOCK
 /// socket and a host-side `Read + Write + AsRawFd` stream.
@@ -128,6 +130,8 @@ pub struct VsockConnection<S: Read + Write + AsRawFd> {
     /// Instant when this connection should be scheduled for immediate termination, due to some
     /// timeout condition having been fulfilled.
     expiry: Option<Instant>,
+    /// The per-device connection parameters.
+    params: ConnParams,
 }
 
 impl<S> VsockChannel for VsockConnection<S>
@@ -175,7 +179,7 @@ where
         // request.
         if self.pending_rx.remove(PendingRx::Request) {
             self.expiry =
-                Some(Instant::now() + Duration::from_millis(defs::CONN_REQUEST_TIMEOUT_MS));
+                Some(Instant::now() + Duration::from_millis(self.params.request_timeout_ms));
             pkt.set_op(uapi::VSOCK_OP_REQUEST);
             return Ok(());
         }
@@ -219,7 +223,7 @@ where
                         // receive any more data.
                         self.state = ConnState::LocalClosed;
                         self.expiry = Some(
-                            Instant::now() + Duration::from_millis(defs::CONN_SHUTDOWN_TIMEOUT_MS),
+                            Instant::now() + Duration::from_millis(self.params.shutdown_timeout_ms),
                         );
                         pkt.set_op(uapi::VSOCK_OP_SHUTDOWN)
                             .set_flag(uapi::VSOCK_FLAGS_SHUTDOWN_RCV)
@@ -342,7 +346,7 @@ where
                         self.pending_rx.insert(PendingRx::Rst);
                     } else {
                         self.expiry = Some(
-                            Instant::now() + Duration::from_millis(defs::CONN_SHUTDOWN_TIMEOUT_MS),
+                            Instant::now() + Duration::from_millis(self.params.shutdown_timeout_ms),
                         );
                     }
                 }
@@ -496,6 +500,7 @@ where
         local_port: u32,
         peer_port: u32,
         peer_buf_alloc: u32,
+        params: ConnParams,
     ) -> Self {
         Self {
             local_cid,
@@ -504,7 +509,7 @@ where
             peer_port,
             stream,
             state: ConnState::PeerInit,
-            tx_buf: TxBuf::new(),
+            tx_buf: TxBuf::new(params.buf_alloc),
             fwd_cnt: Wrapping(0),
             peer_buf_alloc,
             peer_fwd_cnt: Wrapping(0),
@@ -512,6 +517,7 @@ where
             last_fwd_cnt_to_peer: Wrapping(0),
             pending_rx: PendingRxSet::from(PendingRx::Response),
             expiry: None,
+            params,
         }
     }
 
@@ -522,6 +528,7 @@ where
         peer_cid: u64,
         local_port: u32,
         peer_port: u32,
+        params: ConnParams,
     ) -> Self {
         Self {
             local_cid,
@@ -530,7 +537,7 @@ where
             peer_port,
             stream,
             state: ConnState::LocalInit,
-            tx_buf: TxBuf::new(),
+            tx_buf: TxBuf::new(params.buf_alloc),
             fwd_cnt: Wrapping(0),
             peer_buf_alloc: 0,
             peer_fwd_cnt: Wrapping(0),
@@ -538,6 +545,7 @@ where
             last_fwd_cnt_to_peer: Wrapping(0),
             pending_rx: PendingRxSet::from(PendingRx::Request),
             expiry: None,
+            params,
         }
     }
 
@@ -576,6 +584,11 @@ where
         self.state
     }
 
+    /// Return the underlying host-side stream.
+    pub fn stream(&self) -> &S {
+        &self.stream
+    }
+
     /// Send some raw, untracked, data straight to the underlying connected stream.
     /// Returns: number of bytes written, or the error describing the write failure.
     ///
@@ -630,7 +643,7 @@ where
     /// Check if the credit information the peer has last received from us is outdated.
     fn peer_needs_credit_update(&self) -> bool {
         let peer_seen_free_buf =
-            Wrapping(defs::CONN_TX_BUF_SIZE) - (self.fwd_cnt - self.last_fwd_cnt_to_peer);
+            Wrapping(self.params.buf_alloc) - (self.fwd_cnt - self.last_fwd_cnt_to_peer);
         peer_seen_free_buf < Wrapping(defs::CONN_CREDIT_UPDATE_THRESHOLD)
     }
 
@@ -660,11 +673,95 @@ where
             .set_src_port(self.local_port)
             .set_dst_port(self.peer_port)
             .set_type(uapi::VSOCK_TYPE_STREAM)
-            .set_buf_alloc(defs::CONN_TX_BUF_SIZE)
+            .set_buf_alloc(self.params.buf_alloc)
             .set_fwd_cnt(self.fwd_cnt.0)
     }
 }
 
+/// A helper structure that holds the constructor arguments for `VsockConnection`.
+pub struct VsockConnectionConstructorArgs<S> {
+    /// The (connected) host-side stream to resume the connection over.
+    pub stream: S,
+    /// The local CID.
+    pub local_cid: u64,
+    /// The peer (guest) CID.
+    pub peer_cid: u64,
+    /// The per-device connection parameters.
+    pub params: ConnParams,
+}
+
+impl<S> Persist<'_> for VsockConnection<S>
+where
+    S: Read + Write + AsRawFd,
+{
+    type State = VsockConnectionState;
+    type ConstructorArgs = VsockConnectionConstructorArgs<S>;
+    type Error = Error;
+
+    fn save(&self) -> Self::State {
+        let (resumable, peer_closed_recv, peer_closed_send) = match self.state {
+            ConnState::Established => (true, false, false),
+            // Once the guest has shut down both directions, the connection is only waiting to
+            // be torn down.
+            ConnState::PeerClosed(recv_off, send_off) => {
+                (!(recv_off && send_off), recv_off, send_off)
+            }
+            _ => (false, false, false),
+        };
+        VsockConnectionState {
+            local_port: self.local_port,
+            peer_port: self.peer_port,
+            resumable,
+            peer_closed_recv,
+            peer_closed_send,
+            fwd_cnt: self.fwd_cnt.0,
+            peer_buf_alloc: self.peer_buf_alloc,
+            peer_fwd_cnt: self.peer_fwd_cnt.0,
+            rx_cnt: self.rx_cnt.0,
+            last_fwd_cnt_to_peer: self.last_fwd_cnt_to_peer.0,
+            pending_rx: self.pending_rx.data,
+            tx_buf: self.tx_buf.to_vec(),
+        }
+    }
+
+    /// Resume a connection over a new host stream. Only meant for states that are
+    /// `resumable`, since the connection will be either established or half-closed.
+    fn restore(
+        constructor_args: Self::ConstructorArgs,
+        state: &Self::State,
+    ) -> std::result::Result<Self, Self::Error> {
+        let mut tx_buf = TxBuf::new(constructor_args.params.buf_alloc);
+        if !state.tx_buf.is_empty() {
+            tx_buf.push(&state.tx_buf)?;
+        }
+        let conn_state = if state.peer_closed_recv || state.peer_closed_send {
+            ConnState::PeerClosed(state.peer_closed_recv, state.peer_closed_send)
+        } else {
+            ConnState::Established
+        };
+
+        Ok(Self {
+            local_cid: constructor_args.local_cid,
+            peer_cid: constructor_args.peer_cid,
+            local_port: state.local_port,
+            peer_port: state.peer_port,
+            stream: constructor_args.stream,
+            state: conn_state,
+            tx_buf,
+            fwd_cnt: Wrapping(state.fwd_cnt),
+            peer_buf_alloc: state.peer_buf_alloc,
+            peer_fwd_cnt: Wrapping(state.peer_fwd_cnt),
+            rx_cnt: Wrapping(state.rx_cnt),
+            last_fwd_cnt_to_peer: Wrapping(state.last_fwd_cnt_to_peer),
+            pending_rx: PendingRxSet {
+                data: state.pending_rx,
+            },
+            expiry: None,
+            params: constructor_args.params,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::{Error as IoError, ErrorKind, Read, Result as IoResult, Write};
@@ -826,9 +923,15 @@ mod tests {
                     LOCAL_PORT,
                     PEER_PORT,
                     PEER_BUF_ALLOC,
+                    ConnParams::default(),
                 ),
                 ConnState::LocalInit => VsockConnection::<TestStream>::new_local_init(
-                    stream, LOCAL_CID, PEER_CID, LOCAL_PORT, PEER_PORT,
+                    stream,
+                    LOCAL_CID,
+                    PEER_CID,
+                    LOCAL_PORT,
+                    PEER_PORT,
+                    ConnParams::default(),
                 ),
                 ConnState::Established => {
                     let mut conn = VsockConnection::<TestStream>::new_peer_init(
@@ -838,6 +941,7 @@ mod tests {
                         LOCAL_PORT,
                         PEER_PORT,
                         PEER_BUF_ALLOC,
+                        ConnParams::default(),
                     );
                     assert!(conn.has_pending_rx());
                     conn.recv_pkt(&mut pkt).unwrap();
@@ -947,6 +1051,41 @@ mod tests {
         assert!(ctx.conn.has_expired());
     }
 
+    #[test]
+    fn test_custom_params() {
+        let vsock_test_ctx = TestContext::new();
+        let mut handler_ctx = vsock_test_ctx.create_event_handler_context();
+        let mut pkt = VsockPacket::from_rx_virtq_head(
+            &handler_ctx.device.queues[RXQ_INDEX]
+                .pop(&vsock_test_ctx.mem)
+                .unwrap(),
+        )
+        .unwrap();
+        let params = ConnParams {
+            buf_alloc: 16 * 1024,
+            request_timeout_ms: 10,
+            shutdown_timeout_ms: 20,
+        };
+        let mut conn = VsockConnection::<TestStream>::new_local_init(
+            TestStream::new(),
+            LOCAL_CID,
+            PEER_CID,
+            LOCAL_PORT,
+            PEER_PORT,
+            params,
+        );
+
+        // The connection request should advertise the configured buffer space, and expire after
+        // the configured timeout.
+        conn.recv_pkt(&mut pkt).unwrap();
+        assert_eq!(pkt.op(), uapi::VSOCK_OP_REQUEST);
+        assert_eq!(pkt.buf_alloc(), params.buf_alloc);
+        assert!(conn.will_expire());
+        assert!(!conn.has_expired());
+        std::thread::sleep(std::time::Duration::from_millis(params.request_timeout_ms));
+        assert!(conn.has_expired());
+    }
+
     #[test]
     fn test_rx_data() {
         let mut ctx = CsmTestContext::new_established();
@@ -1234,4 +1373,72 @@ mod tests {
         ctx.recv();
         assert_eq!(ctx.pkt.op(), uapi::VSOCK_OP_RST);
     }
+
+    #[test]
+    fn test_persist() {
+        // Test case: an established connection, with buffered TX data, is resumed over a new
+        // stream with the same counters, and flushes its TX buffer there.
+        {
+            let mut ctx = CsmTestContext::new_established();
+            let mut stream = TestStream::new();
+            stream.write_state = StreamState::WouldBlock;
+            ctx.set_stream(stream);
+            let data = &[1, 2, 3, 4];
+            ctx.init_data_pkt(data);
+            ctx.send();
+            ctx.conn.pending_rx.insert(PendingRx::CreditUpdate);
+
+            let state = ctx.conn.save();
+            assert!(state.resumable);
+            assert_eq!(state.local_port, LOCAL_PORT);
+            assert_eq!(state.peer_port, PEER_PORT);
+            assert_eq!(state.tx_buf, data);
+
+            let mut conn = VsockConnection::restore(
+                VsockConnectionConstructorArgs {
+                    stream: TestStream::new(),
+                    local_cid: LOCAL_CID,
+                    peer_cid: PEER_CID,
+                    params: ConnParams::default(),
+                },
+                &state,
+            )
+            .unwrap();
+            assert_eq!(conn.state(), ConnState::Established);
+            assert_eq!(conn.peer_buf_alloc, PEER_BUF_ALLOC);
+            assert_eq!(conn.fwd_cnt, ctx.conn.fwd_cnt);
+            assert!(conn.has_pending_rx());
+            assert!(conn.get_polled_evset().contains(EventSet::OUT));
+            conn.notify(EventSet::OUT);
+            assert!(conn.tx_buf.is_empty());
+            assert_eq!(conn.stream.write_buf, data);
+        }
+
+        // Test case: half-closed connections keep their shutdown indications.
+        {
+            let mut ctx = CsmTestContext::new_established();
+            ctx.init_pkt(uapi::VSOCK_OP_SHUTDOWN, 0)
+                .set_flags(uapi::VSOCK_FLAGS_SHUTDOWN_SEND);
+            ctx.send();
+            let state = ctx.conn.save();
+            assert!(state.resumable);
+            let conn = VsockConnection::restore(
+                VsockConnectionConstructorArgs {
+                    stream: TestStream::new(),
+                    local_cid: LOCAL_CID,
+                    peer_cid: PEER_CID,
+                    params: ConnParams::default(),
+                },
+                &state,
+            )
+            .unwrap();
+            assert_eq!(conn.state(), ConnState::PeerClosed(false, true));
+        }
+
+        // Test case: connections still being set up can't be resumed.
+        {
+            let ctx = CsmTestContext::new(ConnState::LocalInit);
+            assert!(!ctx.conn.save().resumable);
+        }
+    }
 }
@@ -6,23 +6,61 @@
 mod connection;
 mod txbuf;
 
-pub use connection::VsockConnection;
+pub use connection::{VsockConnection, VsockConnectionConstructorArgs};
 
 pub mod defs {
-    /// Vsock connection TX buffer capacity.
+    /// Default vsock connection TX buffer capacity.
     pub const CONN_TX_BUF_SIZE: u32 = 64 * 1024;
 
+    /// Minimum vsock connection TX buffer capacity. This must leave room above the credit
+    /// update threshold.
+    pub const CONN_TX_BUF_SIZE_MIN: u32 = 8 * 1024;
+
+    /// Maximum vsock connection TX buffer capacity.
+    pub const CONN_TX_BUF_SIZE_MAX: u32 = 16 * 1024 * 1024;
+
     /// When the guest thinks we have less than this amount of free buffer space,
     /// we will send them a credit update packet.
     pub const CONN_CREDIT_UPDATE_THRESHOLD: u32 = 4 * 1024;
 
-    /// Connection request timeout, in millis.
+    /// Default connection request timeout, in millis.
     pub const CONN_REQUEST_TIMEOUT_MS: u64 = 2000;
 
-    /// Connection graceful shutdown timeout, in millis.
+    /// Default connection graceful shutdown timeout, in millis.
     pub const CONN_SHUTDOWN_TIMEOUT_MS: u64 = 2000;
 }
 
+/// Connection parameters that can be tuned per device.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ConnParams {
+    /// The connection TX buffer capacity, advertised to the guest as `buf_alloc`. Since the TX
+    /// buffer is a ring indexed by wrapping `u32` counters, this must be a power of two.
+    pub buf_alloc: u32,
+    /// Connection request timeout, in millis.
+    pub request_timeout_ms: u64,
+    /// Connection graceful shutdown timeout, in millis.
+    pub shutdown_timeout_ms: u64,
+}
+
+impl Default for ConnParams {
+    fn default() -> Self {
+        ConnParams {
+            buf_alloc: defs::CONN_TX_BUF_SIZE,
+            request_timeout_ms: defs::CONN_REQUEST_TIMEOUT_MS,
+            shutdown_timeout_ms: defs::CONN_SHUTDOWN_TIMEOUT_MS,
+        }
+    }
+}
+
+impl ConnParams {
+    /// Check if `buf_alloc` can be used as a connection TX buffer capacity.
+    pub fn is_valid_buf_alloc(buf_alloc: u32) -> bool {
+        buf_alloc.is_power_of_two()
+            && buf_alloc >= defs::CONN_TX_BUF_SIZE_MIN
+            && buf_alloc <= defs::CONN_TX_BUF_SIZE_MAX
+    }
+}
+
 #[derive(Debug)]
 pub enum Error {
     /// Attempted to push data to a full TX buffer.
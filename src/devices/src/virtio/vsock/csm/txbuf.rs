@@ -5,7 +5,6 @@
 use std::io::Write;
 use std::num::Wrapping;
 
-use super::defs;
 use super::{Error, Result};
 
 /// A simple ring-buffer implementation, used by vsock connections to buffer TX (guest -> host)
@@ -18,18 +17,19 @@ pub struct TxBuf {
     head: Wrapping<u32>,
     /// Ring-buffer tail offset - where data is flushed from.
     tail: Wrapping<u32>,
+    /// Total buffer size, in bytes. Must be a power of two, so that offsets stay consistent
+    /// when the head and tail counters wrap around.
+    size: usize,
 }
 
 impl TxBuf {
-    /// Total buffer size, in bytes.
-    const SIZE: usize = defs::CONN_TX_BUF_SIZE as usize;
-
     /// Ring-buffer constructor.
-    pub fn new() -> Self {
+    pub fn new(size: u32) -> Self {
         Self {
             data: None,
             head: Wrapping(0),
             tail: Wrapping(0),
+            size: size as usize,
         }
     }
 
@@ -45,23 +45,24 @@ impl TxBuf {
     /// there isn't enough room, in which case `Err(Error::TxBufFull)` is returned.
     pub fn push(&mut self, src: &[u8]) -> Result<()> {
         // Error out if there's no room to push the entire slice.
-        if self.len() + src.len() > Self::SIZE {
+        if self.len() + src.len() > self.size {
             return Err(Error::TxBufFull);
         }
 
+        let size = self.size;
         let data = self
             .data
-            .get_or_insert_with(|| vec![0u8; Self::SIZE].into_boxed_slice());
+            .get_or_insert_with(|| vec![0u8; size].into_boxed_slice());
 
         // Buffer head, as an offset into the data slice.
-        let head_ofs = self.head.0 as usize % Self::SIZE;
+        let head_ofs = self.head.0 as usize % self.size;
 
         // Pushing a slice to this buffer can take either one or two slice copies: - one copy,
-        // if the slice fits between `head_ofs` and `Self::SIZE`; or - two copies, if the
+        // if the slice fits between `head_ofs` and `self.size`; or - two copies, if the
         // ring-buffer head wraps around.
 
         // First copy length: we can only go from the head offset up to the total buffer size.
-        let len = std::cmp::min(Self::SIZE - head_ofs, src.len());
+        let len = std::cmp::min(self.size - head_ofs, src.len());
         data[head_ofs..(head_ofs + len)].copy_from_slice(&src[..len]);
 
         // If the slice didn't fit, the buffer head will wrap around, and pushing continues
@@ -91,7 +92,7 @@ impl TxBuf {
         }
 
         // Buffer tail, as an offset into the buffer data slice.
-        let tail_ofs = self.tail.0 as usize % Self::SIZE;
+        let tail_ofs = self.tail.0 as usize % self.size;
 
         // Flushing the buffer can take either one or two writes:
         // - one write, if the tail doesn't need to wrap around to reach the head; or
@@ -99,7 +100,7 @@ impl TxBuf {
         //   head.
 
         // First write length: the lesser of tail to slice end, or tail to head.
-        let len_to_write = std::cmp::min(Self::SIZE - tail_ofs, self.len());
+        let len_to_write = std::cmp::min(self.size - tail_ofs, self.len());
 
         // It's safe to unwrap here, since we've already checked if the buffer was empty.
         let data = self.data.as_ref().unwrap();
@@ -133,6 +134,18 @@ impl TxBuf {
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
+
+    /// Copy out the data that hasn't yet been flushed, without consuming it.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.len());
+        if let Some(data) = self.data.as_ref() {
+            let tail_ofs = self.tail.0 as usize % self.size;
+            let len = std::cmp::min(self.size - tail_ofs, self.len());
+            out.extend_from_slice(&data[tail_ofs..(tail_ofs + len)]);
+            out.extend_from_slice(&data[..(self.len() - len)]);
+        }
+        out
+    }
 }
 
 #[cfg(test)]
@@ -142,6 +155,10 @@ mod tests {
     use std::io::Result as IoResult;
     use std::io::{ErrorKind, Write};
 
+    use super::super::defs;
+
+    const SIZE: usize = defs::CONN_TX_BUF_SIZE as usize;
+
     struct TestSink {
         data: Vec<u8>,
         err: Option<IoError>,
@@ -149,7 +166,7 @@ mod tests {
     }
 
     impl TestSink {
-        const DEFAULT_CAPACITY: usize = 2 * TxBuf::SIZE;
+        const DEFAULT_CAPACITY: usize = 2 * SIZE;
         fn new() -> Self {
             Self {
                 data: Vec::with_capacity(Self::DEFAULT_CAPACITY),
@@ -191,7 +208,7 @@ mod tests {
 
     #[test]
     fn test_push_nowrap() {
-        let mut txbuf = TxBuf::new();
+        let mut txbuf = TxBuf::new(SIZE as u32);
         let mut sink = TestSink::new();
         assert!(txbuf.is_empty());
 
@@ -204,26 +221,66 @@ mod tests {
 
     #[test]
     fn test_push_wrap() {
-        let mut txbuf = TxBuf::new();
+        let mut txbuf = TxBuf::new(SIZE as u32);
         let mut sink = TestSink::new();
         let mut tmp: Vec<u8> = Vec::new();
 
-        tmp.resize(TxBuf::SIZE - 2, 0);
+        tmp.resize(SIZE - 2, 0);
+        txbuf.push(tmp.as_slice()).unwrap();
+        txbuf.flush_to(&mut sink).unwrap();
+        sink.clear();
+
+        txbuf.push(&[1, 2, 3, 4]).unwrap();
+        assert_eq!(txbuf.flush_to(&mut sink).unwrap(), 4);
+        assert_eq!(sink.data, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_to_vec() {
+        let mut txbuf = TxBuf::new(SIZE as u32);
+        let mut sink = TestSink::new();
+        assert!(txbuf.to_vec().is_empty());
+
+        let tmp = vec![0u8; SIZE - 2];
+        txbuf.push(tmp.as_slice()).unwrap();
+        txbuf.flush_to(&mut sink).unwrap();
+
+        // The data wraps around the end of the buffer, and isn't consumed.
+        txbuf.push(&[1, 2, 3, 4]).unwrap();
+        assert_eq!(txbuf.to_vec(), vec![1, 2, 3, 4]);
+        assert_eq!(txbuf.len(), 4);
+    }
+
+    #[test]
+    fn test_custom_size() {
+        let size = defs::CONN_TX_BUF_SIZE_MIN as usize;
+        let mut txbuf = TxBuf::new(size as u32);
+        let mut sink = TestSink::new();
+
+        let tmp = vec![0u8; size - 2];
         txbuf.push(tmp.as_slice()).unwrap();
         txbuf.flush_to(&mut sink).unwrap();
         sink.clear();
 
         txbuf.push(&[1, 2, 3, 4]).unwrap();
+        assert_eq!(txbuf.data.as_ref().unwrap().len(), size);
         assert_eq!(txbuf.flush_to(&mut sink).unwrap(), 4);
         assert_eq!(sink.data, [1, 2, 3, 4]);
+
+        let tmp = vec![0u8; size];
+        txbuf.push(tmp.as_slice()).unwrap();
+        match txbuf.push(&[1]) {
+            Err(Error::TxBufFull) => (),
+            other => panic!("Unexpected result: {:?}", other),
+        }
     }
 
     #[test]
     fn test_push_error() {
-        let mut txbuf = TxBuf::new();
-        let mut tmp = Vec::with_capacity(TxBuf::SIZE);
+        let mut txbuf = TxBuf::new(SIZE as u32);
+        let mut tmp = Vec::with_capacity(SIZE);
 
-        tmp.resize(TxBuf::SIZE - 1, 0);
+        tmp.resize(SIZE - 1, 0);
         txbuf.push(tmp.as_slice()).unwrap();
         match txbuf.push(&[1, 2]) {
             Err(Error::TxBufFull) => (),
@@ -233,7 +290,7 @@ mod tests {
 
     #[test]
     fn test_incomplete_flush() {
-        let mut txbuf = TxBuf::new();
+        let mut txbuf = TxBuf::new(SIZE as u32);
         let mut sink = TestSink::new();
 
         sink.set_capacity(2);
@@ -252,7 +309,7 @@ mod tests {
     fn test_flush_error() {
         const EACCESS: i32 = 13;
 
-        let mut txbuf = TxBuf::new();
+        let mut txbuf = TxBuf::new(SIZE as u32);
         let mut sink = TestSink::new();
 
         txbuf.push(&[1, 2, 3, 4]).unwrap();
@@ -49,6 +49,7 @@ pub(crate) const AVAIL_FEATURES: u64 =
     1 << uapi::VIRTIO_F_VERSION_1 as u64 | 1 << uapi::VIRTIO_F_IN_ORDER as u64;
 
 pub struct Vsock<B> {
+    id: String,
     cid: u64,
     pub(crate) queues: Vec<VirtQueue>,
     pub(crate) queue_events: Vec<EventFd>,
@@ -75,13 +76,19 @@ impl<B> Vsock<B>
 where
     B: VsockBackend,
 {
-    pub fn with_queues(cid: u64, backend: B, queues: Vec<VirtQueue>) -> super::Result<Vsock<B>> {
+    pub fn with_queues(
+        id: String,
+        cid: u64,
+        backend: B,
+        queues: Vec<VirtQueue>,
+    ) -> super::Result<Vsock<B>> {
         let mut queue_events = Vec::new();
         for _ in 0..queues.len() {
             queue_events.push(EventFd::new(libc::EFD_NONBLOCK).map_err(VsockError::EventFd)?);
         }
 
         Ok(Vsock {
+            id,
             cid,
             queues,
             queue_events,
@@ -95,17 +102,17 @@ where
         })
     }
 
-    /// Create a new virtio-vsock device with the given VM CID and vsock backend.
-    pub fn new(cid: u64, backend: B) -> super::Result<Vsock<B>> {
+    /// Create a new virtio-vsock device with the given ID, VM CID and vsock backend.
+    pub fn new(id: String, cid: u64, backend: B) -> super::Result<Vsock<B>> {
         let queues: Vec<VirtQueue> = defs::QUEUE_SIZES
             .iter()
             .map(|&max_size| VirtQueue::new(max_size))
             .collect();
-        Self::with_queues(cid, backend, queues)
+        Self::with_queues(id, cid, backend, queues)
     }
 
     pub fn id(&self) -> &str {
-        defs::VSOCK_DEV_ID
+        &self.id
     }
 
     pub fn cid(&self) -> u64 {
@@ -116,6 +123,10 @@ where
         &self.backend
     }
 
+    pub fn backend_mut(&mut self) -> &mut B {
+        &mut self.backend
+    }
+
     /// Signal the guest driver that we've used some virtio buffers that it had previously made
     /// available.
     pub fn signal_used_queue(&self) -> result::Result<(), DeviceError> {
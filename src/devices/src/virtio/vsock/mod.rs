@@ -17,9 +17,15 @@ use std::os::unix::io::AsRawFd;
 
 use crate::virtio::persist::Error as VirtioStateError;
 
+pub use self::csm::defs::{
+    CONN_TX_BUF_SIZE_MAX as VSOCK_BUF_ALLOC_MAX, CONN_TX_BUF_SIZE_MIN as VSOCK_BUF_ALLOC_MIN,
+};
+pub use self::csm::ConnParams as VsockConnParams;
 pub use self::defs::uapi::VIRTIO_ID_VSOCK as TYPE_VSOCK;
 pub use self::device::Vsock;
-pub use self::unix::{Error as VsockUnixBackendError, VsockUnixBackend};
+pub use self::unix::{
+    Error as VsockUnixBackendError, VsockUnixBackend, MAX_CONNECTIONS as VSOCK_MAX_CONNECTIONS,
+};
 
 use utils::epoll::EventSet;
 use vm_memory::GuestMemoryError;
@@ -27,10 +33,6 @@ use vm_memory::GuestMemoryError;
 use packet::VsockPacket;
 
 mod defs {
-    /// Device ID used in MMIO device identification.
-    /// Because Vsock is unique per-vm, this ID can be hardcoded.
-    pub const VSOCK_DEV_ID: &str = "vsock";
-
     /// Number of virtio queues.
     pub const NUM_QUEUES: usize = 3;
     /// Max size of virtio queues.
@@ -159,5 +161,5 @@ pub trait VsockChannel {
 
 /// The vsock backend, which is basically an epoll-event-driven vsock channel.
 /// Currently, the only implementation we have is `crate::virtio::unix::muxer::VsockMuxer`, which
-/// translates guest-side vsock connections to host-side Unix domain socket connections.
+/// translates guest-side vsock connections to host-side Unix domain socket or TCP connections.
 pub trait VsockBackend: VsockChannel + VsockEpollListener + Send {}
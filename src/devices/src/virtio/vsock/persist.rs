@@ -3,15 +3,20 @@
 
 //! Defines state and support structures for persisting Vsock devices and backends.
 
+use std::net::SocketAddr;
 use std::sync::atomic::AtomicUsize;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use super::*;
+use logger::warn;
+use mmds::data_store::Mmds;
 use snapshot::Persist;
 use versionize::{VersionMap, Versionize, VersionizeError, VersionizeResult};
 use versionize_derive::Versionize;
 use vm_memory::GuestMemoryMmap;
 
+use super::csm::ConnParams;
+use super::device::RXQ_INDEX;
 use crate::virtio::persist::VirtioDeviceState;
 use crate::virtio::{DeviceState, TYPE_VSOCK};
 
@@ -43,10 +48,162 @@ pub enum VsockBackendState {
 pub struct VsockUdsState {
     /// The path for the UDS socket.
     pub(crate) path: String,
+    /// The guest ports with a dedicated host-side listening socket.
+    #[version(start = 2, default_fn = "def_listen_ports")]
+    pub(crate) listen_ports: Vec<u32>,
+    /// The guest ports with a host TCP listener.
+    #[version(start = 2, default_fn = "def_tcp_ports")]
+    pub(crate) tcp_listeners: Vec<VsockTcpPortState>,
+    /// The host ports forwarded to TCP endpoints.
+    #[version(start = 2, default_fn = "def_tcp_ports")]
+    pub(crate) tcp_endpoints: Vec<VsockTcpPortState>,
+    /// Whether live connections are saved in snapshots.
+    #[version(start = 2, default_fn = "def_persist_connections")]
+    pub(crate) persist_connections: bool,
+    /// The live connections, if `persist_connections` is set.
+    #[version(start = 2, default_fn = "def_connections")]
+    pub(crate) connections: Vec<VsockConnectionState>,
+    /// The connection tuning parameters.
+    #[version(start = 2, default_fn = "def_conn_params")]
+    pub(crate) conn_params: VsockConnParamsState,
+    /// The host port on which the MMDS is served, if any.
+    #[version(start = 2, default_fn = "def_mmds_port")]
+    pub(crate) mmds_port: Option<u32>,
+}
+
+impl VsockUdsState {
+    fn def_listen_ports(_source_version: u16) -> Vec<u32> {
+        Vec::new()
+    }
+
+    fn def_tcp_ports(_source_version: u16) -> Vec<VsockTcpPortState> {
+        Vec::new()
+    }
+
+    fn def_persist_connections(_source_version: u16) -> bool {
+        false
+    }
+
+    fn def_connections(_source_version: u16) -> Vec<VsockConnectionState> {
+        Vec::new()
+    }
+
+    fn def_conn_params(_source_version: u16) -> VsockConnParamsState {
+        VsockConnParamsState::from_params(ConnParams::default(), VSOCK_MAX_CONNECTIONS)
+    }
+
+    fn def_mmds_port(_source_version: u16) -> Option<u32> {
+        None
+    }
+}
+
+/// The serializable connection tuning parameters of a vsock backend.
+#[derive(Clone, Debug, PartialEq, Versionize)]
+// NOTICE: Any changes to this structure require a snapshot version bump.
+pub struct VsockConnParamsState {
+    /// The buffer space advertised to the guest, per connection.
+    pub(crate) buf_alloc: u32,
+    /// The maximum number of concurrent connections.
+    pub(crate) max_connections: u32,
+    /// The connection request timeout, in milliseconds.
+    pub(crate) request_timeout_ms: u64,
+    /// The connection shutdown timeout, in milliseconds.
+    pub(crate) shutdown_timeout_ms: u64,
+}
+
+impl VsockConnParamsState {
+    fn from_params(params: ConnParams, max_connections: usize) -> Self {
+        VsockConnParamsState {
+            buf_alloc: params.buf_alloc,
+            max_connections: max_connections as u32,
+            request_timeout_ms: params.request_timeout_ms,
+            shutdown_timeout_ms: params.shutdown_timeout_ms,
+        }
+    }
+
+    fn params(&self) -> ConnParams {
+        ConnParams {
+            buf_alloc: self.buf_alloc,
+            request_timeout_ms: self.request_timeout_ms,
+            shutdown_timeout_ms: self.shutdown_timeout_ms,
+        }
+    }
+}
+
+/// The serializable state of a vsock connection.
+#[derive(Clone, Debug, PartialEq, Versionize)]
+// NOTICE: Any changes to this structure require a snapshot version bump.
+pub struct VsockConnectionState {
+    /// The local (host) port.
+    pub(crate) local_port: u32,
+    /// The peer (guest) port.
+    pub(crate) peer_port: u32,
+    /// Whether the connection was established, or only half-closed by the guest. All other
+    /// connections are reset after restore.
+    pub(crate) resumable: bool,
+    /// The guest will not receive any more data.
+    pub(crate) peer_closed_recv: bool,
+    /// The guest will not send any more data.
+    pub(crate) peer_closed_send: bool,
+    /// Total number of bytes forwarded to the host stream.
+    pub(crate) fwd_cnt: u32,
+    /// The buffer space that the guest has allocated for this connection.
+    pub(crate) peer_buf_alloc: u32,
+    /// Total number of bytes that the guest has forwarded away.
+    pub(crate) peer_fwd_cnt: u32,
+    /// Total number of bytes sent to the guest.
+    pub(crate) rx_cnt: u32,
+    /// The forward count, as last sent to the guest.
+    pub(crate) last_fwd_cnt_to_peer: u32,
+    /// The set of pending RX indications, as a bitmask.
+    pub(crate) pending_rx: u16,
+    /// Guest data not yet flushed to the host stream.
+    pub(crate) tx_buf: Vec<u8>,
+}
+
+/// A host Unix socket to which a persisted connection is reattached, on restore.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VsockConnReattach {
+    /// The ID of the vsock device that the connection belongs to. When not set, the
+    /// connection is looked up on the only vsock device.
+    pub vsock_id: Option<String>,
+    /// The local (host) port of the connection.
+    pub local_port: u32,
+    /// The peer (guest) port of the connection. When not set, the first connection on
+    /// `local_port` that wasn't already reattached is used.
+    pub peer_port: Option<u32>,
+    /// The path of the Unix socket to connect to.
+    pub uds_path: String,
+}
+
+/// A vsock port mapped to a host TCP address.
+#[derive(Clone, Debug, PartialEq, Versionize)]
+// NOTICE: Any changes to this structure require a snapshot version bump.
+pub struct VsockTcpPortState {
+    /// The vsock port.
+    pub(crate) port: u32,
+    /// The host TCP address.
+    pub(crate) addr: String,
+}
+
+impl VsockTcpPortState {
+    fn from_socket_addr((port, addr): (u32, SocketAddr)) -> Self {
+        VsockTcpPortState {
+            port,
+            addr: addr.to_string(),
+        }
+    }
+
+    fn socket_addr(&self) -> std::result::Result<SocketAddr, VsockUnixBackendError> {
+        self.addr.parse().map_err(|_| {
+            VsockUnixBackendError::TcpBind(std::io::Error::from(std::io::ErrorKind::InvalidInput))
+        })
+    }
 }
 
 /// A helper structure that holds the constructor arguments for VsockUnixBackend
 pub struct VsockConstructorArgs<B> {
+    pub id: String,
     pub mem: GuestMemoryMmap,
     pub backend: B,
 }
@@ -55,6 +212,11 @@ pub struct VsockConstructorArgs<B> {
 pub struct VsockUdsConstructorArgs {
     // cid available in VsockFrontendState.
     pub cid: u64,
+    /// Host sockets to reattach persisted connections to. Persisted connections that don't
+    /// get reattached are reset.
+    pub reattach: Vec<VsockConnReattach>,
+    /// The data store serving the MMDS port, if the backend has one.
+    pub mmds: Arc<Mutex<Mmds>>,
 }
 
 impl Persist<'_> for VsockUnixBackend {
@@ -65,6 +227,24 @@ impl Persist<'_> for VsockUnixBackend {
     fn save(&self) -> Self::State {
         VsockBackendState::Uds(VsockUdsState {
             path: self.host_sock_path.clone(),
+            listen_ports: self.listen_ports(),
+            tcp_listeners: self
+                .tcp_listeners()
+                .into_iter()
+                .map(VsockTcpPortState::from_socket_addr)
+                .collect(),
+            tcp_endpoints: self
+                .tcp_endpoints()
+                .into_iter()
+                .map(VsockTcpPortState::from_socket_addr)
+                .collect(),
+            persist_connections: self.persist_connections(),
+            connections: self.connection_states(),
+            conn_params: VsockConnParamsState::from_params(
+                self.conn_params(),
+                self.max_connections(),
+            ),
+            mmds_port: self.mmds_port(),
         })
     }
 
@@ -73,10 +253,48 @@ impl Persist<'_> for VsockUnixBackend {
         state: &Self::State,
     ) -> std::result::Result<Self, Self::Error> {
         match state {
-            VsockBackendState::Uds(uds_state) => Ok(VsockUnixBackend::new(
-                constructor_args.cid,
-                uds_state.path.clone(),
-            )?),
+            VsockBackendState::Uds(uds_state) => {
+                let mut backend =
+                    VsockUnixBackend::new(constructor_args.cid, uds_state.path.clone())?;
+                backend.set_conn_params(uds_state.conn_params.params());
+                backend.set_max_connections(uds_state.conn_params.max_connections as usize);
+                for port in uds_state.listen_ports.iter() {
+                    backend.listen_on_port(*port)?;
+                }
+                for listener in uds_state.tcp_listeners.iter() {
+                    backend.listen_on_tcp(listener.port, listener.socket_addr()?)?;
+                }
+                for endpoint in uds_state.tcp_endpoints.iter() {
+                    backend.connect_port_to_tcp(endpoint.port, endpoint.socket_addr()?);
+                }
+                backend.set_persist_connections(uds_state.persist_connections);
+                if let Some(port) = uds_state.mmds_port {
+                    backend.set_mmds(port, constructor_args.mmds.clone());
+                }
+
+                let mut reattach = constructor_args.reattach;
+                for conn in uds_state.connections.iter() {
+                    let target = reattach.iter().position(|target| {
+                        target.local_port == conn.local_port
+                            && target.peer_port.map_or(true, |port| port == conn.peer_port)
+                    });
+                    match target {
+                        Some(idx) if conn.resumable => {
+                            let target = reattach.remove(idx);
+                            if let Err(err) = backend.resume_connection(conn, &target.uds_path) {
+                                warn!(
+                                    "vsock: unable to reattach connection (lp={}, pp={}) to {}: \
+                                     {:?}",
+                                    conn.local_port, conn.peer_port, target.uds_path, err
+                                );
+                                backend.reset_connection(conn.local_port, conn.peer_port);
+                            }
+                        }
+                        _ => backend.reset_connection(conn.local_port, conn.peer_port),
+                    }
+                }
+                Ok(backend)
+            }
         }
     }
 }
@@ -110,7 +328,12 @@ where
                 defs::QUEUE_SIZE,
             )
             .map_err(VsockError::VirtioState)?;
-        let mut vsock = Self::with_queues(state.cid, constructor_args.backend, queues)?;
+        let mut vsock = Self::with_queues(
+            constructor_args.id,
+            state.cid,
+            constructor_args.backend,
+            queues,
+        )?;
 
         vsock.acked_features = state.virtio_state.acked_features;
         vsock.avail_features = state.virtio_state.avail_features;
@@ -120,6 +343,14 @@ where
         } else {
             DeviceState::Inactive
         };
+
+        // The backend may already have packets for the guest (e.g. resets for connections that
+        // couldn't be resumed), so make sure the RX queue gets processed.
+        if state.virtio_state.activated && vsock.backend.has_pending_rx() {
+            vsock.queue_events[RXQ_INDEX]
+                .write(1)
+                .map_err(VsockError::EventFd)?;
+        }
         Ok(vsock)
     }
 }
@@ -141,6 +372,37 @@ pub(crate) mod tests {
         fn save(&self) -> Self::State {
             VsockBackendState::Uds(VsockUdsState {
                 path: "test".to_owned(),
+                listen_ports: vec![52],
+                tcp_listeners: vec![VsockTcpPortState {
+                    port: 53,
+                    addr: "127.0.0.1:1053".to_owned(),
+                }],
+                tcp_endpoints: vec![VsockTcpPortState {
+                    port: 1054,
+                    addr: "127.0.0.1:54".to_owned(),
+                }],
+                persist_connections: true,
+                connections: vec![VsockConnectionState {
+                    local_port: 1026,
+                    peer_port: 1025,
+                    resumable: true,
+                    peer_closed_recv: false,
+                    peer_closed_send: false,
+                    fwd_cnt: 4,
+                    peer_buf_alloc: 64 * 1024,
+                    peer_fwd_cnt: 8,
+                    rx_cnt: 16,
+                    last_fwd_cnt_to_peer: 4,
+                    pending_rx: 0,
+                    tx_buf: vec![1, 2, 3, 4],
+                }],
+                conn_params: VsockConnParamsState {
+                    buf_alloc: 128 * 1024,
+                    max_connections: 16,
+                    request_timeout_ms: 1000,
+                    shutdown_timeout_ms: 4000,
+                },
+                mmds_port: Some(1055),
             })
         }
 
@@ -185,10 +447,17 @@ pub(crate) mod tests {
         let restored_state = VsockState::deserialize(&mut mem.as_slice(), &version_map, 1).unwrap();
         let mut restored_device = Vsock::restore(
             VsockConstructorArgs {
+                id: "vsock".to_string(),
                 mem: ctx.mem.clone(),
                 backend: match restored_state.backend {
                     VsockBackendState::Uds(uds_state) => {
                         assert_eq!(uds_state.path, "test".to_owned());
+                        // Version 1 doesn't hold the connection tuning, so it falls back
+                        // to the defaults.
+                        assert_eq!(
+                            uds_state.conn_params.max_connections,
+                            VSOCK_MAX_CONNECTIONS as u32
+                        );
                         TestBackend::new()
                     }
                 },
@@ -234,4 +503,48 @@ pub(crate) mod tests {
         restored_device.read_config(2, &mut data);
         assert_eq!(data, [0u8, 1, 2, 3, 4, 5, 6, 7]);
     }
+
+    #[test]
+    fn test_persist_uds_listen_ports() {
+        let ctx = TestContext::new();
+        let mut mem = vec![0; 4096];
+        let mut version_map = VersionMap::new();
+        version_map
+            .new_version()
+            .set_type_version(VsockUdsState::type_id(), 2);
+
+        let state = ctx.device.backend().save();
+        state
+            .serialize(&mut mem.as_mut_slice(), &version_map, 2)
+            .unwrap();
+        match VsockBackendState::deserialize(&mut mem.as_slice(), &version_map, 2).unwrap() {
+            VsockBackendState::Uds(uds_state) => {
+                assert_eq!(uds_state.listen_ports, vec![52]);
+                assert_eq!(uds_state.tcp_listeners[0].port, 53);
+                assert_eq!(
+                    uds_state.tcp_endpoints[0].socket_addr().unwrap(),
+                    "127.0.0.1:54".parse().unwrap()
+                );
+                assert!(uds_state.persist_connections);
+                assert_eq!(uds_state.connections[0].local_port, 1026);
+                assert_eq!(uds_state.connections[0].tx_buf, vec![1, 2, 3, 4]);
+                assert_eq!(uds_state.mmds_port, Some(1055));
+            }
+        }
+
+        // Older versions don't know about the listening ports.
+        state
+            .serialize(&mut mem.as_mut_slice(), &version_map, 1)
+            .unwrap();
+        match VsockBackendState::deserialize(&mut mem.as_slice(), &version_map, 1).unwrap() {
+            VsockBackendState::Uds(uds_state) => {
+                assert!(uds_state.listen_ports.is_empty());
+                assert!(uds_state.tcp_listeners.is_empty());
+                assert!(uds_state.tcp_endpoints.is_empty());
+                assert!(!uds_state.persist_connections);
+                assert!(uds_state.connections.is_empty());
+                assert_eq!(uds_state.mmds_port, None);
+            }
+        }
+    }
 }
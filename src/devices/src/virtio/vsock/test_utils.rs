@@ -120,7 +120,7 @@ impl TestContext {
             cid: CID,
             mem,
             mem_size: MEM_SIZE,
-            device: Vsock::new(CID, TestBackend::new()).unwrap(),
+            device: Vsock::new("vsock".to_string(), CID, TestBackend::new()).unwrap(),
         }
     }
 
@@ -157,7 +157,8 @@ impl TestContext {
             guest_rxvq,
             guest_txvq,
             guest_evvq,
-            device: Vsock::with_queues(self.cid, TestBackend::new(), queues).unwrap(),
+            device: Vsock::with_queues("vsock".to_string(), self.cid, TestBackend::new(), queues)
+                .unwrap(),
         }
     }
 }
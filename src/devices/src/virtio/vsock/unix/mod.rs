@@ -7,14 +7,19 @@
 /// `muxer::VsockMuxer`, a connection multiplexer that uses `super::csm::VsockConnection` for
 /// handling vsock connection states.
 /// Check out `muxer.rs` for a more detailed explanation of the inner workings of this backend.
+/// Guest ports can also be mapped to host TCP endpoints, in which case connections are carried
+/// over TCP streams instead (see `stream.rs`).
 mod muxer;
 mod muxer_killq;
 mod muxer_rxq;
+mod stream;
 
+pub use self::defs::MAX_CONNECTIONS;
 pub use muxer::VsockMuxer as VsockUnixBackend;
 
 mod defs {
-    /// Maximum number of established connections that we can handle.
+    /// Maximum number of established connections that we can handle. This is also the default
+    /// per-device limit.
     pub const MAX_CONNECTIONS: usize = 1023;
 
     /// Size of the muxer RX packet queue.
@@ -32,6 +37,16 @@ pub enum Error {
     EpollFdCreate(std::io::Error),
     /// The host made an invalid vsock port connection request.
     InvalidPortRequest,
+    /// Error creating the Unix socket pair carrying a connection to the MMDS.
+    MmdsStream(std::io::Error),
+    /// Error resuming a persisted connection.
+    ResumeConnection(super::csm::Error),
+    /// Error accepting a new connection from a host-side TCP listener.
+    TcpAccept(std::io::Error),
+    /// Error binding to a host-side TCP address.
+    TcpBind(std::io::Error),
+    /// Error connecting to a host-side TCP endpoint.
+    TcpConnect(std::io::Error),
     /// Error accepting a new connection from the host-side Unix socket.
     UnixAccept(std::io::Error),
     /// Error binding to the host-side Unix socket.
@@ -45,4 +60,4 @@ pub enum Error {
 }
 
 type Result<T> = std::result::Result<T, Error>;
-type MuxerConnection = super::csm::VsockConnection<std::os::unix::net::UnixStream>;
+type MuxerConnection = super::csm::VsockConnection<stream::HostStream>;
@@ -19,12 +19,15 @@
 /// 2. Event dispatcher
 ///    There are three event categories that the vsock backend is interested it:
 ///    1. A new host-initiated connection is ready to be accepted from the listening host Unix
-///       socket;
+///       socket, or from one of the per-port listening (Unix or TCP) sockets;
 ///    2. Data is available for reading from a newly-accepted host-initiated connection (i.e.
 ///       the host is ready to issue a vsock connection request, informing us of the
 ///       destination port to which it wants to connect);
 ///    3. Some event was triggered for a connected Unix socket, that belongs to a
-///       `VsockConnection`.
+///       `VsockConnection`;
+///    4. Some event was triggered for the MMDS end of a guest connection to the MMDS port
+///       (these connections are carried over Unix socket pairs, served by `MmdsStream`);
+///    5. A guest-initiated connection to a host TCP endpoint completed (successfully or not).
 ///    The muxer gets notified about all of these events, because, as a `VsockEpollListener`
 ///    implementor, it gets to register a nested epoll FD into the main VMM epolling loop. All
 ///    other pollable FDs are then registered under this nested epoll FD.
@@ -32,21 +35,28 @@
 ///    mapping `RawFd`s to `EpollListener`s.
 use std::collections::{HashMap, HashSet};
 use std::io::Read;
+use std::net::{SocketAddr, TcpListener, TcpStream};
 use std::os::unix::io::{AsRawFd, RawFd};
 use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::{Arc, Mutex};
 
 use logger::{debug, error, info, warn, IncMetric, METRICS};
+use mmds::data_store::Mmds;
+use mmds::stream::MmdsStream;
+use snapshot::Persist;
 use utils::epoll::{ControlOperation, Epoll, EpollEvent, EventSet};
 
-use super::super::csm::ConnState;
+use super::super::csm::{ConnParams, ConnState, VsockConnectionConstructorArgs};
 use super::super::defs::uapi;
 use super::super::packet::VsockPacket;
+use super::super::persist::VsockConnectionState;
 use super::super::{
     Result as VsockResult, VsockBackend, VsockChannel, VsockEpollListener, VsockError,
 };
 use super::defs;
 use super::muxer_killq::MuxerKillQ;
 use super::muxer_rxq::MuxerRxQ;
+use super::stream::{tcp_connect_nonblocking, HostStream};
 use super::MuxerConnection;
 use super::{Error, Result};
 
@@ -75,9 +85,25 @@ enum EpollListener {
     Connection { key: ConnMapKey, evset: EventSet },
     /// A listener interested in new host-initiated connections.
     HostSock,
+    /// A listener interested in new host-initiated connections to a fixed guest port. These
+    /// don't need the "connect <port>" command, since the port is implied by the socket.
+    PortSock(u32),
+    /// Same as `PortSock`, but listening on a host TCP address.
+    TcpSock(u32),
     /// A listener interested in reading host "connect <port>" commands from a freshly
     /// connected host socket.
     LocalStream(UnixStream),
+    /// A listener interested in the guest requests to the MMDS, and in writing back the
+    /// responses. The `MmdsStream` is stored in `VsockMuxer::mmds_streams`.
+    MmdsStream,
+    /// A listener interested in the completion of a guest-initiated connection to a host TCP
+    /// endpoint, identified by `key`. `peer_buf_alloc` is the buffer space advertised by the
+    /// guest in its connection request.
+    TcpConnect {
+        stream: TcpStream,
+        key: ConnMapKey,
+        peer_buf_alloc: u32,
+    },
 }
 
 /// The vsock connection multiplexer.
@@ -102,6 +128,20 @@ pub struct VsockMuxer {
     /// The file system path of the host-side Unix socket. This is used to figure out the path
     /// to Unix sockets listening on specific ports. I.e. "<this path>_<port number>".
     pub(crate) host_sock_path: String,
+    /// The Unix sockets through which host-initiated connections to specific guest ports are
+    /// accepted, keyed by guest port.
+    port_socks: HashMap<u32, UnixListener>,
+    /// The TCP listeners through which host-initiated connections to specific guest ports are
+    /// accepted, keyed by guest port.
+    tcp_socks: HashMap<u32, TcpListener>,
+    /// The host TCP endpoints to which guest-initiated connections are forwarded, instead of
+    /// "<host_sock_path>_<port>", keyed by (host-side) port.
+    tcp_endpoints: HashMap<u32, SocketAddr>,
+    /// The host-side port on which the MMDS is served to the guest, and the data store serving
+    /// it.
+    mmds: Option<(u32, Arc<Mutex<Mmds>>)>,
+    /// The MMDS ends of the guest connections to the MMDS port, keyed by FD.
+    mmds_streams: HashMap<RawFd, MmdsStream<UnixStream>>,
     /// The nested epoll event set, used to register epoll listeners.
     epoll: Epoll,
     /// A hash set used to keep track of used host-side (local) ports, in order to assign local
@@ -109,6 +149,12 @@ pub struct VsockMuxer {
     local_port_set: HashSet<u32>,
     /// The last used host-side port.
     local_port_last: u32,
+    /// Whether the live connections are saved in snapshots.
+    persist_connections: bool,
+    /// The parameters used for new connections.
+    conn_params: ConnParams,
+    /// Maximum number of connections that can be handled at once.
+    max_connections: usize,
 }
 
 impl VsockChannel for VsockMuxer {
@@ -221,6 +267,18 @@ impl VsockChannel for VsockMuxer {
         }
 
         if !self.conn_map.contains_key(&conn_key) {
+            // The connection may still be waiting for its host TCP endpoint. It isn't ready
+            // for any packets yet, so anything other than a repeated request aborts it.
+            if let Some(fd) = self.tcp_connect_fd(conn_key) {
+                if pkt.op() != uapi::VSOCK_OP_REQUEST {
+                    self.remove_listener(fd);
+                    if pkt.op() != uapi::VSOCK_OP_RST {
+                        self.enq_rst(pkt.dst_port(), pkt.src_port());
+                    }
+                }
+                return Ok(());
+            }
+
             // This packet can't be routed to any active connection (based on its src and dst
             // ports).  The only orphan / unroutable packets we know how to handle are
             // connection requests.
@@ -320,6 +378,11 @@ impl VsockMuxer {
             cid,
             host_sock,
             host_sock_path,
+            port_socks: HashMap::new(),
+            tcp_socks: HashMap::new(),
+            tcp_endpoints: HashMap::new(),
+            mmds: None,
+            mmds_streams: HashMap::new(),
             epoll: Epoll::new().map_err(Error::EpollFdCreate)?,
             rxq: MuxerRxQ::new(),
             conn_map: HashMap::with_capacity(defs::MAX_CONNECTIONS),
@@ -327,6 +390,9 @@ impl VsockMuxer {
             killq: MuxerKillQ::new(),
             local_port_last: (1u32 << 30) - 1,
             local_port_set: HashSet::with_capacity(defs::MAX_CONNECTIONS),
+            persist_connections: false,
+            conn_params: ConnParams::default(),
+            max_connections: defs::MAX_CONNECTIONS,
         };
 
         // Listen on the host initiated socket, for incomming connections.
@@ -334,6 +400,200 @@ impl VsockMuxer {
         Ok(muxer)
     }
 
+    /// Get the path of the dedicated Unix socket listening for host-initiated connections to
+    /// guest port `port`. It is kept apart from "<host_sock_path>_<port>", where guest-initiated
+    /// connections to host port `port` are forwarded.
+    pub fn listen_sock_path(host_sock_path: &str, port: u32) -> String {
+        format!("{}_listen_{}", host_sock_path, port)
+    }
+
+    /// Listen for host-initiated connections to guest port `port`, on a dedicated Unix socket
+    /// bound at "<host_sock_path>_listen_<port>". Connections accepted on this socket are
+    /// forwarded straight to the guest port, without a "connect <port>" command.
+    pub fn listen_on_port(&mut self, port: u32) -> Result<()> {
+        let port_sock = UnixListener::bind(Self::listen_sock_path(&self.host_sock_path, port))
+            .and_then(|sock| sock.set_nonblocking(true).map(|_| sock))
+            .map_err(Error::UnixBind)?;
+
+        self.add_listener(port_sock.as_raw_fd(), EpollListener::PortSock(port))?;
+        self.port_socks.insert(port, port_sock);
+        Ok(())
+    }
+
+    /// Get the guest ports that have a dedicated host-side listening socket, in ascending order.
+    pub fn listen_ports(&self) -> Vec<u32> {
+        let mut ports: Vec<u32> = self.port_socks.keys().copied().collect();
+        ports.sort_unstable();
+        ports
+    }
+
+    /// Listen for host-initiated connections to guest port `port`, on the host TCP address
+    /// `addr`. Like with `listen_on_port()`, connections are forwarded straight to the guest
+    /// port. However, no "OK <port>" acknowledgement is sent over TCP, since TCP clients are
+    /// expected to speak their own protocol only.
+    pub fn listen_on_tcp(&mut self, port: u32, addr: SocketAddr) -> Result<()> {
+        let tcp_sock = TcpListener::bind(addr)
+            .and_then(|sock| sock.set_nonblocking(true).map(|_| sock))
+            .map_err(Error::TcpBind)?;
+
+        self.add_listener(tcp_sock.as_raw_fd(), EpollListener::TcpSock(port))?;
+        self.tcp_socks.insert(port, tcp_sock);
+        Ok(())
+    }
+
+    /// Get the guest ports that have a host TCP listener, and the addresses they are bound to,
+    /// in ascending port order.
+    pub fn tcp_listeners(&self) -> Vec<(u32, SocketAddr)> {
+        let mut listeners: Vec<(u32, SocketAddr)> = self
+            .tcp_socks
+            .iter()
+            .filter_map(|(port, sock)| sock.local_addr().ok().map(|addr| (*port, addr)))
+            .collect();
+        listeners.sort_unstable_by_key(|(port, _)| *port);
+        listeners
+    }
+
+    /// Forward guest-initiated connections to host port `port` to the host TCP endpoint
+    /// `addr`, instead of the Unix socket at "<host_sock_path>_<port>".
+    pub fn connect_port_to_tcp(&mut self, port: u32, addr: SocketAddr) {
+        self.tcp_endpoints.insert(port, addr);
+    }
+
+    /// Get the host ports that are forwarded to TCP endpoints, and those endpoints, in
+    /// ascending port order.
+    pub fn tcp_endpoints(&self) -> Vec<(u32, SocketAddr)> {
+        let mut endpoints: Vec<(u32, SocketAddr)> = self
+            .tcp_endpoints
+            .iter()
+            .map(|(port, addr)| (*port, *addr))
+            .collect();
+        endpoints.sort_unstable_by_key(|(port, _)| *port);
+        endpoints
+    }
+
+    /// Serve the MMDS, from the `mmds` data store, to guest-initiated connections to host port
+    /// `port`. Requests are served straight from the muxer, without any host-side socket.
+    pub fn set_mmds(&mut self, port: u32, mmds: Arc<Mutex<Mmds>>) {
+        self.mmds = Some((port, mmds));
+    }
+
+    /// Get the host port on which the MMDS is served, if any.
+    pub fn mmds_port(&self) -> Option<u32> {
+        self.mmds.as_ref().map(|(port, _)| *port)
+    }
+
+    /// Give the MMDS requests deferred until a data store change (`wait-for-change`) another
+    /// chance to be answered. Should be called whenever the data store is updated.
+    pub fn process_mmds_update(&mut self) {
+        let fds: Vec<RawFd> = self.mmds_streams.keys().copied().collect();
+        for fd in fds {
+            let res = match self.mmds_streams.get_mut(&fd) {
+                Some(stream) => {
+                    stream.process_pending_requests();
+                    stream.try_write().is_ok()
+                }
+                None => continue,
+            };
+            self.update_mmds_stream(fd, res);
+        }
+    }
+
+    /// Close all the connections, so that host peers see the microVM going away. Meant to be
+    /// called right before the microVM stops. The Unix socket files are left in place, to be
+    /// removed by whoever created the jail, like the API socket.
+    pub fn close(&mut self) {
+        let keys: Vec<ConnMapKey> = self.conn_map.keys().copied().collect();
+        for key in keys {
+            self.remove_connection(key);
+        }
+        self.rxq = MuxerRxQ::new();
+        self.killq = MuxerKillQ::new();
+    }
+
+    /// Set the parameters used for new connections. The connection buffer size must be valid,
+    /// as per `ConnParams::is_valid_buf_alloc()`.
+    pub fn set_conn_params(&mut self, params: ConnParams) {
+        self.conn_params = params;
+    }
+
+    /// Get the parameters used for new connections.
+    pub fn conn_params(&self) -> ConnParams {
+        self.conn_params
+    }
+
+    /// Set the maximum number of connections that can be handled at once.
+    pub fn set_max_connections(&mut self, max_connections: usize) {
+        self.max_connections = max_connections;
+    }
+
+    /// Get the maximum number of connections that can be handled at once.
+    pub fn max_connections(&self) -> usize {
+        self.max_connections
+    }
+
+    /// Save live connections in snapshots, so that they can be resumed on restore.
+    pub fn set_persist_connections(&mut self, persist: bool) {
+        self.persist_connections = persist;
+    }
+
+    /// Check if live connections are saved in snapshots.
+    pub fn persist_connections(&self) -> bool {
+        self.persist_connections
+    }
+
+    /// Get the state of the live connections, ordered by (host port, guest port). This is
+    /// always empty, unless connection persistence has been enabled.
+    pub fn connection_states(&self) -> Vec<VsockConnectionState> {
+        if !self.persist_connections {
+            return Vec::new();
+        }
+        let mut keys: Vec<&ConnMapKey> = self.conn_map.keys().collect();
+        keys.sort_unstable_by_key(|key| (key.local_port, key.peer_port));
+        keys.into_iter()
+            .map(|key| self.conn_map[key].save())
+            .collect()
+    }
+
+    /// Resume a persisted connection, over a fresh connection to the host Unix socket at
+    /// `uds_path`.
+    pub fn resume_connection(
+        &mut self,
+        state: &VsockConnectionState,
+        uds_path: &str,
+    ) -> Result<()> {
+        let stream = UnixStream::connect(uds_path)
+            .and_then(|stream| stream.set_nonblocking(true).map(|_| stream))
+            .map_err(Error::UnixConnect)?;
+        let conn = MuxerConnection::restore(
+            VsockConnectionConstructorArgs {
+                stream: HostStream::Unix(stream),
+                local_cid: uapi::VSOCK_HOST_CID,
+                peer_cid: self.cid,
+                params: self.conn_params,
+            },
+            state,
+        )
+        .map_err(Error::ResumeConnection)?;
+
+        self.local_port_set.insert(state.local_port);
+        self.add_connection(
+            ConnMapKey {
+                local_port: state.local_port,
+                peer_port: state.peer_port,
+            },
+            conn,
+        )
+        .map_err(|err| {
+            self.free_local_port(state.local_port);
+            err
+        })
+    }
+
+    /// Let the guest know that a persisted connection is gone, by sending it an RST packet.
+    pub fn reset_connection(&mut self, local_port: u32, peer_port: u32) {
+        self.enq_rst(local_port, peer_port);
+    }
+
     /// Handle/dispatch an epoll event to its listener.
     fn handle_event(&mut self, fd: RawFd, evset: EventSet) {
         debug!(
@@ -358,7 +618,7 @@ impl VsockMuxer {
 
             // A new host-initiated connection is ready to be accepted.
             Some(EpollListener::HostSock) => {
-                if self.conn_map.len() == defs::MAX_CONNECTIONS {
+                if self.conn_map.len() >= self.max_connections {
                     // If we're already maxed-out on connections, we'll just accept and
                     // immediately discard this potentially new one.
                     warn!("vsock: connection limit reached; refusing new host connection");
@@ -386,6 +646,56 @@ impl VsockMuxer {
                     });
             }
 
+            // A new host-initiated connection to a fixed guest port is ready to be accepted.
+            Some(EpollListener::PortSock(peer_port)) => {
+                let peer_port = *peer_port;
+                let accepted = match self.port_socks.get(&peer_port) {
+                    Some(port_sock) => port_sock
+                        .accept()
+                        .and_then(|(stream, _)| {
+                            stream
+                                .set_nonblocking(true)
+                                .map(|_| HostStream::Unix(stream))
+                        })
+                        .map_err(Error::UnixAccept),
+                    None => return,
+                };
+                self.add_local_init_connection(accepted, peer_port);
+            }
+
+            // Same as above, but the connection is coming from a TCP listener.
+            Some(EpollListener::TcpSock(peer_port)) => {
+                let peer_port = *peer_port;
+                let accepted = match self.tcp_socks.get(&peer_port) {
+                    Some(tcp_sock) => tcp_sock
+                        .accept()
+                        .and_then(|(stream, _)| {
+                            stream
+                                .set_nonblocking(true)
+                                .map(|_| HostStream::Tcp(stream))
+                        })
+                        .map_err(Error::TcpAccept),
+                    None => return,
+                };
+                self.add_local_init_connection(accepted, peer_port);
+            }
+
+            // Some guest requests are ready to be read from the MMDS end of a connection, or the
+            // MMDS end became writable again.
+            Some(EpollListener::MmdsStream) => {
+                let res = match self.mmds_streams.get_mut(&fd) {
+                    Some(stream)
+                        if evset.contains(EventSet::OUT) && !evset.contains(EventSet::IN) =>
+                    {
+                        stream.try_write().is_ok()
+                    }
+                    // Hang-ups are also detected while reading.
+                    Some(stream) => stream.try_read().is_ok(),
+                    None => return,
+                };
+                self.update_mmds_stream(fd, res);
+            }
+
             // Data is ready to be read from a host-initiated connection. That would be the
             // "connect" command that we're expecting.
             Some(EpollListener::LocalStream(_)) => {
@@ -399,11 +709,12 @@ impl VsockMuxer {
                                     peer_port,
                                 },
                                 MuxerConnection::new_local_init(
-                                    stream,
+                                    HostStream::Unix(stream),
                                     uapi::VSOCK_HOST_CID,
                                     self.cid,
                                     local_port,
                                     peer_port,
+                                    self.conn_params,
                                 ),
                             )
                         })
@@ -413,6 +724,18 @@ impl VsockMuxer {
                 }
             }
 
+            // A connection attempt to a host TCP endpoint completed.
+            Some(EpollListener::TcpConnect { .. }) => {
+                if let Some(EpollListener::TcpConnect {
+                    stream,
+                    key,
+                    peer_buf_alloc,
+                }) = self.remove_listener(fd)
+                {
+                    self.complete_tcp_connect(stream, key, peer_buf_alloc);
+                }
+            }
+
             _ => {
                 info!("vsock: unexpected event: fd={:?}, evset={:?}", fd, evset);
                 METRICS.vsock.muxer_event_fails.inc();
@@ -420,6 +743,81 @@ impl VsockMuxer {
         }
     }
 
+    /// Add a guest-initiated connection to the active connection pool, once its host TCP
+    /// endpoint accepted it. If the endpoint could not be reached, an RST packet is scheduled
+    /// for delivery to the guest instead.
+    fn complete_tcp_connect(&mut self, stream: TcpStream, key: ConnMapKey, peer_buf_alloc: u32) {
+        stream
+            .take_error()
+            .and_then(|err| err.map_or(Ok(()), Err))
+            .map_err(Error::TcpConnect)
+            .and_then(|_| {
+                self.add_connection(
+                    key,
+                    MuxerConnection::new_peer_init(
+                        HostStream::Tcp(stream),
+                        uapi::VSOCK_HOST_CID,
+                        self.cid,
+                        key.local_port,
+                        key.peer_port,
+                        peer_buf_alloc,
+                        self.conn_params,
+                    ),
+                )
+            })
+            .unwrap_or_else(|err| {
+                info!("vsock: error connecting to TCP endpoint: {:?}", err);
+                self.enq_rst(key.local_port, key.peer_port);
+            });
+    }
+
+    /// Find the FD of the pending connection attempt to a host TCP endpoint, on behalf of the
+    /// guest connection identified by `key`.
+    fn tcp_connect_fd(&self, key: ConnMapKey) -> Option<RawFd> {
+        self.listener_map
+            .iter()
+            .find_map(|(fd, listener)| match listener {
+                EpollListener::TcpConnect { key: k, .. } if *k == key => Some(*fd),
+                _ => None,
+            })
+    }
+
+    /// Add a host-initiated connection, freshly accepted from one of the per-port listeners,
+    /// to the active connection pool.
+    fn add_local_init_connection(&mut self, accepted: Result<HostStream>, peer_port: u32) {
+        if self.conn_map.len() >= self.max_connections {
+            // If we're already maxed-out on connections, the new one is discarded as soon as
+            // it's been accepted.
+            warn!("vsock: connection limit reached; refusing new host connection");
+            return;
+        }
+        accepted
+            .and_then(|stream| {
+                let local_port = self.allocate_local_port();
+                self.add_connection(
+                    ConnMapKey {
+                        local_port,
+                        peer_port,
+                    },
+                    MuxerConnection::new_local_init(
+                        stream,
+                        uapi::VSOCK_HOST_CID,
+                        self.cid,
+                        local_port,
+                        peer_port,
+                        self.conn_params,
+                    ),
+                )
+                .map_err(|err| {
+                    self.free_local_port(local_port);
+                    err
+                })
+            })
+            .unwrap_or_else(|err| {
+                info!("vsock: error adding local-init connection: {:?}", err);
+            });
+    }
+
     /// Parse a host "connect" command, and extract the destination vsock port.
     fn read_local_stream_port(stream: &mut UnixStream) -> Result<u32> {
         let mut buf = [0u8; 32];
@@ -472,10 +870,10 @@ impl VsockMuxer {
         //   termination.
         self.sweep_killq();
 
-        if self.conn_map.len() >= defs::MAX_CONNECTIONS {
+        if self.conn_map.len() >= self.max_connections {
             info!(
                 "vsock: muxer connection limit reached ({})",
-                defs::MAX_CONNECTIONS
+                self.max_connections
             );
             return Err(Error::TooManyConnections);
         }
@@ -533,8 +931,12 @@ impl VsockMuxer {
     fn add_listener(&mut self, fd: RawFd, listener: EpollListener) -> Result<()> {
         let evset = match listener {
             EpollListener::Connection { evset, .. } => evset,
-            EpollListener::LocalStream(_) => EventSet::IN,
-            EpollListener::HostSock => EventSet::IN,
+            EpollListener::LocalStream(_) | EpollListener::MmdsStream => EventSet::IN,
+            EpollListener::HostSock | EpollListener::PortSock(_) | EpollListener::TcpSock(_) => {
+                EventSet::IN
+            }
+            // Errors and hang-ups are always reported, so a failed attempt also wakes us up.
+            EpollListener::TcpConnect { .. } => EventSet::OUT,
         };
 
         self.epoll
@@ -565,6 +967,50 @@ impl VsockMuxer {
         maybe_listener
     }
 
+    /// Create a Unix socket pair carrying a guest connection to the MMDS. The MMDS end is
+    /// registered under the muxer's nested epoll FD, while the other end is returned, to be used
+    /// as the host stream of the connection.
+    fn connect_to_mmds(&mut self, mmds: Arc<Mutex<Mmds>>) -> Result<HostStream> {
+        let (stream, mmds_end) = UnixStream::pair()
+            .and_then(|(stream, mmds_end)| {
+                stream.set_nonblocking(true)?;
+                mmds_end.set_nonblocking(true)?;
+                Ok((stream, mmds_end))
+            })
+            .map_err(Error::MmdsStream)?;
+
+        let fd = mmds_end.as_raw_fd();
+        self.add_listener(fd, EpollListener::MmdsStream)?;
+        self.mmds_streams
+            .insert(fd, MmdsStream::new(mmds_end, mmds));
+        Ok(HostStream::Unix(stream))
+    }
+
+    /// Update the epoll listener of the MMDS end of a connection, after it was read from or
+    /// written to. The MMDS end is dropped if that failed, which also closes the connection.
+    fn update_mmds_stream(&mut self, fd: RawFd, ok: bool) {
+        let evset = match self.mmds_streams.get(&fd) {
+            Some(stream) if ok && stream.pending_write() => EventSet::IN | EventSet::OUT,
+            Some(_) if ok => EventSet::IN,
+            _ => {
+                self.remove_listener(fd);
+                self.mmds_streams.remove(&fd);
+                return;
+            }
+        };
+
+        if let Err(err) = self.epoll.ctl(
+            ControlOperation::Modify,
+            fd,
+            EpollEvent::new(evset, fd as u64),
+        ) {
+            warn!("vsock: error updating MMDS epoll listener: {:?}", err);
+            METRICS.vsock.muxer_event_fails.inc();
+            self.remove_listener(fd);
+            self.mmds_streams.remove(&fd);
+        }
+    }
+
     /// Allocate a host-side port to be assigned to a new host-initiated connection.
     fn allocate_local_port(&mut self) -> u32 {
         // TODO: this doesn't seem very space-efficient.
@@ -588,15 +1034,58 @@ impl VsockMuxer {
     /// Handle a new connection request comming from our peer (the guest vsock driver).
     ///
     /// This will attempt to connect to a host-side Unix socket, expected to be listening at
-    /// the file system path corresponing to the destination port. If successful, a new
+    /// the file system path corresponing to the destination port, or to the host TCP endpoint
+    /// that the destination port is forwarded to. Connections to the MMDS port are served by
+    /// the muxer itself, over a Unix socket pair. If successful, a new
     /// connection object will be created and added to the connection pool. On failure, a new
     /// RST packet will be scheduled for delivery to the guest.
+    ///
+    /// Connecting to a TCP endpoint doesn't block: the connection is only added to the pool
+    /// once the attempt completes (see `complete_tcp_connect()`).
     fn handle_peer_request_pkt(&mut self, pkt: &VsockPacket) {
-        let port_path = format!("{}_{}", self.host_sock_path, pkt.dst_port());
+        let mmds = self
+            .mmds
+            .as_ref()
+            .filter(|(port, _)| *port == pkt.dst_port())
+            .map(|(_, mmds)| mmds.clone());
+
+        let stream = match (mmds, self.tcp_endpoints.get(&pkt.dst_port())) {
+            (Some(mmds), _) => self.connect_to_mmds(mmds),
+            (None, Some(addr)) => {
+                let key = ConnMapKey {
+                    local_port: pkt.dst_port(),
+                    peer_port: pkt.src_port(),
+                };
+                tcp_connect_nonblocking(addr)
+                    .map_err(Error::TcpConnect)
+                    .and_then(|stream| {
+                        self.add_listener(
+                            stream.as_raw_fd(),
+                            EpollListener::TcpConnect {
+                                stream,
+                                key,
+                                peer_buf_alloc: pkt.buf_alloc(),
+                            },
+                        )
+                    })
+                    .unwrap_or_else(|err| {
+                        info!("vsock: error connecting to TCP endpoint: {:?}", err);
+                        self.enq_rst(pkt.dst_port(), pkt.src_port());
+                    });
+                return;
+            }
+            (None, None) => {
+                UnixStream::connect(format!("{}_{}", self.host_sock_path, pkt.dst_port()))
+                    .and_then(|stream| {
+                        stream
+                            .set_nonblocking(true)
+                            .map(|_| HostStream::Unix(stream))
+                    })
+                    .map_err(Error::UnixConnect)
+            }
+        };
 
-        UnixStream::connect(port_path)
-            .and_then(|stream| stream.set_nonblocking(true).map(|_| stream))
-            .map_err(Error::UnixConnect)
+        stream
             .and_then(|stream| {
                 self.add_connection(
                     ConnMapKey {
@@ -610,6 +1099,7 @@ impl VsockMuxer {
                         pkt.dst_port(),
                         pkt.src_port(),
                         pkt.buf_alloc(),
+                        self.conn_params,
                     ),
                 )
             })
@@ -635,8 +1125,11 @@ impl VsockMuxer {
             mut_fn(conn);
 
             // If this is a host-initiated connection that has just become established, we'll have
-            // to send an ack message to the host end.
-            if prev_state == ConnState::LocalInit && conn.state() == ConnState::Established {
+            // to send an ack message to the host end. TCP clients don't expect one.
+            if prev_state == ConnState::LocalInit
+                && conn.state() == ConnState::Established
+                && conn.stream().is_unix()
+            {
                 let msg = format!("OK {}\n", key.local_port);
                 match conn.send_bytes_raw(msg.as_bytes()) {
                     Ok(written) if written == msg.len() => (),
@@ -865,6 +1358,13 @@ mod tests {
             self.muxer.notify(EventSet::IN);
         }
 
+        // Wait for an event to be pending under the muxer's nested epoll FD, then process it.
+        fn wait_and_notify_muxer(&mut self) {
+            let mut events = vec![EpollEvent::default(); 1];
+            assert_eq!(self.muxer.epoll.wait(1, 1000, &mut events).unwrap(), 1);
+            self.notify_muxer();
+        }
+
         fn count_epoll_listeners(&self) -> (usize, usize) {
             let mut local_lsn_count = 0usize;
             let mut conn_lsn_count = 0usize;
@@ -1082,6 +1582,37 @@ mod tests {
         assert!(!ctx.muxer.has_pending_rx());
     }
 
+    #[test]
+    fn test_conn_params() {
+        const LOCAL_PORT: u32 = 1026;
+
+        let mut ctx = MuxerTestContext::new("conn_params");
+        let params = ConnParams {
+            buf_alloc: 32 * 1024,
+            ..ConnParams::default()
+        };
+        ctx.muxer.set_conn_params(params);
+        ctx.muxer.set_max_connections(1);
+        assert_eq!(ctx.muxer.conn_params(), params);
+        assert_eq!(ctx.muxer.max_connections(), 1);
+
+        // The first connection is accepted, and advertises the configured buffer space.
+        let _listener = ctx.create_local_listener(LOCAL_PORT);
+        ctx.init_pkt(LOCAL_PORT, 1025, uapi::VSOCK_OP_REQUEST);
+        ctx.send();
+        ctx.recv();
+        assert_eq!(ctx.pkt.op(), uapi::VSOCK_OP_RESPONSE);
+        assert_eq!(ctx.pkt.buf_alloc(), params.buf_alloc);
+
+        // Going over the connection limit gets the new connection reset.
+        ctx.init_pkt(LOCAL_PORT, 1027, uapi::VSOCK_OP_REQUEST);
+        ctx.send();
+        ctx.recv();
+        assert_eq!(ctx.pkt.op(), uapi::VSOCK_OP_RST);
+        assert_eq!(ctx.pkt.dst_port(), 1027);
+        assert_eq!(ctx.muxer.conn_map.len(), 1);
+    }
+
     #[test]
     fn test_local_connection() {
         let mut ctx = MuxerTestContext::new("local_connection");
@@ -1110,6 +1641,299 @@ mod tests {
         assert_eq!(ctx.pkt.buf().unwrap()[..data.len()], data);
     }
 
+    #[test]
+    fn test_local_port_connection() {
+        let mut ctx = MuxerTestContext::new("local_port_connection");
+        let peer_port = 1025;
+        let port_path = VsockMuxer::listen_sock_path(&ctx.muxer.host_sock_path, peer_port);
+        ctx.muxer.listen_on_port(peer_port).unwrap();
+        assert_eq!(ctx.muxer.listen_ports(), vec![peer_port]);
+        // The same port can't have two listening sockets.
+        assert!(ctx.muxer.listen_on_port(peer_port).is_err());
+
+        // No "connect" command is needed, the connection is added as soon as it's accepted.
+        let mut stream = UnixStream::connect(&port_path).unwrap();
+        stream.set_nonblocking(true).unwrap();
+        ctx.notify_muxer();
+        let (local_lsn_count, conn_lsn_count) = ctx.count_epoll_listeners();
+        assert_eq!(local_lsn_count, 0);
+        assert_eq!(conn_lsn_count, 1);
+
+        let local_port = ctx.muxer.local_port_last;
+        assert!(ctx.muxer.conn_map.contains_key(&ConnMapKey {
+            local_port,
+            peer_port,
+        }));
+        assert!(ctx.muxer.has_pending_rx());
+        ctx.recv();
+        assert_eq!(ctx.pkt.op(), uapi::VSOCK_OP_REQUEST);
+        assert_eq!(ctx.pkt.src_port(), local_port);
+        assert_eq!(ctx.pkt.dst_port(), peer_port);
+
+        // The host end gets the same ack as with "connect", once the guest accepts.
+        ctx.init_pkt(local_port, peer_port, uapi::VSOCK_OP_RESPONSE);
+        ctx.send();
+        let mut buf = vec![0u8; 32];
+        let len = stream.read(&mut buf[..]).unwrap();
+        assert_eq!(&buf[..len], format!("OK {}\n", local_port).as_bytes());
+
+        // Test host -> guest data flow.
+        let data = [5, 6, 7, 8];
+        stream.write_all(&data).unwrap();
+        ctx.notify_muxer();
+        ctx.recv();
+        assert_eq!(ctx.pkt.op(), uapi::VSOCK_OP_RW);
+        assert_eq!(ctx.pkt.buf().unwrap()[..data.len()], data);
+
+        // The guest can still connect to the same port on the host, which is served through a
+        // separate socket.
+        let mut listener = ctx.create_local_listener(peer_port);
+        ctx.init_pkt(peer_port, 1026, uapi::VSOCK_OP_REQUEST);
+        ctx.send();
+        let mut guest_stream = listener.accept();
+        ctx.recv();
+        assert_eq!(ctx.pkt.op(), uapi::VSOCK_OP_RESPONSE);
+        assert_eq!(ctx.pkt.src_port(), peer_port);
+        assert_eq!(ctx.pkt.dst_port(), 1026);
+
+        // Test guest -> host data flow, on both connections.
+        let data = [1, 2, 3, 4];
+        ctx.init_data_pkt(peer_port, 1026, &data);
+        ctx.send();
+        let mut buf = vec![0u8; data.len()];
+        guest_stream.read_exact(buf.as_mut_slice()).unwrap();
+        assert_eq!(buf.as_slice(), &data);
+
+        let data = [9, 10, 11, 12];
+        ctx.init_data_pkt(local_port, peer_port, &data);
+        ctx.send();
+        let mut buf = vec![0u8; data.len()];
+        stream.read_exact(buf.as_mut_slice()).unwrap();
+        assert_eq!(buf.as_slice(), &data);
+
+        std::fs::remove_file(port_path).unwrap();
+    }
+
+    #[test]
+    fn test_close() {
+        let mut ctx = MuxerTestContext::new("close");
+        let peer_port = 1025;
+        let port_path = VsockMuxer::listen_sock_path(&ctx.muxer.host_sock_path, peer_port);
+        ctx.muxer.listen_on_port(peer_port).unwrap();
+        let (mut stream, _) = ctx.local_connect(1026);
+        assert_eq!(ctx.muxer.conn_map.len(), 1);
+
+        ctx.muxer.close();
+        assert!(ctx.muxer.conn_map.is_empty());
+        assert!(!ctx.muxer.has_pending_rx());
+        assert_eq!(ctx.count_epoll_listeners(), (0, 0));
+        // The host end of the connection was closed.
+        let mut buf = [0u8; 8];
+        assert_eq!(stream.read(&mut buf).unwrap(), 0);
+        // The socket files are left in place.
+        assert!(std::path::Path::new(&port_path).exists());
+        assert!(std::path::Path::new(&ctx.muxer.host_sock_path).exists());
+
+        std::fs::remove_file(port_path).unwrap();
+    }
+
+    #[test]
+    fn test_tcp_connections() {
+        let mut ctx = MuxerTestContext::new("tcp_connections");
+
+        // Test host -> guest, through a TCP listener.
+        let peer_port = 52;
+        ctx.muxer
+            .listen_on_tcp(peer_port, "127.0.0.1:0".parse().unwrap())
+            .unwrap();
+        let listeners = ctx.muxer.tcp_listeners();
+        assert_eq!(listeners.len(), 1);
+        assert_eq!(listeners[0].0, peer_port);
+
+        let mut stream = std::net::TcpStream::connect(listeners[0].1).unwrap();
+        stream.set_nonblocking(true).unwrap();
+        ctx.notify_muxer();
+        let local_port = ctx.muxer.local_port_last;
+        ctx.recv();
+        assert_eq!(ctx.pkt.op(), uapi::VSOCK_OP_REQUEST);
+        assert_eq!(ctx.pkt.src_port(), local_port);
+        assert_eq!(ctx.pkt.dst_port(), peer_port);
+        ctx.init_pkt(local_port, peer_port, uapi::VSOCK_OP_RESPONSE);
+        ctx.send();
+
+        // No "OK <port>" ack is written to TCP clients.
+        let mut buf = vec![0u8; 32];
+        assert_eq!(
+            stream.read(&mut buf[..]).unwrap_err().kind(),
+            std::io::ErrorKind::WouldBlock
+        );
+
+        let data = [1, 2, 3, 4];
+        stream.write_all(&data).unwrap();
+        ctx.notify_muxer();
+        ctx.recv();
+        assert_eq!(ctx.pkt.op(), uapi::VSOCK_OP_RW);
+        assert_eq!(ctx.pkt.buf().unwrap()[..data.len()], data);
+
+        // Test guest -> host, through a TCP endpoint.
+        let local_port = 1026;
+        let peer_port = 1025;
+        let endpoint = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint_addr = endpoint.local_addr().unwrap();
+        ctx.muxer.connect_port_to_tcp(local_port, endpoint_addr);
+        assert_eq!(ctx.muxer.tcp_endpoints(), vec![(local_port, endpoint_addr)]);
+
+        ctx.init_pkt(local_port, peer_port, uapi::VSOCK_OP_REQUEST);
+        ctx.send();
+        // The guest only gets a response once the endpoint accepted the connection.
+        assert!(!ctx.muxer.has_pending_rx());
+        ctx.wait_and_notify_muxer();
+        ctx.recv();
+        assert_eq!(ctx.pkt.op(), uapi::VSOCK_OP_RESPONSE);
+        assert_eq!(ctx.pkt.src_port(), local_port);
+        assert_eq!(ctx.pkt.dst_port(), peer_port);
+        let (mut stream, _) = endpoint.accept().unwrap();
+
+        let data = [5, 6, 7, 8];
+        ctx.init_data_pkt(local_port, peer_port, &data);
+        ctx.send();
+        let mut buf = vec![0u8; data.len()];
+        stream.read_exact(buf.as_mut_slice()).unwrap();
+        assert_eq!(buf.as_slice(), &data);
+
+        // A guest RST aborts a pending connection attempt.
+        ctx.init_pkt(local_port, peer_port + 1, uapi::VSOCK_OP_REQUEST);
+        ctx.send();
+        let key = ConnMapKey {
+            local_port,
+            peer_port: peer_port + 1,
+        };
+        assert!(ctx.muxer.tcp_connect_fd(key).is_some());
+        ctx.init_pkt(local_port, peer_port + 1, uapi::VSOCK_OP_RST);
+        ctx.send();
+        assert!(ctx.muxer.tcp_connect_fd(key).is_none());
+        assert!(!ctx.muxer.has_pending_rx());
+        assert!(!ctx.muxer.conn_map.contains_key(&key));
+
+        // An unreachable endpoint gets the guest an RST.
+        drop(endpoint);
+        ctx.init_pkt(local_port, peer_port + 2, uapi::VSOCK_OP_REQUEST);
+        ctx.send();
+        // The attempt may fail right away, or once the endpoint refused it.
+        if !ctx.muxer.has_pending_rx() {
+            ctx.wait_and_notify_muxer();
+        }
+        ctx.recv();
+        assert_eq!(ctx.pkt.op(), uapi::VSOCK_OP_RST);
+        assert_eq!(ctx.pkt.dst_port(), peer_port + 2);
+        assert!(ctx
+            .muxer
+            .listener_map
+            .values()
+            .all(|listener| match listener {
+                EpollListener::TcpConnect { .. } => false,
+                _ => true,
+            }));
+    }
+
+    #[test]
+    fn test_mmds_connection() {
+        let mut ctx = MuxerTestContext::new("mmds_connection");
+        let local_port = 1050;
+        let peer_port = 1025;
+        assert_eq!(ctx.muxer.mmds_port(), None);
+        ctx.muxer
+            .set_mmds(local_port, Arc::new(Mutex::new(Mmds::default())));
+        assert_eq!(ctx.muxer.mmds_port(), Some(local_port));
+
+        // Guest connections to the MMDS port are accepted without any host-side socket.
+        ctx.init_pkt(local_port, peer_port, uapi::VSOCK_OP_REQUEST);
+        ctx.send();
+        ctx.recv();
+        assert_eq!(ctx.pkt.op(), uapi::VSOCK_OP_RESPONSE);
+        assert_eq!(ctx.pkt.src_port(), local_port);
+        assert_eq!(ctx.pkt.dst_port(), peer_port);
+        assert_eq!(ctx.muxer.mmds_streams.len(), 1);
+
+        let request = b"GET / HTTP/1.1\r\n\r\n";
+        ctx.init_data_pkt(local_port, peer_port, request);
+        ctx.send();
+        // The MMDS end of the connection reads the request, and writes back the response.
+        ctx.notify_muxer();
+        // The connection then forwards the response to the guest.
+        ctx.notify_muxer();
+        ctx.recv();
+        assert_eq!(ctx.pkt.op(), uapi::VSOCK_OP_RW);
+        let response = &ctx.pkt.buf().unwrap()[..ctx.pkt.len() as usize];
+        assert!(response.starts_with(b"HTTP/1.1 "));
+
+        // Nothing is pending, so data store updates are no-ops.
+        ctx.muxer.process_mmds_update();
+        assert_eq!(ctx.muxer.mmds_streams.len(), 1);
+
+        // The MMDS end goes away along with the connection.
+        ctx.init_pkt(local_port, peer_port, uapi::VSOCK_OP_RST);
+        ctx.send();
+        ctx.notify_muxer();
+        assert!(ctx.muxer.mmds_streams.is_empty());
+        assert!(!ctx.muxer.has_pending_rx());
+    }
+
+    #[test]
+    fn test_persist_connections() {
+        const LOCAL_PORT: u32 = 1026;
+        const PEER_PORT: u32 = 1025;
+
+        let mut ctx = MuxerTestContext::new("persist_connections");
+        let mut listener = ctx.create_local_listener(LOCAL_PORT);
+        ctx.init_pkt(LOCAL_PORT, PEER_PORT, uapi::VSOCK_OP_REQUEST);
+        ctx.send();
+        let _stream = listener.accept();
+        ctx.recv();
+        assert_eq!(ctx.pkt.op(), uapi::VSOCK_OP_RESPONSE);
+
+        // Connections aren't saved, unless asked to.
+        assert!(!ctx.muxer.persist_connections());
+        assert!(ctx.muxer.connection_states().is_empty());
+        ctx.muxer.set_persist_connections(true);
+        let states = ctx.muxer.connection_states();
+        assert_eq!(states.len(), 1);
+        assert_eq!(states[0].local_port, LOCAL_PORT);
+        assert_eq!(states[0].peer_port, PEER_PORT);
+        assert!(states[0].resumable);
+
+        // Resume the connection in another muxer, over a different host socket.
+        let mut restored = MuxerTestContext::new("persist_connections_restored");
+        let mut reattach_listener = restored.create_local_listener(LOCAL_PORT);
+        assert!(restored
+            .muxer
+            .resume_connection(&states[0], "/invalid/path")
+            .is_err());
+        restored
+            .muxer
+            .resume_connection(&states[0], &reattach_listener.path.to_string_lossy())
+            .unwrap();
+        let mut stream = reattach_listener.accept();
+        assert!(restored.muxer.conn_map.contains_key(&ConnMapKey {
+            local_port: LOCAL_PORT,
+            peer_port: PEER_PORT,
+        }));
+
+        let data = [1, 2, 3, 4];
+        restored.init_data_pkt(LOCAL_PORT, PEER_PORT, &data);
+        restored.send();
+        let mut buf = vec![0u8; data.len()];
+        stream.read_exact(buf.as_mut_slice()).unwrap();
+        assert_eq!(buf.as_slice(), &data);
+
+        // Connections that aren't resumed get reset.
+        restored.muxer.reset_connection(LOCAL_PORT + 1, PEER_PORT);
+        restored.recv();
+        assert_eq!(restored.pkt.op(), uapi::VSOCK_OP_RST);
+        assert_eq!(restored.pkt.src_port(), LOCAL_PORT + 1);
+        assert_eq!(restored.pkt.dst_port(), PEER_PORT);
+    }
+
     #[test]
     fn test_local_close() {
         let peer_port = 1025;
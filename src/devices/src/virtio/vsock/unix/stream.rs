@@ -0,0 +1,181 @@
+// Copyright 2021 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+
+/// `HostStream` is the host-side end of a muxer connection. Most connections are carried over
+/// Unix domain sockets, but guest ports can also be mapped to TCP endpoints on the host, in
+/// which case the connection is carried over a TCP stream.
+use std::io::{Error, Read, Result, Write};
+use std::mem;
+use std::net::{SocketAddr, TcpStream};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+
+/// Start connecting a non-blocking TCP stream to `addr`, without waiting for the connection to
+/// be established. The stream becomes writable once the attempt completes, and its outcome can
+/// then be read with `TcpStream::take_error()`.
+pub fn tcp_connect_nonblocking(addr: &SocketAddr) -> Result<TcpStream> {
+    // Safe because `sockaddr_storage` is plain data, large enough for any socket address.
+    let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let (domain, len) = match addr {
+        SocketAddr::V4(addr) => {
+            // Safe because `sockaddr_storage` is suitably sized and aligned for `sockaddr_in`.
+            let sin = unsafe { &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in) };
+            sin.sin_family = libc::AF_INET as libc::sa_family_t;
+            sin.sin_port = addr.port().to_be();
+            sin.sin_addr.s_addr = u32::from_ne_bytes(addr.ip().octets());
+            (libc::AF_INET, mem::size_of::<libc::sockaddr_in>())
+        }
+        SocketAddr::V6(addr) => {
+            // Safe because `sockaddr_storage` is suitably sized and aligned for `sockaddr_in6`.
+            let sin6 = unsafe { &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in6) };
+            sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            sin6.sin6_port = addr.port().to_be();
+            sin6.sin6_flowinfo = addr.flowinfo();
+            sin6.sin6_addr.s6_addr = addr.ip().octets();
+            sin6.sin6_scope_id = addr.scope_id();
+            (libc::AF_INET6, mem::size_of::<libc::sockaddr_in6>())
+        }
+    };
+
+    // Safe because the arguments are constants, and the result is checked.
+    let fd = unsafe {
+        libc::socket(
+            domain,
+            libc::SOCK_STREAM | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+            0,
+        )
+    };
+    if fd < 0 {
+        return Err(Error::last_os_error());
+    }
+    // We just checked that the fd is valid, and nothing else owns it.
+    let stream = unsafe { TcpStream::from_raw_fd(fd) };
+
+    // Safe because `storage` holds a socket address of `len` bytes, and the result is checked.
+    let ret = unsafe {
+        libc::connect(
+            fd,
+            &storage as *const _ as *const libc::sockaddr,
+            len as libc::socklen_t,
+        )
+    };
+    if ret < 0 {
+        let err = Error::last_os_error();
+        if err.raw_os_error() != Some(libc::EINPROGRESS) {
+            return Err(err);
+        }
+    }
+    Ok(stream)
+}
+
+/// A connected host-side stream.
+pub enum HostStream {
+    /// A connected Unix domain socket.
+    Unix(UnixStream),
+    /// A connected TCP socket.
+    Tcp(TcpStream),
+}
+
+impl HostStream {
+    /// Check if this is a Unix domain socket. Only these understand the text protocol used for
+    /// host-initiated connections (i.e. "CONNECT <port>" / "OK <port>").
+    pub fn is_unix(&self) -> bool {
+        match self {
+            HostStream::Unix(_) => true,
+            HostStream::Tcp(_) => false,
+        }
+    }
+
+    /// Move the stream into or out of non-blocking mode.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> Result<()> {
+        match self {
+            HostStream::Unix(stream) => stream.set_nonblocking(nonblocking),
+            HostStream::Tcp(stream) => stream.set_nonblocking(nonblocking),
+        }
+    }
+}
+
+impl Read for HostStream {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        match self {
+            HostStream::Unix(stream) => stream.read(buf),
+            HostStream::Tcp(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for HostStream {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        match self {
+            HostStream::Unix(stream) => stream.write(buf),
+            HostStream::Tcp(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match self {
+            HostStream::Unix(stream) => stream.flush(),
+            HostStream::Tcp(stream) => stream.flush(),
+        }
+    }
+}
+
+impl AsRawFd for HostStream {
+    fn as_raw_fd(&self) -> RawFd {
+        match self {
+            HostStream::Unix(stream) => stream.as_raw_fd(),
+            HostStream::Tcp(stream) => stream.as_raw_fd(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_host_stream() {
+        let (unix_stream, mut unix_peer) = UnixStream::pair().unwrap();
+        let mut stream = HostStream::Unix(unix_stream);
+        assert!(stream.is_unix());
+        stream.set_nonblocking(true).unwrap();
+        stream.write_all(b"unix").unwrap();
+        let mut buf = [0u8; 4];
+        unix_peer.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"unix");
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let tcp_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (mut tcp_peer, _) = listener.accept().unwrap();
+        let mut stream = HostStream::Tcp(tcp_stream);
+        assert!(!stream.is_unix());
+        tcp_peer.write_all(b"tcp").unwrap();
+        let mut buf = [0u8; 3];
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"tcp");
+        assert!(stream.flush().is_ok());
+    }
+
+    #[test]
+    fn test_tcp_connect_nonblocking() {
+        for bind_addr in ["127.0.0.1:0", "[::1]:0"].iter() {
+            let listener = match TcpListener::bind(bind_addr) {
+                Ok(listener) => listener,
+                // IPv6 may be disabled on the host.
+                Err(_) => continue,
+            };
+            let addr = listener.local_addr().unwrap();
+            let mut stream = tcp_connect_nonblocking(&addr).unwrap();
+            let (mut peer, _) = listener.accept().unwrap();
+            assert!(stream.take_error().unwrap().is_none());
+            assert_eq!(stream.peer_addr().unwrap(), addr);
+            peer.write_all(b"tcp").unwrap();
+            let mut buf = [0u8; 3];
+            stream.set_nonblocking(false).unwrap();
+            stream.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, b"tcp");
+        }
+    }
+}
@@ -0,0 +1,328 @@
+// Copyright 2021 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Contains logic that helps with handling the ICMPv6 Neighbor Discovery messages which resolve
+//! IPv6 addresses to link-layer addresses, the IPv6 counterpart of ARP.
+//!
+//! A more detailed view of the Neighbor Solicitation and Neighbor Advertisement messages can be
+//! found [here].
+//!
+//! [here]: https://tools.ietf.org/html/rfc4861#section-4.3
+use std::convert::From;
+use std::net::{IpAddr, Ipv6Addr};
+use std::result::Result;
+
+use super::bytes::{InnerBytes, NetworkBytes, NetworkBytesMut};
+use super::ChecksumProto;
+
+use utils::net::mac::{MacAddr, MAC_ADDR_LEN};
+
+/// ICMPv6 type of Neighbor Solicitation messages.
+pub const TYPE_NEIGHBOR_SOLICITATION: u8 = 135;
+
+/// ICMPv6 type of Neighbor Advertisement messages.
+pub const TYPE_NEIGHBOR_ADVERTISEMENT: u8 = 136;
+
+/// Neighbor Advertisement flag set by routers.
+pub const FLAG_ROUTER: u32 = 0x8000_0000;
+
+/// Neighbor Advertisement flag set when answering a Neighbor Solicitation.
+pub const FLAG_SOLICITED: u32 = 0x4000_0000;
+
+/// Neighbor Advertisement flag asking the receiver to update its cached link-layer address.
+pub const FLAG_OVERRIDE: u32 = 0x2000_0000;
+
+/// The length of a Neighbor Solicitation message, without options.
+pub const NEIGHBOR_SOLICITATION_LEN: usize = 24;
+
+/// The length of a Neighbor Advertisement message carrying the target link-layer address option.
+pub const NEIGHBOR_ADVERTISEMENT_LEN: usize = 32;
+
+const TYPE_OFFSET: usize = 0;
+const CODE_OFFSET: usize = 1;
+const CHECKSUM_OFFSET: usize = 2;
+const FLAGS_OFFSET: usize = 4;
+const TARGET_ADDRESS_OFFSET: usize = 8;
+const OPTIONS_OFFSET: usize = 24;
+
+const IPV6_ADDR_LEN: usize = 16;
+
+// Neighbor Discovery option carrying the link-layer address of the target. Its length is
+// expressed in units of 8 octets.
+const OPTION_TARGET_LINK_LAYER_ADDRESS: u8 = 2;
+const LINK_LAYER_ADDRESS_OPTION_LEN: u8 = 1;
+
+/// Represents errors which may occur while parsing or writing a message.
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// The checksum is invalid.
+    Checksum,
+    /// Invalid code.
+    Code,
+    /// The provided slice is shorter than the message.
+    SliceTooShort,
+    /// Invalid message type.
+    Type,
+}
+
+/// The inner bytes will be interpreted as an ICMPv6 Neighbor Solicitation or Neighbor
+/// Advertisement message.
+pub struct NdpNeighborMessage<'a, T: 'a> {
+    bytes: InnerBytes<'a, T>,
+}
+
+#[allow(clippy::len_without_is_empty)]
+impl<'a, T: NetworkBytes> NdpNeighborMessage<'a, T> {
+    /// Interprets the given bytes as a neighbor message, without doing any validity checks
+    /// beforehand.
+    ///
+    ///  # Panics
+    ///
+    /// This method does not panic, but further method calls on the resulting object may panic if
+    /// `bytes` contains invalid input.
+    #[inline]
+    pub fn from_bytes_unchecked(bytes: T) -> Self {
+        NdpNeighborMessage {
+            bytes: InnerBytes::new(bytes),
+        }
+    }
+
+    /// Tries to interpret a byte slice as a valid Neighbor Solicitation message.
+    ///
+    /// The `verify_checksum` parameter must contain the source and destination addresses from the
+    /// enclosing IPv6 packet if the ICMPv6 checksum must be validated. Options are not examined.
+    pub fn solicitation_from_bytes(
+        bytes: T,
+        verify_checksum: Option<(Ipv6Addr, Ipv6Addr)>,
+    ) -> Result<Self, Error> {
+        if bytes.len() < NEIGHBOR_SOLICITATION_LEN {
+            return Err(Error::SliceTooShort);
+        }
+
+        let maybe = NdpNeighborMessage::from_bytes_unchecked(bytes);
+
+        if maybe.message_type() != TYPE_NEIGHBOR_SOLICITATION {
+            return Err(Error::Type);
+        }
+
+        if maybe.code() != 0 {
+            return Err(Error::Code);
+        }
+
+        if let Some((src_addr, dst_addr)) = verify_checksum {
+            if maybe.compute_checksum(src_addr, dst_addr) != 0 {
+                return Err(Error::Checksum);
+            }
+        }
+
+        Ok(maybe)
+    }
+
+    /// Returns the ICMPv6 type of the message.
+    #[inline]
+    pub fn message_type(&self) -> u8 {
+        self.bytes[TYPE_OFFSET]
+    }
+
+    /// Returns the ICMPv6 code of the message.
+    #[inline]
+    pub fn code(&self) -> u8 {
+        self.bytes[CODE_OFFSET]
+    }
+
+    /// Returns the checksum of the message.
+    #[inline]
+    pub fn checksum(&self) -> u16 {
+        self.bytes.ntohs_unchecked(CHECKSUM_OFFSET)
+    }
+
+    /// Returns the flags of the message (always 0 for Neighbor Solicitations).
+    #[inline]
+    pub fn flags(&self) -> u32 {
+        self.bytes.ntohl_unchecked(FLAGS_OFFSET)
+    }
+
+    /// Returns the target address, whose link-layer address is being resolved.
+    #[inline]
+    pub fn target_address(&self) -> Ipv6Addr {
+        let mut octets = [0u8; IPV6_ADDR_LEN];
+        octets.copy_from_slice(&self.bytes[TARGET_ADDRESS_OFFSET..OPTIONS_OFFSET]);
+        Ipv6Addr::from(octets)
+    }
+
+    /// Computes the ICMPv6 checksum of the message.
+    #[inline]
+    pub fn compute_checksum(&self, src_addr: Ipv6Addr, dst_addr: Ipv6Addr) -> u16 {
+        crate::pdu::compute_checksum(
+            &self.bytes,
+            IpAddr::V6(src_addr),
+            IpAddr::V6(dst_addr),
+            ChecksumProto::Icmpv6,
+        )
+    }
+
+    /// Returns the length of the message.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+}
+
+impl<'a, T: NetworkBytesMut> NdpNeighborMessage<'a, T> {
+    /// Attempts to write a Neighbor Advertisement to `buf`, carrying the link-layer address of
+    /// `target_addr` in the target link-layer address option. The checksum is computed using the
+    /// addresses of the enclosing IPv6 packet.
+    pub fn write_advertisement(
+        buf: T,
+        flags: u32,
+        target_addr: Ipv6Addr,
+        target_mac: MacAddr,
+        src_addr: Ipv6Addr,
+        dst_addr: Ipv6Addr,
+    ) -> Result<Self, Error> {
+        if buf.len() < NEIGHBOR_ADVERTISEMENT_LEN {
+            return Err(Error::SliceTooShort);
+        }
+
+        // This is ok, because we've checked the length of the slice.
+        let mut message = NdpNeighborMessage::from_bytes_unchecked(buf);
+        message.bytes.shrink_unchecked(NEIGHBOR_ADVERTISEMENT_LEN);
+
+        message.bytes[TYPE_OFFSET] = TYPE_NEIGHBOR_ADVERTISEMENT;
+        message.bytes[CODE_OFFSET] = 0;
+        message.bytes.htonl_unchecked(FLAGS_OFFSET, flags);
+        message.bytes[TARGET_ADDRESS_OFFSET..OPTIONS_OFFSET].copy_from_slice(&target_addr.octets());
+        message.bytes[OPTIONS_OFFSET] = OPTION_TARGET_LINK_LAYER_ADDRESS;
+        message.bytes[OPTIONS_OFFSET + 1] = LINK_LAYER_ADDRESS_OPTION_LEN;
+        message.bytes[OPTIONS_OFFSET + 2..OPTIONS_OFFSET + 2 + MAC_ADDR_LEN]
+            .copy_from_slice(target_mac.get_bytes());
+
+        // Set this to 0 first.
+        message.bytes.htons_unchecked(CHECKSUM_OFFSET, 0);
+        let checksum = message.compute_checksum(src_addr, dst_addr);
+        message.bytes.htons_unchecked(CHECKSUM_OFFSET, checksum);
+
+        Ok(message)
+    }
+}
+
+/// Returns the solicited-node multicast address which Neighbor Solicitations for `addr` are sent
+/// to.
+#[inline]
+pub fn solicited_node_multicast_addr(addr: Ipv6Addr) -> Ipv6Addr {
+    let segments = addr.segments();
+    Ipv6Addr::new(
+        0xff02,
+        0,
+        0,
+        0,
+        0,
+        1,
+        0xff00 | (segments[6] & 0x00ff),
+        segments[7],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt;
+
+    use super::*;
+
+    impl<'a, T: NetworkBytes> fmt::Debug for NdpNeighborMessage<'a, T> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "(NDP neighbor message)")
+        }
+    }
+
+    #[test]
+    fn test_solicited_node_multicast_addr() {
+        let addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0x1234, 0x5678, 0x9abc, 0xdef0);
+        assert_eq!(
+            solicited_node_multicast_addr(addr),
+            Ipv6Addr::new(0xff02, 0, 0, 0, 0, 1, 0xffbc, 0xdef0)
+        );
+    }
+
+    #[test]
+    fn test_neighbor_messages() {
+        let mut buf = [0u8; 100];
+        let mac = MacAddr::parse_str("01:23:45:67:89:ab").unwrap();
+        let target = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0xa9fe, 0xa9fe);
+        let remote = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+
+        let len = {
+            let message = NdpNeighborMessage::write_advertisement(
+                buf.as_mut(),
+                FLAG_SOLICITED | FLAG_OVERRIDE,
+                target,
+                mac,
+                target,
+                remote,
+            )
+            .unwrap();
+
+            assert_eq!(message.len(), NEIGHBOR_ADVERTISEMENT_LEN);
+            assert_eq!(message.message_type(), TYPE_NEIGHBOR_ADVERTISEMENT);
+            assert_eq!(message.code(), 0);
+            assert_eq!(message.flags(), FLAG_SOLICITED | FLAG_OVERRIDE);
+            assert_eq!(message.target_address(), target);
+            assert_eq!(message.compute_checksum(target, remote), 0);
+            message.len()
+        };
+        assert_eq!(
+            &buf[OPTIONS_OFFSET..len],
+            &[2, 1, 0x01, 0x23, 0x45, 0x67, 0x89, 0xab]
+        );
+
+        // An advertisement is not a solicitation.
+        assert_eq!(
+            NdpNeighborMessage::solicitation_from_bytes(&buf[..len], None).unwrap_err(),
+            Error::Type
+        );
+
+        // Turn it into a solicitation, and check the checksum is verified.
+        buf[TYPE_OFFSET] = TYPE_NEIGHBOR_SOLICITATION;
+        let dst = solicited_node_multicast_addr(target);
+        assert_eq!(
+            NdpNeighborMessage::solicitation_from_bytes(&buf[..len], Some((remote, dst)))
+                .unwrap_err(),
+            Error::Checksum
+        );
+        {
+            let mut message = NdpNeighborMessage::from_bytes_unchecked(&mut buf[..len]);
+            message.bytes.htons_unchecked(CHECKSUM_OFFSET, 0);
+            let checksum = message.compute_checksum(remote, dst);
+            message.bytes.htons_unchecked(CHECKSUM_OFFSET, checksum);
+        }
+        let message =
+            NdpNeighborMessage::solicitation_from_bytes(&buf[..len], Some((remote, dst))).unwrap();
+        assert_eq!(message.target_address(), target);
+
+        buf[CODE_OFFSET] = 1;
+        assert_eq!(
+            NdpNeighborMessage::solicitation_from_bytes(&buf[..len], None).unwrap_err(),
+            Error::Code
+        );
+        assert_eq!(
+            NdpNeighborMessage::solicitation_from_bytes(
+                &buf[..NEIGHBOR_SOLICITATION_LEN - 1],
+                None
+            )
+            .unwrap_err(),
+            Error::SliceTooShort
+        );
+        assert_eq!(
+            NdpNeighborMessage::write_advertisement(
+                &mut buf[..NEIGHBOR_ADVERTISEMENT_LEN - 1],
+                0,
+                target,
+                mac,
+                target,
+                remote
+            )
+            .unwrap_err(),
+            Error::SliceTooShort
+        );
+    }
+}
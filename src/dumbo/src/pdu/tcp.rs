@@ -8,7 +8,7 @@
 //! [Here]: https://en.wikipedia.org/wiki/Transmission_Control_Protocol#TCP_segment_structure
 
 use std::cmp::min;
-use std::net::Ipv4Addr;
+use std::net::IpAddr;
 use std::num::NonZeroU16;
 use std::result::Result;
 
@@ -87,9 +87,6 @@ pub enum Error {
     SliceTooShort,
 }
 
-// TODO: The implementation of TcpSegment is IPv4 specific in regard to checksum computation. Maybe
-// make it more generic at some point.
-
 /// Interprets the inner bytes as a TCP segment.
 pub struct TcpSegment<'a, T: 'a> {
     bytes: InnerBytes<'a, T>,
@@ -207,7 +204,7 @@ impl<'a, T: NetworkBytes> TcpSegment<'a, T> {
     /// be found [here].
     ///
     /// [here]: https://en.wikipedia.org/wiki/Transmission_Control_Protocol#Checksum_computation
-    pub fn compute_checksum(&self, src_addr: Ipv4Addr, dst_addr: Ipv4Addr) -> u16 {
+    pub fn compute_checksum(&self, src_addr: IpAddr, dst_addr: IpAddr) -> u16 {
         crate::pdu::compute_checksum(&self.bytes, src_addr, dst_addr, ChecksumProto::Tcp)
     }
 
@@ -276,12 +273,9 @@ impl<'a, T: NetworkBytes> TcpSegment<'a, T> {
     /// Attempts to interpret `bytes` as a TCP segment, checking the validity of the header fields.
     ///
     /// The `verify_checksum` parameter must contain the source and destination addresses from the
-    /// enclosing IPv4 packet if the TCP checksum must be validated.
+    /// enclosing IP packet if the TCP checksum must be validated.
     #[inline]
-    pub fn from_bytes(
-        bytes: T,
-        verify_checksum: Option<(Ipv4Addr, Ipv4Addr)>,
-    ) -> Result<Self, Error> {
+    pub fn from_bytes(bytes: T, verify_checksum: Option<(IpAddr, IpAddr)>) -> Result<Self, Error> {
         if bytes.len() < OPTIONS_OFFSET {
             return Err(Error::SliceTooShort);
         }
@@ -411,7 +405,7 @@ impl<'a, T: NetworkBytesMut> TcpSegment<'a, T> {
     ///    or changing something.
     /// * `payload` - May contain a buffer which holds payload data and the maximum amount of bytes
     ///    we should read from that buffer. When `None`, the TCP segment will carry no payload.
-    /// * `compute_checksum` - May contain the pair addresses from the enclosing IPv4 packet, which
+    /// * `compute_checksum` - May contain the pair addresses from the enclosing IP packet, which
     ///    are required for TCP checksum computation. Skip the checksum altogether when `None`.
     #[allow(clippy::too_many_arguments)]
     #[inline]
@@ -426,7 +420,7 @@ impl<'a, T: NetworkBytesMut> TcpSegment<'a, T> {
         mss_option: Option<u16>,
         mss_remaining: u16,
         payload: Option<(&R, usize)>,
-        compute_checksum: Option<(Ipv4Addr, Ipv4Addr)>,
+        compute_checksum: Option<(IpAddr, IpAddr)>,
     ) -> Result<Self, Error> {
         Ok(Self::write_incomplete_segment(
             buf,
@@ -553,7 +547,7 @@ impl<'a, T: NetworkBytesMut> Incomplete<TcpSegment<'a, T>> {
         mut self,
         src_port: u16,
         dst_port: u16,
-        compute_checksum: Option<(Ipv4Addr, Ipv4Addr)>,
+        compute_checksum: Option<(IpAddr, IpAddr)>,
     ) -> TcpSegment<'a, T> {
         self.inner.set_source_port(src_port);
         self.inner.set_destination_port(dst_port);
@@ -570,6 +564,7 @@ impl<'a, T: NetworkBytesMut> Incomplete<TcpSegment<'a, T>> {
 #[cfg(test)]
 mod tests {
     use std::fmt;
+    use std::net::Ipv4Addr;
 
     use super::*;
 
@@ -637,8 +632,8 @@ mod tests {
         let b = [2u8; 1000];
         let c = [3u8; 2000];
 
-        let src_addr = Ipv4Addr::new(10, 1, 2, 3);
-        let dst_addr = Ipv4Addr::new(192, 168, 44, 77);
+        let src_addr = IpAddr::from(Ipv4Addr::new(10, 1, 2, 3));
+        let dst_addr = IpAddr::from(Ipv4Addr::new(192, 168, 44, 77));
         let src_port = 1234;
         let dst_port = 5678;
         let seq_number = 11_111_222;
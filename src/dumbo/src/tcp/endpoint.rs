@@ -61,11 +61,16 @@ pub struct Endpoint {
     // We ignore incoming segments when this is set, and that happens when we decide to reset
     // the connection (or it decides to reset itself).
     stop_receiving: bool,
+    // Set when the callback deferred the response to the request at the beginning of
+    // receive_buf, which has to be processed again later.
+    request_pending: bool,
 }
 
 // The "contract" for the Endpoint (if it implemented a trait or something) is something along
 // these lines:
-// - Incoming segments are passed by calling receive_segment().
+// - Incoming segments are passed by calling receive_segment(). The callback building the
+// response may defer it, in which case process_pending_request() has to be called once the
+// response can be built.
 // - To check whether the Endpoint has something to transmit, we must call write_next_segment()
 // (the buf parameter should point to where the TCP segment begins). This function will return
 // None if there's nothing to write (or there was an error writing, in which case it also
@@ -108,6 +113,7 @@ impl Endpoint {
             last_segment_received_timestamp: timestamp_cycles(),
             eviction_threshold: eviction_threshold.get(),
             stop_receiving: false,
+            request_pending: false,
         })
     }
 
@@ -123,10 +129,10 @@ impl Endpoint {
         )
     }
 
-    pub fn receive_segment<T: NetworkBytes>(
+    pub fn receive_segment<T: NetworkBytes, F: FnMut(Request) -> Option<Response>>(
         &mut self,
         s: &TcpSegment<T>,
-        callback: fn(Request) -> Response,
+        mut callback: F,
     ) {
         if self.stop_receiving {
             return;
@@ -183,47 +189,7 @@ impl Endpoint {
         if self.response_buf.is_empty() {
             // There's no pending response currently, so we're back to waiting for a request to be
             // available in self.receive_buf.
-
-            // The following is some ugly but workable code that attempts to find the end of an
-            // HTTP 1.x request in receive_buf. We need to do this for now because parse_request_bytes()
-            // expects the entire request contents as parameter.
-            if self.receive_buf_left > 2 {
-                let b = self.receive_buf.as_mut();
-                for i in 0..self.receive_buf_left - 1 {
-                    // We're basically looking for a double new line, which can only appear at the
-                    // end of a valid request.
-                    if b[i] == b'\n' {
-                        let end = if b[i + 1] == b'\n' {
-                            i + 2
-                        } else if i + 3 <= self.receive_buf_left && &b[i + 1..i + 3] == b"\r\n" {
-                            i + 3
-                        } else {
-                            continue;
-                        };
-
-                        // We found a potential request, let's parse it.
-                        let response = parse_request_bytes(&b[..end], callback);
-
-                        // The unwrap is safe because a Vec will allocate more space until all the
-                        // writes succeed.
-                        response.write_all(&mut self.response_buf).unwrap();
-
-                        // Sanity check because the current logic operates under this assumption.
-                        assert!(self.response_buf.len() < u32::max_value() as usize);
-
-                        // We have to remove the bytes up to end from receive_buf, by shifting the
-                        // others to the beginning of the buffer, and updating receive_buf_left.
-                        // Also, advance the rwnd edge of the inner connection.
-                        // TODO: Maximum efficiency.
-                        for j in 0..b.len() - end {
-                            b[j] = b[j + end];
-                        }
-                        self.receive_buf_left -= end;
-                        self.connection.advance_local_rwnd_edge(end as u32);
-                        break;
-                    }
-                }
-            }
+            self.process_request(&mut callback);
 
             if self.receive_buf_left == self.receive_buf.len() {
                 // If we get here the buffer is full, but we still couldn't identify the end of a
@@ -235,12 +201,81 @@ impl Endpoint {
         }
 
         // We close the connection after receiving a FIN, and making sure there are no more
-        // responses to send.
-        if self.connection.fin_received() && self.response_buf.is_empty() {
+        // responses to send, or to build.
+        if self.connection.fin_received() && self.response_buf.is_empty() && !self.request_pending {
             self.connection.close();
         }
     }
 
+    // Looks for a complete request in `receive_buf` and, if one is found, writes the response
+    // built by `callback` to `response_buf`.
+    fn process_request<F: FnMut(Request) -> Option<Response>>(&mut self, callback: &mut F) {
+        // The following is some ugly but workable code that attempts to find the end of an
+        // HTTP 1.x request in receive_buf. We need to do this for now because parse_request_bytes()
+        // expects the entire request contents as parameter.
+        if self.receive_buf_left > 2 {
+            let b = self.receive_buf.as_mut();
+            for i in 0..self.receive_buf_left - 1 {
+                // We're basically looking for a double new line, which can only appear at the
+                // end of a valid request.
+                if b[i] == b'\n' {
+                    let end = if b[i + 1] == b'\n' {
+                        i + 2
+                    } else if i + 3 <= self.receive_buf_left && &b[i + 1..i + 3] == b"\r\n" {
+                        i + 3
+                    } else {
+                        continue;
+                    };
+
+                    // We found a potential request, let's parse it. If the callback is not able
+                    // to answer it yet, we leave it in the buffer for a later attempt.
+                    let response = match parse_request_bytes(&b[..end], &mut *callback) {
+                        Some(response) => response,
+                        None => {
+                            self.request_pending = true;
+                            break;
+                        }
+                    };
+                    self.request_pending = false;
+
+                    // The unwrap is safe because a Vec will allocate more space until all the
+                    // writes succeed.
+                    response.write_all(&mut self.response_buf).unwrap();
+
+                    // Sanity check because the current logic operates under this assumption.
+                    assert!(self.response_buf.len() < u32::max_value() as usize);
+
+                    // We have to remove the bytes up to end from receive_buf, by shifting the
+                    // others to the beginning of the buffer, and updating receive_buf_left.
+                    // Also, advance the rwnd edge of the inner connection.
+                    // TODO: Maximum efficiency.
+                    for j in 0..b.len() - end {
+                        b[j] = b[j + end];
+                    }
+                    self.receive_buf_left -= end;
+                    self.connection.advance_local_rwnd_edge(end as u32);
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Attempts to answer the request whose response was deferred by the callback, if any.
+    pub fn process_pending_request<F: FnMut(Request) -> Option<Response>>(
+        &mut self,
+        mut callback: F,
+    ) {
+        if self.request_pending && self.response_buf.is_empty() && !self.stop_receiving {
+            self.process_request(&mut callback);
+        }
+    }
+
+    /// Returns `true` if the callback deferred the response to the current request.
+    #[inline]
+    pub fn has_pending_request(&self) -> bool {
+        self.request_pending
+    }
+
     pub fn write_next_segment<'a>(
         &mut self,
         buf: &'a mut [u8],
@@ -311,11 +346,15 @@ fn build_response(http_version: Version, status_code: StatusCode, body: Body) ->
 }
 
 /// Parses the request bytes and builds a `micro_http::Response` by the given callback function.
-fn parse_request_bytes(byte_stream: &[u8], callback: fn(Request) -> Response) -> Response {
+/// Returns `None` when the callback defers the response.
+fn parse_request_bytes<F: FnOnce(Request) -> Option<Response>>(
+    byte_stream: &[u8],
+    callback: F,
+) -> Option<Response> {
     let request = Request::try_from(byte_stream);
     match request {
         Ok(request) => callback(request),
-        Err(e) => match e {
+        Err(e) => Some(match e {
             RequestError::BodyWithoutPendingRequest => build_response(
                 Version::default(),
                 StatusCode::BadRequest,
@@ -361,7 +400,7 @@ fn parse_request_bytes(byte_stream: &[u8], callback: fn(Request) -> Response) ->
                 StatusCode::BadRequest,
                 Body::new(e.to_string()),
             ),
-        },
+        }),
     }
 }
 
@@ -590,20 +629,78 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_deferred_response() {
+        let mut buf = [0u8; 500];
+        let mut write_buf = [0u8; RCV_BUF_MAX_SIZE + 100];
+        let t = ConnectionTester::new();
+
+        let syn = t.write_syn(buf.as_mut());
+        let remote_isn = syn.sequence_number();
+        let mut e = Endpoint::new_with_defaults(&syn).unwrap();
+        let endpoint_isn = e
+            .write_next_segment(write_buf.as_mut(), t.mss_reserved)
+            .unwrap()
+            .inner()
+            .sequence_number();
+
+        let mut ctrl = t.write_ctrl(buf.as_mut());
+        ctrl.set_flags_after_ns(TcpFlags::ACK);
+        ctrl.set_ack_number(endpoint_isn.wrapping_add(1));
+        e.receive_segment(&ctrl, mock_callback);
+        assert!(e.connection.is_established());
+
+        // The callback is not able to answer the request yet.
+        let request = b"GET http://169.254.169.255/asdfghjkl HTTP/1.1\r\n\r\n";
+        {
+            let mut data = t.write_data(write_buf.as_mut(), request.as_ref());
+            data.set_flags_after_ns(TcpFlags::ACK);
+            data.set_sequence_number(remote_isn.wrapping_add(1));
+            data.set_ack_number(endpoint_isn.wrapping_add(1));
+            e.receive_segment(&data, |_| None);
+        }
+        assert!(e.has_pending_request());
+        assert_eq!(e.receive_buf_left, request.len());
+
+        // Only the request bytes get ACKed.
+        {
+            let s = e
+                .write_next_segment(write_buf.as_mut(), t.mss_reserved)
+                .unwrap();
+            assert_eq!(s.inner().payload_len(), 0);
+        }
+        assert_eq!(e.next_segment_status(), NextSegmentStatus::Nothing);
+
+        // Still not ready.
+        e.process_pending_request(|_| None);
+        assert!(e.has_pending_request());
+        assert_eq!(e.next_segment_status(), NextSegmentStatus::Nothing);
+
+        // The response is sent once the callback is able to build it.
+        e.process_pending_request(mock_callback);
+        assert!(!e.has_pending_request());
+        assert_eq!(e.receive_buf_left, 0);
+        assert_eq!(e.next_segment_status(), NextSegmentStatus::Available);
+        let s = e
+            .write_next_segment(write_buf.as_mut(), t.mss_reserved)
+            .unwrap();
+        assert!(from_utf8(s.inner().payload()).unwrap().contains("200"));
+    }
+
     #[test]
     fn test_parse_request_bytes_error() {
         // Test unsupported HTTP version.
         let request_bytes = b"GET http://169.254.169.255/ HTTP/2.0\r\n\r\n";
         let mut expected_response = Response::new(Version::Http11, StatusCode::NotImplemented);
         expected_response.set_body(Body::new("Unsupported HTTP version.".to_string()));
-        let actual_response = parse_request_bytes(request_bytes, mock_callback);
+        let actual_response = parse_request_bytes(request_bytes, mock_callback).unwrap();
         assert_eq!(actual_response, expected_response);
 
         // Test invalid URI (empty URI).
         let request_bytes = b"GET   HTTP/1.0\r\n\r\n";
         let mut expected_response = Response::new(Version::Http11, StatusCode::BadRequest);
         expected_response.set_body(Body::new("Empty URI not allowed.".to_string()));
-        let actual_response = parse_request_bytes(request_bytes, mock_callback);
+        let actual_response = parse_request_bytes(request_bytes, mock_callback).unwrap();
         assert_eq!(actual_response, expected_response);
 
         // Test invalid HTTP methods.
@@ -612,7 +709,8 @@ mod tests {
             let request_bytes = format!("{} http://169.254.169.255/ HTTP/1.0\r\n\r\n", method);
             let mut expected_response = Response::new(Version::Http11, StatusCode::NotImplemented);
             expected_response.set_body(Body::new("Unsupported HTTP method.".to_string()));
-            let actual_response = parse_request_bytes(request_bytes.as_bytes(), mock_callback);
+            let actual_response =
+                parse_request_bytes(request_bytes.as_bytes(), mock_callback).unwrap();
             assert_eq!(actual_response, expected_response);
         }
 
@@ -621,7 +719,8 @@ mod tests {
         for method in valid_methods.iter() {
             let request_bytes = format!("{} http://169.254.169.255/ HTTP/1.0\r\n\r\n", method);
             let expected_response = Response::new(Version::Http11, StatusCode::OK);
-            let actual_response = parse_request_bytes(request_bytes.as_bytes(), mock_callback);
+            let actual_response =
+                parse_request_bytes(request_bytes.as_bytes(), mock_callback).unwrap();
             assert_eq!(actual_response, expected_response);
         }
 
@@ -629,7 +728,7 @@ mod tests {
         let request_bytes = b"GET / HTTP/1.1\r\n";
         let mut expected_response = Response::new(Version::Http11, StatusCode::BadRequest);
         expected_response.set_body(Body::new("Invalid request.".to_string()));
-        let actual_response = parse_request_bytes(request_bytes, mock_callback);
+        let actual_response = parse_request_bytes(request_bytes, mock_callback).unwrap();
         assert_eq!(actual_response, expected_response);
 
         // Test invalid HTTP headers.
@@ -638,6 +737,7 @@ mod tests {
                                  Transfer-Encoding: identity; q=0\r\n\
                                  Content-Length: 26\r\n\r\nthis is not\n\r\na json \nbody";
         assert!(parse_request_bytes(request_bytes, mock_callback)
+            .unwrap()
             .body()
             .is_none());
 
@@ -649,7 +749,7 @@ mod tests {
         expected_response.set_body(Body::new(
             "Invalid value. Key:Content-Length; Value: alpha".to_string(),
         ));
-        let actual_response = parse_request_bytes(request_bytes, mock_callback);
+        let actual_response = parse_request_bytes(request_bytes, mock_callback).unwrap();
         assert_eq!(actual_response, expected_response);
 
         let request_bytes = b"PATCH http://localhost/home HTTP/1.1\r\n\
@@ -661,7 +761,7 @@ mod tests {
         expected_response.set_body(Body::new(
             "Invalid value. Key:Accept-Encoding; Value: *;q=0".to_string(),
         ));
-        let actual_response = parse_request_bytes(request_bytes, mock_callback);
+        let actual_response = parse_request_bytes(request_bytes, mock_callback).unwrap();
         assert_eq!(actual_response, expected_response);
     }
 }
@@ -1,23 +1,24 @@
 // Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-//! Exposes simple TCP over IPv4 listener functionality via the [`TcpIPv4Handler`] structure.
+//! Exposes simple TCP over IPv4 and IPv6 listener functionality via the [`TcpIPHandler`]
+//! structure.
 //!
-//! [`TcpIPv4Handler`]: struct.TcpIPv4Handler.html
+//! [`TcpIPHandler`]: struct.TcpIPHandler.html
 
 use std::collections::{HashMap, HashSet};
-use std::net::Ipv4Addr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::num::NonZeroUsize;
 
 use crate::pdu::bytes::NetworkBytes;
 use crate::pdu::ipv4::{Error as IPv4PacketError, IPv4Packet, PROTOCOL_TCP};
+use crate::pdu::ipv6::{Error as IPv6PacketError, IPv6Packet};
 use crate::pdu::tcp::{Error as TcpSegmentError, Flags as TcpFlags, TcpSegment};
+use crate::pdu::Incomplete;
 use crate::tcp::endpoint::Endpoint;
 use crate::tcp::{NextSegmentStatus, RstConfig};
 use micro_http::{Request, Response};
 
-// TODO: This is currently IPv4 specific. Maybe change it to a more generic implementation.
-
 /// Describes events which may occur when the handler receives packets.
 #[cfg_attr(test, derive(Debug, PartialEq))]
 pub enum RecvEvent {
@@ -51,12 +52,14 @@ pub enum WriteEvent {
 }
 
 /// Describes errors which may be encountered by the [`receive_packet`] method from
-/// [`TcpIPv4Handler`].
+/// [`TcpIPHandler`].
 ///
-/// [`receive_packet`]: struct.TcpIPv4Handler.html#method.receive_packet
-/// [`TcpIPv4Handler`]: struct.TcpIPv4Handler.html
+/// [`receive_packet`]: struct.TcpIPHandler.html#method.receive_packet
+/// [`TcpIPHandler`]: struct.TcpIPHandler.html
 #[cfg_attr(test, derive(Debug, PartialEq))]
 pub enum RecvError {
+    /// The handler has no local address of the packet family.
+    InvalidAddressFamily,
     /// The inner segment has an invalid destination port.
     InvalidPort,
     /// The handler encountered an error while parsing the inner TCP segment.
@@ -64,30 +67,32 @@ pub enum RecvError {
 }
 
 /// Describes errors which may be encountered by the [`write_next_packet`] method from
-/// [`TcpIPv4Handler`].
+/// [`TcpIPHandler`].
 ///
-/// [`write_next_packet`]: struct.TcpIPv4Handler.html#method.write_next_packet
-/// [`TcpIPv4Handler`]: struct.TcpIPv4Handler.html
+/// [`write_next_packet`]: struct.TcpIPHandler.html#method.write_next_packet
+/// [`TcpIPHandler`]: struct.TcpIPHandler.html
 #[cfg_attr(test, derive(Debug, PartialEq))]
 pub enum WriteNextError {
     /// There was an error while writing the contents of the IPv4 packet.
     IPv4Packet(IPv4PacketError),
+    /// There was an error while writing the contents of the IPv6 packet.
+    IPv6Packet(IPv6PacketError),
     /// There was an error while writing the contents of the inner TCP segment.
     TcpSegment(TcpSegmentError),
 }
 
-// Generally speaking, a TCP/IPv4 connection is identified using the four-tuple (src_addr, src_port,
-// dst_addr, dst_port). However, the IPv4 address and TCP port of the MMDS endpoint are fixed, so
+// Generally speaking, a TCP/IP connection is identified using the four-tuple (src_addr, src_port,
+// dst_addr, dst_port). However, the IP addresses and TCP port of the MMDS endpoint are fixed, so
 // we can get away with uniquely identifying connections using just the remote address and port.
 #[derive(Clone, Copy, Eq, Hash, PartialEq)]
 #[cfg_attr(test, derive(Debug))]
 struct ConnectionTuple {
-    remote_addr: Ipv4Addr,
+    remote_addr: IpAddr,
     remote_port: u16,
 }
 
 impl ConnectionTuple {
-    fn new(remote_addr: Ipv4Addr, remote_port: u16) -> Self {
+    fn new(remote_addr: IpAddr, remote_port: u16) -> Self {
         ConnectionTuple {
             remote_addr,
             remote_port,
@@ -95,20 +100,20 @@ impl ConnectionTuple {
     }
 }
 
-/// Implements a minimalist TCP over IPv4 listener.
+/// Implements a minimalist TCP over IPv4 and IPv6 listener.
 ///
 /// Forwards incoming TCP segments to the appropriate connection object, based on the associated
 /// tuple, or attempts to establish new connections (when receiving `SYN` segments). Aside from
 /// constructors, the handler operation is based on three methods:
 ///
-/// * [`receive_packet`] examines an incoming IPv4 packet. It checks whether the destination
-///   address is correct, the attempts examine the inner TCP segment, making sure the destination
+/// * [`receive_packet`] examines an incoming IPv4 packet ([`receive_ipv6_packet`] does the same
+///   for IPv6 packets). It examines the inner TCP segment, making sure the destination
 ///   port number is also correct. Then, it steers valid segments towards exiting connections,
 ///   creates new connections for incoming `SYN` segments, and enqueues `RST` replies in response
 ///   to any segments which cannot be associated with a connection (except other `RST` segments).
 ///   On success, also describes any internal status changes triggered by the reception of the
 ///   packet.
-/// * [`write_next_packet`] writes the next IPv4 packet (if available) that would be sent by the
+/// * [`write_next_packet`] writes the next IP packet (if available) that would be sent by the
 ///   handler itself (right now it can only mean an enqueued `RST`), or one of the existing
 ///   connections. On success, also describes any internal status changes triggered as the packet
 ///   gets transmitted.
@@ -117,12 +122,15 @@ impl ConnectionTuple {
 ///   to send for the moment. This is used to determine whether it's appropriate to call
 ///   [`write_next_packet`].
 ///
-/// [`receive_packet`]: ../handler/struct.TcpIPv4Handler.html#method.receive_packet
-/// [`write_next_packet`]: ../handler/struct.TcpIPv4Handler.html#method.write_next_packet
-/// [`next_segment_status`]: ../handler/struct.TcpIPv4Handler.html#method.next_segment_status
-pub struct TcpIPv4Handler {
-    // Handler IPv4 address used for every connection.
+/// [`receive_packet`]: ../handler/struct.TcpIPHandler.html#method.receive_packet
+/// [`receive_ipv6_packet`]: ../handler/struct.TcpIPHandler.html#method.receive_ipv6_packet
+/// [`write_next_packet`]: ../handler/struct.TcpIPHandler.html#method.write_next_packet
+/// [`next_segment_status`]: ../handler/struct.TcpIPHandler.html#method.next_segment_status
+pub struct TcpIPHandler {
+    // Handler IPv4 address used for every IPv4 connection.
     local_ipv4_addr: Ipv4Addr,
+    // Handler IPv6 address used for every IPv6 connection, if the handler accepts any.
+    local_ipv6_addr: Option<Ipv6Addr>,
     // Handler TCP port used for every connection.
     local_port: u16,
     // This map holds the currently active endpoints, identified by their connection tuple.
@@ -149,8 +157,8 @@ enum RecvSegmentOutcome {
     UnexpectedSegment(bool),
 }
 
-impl TcpIPv4Handler {
-    /// Creates a new `TcpIPv4Handler`.
+impl TcpIPHandler {
+    /// Creates a new `TcpIPHandler`.
     ///
     /// The handler acts as if bound to `local_addr`:`local_port`, and will accept at most
     /// `max_connections` concurrent connections. `RST` segments generated by unexpected incoming
@@ -164,8 +172,9 @@ impl TcpIPv4Handler {
     ) -> Self {
         let max_connections = max_connections.get();
         let max_pending_resets = max_pending_resets.get();
-        TcpIPv4Handler {
+        TcpIPHandler {
             local_ipv4_addr,
+            local_ipv6_addr: None,
             local_port,
             connections: HashMap::with_capacity(max_connections),
             max_connections,
@@ -186,6 +195,17 @@ impl TcpIPv4Handler {
         self.local_ipv4_addr
     }
 
+    /// Setter for the local IPv6 address of this TCP handler. IPv6 packets are only accepted
+    /// when one is set.
+    pub fn set_local_ipv6_addr(&mut self, ipv6_addr: Option<Ipv6Addr>) {
+        self.local_ipv6_addr = ipv6_addr;
+    }
+
+    /// Returns the local IPv6 address of this TCP handler, if any.
+    pub fn local_ipv6_addr(&self) -> Option<Ipv6Addr> {
+        self.local_ipv6_addr
+    }
+
     /// Returns the local port of this TCP handler.
     pub fn local_port(&self) -> u16 {
         self.local_port
@@ -201,25 +221,56 @@ impl TcpIPv4Handler {
         self.max_pending_resets
     }
 
-    /// Contains logic for handling incoming segments.
+    /// Contains logic for handling incoming segments carried by IPv4 packets.
     ///
     /// Any changes to the state if the handler are communicated through an `Ok(RecvEvent)`.
-    pub fn receive_packet<T: NetworkBytes>(
+    pub fn receive_packet<T: NetworkBytes, F: FnMut(Request) -> Option<Response>>(
         &mut self,
         packet: &IPv4Packet<T>,
-        callback: fn(Request) -> Response,
+        callback: F,
+    ) -> Result<RecvEvent, RecvError> {
+        self.receive_segment(
+            IpAddr::V4(packet.source_address()),
+            packet.payload(),
+            callback,
+        )
+    }
+
+    /// Contains logic for handling incoming segments carried by IPv6 packets. Only valid when the
+    /// handler has a local IPv6 address.
+    ///
+    /// Any changes to the state if the handler are communicated through an `Ok(RecvEvent)`.
+    pub fn receive_ipv6_packet<T: NetworkBytes, F: FnMut(Request) -> Option<Response>>(
+        &mut self,
+        packet: &IPv6Packet<T>,
+        callback: F,
+    ) -> Result<RecvEvent, RecvError> {
+        if self.local_ipv6_addr.is_none() {
+            return Err(RecvError::InvalidAddressFamily);
+        }
+        self.receive_segment(
+            IpAddr::V6(packet.source_address()),
+            packet.payload(),
+            callback,
+        )
+    }
+
+    fn receive_segment<F: FnMut(Request) -> Option<Response>>(
+        &mut self,
+        remote_addr: IpAddr,
+        payload: &[u8],
+        callback: F,
     ) -> Result<RecvEvent, RecvError> {
         // TODO: We skip verifying the checksum, just in case the device model relies on offloading
         // checksum computation from the guest to some other entity. Clear this up at some point!
         // (Issue #520)
-        let segment =
-            TcpSegment::from_bytes(packet.payload(), None).map_err(RecvError::TcpSegment)?;
+        let segment = TcpSegment::from_bytes(payload, None).map_err(RecvError::TcpSegment)?;
 
         if segment.destination_port() != self.local_port {
             return Err(RecvError::InvalidPort);
         }
 
-        let tuple = ConnectionTuple::new(packet.source_address(), segment.source_port());
+        let tuple = ConnectionTuple::new(remote_addr, segment.source_port());
 
         let outcome = if let Some(endpoint) = self.connections.get_mut(&tuple) {
             endpoint.receive_segment(&segment, callback);
@@ -285,6 +336,26 @@ impl TcpIPv4Handler {
         }
     }
 
+    /// Gives the endpoints whose responses were deferred by the callback another chance to
+    /// build them.
+    pub fn process_pending_requests<F: FnMut(Request) -> Option<Response>>(
+        &mut self,
+        mut callback: F,
+    ) {
+        let mut statuses = Vec::new();
+        for (tuple, endpoint) in self.connections.iter_mut() {
+            if endpoint.has_pending_request() {
+                endpoint.process_pending_request(&mut callback);
+                statuses.push((*tuple, endpoint.next_segment_status()));
+            }
+        }
+        for (tuple, status) in statuses {
+            if !self.check_next_segment_status(tuple, status) {
+                self.active_connections.remove(&tuple);
+            }
+        }
+    }
+
     fn check_timeout(&mut self, value: u64, tuple: ConnectionTuple) {
         match self.next_timeout {
             Some((t, _)) if t > value => self.next_timeout = Some((value, tuple)),
@@ -386,12 +457,7 @@ impl TcpIPv4Handler {
         let mut writer_status = None;
         let mut event = WriteEvent::Nothing;
 
-        // Write an incomplete Ipv4 packet and complete it afterwards with missing information.
-        let mut packet =
-            IPv4Packet::write_header(buf, PROTOCOL_TCP, Ipv4Addr::LOCALHOST, Ipv4Addr::LOCALHOST)
-                .map_err(WriteNextError::IPv4Packet)?;
-
-        // We set mss_used to 0, because we don't add any IP options.
+        // We set mss_used to 0, because we don't add any IP options or extension headers.
         // TODO: Maybe get this nicely from packet at some point.
         let mss_reserved = 0;
 
@@ -399,9 +465,15 @@ impl TcpIPv4Handler {
         // number, and using mss_remaining = 0 is perfectly fine in this case, because we don't add
         // any TCP options, or a payload.
         if let Some((tuple, rst_cfg)) = self.rst_queue.pop() {
+            let (local_addr, mut packet) = write_ip_header(
+                buf,
+                self.local_ipv4_addr,
+                self.local_ipv6_addr,
+                tuple.remote_addr,
+            )?;
             let (seq, ack, flags_after_ns) = rst_cfg.seq_ack_tcp_flags();
             let segment_len = TcpSegment::write_incomplete_segment::<[u8]>(
-                packet.inner_mut().payload_mut(),
+                packet.payload_mut(),
                 seq,
                 ack,
                 flags_after_ns,
@@ -414,16 +486,11 @@ impl TcpIPv4Handler {
             .finalize(
                 self.local_port,
                 tuple.remote_port,
-                Some((self.local_ipv4_addr, tuple.remote_addr)),
+                Some((local_addr, tuple.remote_addr)),
             )
             .len();
 
-            packet
-                .inner_mut()
-                .set_source_address(self.local_ipv4_addr)
-                .set_destination_address(tuple.remote_addr);
-
-            let packet_len = packet.with_payload_len_unchecked(segment_len, true).len();
+            let packet_len = packet.with_payload_len_unchecked(segment_len);
             // The unwrap() is safe because packet_len > 0.
             return Ok((
                 Some(NonZeroUsize::new(packet_len).unwrap()),
@@ -436,33 +503,34 @@ impl TcpIPv4Handler {
             .iter()
             .chain(self.next_timeout.as_ref().map(|(_, x)| x))
         {
+            // Write an incomplete IP packet and complete it afterwards with missing information.
+            let (local_addr, mut packet) = write_ip_header(
+                &mut *buf,
+                self.local_ipv4_addr,
+                self.local_ipv6_addr,
+                tuple.remote_addr,
+            )?;
             // Tuples in self.active_connection or self.next_timeout should also appear as keys
             // in self.connections.
             let endpoint = self.connections.get_mut(tuple).unwrap();
             // We need this block to clearly delimit the lifetime of the mutable borrow started by
-            // the following packet.inner_mut().
+            // the following packet.payload_mut().
             let segment_len = {
-                let maybe_segment =
-                    endpoint.write_next_segment(packet.inner_mut().payload_mut(), mss_reserved);
+                let maybe_segment = endpoint.write_next_segment(packet.payload_mut(), mss_reserved);
 
                 match maybe_segment {
                     Some(segment) => segment
                         .finalize(
                             self.local_port,
                             tuple.remote_port,
-                            Some((self.local_ipv4_addr, tuple.remote_addr)),
+                            Some((local_addr, tuple.remote_addr)),
                         )
                         .len(),
                     None => continue,
                 }
             };
 
-            packet
-                .inner_mut()
-                .set_source_address(self.local_ipv4_addr)
-                .set_destination_address(tuple.remote_addr);
-
-            let ip_len = packet.with_payload_len_unchecked(segment_len, true).len();
+            let ip_len = packet.with_payload_len_unchecked(segment_len);
 
             // The unwrap is safe because ip_len > 0.
             len = Some(NonZeroUsize::new(ip_len).unwrap());
@@ -503,6 +571,55 @@ impl TcpIPv4Handler {
     }
 }
 
+// An incomplete IP packet of either family, carrying a TCP segment.
+enum IncompleteIPPacket<'a> {
+    V4(Incomplete<IPv4Packet<'a, &'a mut [u8]>>),
+    V6(Incomplete<IPv6Packet<'a, &'a mut [u8]>>),
+}
+
+impl<'a> IncompleteIPPacket<'a> {
+    fn payload_mut(&mut self) -> &mut [u8] {
+        match self {
+            IncompleteIPPacket::V4(packet) => packet.inner_mut().payload_mut(),
+            IncompleteIPPacket::V6(packet) => packet.inner_mut().payload_mut(),
+        }
+    }
+
+    // Completes the packet, and returns its length.
+    fn with_payload_len_unchecked(self, payload_len: usize) -> usize {
+        match self {
+            IncompleteIPPacket::V4(packet) => {
+                packet.with_payload_len_unchecked(payload_len, true).len()
+            }
+            IncompleteIPPacket::V6(packet) => packet.with_payload_len_unchecked(payload_len).len(),
+        }
+    }
+}
+
+// Writes the header of an IP packet heading towards `remote_addr` to `buf`, using the local address
+// of the same family as source. Returns the source address along with the incomplete packet.
+fn write_ip_header(
+    buf: &mut [u8],
+    local_ipv4_addr: Ipv4Addr,
+    local_ipv6_addr: Option<Ipv6Addr>,
+    remote_addr: IpAddr,
+) -> Result<(IpAddr, IncompleteIPPacket), WriteNextError> {
+    match remote_addr {
+        IpAddr::V4(remote_addr) => {
+            IPv4Packet::write_header(buf, PROTOCOL_TCP, local_ipv4_addr, remote_addr)
+                .map(|packet| (IpAddr::V4(local_ipv4_addr), IncompleteIPPacket::V4(packet)))
+                .map_err(WriteNextError::IPv4Packet)
+        }
+        IpAddr::V6(remote_addr) => {
+            // IPv6 connections only exist while the handler has a local IPv6 address.
+            let local_addr = local_ipv6_addr.unwrap_or(Ipv6Addr::UNSPECIFIED);
+            IPv6Packet::write_header(buf, PROTOCOL_TCP, local_addr, remote_addr)
+                .map(|packet| (IpAddr::V6(local_addr), IncompleteIPPacket::V6(packet)))
+                .map_err(WriteNextError::IPv6Packet)
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -517,7 +634,7 @@ mod tests {
 
     #[allow(clippy::type_complexity)]
     fn write_next<'a>(
-        h: &mut TcpIPv4Handler,
+        h: &mut TcpIPHandler,
         buf: &'a mut [u8],
     ) -> Result<(Option<IPv4Packet<'a, &'a mut [u8]>>, WriteEvent), WriteNextError> {
         h.write_next_packet(buf).map(|(o, e)| {
@@ -532,7 +649,7 @@ mod tests {
     }
 
     fn next_written_segment<'a>(
-        h: &mut TcpIPv4Handler,
+        h: &mut TcpIPHandler,
         buf: &'a mut [u8],
         expected_event: WriteEvent,
     ) -> TcpSegment<'a, &'a mut [u8]> {
@@ -550,7 +667,7 @@ mod tests {
     // When successful, returns how many packets were written. The remote_addr argument is used
     // to check the packets are sent to the appropriate destination.
     fn drain_packets(
-        h: &mut TcpIPv4Handler,
+        h: &mut TcpIPHandler,
         src_addr: Ipv4Addr,
         remote_addr: Ipv4Addr,
     ) -> Result<usize, WriteNextError> {
@@ -583,7 +700,7 @@ mod tests {
         let max_connections = 2;
         let max_pending_resets = 2;
 
-        let mut h = TcpIPv4Handler::new(
+        let mut h = TcpIPHandler::new(
             local_addr,
             local_port,
             NonZeroUsize::new(max_connections).unwrap(),
@@ -709,8 +826,8 @@ mod tests {
         assert_eq!(h.next_segment_status(), NextSegmentStatus::Available);
         assert_eq!(drain_packets(&mut h, local_addr, remote_addr), Ok(1));
 
-        let remote_tuple = ConnectionTuple::new(remote_addr, remote_port);
-        let remote_tuple2 = ConnectionTuple::new(remote_addr, remote_port + 1);
+        let remote_tuple = ConnectionTuple::new(remote_addr.into(), remote_port);
+        let remote_tuple2 = ConnectionTuple::new(remote_addr.into(), remote_port + 1);
 
         // Also, there should be a retransmission timer associated with the previous SYNACK now.
         assert_eq!(h.active_connections.len(), 0);
@@ -771,7 +888,7 @@ mod tests {
         // The timeout associated with the SYNACK of the second connection should be next.
         assert_eq!(h.active_connections.len(), 0);
         if let Some((_, tuple)) = h.next_timeout {
-            assert_ne!(tuple, ConnectionTuple::new(remote_addr, remote_port));
+            assert_ne!(tuple, ConnectionTuple::new(remote_addr.into(), remote_port));
         } else {
             panic!("missing third expected timeout");
         }
@@ -823,4 +940,77 @@ mod tests {
         assert_eq!(h.connections.len(), 1);
         assert_eq!(h.active_connections.len(), 0);
     }
+
+    #[test]
+    fn test_handler_ipv6() {
+        let mut buf = [0u8; 100];
+        let mut buf2 = [0u8; 2000];
+
+        let local_addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0xa9fe, 0xa9fe);
+        let local_port = 80;
+        let remote_addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+        let remote_port = 1012;
+
+        let mut h = TcpIPHandler::new(
+            Ipv4Addr::new(169, 254, 169, 254),
+            local_port,
+            NonZeroUsize::new(2).unwrap(),
+            NonZeroUsize::new(2).unwrap(),
+        );
+
+        let mut p =
+            IPv6Packet::write_header(buf.as_mut(), PROTOCOL_TCP, remote_addr, local_addr).unwrap();
+        let s_len = TcpSegment::write_segment::<[u8]>(
+            p.inner_mut().payload_mut(),
+            remote_port,
+            local_port,
+            123,
+            456,
+            TcpFlags::SYN,
+            10000,
+            None,
+            100,
+            None,
+            None,
+        )
+        .unwrap()
+        .len();
+        let p = p.with_payload_len_unchecked(s_len);
+
+        // IPv6 packets are rejected until the handler has a local IPv6 address.
+        assert_eq!(h.local_ipv6_addr(), None);
+        assert_eq!(
+            h.receive_ipv6_packet(&p, mock_callback).unwrap_err(),
+            RecvError::InvalidAddressFamily
+        );
+
+        h.set_local_ipv6_addr(Some(local_addr));
+        assert_eq!(h.local_ipv6_addr(), Some(local_addr));
+        assert_eq!(
+            h.receive_ipv6_packet(&p, mock_callback),
+            Ok(RecvEvent::NewConnectionSuccessful)
+        );
+        assert_eq!(h.connections.len(), 1);
+        assert!(h
+            .connections
+            .contains_key(&ConnectionTuple::new(remote_addr.into(), remote_port)));
+
+        // The SYNACK is sent back as an IPv6 packet.
+        let (len, event) = h.write_next_packet(buf2.as_mut()).unwrap();
+        assert_eq!(event, WriteEvent::Nothing);
+        let len = len.unwrap().get();
+        let packet = IPv6Packet::from_bytes(&buf2[..len]).unwrap();
+        assert_eq!(packet.next_header(), PROTOCOL_TCP);
+        assert_eq!(packet.source_address(), local_addr);
+        assert_eq!(packet.destination_address(), remote_addr);
+
+        let s = TcpSegment::from_bytes(
+            packet.payload(),
+            Some((local_addr.into(), remote_addr.into())),
+        )
+        .unwrap();
+        assert!(s.flags_after_ns().contains(TcpFlags::SYN | TcpFlags::ACK));
+        assert_eq!(s.source_port(), local_port);
+        assert_eq!(s.destination_port(), remote_port);
+    }
 }
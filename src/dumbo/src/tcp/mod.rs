@@ -96,8 +96,8 @@ mod tests {
 
     // In tcp tests, some of the functions require a callback parameter. Since we do not care,
     // for the purpose of those tests, what that callback does, we need to provide a dummy one.
-    pub fn mock_callback(_request: Request) -> Response {
-        Response::new(Version::Http11, StatusCode::OK)
+    pub fn mock_callback(_request: Request) -> Option<Response> {
+        Some(Response::new(Version::Http11, StatusCode::OK))
     }
 
     #[test]
@@ -9,9 +9,8 @@ use std::{
     thread,
 };
 
-use api_server::{ApiRequest, ApiResponse, ApiServer};
+use api_server::{AccessControl, ApiRequest, ApiResponse, ApiServer};
 use logger::{error, warn};
-use mmds::MMDS;
 use polly::event_manager::{EventManager, Subscriber};
 use seccomp::BpfProgram;
 use utils::{
@@ -30,6 +29,7 @@ struct ApiServerAdapter {
     api_event_fd: EventFd,
     from_api: Receiver<ApiRequest>,
     to_api: Sender<ApiResponse>,
+    to_api_event_fd: EventFd,
     controller: RuntimeApiController,
 }
 
@@ -40,6 +40,7 @@ impl ApiServerAdapter {
         api_event_fd: EventFd,
         from_api: Receiver<ApiRequest>,
         to_api: Sender<ApiResponse>,
+        to_api_event_fd: EventFd,
         vm_resources: VmResources,
         vmm: Arc<Mutex<Vmm>>,
         event_manager: &mut EventManager,
@@ -48,6 +49,7 @@ impl ApiServerAdapter {
             api_event_fd,
             from_api,
             to_api,
+            to_api_event_fd,
             controller: RuntimeApiController::new(vm_resources, vmm),
         }));
         event_manager
@@ -60,18 +62,21 @@ impl ApiServerAdapter {
         }
     }
 
-    fn handle_request(&mut self, req_action: VmmAction) {
-        let response = self.controller.handle_request(req_action);
+    fn handle_request(&mut self, req_action: VmmAction, event_manager: &mut EventManager) {
+        let response = self.controller.handle_request(req_action, event_manager);
         // Send back the result.
         self.to_api
             .send(Box::new(response))
             .map_err(|_| ())
             .expect("one-shot channel closed");
+        self.to_api_event_fd
+            .write(1)
+            .expect("Cannot update send API fd");
     }
 }
 impl Subscriber for ApiServerAdapter {
     /// Handle a read event (EPOLLIN).
-    fn process(&mut self, event: &EpollEvent, _: &mut EventManager) {
+    fn process(&mut self, event: &EpollEvent, event_manager: &mut EventManager) {
         let source = event.fd();
         let event_set = event.event_set();
 
@@ -79,7 +84,7 @@ impl Subscriber for ApiServerAdapter {
             match self.from_api.try_recv() {
                 Ok(api_request) => {
                     let request_is_pause = *api_request == VmmAction::Pause;
-                    self.handle_request(*api_request);
+                    self.handle_request(*api_request, event_manager);
 
                     // If the latest req is a pause request, temporarily switch to a mode where we
                     // do blocking `recv`s on the `from_api` receiver in a loop, until we get
@@ -92,7 +97,7 @@ impl Subscriber for ApiServerAdapter {
                         loop {
                             let req = self.from_api.recv().expect("Error receiving API request.");
                             let req_is_resume = *req == VmmAction::Resume;
-                            self.handle_request(*req);
+                            self.handle_request(*req, event_manager);
                             if req_is_resume {
                                 break;
                             }
@@ -120,10 +125,12 @@ impl Subscriber for ApiServerAdapter {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn run_with_api(
     seccomp_filter: BpfProgram,
     config_json: Option<String>,
     bind_path: PathBuf,
+    access_control: AccessControl,
     instance_info: InstanceInfo,
     start_time_us: Option<u64>,
     start_time_cpu_us: Option<u64>,
@@ -136,9 +143,12 @@ pub(crate) fn run_with_api(
     // Channels for both directions between Vmm and Api threads.
     let (to_vmm, from_api) = channel();
     let (to_api, from_vmm) = channel();
+    // FD to notify the API thread of VMM responses, which it polls along with its clients.
+    let to_api_event_fd = EventFd::new(libc::EFD_NONBLOCK).expect("Cannot create VMM Eventfd.");
+    let from_vmm_event_fd = to_api_event_fd
+        .try_clone()
+        .expect("Failed to clone VMM event FD");
 
-    // MMDS only supported with API.
-    let mmds_info = MMDS.clone();
     let api_server_instance_info = instance_info.clone();
     let to_vmm_event_fd = api_event_fd
         .try_clone()
@@ -151,14 +161,15 @@ pub(crate) fn run_with_api(
         .spawn(move || {
             mask_handled_signals().expect("Unable to install signal mask on API thread.");
 
-            match ApiServer::new(
-                mmds_info,
+            let mut api_server = ApiServer::new(
                 api_server_instance_info,
                 to_vmm,
                 from_vmm,
                 to_vmm_event_fd,
-            )
-            .bind_and_run(
+                from_vmm_event_fd,
+            );
+            api_server.set_access_control(access_control);
+            match api_server.bind_and_run(
                 bind_path,
                 start_time_us,
                 start_time_cpu_us,
@@ -228,7 +239,8 @@ pub(crate) fn run_with_api(
             |response| {
                 to_api
                     .send(Box::new(response))
-                    .expect("one-shot channel closed")
+                    .expect("one-shot channel closed");
+                to_api_event_fd.write(1).expect("Cannot update send API fd");
             },
             boot_timer_enabled,
         ),
@@ -244,6 +256,7 @@ pub(crate) fn run_with_api(
         api_event_fd,
         from_api,
         to_api,
+        to_api_event_fd,
         vm_resources,
         vmm,
         &mut event_manager,
@@ -10,6 +10,7 @@ use std::path::PathBuf;
 use std::process;
 use std::sync::{Arc, Mutex};
 
+use api_server::AccessControl;
 use logger::{error, info, IncMetric, LOGGER, METRICS};
 use polly::event_manager::EventManager;
 use seccomp::{BpfProgram, SeccompLevel};
@@ -68,6 +69,23 @@ fn main() {
                 .default_value(DEFAULT_API_SOCK_PATH)
                 .help("Path to unix domain socket used by the API."),
         )
+        .arg(
+            Argument::new("api-sock-read-only")
+                .takes_value(true)
+                .help("Path to an additional unix domain socket used by the API, which only serves the requests reporting the microVM state and statistics."),
+        )
+        .arg(
+            Argument::new("api-allowed-uid")
+                .allow_multiple(true)
+                .help("User ID allowed to use the API. Clients are allowed if either their user or group ID is listed. \
+                      All clients are allowed if no user or group ID is listed."),
+        )
+        .arg(
+            Argument::new("api-allowed-gid")
+                .allow_multiple(true)
+                .help("Group ID allowed to use the API. Clients are allowed if either their user or group ID is listed. \
+                      All clients are allowed if no user or group ID is listed."),
+        )
         .arg(
             Argument::new("id")
                 .takes_value(true)
@@ -234,10 +252,31 @@ fn main() {
             s.parse::<u64>()
                 .expect("'start-time-cpu-us' parameter expected to be of 'u64' type.")
         });
+        let parse_ids = |arg_name: &'static str| -> Vec<u32> {
+            arguments
+                .multiple_values(arg_name)
+                .unwrap_or_default()
+                .iter()
+                .map(|id| {
+                    id.parse::<u32>().unwrap_or_else(|_| {
+                        panic!("'{}' parameter expected to be of 'u32' type.", arg_name)
+                    })
+                })
+                .collect()
+        };
+        let access_control = AccessControl {
+            allowed_uids: parse_ids("api-allowed-uid"),
+            allowed_gids: parse_ids("api-allowed-gid"),
+            read_only_socket: arguments
+                .single_value("api-sock-read-only")
+                .map(PathBuf::from),
+        };
+
         api_server_adapter::run_with_api(
             seccomp_filter,
             vmm_config_json,
             bind_path,
+            access_control,
             instance_info,
             start_time_us,
             start_time_cpu_us,
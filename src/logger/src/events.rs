@@ -0,0 +1,304 @@
+// Copyright 2021 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Defines the lifecycle events of the microVM, pushed to the subscribers of the API
+//! event stream.
+//!
+//! # Format
+//! Events are written as [server-sent events](https://html.spec.whatwg.org/multipage/server-sent-events.html):
+//! the `event` field carries the event type and the `data` field a JSON object with the
+//! event type, the timestamp and the event specific fields.
+//!
+//! ```bash
+//! event: vcpu_exit
+//! data: {"utc_timestamp_ms":1541591155180,"event":"vcpu_exit","vcpu":0,"reason":"Guest shutdown","exit_code":0}
+//!
+//! ```
+//!
+//! # Design
+//! Events are written directly from the thread which emits them, so that they reach the
+//! subscribers even when the process exits right afterwards. The subscriber streams are
+//! non-blocking: a subscriber which cannot keep up, or which went away, is dropped. The owner
+//! of the streams can also drop a subscriber as soon as it notices the peer went away, so
+//! that it doesn't hold a slot until the next event.
+
+use std::fmt;
+use std::io::Write;
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+use serde::Serialize;
+
+use super::extract_guard;
+
+/// The maximum number of subscribers to the event stream.
+pub const MAX_EVENT_SUBSCRIBERS: usize = 8;
+
+lazy_static! {
+    /// Static instance used for emitting the lifecycle events of the microVM.
+    pub static ref EVENTS: Events = Events::new();
+}
+
+/// Lifecycle events of the microVM.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum VmEvent {
+    /// The microVM was started.
+    Started,
+    /// The microVM was paused.
+    Paused,
+    /// The microVM was resumed.
+    Resumed,
+    /// A snapshot of the microVM was created.
+    SnapshotCreated {
+        /// Path to the file holding the microVM state.
+        snapshot_path: String,
+        /// Path to the file holding the guest memory.
+        mem_file_path: String,
+    },
+    /// The guest balloon driver reached the target size of the balloon device.
+    BalloonTargetReached {
+        /// The target size of the balloon, in MiB.
+        target_mb: u32,
+    },
+    /// A vCPU exited its run loop.
+    VcpuExit {
+        /// The vCPU index.
+        vcpu: u8,
+        /// Why the vCPU exited.
+        reason: String,
+        /// The exit code reported by the vCPU.
+        exit_code: i32,
+    },
+    /// The VMM is about to exit.
+    VmmStopping {
+        /// The exit code of the process.
+        exit_code: i32,
+    },
+}
+
+impl VmEvent {
+    /// Returns the event type, as written in the `event` field of the stream.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Started => "started",
+            Self::Paused => "paused",
+            Self::Resumed => "resumed",
+            Self::SnapshotCreated { .. } => "snapshot_created",
+            Self::BalloonTargetReached { .. } => "balloon_target_reached",
+            Self::VcpuExit { .. } => "vcpu_exit",
+            Self::VmmStopping { .. } => "vmm_stopping",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct EventRecord<'a> {
+    utc_timestamp_ms: u64,
+    #[serde(flatten)]
+    event: &'a VmEvent,
+}
+
+/// Describes the errors which may occur while subscribing to the events.
+#[derive(Debug, PartialEq)]
+pub enum EventsError {
+    /// The maximum number of subscribers has been reached.
+    TooManySubscribers,
+}
+
+impl fmt::Display for EventsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EventsError::TooManySubscribers => write!(
+                f,
+                "The maximum number of event subscribers ({}) has been reached.",
+                MAX_EVENT_SUBSCRIBERS
+            ),
+        }
+    }
+}
+
+/// Event system.
+pub struct Events {
+    // Events get written to each of these, identified by the ID given on subscription.
+    subscribers: Mutex<Vec<(u64, Box<dyn Write + Send>)>>,
+}
+
+impl Events {
+    /// Creates an event system without subscribers.
+    pub fn new() -> Events {
+        Events {
+            subscribers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Adds a destination for the events emitted from now on, identified by `id`. It is
+    /// dropped when writing to it fails, or through `unsubscribe`.
+    pub fn subscribe(&self, id: u64, subscriber: Box<dyn Write + Send>) -> Result<(), EventsError> {
+        let mut subscribers = extract_guard(self.subscribers.lock());
+        if subscribers.len() >= MAX_EVENT_SUBSCRIBERS {
+            return Err(EventsError::TooManySubscribers);
+        }
+        subscribers.push((id, subscriber));
+        Ok(())
+    }
+
+    /// Drops the subscriber identified by `id`, if it is still subscribed.
+    pub fn unsubscribe(&self, id: u64) {
+        extract_guard(self.subscribers.lock()).retain(|(subscriber_id, _)| *subscriber_id != id);
+    }
+
+    /// Returns the number of subscribers.
+    pub fn subscriber_count(&self) -> usize {
+        extract_guard(self.subscribers.lock()).len()
+    }
+
+    /// Writes `event` to all the subscribers.
+    pub fn emit(&self, event: VmEvent) {
+        let mut subscribers = extract_guard(self.subscribers.lock());
+        if subscribers.is_empty() {
+            return;
+        }
+
+        let record = EventRecord {
+            utc_timestamp_ms: utils::time::get_time_ns(utils::time::ClockType::Real) / 1_000_000,
+            event: &event,
+        };
+        let data = match serde_json::to_string(&record) {
+            Ok(data) => data,
+            Err(_) => return,
+        };
+        let frame = format!("event: {}\ndata: {}\n\n", event.name(), data);
+        *subscribers = std::mem::take(&mut *subscribers)
+            .into_iter()
+            .filter_map(|(id, mut subscriber)| {
+                subscriber
+                    .write_all(frame.as_bytes())
+                    .and_then(|_| subscriber.flush())
+                    .ok()
+                    .map(|_| (id, subscriber))
+            })
+            .collect();
+    }
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::sync::Arc;
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct BrokenPipe;
+
+    impl Write for BrokenPipe {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::from(io::ErrorKind::BrokenPipe))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_emit() {
+        let events = Events::new();
+        // Emitting without subscribers is a no-op.
+        events.emit(VmEvent::Started);
+
+        let buf = SharedBuf::default();
+        events.subscribe(1, Box::new(buf.clone())).unwrap();
+        events.subscribe(2, Box::new(BrokenPipe)).unwrap();
+        assert_eq!(events.subscriber_count(), 2);
+
+        events.emit(VmEvent::VcpuExit {
+            vcpu: 1,
+            reason: "Guest shutdown".to_string(),
+            exit_code: 0,
+        });
+        // The failing subscriber is dropped.
+        assert_eq!(events.subscriber_count(), 1);
+
+        let output = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+        let mut lines = output.lines();
+        assert_eq!(lines.next(), Some("event: vcpu_exit"));
+        let data: serde_json::Value =
+            serde_json::from_str(lines.next().unwrap().strip_prefix("data: ").unwrap()).unwrap();
+        assert!(data["utc_timestamp_ms"].as_u64().unwrap() > 0);
+        assert_eq!(data["event"], "vcpu_exit");
+        assert_eq!(data["vcpu"], 1);
+        assert_eq!(data["reason"], "Guest shutdown");
+        assert_eq!(data["exit_code"], 0);
+        assert_eq!(lines.next(), Some(""));
+        assert!(output.ends_with("\n\n"));
+    }
+
+    #[test]
+    fn test_subscriber_limit() {
+        let events = Events::new();
+        for id in 0..MAX_EVENT_SUBSCRIBERS {
+            events
+                .subscribe(id as u64, Box::new(SharedBuf::default()))
+                .unwrap();
+        }
+        assert_eq!(
+            events.subscribe(0, Box::new(SharedBuf::default())),
+            Err(EventsError::TooManySubscribers)
+        );
+        // Unsubscribing frees a slot.
+        events.unsubscribe(0);
+        events.unsubscribe(MAX_EVENT_SUBSCRIBERS as u64);
+        assert_eq!(events.subscriber_count(), MAX_EVENT_SUBSCRIBERS - 1);
+        events.subscribe(0, Box::new(SharedBuf::default())).unwrap();
+        assert_eq!(
+            EventsError::TooManySubscribers.to_string(),
+            format!(
+                "The maximum number of event subscribers ({}) has been reached.",
+                MAX_EVENT_SUBSCRIBERS
+            )
+        );
+    }
+
+    #[test]
+    fn test_event_names() {
+        let events = vec![
+            VmEvent::Started,
+            VmEvent::Paused,
+            VmEvent::Resumed,
+            VmEvent::SnapshotCreated {
+                snapshot_path: "vm.snap".to_string(),
+                mem_file_path: "vm.mem".to_string(),
+            },
+            VmEvent::BalloonTargetReached { target_mb: 64 },
+            VmEvent::VcpuExit {
+                vcpu: 0,
+                reason: String::new(),
+                exit_code: 0,
+            },
+            VmEvent::VmmStopping { exit_code: 0 },
+        ];
+        for event in events {
+            // The `event` field of the stream matches the type in the JSON data.
+            assert_eq!(serde_json::to_value(&event).unwrap()["event"], event.name());
+        }
+    }
+}
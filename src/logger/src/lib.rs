@@ -1,14 +1,17 @@
 // Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
+mod events;
 mod init;
 mod logger;
 mod metrics;
 
 use std::sync::LockResult;
 
+pub use crate::events::{Events, EventsError, VmEvent, EVENTS, MAX_EVENT_SUBSCRIBERS};
 pub use crate::logger::{LoggerError, LOGGER};
 pub use crate::metrics::{
-    IncMetric, MetricsError, SharedIncMetric, SharedStoreMetric, StoreMetric, METRICS,
+    IncMetric, MetricsError, NetInterfaceMetrics, SharedIncMetric, SharedStoreMetric, StoreMetric,
+    METRICS,
 };
 pub use log::Level::*;
 pub use log::*;
@@ -136,6 +136,11 @@ impl Logger {
         }
     }
 
+    /// Returns `true` if the logger was initialized, after which it can't be initialized again.
+    pub fn is_initialized(&self) -> bool {
+        self.init.is_initialized()
+    }
+
     fn show_level(&self) -> bool {
         self.show_level.load(Ordering::Relaxed)
     }
@@ -423,9 +428,16 @@ impl Log for Logger {
         self.write_log(msg, record.metadata().level());
     }
 
-    // This is currently not used.
     fn flush(&self) {
-        unreachable!();
+        let result = if self.init.is_initialized() {
+            extract_guard(self.log_buf.lock()).flush()
+        } else {
+            stdout().flush().and(stderr().flush())
+        };
+        if result.is_err() {
+            // No reason to log the error to stderr here, just increment the metric.
+            METRICS.logger.missed_log_count.inc();
+        }
     }
 }
 
@@ -570,9 +582,11 @@ mod tests {
         // Assert that the first call to `init()` is successful.
         let (writer, mut reader) = log_channel();
         logger.set_instance_id(TEST_INSTANCE_ID.to_string());
+        assert!(!logger.is_initialized());
         assert!(logger
             .init(TEST_APP_HEADER.to_string(), Box::new(writer))
             .is_ok());
+        assert!(logger.is_initialized());
         validate_log(
             &mut Box::new(&mut reader),
             &format!("{}\n", TEST_APP_HEADER),
@@ -604,6 +618,31 @@ mod tests {
         validate_log(&mut Box::new(&mut reader_2), "");
     }
 
+    #[test]
+    fn test_flush() {
+        let logger = Logger::mock_new();
+        // Flushing the standard output and error works before initialization.
+        logger.flush();
+
+        let (writer, mut reader) = log_channel();
+        assert!(logger
+            .init(
+                TEST_APP_HEADER.to_string(),
+                Box::new(std::io::BufWriter::new(writer))
+            )
+            .is_ok());
+        let mut log = Vec::new();
+        reader.read_to_end(&mut log).unwrap();
+        assert!(log.is_empty());
+
+        // The buffered logs reach the destination once flushed.
+        logger.flush();
+        validate_log(
+            &mut Box::new(&mut reader),
+            &format!("{}\n", TEST_APP_HEADER),
+        );
+    }
+
     #[test]
     fn test_create_prefix() {
         let logger = Logger::mock_new();
@@ -60,13 +60,15 @@
 //! If if turns out this approach is not really what we want, it's pretty easy to resort to
 //! something else, while working behind the same interface.
 
+use std::collections::BTreeMap;
 use std::fmt;
 use std::io::Write;
 use std::ops::Deref;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex, RwLock};
 
 use lazy_static::lazy_static;
+use serde::ser::SerializeMap;
 use serde::{Serialize, Serializer};
 
 use super::extract_guard;
@@ -117,6 +119,12 @@ impl<T: Serialize> Metrics<T> {
         Ok(())
     }
 
+    /// Returns `true` if the metrics system was initialized, after which it can't be
+    /// initialized again.
+    pub fn is_initialized(&self) -> bool {
+        self.is_initialized.load(Ordering::Relaxed)
+    }
+
     /// Writes metrics to the destination provided as argument upon initialization of the metrics.
     /// Upon failure, an error is returned if metrics system is initialized and metrics could not be
     /// written.
@@ -297,6 +305,10 @@ pub struct GetRequestsMetrics {
     pub machine_cfg_count: SharedIncMetric,
     /// Number of failures during GETs for getting information on the instance.
     pub machine_cfg_fails: SharedIncMetric,
+    /// Number of GETs for getting network interface configuration or statistics.
+    pub network_count: SharedIncMetric,
+    /// Number of failures during GETs for getting network interface configuration or statistics.
+    pub network_fails: SharedIncMetric,
 }
 
 /// Metrics specific to PUT API Requests for counting user triggered actions and/or failures.
@@ -349,6 +361,15 @@ pub struct PatchRequestsMetrics {
     pub machine_cfg_fails: SharedIncMetric,
 }
 
+/// Metrics specific to DELETE API Requests for counting user triggered actions and/or failures.
+#[derive(Default, Serialize)]
+pub struct DeleteRequestsMetrics {
+    /// Number of tries to DELETE a net device.
+    pub network_count: SharedIncMetric,
+    /// Number of failures in DELETEing a net device.
+    pub network_fails: SharedIncMetric,
+}
+
 /// Balloon Device associated metrics.
 #[derive(Default, Serialize)]
 pub struct BalloonDeviceMetrics {
@@ -364,6 +385,16 @@ pub struct BalloonDeviceMetrics {
     pub deflate_count: SharedIncMetric,
     /// Number of times when handling events on a balloon device failed.
     pub event_fails: SharedIncMetric,
+    /// Number of free page reports received from the driver.
+    pub free_page_report_count: SharedIncMetric,
+    /// Number of 4K pages released to the host through free page reporting.
+    pub free_page_report_pages: SharedIncMetric,
+    /// Number of reported ranges that could not be released to the host.
+    pub free_page_report_fails: SharedIncMetric,
+    /// Number of times the automatic sizing policy inflated the balloon.
+    pub auto_size_inflate_count: SharedIncMetric,
+    /// Number of times the automatic sizing policy deflated the balloon.
+    pub auto_size_deflate_count: SharedIncMetric,
 }
 
 /// Block Device associated metrics.
@@ -446,6 +477,12 @@ pub struct MmdsMetrics {
     pub rx_bad_eth: SharedIncMetric,
     /// The total number of successful receive operations by the MMDS.
     pub rx_count: SharedIncMetric,
+    /// Number of metadata requests presenting an expired token.
+    pub rx_expired_token: SharedIncMetric,
+    /// Number of metadata requests presenting an invalid token.
+    pub rx_invalid_token: SharedIncMetric,
+    /// Number of metadata requests without a token, when one is required.
+    pub rx_no_token: SharedIncMetric,
     /// The total number of bytes sent by the MMDS.
     pub tx_bytes: SharedIncMetric,
     /// The total number of successful send operations by the MMDS.
@@ -519,6 +556,78 @@ pub struct NetDeviceMetrics {
     pub tx_spoofed_mac_count: SharedIncMetric,
 }
 
+/// Metrics of a single network interface.
+#[derive(Default, Serialize)]
+pub struct NetInterfaceMetrics {
+    /// Number of bytes received by the guest.
+    pub rx_bytes_count: SharedIncMetric,
+    /// Number of packets received by the guest.
+    pub rx_packets_count: SharedIncMetric,
+    /// Number of errors while writing received frames to the guest.
+    pub rx_fails: SharedIncMetric,
+    /// Number of RX rate limiter throttling events.
+    pub rx_rate_limiter_throttled: SharedIncMetric,
+    /// Number of frames sent to the guest by the MMDS.
+    pub rx_mmds_frames: SharedIncMetric,
+    /// Number of bytes transmitted by the guest.
+    pub tx_bytes_count: SharedIncMetric,
+    /// Number of packets transmitted by the guest.
+    pub tx_packets_count: SharedIncMetric,
+    /// Number of frames transmitted by the guest which were dropped, because they were malformed
+    /// or the link was down.
+    pub tx_dropped: SharedIncMetric,
+    /// Number of TX rate limiter throttling events.
+    pub tx_rate_limiter_throttled: SharedIncMetric,
+    /// Number of frames transmitted by the guest which were consumed by the MMDS.
+    pub tx_mmds_frames: SharedIncMetric,
+    /// Number of times reading from the host backend failed.
+    pub tap_read_fails: SharedIncMetric,
+    /// Number of times writing to the host backend failed.
+    pub tap_write_fails: SharedIncMetric,
+}
+
+/// Metrics of all the network interfaces, keyed by interface ID.
+#[derive(Default)]
+pub struct NetInterfacesMetrics(RwLock<BTreeMap<String, Arc<NetInterfaceMetrics>>>);
+
+impl NetInterfacesMetrics {
+    /// Returns new metrics for the interface with `iface_id` ID, to be flushed from now on.
+    /// Replaces any metrics previously registered for the same ID.
+    pub fn register(&self, iface_id: &str) -> Arc<NetInterfaceMetrics> {
+        let metrics = Arc::new(NetInterfaceMetrics::default());
+        extract_guard(self.0.write()).insert(iface_id.to_string(), metrics.clone());
+        metrics
+    }
+
+    /// Stops flushing `metrics` for the interface with `iface_id` ID, unless they were replaced
+    /// in the meantime.
+    pub fn unregister(&self, iface_id: &str, metrics: &Arc<NetInterfaceMetrics>) {
+        let mut ifaces = extract_guard(self.0.write());
+        if ifaces
+            .get(iface_id)
+            .map_or(false, |registered| Arc::ptr_eq(registered, metrics))
+        {
+            ifaces.remove(iface_id);
+        }
+    }
+
+    /// Returns the metrics of the interface with `iface_id` ID, if there is one.
+    pub fn get(&self, iface_id: &str) -> Option<Arc<NetInterfaceMetrics>> {
+        extract_guard(self.0.read()).get(iface_id).cloned()
+    }
+}
+
+impl Serialize for NetInterfacesMetrics {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let ifaces = extract_guard(self.0.read());
+        let mut map = serializer.serialize_map(Some(ifaces.len()))?;
+        for (iface_id, metrics) in ifaces.iter() {
+            map.serialize_entry(iface_id, metrics.as_ref())?;
+        }
+        map.end()
+    }
+}
+
 /// Performance metrics related for the moment only to snapshots.
 // These store the duration of creating/loading a snapshot and of
 // pausing/resuming the microVM.
@@ -698,6 +807,8 @@ pub struct FirecrackerMetrics {
     pub balloon: BalloonDeviceMetrics,
     /// A block device's related metrics.
     pub block: BlockDeviceMetrics,
+    /// Metrics related to API DELETE requests.
+    pub delete_api_requests: DeleteRequestsMetrics,
     /// Metrics related to API GET requests.
     pub get_api_requests: GetRequestsMetrics,
     /// Metrics related to the i8042 device.
@@ -710,6 +821,8 @@ pub struct FirecrackerMetrics {
     pub mmds: MmdsMetrics,
     /// A network device's related metrics.
     pub net: NetDeviceMetrics,
+    /// Metrics of each network interface, keyed by interface ID.
+    pub net_interfaces: NetInterfacesMetrics,
     /// Metrics related to API PATCH requests.
     pub patch_api_requests: PatchRequestsMetrics,
     /// Metrics related to API PUT requests.
@@ -750,7 +863,9 @@ mod tests {
         assert!(res.is_ok() && !res.unwrap());
 
         let f = TempFile::new().expect("Failed to create temporary metrics file");
+        assert!(!m.is_initialized());
         assert!(m.init(Box::new(f.into_file()),).is_ok());
+        assert!(m.is_initialized());
 
         assert!(m.write().is_ok());
 
@@ -802,6 +917,33 @@ mod tests {
         assert_eq!(1, m1.fetch());
     }
 
+    #[test]
+    fn test_net_interfaces_metrics() {
+        let ifaces = NetInterfacesMetrics::default();
+        assert!(ifaces.get("eth0").is_none());
+
+        let eth0 = ifaces.register("eth0");
+        eth0.rx_packets_count.add(3);
+        assert_eq!(ifaces.get("eth0").unwrap().rx_packets_count.count(), 3);
+        let _eth1 = ifaces.register("eth1");
+
+        let json: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&ifaces).unwrap()).unwrap();
+        assert_eq!(json["eth0"]["rx_packets_count"], 3);
+        assert_eq!(json["eth1"]["rx_packets_count"], 0);
+        // Counters are reset upon flush.
+        let json: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&ifaces).unwrap()).unwrap();
+        assert_eq!(json["eth0"]["rx_packets_count"], 0);
+
+        // Metrics replaced by a newer interface with the same ID are kept.
+        let new_eth0 = ifaces.register("eth0");
+        ifaces.unregister("eth0", &eth0);
+        assert!(Arc::ptr_eq(&ifaces.get("eth0").unwrap(), &new_eth0));
+        ifaces.unregister("eth0", &new_eth0);
+        assert!(ifaces.get("eth0").is_none());
+    }
+
     #[test]
     fn test_serialize() {
         let s = serde_json::to_string(&FirecrackerMetrics::default());
@@ -1,6 +1,7 @@
 // Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
+use std::collections::BTreeMap;
 use std::result::Result;
 
 use crate::HttpHeaderError;
@@ -69,8 +70,8 @@ impl Header {
 /// Wrapper over the list of headers associated with a Request that we need
 /// in order to parse the request correctly and be able to respond to it.
 ///
-/// The only `Content-Type`s supported are `text/plain` and `application/json`, which are both
-/// in plain text actually and don't influence our parsing process.
+/// The only `Content-Type`s supported are `text/plain`, `application/json` and
+/// `application/octet-stream`, which don't influence our parsing process.
 ///
 /// All the other possible header fields are not necessary in order to serve this connection
 /// and, thus, are not of interest to us. However, we still look for header fields that might
@@ -94,6 +95,9 @@ pub struct Headers {
     /// `Accept` header might be used by HTTP clients to enforce server responses with content
     /// formatted in a specific way.
     accept: MediaType,
+    /// Header fields that are not interpreted by the parser, keyed by field name as received.
+    /// They are kept for the users of the request, which might define their own headers.
+    custom_entries: BTreeMap<String, String>,
 }
 
 impl Default for Headers {
@@ -106,6 +110,7 @@ impl Default for Headers {
             // The default `Accept` media type is plain text. This is inclusive enough
             // for structured and unstructured text.
             accept: MediaType::PlainText,
+            custom_entries: BTreeMap::default(),
         }
     }
 }
@@ -206,12 +211,9 @@ impl Headers {
                         Header::AcceptEncoding => Encoding::try_from(entry[1].trim().as_bytes()),
                     }
                 } else {
-                    Err(RequestError::HeaderError(
-                        HttpHeaderError::UnsupportedValue(
-                            entry[0].to_string(),
-                            entry[1].to_string(),
-                        ),
-                    ))
+                    self.custom_entries
+                        .insert(entry[0].trim().to_string(), entry[1].trim().to_string());
+                    Ok(())
                 }
             }
             Err(utf8_err) => Err(RequestError::HeaderError(
@@ -242,6 +244,29 @@ impl Headers {
         self.accept
     }
 
+    /// Returns the header fields that are not interpreted by the parser.
+    pub fn custom_entries(&self) -> &BTreeMap<String, String> {
+        &self.custom_entries
+    }
+
+    /// Returns the value of a header field that is not interpreted by the parser, looked up
+    /// case-insensitively by name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use micro_http::Headers;
+    ///
+    /// let headers = Headers::try_from(b"X-Custom-Header: value\r\n\r\n").unwrap();
+    /// assert_eq!(headers.custom_entry("x-custom-header"), Some("value"));
+    /// ```
+    pub fn custom_entry(&self, name: &str) -> Option<&str> {
+        self.custom_entries
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
     /// Parses a byte slice into a Headers structure for a HTTP request.
     ///
     /// The byte slice is expected to have the following format: </br>
@@ -355,6 +380,10 @@ pub enum MediaType {
     PlainText,
     /// Media Type: "application/json".
     ApplicationJson,
+    /// Media Type: "application/octet-stream".
+    OctetStream,
+    /// Media Type: "text/event-stream".
+    EventStream,
 }
 
 impl Default for MediaType {
@@ -389,6 +418,8 @@ impl MediaType {
         match utf8_slice.as_str().trim() {
             "text/plain" => Ok(Self::PlainText),
             "application/json" => Ok(Self::ApplicationJson),
+            "application/octet-stream" => Ok(Self::OctetStream),
+            "text/event-stream" => Ok(Self::EventStream),
             _ => Err(RequestError::InvalidRequest),
         }
     }
@@ -407,6 +438,8 @@ impl MediaType {
         match self {
             Self::PlainText => "text/plain",
             Self::ApplicationJson => "application/json",
+            Self::OctetStream => "application/octet-stream",
+            Self::EventStream => "text/event-stream",
         }
     }
 }
@@ -446,6 +479,11 @@ mod tests {
             MediaType::PlainText
         );
 
+        assert_eq!(
+            MediaType::try_from(b"application/octet-stream").unwrap(),
+            MediaType::OctetStream
+        );
+
         assert_eq!(
             MediaType::try_from(b"").unwrap_err(),
             RequestError::InvalidRequest
@@ -464,6 +502,12 @@ mod tests {
 
         let media_type = MediaType::PlainText;
         assert_eq!(media_type.as_str(), "text/plain");
+
+        let media_type = MediaType::OctetStream;
+        assert_eq!(media_type.as_str(), "application/octet-stream");
+
+        let media_type = MediaType::EventStream;
+        assert_eq!(media_type.as_str(), "text/event-stream");
     }
 
     #[test]
@@ -527,6 +571,14 @@ mod tests {
         )
         .unwrap();
         assert_eq!(headers.content_length, 29);
+        // Uninterpreted header fields are kept, with their values trimmed.
+        assert_eq!(headers.custom_entries().len(), 1);
+        assert_eq!(
+            headers.custom_entry("last-modified"),
+            Some("Tue, 15 Nov 1994 12:45:26 GMT")
+        );
+        assert_eq!(headers.custom_entry("Content-Length"), None);
+        assert_eq!(headers.custom_entry("Expires"), None);
 
         let bytes: [u8; 10] = [130, 140, 150, 130, 140, 150, 130, 140, 150, 160];
         // Invalid headers.
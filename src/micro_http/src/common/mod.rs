@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use std::fmt::{Display, Error, Formatter};
+use std::io::Read;
 use std::str::Utf8Error;
 
 pub mod headers;
@@ -196,6 +197,73 @@ impl Body {
     }
 }
 
+/// A source of bytes for a response body which is produced incrementally, instead of being
+/// held whole in memory. Such bodies are sent with the chunked transfer coding, one chunk for
+/// each piece returned by the source.
+///
+/// The source is read by `HttpConnection::try_write` whenever the previous chunk has been
+/// written, so it should produce the next piece without blocking.
+///
+/// # Example
+///
+/// ```
+/// use micro_http::BodySource;
+///
+/// let mut source = vec![b"Hello, ".to_vec(), b"world!".to_vec()].into_iter();
+/// assert_eq!(source.next_chunk().unwrap(), Some(b"Hello, ".to_vec()));
+/// ```
+pub trait BodySource: Send {
+    /// Returns the next piece of the body, or `None` once the body is complete.
+    fn next_chunk(&mut self) -> std::io::Result<Option<Vec<u8>>>;
+}
+
+impl<I: Iterator<Item = Vec<u8>> + Send> BodySource for I {
+    fn next_chunk(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        Ok(self.next())
+    }
+}
+
+/// A `BodySource` which reads the body from a reader, at most `chunk_size` bytes at a time.
+///
+/// # Example
+///
+/// ```
+/// use micro_http::{BodySource, ReadSource};
+///
+/// let mut source = ReadSource::new(&b"Hello, world!"[..], 7);
+/// assert_eq!(source.next_chunk().unwrap(), Some(b"Hello, ".to_vec()));
+/// assert_eq!(source.next_chunk().unwrap(), Some(b"world!".to_vec()));
+/// assert_eq!(source.next_chunk().unwrap(), None);
+/// ```
+pub struct ReadSource<R> {
+    reader: R,
+    chunk_size: usize,
+}
+
+impl<R: Read + Send> ReadSource<R> {
+    /// Creates a `ReadSource` which reads from `reader` until it reaches the end of file.
+    pub fn new(reader: R, chunk_size: usize) -> Self {
+        Self { reader, chunk_size }
+    }
+}
+
+impl<R: Read + Send> BodySource for ReadSource<R> {
+    fn next_chunk(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        let mut data = vec![0; self.chunk_size];
+        loop {
+            match self.reader.read(&mut data) {
+                Ok(0) => return Ok(None),
+                Ok(len) => {
+                    data.truncate(len);
+                    return Ok(Some(data));
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
 /// Supported HTTP Methods.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Method {
@@ -205,6 +273,8 @@ pub enum Method {
     Put,
     /// PATCH Method.
     Patch,
+    /// DELETE Method.
+    Delete,
 }
 
 impl Method {
@@ -220,6 +290,7 @@ impl Method {
             b"GET" => Ok(Self::Get),
             b"PUT" => Ok(Self::Put),
             b"PATCH" => Ok(Self::Patch),
+            b"DELETE" => Ok(Self::Delete),
             _ => Err(RequestError::InvalidHttpMethod("Unsupported HTTP method.")),
         }
     }
@@ -230,6 +301,7 @@ impl Method {
             Self::Get => b"GET",
             Self::Put => b"PUT",
             Self::Patch => b"PATCH",
+            Self::Delete => b"DELETE",
         }
     }
 }
@@ -330,11 +402,13 @@ mod tests {
         assert_eq!(Method::Get.raw(), b"GET");
         assert_eq!(Method::Put.raw(), b"PUT");
         assert_eq!(Method::Patch.raw(), b"PATCH");
+        assert_eq!(Method::Delete.raw(), b"DELETE");
 
         // Tests for try_from
         assert_eq!(Method::try_from(b"GET").unwrap(), Method::Get);
         assert_eq!(Method::try_from(b"PUT").unwrap(), Method::Put);
         assert_eq!(Method::try_from(b"PATCH").unwrap(), Method::Patch);
+        assert_eq!(Method::try_from(b"DELETE").unwrap(), Method::Delete);
         assert_eq!(
             Method::try_from(b"POST").unwrap_err(),
             RequestError::InvalidHttpMethod("Unsupported HTTP method.")
@@ -5,7 +5,7 @@ use std::collections::VecDeque;
 use std::io::{Read, Write};
 
 use crate::common::ascii::{CR, CRLF_LEN, LF};
-use crate::common::Body;
+use crate::common::{Body, BodySource};
 pub use crate::common::{ConnectionError, HttpHeaderError, RequestError};
 use crate::headers::Headers;
 use crate::request::{find, Request, RequestLine};
@@ -49,6 +49,9 @@ pub struct HttpConnection<T> {
     /// A buffer containing the bytes of a response that is currently
     /// being sent.
     response_buffer: Option<Vec<u8>>,
+    /// The source of the chunked body of the response that is currently
+    /// being sent, read once `response_buffer` has been written.
+    body_source: Option<Box<dyn BodySource>>,
 }
 
 impl<T: Read + Write> HttpConnection<T> {
@@ -65,6 +68,7 @@ impl<T: Read + Write> HttpConnection<T> {
             parsed_requests: VecDeque::new(),
             response_queue: VecDeque::new(),
             response_buffer: None,
+            body_source: None,
         }
     }
 
@@ -381,19 +385,47 @@ impl<T: Read + Write> HttpConnection<T> {
     /// we will discard all responses from response_queue because there is no way
     /// to deliver it to client.
     ///
+    /// The body of a chunked response is read from its source one chunk at a time,
+    /// each time the previous chunk has been fully written. The following responses
+    /// are sent after the last chunk.
+    ///
     /// # Errors
-    /// `StreamError` is returned when an IO operation fails.
+    /// `StreamError` is returned when an IO operation fails, including reading
+    /// from the source of a chunked body.
     /// `ConnectionClosed` is returned when trying to write on a closed connection.
     /// `InvalidWrite` is returned when trying to write on a connection with an
     /// empty outgoing buffer.
     pub fn try_write(&mut self) -> Result<(), ConnectionError> {
+        self.write_next().map(|_| ())
+    }
+
+    /// Writes the pending responses until the stream would block.
+    /// Returns `true` once all of them were written.
+    ///
+    /// # Errors
+    /// The same as `try_write`.
+    pub fn try_write_pending(&mut self) -> Result<bool, ConnectionError> {
+        while self.pending_write() {
+            if !self.write_next()? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    // Writes the next piece of the first available response. Returns `false` if the stream
+    // is not writable right now.
+    fn write_next(&mut self) -> Result<bool, ConnectionError> {
         if self.response_buffer.is_none() {
-            if let Some(response) = self.response_queue.pop_front() {
+            if self.body_source.is_some() {
+                self.response_buffer = Some(self.next_body_chunk()?);
+            } else if let Some(mut response) = self.response_queue.pop_front() {
                 let mut response_buffer_vec: Vec<u8> = Vec::new();
                 response
                     .write_all(&mut response_buffer_vec)
                     .map_err(ConnectionError::StreamError)?;
                 self.response_buffer = Some(response_buffer_vec);
+                self.body_source = response.take_body_source();
             } else {
                 return Err(ConnectionError::InvalidWrite);
             }
@@ -401,10 +433,14 @@ impl<T: Read + Write> HttpConnection<T> {
 
         let mut response_fully_written = false;
         let mut connection_closed = false;
+        let mut would_block = false;
 
         if let Some(response_buffer_vec) = self.response_buffer.as_mut() {
             let bytes_to_be_written = response_buffer_vec.len();
             match self.stream.write(response_buffer_vec.as_slice()) {
+                // The stream is not writable right now, so the response is written on a
+                // later call.
+                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => would_block = true,
                 Ok(0) | Err(_) => {
                     connection_closed = true;
                 }
@@ -425,13 +461,45 @@ impl<T: Read + Write> HttpConnection<T> {
             self.response_buffer.take();
         }
 
-        Ok(())
+        Ok(!would_block)
+    }
+
+    // Reads the next piece of the chunked body being sent, and frames it as a chunk. The
+    // last chunk, which has no data, is returned once the source is exhausted.
+    fn next_body_chunk(&mut self) -> Result<Vec<u8>, ConnectionError> {
+        let source = match self.body_source.as_mut() {
+            Some(source) => source,
+            None => return Err(ConnectionError::InvalidWrite),
+        };
+        loop {
+            match source.next_chunk() {
+                // An empty chunk would end the body.
+                Ok(Some(data)) if data.is_empty() => continue,
+                Ok(Some(data)) => {
+                    let mut chunk = format!("{:x}\r\n", data.len()).into_bytes();
+                    chunk.extend_from_slice(&data);
+                    chunk.extend_from_slice(&[CR, LF]);
+                    return Ok(chunk);
+                }
+                Ok(None) => {
+                    self.body_source = None;
+                    return Ok(b"0\r\n\r\n".to_vec());
+                }
+                Err(e) => {
+                    // The response can't be completed, so the client will only notice once
+                    // the connection is closed.
+                    self.clear_write_buffer();
+                    return Err(ConnectionError::StreamError(e));
+                }
+            }
+        }
     }
 
     /// Discards all pending writes from the connection.
     pub fn clear_write_buffer(&mut self) {
         self.response_queue.clear();
         self.response_buffer.take();
+        self.body_source.take();
     }
 
     /// Send a response back to the source of a request.
@@ -480,7 +548,20 @@ impl<T: Read + Write> HttpConnection<T> {
 
     /// Returns `true` if there are bytes waiting to be written into the stream.
     pub fn pending_write(&self) -> bool {
-        self.response_buffer.is_some() || !self.response_queue.is_empty()
+        self.response_buffer.is_some()
+            || self.body_source.is_some()
+            || !self.response_queue.is_empty()
+    }
+
+    /// Returns a reference to the underlying stream.
+    pub fn stream(&self) -> &T {
+        &self.stream
+    }
+
+    /// Consumes the connection and returns the underlying stream. Pending writes are
+    /// discarded.
+    pub fn into_stream(self) -> T {
+        self.stream
     }
 }
 
@@ -889,6 +970,100 @@ mod tests {
         assert_eq!(response_buffer, expected_response);
     }
 
+    #[test]
+    fn test_write_chunked_response() {
+        let (sender, mut receiver) = UnixStream::pair().unwrap();
+        let mut conn = HttpConnection::new(sender);
+
+        let mut response = Response::new(Version::Http11, StatusCode::OK);
+        response.set_body_source(Box::new(
+            vec![b"Hello, ".to_vec(), vec![], b"chunked world!".to_vec()].into_iter(),
+        ));
+        assert!(response.is_chunked());
+        conn.enqueue_response(response);
+        // Pipelined responses are sent after the chunked body.
+        let mut response = Response::new(Version::Http11, StatusCode::OK);
+        response.set_body(Body::new("done"));
+        conn.enqueue_response(response);
+
+        while conn.pending_write() {
+            conn.try_write().unwrap();
+        }
+        match conn.try_write() {
+            Err(ConnectionError::InvalidWrite) => (),
+            _ => panic!("Test failed."),
+        }
+        drop(conn);
+
+        let expected_response: &'static [u8] = b"HTTP/1.1 200 \r\n\
+            Server: Firecracker API\r\n\
+            Connection: keep-alive\r\n\
+            Content-Type: application/json\r\n\
+            Transfer-Encoding: chunked\r\n\r\n\
+            7\r\nHello, \r\n\
+            e\r\nchunked world!\r\n\
+            0\r\n\r\n\
+            HTTP/1.1 200 \r\n\
+            Server: Firecracker API\r\n\
+            Connection: keep-alive\r\n\
+            Content-Type: application/json\r\n\
+            Content-Length: 4\r\n\r\n\
+            done";
+        let mut response_buffer = vec![];
+        receiver.read_to_end(&mut response_buffer).unwrap();
+        assert_eq!(response_buffer.as_slice(), expected_response);
+    }
+
+    #[test]
+    fn test_try_write_pending() {
+        let (sender, mut receiver) = UnixStream::pair().unwrap();
+        sender.set_nonblocking(true).unwrap();
+        let mut conn = HttpConnection::new(sender);
+
+        conn.enqueue_response(Response::new(Version::Http11, StatusCode::NoContent));
+        conn.enqueue_response(Response::new(Version::Http11, StatusCode::NoContent));
+        assert!(conn.try_write_pending().unwrap());
+        assert!(!conn.pending_write());
+
+        // A response which doesn't fit in the socket buffer is left pending.
+        let mut response = Response::new(Version::Http11, StatusCode::OK);
+        response.set_body(Body::new(vec![b'a'; 16 << 20]));
+        conn.enqueue_response(response);
+        assert!(!conn.try_write_pending().unwrap());
+        assert!(conn.pending_write());
+
+        drop(conn);
+        let mut response_buffer = vec![];
+        receiver.read_to_end(&mut response_buffer).unwrap();
+        assert!(response_buffer.starts_with(b"HTTP/1.1 204 \r\n"));
+    }
+
+    #[test]
+    fn test_write_chunked_response_source_error() {
+        struct FailingSource;
+        impl BodySource for FailingSource {
+            fn next_chunk(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+                Err(std::io::Error::from(std::io::ErrorKind::Other))
+            }
+        }
+
+        let (sender, _receiver) = UnixStream::pair().unwrap();
+        let mut conn = HttpConnection::new(sender);
+        let mut response = Response::new(Version::Http11, StatusCode::OK);
+        response.set_body_source(Box::new(FailingSource));
+        conn.enqueue_response(response);
+        conn.enqueue_response(Response::new(Version::Http11, StatusCode::OK));
+
+        // The headers are sent first.
+        assert!(conn.try_write().is_ok());
+        match conn.try_write() {
+            Err(ConnectionError::StreamError(_)) => (),
+            _ => panic!("Test failed."),
+        }
+        // The remaining responses are discarded.
+        assert!(!conn.pending_write());
+    }
+
     #[test]
     fn test_try_read_negative_content_len() {
         // Request with negative `Content-Length` header.
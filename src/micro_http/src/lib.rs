@@ -117,7 +117,7 @@ use crate::common::headers;
 pub use crate::connection::{ConnectionError, HttpConnection};
 pub use crate::request::{Request, RequestError};
 pub use crate::response::{Response, ResponseHeaders, StatusCode};
-pub use crate::server::{HttpServer, ServerError, ServerRequest, ServerResponse};
+pub use crate::server::{HttpServer, PeerCredentials, ServerError, ServerRequest, ServerResponse};
 
 pub use crate::common::headers::{Encoding, Headers, MediaType};
-pub use crate::common::{Body, HttpHeaderError, Method, Version};
+pub use crate::common::{Body, BodySource, HttpHeaderError, Method, ReadSource, Version};
@@ -280,6 +280,25 @@ impl Request {
         &self.request_line.uri
     }
 
+    /// Returns `true` if the client keeps the connection open after the response. HTTP/1.1
+    /// clients do so unless they send `Connection: close`, while HTTP/1.0 clients have to
+    /// send `Connection: keep-alive`.
+    pub fn keep_alive(&self) -> bool {
+        let has_option = |option: &str| {
+            self.headers
+                .custom_entry("Connection")
+                .map_or(false, |value| {
+                    value
+                        .split(',')
+                        .any(|token| token.trim().eq_ignore_ascii_case(option))
+                })
+        };
+        match self.http_version() {
+            Version::Http10 => has_option("keep-alive"),
+            Version::Http11 => !has_option("close"),
+        }
+    }
+
     /// Returns the HTTP `Version` of the `Request`.
     pub fn http_version(&self) -> Version {
         self.request_line.http_version
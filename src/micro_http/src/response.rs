@@ -4,7 +4,7 @@
 use std::io::{Error as WriteError, Write};
 
 use crate::ascii::{COLON, CR, LF, SP};
-use crate::common::{Body, Version};
+use crate::common::{Body, BodySource, Version};
 use crate::headers::{Header, MediaType};
 use crate::Method;
 
@@ -19,18 +19,30 @@ pub enum StatusCode {
     Continue,
     /// 200, OK
     OK,
+    /// 202, Accepted
+    Accepted,
     /// 204, No Content
     NoContent,
     /// 400, Bad Request
     BadRequest,
+    /// 401, Unauthorized
+    Unauthorized,
+    /// 403, Forbidden
+    Forbidden,
     /// 404, Not Found
     NotFound,
     /// 405, Method Not Allowed
     MethodNotAllowed,
+    /// 409, Conflict
+    Conflict,
+    /// 413, Payload Too Large
+    PayloadTooLarge,
     /// 500, Internal Server Error
     InternalServerError,
     /// 501, Not Implemented
     NotImplemented,
+    /// 503, Service Unavailable
+    ServiceUnavailable,
 }
 
 impl StatusCode {
@@ -39,12 +51,18 @@ impl StatusCode {
         match self {
             Self::Continue => b"100",
             Self::OK => b"200",
+            Self::Accepted => b"202",
             Self::NoContent => b"204",
             Self::BadRequest => b"400",
+            Self::Unauthorized => b"401",
+            Self::Forbidden => b"403",
             Self::NotFound => b"404",
             Self::MethodNotAllowed => b"405",
+            Self::Conflict => b"409",
+            Self::PayloadTooLarge => b"413",
             Self::InternalServerError => b"500",
             Self::NotImplemented => b"501",
+            Self::ServiceUnavailable => b"503",
         }
     }
 }
@@ -83,6 +101,10 @@ pub struct ResponseHeaders {
     server: String,
     allow: Vec<Method>,
     accept_encoding: bool,
+    custom_headers: Vec<(String, String)>,
+    streaming: bool,
+    chunked: bool,
+    close: bool,
 }
 
 impl Default for ResponseHeaders {
@@ -93,6 +115,10 @@ impl Default for ResponseHeaders {
             server: String::from("Firecracker API"),
             allow: Vec::new(),
             accept_encoding: false,
+            custom_headers: Vec::new(),
+            streaming: false,
+            chunked: false,
+            close: false,
         }
     }
 }
@@ -125,12 +151,36 @@ impl ResponseHeaders {
         buf.write_all(self.server.as_bytes())?;
 
         buf.write_all(&[CR, LF])?;
-        buf.write_all(b"Connection: keep-alive")?;
+        if self.streaming || self.close {
+            // The body of a streaming response ends when the connection is closed.
+            buf.write_all(b"Connection: close")?;
+        } else {
+            buf.write_all(b"Connection: keep-alive")?;
+        }
         buf.write_all(&[CR, LF])?;
 
         self.write_allow_header(buf)?;
 
-        if self.content_length != 0 {
+        for (name, value) in self.custom_headers.iter() {
+            buf.write_all(name.as_bytes())?;
+            buf.write_all(&[COLON, SP])?;
+            buf.write_all(value.as_bytes())?;
+            buf.write_all(&[CR, LF])?;
+        }
+
+        if self.streaming || self.chunked {
+            buf.write_all(Header::ContentType.raw())?;
+            buf.write_all(&[COLON, SP])?;
+            buf.write_all(self.content_type.as_str().as_bytes())?;
+            buf.write_all(&[CR, LF])?;
+
+            if self.chunked {
+                buf.write_all(Header::TransferEncoding.raw())?;
+                buf.write_all(&[COLON, SP])?;
+                buf.write_all(b"chunked")?;
+                buf.write_all(&[CR, LF])?;
+            }
+        } else if self.content_length != 0 {
             buf.write_all(Header::ContentType.raw())?;
             buf.write_all(&[COLON, SP])?;
             buf.write_all(self.content_type.as_str().as_bytes())?;
@@ -175,6 +225,26 @@ impl ResponseHeaders {
     }
 }
 
+// Holds the source of a response body sent with the chunked transfer coding.
+struct ChunkedBody(Box<dyn BodySource>);
+
+impl std::fmt::Debug for ChunkedBody {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "ChunkedBody")
+    }
+}
+
+impl PartialEq for ChunkedBody {
+    // Body sources are consumed as they are sent, so their contents can't be compared: a
+    // body is only equal to itself.
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(
+            &*self.0 as *const dyn BodySource as *const u8,
+            &*other.0 as *const dyn BodySource as *const u8,
+        )
+    }
+}
+
 /// Wrapper over an HTTP Response.
 ///
 /// The Response is created using a `Version` and a `StatusCode`. When creating a Response object,
@@ -186,6 +256,7 @@ pub struct Response {
     status_line: StatusLine,
     headers: ResponseHeaders,
     body: Option<Body>,
+    body_source: Option<ChunkedBody>,
 }
 
 impl Response {
@@ -195,6 +266,7 @@ impl Response {
             status_line: StatusLine::new(http_version, status_code),
             headers: ResponseHeaders::default(),
             body: Default::default(),
+            body_source: None,
         }
     }
 
@@ -204,9 +276,57 @@ impl Response {
     /// - `ContentLength`: this is set to the length of the specified body.
     pub fn set_body(&mut self, body: Body) {
         self.headers.set_content_length(body.len() as i32);
+        self.headers.chunked = false;
+        self.body_source = None;
         self.body = Some(body);
     }
 
+    /// Sets a body which is read incrementally from `source` while the `Response` is sent,
+    /// using the chunked transfer coding. This replaces any body set through `set_body`.
+    ///
+    /// The chunked transfer coding is only defined for HTTP/1.1.
+    pub fn set_body_source(&mut self, source: Box<dyn BodySource>) {
+        self.headers.set_content_length(0);
+        self.headers.chunked = true;
+        self.body = None;
+        self.body_source = Some(ChunkedBody(source));
+    }
+
+    /// Returns `true` if the body of the `Response` is sent with the chunked transfer coding.
+    pub fn is_chunked(&self) -> bool {
+        self.headers.chunked
+    }
+
+    /// Reads the whole body source of a chunked `Response`, and sets it as a regular body
+    /// instead. Used for the clients which don't support the chunked transfer coding.
+    ///
+    /// # Errors
+    /// Returns the error of the body source, in which case the body is left empty.
+    pub fn read_body_source(&mut self) -> std::io::Result<()> {
+        let mut source = match self.take_body_source() {
+            Some(source) => source,
+            None => return Ok(()),
+        };
+        let mut body = Vec::new();
+        let result = loop {
+            match source.next_chunk() {
+                Ok(Some(data)) => body.extend_from_slice(&data),
+                Ok(None) => break Ok(()),
+                Err(e) => {
+                    body.clear();
+                    break Err(e);
+                }
+            }
+        };
+        self.set_body(Body::new(body));
+        result
+    }
+
+    /// Takes the body source out of the `Response`, leaving the headers untouched.
+    pub fn take_body_source(&mut self) -> Option<Box<dyn BodySource>> {
+        self.body_source.take().map(|body_source| body_source.0)
+    }
+
     /// Updates the content type of the `Response`.
     pub fn set_content_type(&mut self, content_type: MediaType) {
         self.headers.set_content_type(content_type);
@@ -232,6 +352,41 @@ impl Response {
         self.headers.allow.push(method);
     }
 
+    /// Adds a header field that is not interpreted by the library, such as an application
+    /// defined header.
+    pub fn add_custom_header(&mut self, name: &str, value: &str) {
+        self.headers
+            .custom_headers
+            .push((name.to_string(), value.to_string()));
+    }
+
+    /// Marks the `Response` as the start of a stream: only the status line and the headers
+    /// are sent by the server, and the body is written by the application directly to the
+    /// connection, which is closed when the stream ends. See `HttpServer::detach`.
+    pub fn set_streaming(&mut self) {
+        self.headers.streaming = true;
+        self.headers.chunked = false;
+        self.headers.set_content_length(0);
+        self.body = None;
+        self.body_source = None;
+    }
+
+    /// Returns `true` if the `Response` starts a stream.
+    pub fn is_streaming(&self) -> bool {
+        self.headers.streaming
+    }
+
+    /// Marks the `Response` as the last one sent on its connection, which the server closes
+    /// once the response is written.
+    pub fn set_close(&mut self) {
+        self.headers.close = true;
+    }
+
+    /// Returns `true` if the connection is closed after the `Response` is sent.
+    pub fn closes_connection(&self) -> bool {
+        self.headers.streaming || self.headers.close
+    }
+
     fn write_body<T: Write>(&self, mut buf: T) -> Result<(), WriteError> {
         if let Some(ref body) = self.body {
             buf.write_all(body.raw())?;
@@ -241,6 +396,9 @@ impl Response {
 
     /// Writes the content of the `Response` to the specified `buf`.
     ///
+    /// The body of a chunked `Response` is not written: it is read from its source, and
+    /// framed, by `HttpConnection::try_write`.
+    ///
     /// # Errors
     /// Returns an error when the buffer is not large enough.
     pub fn write_all<T: Write>(&self, mut buf: &mut T) -> Result<(), WriteError> {
@@ -281,6 +439,16 @@ impl Response {
     pub fn allow(&self) -> Vec<Method> {
         self.headers.allow.clone()
     }
+
+    /// Returns the value of a header field added through `add_custom_header`, looked up
+    /// case-insensitively by name.
+    pub fn custom_header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .custom_headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
 }
 
 #[cfg(test)]
@@ -366,12 +534,130 @@ mod tests {
     fn test_status_code() {
         assert_eq!(StatusCode::Continue.raw(), b"100");
         assert_eq!(StatusCode::OK.raw(), b"200");
+        assert_eq!(StatusCode::Accepted.raw(), b"202");
         assert_eq!(StatusCode::NoContent.raw(), b"204");
         assert_eq!(StatusCode::BadRequest.raw(), b"400");
+        assert_eq!(StatusCode::Unauthorized.raw(), b"401");
+        assert_eq!(StatusCode::Forbidden.raw(), b"403");
         assert_eq!(StatusCode::NotFound.raw(), b"404");
         assert_eq!(StatusCode::MethodNotAllowed.raw(), b"405");
+        assert_eq!(StatusCode::Conflict.raw(), b"409");
+        assert_eq!(StatusCode::PayloadTooLarge.raw(), b"413");
         assert_eq!(StatusCode::InternalServerError.raw(), b"500");
         assert_eq!(StatusCode::NotImplemented.raw(), b"501");
+        assert_eq!(StatusCode::ServiceUnavailable.raw(), b"503");
+    }
+
+    #[test]
+    fn test_chunked_response() {
+        let mut response = Response::new(Version::Http11, StatusCode::OK);
+        response.set_body(Body::new("replaced"));
+        response.set_body_source(Box::new(vec![b"chunk".to_vec()].into_iter()));
+        assert!(response.is_chunked());
+        assert!(response.body().is_none());
+        assert_eq!(response.content_length(), 0);
+        assert_ne!(response, Response::new(Version::Http11, StatusCode::OK));
+        // A body source is only equal to itself.
+        let same_response = &response;
+        assert!(response == *same_response);
+        let mut other_response = Response::new(Version::Http11, StatusCode::OK);
+        other_response.set_body_source(Box::new(vec![b"chunk".to_vec()].into_iter()));
+        assert_ne!(response, other_response);
+
+        // Only the head of the response is written, the body is read from the source.
+        let expected_response: &'static [u8] = b"HTTP/1.1 200 \r\n\
+            Server: Firecracker API\r\n\
+            Connection: keep-alive\r\n\
+            Content-Type: application/json\r\n\
+            Transfer-Encoding: chunked\r\n\r\n";
+        let mut response_buf = Vec::new();
+        assert!(response.write_all(&mut response_buf).is_ok());
+        assert_eq!(response_buf.as_slice(), expected_response);
+
+        let mut source = response.take_body_source().unwrap();
+        assert_eq!(source.next_chunk().unwrap(), Some(b"chunk".to_vec()));
+        assert_eq!(source.next_chunk().unwrap(), None);
+        assert!(response.take_body_source().is_none());
+
+        // Setting a regular body ends the chunked transfer coding.
+        response.set_body(Body::new("body"));
+        assert!(!response.is_chunked());
+        assert_eq!(response.content_length(), 4);
+    }
+
+    #[test]
+    fn test_read_body_source() {
+        let mut response = Response::new(Version::Http11, StatusCode::OK);
+        assert!(response.read_body_source().is_ok());
+        assert!(response.body().is_none());
+
+        response.set_body_source(Box::new(
+            vec![b"Hello, ".to_vec(), b"world!".to_vec()].into_iter(),
+        ));
+        assert!(response.read_body_source().is_ok());
+        assert!(!response.is_chunked());
+        assert_eq!(response.body().unwrap(), Body::new("Hello, world!"));
+        assert_eq!(response.content_length(), 13);
+
+        struct FailingSource;
+        impl BodySource for FailingSource {
+            fn next_chunk(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+                Err(std::io::Error::from(std::io::ErrorKind::Other))
+            }
+        }
+        response.set_body_source(Box::new(FailingSource));
+        assert!(response.read_body_source().is_err());
+        assert!(!response.is_chunked());
+        assert_eq!(response.content_length(), 0);
+    }
+
+    #[test]
+    fn test_close_response() {
+        let mut response = Response::new(Version::Http11, StatusCode::NoContent);
+        assert!(!response.closes_connection());
+        response.set_close();
+        assert!(response.closes_connection());
+
+        let expected_response: &'static [u8] = b"HTTP/1.1 204 \r\n\
+            Server: Firecracker API\r\n\
+            Connection: close\r\n\r\n";
+        let mut response_buf = Vec::new();
+        assert!(response.write_all(&mut response_buf).is_ok());
+        assert_eq!(response_buf.as_slice(), expected_response);
+    }
+
+    #[test]
+    fn test_streaming_response() {
+        let mut response = Response::new(Version::Http11, StatusCode::OK);
+        response.set_body(Body::new("ignored"));
+        response.set_content_type(MediaType::EventStream);
+        response.set_streaming();
+        assert!(response.is_streaming());
+        assert!(response.body().is_none());
+
+        let expected_response: &'static [u8] = b"HTTP/1.1 200 \r\n\
+            Server: Firecracker API\r\n\
+            Connection: close\r\n\
+            Content-Type: text/event-stream\r\n\r\n";
+        let mut response_buf = Vec::new();
+        assert!(response.write_all(&mut response_buf).is_ok());
+        assert_eq!(response_buf.as_slice(), expected_response);
+    }
+
+    #[test]
+    fn test_custom_header() {
+        let mut response = Response::new(Version::Http11, StatusCode::OK);
+        response.add_custom_header("X-Custom-Header", "value");
+        assert_eq!(response.custom_header("x-custom-header"), Some("value"));
+        assert_eq!(response.custom_header("X-Other-Header"), None);
+
+        let expected_response: &'static [u8] = b"HTTP/1.1 200 \r\n\
+            Server: Firecracker API\r\n\
+            Connection: keep-alive\r\n\
+            X-Custom-Header: value\r\n\r\n";
+        let mut response_buf = Vec::new();
+        assert!(response.write_all(&mut response_buf).is_ok());
+        assert_eq!(response_buf.as_slice(), expected_response);
     }
 
     #[test]
@@ -21,23 +21,98 @@ static SERVER_FULL_ERROR_MESSAGE: &[u8] = b"HTTP/1.1 503\r\n\
                                             Server: Firecracker API\r\n\
                                             Connection: close\r\n\
                                             Content-Length: 40\r\n\r\n{ \"error\": \"Too many open connections\" }";
+static ACCESS_DENIED_ERROR_MESSAGE: &[u8] = b"HTTP/1.1 403\r\n\
+                                              Server: Firecracker API\r\n\
+                                              Connection: close\r\n\
+                                              Content-Length: 28\r\n\r\n{ \"error\": \"Access denied\" }";
 const MAX_CONNECTIONS: usize = 10;
 
 type Result<T> = std::result::Result<T, ServerError>;
 
+/// Decides, from the credentials of a client, whether its connection is accepted.
+type PeerFilter = Box<dyn Fn(Option<PeerCredentials>) -> bool>;
+
+/// Credentials of the process on the other end of a connection, as reported by the
+/// `SO_PEERCRED` socket option when the connection was accepted.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PeerCredentials {
+    /// Process ID of the peer.
+    pub pid: i32,
+    /// Effective user ID of the peer.
+    pub uid: u32,
+    /// Effective group ID of the peer.
+    pub gid: u32,
+}
+
+impl PeerCredentials {
+    // Returns the credentials of the peer of `stream`, if the kernel reports them.
+    fn from_stream(stream: &UnixStream) -> Option<Self> {
+        let mut ucred = libc::ucred {
+            pid: 0,
+            uid: 0,
+            gid: 0,
+        };
+        let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+        // Safe because the kernel writes at most `len` bytes to `ucred`, and we check the result.
+        let ret = unsafe {
+            libc::getsockopt(
+                stream.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_PEERCRED,
+                &mut ucred as *mut libc::ucred as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        if ret != 0 || len as usize != std::mem::size_of::<libc::ucred>() {
+            return None;
+        }
+        Some(Self {
+            pid: ucred.pid,
+            uid: ucred.uid,
+            gid: ucred.gid,
+        })
+    }
+}
+
 /// Wrapper over `Request` which adds an identification token.
 pub struct ServerRequest {
     /// Inner request.
     pub request: Request,
     /// Identification token.
     id: u64,
+    /// Credentials of the client which sent the request.
+    peer_credentials: Option<PeerCredentials>,
+    /// Whether the request was received on the read-only socket.
+    read_only: bool,
 }
 
 impl ServerRequest {
     /// Creates a new `ServerRequest` object from an existing `Request`,
     /// adding an identification token.
     pub fn new(request: Request, id: u64) -> Self {
-        Self { request, id }
+        Self {
+            request,
+            id,
+            peer_credentials: None,
+            read_only: false,
+        }
+    }
+
+    /// Returns the credentials of the client which sent the request, if known.
+    pub fn peer_credentials(&self) -> Option<PeerCredentials> {
+        self.peer_credentials
+    }
+
+    /// Returns `true` if the request was received on the read-only socket, in which case the
+    /// user decides which requests it serves.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Returns the identification token, which is shared by all the requests
+    /// received on the same connection.
+    pub fn id(&self) -> u64 {
+        self.id
     }
 
     /// Returns a reference to the inner request.
@@ -48,12 +123,29 @@ impl ServerRequest {
     /// Calls the function provided on the inner request to obtain the response.
     /// The response is then wrapped in a `ServerResponse`.
     ///
+    /// The response is adapted to the client: a chunked body is sent whole, with a
+    /// `Content-Length`, to HTTP/1.0 clients, and the connection is closed after the
+    /// response if the client doesn't keep it alive.
+    ///
     /// Returns a `ServerResponse` ready for yielding to the server
     pub fn process<F>(&self, mut callable: F) -> ServerResponse
     where
         F: FnMut(&Request) -> Response,
     {
-        let http_response = callable(self.inner());
+        let mut http_response = callable(self.inner());
+        // The chunked transfer coding is only defined for HTTP/1.1.
+        if http_response.is_chunked() && self.request.http_version() == Version::Http10 {
+            if let Err(e) = http_response.read_body_source() {
+                http_response = Response::new(
+                    http_response.http_version(),
+                    StatusCode::InternalServerError,
+                );
+                http_response.set_body(Body::new(format!("{{ \"error\": \"{}\" }}", e)));
+            }
+        }
+        if !self.request.keep_alive() {
+            http_response.set_close();
+        }
         ServerResponse::new(http_response, self.id)
     }
 }
@@ -70,6 +162,12 @@ impl ServerResponse {
     fn new(response: Response, id: u64) -> Self {
         Self { response, id }
     }
+
+    /// Returns `true` if the inner response starts a stream, which has to be sent through
+    /// `HttpServer::detach`.
+    pub fn is_streaming(&self) -> bool {
+        self.response.is_streaming()
+    }
 }
 
 /// Describes the state of the connection as far as data exchange
@@ -92,6 +190,13 @@ struct ClientConnection<T> {
     /// absorbed responses.
     /// This has to be `0` if we want to drop the connection.
     in_flight_response_count: u32,
+    /// Whether the connection was accepted on the read-only socket.
+    read_only: bool,
+    /// Credentials of the client.
+    peer_credentials: Option<PeerCredentials>,
+    /// Whether a response closing the connection was enqueued, after which the
+    /// connection is closed and no other response is sent.
+    close_after_write: bool,
 }
 
 impl<T: Read + Write> ClientConnection<T> {
@@ -100,6 +205,9 @@ impl<T: Read + Write> ClientConnection<T> {
             connection,
             state: ClientConnectionState::AwaitingIncoming,
             in_flight_response_count: 0,
+            read_only: false,
+            peer_credentials: None,
+            close_after_write: false,
         }
     }
 
@@ -175,7 +283,11 @@ impl<T: Read + Write> ClientConnection<T> {
             _ => {
                 // Check if we still have bytes to write for this connection.
                 if !self.connection.pending_write() {
-                    self.state = ClientConnectionState::AwaitingIncoming;
+                    self.state = if self.close_after_write {
+                        ClientConnectionState::Closed
+                    } else {
+                        ClientConnectionState::AwaitingIncoming
+                    };
                 }
             }
         }
@@ -183,7 +295,8 @@ impl<T: Read + Write> ClientConnection<T> {
     }
 
     fn enqueue_response(&mut self, response: Response) -> Result<()> {
-        if self.state != ClientConnectionState::Closed {
+        if self.state != ClientConnectionState::Closed && !self.close_after_write {
+            self.close_after_write = response.closes_connection();
             self.connection.enqueue_response(response);
         }
         self.in_flight_response_count = self
@@ -260,6 +373,10 @@ pub struct HttpServer {
     /// We use the file descriptor of the stream as the key for mapping
     /// connections because the 1-to-1 relation is guaranteed by the OS.
     connections: HashMap<RawFd, ClientConnection<UnixStream>>,
+    /// Socket on which we listen for new read-only connections.
+    read_only_socket: Option<UnixListener>,
+    /// Filter for the clients allowed to connect.
+    peer_filter: Option<PeerFilter>,
 }
 
 impl HttpServer {
@@ -276,14 +393,42 @@ impl HttpServer {
             socket,
             epoll,
             connections: HashMap::new(),
+            read_only_socket: None,
+            peer_filter: None,
         })
     }
 
+    /// Binds an additional socket, whose requests are marked as read-only, so the user can
+    /// restrict what they may do. Must be called before `start_server`.
+    ///
+    /// # Errors
+    /// Returns an `IOError` when binding fails.
+    pub fn add_read_only_socket<P: AsRef<Path>>(&mut self, path_to_socket: P) -> Result<()> {
+        self.read_only_socket =
+            Some(UnixListener::bind(path_to_socket).map_err(ServerError::IOError)?);
+        Ok(())
+    }
+
+    /// Restricts the clients which may connect to the server. When a connection is accepted,
+    /// on any socket, `filter` is called with the credentials of the client. If it returns
+    /// `false`, the server answers with `403 Forbidden` and closes the connection right away,
+    /// so rejected clients don't take up any of the connection slots.
+    pub fn set_peer_filter<F>(&mut self, filter: F)
+    where
+        F: Fn(Option<PeerCredentials>) -> bool + 'static,
+    {
+        self.peer_filter = Some(Box::new(filter));
+    }
+
     /// Starts the HTTP Server.
     pub fn start_server(&mut self) -> Result<()> {
-        // Add the socket on which we listen for new connections to the
+        // Add the sockets on which we listen for new connections to the
         // `epoll` structure.
-        Self::epoll_add(&self.epoll, self.socket.as_raw_fd())
+        Self::epoll_add(&self.epoll, self.socket.as_raw_fd())?;
+        if let Some(read_only_socket) = self.read_only_socket.as_ref() {
+            Self::epoll_add(&self.epoll, read_only_socket.as_raw_fd())?;
+        }
+        Ok(())
     }
 
     /// This function is responsible for the data exchange with the clients and should
@@ -323,15 +468,19 @@ impl HttpServer {
             // Check the file descriptor which produced the notification `e`.
             // It could be that we have a new connection, or one of our open
             // connections is ready to exchange data with a client.
-            if e.fd() == self.socket.as_raw_fd() {
-                // We have received a notification on the listener socket, which
+            let read_only = self
+                .read_only_socket
+                .as_ref()
+                .map_or(false, |socket| e.fd() == socket.as_raw_fd());
+            if read_only || e.fd() == self.socket.as_raw_fd() {
+                // We have received a notification on a listener socket, which
                 // means we have a new connection to accept.
-                match self.handle_new_connection() {
+                match self.handle_new_connection(read_only) {
                     // If the server is full, we send a message to the client
                     // notifying them that we will close the connection, then
                     // we discard it.
                     Err(ServerError::ServerFull) => {
-                        self.socket
+                        self.listener(read_only)
                             .accept()
                             .map_err(ServerError::IOError)
                             .and_then(move |(mut stream, _)| {
@@ -372,7 +521,11 @@ impl HttpServer {
                         &mut client_connection
                             .read()?
                             .into_iter()
-                            .map(|request| ServerRequest::new(request, e.data()))
+                            .map(|request| ServerRequest {
+                                peer_credentials: client_connection.peer_credentials,
+                                read_only: client_connection.read_only,
+                                ..ServerRequest::new(request, e.data())
+                            })
                             .collect(),
                     );
                     // If the connection was incoming before we read and we now have to write
@@ -397,6 +550,10 @@ impl HttpServer {
                             fd,
                             epoll::EventSet::IN | epoll::EventSet::READ_HANG_UP,
                         )?;
+                    } else if client_connection.state == ClientConnectionState::Closed {
+                        // The connection is only kept until the in-flight responses, which
+                        // are discarded, are yielded.
+                        Self::epoll_mod(&self.epoll, fd, epoll::EventSet::READ_HANG_UP)?;
                     }
                 }
             }
@@ -524,19 +681,64 @@ impl HttpServer {
         Ok(())
     }
 
-    /// Accepts a new incoming connection and adds it to the `epoll` notification structure.
+    /// Sends a streaming response (see `Response::set_streaming`), after the responses
+    /// still queued on its connection, and hands the connection over to the caller, which
+    /// writes the body of the stream directly to the returned stream. The server no longer
+    /// tracks the connection afterwards. Returns `None` if the connection is gone, or if
+    /// the client can't take the responses right away, in which case it is closed.
+    ///
+    /// # Errors
+    /// `IOError` is returned when an `epoll` or socket operation fails.
+    /// `ConnectionError` is returned when the responses cannot be written.
+    pub fn detach(&mut self, response: ServerResponse) -> Result<Option<UnixStream>> {
+        let stream_fd = response.id as RawFd;
+        let mut connection = match self.connections.remove(&stream_fd) {
+            Some(client_connection) => client_connection.connection,
+            None => return Ok(None),
+        };
+        self.epoll
+            .ctl(
+                epoll::ControlOperation::Delete,
+                stream_fd,
+                epoll::EpollEvent::default(),
+            )
+            .map_err(ServerError::IOError)?;
+
+        connection.enqueue_response(response.response);
+        // The responses are short, so they fit in the socket buffer of a client which reads
+        // them. A client which doesn't would not keep up with the stream either, and must not
+        // block the server.
+        if !connection
+            .try_write_pending()
+            .map_err(ServerError::ConnectionError)?
+        {
+            return Ok(None);
+        }
+        Ok(Some(connection.into_stream()))
+    }
+
+    // Returns the socket on which the read-only connections, or the other ones, are accepted.
+    fn listener(&self, read_only: bool) -> &UnixListener {
+        match self.read_only_socket.as_ref() {
+            Some(read_only_socket) if read_only => read_only_socket,
+            _ => &self.socket,
+        }
+    }
+
+    /// Accepts a new incoming connection, on the read-only socket or the other one, and adds
+    /// it to the `epoll` notification structure, unless the peer filter rejects the client.
     ///
     /// # Errors
     /// `IOError` is returned when socket or epoll operations fail.
     /// `ServerFull` is returned if server full capacity has been reached.
-    fn handle_new_connection(&mut self) -> Result<()> {
+    fn handle_new_connection(&mut self, read_only: bool) -> Result<()> {
         if self.connections.len() == MAX_CONNECTIONS {
             // If we want a replacement policy for connections
             // this is where we will have it.
             return Err(ServerError::ServerFull);
         }
 
-        self.socket
+        self.listener(read_only)
             .accept()
             .map_err(ServerError::IOError)
             .and_then(|(stream, _)| {
@@ -546,13 +748,26 @@ impl HttpServer {
                     .map(|_| stream)
                     .map_err(ServerError::IOError)
             })
-            .and_then(|stream| {
+            .and_then(|mut stream| {
+                let peer_credentials = PeerCredentials::from_stream(&stream);
+                if let Some(peer_filter) = self.peer_filter.as_ref() {
+                    if !peer_filter(peer_credentials) {
+                        // The client is notified on a best effort basis, then the
+                        // connection is dropped.
+                        let _ = stream.write(ACCESS_DENIED_ERROR_MESSAGE);
+                        return Ok(());
+                    }
+                }
                 // Add the stream to the `epoll` structure and listen for bytes to be read.
                 Self::epoll_add(&self.epoll, stream.as_raw_fd())?;
                 // Then add it to our open connections.
                 self.connections.insert(
                     stream.as_raw_fd(),
-                    ClientConnection::new(HttpConnection::new(stream)),
+                    ClientConnection {
+                        read_only,
+                        peer_credentials,
+                        ..ClientConnection::new(HttpConnection::new(stream))
+                    },
                 );
                 Ok(())
             })
@@ -595,7 +810,8 @@ mod tests {
     use std::net::Shutdown;
     use std::os::unix::net::UnixStream;
 
-    use crate::common::Body;
+    use crate::common::{Body, Method};
+    use crate::MediaType;
     use utils::tempfile::TempFile;
 
     fn get_temp_socket_file() -> TempFile {
@@ -933,4 +1149,229 @@ mod tests {
         second_socket.shutdown(std::net::Shutdown::Both).unwrap();
         assert!(server.requests().is_ok());
     }
+
+    #[test]
+    fn test_connection_close() {
+        let path_to_socket = get_temp_socket_file();
+
+        let mut server = HttpServer::new(path_to_socket.as_path()).unwrap();
+        server.start_server().unwrap();
+
+        let mut socket = UnixStream::connect(path_to_socket.as_path()).unwrap();
+        assert!(server.requests().unwrap().is_empty());
+
+        // Pipelined requests are answered in order, until one closes the connection.
+        socket
+            .write_all(
+                b"GET /first HTTP/1.1\r\n\r\n\
+                GET /second HTTP/1.1\r\nConnection: close\r\n\r\n\
+                GET /third HTTP/1.1\r\n\r\n",
+            )
+            .unwrap();
+        let req_vec = server.requests().unwrap();
+        assert_eq!(req_vec.len(), 3);
+        for server_request in req_vec {
+            server
+                .respond(server_request.process(|request| {
+                    let mut response = Response::new(Version::Http11, StatusCode::OK);
+                    response.set_body_source(Box::new(
+                        vec![request.uri().get_abs_path().as_bytes().to_vec()].into_iter(),
+                    ));
+                    response
+                }))
+                .unwrap();
+        }
+        while !server.connections.is_empty() {
+            assert!(server.requests().unwrap().is_empty());
+        }
+
+        let expected_response: &'static [u8] = b"HTTP/1.1 200 \r\n\
+            Server: Firecracker API\r\n\
+            Connection: keep-alive\r\n\
+            Content-Type: application/json\r\n\
+            Transfer-Encoding: chunked\r\n\r\n\
+            6\r\n/first\r\n0\r\n\r\n\
+            HTTP/1.1 200 \r\n\
+            Server: Firecracker API\r\n\
+            Connection: close\r\n\
+            Content-Type: application/json\r\n\
+            Transfer-Encoding: chunked\r\n\r\n\
+            7\r\n/second\r\n0\r\n\r\n";
+        let mut buf = Vec::new();
+        socket.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf.as_slice(), expected_response);
+    }
+
+    #[test]
+    fn test_http10_chunked_response() {
+        let path_to_socket = get_temp_socket_file();
+
+        let mut server = HttpServer::new(path_to_socket.as_path()).unwrap();
+        server.start_server().unwrap();
+
+        let mut socket = UnixStream::connect(path_to_socket.as_path()).unwrap();
+        assert!(server.requests().unwrap().is_empty());
+
+        // HTTP/1.0 clients get the whole body, and the connection is closed unless they keep
+        // it alive.
+        socket.write_all(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+        let mut req_vec = server.requests().unwrap();
+        server
+            .respond(req_vec.remove(0).process(|_request| {
+                let mut response = Response::new(Version::Http11, StatusCode::OK);
+                response.set_body_source(Box::new(
+                    vec![b"Hello, ".to_vec(), b"world!".to_vec()].into_iter(),
+                ));
+                response
+            }))
+            .unwrap();
+        while !server.connections.is_empty() {
+            assert!(server.requests().unwrap().is_empty());
+        }
+
+        let expected_response: &'static [u8] = b"HTTP/1.1 200 \r\n\
+            Server: Firecracker API\r\n\
+            Connection: close\r\n\
+            Content-Type: application/json\r\n\
+            Content-Length: 13\r\n\r\n\
+            Hello, world!";
+        let mut buf = Vec::new();
+        socket.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf.as_slice(), expected_response);
+    }
+
+    #[test]
+    fn test_detach_streaming_response() {
+        let path_to_socket = get_temp_socket_file();
+
+        let mut server = HttpServer::new(path_to_socket.as_path()).unwrap();
+        server.start_server().unwrap();
+
+        let mut socket = UnixStream::connect(path_to_socket.as_path()).unwrap();
+        assert!(server.requests().unwrap().is_empty());
+        socket.write_all(b"GET /events HTTP/1.1\r\n\r\n").unwrap();
+
+        let mut req_vec = server.requests().unwrap();
+        let server_response = req_vec.remove(0).process(|_request| {
+            let mut response = Response::new(Version::Http11, StatusCode::OK);
+            response.set_content_type(MediaType::EventStream);
+            response.set_streaming();
+            response
+        });
+        assert!(server_response.is_streaming());
+
+        let mut stream = server.detach(server_response).unwrap().unwrap();
+        assert!(server.connections.is_empty());
+        stream.write_all(b"data: 1\n\n").unwrap();
+        drop(stream);
+
+        let expected_response: &'static [u8] = b"HTTP/1.1 200 \r\n\
+            Server: Firecracker API\r\n\
+            Connection: close\r\n\
+            Content-Type: text/event-stream\r\n\r\n\
+            data: 1\n\n";
+        let mut buf = Vec::new();
+        socket.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf.as_slice(), expected_response);
+    }
+
+    #[test]
+    fn test_read_only_socket() {
+        let path_to_socket = get_temp_socket_file();
+        let path_to_read_only_socket = get_temp_socket_file();
+
+        let mut server = HttpServer::new(path_to_socket.as_path()).unwrap();
+        server
+            .add_read_only_socket(path_to_read_only_socket.as_path())
+            .unwrap();
+        server.start_server().unwrap();
+
+        let mut socket = UnixStream::connect(path_to_read_only_socket.as_path()).unwrap();
+        assert!(server.requests().unwrap().is_empty());
+        let peer_credentials = server.connections.values().next().unwrap().peer_credentials;
+        // Both ends belong to this process.
+        assert_eq!(
+            peer_credentials,
+            Some(PeerCredentials {
+                pid: std::process::id() as i32,
+                uid: unsafe { libc::geteuid() },
+                gid: unsafe { libc::getegid() },
+            })
+        );
+
+        // The requests are handed to the user, along with the client credentials, and marked
+        // as read-only.
+        socket
+            .write_all(
+                b"PUT /actions HTTP/1.1\r\n\
+                  Content-Length: 2\r\n\r\n{}",
+            )
+            .unwrap();
+        let mut req_vec = server.requests().unwrap();
+        let server_request = req_vec.remove(0);
+        assert_eq!(server_request.request.method(), Method::Put);
+        assert_eq!(server_request.peer_credentials(), peer_credentials);
+        assert!(server_request.is_read_only());
+        server
+            .respond(
+                server_request
+                    .process(|_request| Response::new(Version::Http11, StatusCode::Forbidden)),
+            )
+            .unwrap();
+        assert!(server.requests().unwrap().is_empty());
+        let mut buf: [u8; 1024] = [0; 1024];
+        assert!(socket.read(&mut buf[..]).unwrap() > 0);
+        assert!(buf.starts_with(b"HTTP/1.1 403"));
+
+        // The other socket still accepts any request.
+        let mut socket = UnixStream::connect(path_to_socket.as_path()).unwrap();
+        assert!(server.requests().unwrap().is_empty());
+        socket
+            .write_all(
+                b"PUT /actions HTTP/1.1\r\n\
+                  Content-Length: 2\r\n\r\n{}",
+            )
+            .unwrap();
+        let req_vec = server.requests().unwrap();
+        assert_eq!(req_vec[0].request.method(), Method::Put);
+        assert!(!req_vec[0].is_read_only());
+    }
+
+    #[test]
+    fn test_peer_filter() {
+        let path_to_socket = get_temp_socket_file();
+        let path_to_read_only_socket = get_temp_socket_file();
+
+        let mut server = HttpServer::new(path_to_socket.as_path()).unwrap();
+        server
+            .add_read_only_socket(path_to_read_only_socket.as_path())
+            .unwrap();
+        // Both ends belong to this process, so the group of this process is checked.
+        let gid = unsafe { libc::getegid() };
+        server.set_peer_filter(move |peer| peer.map_or(false, |peer| peer.gid != gid));
+        server.start_server().unwrap();
+
+        // Rejected clients are answered, then disconnected, on any socket.
+        let expected_response: &'static [u8] = b"HTTP/1.1 403\r\n\
+            Server: Firecracker API\r\n\
+            Connection: close\r\n\
+            Content-Length: 28\r\n\r\n\
+            { \"error\": \"Access denied\" }";
+        for path in &[path_to_socket.as_path(), path_to_read_only_socket.as_path()] {
+            let mut socket = UnixStream::connect(path).unwrap();
+            assert!(server.requests().unwrap().is_empty());
+            assert!(server.connections.is_empty());
+            let mut buf = vec![];
+            socket.read_to_end(&mut buf).unwrap();
+            assert_eq!(buf.as_slice(), expected_response);
+        }
+
+        // Rejected clients don't take up connection slots.
+        let mut sockets: Vec<UnixStream> = Vec::with_capacity(MAX_CONNECTIONS + 1);
+        for _ in 0..=MAX_CONNECTIONS {
+            sockets.push(UnixStream::connect(path_to_socket.as_path()).unwrap());
+            assert!(server.requests().unwrap().is_empty());
+        }
+        assert!(server.connections.is_empty());
+    }
 }
@@ -4,21 +4,34 @@
 use serde_json::Value;
 use std::fmt;
 
+use crate::token::{TokenAuthority, TokenMode};
+
+/// The default maximum size, in bytes, of the data store serialized as a JSON document.
+pub const DEFAULT_DATA_STORE_LIMIT: usize = 51200;
+
 /// The Mmds is the Microvm Metadata Service represented as an untyped json.
 #[derive(Clone)]
 pub struct Mmds {
     data_store: Value,
     is_initialized: bool,
+    token_authority: TokenAuthority,
+    // Maximum size, in bytes, of the data store serialized as a JSON document.
+    data_store_limit: usize,
+    // Incremented on every update of the data store, so that guests can detect changes.
+    version: u64,
 }
 
 /// MMDS possible outputs.
 pub enum OutputFormat {
     Json,
     Imds,
+    /// The raw contents of a string value.
+    OctetStream,
 }
 
 #[derive(Debug, PartialEq)]
 pub enum Error {
+    DataStoreLimitExceeded(usize),
     NotFound,
     NotInitialized,
     UnsupportedValueType,
@@ -27,6 +40,11 @@ pub enum Error {
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
+            Error::DataStoreLimitExceeded(limit) => write!(
+                f,
+                "The MMDS data store cannot exceed {} bytes in size.",
+                limit
+            ),
             Error::NotFound => write!(f, "The MMDS resource does not exist."),
             Error::NotInitialized => write!(f, "The MMDS data store is not initialized."),
             Error::UnsupportedValueType => write!(
@@ -42,6 +60,9 @@ impl Default for Mmds {
         Mmds {
             data_store: Value::default(),
             is_initialized: false,
+            token_authority: TokenAuthority::default(),
+            data_store_limit: DEFAULT_DATA_STORE_LIMIT,
+            version: 0,
         }
     }
 }
@@ -58,18 +79,77 @@ impl Mmds {
         }
     }
 
+    /// Whether the data store was ever populated through `put_data`.
+    pub fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+
+    /// Returns the authority generating and checking the session tokens.
+    pub fn token_authority(&self) -> &TokenAuthority {
+        &self.token_authority
+    }
+
+    pub fn token_mode(&self) -> TokenMode {
+        self.token_authority.mode()
+    }
+
+    pub fn set_token_mode(&mut self, mode: TokenMode) {
+        self.token_authority.set_mode(mode);
+    }
+
+    // Rejects data stores whose JSON serialization exceeds the size limit.
+    fn check_data_store_limit(&self, data: &Value) -> Result<(), Error> {
+        if data.to_string().len() > self.data_store_limit {
+            return Err(Error::DataStoreLimitExceeded(self.data_store_limit));
+        }
+        Ok(())
+    }
+
+    pub fn data_store_limit(&self) -> usize {
+        self.data_store_limit
+    }
+
+    /// Sets the maximum size, in bytes, of the data store serialized as a JSON document. The
+    /// limit is enforced on the subsequent updates only.
+    pub fn set_data_store_limit(&mut self, limit: usize) {
+        self.data_store_limit = limit;
+    }
+
+    /// Returns the version of the data store, which changes on every update.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
     pub fn put_data(&mut self, data: Value) -> Result<(), Error> {
-        self.data_store = data;
-        self.is_initialized = true;
+        self.check_data_store_limit(&data)?;
+        self.set_data(data, self.version.wrapping_add(1));
         Ok(())
     }
 
     pub fn patch_data(&mut self, patch_data: Value) -> Result<(), Error> {
         self.check_data_store_initialized()?;
-        super::json_patch(&mut self.data_store, &patch_data);
+        let mut data = self.data_store.clone();
+        super::json_patch(&mut data, &patch_data);
+        self.check_data_store_limit(&data)?;
+        self.set_data(data, self.version.wrapping_add(1));
         Ok(())
     }
 
+    // Replaces the data store contents, without checking the size limit.
+    pub(crate) fn set_data(&mut self, data: Value, version: u64) {
+        self.data_store = data;
+        self.is_initialized = true;
+        self.version = version;
+    }
+
+    /// Returns a copy of the whole data store contents.
+    pub fn data_store_value(&self) -> Value {
+        if self.data_store.is_null() {
+            return Value::Object(serde_json::Map::new());
+        }
+        self.data_store.clone()
+    }
+
     pub fn get_data_str(&self) -> String {
         if self.data_store.is_null() {
             return String::from("{}");
@@ -147,6 +227,8 @@ impl Mmds {
 
     /// Returns the subtree located at path. When the path corresponds to a leaf, it returns the value.
     /// Returns Error::NotFound when the path is invalid.
+    /// Only string values can be returned as octet streams, in which case the raw contents of
+    /// the string are returned.
     pub fn get_value(&self, path: String, format: OutputFormat) -> Result<String, Error> {
         // The pointer function splits the input by "/". With a trailing "/", pointer does not
         // know how to get the object.
@@ -160,6 +242,10 @@ impl Mmds {
             match format {
                 OutputFormat::Json => Ok(json.to_string()),
                 OutputFormat::Imds => Mmds::format_imds(json),
+                OutputFormat::OctetStream => json
+                    .as_str()
+                    .map(str::to_string)
+                    .ok_or(Error::UnsupportedValueType),
             }
         } else {
             Err(Error::NotFound)
@@ -174,6 +260,7 @@ mod tests {
     #[test]
     fn test_mmds() {
         let mut mmds = Mmds::default();
+        assert_eq!(mmds.data_store_value(), serde_json::json!({}));
 
         assert_eq!(
             mmds.check_data_store_initialized().unwrap_err().to_string(),
@@ -187,6 +274,10 @@ mod tests {
         assert!(mmds.check_data_store_initialized().is_ok());
 
         assert_eq!(mmds.get_data_str(), mmds_json);
+        assert_eq!(
+            mmds.data_store_value(),
+            serde_json::from_str::<Value>(mmds_json).unwrap()
+        );
 
         // update the user-data field add test that patch works as expected
         let patch_json = "{\"user-data\":\"10\"}";
@@ -302,6 +393,23 @@ mod tests {
                 .unwrap(),
             "+401234567"
         );
+        assert_eq!(
+            mmds.get_value("/phones/0/".to_string(), OutputFormat::OctetStream)
+                .unwrap(),
+            "+401234567"
+        );
+        assert_eq!(
+            mmds.get_value("/phones".to_string(), OutputFormat::OctetStream)
+                .err()
+                .unwrap(),
+            Error::UnsupportedValueType
+        );
+        assert_eq!(
+            mmds.get_value("/age".to_string(), OutputFormat::OctetStream)
+                .err()
+                .unwrap(),
+            Error::UnsupportedValueType
+        );
 
         // Retrieve a boolean.
         assert_eq!(
@@ -387,4 +495,48 @@ mod tests {
         let data_store: Value = serde_json::from_str(data).unwrap();
         assert!(mmds.patch_data(data_store).is_ok());
     }
+
+    #[test]
+    fn test_data_store_limit() {
+        let mut mmds = Mmds::default();
+        assert_eq!(mmds.data_store_limit(), DEFAULT_DATA_STORE_LIMIT);
+        mmds.set_data_store_limit(16);
+
+        // `{"key":"value"}` is 15 bytes long.
+        mmds.put_data(serde_json::json!({"key": "value"})).unwrap();
+        assert_eq!(
+            mmds.put_data(serde_json::json!({"key": "value1234"}))
+                .unwrap_err(),
+            Error::DataStoreLimitExceeded(16)
+        );
+        assert_eq!(
+            mmds.patch_data(serde_json::json!({"other": 1}))
+                .unwrap_err()
+                .to_string(),
+            "The MMDS data store cannot exceed 16 bytes in size."
+        );
+        // Rejected updates leave the data store untouched.
+        assert_eq!(mmds.get_data_str(), r#"{"key":"value"}"#);
+        assert_eq!(mmds.version(), 1);
+
+        mmds.patch_data(serde_json::json!({"key": "v"})).unwrap();
+        assert_eq!(mmds.get_data_str(), r#"{"key":"v"}"#);
+    }
+
+    #[test]
+    fn test_version() {
+        let mut mmds = Mmds::default();
+        assert_eq!(mmds.version(), 0);
+
+        assert!(mmds.patch_data(serde_json::json!({"a": 1})).is_err());
+        assert_eq!(mmds.version(), 0);
+
+        mmds.put_data(serde_json::json!({"a": 1})).unwrap();
+        assert_eq!(mmds.version(), 1);
+        mmds.patch_data(serde_json::json!({"b": 2})).unwrap();
+        assert_eq!(mmds.version(), 2);
+        // Every update counts, even when the contents don't change.
+        mmds.put_data(serde_json::json!({"a": 1, "b": 2})).unwrap();
+        assert_eq!(mmds.version(), 3);
+    }
 }
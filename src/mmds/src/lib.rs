@@ -4,26 +4,35 @@
 pub mod data_store;
 pub mod ns;
 pub mod persist;
+pub mod stream;
+pub mod token;
 
 use serde_json::{Map, Value};
-use std::sync::{Arc, Mutex};
+use std::sync::Mutex;
 
 use crate::data_store::{Error as MmdsError, Mmds, OutputFormat};
-use lazy_static::lazy_static;
+use crate::token::{
+    Error as TokenError, PATH_TO_TOKEN, X_FORWARDED_FOR_HEADER, X_METADATA_TOKEN_HEADER,
+    X_METADATA_TOKEN_TTL_SECONDS_HEADER,
+};
+use logger::{IncMetric, METRICS};
 use micro_http::{Body, MediaType, Method, Request, Response, StatusCode, Version};
 
-lazy_static! {
-    // A static reference to a global Mmds instance. We currently use this for ease of access during
-    // prototyping. We'll consider something like passing Arc<Mutex<Mmds>> references to the
-    // appropriate threads in the future.
-    pub static ref MMDS: Arc<Mutex<Mmds>> = Arc::new(Mutex::new(Mmds::default()));
-}
+/// Header carrying the data store version on the responses to metadata requests.
+pub const X_METADATA_VERSION_HEADER: &str = "X-metadata-version";
+/// Query parameter deferring the response to a metadata request until the data store version
+/// differs from the one given through the `version` parameter.
+pub const WAIT_FOR_CHANGE_PARAM: &str = "wait-for-change";
+/// Query parameter carrying the data store version last seen by the guest.
+pub const VERSION_PARAM: &str = "version";
 
 impl Into<OutputFormat> for MediaType {
     fn into(self) -> OutputFormat {
         match self {
-            MediaType::ApplicationJson => OutputFormat::Json,
+            // The data store isn't served as a stream, so it falls back to the default format.
+            MediaType::ApplicationJson | MediaType::EventStream => OutputFormat::Json,
             MediaType::PlainText => OutputFormat::Imds,
+            MediaType::OctetStream => OutputFormat::OctetStream,
         }
     }
 }
@@ -76,46 +85,135 @@ fn sanitize_uri(mut uri: String) -> String {
     uri
 }
 
-fn convert_to_response(request: Request) -> Response {
+// Parses the query of a metadata request. Returns the data store version the response has to
+// wait a change from, if any.
+fn parse_query(query: &str) -> Result<Option<u64>, String> {
+    let mut wait_for_change = false;
+    let mut version = None;
+    for param in query.split('&').filter(|param| !param.is_empty()) {
+        let mut name_value = param.splitn(2, '=');
+        match (name_value.next(), name_value.next()) {
+            (Some(WAIT_FOR_CHANGE_PARAM), None) | (Some(WAIT_FOR_CHANGE_PARAM), Some("true")) => {
+                wait_for_change = true
+            }
+            (Some(VERSION_PARAM), Some(value)) => {
+                version = Some(value.parse::<u64>().map_err(|_| {
+                    format!(
+                        "Invalid value for the `{}` query parameter: {}.",
+                        VERSION_PARAM, value
+                    )
+                })?)
+            }
+            _ => return Err(format!("Unsupported query parameter: {}.", param)),
+        }
+    }
+
+    match (wait_for_change, version) {
+        (true, Some(version)) => Ok(Some(version)),
+        (false, None) => Ok(None),
+        _ => Err(format!(
+            "The `{}` and `{}` query parameters must be used together.",
+            WAIT_FOR_CHANGE_PARAM, VERSION_PARAM
+        )),
+    }
+}
+
+// Builds the response to a guest request, served from the `mmds` data store. Returns `None`
+// when the response is deferred until the data store changes.
+fn convert_to_response(mmds: &Mutex<Mmds>, request: Request) -> Option<Response> {
     let uri = request.uri().get_abs_path();
     if uri.is_empty() {
-        return build_response(
+        return Some(build_response(
             request.http_version(),
             StatusCode::BadRequest,
             Body::new("Invalid URI.".to_string()),
-        );
+        ));
     }
 
-    if request.method() != Method::Get {
-        let mut response = build_response(
-            request.http_version(),
-            StatusCode::MethodNotAllowed,
-            Body::new("Not allowed HTTP method."),
-        );
-        response.allow_method(Method::Get);
-        return response;
-    }
+    let (path, query) = match uri.find('?') {
+        Some(index) => (&uri[..index], &uri[index + 1..]),
+        None => (uri, ""),
+    };
+    let wait_for_version = match parse_query(query) {
+        Ok(wait_for_version) => wait_for_version,
+        Err(msg) => {
+            return Some(build_response(
+                request.http_version(),
+                StatusCode::BadRequest,
+                Body::new(msg),
+            ))
+        }
+    };
 
     // The data store expects a strict json path, so we need to
     // sanitize the URI.
-    let json_pointer = sanitize_uri(uri.to_string());
+    let json_pointer = sanitize_uri(path.to_string());
+
+    match request.method() {
+        Method::Get => respond_to_get_request(mmds, &request, json_pointer, wait_for_version),
+        Method::Put if json_pointer == PATH_TO_TOKEN => {
+            Some(respond_to_put_token_request(mmds, &request))
+        }
+        _ => {
+            let mut response = build_response(
+                request.http_version(),
+                StatusCode::MethodNotAllowed,
+                Body::new("Not allowed HTTP method."),
+            );
+            if json_pointer == PATH_TO_TOKEN {
+                response.allow_method(Method::Put);
+            } else {
+                response.allow_method(Method::Get);
+            }
+            Some(response)
+        }
+    }
+}
 
+fn respond_to_get_request(
+    mmds: &Mutex<Mmds>,
+    request: &Request,
+    json_pointer: String,
+    wait_for_version: Option<u64>,
+) -> Option<Response> {
     // The lock can be held by one thread only, so it is safe to unwrap.
     // If another thread poisoned the lock, we abort the execution.
-    let response = MMDS
-        .lock()
-        .expect("Poisoned lock")
-        .get_value(json_pointer, request.headers.accept().into());
+    let mmds = mmds.lock().expect("Poisoned lock");
 
-    match response {
-        Ok(response_body) => build_response(
+    let token = request.headers.custom_entry(X_METADATA_TOKEN_HEADER);
+    if let Err(e) = mmds.token_authority().authorize(token) {
+        match e {
+            TokenError::ExpiredToken => METRICS.mmds.rx_expired_token.inc(),
+            TokenError::MissingToken => METRICS.mmds.rx_no_token.inc(),
+            _ => METRICS.mmds.rx_invalid_token.inc(),
+        }
+        return Some(build_response(
             request.http_version(),
-            StatusCode::OK,
-            Body::new(response_body),
-        ),
+            StatusCode::Unauthorized,
+            Body::new(e.to_string()),
+        ));
+    }
+
+    if wait_for_version == Some(mmds.version()) {
+        return None;
+    }
+
+    let response = match mmds.get_value(json_pointer, request.headers.accept().into()) {
+        Ok(response_body) => {
+            let mut response = build_response(
+                request.http_version(),
+                StatusCode::OK,
+                Body::new(response_body),
+            );
+            if request.headers.accept() == MediaType::OctetStream {
+                response.set_content_type(MediaType::OctetStream);
+            }
+            response.add_custom_header(X_METADATA_VERSION_HEADER, &mmds.version().to_string());
+            response
+        }
         Err(e) => match e {
             MmdsError::NotFound => {
-                let error_msg = format!("Resource not found: {}.", uri);
+                let error_msg = format!("Resource not found: {}.", request.uri().get_abs_path());
                 build_response(
                     request.http_version(),
                     StatusCode::NotFound,
@@ -127,14 +225,69 @@ fn convert_to_response(request: Request) -> Response {
                 StatusCode::NotImplemented,
                 Body::new(e.to_string()),
             ),
-            MmdsError::NotInitialized => unreachable!(),
+            MmdsError::NotInitialized | MmdsError::DataStoreLimitExceeded(_) => unreachable!(),
         },
+    };
+    Some(response)
+}
+
+fn respond_to_put_token_request(mmds: &Mutex<Mmds>, request: &Request) -> Response {
+    // Tokens must not be handed out to remote clients through a proxy running in the guest.
+    if request
+        .headers
+        .custom_entry(X_FORWARDED_FOR_HEADER)
+        .is_some()
+    {
+        return build_response(
+            request.http_version(),
+            StatusCode::BadRequest,
+            Body::new(format!(
+                "Invalid header. Reason: Unsupported header name. Key: {}.",
+                X_FORWARDED_FOR_HEADER
+            )),
+        );
+    }
+
+    let ttl_seconds = match request
+        .headers
+        .custom_entry(X_METADATA_TOKEN_TTL_SECONDS_HEADER)
+        .map(str::parse::<u32>)
+    {
+        Some(Ok(ttl_seconds)) => ttl_seconds,
+        Some(Err(_)) | None => {
+            return build_response(
+                request.http_version(),
+                StatusCode::BadRequest,
+                Body::new(format!(
+                    "Token time to live value not found. Use `{}` header to specify the \
+                     token's lifetime.",
+                    X_METADATA_TOKEN_TTL_SECONDS_HEADER
+                )),
+            )
+        }
+    };
+
+    match mmds
+        .lock()
+        .expect("Poisoned lock")
+        .token_authority()
+        .generate_token(ttl_seconds)
+    {
+        Ok(token) => build_response(request.http_version(), StatusCode::OK, Body::new(token)),
+        Err(e) => {
+            let status = match e {
+                TokenError::Randomness => StatusCode::InternalServerError,
+                _ => StatusCode::BadRequest,
+            };
+            build_response(request.http_version(), status, Body::new(e.to_string()))
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::token::TokenMode;
 
     #[test]
     fn test_sanitize_uri() {
@@ -171,7 +324,8 @@ mod tests {
                 "mobile": "+442345678"
             }
         }"#;
-        MMDS.lock()
+        let mmds = Mutex::new(Mmds::default());
+        mmds.lock()
             .unwrap()
             .put_data(serde_json::from_str(data).unwrap())
             .unwrap();
@@ -181,7 +335,7 @@ mod tests {
         let request = Request::try_from(request_bytes).unwrap();
         let mut expected_response = Response::new(Version::Http10, StatusCode::NotFound);
         expected_response.set_body(Body::new("Resource not found: /invalid.".to_string()));
-        let actual_response = convert_to_response(request);
+        let actual_response = convert_to_response(&mmds, request).unwrap();
         assert_eq!(actual_response, expected_response);
 
         // Test NotImplemented.
@@ -190,7 +344,7 @@ mod tests {
         let mut expected_response = Response::new(Version::Http11, StatusCode::NotImplemented);
         let body = "Cannot retrieve value. The value has an unsupported type.".to_string();
         expected_response.set_body(Body::new(body));
-        let actual_response = convert_to_response(request);
+        let actual_response = convert_to_response(&mmds, request).unwrap();
         assert_eq!(actual_response, expected_response);
 
         // Test not allowed HTTP Method.
@@ -202,7 +356,7 @@ mod tests {
                 Response::new(Version::Http10, StatusCode::MethodNotAllowed);
             expected_response.set_body(Body::new("Not allowed HTTP method.".to_string()));
             expected_response.allow_method(Method::Get);
-            let actual_response = convert_to_response(request);
+            let actual_response = convert_to_response(&mmds, request).unwrap();
             assert_eq!(actual_response, expected_response);
         }
 
@@ -211,7 +365,7 @@ mod tests {
         let request = Request::try_from(request_bytes).unwrap();
         let mut expected_response = Response::new(Version::Http10, StatusCode::BadRequest);
         expected_response.set_body(Body::new("Invalid URI.".to_string()));
-        let actual_response = convert_to_response(request);
+        let actual_response = convert_to_response(&mmds, request).unwrap();
         assert_eq!(actual_response, expected_response);
 
         // Test Ok path.
@@ -236,8 +390,192 @@ mod tests {
         .to_string();
         body.retain(|c| !c.is_whitespace());
         expected_response.set_body(Body::new(body));
-        let actual_response = convert_to_response(request);
+        expected_response.add_custom_header(X_METADATA_VERSION_HEADER, "1");
+        let actual_response = convert_to_response(&mmds, request).unwrap();
         assert_eq!(actual_response, expected_response);
+
+        // Test string values served as octet streams.
+        let request_bytes = b"GET /phones/mobile HTTP/1.1\r\n\
+                              Accept: application/octet-stream\r\n\r\n";
+        let request = Request::try_from(request_bytes).unwrap();
+        let mut expected_response = Response::new(Version::Http11, StatusCode::OK);
+        expected_response.set_body(Body::new("+442345678".to_string()));
+        expected_response.set_content_type(MediaType::OctetStream);
+        expected_response.add_custom_header(X_METADATA_VERSION_HEADER, "1");
+        assert_eq!(
+            convert_to_response(&mmds, request).unwrap(),
+            expected_response
+        );
+
+        let request_bytes = b"GET /phones HTTP/1.1\r\n\
+                              Accept: application/octet-stream\r\n\r\n";
+        let request = Request::try_from(request_bytes).unwrap();
+        assert_eq!(
+            convert_to_response(&mmds, request).unwrap().status(),
+            StatusCode::NotImplemented
+        );
+    }
+
+    #[test]
+    fn test_parse_query() {
+        assert_eq!(parse_query(""), Ok(None));
+        assert_eq!(parse_query("wait-for-change&version=3"), Ok(Some(3)));
+        assert_eq!(parse_query("version=3&wait-for-change=true"), Ok(Some(3)));
+        assert_eq!(
+            parse_query("wait-for-change").unwrap_err(),
+            "The `wait-for-change` and `version` query parameters must be used together."
+        );
+        assert!(parse_query("version=3").is_err());
+        assert_eq!(
+            parse_query("wait-for-change&version=abc").unwrap_err(),
+            "Invalid value for the `version` query parameter: abc."
+        );
+        assert_eq!(
+            parse_query("foo=bar").unwrap_err(),
+            "Unsupported query parameter: foo=bar."
+        );
+    }
+
+    #[test]
+    fn test_wait_for_change() {
+        let mmds = Mutex::new(Mmds::default());
+        mmds.lock()
+            .unwrap()
+            .put_data(serde_json::json!({"status": "initializing"}))
+            .unwrap();
+
+        // The current version is returned along with the data.
+        let request_bytes = b"GET /status HTTP/1.1\r\n\r\n";
+        let request = Request::try_from(request_bytes).unwrap();
+        let response = convert_to_response(&mmds, request).unwrap();
+        assert_eq!(response.custom_header(X_METADATA_VERSION_HEADER), Some("1"));
+
+        // The response is deferred while the data store version is the one known by the guest.
+        let request_bytes = b"GET /status?wait-for-change&version=1 HTTP/1.1\r\n\r\n";
+        let request = Request::try_from(request_bytes.as_ref()).unwrap();
+        assert!(convert_to_response(&mmds, request).is_none());
+
+        mmds.lock()
+            .unwrap()
+            .patch_data(serde_json::json!({"status": "ready"}))
+            .unwrap();
+        let request = Request::try_from(request_bytes.as_ref()).unwrap();
+        let response = convert_to_response(&mmds, request).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.body().unwrap(), Body::new("ready".to_string()));
+        assert_eq!(response.custom_header(X_METADATA_VERSION_HEADER), Some("2"));
+
+        // Unauthorized requests are not deferred.
+        mmds.lock().unwrap().set_token_mode(TokenMode::Required);
+        let request_bytes = b"GET /status?wait-for-change&version=2 HTTP/1.1\r\n\r\n";
+        let request = Request::try_from(request_bytes.as_ref()).unwrap();
+        assert_eq!(
+            convert_to_response(&mmds, request).unwrap().status(),
+            StatusCode::Unauthorized
+        );
+
+        // Invalid queries are rejected.
+        let request_bytes = b"GET /status?version=2 HTTP/1.1\r\n\r\n";
+        let request = Request::try_from(request_bytes.as_ref()).unwrap();
+        assert_eq!(
+            convert_to_response(&mmds, request).unwrap().status(),
+            StatusCode::BadRequest
+        );
+    }
+
+    #[test]
+    fn test_token_requests() {
+        let mmds = Mutex::new(Mmds::default());
+        let data = r#"{"latest": {"meta-data": {"ami-id": "ami-12345678"}}}"#;
+        mmds.lock()
+            .unwrap()
+            .put_data(serde_json::from_str(data).unwrap())
+            .unwrap();
+
+        // Test missing TTL.
+        let request_bytes = b"PUT http://169.254.169.254/latest/api/token HTTP/1.0\r\n\r\n";
+        let request = Request::try_from(request_bytes).unwrap();
+        let response = convert_to_response(&mmds, request).unwrap();
+        assert_eq!(response.status(), StatusCode::BadRequest);
+
+        // Test out of range TTL.
+        let request_bytes = b"PUT /latest/api/token HTTP/1.1\r\n\
+                              X-metadata-token-ttl-seconds: 0\r\n\r\n";
+        let request = Request::try_from(request_bytes).unwrap();
+        let mut expected_response = Response::new(Version::Http11, StatusCode::BadRequest);
+        expected_response.set_body(Body::new(TokenError::InvalidTtl(0).to_string()));
+        assert_eq!(
+            convert_to_response(&mmds, request).unwrap(),
+            expected_response
+        );
+
+        // Test token requests forwarded by a proxy.
+        let request_bytes = b"PUT /latest/api/token HTTP/1.1\r\n\
+                              X-metadata-token-ttl-seconds: 60\r\n\
+                              X-Forwarded-For: 203.0.113.1\r\n\r\n";
+        let request = Request::try_from(request_bytes).unwrap();
+        let response = convert_to_response(&mmds, request).unwrap();
+        assert_eq!(response.status(), StatusCode::BadRequest);
+
+        // Test not allowed HTTP method on the token resource.
+        let request_bytes = b"PATCH /latest/api/token HTTP/1.1\r\n\r\n";
+        let request = Request::try_from(request_bytes).unwrap();
+        let response = convert_to_response(&mmds, request).unwrap();
+        assert_eq!(response.status(), StatusCode::MethodNotAllowed);
+        assert_eq!(response.allow(), vec![Method::Put]);
+
+        // Test Ok path.
+        let request_bytes = b"PUT /latest/api/token HTTP/1.1\r\n\
+                              x-metadata-token-ttl-seconds: 60\r\n\r\n";
+        let request = Request::try_from(request_bytes).unwrap();
+        let response = convert_to_response(&mmds, request).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let token = String::from_utf8(response.body().unwrap().raw().to_vec()).unwrap();
+
+        // A valid token grants access to the data store.
+        let request_bytes_with_token = format!(
+            "GET /latest/meta-data HTTP/1.1\r\nX-metadata-token: {}\r\n\r\n",
+            token
+        );
+        let request = Request::try_from(request_bytes_with_token.as_bytes()).unwrap();
+        let mut expected_response = Response::new(Version::Http11, StatusCode::OK);
+        expected_response.set_body(Body::new("ami-id".to_string()));
+        expected_response.add_custom_header(X_METADATA_VERSION_HEADER, "1");
+        assert_eq!(
+            convert_to_response(&mmds, request).unwrap(),
+            expected_response
+        );
+
+        // An invalid token is rejected, even though tokens are optional.
+        let invalid_token_count = METRICS.mmds.rx_invalid_token.count();
+        let request_bytes = b"GET /latest/meta-data HTTP/1.1\r\n\
+                              X-metadata-token: invalid\r\n\r\n";
+        let request = Request::try_from(request_bytes).unwrap();
+        let mut expected_response = Response::new(Version::Http11, StatusCode::Unauthorized);
+        expected_response.set_body(Body::new(TokenError::InvalidToken.to_string()));
+        assert_eq!(
+            convert_to_response(&mmds, request).unwrap(),
+            expected_response
+        );
+        assert_eq!(
+            METRICS.mmds.rx_invalid_token.count(),
+            invalid_token_count + 1
+        );
+
+        // Requests without a token are rejected once tokens are required.
+        mmds.lock().unwrap().set_token_mode(TokenMode::Required);
+        let no_token_count = METRICS.mmds.rx_no_token.count();
+        let request_bytes = b"GET /latest/meta-data HTTP/1.1\r\n\r\n";
+        let request = Request::try_from(request_bytes).unwrap();
+        let response = convert_to_response(&mmds, request).unwrap();
+        assert_eq!(response.status(), StatusCode::Unauthorized);
+        assert_eq!(METRICS.mmds.rx_no_token.count(), no_token_count + 1);
+
+        let request = Request::try_from(request_bytes_with_token.as_bytes()).unwrap();
+        assert_eq!(
+            convert_to_response(&mmds, request).unwrap().status(),
+            StatusCode::OK
+        );
     }
 
     #[test]
@@ -5,27 +5,38 @@
 #![allow(missing_docs)]
 
 use std::convert::From;
-use std::net::Ipv4Addr;
+use std::net::{Ipv4Addr, Ipv6Addr};
 use std::num::NonZeroUsize;
 use std::result::Result;
+use std::sync::{Arc, Mutex};
 
 use dumbo::pdu::arp::{
     test_speculative_tpa, Error as ArpFrameError, EthIPv4ArpFrame, ETH_IPV4_FRAME_LEN,
 };
 use dumbo::pdu::ethernet::{
-    Error as EthernetFrameError, EthernetFrame, ETHERTYPE_ARP, ETHERTYPE_IPV4,
+    Error as EthernetFrameError, EthernetFrame, ETHERTYPE_ARP, ETHERTYPE_IPV4, ETHERTYPE_IPV6,
+};
+use dumbo::pdu::icmpv6::{
+    solicited_node_multicast_addr, Error as NdpMessageError, NdpNeighborMessage, FLAG_OVERRIDE,
+    FLAG_SOLICITED, NEIGHBOR_ADVERTISEMENT_LEN,
 };
 use dumbo::pdu::ipv4::{
     test_speculative_dst_addr, Error as IPv4PacketError, IPv4Packet, PROTOCOL_TCP,
 };
+use dumbo::pdu::ipv6::{
+    test_speculative_dst_addr as test_speculative_ipv6_dst_addr, Error as IPv6PacketError,
+    IPv6Packet, IPV6_VERSION, PROTOCOL_ICMPV6,
+};
 use dumbo::pdu::tcp::Error as TcpSegmentError;
 use dumbo::pdu::Incomplete;
-use dumbo::tcp::handler::{self, RecvEvent, TcpIPv4Handler, WriteEvent};
+use dumbo::tcp::handler::{self, RecvError, RecvEvent, TcpIPHandler, WriteEvent};
 use dumbo::tcp::NextSegmentStatus;
 use logger::{IncMetric, METRICS};
 use utils::net::mac::MacAddr;
 use utils::time::timestamp_cycles;
 
+use crate::data_store::Mmds;
+
 const DEFAULT_MAC_ADDR: &str = "06:01:23:45:67:01";
 const DEFAULT_IPV4_ADDR: [u8; 4] = [169, 254, 169, 254];
 const DEFAULT_TCP_PORT: u16 = 80;
@@ -39,9 +50,18 @@ enum WriteArpFrameError {
     Ethernet(EthernetFrameError),
 }
 
+#[cfg_attr(test, derive(Debug, PartialEq))]
+enum WriteNdpFrameError {
+    NoPendingNdpReply,
+    Ethernet(EthernetFrameError),
+    IPv6Packet(IPv6PacketError),
+    Ndp(NdpMessageError),
+}
+
 #[cfg_attr(test, derive(Debug, PartialEq))]
 enum WritePacketError {
     IPv4Packet(IPv4PacketError),
+    IPv6Packet(IPv6PacketError),
     Ethernet(EthernetFrameError),
     TcpSegment(TcpSegmentError),
 }
@@ -50,6 +70,7 @@ impl From<handler::WriteNextError> for WritePacketError {
     fn from(error: handler::WriteNextError) -> Self {
         match error {
             handler::WriteNextError::IPv4Packet(inner) => WritePacketError::IPv4Packet(inner),
+            handler::WriteNextError::IPv6Packet(inner) => WritePacketError::IPv6Packet(inner),
             handler::WriteNextError::TcpSegment(inner) => WritePacketError::TcpSegment(inner),
         }
     }
@@ -66,8 +87,14 @@ pub struct MmdsNetworkStack {
     // It is the Ipv4Addr of the network interface for which the MmdsNetworkStack
     // routes the packets.
     pending_arp_reply_dest: Option<Ipv4Addr>,
+    // MMDS server IPv6 link-local address, if the MMDS should also be reachable over IPv6.
+    pub(crate) ipv6_addr: Option<Ipv6Addr>,
+    // Neighbor Advertisement destination IPv6 address (source of the Neighbor Solicitation).
+    pending_ndp_reply_dest: Option<Ipv6Addr>,
     // This handles MMDS<->guest interaction at the TCP level.
-    pub(crate) tcp_handler: TcpIPv4Handler,
+    pub(crate) tcp_handler: TcpIPHandler,
+    // The data store serving the guest requests.
+    mmds: Arc<Mutex<Mmds>>,
 }
 
 impl MmdsNetworkStack {
@@ -77,22 +104,26 @@ impl MmdsNetworkStack {
         tcp_port: u16,
         max_connections: NonZeroUsize,
         max_pending_resets: NonZeroUsize,
+        mmds: Arc<Mutex<Mmds>>,
     ) -> Self {
         MmdsNetworkStack {
             remote_mac_addr: mac_addr,
             mac_addr,
             ipv4_addr,
             pending_arp_reply_dest: None,
-            tcp_handler: TcpIPv4Handler::new(
+            ipv6_addr: None,
+            pending_ndp_reply_dest: None,
+            tcp_handler: TcpIPHandler::new(
                 ipv4_addr,
                 tcp_port,
                 max_connections,
                 max_pending_resets,
             ),
+            mmds,
         }
     }
 
-    pub fn new_with_defaults(mmds_ipv4_addr: Option<Ipv4Addr>) -> Self {
+    pub fn new_with_defaults(mmds_ipv4_addr: Option<Ipv4Addr>, mmds: Arc<Mutex<Mmds>>) -> Self {
         // The unwrap is safe if parse_str() is implemented properly.
         let mac_addr = MacAddr::parse_str(DEFAULT_MAC_ADDR).unwrap();
         let ipv4_addr = mmds_ipv4_addr.unwrap_or_else(|| Ipv4Addr::from(DEFAULT_IPV4_ADDR));
@@ -104,14 +135,41 @@ impl MmdsNetworkStack {
             DEFAULT_TCP_PORT,
             NonZeroUsize::new(DEFAULT_MAX_CONNECTIONS).unwrap(),
             NonZeroUsize::new(DEFAULT_MAX_PENDING_RESETS).unwrap(),
+            mmds,
         )
     }
 
+    pub fn ipv4_addr(&self) -> Ipv4Addr {
+        self.ipv4_addr
+    }
+
     pub fn set_ipv4_addr(&mut self, ipv4_addr: Ipv4Addr) {
         self.ipv4_addr = ipv4_addr;
         self.tcp_handler.set_local_ipv4_addr(ipv4_addr);
     }
 
+    pub fn ipv6_addr(&self) -> Option<Ipv6Addr> {
+        self.ipv6_addr
+    }
+
+    /// Sets the IPv6 link-local address the MMDS answers on, or disables IPv6 access altogether
+    /// when `None`.
+    pub fn set_ipv6_addr(&mut self, ipv6_addr: Option<Ipv6Addr>) {
+        self.ipv6_addr = ipv6_addr;
+        self.pending_ndp_reply_dest = None;
+        self.tcp_handler.set_local_ipv6_addr(ipv6_addr);
+    }
+
+    /// Returns the data store serving the guest requests.
+    pub fn mmds(&self) -> &Arc<Mutex<Mmds>> {
+        &self.mmds
+    }
+
+    /// Replaces the data store serving the guest requests.
+    pub fn set_mmds(&mut self, mmds: Arc<Mutex<Mmds>>) {
+        self.mmds = mmds;
+    }
+
     pub fn default_ipv4_addr() -> Ipv4Addr {
         Ipv4Addr::from(DEFAULT_IPV4_ADDR)
     }
@@ -119,9 +177,13 @@ impl MmdsNetworkStack {
     // This is the entry point into the MMDS network stack. The src slice should hold the contents
     // of an Ethernet frame (of that exact size, without the CRC).
     pub fn detour_frame(&mut self, src: &[u8]) -> bool {
-        // The frame cannot possibly contain an ARP request or IPv4 packet for the MMDS.
+        // The frame cannot possibly contain an ARP request, IPv4 packet or IPv6 packet for the MMDS.
         if !test_speculative_tpa(src, self.ipv4_addr)
             && !test_speculative_dst_addr(src, self.ipv4_addr)
+            && !self.ipv6_addr.map_or(false, |addr| {
+                test_speculative_ipv6_dst_addr(src, addr)
+                    || test_speculative_ipv6_dst_addr(src, solicited_node_multicast_addr(addr))
+            })
         {
             return false;
         }
@@ -130,6 +192,7 @@ impl MmdsNetworkStack {
             match eth.ethertype() {
                 ETHERTYPE_ARP => return self.detour_arp(eth),
                 ETHERTYPE_IPV4 => return self.detour_ipv4(eth),
+                ETHERTYPE_IPV6 => return self.detour_ipv6(eth),
                 _ => (),
             };
         } else {
@@ -160,30 +223,63 @@ impl MmdsNetworkStack {
                 // Note-2: For every routed packet we will have a single source MAC address, because
                 // each MmdsNetworkStack routes packets for only one network device.
                 self.remote_mac_addr = eth.src_mac();
-                match self
+                let mmds = &self.mmds;
+                let result = self
                     .tcp_handler
-                    .receive_packet(&ip, super::convert_to_response)
-                {
-                    Ok(event) => {
-                        METRICS.mmds.rx_count.inc();
-                        match event {
-                            RecvEvent::NewConnectionSuccessful => {
-                                METRICS.mmds.connections_created.inc()
-                            }
-                            RecvEvent::NewConnectionReplacing => {
-                                METRICS.mmds.connections_created.inc();
-                                METRICS.mmds.connections_destroyed.inc();
-                            }
-                            RecvEvent::EndpointDone => {
-                                METRICS.mmds.connections_destroyed.inc();
-                            }
-                            _ => (),
-                        }
+                    .receive_packet(&ip, |request| super::convert_to_response(mmds, request));
+                Self::record_recv_result(result);
+            } else {
+                // A non-TCP IPv4 packet heading towards the MMDS; we consider it unusual.
+                METRICS.mmds.rx_accepted_unusual.inc();
+            }
+            return true;
+        }
+
+        false
+    }
+
+    fn detour_ipv6(&mut self, eth: EthernetFrame<&[u8]>) -> bool {
+        let ipv6_addr = match self.ipv6_addr {
+            Some(addr) => addr,
+            None => return false,
+        };
+
+        if let Ok(ip) = IPv6Packet::from_bytes(eth.payload()) {
+            let dst_addr = ip.destination_address();
+            if ip.next_header() == PROTOCOL_ICMPV6 {
+                // Neighbor Solicitations are sent either to the solicited-node multicast address
+                // or directly to the MMDS address (when refreshing a neighbor cache entry). The
+                // checksum is not verified, for the same reasons as TCP segments below.
+                if let Ok(ns) = NdpNeighborMessage::solicitation_from_bytes(ip.payload(), None) {
+                    // Solicitations from the unspecified address are used for Duplicate Address
+                    // Detection, and there's nothing to answer to them.
+                    if ns.target_address() == ipv6_addr
+                        && !ip.source_address().is_unspecified()
+                        && (dst_addr == ipv6_addr
+                            || dst_addr == solicited_node_multicast_addr(ipv6_addr))
+                    {
+                        self.remote_mac_addr = eth.src_mac();
+                        self.pending_ndp_reply_dest = Some(ip.source_address());
+                        return true;
                     }
-                    Err(_) => METRICS.mmds.rx_accepted_err.inc(),
                 }
+                return false;
+            }
+
+            if dst_addr != ipv6_addr {
+                return false;
+            }
+
+            if ip.next_header() == PROTOCOL_TCP {
+                // TODO: We skip verifying the checksum, just like for IPv4.
+                self.remote_mac_addr = eth.src_mac();
+                let mmds = &self.mmds;
+                let result = self
+                    .tcp_handler
+                    .receive_ipv6_packet(&ip, |request| super::convert_to_response(mmds, request));
+                Self::record_recv_result(result);
             } else {
-                // A non-TCP IPv4 packet heading towards the MMDS; we consider it unusual.
+                // A non-TCP IPv6 packet heading towards the MMDS; we consider it unusual.
                 METRICS.mmds.rx_accepted_unusual.inc();
             }
             return true;
@@ -192,12 +288,40 @@ impl MmdsNetworkStack {
         false
     }
 
+    fn record_recv_result(result: Result<RecvEvent, RecvError>) {
+        match result {
+            Ok(event) => {
+                METRICS.mmds.rx_count.inc();
+                match event {
+                    RecvEvent::NewConnectionSuccessful => METRICS.mmds.connections_created.inc(),
+                    RecvEvent::NewConnectionReplacing => {
+                        METRICS.mmds.connections_created.inc();
+                        METRICS.mmds.connections_destroyed.inc();
+                    }
+                    RecvEvent::EndpointDone => {
+                        METRICS.mmds.connections_destroyed.inc();
+                    }
+                    _ => (),
+                }
+            }
+            Err(_) => METRICS.mmds.rx_accepted_err.inc(),
+        }
+    }
+
+    // Gives the requests deferred until a data store change (`wait-for-change`) another chance
+    // to be answered. Should be called by the device model whenever the data store is updated.
+    pub fn process_pending_requests(&mut self) {
+        let mmds = &self.mmds;
+        self.tcp_handler
+            .process_pending_requests(|request| super::convert_to_response(mmds, request));
+    }
+
     // Allows the MMDS network stack to write a frame to the specified buffer. Will return:
     // - None, if the MMDS network stack has no frame to send at this point. The buffer can be
     // used for something else by the device model.
     // - Some(len), if a frame of the given length has been written to the specified buffer.
     pub fn write_next_frame(&mut self, buf: &mut [u8]) -> Option<NonZeroUsize> {
-        // We try to send ARP replies first.
+        // We try to send ARP and NDP replies first.
         if self.pending_arp_reply_dest.is_some() {
             return match self.write_arp_reply(buf) {
                 Ok(something) => {
@@ -210,6 +334,18 @@ impl MmdsNetworkStack {
                     None
                 }
             };
+        } else if self.pending_ndp_reply_dest.is_some() {
+            return match self.write_ndp_reply(buf) {
+                Ok(something) => {
+                    METRICS.mmds.tx_count.inc();
+                    self.pending_ndp_reply_dest = None;
+                    something
+                }
+                Err(_) => {
+                    METRICS.mmds.tx_errors.inc();
+                    None
+                }
+            };
         } else {
             let call_write = match self.tcp_handler.next_segment_status() {
                 NextSegmentStatus::Available => true,
@@ -270,7 +406,51 @@ impl MmdsNetworkStack {
         ))
     }
 
+    fn write_ndp_reply(&self, buf: &mut [u8]) -> Result<Option<NonZeroUsize>, WriteNdpFrameError> {
+        let ndp_reply_dest = self
+            .pending_ndp_reply_dest
+            .ok_or_else(|| WriteNdpFrameError::NoPendingNdpReply)?;
+        // A pending reply is only ever set while an IPv6 address is configured.
+        let ipv6_addr = self
+            .ipv6_addr
+            .ok_or_else(|| WriteNdpFrameError::NoPendingNdpReply)?;
+
+        let mut eth_unsized = self
+            .prepare_eth_unsized(buf, ETHERTYPE_IPV6)
+            .map_err(WriteNdpFrameError::Ethernet)?;
+
+        let packet_len = {
+            let mut packet = IPv6Packet::write_header(
+                eth_unsized.inner_mut().payload_mut(),
+                PROTOCOL_ICMPV6,
+                ipv6_addr,
+                ndp_reply_dest,
+            )
+            .map_err(WriteNdpFrameError::IPv6Packet)?;
+
+            NdpNeighborMessage::write_advertisement(
+                packet.inner_mut().payload_mut(),
+                FLAG_SOLICITED | FLAG_OVERRIDE,
+                ipv6_addr,
+                self.mac_addr,
+                ipv6_addr,
+                ndp_reply_dest,
+            )
+            .map_err(WriteNdpFrameError::Ndp)?;
+
+            packet
+                .with_payload_len_unchecked(NEIGHBOR_ADVERTISEMENT_LEN)
+                .len()
+        };
+
+        Ok(Some(
+            // The unwrap() is safe because packet_len > 0.
+            NonZeroUsize::new(eth_unsized.with_payload_len_unchecked(packet_len).len()).unwrap(),
+        ))
+    }
+
     fn write_packet(&mut self, buf: &mut [u8]) -> Result<Option<NonZeroUsize>, WritePacketError> {
+        // The ethertype is fixed up below, once the family of the written packet is known.
         let mut eth_unsized = self
             .prepare_eth_unsized(buf, ETHERTYPE_IPV4)
             .map_err(WritePacketError::Ethernet)?;
@@ -284,6 +464,10 @@ impl MmdsNetworkStack {
         }
 
         if let Some(packet_len) = maybe_len {
+            // The IP version is stored in the upper nibble of the first byte of both headers.
+            if eth_unsized.inner_mut().payload_mut()[0] >> 4 == IPV6_VERSION {
+                eth_unsized.inner_mut().set_ethertype(ETHERTYPE_IPV6);
+            }
             return Ok(Some(
                 // The unwrap() is safe because packet_len > 0.
                 NonZeroUsize::new(
@@ -309,6 +493,7 @@ mod tests {
     // We use LOCALHOST here because const new() is not stable yet, so just reuse this const, since
     // all we're interested in is having some address different from the MMDS one.
     const REMOTE_ADDR: Ipv4Addr = Ipv4Addr::LOCALHOST;
+    const REMOTE_IPV6_ADDR: Ipv6Addr = Ipv6Addr::LOCALHOST;
     const REMOTE_MAC_STR: &str = "11:11:11:22:22:22";
     const MMDS_PORT: u16 = 80;
     const REMOTE_PORT: u16 = 1235;
@@ -364,7 +549,11 @@ mod tests {
                     None,
                 )
                 .unwrap()
-                .finalize(REMOTE_PORT, MMDS_PORT, Some((REMOTE_ADDR, addr)))
+                .finalize(
+                    REMOTE_PORT,
+                    MMDS_PORT,
+                    Some((REMOTE_ADDR.into(), addr.into())),
+                )
                 .len();
 
                 packet.with_payload_len_unchecked(segment_len, true).len()
@@ -373,6 +562,77 @@ mod tests {
             eth_unsized.with_payload_len_unchecked(packet_len).len()
         }
 
+        fn write_incoming_ipv6_tcp_segment(
+            &self,
+            buf: &mut [u8],
+            addr: Ipv6Addr,
+            flags: TcpFlags,
+        ) -> usize {
+            let mut eth_unsized = self.prepare_eth_unsized(buf, ETHERTYPE_IPV6).unwrap();
+            let packet_len = {
+                let mut packet = IPv6Packet::write_header(
+                    eth_unsized.inner_mut().payload_mut(),
+                    PROTOCOL_TCP,
+                    REMOTE_IPV6_ADDR,
+                    addr,
+                )
+                .unwrap();
+
+                let segment_len = TcpSegment::write_incomplete_segment::<[u8]>(
+                    packet.inner_mut().payload_mut(),
+                    SEQ_NUMBER,
+                    1234,
+                    flags,
+                    10000,
+                    None,
+                    0,
+                    None,
+                )
+                .unwrap()
+                .finalize(
+                    REMOTE_PORT,
+                    MMDS_PORT,
+                    Some((REMOTE_IPV6_ADDR.into(), addr.into())),
+                )
+                .len();
+
+                packet.with_payload_len_unchecked(segment_len).len()
+            };
+
+            eth_unsized.with_payload_len_unchecked(packet_len).len()
+        }
+
+        fn write_neighbor_solicitation(&mut self, buf: &mut [u8], dst: Ipv6Addr) -> usize {
+            // Write an advertisement and then modify it into a solicitation.
+            let ipv6_addr = self.ipv6_addr.unwrap();
+            self.pending_ndp_reply_dest = Some(REMOTE_IPV6_ADDR);
+            let len = self.write_ndp_reply(buf).unwrap().unwrap().get();
+            self.pending_ndp_reply_dest = None;
+
+            let mut eth = EthernetFrame::from_bytes_unchecked(&mut buf[..len]);
+            eth.set_src_mac(MacAddr::parse_str(REMOTE_MAC_STR).unwrap());
+            let mut ip = IPv6Packet::from_bytes_unchecked(eth.payload_mut());
+            ip.set_source_address(REMOTE_IPV6_ADDR)
+                .set_destination_address(dst);
+
+            // Set the type to Neighbor Solicitation, while the target remains the MMDS address.
+            ip.payload_mut()[0] = dumbo::pdu::icmpv6::TYPE_NEIGHBOR_SOLICITATION;
+            assert_eq!(
+                NdpNeighborMessage::solicitation_from_bytes(ip.payload(), None)
+                    .unwrap()
+                    .target_address(),
+                ipv6_addr
+            );
+            len
+        }
+
+        fn next_frame_as_ipv6_packet<'a>(&mut self, buf: &'a mut [u8]) -> IPv6Packet<&'a [u8]> {
+            let len = self.write_next_frame(buf).unwrap().get();
+            let eth = EthernetFrame::from_bytes(&buf[..len]).unwrap();
+            assert_eq!(eth.ethertype(), ETHERTYPE_IPV6);
+            IPv6Packet::from_bytes(&buf[eth.payload_offset()..len]).unwrap()
+        }
+
         fn next_frame_as_ipv4_packet<'a>(&mut self, buf: &'a mut [u8]) -> IPv4Packet<&'a [u8]> {
             let len = self.write_next_frame(buf).unwrap().get();
             let eth = EthernetFrame::from_bytes(&buf[..len]).unwrap();
@@ -382,11 +642,11 @@ mod tests {
 
     #[test]
     fn test_ns_new_with_defaults() {
-        let ns = MmdsNetworkStack::new_with_defaults(None);
+        let ns = MmdsNetworkStack::new_with_defaults(None, Arc::default());
         assert_eq!(ns.mac_addr, MacAddr::parse_str(DEFAULT_MAC_ADDR).unwrap());
         assert_eq!(ns.ipv4_addr, Ipv4Addr::from(DEFAULT_IPV4_ADDR));
 
-        let ns = MmdsNetworkStack::new_with_defaults(Some(Ipv4Addr::LOCALHOST));
+        let ns = MmdsNetworkStack::new_with_defaults(Some(Ipv4Addr::LOCALHOST), Arc::default());
         assert_eq!(ns.mac_addr, MacAddr::parse_str(DEFAULT_MAC_ADDR).unwrap());
         assert_eq!(ns.ipv4_addr, Ipv4Addr::LOCALHOST);
     }
@@ -394,7 +654,7 @@ mod tests {
     #[test]
     #[allow(clippy::cognitive_complexity)]
     fn test_ns() {
-        let mut ns = MmdsNetworkStack::new_with_defaults(None);
+        let mut ns = MmdsNetworkStack::new_with_defaults(None, Arc::default());
         let mut buf = [0u8; 2000];
         let mut bad_buf = [0u8; 1];
 
@@ -473,7 +733,7 @@ mod tests {
 
             let s = TcpSegment::from_bytes(
                 ip.payload(),
-                Some((ip.source_address(), ip.destination_address())),
+                Some((ip.source_address().into(), ip.destination_address().into())),
             )
             .unwrap();
             assert_eq!(s.flags_after_ns(), TcpFlags::RST);
@@ -498,7 +758,7 @@ mod tests {
 
             let s = TcpSegment::from_bytes(
                 ip.payload(),
-                Some((ip.source_address(), ip.destination_address())),
+                Some((ip.source_address().into(), ip.destination_address().into())),
             )
             .unwrap();
             assert_eq!(s.flags_after_ns(), TcpFlags::SYN | TcpFlags::ACK);
@@ -511,9 +771,102 @@ mod tests {
         assert!(ns.write_next_frame(buf.as_mut()).is_none());
     }
 
+    #[test]
+    fn test_ns_ipv6() {
+        let mut ns = MmdsNetworkStack::new_with_defaults(None, Arc::default());
+        let mut buf = [0u8; 2000];
+        let mmds_addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0xa9fe, 0xa9fe);
+
+        // IPv6 is disabled by default, so nothing heading to the address is detoured.
+        ns.ipv6_addr = Some(mmds_addr);
+        let len = ns.write_neighbor_solicitation(buf.as_mut(), mmds_addr);
+        ns.ipv6_addr = None;
+        assert!(!ns.detour_frame(&buf[..len]));
+        let len = ns.write_incoming_ipv6_tcp_segment(buf.as_mut(), mmds_addr, TcpFlags::SYN);
+        assert!(!ns.detour_frame(&buf[..len]));
+        assert!(ns.write_next_frame(buf.as_mut()).is_none());
+
+        ns.set_ipv6_addr(Some(mmds_addr));
+        assert_eq!(ns.ipv6_addr(), Some(mmds_addr));
+        assert_eq!(ns.tcp_handler.local_ipv6_addr(), Some(mmds_addr));
+
+        // A solicitation sent to some other unicast address is ignored.
+        {
+            let other_addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0x1, 0xa9fe, 0xa9fe);
+            let len = ns.write_neighbor_solicitation(buf.as_mut(), other_addr);
+            assert!(!ns.detour_frame(&buf[..len]));
+        }
+
+        // Solicitations can be sent either to the solicited-node multicast address, or directly
+        // to the MMDS address.
+        for dst in [solicited_node_multicast_addr(mmds_addr), mmds_addr].iter() {
+            let len = ns.write_neighbor_solicitation(buf.as_mut(), *dst);
+            assert!(ns.detour_frame(&buf[..len]));
+            assert_eq!(
+                ns.remote_mac_addr,
+                MacAddr::parse_str(REMOTE_MAC_STR).unwrap()
+            );
+
+            let ip = ns.next_frame_as_ipv6_packet(buf.as_mut());
+            assert_eq!(ip.next_header(), PROTOCOL_ICMPV6);
+            assert_eq!(ip.hop_limit(), 255);
+            assert_eq!(ip.source_address(), mmds_addr);
+            assert_eq!(ip.destination_address(), REMOTE_IPV6_ADDR);
+
+            let na = NdpNeighborMessage::from_bytes_unchecked(ip.payload());
+            assert_eq!(
+                na.message_type(),
+                dumbo::pdu::icmpv6::TYPE_NEIGHBOR_ADVERTISEMENT
+            );
+            assert_eq!(na.flags(), FLAG_SOLICITED | FLAG_OVERRIDE);
+            assert_eq!(na.target_address(), mmds_addr);
+            assert_eq!(
+                na.compute_checksum(ip.source_address(), ip.destination_address()),
+                0
+            );
+
+            // Nothing else to send.
+            assert!(ns.write_next_frame(buf.as_mut()).is_none());
+        }
+
+        // A TCP SYN over IPv6 gets a SYNACK in response, also over IPv6.
+        {
+            let len = ns.write_incoming_ipv6_tcp_segment(buf.as_mut(), mmds_addr, TcpFlags::SYN);
+            assert!(ns.detour_frame(&buf[..len]));
+
+            let ip = ns.next_frame_as_ipv6_packet(buf.as_mut());
+            assert_eq!(ip.source_address(), mmds_addr);
+            assert_eq!(ip.destination_address(), REMOTE_IPV6_ADDR);
+
+            let s = TcpSegment::from_bytes(
+                ip.payload(),
+                Some((ip.source_address().into(), ip.destination_address().into())),
+            )
+            .unwrap();
+            assert_eq!(s.flags_after_ns(), TcpFlags::SYN | TcpFlags::ACK);
+            assert_eq!(s.source_port(), MMDS_PORT);
+            assert_eq!(s.destination_port(), REMOTE_PORT);
+            assert_eq!(s.ack_number(), SEQ_NUMBER.wrapping_add(1));
+        }
+
+        // Nothing else to send.
+        assert!(ns.write_next_frame(buf.as_mut()).is_none());
+    }
+
+    #[test]
+    fn test_set_mmds() {
+        let mmds = Arc::new(Mutex::new(Mmds::default()));
+        let mut ns = MmdsNetworkStack::new_with_defaults(None, mmds.clone());
+        assert!(Arc::ptr_eq(ns.mmds(), &mmds));
+
+        let other_mmds = Arc::new(Mutex::new(Mmds::default()));
+        ns.set_mmds(other_mmds.clone());
+        assert!(Arc::ptr_eq(ns.mmds(), &other_mmds));
+    }
+
     #[test]
     fn test_set_ipv4_addr() {
-        let mut ns = MmdsNetworkStack::new_with_defaults(None);
+        let mut ns = MmdsNetworkStack::new_with_defaults(None, Arc::default());
         assert_ne!(ns.ipv4_addr, Ipv4Addr::LOCALHOST);
         assert_ne!(ns.tcp_handler.local_ipv4_addr(), Ipv4Addr::LOCALHOST);
         ns.set_ipv4_addr(Ipv4Addr::LOCALHOST);
@@ -1,16 +1,20 @@
 // Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-//! Defines the structures needed for saving/restoring MmdsNetworkStack.
+//! Defines the structures needed for saving/restoring MmdsNetworkStack and the Mmds data store.
 
-use std::net::Ipv4Addr;
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::sync::{Arc, Mutex};
 
 use snapshot::Persist;
 use utils::net::mac::{MacAddr, MAC_ADDR_LEN};
 use versionize::{VersionMap, Versionize, VersionizeResult};
 use versionize_derive::Versionize;
 
+use super::data_store::{Error as MmdsError, Mmds, DEFAULT_DATA_STORE_LIMIT};
 use super::ns::MmdsNetworkStack;
+use super::token::TokenMode;
 
 /// State of a MmdsNetworkStack.
 #[derive(Clone, Versionize)]
@@ -21,11 +25,20 @@ pub struct MmdsNetworkStackState {
     tcp_port: u16,
     max_connections: usize,
     max_pending_resets: usize,
+    #[version(start = 2, default_fn = "def_ipv6_addr")]
+    ipv6_addr: Option<[u8; 16]>,
+}
+
+impl MmdsNetworkStackState {
+    fn def_ipv6_addr(_source_version: u16) -> Option<[u8; 16]> {
+        None
+    }
 }
 
 impl Persist<'_> for MmdsNetworkStack {
     type State = MmdsNetworkStackState;
-    type ConstructorArgs = ();
+    /// The data store serving the guest requests.
+    type ConstructorArgs = Arc<Mutex<Mmds>>;
     type Error = ();
 
     fn save(&self) -> Self::State {
@@ -38,20 +51,133 @@ impl Persist<'_> for MmdsNetworkStack {
             tcp_port: self.tcp_handler.local_port(),
             max_connections: self.tcp_handler.max_connections(),
             max_pending_resets: self.tcp_handler.max_pending_resets(),
+            ipv6_addr: self.ipv6_addr.map(|addr| addr.octets()),
         }
     }
 
     fn restore(
-        _: Self::ConstructorArgs,
+        mmds: Self::ConstructorArgs,
         state: &Self::State,
     ) -> std::result::Result<Self, Self::Error> {
-        Ok(MmdsNetworkStack::new(
+        let mut ns = MmdsNetworkStack::new(
             MacAddr::from_bytes_unchecked(&state.mac_addr),
             Ipv4Addr::from(state.ipv4_addr),
             state.tcp_port,
             std::num::NonZeroUsize::new(state.max_connections).unwrap(),
             std::num::NonZeroUsize::new(state.max_pending_resets).unwrap(),
-        ))
+            mmds,
+        );
+        ns.set_ipv6_addr(state.ipv6_addr.map(Ipv6Addr::from));
+        Ok(ns)
+    }
+}
+
+/// Persisted token mode of the Mmds.
+#[derive(Clone, Copy, Debug, PartialEq, Versionize)]
+// NOTICE: Any changes to this structure require a snapshot version bump.
+pub enum TokenModeState {
+    Optional,
+    Required,
+}
+
+impl From<TokenMode> for TokenModeState {
+    fn from(mode: TokenMode) -> Self {
+        match mode {
+            TokenMode::Optional => TokenModeState::Optional,
+            TokenMode::Required => TokenModeState::Required,
+        }
+    }
+}
+
+impl Into<TokenMode> for TokenModeState {
+    fn into(self) -> TokenMode {
+        match self {
+            TokenModeState::Optional => TokenMode::Optional,
+            TokenModeState::Required => TokenMode::Required,
+        }
+    }
+}
+
+/// State of the Mmds data store.
+#[derive(Clone, Versionize)]
+// NOTICE: Any changes to this structure require a snapshot version bump.
+pub struct MmdsState {
+    // The data store contents, as a JSON document.
+    data_store: String,
+    is_initialized: bool,
+    #[version(start = 2, default_fn = "def_token_mode")]
+    token_mode: TokenModeState,
+    #[version(start = 2, default_fn = "def_version")]
+    version: u64,
+    #[version(start = 2, default_fn = "def_data_store_limit")]
+    data_store_limit: usize,
+}
+
+impl MmdsState {
+    fn def_token_mode(_source_version: u16) -> TokenModeState {
+        TokenModeState::Optional
+    }
+
+    fn def_version(_source_version: u16) -> u64 {
+        0
+    }
+
+    fn def_data_store_limit(_source_version: u16) -> usize {
+        DEFAULT_DATA_STORE_LIMIT
+    }
+}
+
+/// Errors associated with restoring the Mmds data store.
+#[derive(Debug)]
+pub enum MmdsPersistError {
+    /// The saved data store is not a valid JSON document.
+    Deserialize(serde_json::Error),
+    /// The saved data store was rejected by the Mmds.
+    DataStore(MmdsError),
+}
+
+impl fmt::Display for MmdsPersistError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::MmdsPersistError::*;
+        match self {
+            Deserialize(e) => write!(f, "Cannot deserialize the data store: {}", e),
+            DataStore(e) => write!(f, "Cannot populate the data store: {}", e),
+        }
+    }
+}
+
+impl Persist<'_> for Mmds {
+    type State = MmdsState;
+    type ConstructorArgs = ();
+    type Error = MmdsPersistError;
+
+    fn save(&self) -> Self::State {
+        MmdsState {
+            data_store: self.get_data_str(),
+            is_initialized: self.is_initialized(),
+            token_mode: self.token_mode().into(),
+            version: self.version(),
+            data_store_limit: self.data_store_limit(),
+        }
+    }
+
+    fn restore(
+        _: Self::ConstructorArgs,
+        state: &Self::State,
+    ) -> std::result::Result<Self, Self::Error> {
+        // Tokens handed out before the snapshot are not valid after restoring it, since the
+        // token authority gets a new key.
+        let mut mmds = Mmds::default();
+        mmds.set_token_mode(state.token_mode.into());
+        mmds.set_data_store_limit(state.data_store_limit);
+        if state.is_initialized {
+            let data =
+                serde_json::from_str(&state.data_store).map_err(MmdsPersistError::Deserialize)?;
+            // The saved data store was accepted under the size limit in effect when it was
+            // populated, which may have been changed since.
+            mmds.set_data(data, state.version);
+        }
+        Ok(mmds)
     }
 }
 
@@ -61,7 +187,8 @@ mod tests {
 
     #[test]
     fn test_persistence() {
-        let ns = MmdsNetworkStack::new_with_defaults(None);
+        let mmds = Arc::new(Mutex::new(Mmds::default()));
+        let ns = MmdsNetworkStack::new_with_defaults(None, mmds.clone());
 
         let mut mem = vec![0; 4096];
         let version_map = VersionMap::new();
@@ -71,11 +198,12 @@ mod tests {
             .unwrap();
 
         let restored_ns = MmdsNetworkStack::restore(
-            (),
+            mmds.clone(),
             &MmdsNetworkStackState::deserialize(&mut mem.as_slice(), &version_map, 1).unwrap(),
         )
         .unwrap();
 
+        assert!(Arc::ptr_eq(restored_ns.mmds(), &mmds));
         assert_eq!(restored_ns.mac_addr, ns.mac_addr);
         assert_eq!(restored_ns.ipv4_addr, ns.ipv4_addr);
         assert_eq!(
@@ -91,4 +219,157 @@ mod tests {
             ns.tcp_handler.max_pending_resets()
         );
     }
+
+    #[test]
+    fn test_ipv6_addr_persistence() {
+        let mut ns = MmdsNetworkStack::new_with_defaults(None, Arc::default());
+        let ipv6_addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0xa9fe, 0xa9fe);
+        ns.set_ipv6_addr(Some(ipv6_addr));
+
+        let mut mem = vec![0; 4096];
+        let mut version_map = VersionMap::new();
+
+        // The IPv6 address is not saved in version 1 states.
+        ns.save()
+            .serialize(&mut mem.as_mut_slice(), &version_map, 1)
+            .unwrap();
+        let restored_ns = MmdsNetworkStack::restore(
+            Arc::default(),
+            &MmdsNetworkStackState::deserialize(&mut mem.as_slice(), &version_map, 1).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(restored_ns.ipv6_addr(), None);
+
+        version_map
+            .new_version()
+            .set_type_version(MmdsNetworkStackState::type_id(), 2);
+        ns.save()
+            .serialize(&mut mem.as_mut_slice(), &version_map, 2)
+            .unwrap();
+        let restored_ns = MmdsNetworkStack::restore(
+            Arc::default(),
+            &MmdsNetworkStackState::deserialize(&mut mem.as_slice(), &version_map, 2).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(restored_ns.ipv6_addr(), Some(ipv6_addr));
+        assert_eq!(restored_ns.tcp_handler.local_ipv6_addr(), Some(ipv6_addr));
+    }
+
+    #[test]
+    fn test_mmds_persistence() {
+        let mut mem = vec![0; 4096];
+        let version_map = VersionMap::new();
+
+        // An uninitialized data store stays uninitialized.
+        Mmds::default()
+            .save()
+            .serialize(&mut mem.as_mut_slice(), &version_map, 1)
+            .unwrap();
+        let restored_mmds = Mmds::restore(
+            (),
+            &MmdsState::deserialize(&mut mem.as_slice(), &version_map, 1).unwrap(),
+        )
+        .unwrap();
+        assert!(!restored_mmds.is_initialized());
+
+        let mut mmds = Mmds::default();
+        let data = r#"{"latest": {"meta-data": {"ami-id": "ami-12345678"}}}"#;
+        mmds.put_data(serde_json::from_str(data).unwrap()).unwrap();
+        mmds.save()
+            .serialize(&mut mem.as_mut_slice(), &version_map, 1)
+            .unwrap();
+        let restored_mmds = Mmds::restore(
+            (),
+            &MmdsState::deserialize(&mut mem.as_slice(), &version_map, 1).unwrap(),
+        )
+        .unwrap();
+        assert!(restored_mmds.is_initialized());
+        assert_eq!(restored_mmds.get_data_str(), mmds.get_data_str());
+
+        // A corrupted data store is rejected.
+        let state = MmdsState {
+            data_store: String::from("{"),
+            is_initialized: true,
+            token_mode: TokenModeState::Optional,
+            version: 0,
+            data_store_limit: DEFAULT_DATA_STORE_LIMIT,
+        };
+        let err = Mmds::restore((), &state).err().unwrap();
+        assert!(matches!(err, MmdsPersistError::Deserialize(_)));
+        let _ = format!("{}{:?}", err, err);
+    }
+
+    #[test]
+    fn test_mmds_token_mode_persistence() {
+        let mut mem = vec![0; 4096];
+        let mut version_map = VersionMap::new();
+
+        let mut mmds = Mmds::default();
+        mmds.set_token_mode(TokenMode::Required);
+
+        // The token mode is not saved in version 1 states.
+        mmds.save()
+            .serialize(&mut mem.as_mut_slice(), &version_map, 1)
+            .unwrap();
+        let restored_mmds = Mmds::restore(
+            (),
+            &MmdsState::deserialize(&mut mem.as_slice(), &version_map, 1).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(restored_mmds.token_mode(), TokenMode::Optional);
+
+        version_map
+            .new_version()
+            .set_type_version(MmdsState::type_id(), 2);
+        mmds.save()
+            .serialize(&mut mem.as_mut_slice(), &version_map, 2)
+            .unwrap();
+        let restored_mmds = Mmds::restore(
+            (),
+            &MmdsState::deserialize(&mut mem.as_slice(), &version_map, 2).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(restored_mmds.token_mode(), TokenMode::Required);
+        assert!(!restored_mmds.is_initialized());
+    }
+
+    #[test]
+    fn test_mmds_version_persistence() {
+        let mut mem = vec![0; 4096];
+        let mut version_map = VersionMap::new();
+
+        let mut mmds = Mmds::default();
+        mmds.put_data(serde_json::json!({"a": 1})).unwrap();
+        mmds.patch_data(serde_json::json!({"b": 2})).unwrap();
+        // The size limit does not apply to the restored data store.
+        mmds.set_data_store_limit(8);
+
+        // The version is not saved in version 1 states.
+        mmds.save()
+            .serialize(&mut mem.as_mut_slice(), &version_map, 1)
+            .unwrap();
+        let restored_mmds = Mmds::restore(
+            (),
+            &MmdsState::deserialize(&mut mem.as_slice(), &version_map, 1).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(restored_mmds.version(), 0);
+        assert_eq!(restored_mmds.data_store_limit(), DEFAULT_DATA_STORE_LIMIT);
+        assert_eq!(restored_mmds.get_data_str(), mmds.get_data_str());
+
+        version_map
+            .new_version()
+            .set_type_version(MmdsState::type_id(), 2);
+        mmds.save()
+            .serialize(&mut mem.as_mut_slice(), &version_map, 2)
+            .unwrap();
+        let restored_mmds = Mmds::restore(
+            (),
+            &MmdsState::deserialize(&mut mem.as_slice(), &version_map, 2).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(restored_mmds.version(), 2);
+        assert_eq!(restored_mmds.data_store_limit(), 8);
+        assert_eq!(restored_mmds.get_data_str(), mmds.get_data_str());
+    }
 }
@@ -0,0 +1,165 @@
+// Copyright 2021 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Serves guest requests received over a byte stream (e.g. a vsock connection), for guests
+//! which reach the MMDS without going through a network interface.
+
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+
+use logger::{IncMetric, METRICS};
+use micro_http::{ConnectionError, HttpConnection, Request};
+
+use crate::data_store::Mmds;
+
+/// An HTTP connection to the MMDS, carried over a non-blocking stream.
+pub struct MmdsStream<T> {
+    conn: HttpConnection<T>,
+    // Requests which have yet to be answered, in the order they were received. Responses must
+    // be sent in the same order, so requests queued behind a deferred (`wait-for-change`) one
+    // are deferred as well.
+    pending_requests: VecDeque<Request>,
+    // The data store serving the guest requests.
+    mmds: Arc<Mutex<Mmds>>,
+}
+
+impl<T: Read + Write> MmdsStream<T> {
+    /// Creates a new connection, serving the requests received on `stream` from `mmds`.
+    pub fn new(stream: T, mmds: Arc<Mutex<Mmds>>) -> Self {
+        MmdsStream {
+            conn: HttpConnection::new(stream),
+            pending_requests: VecDeque::new(),
+            mmds,
+        }
+    }
+
+    /// Returns the data store serving the guest requests.
+    pub fn mmds(&self) -> &Arc<Mutex<Mmds>> {
+        &self.mmds
+    }
+
+    /// Reads the available bytes from the stream, and answers the requests which are now
+    /// complete. Should be called whenever the stream becomes readable.
+    ///
+    /// An error means the connection can no longer be used, and must be dropped.
+    pub fn try_read(&mut self) -> Result<(), ConnectionError> {
+        match self.conn.try_read() {
+            Err(ConnectionError::StreamError(ref err))
+                if err.kind() == std::io::ErrorKind::WouldBlock => {}
+            result => result?,
+        }
+        while let Some(request) = self.conn.pop_parsed_request() {
+            METRICS.mmds.rx_count.inc();
+            self.pending_requests.push_back(request);
+        }
+        self.process_pending_requests();
+        self.try_write()
+    }
+
+    /// Writes the pending responses, or as much of them as the stream accepts. Should be
+    /// called whenever the stream becomes writable, while `pending_write()` holds.
+    ///
+    /// An error means the connection can no longer be used, and must be dropped.
+    pub fn try_write(&mut self) -> Result<(), ConnectionError> {
+        if self.conn.pending_write() {
+            self.conn.try_write().map_err(|err| {
+                METRICS.mmds.tx_errors.inc();
+                err
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Gives the requests deferred until a data store change (`wait-for-change`) another
+    /// chance to be answered. Should be called whenever the data store is updated, followed
+    /// by `try_write()`.
+    pub fn process_pending_requests(&mut self) {
+        while let Some(request) = self.pending_requests.pop_front() {
+            match super::convert_to_response(&self.mmds, request.clone()) {
+                Some(response) => {
+                    METRICS.mmds.tx_count.inc();
+                    self.conn.enqueue_response(response);
+                }
+                None => {
+                    self.pending_requests.push_front(request);
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Returns `true` if there are responses waiting to be written to the stream.
+    pub fn pending_write(&self) -> bool {
+        self.conn.pending_write()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::UnixStream;
+
+    use super::*;
+
+    fn read_response(peer: &mut UnixStream) -> String {
+        let mut buf = [0u8; 1024];
+        let len = peer.read(&mut buf).unwrap();
+        String::from_utf8(buf[..len].to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_mmds_stream() {
+        let mmds = Arc::new(Mutex::new(Mmds::default()));
+        mmds.lock()
+            .unwrap()
+            .put_data(serde_json::json!({"name": "mmds"}))
+            .unwrap();
+
+        let (stream, mut peer) = UnixStream::pair().unwrap();
+        stream.set_nonblocking(true).unwrap();
+        let mut mmds_stream = MmdsStream::new(stream, mmds.clone());
+        assert!(Arc::ptr_eq(mmds_stream.mmds(), &mmds));
+
+        // Nothing to read yet.
+        mmds_stream.try_read().unwrap();
+        assert!(!mmds_stream.pending_write());
+
+        peer.write_all(b"GET /name HTTP/1.1\r\nAccept: text/plain\r\n\r\n")
+            .unwrap();
+        mmds_stream.try_read().unwrap();
+        assert!(!mmds_stream.pending_write());
+        let response = read_response(&mut peer);
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("mmds"));
+
+        // A request waiting for a change holds back the ones behind it.
+        let version = mmds.lock().unwrap().version();
+        peer.write_all(
+            format!(
+                "GET /name?wait-for-change&version={} HTTP/1.1\r\n\r\nGET /name HTTP/1.1\r\n\r\n",
+                version
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        mmds_stream.try_read().unwrap();
+        assert!(!mmds_stream.pending_write());
+        assert_eq!(mmds_stream.pending_requests.len(), 2);
+
+        mmds.lock()
+            .unwrap()
+            .put_data(serde_json::json!({"name": "new"}))
+            .unwrap();
+        mmds_stream.process_pending_requests();
+        assert!(mmds_stream.pending_requests.is_empty());
+        while mmds_stream.pending_write() {
+            mmds_stream.try_write().unwrap();
+        }
+        let response = read_response(&mut peer);
+        assert_eq!(response.matches("HTTP/1.1 200").count(), 2);
+
+        // The connection is dropped once the peer goes away.
+        drop(peer);
+        assert!(mmds_stream.try_read().is_err());
+    }
+}
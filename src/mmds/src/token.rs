@@ -0,0 +1,390 @@
+// Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Session tokens guarding the guest access to the MMDS, in the style of IMDSv2.
+//!
+//! A token is obtained through `PUT /latest/api/token`, specifying its lifetime through the
+//! `X-metadata-token-ttl-seconds` header, and is presented on subsequent requests through the
+//! `X-metadata-token` header. Tokens are not stored: each one carries its expiry time, a random
+//! nonce and an HMAC-SHA256 tag computed over both, whose key is randomly generated and never
+//! leaves the Firecracker process.
+
+use std::fmt;
+use std::io;
+
+use hmac::{Hmac, Mac};
+use logger::error;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use utils::time::{get_time_us, ClockType};
+
+/// Path of the token generation resource.
+pub const PATH_TO_TOKEN: &str = "/latest/api/token";
+/// Header carrying the session token on metadata requests.
+pub const X_METADATA_TOKEN_HEADER: &str = "X-metadata-token";
+/// Header carrying the requested token lifetime, in seconds, on token requests.
+pub const X_METADATA_TOKEN_TTL_SECONDS_HEADER: &str = "X-metadata-token-ttl-seconds";
+/// Header set by proxies. Token requests carrying it are rejected, so that tokens can't be
+/// obtained on behalf of remote clients.
+pub const X_FORWARDED_FOR_HEADER: &str = "X-Forwarded-For";
+/// Minimum token lifetime, in seconds.
+pub const MIN_TOKEN_TTL_SECONDS: u32 = 1;
+/// Maximum token lifetime, in seconds.
+pub const MAX_TOKEN_TTL_SECONDS: u32 = 21600;
+
+// Length, in bytes, of the HMAC key.
+const KEY_LEN: usize = 32;
+// Lengths, in bytes, of the token fields: the expiry time, the nonce and the tag.
+const EXPIRY_LEN: usize = 8;
+const NONCE_LEN: usize = 16;
+const TAG_LEN: usize = 32;
+// Length, in bytes, of a token once decoded from hex.
+const TOKEN_LEN: usize = EXPIRY_LEN + NONCE_LEN + TAG_LEN;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Whether metadata requests must present a session token.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenMode {
+    /// Requests without a token are served. Requests presenting a token are only served if
+    /// the token is valid.
+    Optional,
+    /// Only requests presenting a valid token are served.
+    Required,
+}
+
+impl Default for TokenMode {
+    fn default() -> Self {
+        TokenMode::Optional
+    }
+}
+
+/// Errors associated with session tokens.
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// The token has expired.
+    ExpiredToken,
+    /// The token is malformed or was not generated by this MMDS.
+    InvalidToken,
+    /// The requested token lifetime is out of range.
+    InvalidTtl(u32),
+    /// The request carries no token, but one is required.
+    MissingToken,
+    /// No randomness could be obtained to generate the token.
+    Randomness,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::ExpiredToken => write!(f, "The MMDS token has expired."),
+            Error::InvalidToken => write!(f, "The MMDS token is not valid."),
+            Error::InvalidTtl(ttl) => write!(
+                f,
+                "Invalid token TTL: {}. The TTL must be between {} and {} seconds.",
+                ttl, MIN_TOKEN_TTL_SECONDS, MAX_TOKEN_TTL_SECONDS
+            ),
+            Error::MissingToken => write!(f, "No MMDS token provided."),
+            Error::Randomness => write!(f, "Cannot generate an MMDS token."),
+        }
+    }
+}
+
+/// Generates and checks session tokens.
+#[derive(Clone, Debug)]
+pub struct TokenAuthority {
+    // Missing if no randomness could be obtained, in which case no token is valid.
+    key: Option<[u8; KEY_LEN]>,
+    mode: TokenMode,
+}
+
+impl Default for TokenAuthority {
+    fn default() -> Self {
+        TokenAuthority::new(TokenMode::default())
+    }
+}
+
+impl TokenAuthority {
+    /// Creates a token authority with a freshly generated key, which it keeps for its whole
+    /// lifetime.
+    pub fn new(mode: TokenMode) -> Self {
+        let mut key = [0u8; KEY_LEN];
+        let key = match fill_random(&mut key) {
+            Ok(()) => Some(key),
+            Err(e) => {
+                error!("Cannot generate the MMDS token key: {}", e);
+                None
+            }
+        };
+        TokenAuthority { key, mode }
+    }
+
+    pub fn mode(&self) -> TokenMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: TokenMode) {
+        self.mode = mode;
+    }
+
+    /// Generates a token valid for `ttl_seconds`.
+    pub fn generate_token(&self, ttl_seconds: u32) -> Result<String, Error> {
+        self.generate_token_at(now_ms(), ttl_seconds)
+    }
+
+    /// Checks whether a request carrying `token` may be served, according to the mode.
+    pub fn authorize(&self, token: Option<&str>) -> Result<(), Error> {
+        match token {
+            Some(token) => self.check_token_at(token, now_ms()),
+            None if self.mode == TokenMode::Required => Err(Error::MissingToken),
+            None => Ok(()),
+        }
+    }
+
+    fn generate_token_at(&self, now_ms: u64, ttl_seconds: u32) -> Result<String, Error> {
+        if ttl_seconds < MIN_TOKEN_TTL_SECONDS || ttl_seconds > MAX_TOKEN_TTL_SECONDS {
+            return Err(Error::InvalidTtl(ttl_seconds));
+        }
+        let expiry_ms = now_ms + u64::from(ttl_seconds) * 1000;
+
+        let mut token = [0u8; TOKEN_LEN];
+        token[..EXPIRY_LEN].copy_from_slice(&expiry_ms.to_be_bytes());
+        fill_random(&mut token[EXPIRY_LEN..EXPIRY_LEN + NONCE_LEN]).map_err(|e| {
+            error!("Cannot generate the MMDS token nonce: {}", e);
+            Error::Randomness
+        })?;
+        let tag = self
+            .mac(&token[..EXPIRY_LEN + NONCE_LEN])
+            .ok_or(Error::Randomness)?
+            .finalize()
+            .into_bytes();
+        token[EXPIRY_LEN + NONCE_LEN..].copy_from_slice(&tag);
+        Ok(token.iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+
+    fn check_token_at(&self, token: &str, now_ms: u64) -> Result<(), Error> {
+        let token = decode_hex(token).ok_or(Error::InvalidToken)?;
+        if token.len() != TOKEN_LEN {
+            return Err(Error::InvalidToken);
+        }
+        let (payload, tag) = token.split_at(EXPIRY_LEN + NONCE_LEN);
+        // The comparison takes the same time wherever the tags differ.
+        self.mac(payload)
+            .ok_or(Error::InvalidToken)?
+            .verify_slice(tag)
+            .map_err(|_| Error::InvalidToken)?;
+
+        let mut expiry = [0u8; EXPIRY_LEN];
+        expiry.copy_from_slice(&payload[..EXPIRY_LEN]);
+        if u64::from_be_bytes(expiry) <= now_ms {
+            return Err(Error::ExpiredToken);
+        }
+        Ok(())
+    }
+
+    // Returns the MAC state after feeding it `payload`, if the authority has a key.
+    fn mac(&self, payload: &[u8]) -> Option<HmacSha256> {
+        let mut mac = HmacSha256::new_from_slice(self.key.as_ref()?).ok()?;
+        mac.update(payload);
+        Some(mac)
+    }
+}
+
+fn now_ms() -> u64 {
+    get_time_us(ClockType::Monotonic) / 1000
+}
+
+// Fills `buf` with random bytes from the kernel's CSPRNG, through getrandom(2), which doesn't
+// need to open any file, unlike reading /dev/urandom.
+fn fill_random(buf: &mut [u8]) -> io::Result<()> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        let remaining = &mut buf[filled..];
+        // This is safe since the kernel writes at most `remaining.len()` bytes to `remaining`,
+        // and we check the return value.
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_getrandom,
+                remaining.as_mut_ptr(),
+                remaining.len(),
+                0,
+            )
+        };
+        if ret < 0 {
+            let e = io::Error::last_os_error();
+            if e.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(e);
+        }
+        filled += ret as usize;
+    }
+    Ok(())
+}
+
+// Decodes a string of lowercase or uppercase hex digits, two per byte.
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_token() {
+        let authority = TokenAuthority::default();
+        assert_eq!(authority.mode(), TokenMode::Optional);
+
+        assert_eq!(
+            authority.generate_token(0).unwrap_err(),
+            Error::InvalidTtl(0)
+        );
+        assert_eq!(
+            authority
+                .generate_token(MAX_TOKEN_TTL_SECONDS + 1)
+                .unwrap_err()
+                .to_string(),
+            "Invalid token TTL: 21601. The TTL must be between 1 and 21600 seconds."
+        );
+
+        let token = authority.generate_token(MAX_TOKEN_TTL_SECONDS).unwrap();
+        assert_eq!(token.len(), 2 * TOKEN_LEN);
+        assert!(authority.authorize(Some(&token)).is_ok());
+
+        // Tokens with the same expiry time differ by their nonce.
+        assert_ne!(
+            authority.generate_token_at(1000, 1).unwrap(),
+            authority.generate_token_at(1000, 1).unwrap()
+        );
+
+        // Tokens are only valid for the authority that generated them.
+        let other = TokenAuthority::default();
+        assert_eq!(
+            other.authorize(Some(&token)).unwrap_err(),
+            Error::InvalidToken
+        );
+    }
+
+    #[test]
+    fn test_check_token() {
+        let authority = TokenAuthority::default();
+        let token = authority.generate_token_at(1000, 1).unwrap();
+
+        assert!(authority.check_token_at(&token, 1999).is_ok());
+        assert_eq!(
+            authority.check_token_at(&token, 2000).unwrap_err(),
+            Error::ExpiredToken
+        );
+
+        // Extending the expiry time invalidates the tag.
+        let forged = format!("{:016x}{}", 10_000, &token[2 * EXPIRY_LEN..]);
+        assert_eq!(
+            authority.check_token_at(&forged, 1000).unwrap_err(),
+            Error::InvalidToken
+        );
+        // So does changing the nonce.
+        let mut forged = token.clone().into_bytes();
+        forged[2 * EXPIRY_LEN] = if forged[2 * EXPIRY_LEN] == b'0' {
+            b'1'
+        } else {
+            b'0'
+        };
+        assert_eq!(
+            authority
+                .check_token_at(std::str::from_utf8(&forged).unwrap(), 1000)
+                .unwrap_err(),
+            Error::InvalidToken
+        );
+
+        for malformed in &[
+            "",
+            "0123",
+            &token[1..],
+            &token[2..],
+            "z".repeat(2 * TOKEN_LEN).as_str(),
+        ] {
+            assert_eq!(
+                authority.check_token_at(malformed, 1000).unwrap_err(),
+                Error::InvalidToken
+            );
+        }
+    }
+
+    #[test]
+    fn test_authorize() {
+        let mut authority = TokenAuthority::new(TokenMode::Required);
+        let token = authority.generate_token(1).unwrap();
+
+        assert_eq!(authority.authorize(None).unwrap_err(), Error::MissingToken);
+        assert!(authority.authorize(Some(&token)).is_ok());
+        assert_eq!(
+            authority.authorize(Some("invalid")).unwrap_err(),
+            Error::InvalidToken
+        );
+
+        authority.set_mode(TokenMode::Optional);
+        assert!(authority.authorize(None).is_ok());
+        // Tokens are still checked when presented.
+        assert_eq!(
+            authority.authorize(Some("invalid")).unwrap_err(),
+            Error::InvalidToken
+        );
+
+        // Without a key, no token is handed out or accepted.
+        let keyless = TokenAuthority {
+            key: None,
+            mode: TokenMode::Optional,
+        };
+        assert_eq!(keyless.generate_token(1).unwrap_err(), Error::Randomness);
+        assert_eq!(
+            keyless.authorize(Some(&token)).unwrap_err(),
+            Error::InvalidToken
+        );
+        assert!(keyless.authorize(None).is_ok());
+    }
+
+    #[test]
+    fn test_hmac_sha256() {
+        // RFC 4231, test case 2.
+        let mut mac = HmacSha256::new_from_slice(b"Jefe").unwrap();
+        mac.update(b"what do ya want for nothing?");
+        assert_eq!(
+            mac.finalize()
+                .into_bytes()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<String>(),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_fill_random() {
+        // Large buffers take several getrandom(2) calls.
+        let mut first = vec![0u8; 1 << 20];
+        let mut second = vec![0u8; 1 << 20];
+        fill_random(&mut first).unwrap();
+        fill_random(&mut second).unwrap();
+        assert_ne!(first, second);
+        assert!(first.iter().any(|byte| *byte != 0));
+        fill_random(&mut []).unwrap();
+    }
+
+    #[test]
+    fn test_decode_hex() {
+        assert_eq!(decode_hex(""), Some(vec![]));
+        assert_eq!(decode_hex("00ff7A"), Some(vec![0x00, 0xff, 0x7a]));
+        assert_eq!(decode_hex("0"), None);
+        assert_eq!(decode_hex("0g"), None);
+        assert_eq!(decode_hex("+f"), None);
+        assert_eq!(decode_hex("\u{e9}"), None);
+    }
+}
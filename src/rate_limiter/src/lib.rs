@@ -96,6 +96,8 @@ pub struct TokenBucket {
     size: u64,
     // Initial burst size (number of free initial tokens, that can be consumed at no cost)
     one_time_burst: u64,
+    // The burst size the bucket was created with, kept for reporting the configuration.
+    initial_one_time_burst: u64,
     // Complete refill time in milliseconds.
     refill_time: u64,
 
@@ -137,6 +139,7 @@ impl TokenBucket {
         Some(TokenBucket {
             size,
             one_time_burst,
+            initial_one_time_burst: one_time_burst,
             refill_time: complete_refill_time_ms,
             // Start off full.
             budget: size,
@@ -225,6 +228,11 @@ impl TokenBucket {
         self.one_time_burst
     }
 
+    /// Returns the one time burst the bucket was created with.
+    pub fn initial_one_time_burst(&self) -> u64 {
+        self.initial_one_time_burst
+    }
+
     /// Returns the time in milliseconds required to to completely fill the bucket.
     pub fn refill_time_ms(&self) -> u64 {
         self.refill_time
@@ -596,6 +604,7 @@ pub(crate) mod tests {
         assert_eq!(tb.one_time_burst(), 100);
         assert_eq!(tb.reduce(500), BucketReduction::Success);
         assert_eq!(tb.one_time_burst(), 0);
+        assert_eq!(tb.initial_one_time_burst(), 1100);
         assert_eq!(tb.reduce(500), BucketReduction::Success);
         assert_eq!(tb.reduce(500), BucketReduction::Failure);
         thread::sleep(Duration::from_millis(500));
@@ -0,0 +1,52 @@
+// Copyright 2021 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::net::Ipv6Addr;
+
+/// Checks if an IPv6 address is a unicast link-local address (`fe80::/10`), as per RFC 4291.
+/// # Examples
+///
+/// ```
+/// use std::net::Ipv6Addr;
+/// use utils::net::ipv6addr::is_link_local_valid;
+///
+/// is_link_local_valid(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0xa9fe, 0xa9fe));
+/// ```
+pub fn is_link_local_valid(ipv6_addr: Ipv6Addr) -> bool {
+    ipv6_addr.segments()[0] & 0xffc0 == 0xfe80
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::net::ipv6addr::is_link_local_valid;
+    use std::net::Ipv6Addr;
+
+    #[test]
+    fn test_is_link_local_valid() {
+        // Outside the link-local IPv6 address range (fe80::/10).
+        assert!(!is_link_local_valid(Ipv6Addr::LOCALHOST));
+        assert!(!is_link_local_valid(Ipv6Addr::UNSPECIFIED));
+        assert!(!is_link_local_valid(Ipv6Addr::new(
+            0xfd00, 0, 0, 0, 0, 0, 0, 1
+        )));
+        assert!(!is_link_local_valid(Ipv6Addr::new(
+            0xfec0, 0, 0, 0, 0, 0, 0, 1
+        )));
+        assert!(!is_link_local_valid(Ipv6Addr::new(
+            0xff02, 0, 0, 0, 0, 0, 0, 1
+        )));
+
+        // First and last link-local IPv6 addresses.
+        assert!(is_link_local_valid(Ipv6Addr::new(
+            0xfe80, 0, 0, 0, 0, 0, 0, 0
+        )));
+        assert!(is_link_local_valid(Ipv6Addr::new(
+            0xfebf, 0xffff, 0xffff, 0xffff, 0xffff, 0xffff, 0xffff, 0xffff
+        )));
+
+        // In between link-local IPv6 address.
+        assert!(is_link_local_valid(Ipv6Addr::new(
+            0xfe80, 0, 0, 0, 0, 0, 0xa9fe, 0xa9fe
+        )));
+    }
+}
@@ -17,6 +17,7 @@ use crate::device_manager::mmio::MMIODeviceManager;
 use crate::device_manager::persist::MMIODevManagerConstructorArgs;
 use crate::persist::{MicrovmState, MicrovmStateError};
 use crate::vmm_config::boot_source::BootConfig;
+use crate::vmm_config::mmds::{MmdsConfig, MmdsStores};
 use crate::vstate::{
     system::KvmContext,
     vcpu::{Vcpu, VcpuConfig},
@@ -26,12 +27,14 @@ use crate::{device_manager, Error, Vmm, VmmEventsObserver};
 
 use arch::InitrdConfig;
 use devices::legacy::Serial;
+use devices::virtio::vsock::persist::VsockConnReattach;
 use devices::virtio::{Balloon, Block, MmioTransport, Net, VirtioDevice, Vsock, VsockUnixBackend};
 use kernel::cmdline::Cmdline as KernelCmdline;
 use logger::{error, warn};
 use polly::event_manager::{Error as EventManagerError, EventManager, Subscriber};
 use seccomp::{BpfProgramRef, SeccompFilter};
 use snapshot::Persist;
+use timerfd::{ClockId, TimerFd};
 use utils::eventfd::EventFd;
 use utils::terminal::Terminal;
 use utils::time::TimestampUs;
@@ -219,6 +222,11 @@ fn create_vmm_and_vcpus(
         .map_err(Error::EventFd)
         .map_err(Internal)?;
 
+    // Timer bounding how long the guest is given to power off after a shutdown request.
+    let shutdown_timer = TimerFd::new_custom(ClockId::Monotonic, true, true)
+        .map_err(Error::TimerFd)
+        .map_err(Internal)?;
+
     // Instantiate the MMIO device manager.
     // 'mmio_base' address has to be an address which is protected by the kernel
     // and is architectural specific.
@@ -267,10 +275,14 @@ fn create_vmm_and_vcpus(
         guest_memory,
         vcpus_handles: Vec::new(),
         exit_evt,
+        shutdown_timer,
+        shutdown_requested: false,
         vm,
         mmio_device_manager,
         #[cfg(target_arch = "x86_64")]
         pio_device_manager,
+        announce_on_resume: false,
+        mmds: MmdsStores::default(),
     };
 
     Ok((vmm, vcpus))
@@ -315,6 +327,7 @@ pub fn build_microvm_for_boot(
         track_dirty_pages,
         vcpu_config.vcpu_count,
     )?;
+    vmm.mmds = vm_resources.mmds.clone();
 
     // The boot timer device needs to be the first device attached in order
     // to maintain the same MMIO address referenced in the documentation
@@ -339,7 +352,18 @@ pub fn build_microvm_for_boot(
         vm_resources.net_builder.iter(),
         event_manager,
     )?;
-    if let Some(unix_vsock) = vm_resources.vsock.get() {
+    let mmds_vsock_port = vm_resources
+        .mmds_config
+        .as_ref()
+        .and_then(MmdsConfig::vsock_port);
+    for unix_vsock in vm_resources.vsock.iter() {
+        if let Some(port) = mmds_vsock_port {
+            unix_vsock
+                .lock()
+                .expect("Poisoned lock")
+                .backend_mut()
+                .set_mmds(port, vm_resources.mmds.shared().clone());
+        }
         attach_unixsock_vsock_device(&mut vmm, &mut boot_cmdline, unix_vsock, event_manager)?;
     }
 
@@ -386,6 +410,8 @@ pub fn build_microvm_from_snapshot(
     microvm_state: MicrovmState,
     guest_memory: GuestMemoryMmap,
     track_dirty_pages: bool,
+    vsock_reattach: Vec<VsockConnReattach>,
+    mmds: &MmdsStores,
     seccomp_filter: BpfProgramRef,
 ) -> std::result::Result<Arc<Mutex<Vmm>>, StartMicrovmError> {
     use self::StartMicrovmError::*;
@@ -423,11 +449,15 @@ pub fn build_microvm_from_snapshot(
         mem: guest_memory,
         vm: vmm.vm.fd(),
         event_manager,
+        vsock_reattach,
+        mmds,
     };
     vmm.mmio_device_manager =
         MMIODeviceManager::restore(mmio_ctor_args, &microvm_state.device_states)
             .map_err(MicrovmStateError::RestoreDevices)
             .map_err(RestoreMicrovmState)?;
+    vmm.announce_on_resume = true;
+    vmm.mmds = mmds.clone();
 
     // Move vcpus to their own threads and start their state machine in the 'Paused' state.
     vmm.start_vcpus(vcpus, seccomp_filter)
@@ -936,10 +966,14 @@ pub mod tests {
             guest_memory,
             vcpus_handles: Vec::new(),
             exit_evt,
+            shutdown_timer: TimerFd::new_custom(ClockId::Monotonic, true, true).unwrap(),
+            shutdown_requested: false,
             vm,
             mmio_device_manager,
             #[cfg(target_arch = "x86_64")]
             pio_device_manager,
+            announce_on_resume: false,
+            mmds: MmdsStores::default(),
         }
     }
 
@@ -982,7 +1016,8 @@ pub mod tests {
         net_config: NetworkInterfaceConfig,
     ) {
         let mut net_builder = NetBuilder::new();
-        net_builder.build(net_config).unwrap();
+        let mmds = vmm.mmds.for_iface(&net_config.iface_id);
+        net_builder.build(net_config, mmds).unwrap();
 
         let res = attach_net_devices(vmm, cmdline, net_builder.iter(), event_manager);
         assert!(res.is_ok());
@@ -1131,6 +1166,7 @@ pub mod tests {
         let network_interface = NetworkInterfaceConfig {
             iface_id: String::from("netif"),
             host_dev_name: String::from("hostname"),
+            unix_socket_path: None,
             guest_mac: None,
             rx_rate_limiter: None,
             tx_rate_limiter: None,
@@ -1147,7 +1183,9 @@ pub mod tests {
 
         // We can not attach it once more.
         let mut net_builder = NetBuilder::new();
-        assert!(net_builder.build(network_interface).is_err());
+        assert!(net_builder
+            .build(network_interface, Arc::default())
+            .is_err());
     }
 
     #[test]
@@ -1348,6 +1386,8 @@ pub mod tests {
             amount_mb: 0,
             deflate_on_oom: false,
             stats_polling_interval_s: 0,
+            free_page_reporting: false,
+            auto_size: None,
         };
 
         let mut cmdline = default_kernel_cmdline();
@@ -1375,6 +1415,67 @@ pub mod tests {
         assert!(cmdline
             .as_str()
             .contains("virtio_mmio.device=4K@0xd0000000:5"));
+
+        // A second vsock device gets its own MMIO slot.
+        let mut tmp_sock_file = TempFile::new().unwrap();
+        tmp_sock_file.remove().unwrap();
+        let mut vsock_config = default_config(&tmp_sock_file);
+        vsock_config.vsock_id = "vsock2".to_string();
+        insert_vsock_device(&mut vmm, &mut cmdline, &mut event_manager, vsock_config);
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        assert!(cmdline
+            .as_str()
+            .contains("virtio_mmio.device=4K@0xd0001000:6"));
+    }
+
+    #[test]
+    fn test_quiesce_devices_on_shutdown() {
+        use devices::virtio::{Vsock, VsockEpollListener, VsockUnixBackend};
+        use std::io::{ErrorKind, Read, Write};
+        use std::os::unix::net::UnixStream;
+        use utils::epoll::EventSet;
+
+        let mut event_manager = EventManager::new().expect("Unable to create EventManager");
+        let mut vmm = default_vmm();
+        let mut tmp_sock_file = TempFile::new().unwrap();
+        tmp_sock_file.remove().unwrap();
+        let vsock_config = default_config(&tmp_sock_file);
+        let uds_path = vsock_config.uds_path.clone();
+        let mut cmdline = default_kernel_cmdline();
+        insert_vsock_device(&mut vmm, &mut cmdline, &mut event_manager, vsock_config);
+
+        // Open a host-initiated connection, and have the muxer accept it.
+        let mut stream = UnixStream::connect(&uds_path).unwrap();
+        stream.set_nonblocking(true).unwrap();
+        stream.write_all(b"CONNECT 1234\n").unwrap();
+        vmm.mmio_device_manager
+            .with_virtio_device_with_id(
+                TYPE_VSOCK,
+                "vsock",
+                |vsock: &mut Vsock<VsockUnixBackend>| {
+                    vsock.backend_mut().notify(EventSet::IN);
+                    vsock.backend_mut().notify(EventSet::IN);
+                    Ok(())
+                },
+            )
+            .unwrap();
+
+        // A guest-initiated exit leaves the connection and the socket in place.
+        let mut buf = [0u8; 8];
+        vmm.quiesce_devices_on_shutdown();
+        assert_eq!(
+            stream.read(&mut buf).unwrap_err().kind(),
+            ErrorKind::WouldBlock
+        );
+        assert!(std::path::Path::new(&uds_path).exists());
+
+        // After a shutdown request, the connection is closed.
+        vmm.shutdown_requested = true;
+        vmm.quiesce_devices_on_shutdown();
+        assert_eq!(stream.read(&mut buf).unwrap(), 0);
+        assert!(std::path::Path::new(&uds_path).exists());
+
+        std::fs::remove_file(uds_path).unwrap();
     }
 
     #[test]
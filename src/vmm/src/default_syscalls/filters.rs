@@ -71,6 +71,8 @@ pub fn default_filter() -> Result<SeccompFilter, Error> {
             // Used by glibc's tgkill
             #[cfg(target_env = "gnu")]
             allow_syscall(libc::SYS_getpid),
+            // Used by the MMDS, generating session tokens
+            allow_syscall(libc::SYS_getrandom),
             allow_syscall_if(libc::SYS_ioctl, super::create_ioctl_seccomp_rule()?),
             // Used by the block device
             allow_syscall(libc::SYS_lseek),
@@ -116,16 +118,88 @@ pub fn default_filter() -> Result<SeccompFilter, Error> {
             // Used by the API thread and vsock
             allow_syscall_if(
                 libc::SYS_socket,
-                or![and![
-                    Cond::new(0, ArgLen::DWORD, Eq, libc::AF_UNIX as u64)?,
-                    Cond::new(
-                        1,
-                        ArgLen::DWORD,
-                        Eq,
-                        (libc::SOCK_STREAM as u64) | (libc::SOCK_CLOEXEC as u64)
-                    )?,
-                    Cond::new(2, ArgLen::DWORD, Eq, 0u64)?
-                ],],
+                or![
+                    and![
+                        Cond::new(0, ArgLen::DWORD, Eq, libc::AF_UNIX as u64)?,
+                        Cond::new(
+                            1,
+                            ArgLen::DWORD,
+                            Eq,
+                            (libc::SOCK_STREAM as u64) | (libc::SOCK_CLOEXEC as u64)
+                        )?,
+                        Cond::new(2, ArgLen::DWORD, Eq, 0u64)?
+                    ],
+                    // Used by vsock TCP endpoints
+                    and![
+                        Cond::new(0, ArgLen::DWORD, Eq, libc::AF_INET as u64)?,
+                        Cond::new(
+                            1,
+                            ArgLen::DWORD,
+                            Eq,
+                            (libc::SOCK_STREAM | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC) as u64
+                        )?,
+                        Cond::new(2, ArgLen::DWORD, Eq, 0u64)?
+                    ],
+                    and![
+                        Cond::new(0, ArgLen::DWORD, Eq, libc::AF_INET6 as u64)?,
+                        Cond::new(
+                            1,
+                            ArgLen::DWORD,
+                            Eq,
+                            (libc::SOCK_STREAM | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC) as u64
+                        )?,
+                        Cond::new(2, ArgLen::DWORD, Eq, 0u64)?
+                    ],
+                    // Used when hot-plugging network interfaces backed by Unix sockets
+                    and![
+                        Cond::new(0, ArgLen::DWORD, Eq, libc::AF_UNIX as u64)?,
+                        Cond::new(
+                            1,
+                            ArgLen::DWORD,
+                            Eq,
+                            (libc::SOCK_SEQPACKET | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC)
+                                as u64
+                        )?,
+                        Cond::new(2, ArgLen::DWORD, Eq, 0u64)?
+                    ],
+                    and![
+                        Cond::new(0, ArgLen::DWORD, Eq, libc::AF_UNIX as u64)?,
+                        Cond::new(
+                            1,
+                            ArgLen::DWORD,
+                            Eq,
+                            (libc::SOCK_STREAM | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC) as u64
+                        )?,
+                        Cond::new(2, ArgLen::DWORD, Eq, 0u64)?
+                    ],
+                    and![
+                        Cond::new(0, ArgLen::DWORD, Eq, libc::AF_UNIX as u64)?,
+                        Cond::new(
+                            1,
+                            ArgLen::DWORD,
+                            Eq,
+                            (libc::SOCK_DGRAM | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC) as u64
+                        )?,
+                        Cond::new(2, ArgLen::DWORD, Eq, 0u64)?
+                    ],
+                ],
+            ),
+            // Used by vsock TCP endpoints
+            allow_syscall(libc::SYS_sendto),
+            // Used by vsock TCP endpoints, checking the outcome of a connection attempt, and by
+            // the API server, checking the credentials of its clients
+            allow_syscall_if(
+                libc::SYS_getsockopt,
+                or![
+                    and![
+                        Cond::new(1, ArgLen::DWORD, Eq, libc::SOL_SOCKET as u64)?,
+                        Cond::new(2, ArgLen::DWORD, Eq, libc::SO_ERROR as u64)?
+                    ],
+                    and![
+                        Cond::new(1, ArgLen::DWORD, Eq, libc::SOL_SOCKET as u64)?,
+                        Cond::new(2, ArgLen::DWORD, Eq, libc::SO_PEERCRED as u64)?
+                    ],
+                ],
             ),
             // Used to kick vcpus
             allow_syscall_if(
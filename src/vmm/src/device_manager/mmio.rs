@@ -14,13 +14,13 @@ use arch::aarch64::DeviceInfoForFDT;
 use arch::DeviceType;
 use devices::pseudo::BootTimer;
 use devices::virtio::{
-    Balloon, Block, MmioTransport, Net, VirtioDevice, TYPE_BALLOON, TYPE_BLOCK, TYPE_NET,
-    TYPE_VSOCK,
+    Balloon, Block, MmioTransport, Net, VirtioDevice, Vsock, VsockUnixBackend, TYPE_BALLOON,
+    TYPE_BLOCK, TYPE_NET, TYPE_VSOCK,
 };
 use devices::BusDevice;
 use kernel::cmdline as kernel_cmdline;
 use kvm_ioctls::{IoEventAddress, VmFd};
-use logger::info;
+use logger::{error, info};
 use versionize::{VersionMap, Versionize, VersionizeResult};
 use versionize_derive::Versionize;
 
@@ -33,6 +33,8 @@ pub enum Error {
     Cmdline(kernel_cmdline::Error),
     /// The device couldn't be found.
     DeviceNotFound,
+    /// The device is still being driven by the guest.
+    DeviceInUse,
     /// Failure in creating or cloning an event fd.
     EventFd(io::Error),
     /// Incorrect device type.
@@ -47,6 +49,10 @@ pub enum Error {
     RegisterIoEvent(kvm_ioctls::Error),
     /// Registering an IRQ FD failed.
     RegisterIrqFd(kvm_ioctls::Error),
+    /// Unregistering an IO Event failed.
+    UnregisterIoEvent(kvm_ioctls::Error),
+    /// Unregistering an IRQ FD failed.
+    UnregisterIrqFd(kvm_ioctls::Error),
     /// Failed to update the mmio device.
     UpdateFailed,
 }
@@ -64,6 +70,12 @@ impl fmt::Display for Error {
             Error::RegisterIoEvent(e) => write!(f, "failed to register IO event: {}", e),
             Error::RegisterIrqFd(e) => write!(f, "failed to register irqfd: {}", e),
             Error::DeviceNotFound => write!(f, "the device couldn't be found"),
+            Error::DeviceInUse => write!(
+                f,
+                "the device is still in use by the guest driver, which must be unbound first"
+            ),
+            Error::UnregisterIoEvent(e) => write!(f, "failed to unregister IO event: {}", e),
+            Error::UnregisterIrqFd(e) => write!(f, "failed to unregister irqfd: {}", e),
             Error::UpdateFailed => write!(f, "failed to update the mmio device"),
         }
     }
@@ -116,6 +128,13 @@ impl IrqManager {
         Ok(irqs)
     }
 
+    /// Marks `irqs` as used, so that they are not handed out again.
+    pub fn reserve(&mut self, irqs: &[u32]) {
+        if let Some(max) = irqs.iter().max() {
+            self.next_avail = std::cmp::max(self.next_avail, max + 1);
+        }
+    }
+
     pub fn check(&self, irqs: &[u32]) -> Result<()> {
         for irq in irqs {
             // Check for out of range.
@@ -133,6 +152,8 @@ pub struct MMIODeviceManager {
     mmio_base: u64,
     next_avail_mmio: u64,
     irqs: IrqManager,
+    // Slots released by devices removed at runtime, available for reuse.
+    free_slots: Vec<MMIODeviceInfo>,
     pub(crate) id_to_dev_info: HashMap<(DeviceType, String), MMIODeviceInfo>,
 }
 
@@ -143,6 +164,7 @@ impl MMIODeviceManager {
             mmio_base,
             next_avail_mmio: mmio_base,
             irqs: IrqManager::new(irq_interval.0, irq_interval.1),
+            free_slots: Vec::new(),
             bus: devices::Bus::new(),
             id_to_dev_info: HashMap::new(),
         }
@@ -150,6 +172,13 @@ impl MMIODeviceManager {
 
     /// Allocates resources for a new device to be added.
     fn allocate_new_slot(&mut self, irq_count: u32) -> Result<MMIODeviceInfo> {
+        if let Some(index) = self
+            .free_slots
+            .iter()
+            .position(|slot| slot.irqs.len() == irq_count as usize)
+        {
+            return Ok(self.free_slots.swap_remove(index));
+        }
         let irqs = self.irqs.get(irq_count)?;
         let slot = MMIODeviceInfo {
             addr: self.next_avail_mmio,
@@ -178,6 +207,10 @@ impl MMIODeviceManager {
         self.bus
             .insert(device, slot.addr, slot.len)
             .map_err(Error::BusError)?;
+        // Slots restored from a snapshot are not handed out by `allocate_new_slot()`, so make
+        // sure devices added later on don't collide with them.
+        self.next_avail_mmio = std::cmp::max(self.next_avail_mmio, slot.addr + slot.len);
+        self.irqs.reserve(&slot.irqs);
         self.id_to_dev_info.insert(identifier, slot);
         Ok(())
     }
@@ -247,6 +280,89 @@ impl MMIODeviceManager {
         Ok(mmio_slot)
     }
 
+    /// Allocate slot and register an already created virtio-over-MMIO device while the guest is
+    /// running. Since the kernel command line can no longer be changed, it is up to the guest to
+    /// discover the device, using the returned slot.
+    pub fn register_mmio_virtio_hotplug(
+        &mut self,
+        vm: &VmFd,
+        device_id: String,
+        mmio_device: MmioTransport,
+    ) -> Result<MMIODeviceInfo> {
+        let device_type = DeviceType::Virtio(mmio_device.locked_device().device_type());
+        if self
+            .id_to_dev_info
+            .contains_key(&(device_type, device_id.clone()))
+        {
+            return Err(Error::InvalidInput);
+        }
+        let mmio_slot = self.allocate_new_slot(1)?;
+        if let Err(e) = self.register_mmio_virtio(vm, device_id, mmio_device, &mmio_slot) {
+            self.free_slots.push(mmio_slot);
+            return Err(e);
+        }
+        Ok(mmio_slot)
+    }
+
+    /// Unregister a virtio-over-MMIO device, releasing its slot for future devices.
+    /// Returns the device's transport, so that the caller can finish tearing it down.
+    ///
+    /// The slot is only released once the device's ioeventfds and irqfd are unregistered from
+    /// KVM. On failure, the device is left registered as it was.
+    pub fn unregister_mmio_virtio(
+        &mut self,
+        vm: &VmFd,
+        virtio_type: u32,
+        device_id: &str,
+    ) -> Result<Arc<Mutex<dyn BusDevice>>> {
+        let identifier = (DeviceType::Virtio(virtio_type), device_id.to_string());
+        let slot = self
+            .id_to_dev_info
+            .get(&identifier)
+            .ok_or(Error::DeviceNotFound)?
+            .clone();
+        let (_, bus_device) = self
+            .bus
+            .get_device(slot.addr)
+            .ok_or(Error::DeviceNotFound)?;
+
+        {
+            let locked_bus_device = bus_device.lock().expect("Poisoned lock");
+            let mmio_device = locked_bus_device
+                .as_any()
+                .downcast_ref::<MmioTransport>()
+                .ok_or(Error::IncorrectDeviceType)?;
+            let locked_device = mmio_device.locked_device();
+            let queue_evts = locked_device.queue_events();
+            let io_addr =
+                IoEventAddress::Mmio(slot.addr + u64::from(devices::virtio::NOTIFY_REG_OFFSET));
+            // Registers again the ioeventfds of the first `count` queues.
+            let reregister_ioevents = |count: usize| {
+                for (i, queue_evt) in queue_evts.iter().enumerate().take(count) {
+                    if let Err(e) = vm.register_ioevent(queue_evt, &io_addr, i as u32) {
+                        error!("Failed to register again IO event {}: {}", i, e);
+                    }
+                }
+            };
+
+            for (i, queue_evt) in queue_evts.iter().enumerate() {
+                if let Err(e) = vm.unregister_ioevent(queue_evt, &io_addr, i as u32) {
+                    reregister_ioevents(i);
+                    return Err(Error::UnregisterIoEvent(e));
+                }
+            }
+            if let Err(e) = vm.unregister_irqfd(locked_device.interrupt_evt(), slot.irqs[0]) {
+                reregister_ioevents(queue_evts.len());
+                return Err(Error::UnregisterIrqFd(e));
+            }
+        }
+
+        self.bus.remove(slot.addr);
+        self.id_to_dev_info.remove(&identifier);
+        self.free_slots.push(slot);
+        Ok(bus_device)
+    }
+
     #[cfg(target_arch = "aarch64")]
     /// Register an early console at the specified MMIO address if given as parameter,
     /// otherwise allocate a new MMIO slot for it.
@@ -326,7 +442,7 @@ impl MMIODeviceManager {
         &self,
         device_type: DeviceType,
         device_id: &str,
-    ) -> Option<&Mutex<dyn BusDevice>> {
+    ) -> Option<Arc<Mutex<dyn BusDevice>>> {
         if let Some(dev_info) = self
             .id_to_dev_info
             .get(&(device_type, device_id.to_string()))
@@ -353,7 +469,7 @@ impl MMIODeviceManager {
                 .get_device(*device_type, device_id)
                 // Safe to unwrap() because we know the device exists.
                 .unwrap();
-            f(device_type, device_id, device_info, bus_device)?;
+            f(device_type, device_id, device_info, &bus_device)?;
         }
         Ok(())
     }
@@ -439,6 +555,88 @@ impl MMIODeviceManager {
             Ok(())
         });
     }
+
+    /// Announces the guest on the networks of all its interfaces.
+    pub fn announce_net_devices(&self) {
+        let _: Result<()> = self.for_each_device(|devtype, id, _, bus_dev| {
+            if *devtype == DeviceType::Virtio(TYPE_NET) {
+                let bus_dev = bus_dev.lock().expect("Poisoned lock");
+                // Virtio devices are guaranteed MmioTransport.
+                let mmio_dev = bus_dev.as_any().downcast_ref::<MmioTransport>().unwrap();
+                let mut virtio = mmio_dev.locked_device();
+                let net = virtio.as_mut_any().downcast_mut::<Net>().unwrap();
+                if let Err(e) = net.announce() {
+                    error!("Failed to announce the guest on net {}: {:?}", id, e);
+                }
+            }
+            Ok(())
+        });
+    }
+
+    /// Settles the device backends before the microVM stops: block devices write the data
+    /// cached on the host out to their backing files, net devices send the frames queued by the
+    /// guest, and vsock devices forward the packets queued by the guest before closing their
+    /// connections.
+    pub fn quiesce_devices(&self) {
+        let _: Result<()> = self.for_each_device(|devtype, _, _, bus_dev| {
+            if let DeviceType::Virtio(virtio_type) = *devtype {
+                let bus_dev = bus_dev.lock().expect("Poisoned lock");
+                // Virtio devices are guaranteed MmioTransport.
+                let mmio_dev = bus_dev.as_any().downcast_ref::<MmioTransport>().unwrap();
+                let mut virtio = mmio_dev.locked_device();
+                match virtio_type {
+                    TYPE_BLOCK => {
+                        let block = virtio.as_mut_any().downcast_mut::<Block>().unwrap();
+                        block.flush_backend();
+                    }
+                    TYPE_NET => {
+                        let net = virtio.as_mut_any().downcast_mut::<Net>().unwrap();
+                        net.flush_tx();
+                    }
+                    TYPE_VSOCK => {
+                        // The only vsock backend is the Unix one.
+                        let vsock = virtio
+                            .as_mut_any()
+                            .downcast_mut::<Vsock<VsockUnixBackend>>()
+                            .unwrap();
+                        if vsock.is_activated() {
+                            vsock.process_tx();
+                        }
+                        vsock.backend_mut().close();
+                    }
+                    _ => (),
+                }
+            }
+            Ok(())
+        });
+    }
+
+    /// Lets the MMDS network stacks of all net devices answer the requests waiting for a data
+    /// store change.
+    pub fn process_mmds_updates(&self) {
+        let _: Result<()> = self.for_each_device(|devtype, _, _, bus_dev| {
+            if *devtype == DeviceType::Virtio(TYPE_NET) {
+                let bus_dev = bus_dev.lock().expect("Poisoned lock");
+                // Virtio devices are guaranteed MmioTransport.
+                let mmio_dev = bus_dev.as_any().downcast_ref::<MmioTransport>().unwrap();
+                let mut virtio = mmio_dev.locked_device();
+                let net = virtio.as_mut_any().downcast_mut::<Net>().unwrap();
+                net.process_mmds_update();
+            } else if *devtype == DeviceType::Virtio(TYPE_VSOCK) {
+                let bus_dev = bus_dev.lock().expect("Poisoned lock");
+                // Virtio devices are guaranteed MmioTransport.
+                let mmio_dev = bus_dev.as_any().downcast_ref::<MmioTransport>().unwrap();
+                let mut virtio = mmio_dev.locked_device();
+                // The only vsock backend is the Unix one.
+                let vsock = virtio
+                    .as_mut_any()
+                    .downcast_mut::<Vsock<VsockUnixBackend>>()
+                    .unwrap();
+                vsock.backend_mut().process_mmds_update();
+            }
+            Ok(())
+        });
+    }
 }
 
 #[cfg(target_arch = "aarch64")]
@@ -583,6 +781,53 @@ mod tests {
             .is_ok());
     }
 
+    #[test]
+    fn test_unregister_virtio_device() {
+        let start_addr1 = GuestAddress(0x0);
+        let start_addr2 = GuestAddress(0x1000);
+        let guest_mem =
+            GuestMemoryMmap::from_ranges(&[(start_addr1, 0x1000), (start_addr2, 0x1000)]).unwrap();
+        let mut vm = builder::setup_kvm_vm(&guest_mem, false).unwrap();
+        let mut device_manager =
+            MMIODeviceManager::new(0xd000_0000, (arch::IRQ_BASE, arch::IRQ_MAX));
+
+        let mut cmdline = kernel_cmdline::Cmdline::new(4096);
+        let dummy = Arc::new(Mutex::new(DummyDevice::new()));
+        #[cfg(target_arch = "x86_64")]
+        assert!(builder::setup_interrupt_controller(&mut vm).is_ok());
+        #[cfg(target_arch = "aarch64")]
+        assert!(builder::setup_interrupt_controller(&mut vm, 1).is_ok());
+
+        let addr = device_manager
+            .register_virtio_test_device(vm.fd(), guest_mem, dummy.clone(), &mut cmdline, "dummy")
+            .unwrap();
+        let io_addr = IoEventAddress::Mmio(addr + u64::from(devices::virtio::NOTIFY_REG_OFFSET));
+
+        // The device stays registered when its ioeventfd can't be unregistered.
+        vm.fd()
+            .unregister_ioevent(&dummy.lock().unwrap().queue_evts[0], &io_addr, 0u32)
+            .unwrap();
+        match device_manager.unregister_mmio_virtio(vm.fd(), 0, "dummy") {
+            Err(Error::UnregisterIoEvent(_)) => (),
+            _ => panic!("Expected Error::UnregisterIoEvent"),
+        }
+        assert!(device_manager
+            .get_device(DeviceType::Virtio(0), "dummy")
+            .is_some());
+        assert!(device_manager.free_slots.is_empty());
+
+        vm.fd()
+            .register_ioevent(&dummy.lock().unwrap().queue_evts[0], &io_addr, 0u32)
+            .unwrap();
+        assert!(device_manager
+            .unregister_mmio_virtio(vm.fd(), 0, "dummy")
+            .is_ok());
+        assert!(device_manager
+            .get_device(DeviceType::Virtio(0), "dummy")
+            .is_none());
+        assert_eq!(device_manager.free_slots.len(), 1);
+    }
+
     #[test]
     fn test_register_too_many_devices() {
         let start_addr1 = GuestAddress(0x0);
@@ -650,6 +895,8 @@ mod tests {
                 Error::IrqsExhausted => format!("{}{:?}", e, e),
                 Error::RegisterIoEvent(_) => format!("{}{:?}", e, e),
                 Error::RegisterIrqFd(_) => format!("{}{:?}", e, e),
+                Error::UnregisterIoEvent(_) => format!("{}{:?}", e, e),
+                Error::UnregisterIrqFd(_) => format!("{}{:?}", e, e),
                 Error::UpdateFailed => format!("{}{:?}", e, e),
             };
             assert!(!msg.is_empty());
@@ -664,6 +911,8 @@ mod tests {
         check_fmt_err(Error::IrqsExhausted);
         check_fmt_err(Error::RegisterIoEvent(errno::Error::new(0)));
         check_fmt_err(Error::RegisterIrqFd(errno::Error::new(0)));
+        check_fmt_err(Error::UnregisterIoEvent(errno::Error::new(0)));
+        check_fmt_err(Error::UnregisterIrqFd(errno::Error::new(0)));
         check_fmt_err(Error::UpdateFailed);
     }
 
@@ -8,6 +8,7 @@ use std::result::Result;
 use std::sync::{Arc, Mutex};
 
 use super::mmio::*;
+use crate::vmm_config::mmds::MmdsStores;
 
 #[cfg(target_arch = "aarch64")]
 use arch::DeviceType;
@@ -18,7 +19,9 @@ use devices::virtio::block::Block;
 use devices::virtio::net::persist::{Error as NetError, NetConstructorArgs, NetState};
 use devices::virtio::net::Net;
 use devices::virtio::persist::{MmioTransportConstructorArgs, MmioTransportState};
-use devices::virtio::vsock::persist::{VsockConstructorArgs, VsockState, VsockUdsConstructorArgs};
+use devices::virtio::vsock::persist::{
+    VsockConnReattach, VsockConstructorArgs, VsockState, VsockUdsConstructorArgs,
+};
 use devices::virtio::vsock::{Vsock, VsockError, VsockUnixBackend, VsockUnixBackendError};
 use devices::virtio::{
     MmioTransport, VirtioDevice, TYPE_BALLOON, TYPE_BLOCK, TYPE_NET, TYPE_VSOCK,
@@ -122,11 +125,19 @@ pub struct DeviceStates {
     pub block_devices: Vec<ConnectedBlockState>,
     /// Net device states.
     pub net_devices: Vec<ConnectedNetState>,
-    /// Vsock device state.
+    /// Vsock device state. Only used by snapshot versions that support a single vsock device.
     pub vsock_device: Option<ConnectedVsockState>,
     /// Balloon device state.
     #[version(start = 2, ser_fn = "balloon_serialize")]
     pub balloon_device: Option<ConnectedBalloonState>,
+    /// Vsock device states.
+    #[version(
+        start = 3,
+        default_fn = "def_vsock_devices",
+        ser_fn = "vsock_devices_serialize",
+        de_fn = "vsock_devices_deserialize"
+    )]
+    pub vsock_devices: Vec<ConnectedVsockState>,
 }
 
 impl DeviceStates {
@@ -139,12 +150,35 @@ impl DeviceStates {
 
         Ok(())
     }
+
+    fn def_vsock_devices(_source_version: u16) -> Vec<ConnectedVsockState> {
+        Vec::new()
+    }
+
+    fn vsock_devices_serialize(&mut self, _target_version: u16) -> VersionizeResult<()> {
+        if self.vsock_devices.len() > 1 {
+            return Err(VersionizeError::Semantic(
+                "Target version does not support multiple vsock devices.".to_owned(),
+            ));
+        }
+        self.vsock_device = self.vsock_devices.pop();
+
+        Ok(())
+    }
+
+    fn vsock_devices_deserialize(&mut self, _source_version: u16) -> VersionizeResult<()> {
+        self.vsock_devices = self.vsock_device.take().into_iter().collect();
+
+        Ok(())
+    }
 }
 
 pub struct MMIODevManagerConstructorArgs<'a> {
     pub mem: GuestMemoryMmap,
     pub vm: &'a VmFd,
     pub event_manager: &'a mut EventManager,
+    pub vsock_reattach: Vec<VsockConnReattach>,
+    pub mmds: &'a MmdsStores,
 }
 
 impl<'a> Persist<'a> for MMIODeviceManager {
@@ -158,6 +192,7 @@ impl<'a> Persist<'a> for MMIODeviceManager {
             block_devices: Vec::new(),
             net_devices: Vec::new(),
             vsock_device: None,
+            vsock_devices: Vec::new(),
             #[cfg(target_arch = "aarch64")]
             legacy_devices: Vec::new(),
         };
@@ -234,7 +269,7 @@ impl<'a> Persist<'a> for MMIODeviceManager {
                         backend: vsock.backend().save(),
                         frontend: vsock.save(),
                     };
-                    states.vsock_device = Some(ConnectedVsockState {
+                    states.vsock_devices.push(ConnectedVsockState {
                         device_id: devid.clone(),
                         device_state: vsock_state,
                         transport_state,
@@ -257,6 +292,7 @@ impl<'a> Persist<'a> for MMIODeviceManager {
             MMIODeviceManager::new(arch::MMIO_MEM_START, (arch::IRQ_BASE, arch::IRQ_MAX));
         let mem = &constructor_args.mem;
         let vm = constructor_args.vm;
+        let mmds = constructor_args.mmds;
 
         #[cfg(target_arch = "aarch64")]
         {
@@ -348,7 +384,10 @@ impl<'a> Persist<'a> for MMIODeviceManager {
         for net_state in &state.net_devices {
             let device = Arc::new(Mutex::new(
                 Net::restore(
-                    NetConstructorArgs { mem: mem.clone() },
+                    NetConstructorArgs {
+                        mem: mem.clone(),
+                        mmds: mmds.for_iface(&net_state.device_id),
+                    },
                     &net_state.device_state,
                 )
                 .map_err(Error::Net)?,
@@ -363,15 +402,31 @@ impl<'a> Persist<'a> for MMIODeviceManager {
                 constructor_args.event_manager,
             )?;
         }
-        if let Some(vsock_state) = &state.vsock_device {
+        let single_vsock = state.vsock_devices.len() == 1;
+        for vsock_state in &state.vsock_devices {
+            // Reattach targets without a device ID are only used when there's a single device.
+            let reattach = constructor_args
+                .vsock_reattach
+                .iter()
+                .filter(|target| {
+                    target
+                        .vsock_id
+                        .as_ref()
+                        .map_or(single_vsock, |id| id == &vsock_state.device_id)
+                })
+                .cloned()
+                .collect();
             let ctor_args = VsockUdsConstructorArgs {
                 cid: vsock_state.device_state.frontend.cid,
+                reattach,
+                mmds: mmds.shared().clone(),
             };
             let backend = VsockUnixBackend::restore(ctor_args, &vsock_state.device_state.backend)
                 .map_err(Error::VsockUnixBackend)?;
             let device = Arc::new(Mutex::new(
                 Vsock::restore(
                     VsockConstructorArgs {
+                        id: vsock_state.device_id.clone(),
                         mem: mem.clone(),
                         backend,
                     },
@@ -400,6 +455,7 @@ mod tests {
     use crate::builder::tests::*;
     use crate::vmm_config::balloon::BalloonDeviceConfig;
     use crate::vmm_config::net::NetworkInterfaceConfig;
+    use crate::vmm_config::vsock::tests::default_config;
     use crate::vmm_config::vsock::VsockDeviceConfig;
     use devices::virtio::block::CacheType;
     use polly::event_manager::EventManager;
@@ -478,7 +534,7 @@ mod tests {
             self.balloon_device == other.balloon_device
                 && self.block_devices == other.block_devices
                 && self.net_devices == other.net_devices
-                && self.vsock_device == other.vsock_device
+                && self.vsock_devices == other.vsock_devices
         }
     }
 
@@ -486,8 +542,8 @@ mod tests {
         fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
             write!(
                 f,
-                "DevicesStates {{ block_devices: {:?}, net_devices: {:?}, vsock_device: {:?} }}",
-                self.block_devices, self.net_devices, self.vsock_device
+                "DevicesStates {{ block_devices: {:?}, net_devices: {:?}, vsock_devices: {:?} }}",
+                self.block_devices, self.net_devices, self.vsock_devices
             )
         }
     }
@@ -544,6 +600,8 @@ mod tests {
                 amount_mb: 123,
                 deflate_on_oom: false,
                 stats_polling_interval_s: 1,
+                free_page_reporting: false,
+                auto_size: None,
             };
             insert_balloon_device(&mut vmm, &mut cmdline, &mut event_manager, balloon_cfg);
             // Add a block device.
@@ -561,6 +619,7 @@ mod tests {
             let network_interface = NetworkInterfaceConfig {
                 iface_id: String::from("netif"),
                 host_dev_name: String::from("hostname"),
+                unix_socket_path: None,
                 guest_mac: None,
                 rx_rate_limiter: None,
                 tx_rate_limiter: None,
@@ -578,6 +637,14 @@ mod tests {
                 vsock_id: vsock_dev_id.to_string(),
                 guest_cid: 3,
                 uds_path: tmp_sock_file.as_path().to_str().unwrap().to_string(),
+                listen_ports: Vec::new(),
+                tcp_listeners: Vec::new(),
+                tcp_endpoints: Vec::new(),
+                persist_connections: false,
+                buf_alloc: None,
+                max_connections: None,
+                conn_request_timeout_ms: None,
+                conn_shutdown_timeout_ms: None,
             };
             insert_vsock_device(&mut vmm, &mut cmdline, &mut event_manager, vsock_config);
 
@@ -611,6 +678,67 @@ mod tests {
             mem: vmm.guest_memory().clone(),
             vm: vmm.vm.fd(),
             event_manager: &mut event_manager,
+            vsock_reattach: Vec::new(),
+            mmds: &MmdsStores::default(),
+        };
+        let restored_dev_manager =
+            MMIODeviceManager::restore(restore_args, &device_states).unwrap();
+
+        assert_eq!(restored_dev_manager, original_mmio_device_manager);
+    }
+
+    #[test]
+    fn test_multiple_vsock_devices_persistence() {
+        let mut buf = vec![0; 16384];
+        let mut version_map = VersionMap::new();
+        version_map
+            .new_version()
+            .set_type_version(DeviceStates::type_id(), 2)
+            .new_version()
+            .set_type_version(DeviceStates::type_id(), 3);
+        let mut tmp_sock_files = vec![TempFile::new().unwrap(), TempFile::new().unwrap()];
+        let original_mmio_device_manager = {
+            let mut event_manager = EventManager::new().expect("Unable to create EventManager");
+            let mut vmm = default_vmm();
+            let mut cmdline = default_kernel_cmdline();
+
+            for (idx, tmp_sock_file) in tmp_sock_files.iter_mut().enumerate() {
+                tmp_sock_file.remove().unwrap();
+                let mut vsock_config = default_config(tmp_sock_file);
+                vsock_config.vsock_id = format!("vsock{}", idx);
+                insert_vsock_device(&mut vmm, &mut cmdline, &mut event_manager, vsock_config);
+            }
+
+            let states = vmm.mmio_device_manager.save();
+            assert_eq!(states.vsock_devices.len(), 2);
+            assert_eq!(
+                states.serialize(&mut buf.as_mut_slice(), &version_map, 2),
+                Err(VersionizeError::Semantic(
+                    "Target version does not support multiple vsock devices.".to_string()
+                ))
+            );
+            states
+                .serialize(&mut buf.as_mut_slice(), &version_map, 3)
+                .unwrap();
+
+            vmm.mmio_device_manager.soft_clone()
+        };
+        for tmp_sock_file in tmp_sock_files.iter_mut() {
+            tmp_sock_file.remove().unwrap();
+        }
+
+        let mut event_manager = EventManager::new().expect("Unable to create EventManager");
+        let vmm = default_vmm();
+        let device_states: DeviceStates =
+            DeviceStates::deserialize(&mut buf.as_slice(), &version_map, 3).unwrap();
+        assert!(device_states.vsock_device.is_none());
+        assert_eq!(device_states.vsock_devices.len(), 2);
+        let restore_args = MMIODevManagerConstructorArgs {
+            mem: vmm.guest_memory().clone(),
+            vm: vmm.vm.fd(),
+            event_manager: &mut event_manager,
+            vsock_reattach: Vec::new(),
+            mmds: &MmdsStores::default(),
         };
         let restored_dev_manager =
             MMIODeviceManager::restore(restore_args, &device_states).unwrap();
@@ -37,14 +37,16 @@ use std::fmt::{Display, Formatter};
 use std::io;
 use std::os::unix::io::AsRawFd;
 use std::sync::mpsc::RecvTimeoutError;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 #[cfg(target_arch = "x86_64")]
 use crate::device_manager::legacy::PortIODeviceManager;
 use crate::device_manager::mmio::MMIODeviceManager;
 use crate::memory_snapshot::SnapshotMemory;
-use crate::persist::{MicrovmState, MicrovmStateError, VmInfo};
+use crate::persist::{IfaceMmdsState, MicrovmState, MicrovmStateError, VmInfo};
+use crate::vmm_config::mmds::MmdsStores;
+use crate::vmm_config::net::NetworkInterfaceStats;
 use crate::vstate::vcpu::VcpuState;
 use crate::vstate::{
     vcpu::{Vcpu, VcpuEvent, VcpuHandle, VcpuResponse},
@@ -57,14 +59,17 @@ use devices::virtio::{
     TYPE_BLOCK, TYPE_NET,
 };
 use devices::BusDevice;
-use logger::{error, info, warn, LoggerError, MetricsError, METRICS};
+use logger::{error, info, warn, LoggerError, MetricsError, VmEvent, EVENTS, METRICS};
 use polly::event_manager::{EventManager, Subscriber};
 use rate_limiter::BucketUpdate;
 use seccomp::BpfProgramRef;
 use snapshot::Persist;
+use timerfd::TimerFd;
+#[cfg(target_arch = "x86_64")]
+use timerfd::{SetTimeFlags, TimerState};
 use utils::epoll::{EpollEvent, EventSet};
 use utils::eventfd::EventFd;
-use vm_memory::{GuestMemory, GuestMemoryMmap, GuestMemoryRegion, GuestRegionMmap};
+use vm_memory::{GuestAddress, GuestMemory, GuestMemoryMmap, GuestMemoryRegion, GuestRegionMmap};
 
 /// Success exit code.
 pub const FC_EXIT_CODE_OK: u8 = 0;
@@ -92,6 +97,10 @@ pub const FC_EXIT_CODE_SIGILL: u8 = 157;
 pub const FC_EXIT_CODE_BAD_CONFIGURATION: u8 = 152;
 /// Command line arguments parsing error.
 pub const FC_EXIT_CODE_ARG_PARSING: u8 = 153;
+/// The guest powered off after being asked to shut down through the API.
+pub const FC_EXIT_CODE_SHUTDOWN: u8 = 158;
+/// The guest did not power off in time after being asked to shut down through the API.
+pub const FC_EXIT_CODE_SHUTDOWN_FORCED: u8 = 159;
 
 /// Errors associated with the VMM internal logic. These errors cannot be generated by direct user
 /// input, but can result from bad configuration of the host (for example if Firecracker doesn't
@@ -108,6 +117,8 @@ pub enum Error {
     DirtyBitmap(kvm_ioctls::Error),
     /// Cannot read from an Event file descriptor.
     EventFd(io::Error),
+    /// Cannot update the event manager registrations.
+    EventManager(polly::event_manager::Error),
     /// I8042 Error.
     I8042Error(devices::legacy::I8042DeviceError),
     /// Cannot access kernel file.
@@ -169,6 +180,7 @@ impl Display for Error {
             DeviceManager(e) => write!(f, "{}", e),
             DirtyBitmap(e) => write!(f, "Error getting the KVM dirty bitmap. {}", e),
             EventFd(e) => write!(f, "Event fd error: {}", e),
+            EventManager(e) => write!(f, "Event manager error: {:?}", e),
             I8042Error(e) => write!(f, "I8042 error: {}", e),
             KernelFile(e) => write!(f, "Cannot access kernel file: {}", e),
             KvmContext(e) => write!(f, "Failed to validate KVM support: {}", e),
@@ -238,10 +250,21 @@ pub struct Vmm {
     exit_evt: EventFd,
     vm: Vm,
 
+    // Expires when the guest failed to power off in time after a shutdown request.
+    shutdown_timer: TimerFd,
+    // Whether the guest was asked to power off through the API.
+    shutdown_requested: bool,
+
     // Guest VM devices.
     mmio_device_manager: MMIODeviceManager,
     #[cfg(target_arch = "x86_64")]
     pio_device_manager: PortIODeviceManager,
+
+    // Whether the guest must be announced on its networks upon resuming, i.e. after a restore.
+    announce_on_resume: bool,
+
+    // The MMDS data stores serving the guest network interfaces.
+    mmds: MmdsStores,
 }
 
 impl Vmm {
@@ -250,7 +273,7 @@ impl Vmm {
         &self,
         device_type: DeviceType,
         device_id: &str,
-    ) -> Option<&Mutex<dyn BusDevice>> {
+    ) -> Option<Arc<Mutex<dyn BusDevice>>> {
         self.mmio_device_manager.get_device(device_type, device_id)
     }
 
@@ -306,7 +329,18 @@ impl Vmm {
     pub fn resume_vm(&mut self) -> Result<()> {
         self.mmio_device_manager.kick_devices();
         self.broadcast_vcpu_event(VcpuEvent::Resume, VcpuResponse::Resumed)
-            .map_err(|_| Error::VcpuResume)
+            .map_err(|_| Error::VcpuResume)?;
+        if self.announce_on_resume {
+            // The guest may now be reachable through another host, so let its peers know.
+            self.mmio_device_manager.announce_net_devices();
+            self.announce_on_resume = false;
+        }
+        Ok(())
+    }
+
+    /// Answers the guest MMDS requests which were waiting for a data store change.
+    pub fn process_mmds_updates(&self) {
+        self.mmio_device_manager.process_mmds_updates();
     }
 
     /// Sends a pause command to the vCPUs.
@@ -339,9 +373,34 @@ impl Vmm {
             .map_err(Error::I8042Error)
     }
 
+    /// Asks the guest to power off, and arms a timer stopping the microVM anyway once
+    /// `timeout_ms` milliseconds have passed.
+    #[cfg(target_arch = "x86_64")]
+    pub fn shutdown(&mut self, timeout_ms: u64) -> Result<()> {
+        self.send_ctrl_alt_del()?;
+
+        info!("Asked the guest to shut down within {} ms.", timeout_ms);
+        // A zero timeout would disarm the timer instead.
+        self.shutdown_timer.set_state(
+            TimerState::Oneshot(Duration::from_millis(std::cmp::max(timeout_ms, 1))),
+            SetTimeFlags::Default,
+        );
+        self.shutdown_requested = true;
+        Ok(())
+    }
+
+    // Settles the device backends before stopping, when the guest was asked to power off
+    // through the API. Other exits, such as guest reboots, leave the backends untouched.
+    fn quiesce_devices_on_shutdown(&self) {
+        if self.shutdown_requested {
+            self.mmio_device_manager.quiesce_devices();
+        }
+    }
+
     /// Waits for all vCPUs to exit and terminates the Firecracker process.
     pub fn stop(&mut self, exit_code: i32) {
         info!("Vmm is stopping.");
+        EVENTS.emit(VmEvent::VmmStopping { exit_code });
 
         if let Some(observer) = self.events_observer.as_mut() {
             if let Err(e) = observer.on_vmm_stop() {
@@ -349,10 +408,11 @@ impl Vmm {
             }
         }
 
-        // Write the metrics before exiting.
+        // Write the metrics and the buffered logs before exiting.
         if let Err(e) = METRICS.write() {
             error!("Failed to write metrics while stopping: {}", e);
         }
+        logger::logger().flush();
 
         // Exit from Firecracker using the provided exit code. Safe because we're terminating
         // the process anyway.
@@ -382,12 +442,24 @@ impl Vmm {
         let mem_size_mib = mem_size_mib(self.guest_memory());
         let memory_state = self.guest_memory().describe();
 
+        let mmds_state = self.mmds.shared().lock().expect("Poisoned lock").save();
+        let iface_mmds_states = self
+            .mmds
+            .dedicated()
+            .map(|(iface_id, mmds)| IfaceMmdsState {
+                iface_id: iface_id.clone(),
+                mmds_state: mmds.lock().expect("Poisoned lock").save(),
+            })
+            .collect();
+
         Ok(MicrovmState {
             vm_info: VmInfo { mem_size_mib },
             memory_state,
             vm_state,
             vcpu_states,
             device_states,
+            mmds_state,
+            iface_mmds_states,
         })
     }
 
@@ -555,6 +627,128 @@ impl Vmm {
             .map_err(Error::DeviceManager)
     }
 
+    /// Returns the traffic statistics of the net device with `net_id` id.
+    pub fn net_interface_stats(&self, net_id: &str) -> Result<NetworkInterfaceStats> {
+        let mut stats = NetworkInterfaceStats::default();
+        self.mmio_device_manager
+            .with_virtio_device_with_id(TYPE_NET, net_id, |net: &mut Net| {
+                stats = NetworkInterfaceStats::new(net_id, net.metrics());
+                Ok(())
+            })
+            .map_err(Error::DeviceManager)?;
+        Ok(stats)
+    }
+
+    /// Re-points the net device with `net_id` id to the TAP interface `host_dev_name` and sets
+    /// its link state. Either both changes are applied, or none of them.
+    pub fn update_net_connection(
+        &mut self,
+        net_id: &str,
+        host_dev_name: Option<&str>,
+        link_up: Option<bool>,
+        event_manager: &mut EventManager,
+    ) -> Result<()> {
+        self.mmio_device_manager
+            .with_virtio_device_with_id(TYPE_NET, net_id, |net: &mut Net| {
+                let old_backend = match host_dev_name {
+                    Some(host_dev_name) => Some(
+                        net.update_tap(host_dev_name, event_manager)
+                            .map_err(|e| format!("{:?}", e))?,
+                    ),
+                    None => None,
+                };
+                if let Some(link_up) = link_up {
+                    if let Err(e) = net.set_link_up(link_up) {
+                        if let Some(old_backend) = old_backend {
+                            if let Err(e) = net.switch_backend(old_backend, event_manager) {
+                                error!("Failed to restore the net device backend: {:?}", e);
+                            }
+                        }
+                        return Err(format!("{:?}", e));
+                    }
+                }
+                Ok(())
+            })
+            .map_err(Error::DeviceManager)
+    }
+
+    /// Attaches a net device to the running microVM. The device is placed in a newly allocated
+    /// MMIO slot, which the guest needs to probe for, as virtio-mmio has no hotplug notification.
+    pub fn hotplug_net_device(
+        &mut self,
+        net: Arc<Mutex<Net>>,
+        event_manager: &mut EventManager,
+    ) -> Result<()> {
+        let id = net.lock().expect("Poisoned lock").id().clone();
+        let device = MmioTransport::new(self.guest_memory().clone(), net.clone());
+        let slot = self
+            .mmio_device_manager
+            .register_mmio_virtio_hotplug(self.vm.fd(), id.clone(), device)
+            .map_err(Error::RegisterMMIODevice)?;
+
+        if let Err(e) = event_manager.add_subscriber(net) {
+            // Don't leave a device that can never be serviced on the bus.
+            let _ = self
+                .mmio_device_manager
+                .unregister_mmio_virtio(self.vm.fd(), TYPE_NET, &id);
+            return Err(Error::EventManager(e));
+        }
+
+        info!(
+            "Hot-plugged net device {}: virtio_mmio.device={}K@0x{:08x}:{}",
+            id,
+            slot.len / 1024,
+            slot.addr,
+            slot.irqs[0]
+        );
+        Ok(())
+    }
+
+    /// Detaches the net device with `net_id` id from the running microVM. The virtio-mmio
+    /// transport cannot ask the guest to let go of the device, so the guest driver must have
+    /// been unbound from it beforehand, which resets the device.
+    pub fn hot_unplug_net_device(
+        &mut self,
+        net_id: &str,
+        event_manager: &mut EventManager,
+    ) -> Result<()> {
+        let busdev = self
+            .get_bus_device(DeviceType::Virtio(TYPE_NET), net_id)
+            .ok_or(Error::DeviceManager(
+                device_manager::mmio::Error::DeviceNotFound,
+            ))?;
+        let virtio_device = {
+            let locked_busdev = busdev.lock().expect("Poisoned lock");
+            let mmio_transport = locked_busdev
+                .as_any()
+                .downcast_ref::<MmioTransport>()
+                // Only MmioTransport implements BusDevice at this point.
+                .expect("Unexpected BusDevice type");
+            // Removing a device the guest still drives would leave its driver bound to a
+            // vanished device.
+            if mmio_transport.is_driver_active() {
+                return Err(Error::DeviceManager(
+                    device_manager::mmio::Error::DeviceInUse,
+                ));
+            }
+            mmio_transport.device()
+        };
+
+        self.mmio_device_manager
+            .unregister_mmio_virtio(self.vm.fd(), TYPE_NET, net_id)
+            .map_err(Error::DeviceManager)?;
+
+        virtio_device
+            .lock()
+            .expect("Poisoned lock")
+            .as_any()
+            .downcast_ref::<Net>()
+            .expect("Unexpected VirtioDevice type")
+            .unregister_events(event_manager);
+        info!("Hot-unplugged net device {}.", net_id);
+        Ok(())
+    }
+
     /// Returns a reference to the balloon device if present.
     pub fn balloon_config(&self) -> std::result::Result<BalloonConfig, BalloonError> {
         if let Some(busdev) = self.get_bus_device(DeviceType::Virtio(TYPE_BALLOON), BALLOON_DEV_ID)
@@ -582,6 +776,30 @@ impl Vmm {
         }
     }
 
+    /// Returns the guest memory ranges held by the balloon device, if present.
+    pub fn balloon_inflated_ranges(&self) -> Vec<(GuestAddress, u64)> {
+        if let Some(busdev) = self.get_bus_device(DeviceType::Virtio(TYPE_BALLOON), BALLOON_DEV_ID)
+        {
+            let virtio_device = busdev
+                .lock()
+                .expect("Poisoned lock")
+                .as_any()
+                .downcast_ref::<MmioTransport>()
+                // Only MmioTransport implements BusDevice at this point.
+                .expect("Unexpected BusDevice type")
+                .device();
+
+            let locked_device = virtio_device.lock().expect("Poisoned lock");
+            locked_device
+                .as_any()
+                .downcast_ref::<Balloon>()
+                .unwrap()
+                .inflated_ranges()
+        } else {
+            Vec::new()
+        }
+    }
+
     /// Returns the latest balloon statistics if they are enabled.
     pub fn latest_balloon_stats(&self) -> std::result::Result<BalloonStats, BalloonError> {
         if let Some(busdev) = self.get_bus_device(DeviceType::Virtio(TYPE_BALLOON), BALLOON_DEV_ID)
@@ -735,16 +953,30 @@ impl Subscriber for Vmm {
                     _ => None,
                 })
                 .unwrap_or(FC_EXIT_CODE_OK);
+            let exit_code = if self.shutdown_requested && exit_code == FC_EXIT_CODE_OK {
+                FC_EXIT_CODE_SHUTDOWN
+            } else {
+                exit_code
+            };
+            self.quiesce_devices_on_shutdown();
             self.stop(i32::from(exit_code));
+        } else if source == self.shutdown_timer.as_raw_fd() && event_set == EventSet::IN {
+            self.shutdown_timer.read();
+            warn!("The guest did not shut down in time, stopping the microVM.");
+            if let Err(e) = self.pause_vm() {
+                error!("Failed to pause the vCPUs before stopping: {}", e);
+            }
+            self.quiesce_devices_on_shutdown();
+            self.stop(i32::from(FC_EXIT_CODE_SHUTDOWN_FORCED));
         } else {
             error!("Spurious EventManager event for handler: Vmm");
         }
     }
 
     fn interest_list(&self) -> Vec<EpollEvent> {
-        vec![EpollEvent::new(
-            EventSet::IN,
-            self.exit_evt.as_raw_fd() as u64,
-        )]
+        vec![
+            EpollEvent::new(EventSet::IN, self.exit_evt.as_raw_fd() as u64),
+            EpollEvent::new(EventSet::IN, self.shutdown_timer.as_raw_fd() as u64),
+        ]
     }
 }
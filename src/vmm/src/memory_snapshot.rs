@@ -44,13 +44,21 @@ where
 {
     /// Describes GuestMemoryMmap through a GuestMemoryState struct.
     fn describe(&self) -> GuestMemoryState;
-    /// Dumps all contents of GuestMemoryMmap to a writer.
-    fn dump<T: std::io::Write>(&self, writer: &mut T) -> std::result::Result<(), Error>;
+    /// Dumps all contents of GuestMemoryMmap to a writer, leaving holes
+    /// for the `excluded` ranges.
+    fn dump<T: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut T,
+        excluded: &[(GuestAddress, u64)],
+    ) -> std::result::Result<(), Error>;
     /// Dumps all pages of GuestMemoryMmap present in `dirty_bitmap` to a writer.
+    /// The ones fully contained in the `excluded` ranges are written as zeros, so
+    /// that they don't keep stale contents once the diff is merged onto a base.
     fn dump_dirty<T: std::io::Write + std::io::Seek>(
         &self,
         writer: &mut T,
         dirty_bitmap: &DirtyBitmap,
+        excluded: &[(GuestAddress, u64)],
     ) -> std::result::Result<(), Error>;
     /// Creates a GuestMemoryMmap given a `file` containing the data
     /// and a `state` containing mapping information.
@@ -107,22 +115,54 @@ impl SnapshotMemory for GuestMemoryMmap {
         guest_memory_state
     }
 
-    /// Dumps all contents of GuestMemoryMmap to a writer.
-    fn dump<T: std::io::Write>(&self, writer: &mut T) -> std::result::Result<(), Error> {
+    /// Dumps all contents of GuestMemoryMmap to a writer, leaving holes
+    /// for the `excluded` ranges.
+    fn dump<T: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut T,
+        excluded: &[(GuestAddress, u64)],
+    ) -> std::result::Result<(), Error> {
+        let mut writer_offset = 0;
+
         self.with_regions_mut(|_, region| {
-            region.write_all_to(MemoryRegionAddress(0), writer, region.len() as usize)
+            let region_start = region.start_addr().0;
+            let region_end = region_start + region.len();
+            // The start of the next range to write, relative to the region.
+            let mut cursor = 0;
+
+            for &(addr, len) in excluded {
+                let hole_start = addr.0.max(region_start) - region_start;
+                let hole_end = (addr.0 + len).min(region_end).saturating_sub(region_start);
+                if hole_start >= hole_end {
+                    continue;
+                }
+                if hole_start > cursor {
+                    write_range(region, writer, writer_offset, cursor, hole_start - cursor)?;
+                }
+                cursor = cursor.max(hole_end);
+            }
+            if region.len() > cursor {
+                write_range(region, writer, writer_offset, cursor, region.len() - cursor)?;
+            }
+
+            writer_offset += region.len();
+            Ok(())
         })
         .map_err(Error::WriteMemory)
     }
 
     /// Dumps all pages of GuestMemoryMmap present in `dirty_bitmap` to a writer.
+    /// The ones fully contained in the `excluded` ranges are written as zeros, so
+    /// that they don't keep stale contents once the diff is merged onto a base.
     fn dump_dirty<T: std::io::Write + std::io::Seek>(
         &self,
         writer: &mut T,
         dirty_bitmap: &DirtyBitmap,
+        excluded: &[(GuestAddress, u64)],
     ) -> std::result::Result<(), Error> {
         let mut writer_offset = 0;
         let page_size = get_page_size()?;
+        let zero_page = vec![0u8; page_size];
 
         self.with_regions_mut(|slot, region| {
             let kvm_bitmap = dirty_bitmap.get(&slot).unwrap();
@@ -135,7 +175,13 @@ impl SnapshotMemory for GuestMemoryMmap {
                     let is_kvm_page_dirty = ((v >> j) & 1u64) != 0u64;
                     let page_offset = ((i * 64) + j) * page_size;
                     let is_firecracker_page_dirty = firecracker_bitmap.is_addr_set(page_offset);
-                    if is_kvm_page_dirty || is_firecracker_page_dirty {
+                    let is_page_excluded = is_range_excluded(
+                        excluded,
+                        region.start_addr().0 + page_offset as u64,
+                        page_size as u64,
+                    );
+                    let is_page_dirty = is_kvm_page_dirty || is_firecracker_page_dirty;
+                    if is_page_dirty && !is_page_excluded {
                         // We are at the start of a new batch of dirty pages.
                         if write_size == 0 {
                             // Seek forward over the unmodified pages.
@@ -145,7 +191,10 @@ impl SnapshotMemory for GuestMemoryMmap {
                             dirty_batch_start = page_offset as u64;
                         }
                         write_size += page_size;
-                    } else if write_size > 0 {
+                        continue;
+                    }
+
+                    if write_size > 0 {
                         // We are at the end of a batch of dirty pages.
                         region.write_all_to(
                             MemoryRegionAddress(dirty_batch_start),
@@ -154,6 +203,13 @@ impl SnapshotMemory for GuestMemoryMmap {
                         )?;
                         write_size = 0;
                     }
+                    if is_page_dirty {
+                        // The page is excluded, but its previous contents may be in the base.
+                        writer
+                            .seek(SeekFrom::Start(writer_offset + page_offset as u64))
+                            .and_then(|_| writer.write_all(&zero_page))
+                            .map_err(GuestMemoryError::IOError)?;
+                    }
                 }
             }
 
@@ -204,6 +260,33 @@ impl SnapshotMemory for GuestMemoryMmap {
     }
 }
 
+// Writes `len` bytes at `offset` in `region` to the same offset in the region's part of
+// the writer, which starts at `writer_offset`.
+fn write_range<T: std::io::Write + std::io::Seek>(
+    region: &GuestRegionMmap,
+    writer: &mut T,
+    writer_offset: u64,
+    offset: u64,
+    len: u64,
+) -> std::result::Result<(), GuestMemoryError> {
+    writer
+        .seek(SeekFrom::Start(writer_offset + offset))
+        .map_err(GuestMemoryError::IOError)?;
+    region.write_all_to(MemoryRegionAddress(offset), writer, len as usize)
+}
+
+// Checks whether `[addr, addr + len)` is fully contained in one of the `excluded` ranges,
+// which are sorted and do not overlap or touch each other.
+fn is_range_excluded(excluded: &[(GuestAddress, u64)], addr: u64, len: u64) -> bool {
+    let idx = match excluded.binary_search_by_key(&addr, |&(start, _)| start.0) {
+        Ok(idx) => idx,
+        Err(0) => return false,
+        Err(idx) => idx - 1,
+    };
+    let (start, range_len) = excluded[idx];
+    addr + len <= start.0 + range_len
+}
+
 fn get_page_size() -> Result<usize, Error> {
     match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
         -1 => Err(Error::PageSize(errno::Error::last())),
@@ -216,7 +299,7 @@ mod tests {
     use std::collections::HashMap;
 
     use super::*;
-    use std::io::{Read, Seek};
+    use std::io::{Read, Seek, Write};
     use utils::tempfile::TempFile;
     use vm_memory::GuestAddress;
 
@@ -308,7 +391,7 @@ mod tests {
         // Case 1: dump the full memory.
         {
             let memory_file = TempFile::new().unwrap();
-            guest_memory.dump(&mut memory_file.as_file()).unwrap();
+            guest_memory.dump(&mut memory_file.as_file(), &[]).unwrap();
 
             let restored_guest_memory =
                 GuestMemoryMmap::restore(&memory_file.as_file(), &memory_state, false).unwrap();
@@ -340,7 +423,7 @@ mod tests {
 
             let file = TempFile::new().unwrap();
             guest_memory
-                .dump_dirty(&mut file.as_file(), &dirty_bitmap)
+                .dump_dirty(&mut file.as_file(), &dirty_bitmap, &[])
                 .unwrap();
 
             // We can restore from this because this is the first dirty dump.
@@ -376,7 +459,9 @@ mod tests {
                 .write(&twos[..], GuestAddress(page_size as u64))
                 .unwrap();
 
-            guest_memory.dump_dirty(&mut reader, &dirty_bitmap).unwrap();
+            guest_memory
+                .dump_dirty(&mut reader, &dirty_bitmap, &[])
+                .unwrap();
 
             // Check that only the dirty regions are dumped.
             let mut diff_file_content = Vec::new();
@@ -392,4 +477,97 @@ mod tests {
             assert_eq!(expected_first_region, diff_file_content);
         }
     }
+
+    #[test]
+    fn test_dump_excluded_ranges() {
+        let page_size: usize = get_page_size().unwrap();
+
+        // Two regions of two pages each, with a one page gap between them.
+        let mem_regions = [
+            (GuestAddress(0), page_size * 2),
+            (GuestAddress(page_size as u64 * 3), page_size * 2),
+        ];
+        let guest_memory = GuestMemoryMmap::from_ranges_with_tracking(&mem_regions[..]).unwrap();
+        let ones = vec![1u8; page_size * 2];
+        guest_memory.write(&ones[..], GuestAddress(0)).unwrap();
+        guest_memory
+            .write(&ones[..], GuestAddress(page_size as u64 * 3))
+            .unwrap();
+        let memory_state = guest_memory.describe();
+
+        // The second page of the first region, and the first page of the second one.
+        let excluded = [
+            (GuestAddress(page_size as u64), page_size as u64),
+            (GuestAddress(page_size as u64 * 3), page_size as u64),
+        ];
+        let zeros = vec![0u8; page_size];
+        let ones = vec![1u8; page_size];
+        let expected_content = [
+            ones.as_slice(),
+            zeros.as_slice(),
+            zeros.as_slice(),
+            ones.as_slice(),
+        ]
+        .concat();
+
+        // Case 1: the excluded ranges are holes in a full dump.
+        {
+            let memory_file = TempFile::new().unwrap();
+            let mut file = memory_file.as_file();
+            file.set_len(page_size as u64 * 4).unwrap();
+            guest_memory.dump(&mut file, &excluded).unwrap();
+
+            let mut file_content = Vec::new();
+            file.seek(SeekFrom::Start(0)).unwrap();
+            file.read_to_end(&mut file_content).unwrap();
+            assert_eq!(expected_content, file_content);
+
+            let restored_guest_memory =
+                GuestMemoryMmap::restore(&memory_file.as_file(), &memory_state, false).unwrap();
+            let mut actual_page = vec![0u8; page_size];
+            restored_guest_memory
+                .read(
+                    &mut actual_page.as_mut_slice(),
+                    GuestAddress(page_size as u64),
+                )
+                .unwrap();
+            assert_eq!(zeros, actual_page);
+        }
+
+        // Case 2: the dirty excluded pages are zeroed in a diff dump, overwriting the
+        // contents of the base.
+        {
+            let mut dirty_bitmap: DirtyBitmap = HashMap::new();
+            dirty_bitmap.insert(0, vec![0b11; 1]);
+            dirty_bitmap.insert(1, vec![0b11; 1]);
+
+            let file = TempFile::new().unwrap();
+            let mut reader = file.as_file();
+            reader.write_all(&vec![2u8; page_size * 4]).unwrap();
+            guest_memory
+                .dump_dirty(&mut reader, &dirty_bitmap, &excluded)
+                .unwrap();
+
+            let mut file_content = Vec::new();
+            reader.seek(SeekFrom::Start(0)).unwrap();
+            reader.read_to_end(&mut file_content).unwrap();
+            assert_eq!(expected_content, file_content);
+        }
+    }
+
+    #[test]
+    fn test_is_range_excluded() {
+        let excluded = [
+            (GuestAddress(0x1000), 0x2000),
+            (GuestAddress(0x8000), 0x1000),
+        ];
+        assert!(!is_range_excluded(&[], 0x1000, 0x1000));
+        assert!(!is_range_excluded(&excluded, 0, 0x1000));
+        assert!(is_range_excluded(&excluded, 0x1000, 0x1000));
+        assert!(is_range_excluded(&excluded, 0x2000, 0x1000));
+        assert!(!is_range_excluded(&excluded, 0x2000, 0x2000));
+        assert!(!is_range_excluded(&excluded, 0x3000, 0x1000));
+        assert!(is_range_excluded(&excluded, 0x8000, 0x1000));
+        assert!(!is_range_excluded(&excluded, 0x9000, 0x1000));
+    }
 }
@@ -5,7 +5,7 @@
 
 use std::fmt::{Display, Formatter};
 use std::fs::{File, OpenOptions};
-use std::io;
+use std::io::{self, Seek, SeekFrom, Write};
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
@@ -13,7 +13,10 @@ use crate::builder::{self, StartMicrovmError};
 use crate::device_manager::persist::Error as DevicePersistError;
 use crate::mem_size_mib;
 use crate::vmm_config::machine_config::MAX_SUPPORTED_VCPUS;
-use crate::vmm_config::snapshot::{CreateSnapshotParams, LoadSnapshotParams, SnapshotType};
+use crate::vmm_config::mmds::MmdsStores;
+use crate::vmm_config::snapshot::{
+    CreateSnapshotParams, LoadSnapshotParams, SnapshotProgress, SnapshotType,
+};
 use crate::vstate::{self, vcpu::VcpuState, vm::VmState};
 
 use crate::device_manager::persist::DeviceStates;
@@ -27,9 +30,11 @@ use cpuid::common::{get_vendor_id_from_cpuid, get_vendor_id_from_host};
 #[cfg(target_arch = "aarch64")]
 use arch::regs::{get_manufacturer_id_from_host, get_manufacturer_id_from_state};
 use logger::{error, info};
+use mmds::data_store::Mmds;
+use mmds::persist::{MmdsPersistError, MmdsState};
 use polly::event_manager::EventManager;
 use seccomp::BpfProgramRef;
-use snapshot::Snapshot;
+use snapshot::{Persist, Snapshot};
 use versionize::{VersionMap, Versionize, VersionizeResult};
 use versionize_derive::Versionize;
 use vm_memory::GuestMemoryMmap;
@@ -60,6 +65,32 @@ pub struct MicrovmState {
     pub vcpu_states: Vec<VcpuState>,
     /// Device states.
     pub device_states: DeviceStates,
+    /// MMDS data store.
+    #[version(start = 2, default_fn = "def_mmds_state")]
+    pub mmds_state: MmdsState,
+    /// MMDS data stores dedicated to network interfaces.
+    #[version(start = 2, default_fn = "def_iface_mmds_states")]
+    pub iface_mmds_states: Vec<IfaceMmdsState>,
+}
+
+impl MicrovmState {
+    fn def_mmds_state(_source_version: u16) -> MmdsState {
+        Mmds::default().save()
+    }
+
+    fn def_iface_mmds_states(_source_version: u16) -> Vec<IfaceMmdsState> {
+        Vec::new()
+    }
+}
+
+/// Holds the state of a MMDS data store dedicated to a network interface.
+#[derive(Versionize)]
+// NOTICE: Any changes to this structure require a snapshot version bump.
+pub struct IfaceMmdsState {
+    /// ID of the network interface.
+    pub iface_id: String,
+    /// MMDS data store.
+    pub mmds_state: MmdsState,
 }
 
 /// Errors related to saving and restoring Microvm state.
@@ -173,6 +204,8 @@ pub enum LoadSnapshotError {
     CpuVendorMismatch(String),
     /// Snapshot failed sanity checks.
     InvalidSnapshot(String),
+    /// Failed to restore the MMDS data store.
+    RestoreMmds(MmdsPersistError),
 }
 
 impl Display for LoadSnapshotError {
@@ -188,6 +221,7 @@ impl Display for LoadSnapshotError {
             SnapshotBackingFileMetadata(err) => write!(f, "Cannot retrieve file metadata: {}", err),
             CpuVendorMismatch(err) => write!(f, "Snapshot cpu vendor mismatch: {}", err),
             InvalidSnapshot(err) => write!(f, "Snapshot sanity check failed: {}", err),
+            RestoreMmds(err) => write!(f, "Cannot restore the MMDS data store: {}", err),
         }
     }
 }
@@ -202,7 +236,12 @@ pub fn create_snapshot(
         .save_state()
         .map_err(CreateSnapshotError::MicrovmState)?;
 
-    snapshot_memory_to_file(vmm, &params.mem_file_path, &params.snapshot_type)?;
+    snapshot_memory_to_file(
+        vmm,
+        &params.mem_file_path,
+        &params.snapshot_type,
+        &params.progress,
+    )?;
 
     let snapshot_data_version = get_snapshot_data_version(&params.version, &version_map, &vmm)?;
 
@@ -211,27 +250,57 @@ pub fn create_snapshot(
         &params.snapshot_path,
         snapshot_data_version,
         version_map,
+        &params.progress,
     )?;
 
     Ok(())
 }
 
+// Forwards the writes to `inner`, counting the bytes written in `progress`.
+struct ProgressWriter<'a, W> {
+    inner: W,
+    progress: &'a SnapshotProgress,
+}
+
+impl<W: Write> Write for ProgressWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let count = self.inner.write(buf)?;
+        self.progress.add(count as u64);
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Seek> Seek for ProgressWriter<'_, W> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
 fn snapshot_state_to_file(
     microvm_state: &MicrovmState,
     snapshot_path: &PathBuf,
     snapshot_data_version: u16,
     version_map: VersionMap,
+    progress: &SnapshotProgress,
 ) -> std::result::Result<(), CreateSnapshotError> {
     use self::CreateSnapshotError::*;
-    let mut snapshot_file = OpenOptions::new()
+    let snapshot_file = OpenOptions::new()
         .create(true)
         .write(true)
         .open(snapshot_path)
         .map_err(SnapshotBackingFile)?;
 
     let mut snapshot = Snapshot::new(version_map, snapshot_data_version);
+    let mut writer = ProgressWriter {
+        inner: snapshot_file,
+        progress,
+    };
     snapshot
-        .save(&mut snapshot_file, microvm_state)
+        .save(&mut writer, microvm_state)
         .map_err(SerializeMicrovmState)?;
 
     Ok(())
@@ -241,9 +310,10 @@ fn snapshot_memory_to_file(
     vmm: &Vmm,
     mem_file_path: &PathBuf,
     snapshot_type: &SnapshotType,
+    progress: &SnapshotProgress,
 ) -> std::result::Result<(), CreateSnapshotError> {
     use self::CreateSnapshotError::*;
-    let mut file = OpenOptions::new()
+    let file = OpenOptions::new()
         .write(true)
         .create(true)
         .truncate(true)
@@ -255,14 +325,24 @@ fn snapshot_memory_to_file(
     file.set_len((mem_size_mib * 1024 * 1024) as u64)
         .map_err(MemoryBackingFile)?;
 
+    // The pages held by the balloon are left out of the memory file.
+    let excluded = vmm.balloon_inflated_ranges();
+
+    let mut writer = ProgressWriter {
+        inner: file,
+        progress,
+    };
     match snapshot_type {
         SnapshotType::Diff => {
             let dirty_bitmap = vmm.get_dirty_bitmap().map_err(|_| DirtyBitmap)?;
             vmm.guest_memory()
-                .dump_dirty(&mut file, &dirty_bitmap)
+                .dump_dirty(&mut writer, &dirty_bitmap, &excluded)
                 .map_err(Memory)
         }
-        SnapshotType::Full => vmm.guest_memory().dump(&mut file).map_err(Memory),
+        SnapshotType::Full => vmm
+            .guest_memory()
+            .dump(&mut writer, &excluded)
+            .map_err(Memory),
     }
 }
 
@@ -384,6 +464,7 @@ pub fn restore_from_snapshot(
     seccomp_filter: BpfProgramRef,
     params: &LoadSnapshotParams,
     version_map: VersionMap,
+    mmds: &mut MmdsStores,
 ) -> std::result::Result<Arc<Mutex<Vmm>>, LoadSnapshotError> {
     use self::LoadSnapshotError::*;
     let track_dirty_pages = params.enable_diff_snapshots;
@@ -397,14 +478,48 @@ pub fn restore_from_snapshot(
         &microvm_state.memory_state,
         track_dirty_pages,
     )?;
-    builder::build_microvm_from_snapshot(
+    // The MMDS data stores are updated only once the microVM is built. Their contents are
+    // replaced only if populated when the snapshot was created, while their token mode is always
+    // restored.
+    let mut restored_mmds = vec![(
+        None,
+        Mmds::restore((), &microvm_state.mmds_state).map_err(RestoreMmds)?,
+    )];
+    // The network devices need their dedicated data stores at build time.
+    let mut stores = mmds.clone();
+    for iface_state in microvm_state.iface_mmds_states.iter() {
+        restored_mmds.push((
+            Some(iface_state.iface_id.clone()),
+            Mmds::restore((), &iface_state.mmds_state).map_err(RestoreMmds)?,
+        ));
+        stores.dedicate(&iface_state.iface_id);
+    }
+    let vmm = builder::build_microvm_from_snapshot(
         event_manager,
         microvm_state,
         guest_memory,
         track_dirty_pages,
+        params.vsock_reattach.iter().map(Into::into).collect(),
+        &stores,
         seccomp_filter,
     )
-    .map_err(BuildMicroVm)
+    .map_err(BuildMicroVm)?;
+    for (iface_id, restored) in restored_mmds {
+        // The unwrap is safe because the dedicated data stores were created above.
+        let mut locked_mmds = stores
+            .get(iface_id.as_deref())
+            .unwrap()
+            .lock()
+            .expect("Poisoned lock");
+        if restored.is_initialized() && !params.discard_mmds_data {
+            *locked_mmds = restored;
+        } else {
+            locked_mmds.set_token_mode(restored.token_mode());
+            locked_mmds.set_data_store_limit(restored.data_store_limit());
+        }
+    }
+    *mmds = stores;
+    Ok(vmm)
 }
 
 fn snapshot_state_from_file(
@@ -455,7 +570,6 @@ mod tests {
     use crate::Vmm;
 
     use polly::event_manager::EventManager;
-    use snapshot::Persist;
     use utils::{errno, tempfile::TempFile};
 
     #[cfg(target_arch = "aarch64")]
@@ -471,6 +585,8 @@ mod tests {
             amount_mb: 0,
             deflate_on_oom: false,
             stats_polling_interval_s: 0,
+            free_page_reporting: false,
+            auto_size: None,
         };
         insert_balloon_device(&mut vmm, &mut cmdline, &mut event_manager, balloon_config);
 
@@ -489,6 +605,7 @@ mod tests {
         let network_interface = NetworkInterfaceConfig {
             iface_id: String::from("netif"),
             host_dev_name: String::from("hostname"),
+            unix_socket_path: None,
             guest_mac: None,
             rx_rate_limiter: None,
             tx_rate_limiter: None,
@@ -520,7 +637,7 @@ mod tests {
         // is tested by that device's tests.
         assert_eq!(states.block_devices.len(), 1);
         assert_eq!(states.net_devices.len(), 1);
-        assert!(states.vsock_device.is_some());
+        assert_eq!(states.vsock_devices.len(), 1);
         assert!(states.balloon_device.is_some());
 
         let memory_state = vmm.guest_memory().describe();
@@ -534,6 +651,11 @@ mod tests {
             vm_state: vmm.vm.save_state(&[1]).unwrap(),
             #[cfg(target_arch = "x86_64")]
             vm_state: vmm.vm.save_state().unwrap(),
+            mmds_state: Mmds::default().save(),
+            iface_mmds_states: vec![IfaceMmdsState {
+                iface_id: String::from("netif"),
+                mmds_state: Mmds::default().save(),
+            }],
         };
 
         let mut buf = vec![0; 10000];
@@ -587,6 +709,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_progress_writer() {
+        let progress = SnapshotProgress::default();
+        let mut writer = ProgressWriter {
+            inner: io::Cursor::new(Vec::new()),
+            progress: &progress,
+        };
+
+        writer.write_all(&[0u8; 10]).unwrap();
+        assert_eq!(writer.seek(SeekFrom::Start(100)).unwrap(), 100);
+        writer.write_all(&[0u8; 5]).unwrap();
+        // Seeking over the holes doesn't count as writing.
+        assert_eq!(progress.bytes_written(), 15);
+        assert_eq!(writer.inner.get_ref().len(), 105);
+    }
+
     #[test]
     fn test_create_snapshot_error_display() {
         use crate::persist::CreateSnapshotError::*;
@@ -651,6 +789,11 @@ mod tests {
 
         let err = CpuVendorMismatch(String::new());
         let _ = format!("{}{:?}", err, err);
+
+        let err = RestoreMmds(MmdsPersistError::DataStore(
+            mmds::data_store::Error::NotInitialized,
+        ));
+        let _ = format!("{}{:?}", err, err);
     }
 
     #[test]
@@ -3,7 +3,10 @@
 
 #![deny(warnings)]
 
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
 use std::fs::File;
+use std::sync::{Arc, Mutex};
 
 use crate::vmm_config::balloon::*;
 use crate::vmm_config::boot_source::{
@@ -11,17 +14,25 @@ use crate::vmm_config::boot_source::{
 };
 use crate::vmm_config::drive::*;
 use crate::vmm_config::instance_info::InstanceInfo;
-use crate::vmm_config::logger::{init_logger, LoggerConfig, LoggerConfigError};
+use crate::vmm_config::logger::{
+    init_logger, init_logger_with_file, open_log_file, LoggerConfig, LoggerConfigError,
+};
 use crate::vmm_config::machine_config::{VmConfig, VmConfigError, DEFAULT_MEM_SIZE_MIB};
-use crate::vmm_config::metrics::{init_metrics, MetricsConfig, MetricsConfigError};
-use crate::vmm_config::mmds::{MmdsConfig, MmdsConfigError};
+use crate::vmm_config::metrics::{
+    init_metrics, init_metrics_with_file, open_metrics_file, MetricsConfig, MetricsConfigError,
+};
+use crate::vmm_config::mmds::{MmdsConfig, MmdsConfigError, MmdsStoreError, MmdsStores};
 use crate::vmm_config::net::*;
 use crate::vmm_config::vsock::*;
 use crate::vstate::vcpu::VcpuConfig;
+use devices::virtio::Net;
+use logger::error;
 use mmds::ns::MmdsNetworkStack;
 use utils::net::ipv4addr::is_link_local_valid;
+use utils::net::ipv6addr::is_link_local_valid as is_ipv6_link_local_valid;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 type Result<E> = std::result::Result<(), E>;
 
@@ -42,6 +53,8 @@ pub enum Error {
     Metrics(MetricsConfigError),
     /// MMDS configuration error.
     MmdsConfig(MmdsConfigError),
+    /// MMDS data store error.
+    MmdsStore(MmdsStoreError),
     /// Net device configuration error.
     NetDevice(NetworkInterfaceError),
     /// microVM vCpus or memory configuration error.
@@ -50,27 +63,174 @@ pub enum Error {
     VsockDevice(VsockConfigError),
 }
 
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        use self::Error::*;
+        match self {
+            BalloonDevice(err) => write!(f, "{}", err),
+            BlockDevice(err) => write!(f, "{}", err),
+            BootSource(err) => write!(f, "{}", err),
+            InvalidJson => write!(f, "The configuration is not valid JSON."),
+            Logger(err) => write!(f, "{}", err),
+            Metrics(err) => write!(f, "{}", err),
+            MmdsConfig(err) => write!(f, "{}", err),
+            MmdsStore(err) => write!(f, "{}", err),
+            NetDevice(err) => write!(f, "{}", err),
+            VmConfig(err) => write!(f, "{}", err),
+            VsockDevice(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+/// Errors encountered when validating a full configuration document, each one paired with the
+/// API path of the resource it was found in.
+#[derive(Debug)]
+pub struct FullConfigError(pub Vec<(String, Error)>);
+
+impl Display for FullConfigError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "Invalid microVM configuration:")?;
+        for (resource, err) in self.0.iter() {
+            write!(f, " [{}] {}", resource, err)?;
+        }
+        Ok(())
+    }
+}
+
 /// Used for configuring a vmm from one single json passed to the Firecracker process.
-#[derive(Deserialize)]
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
 pub struct VmmConfig {
-    #[serde(rename = "balloon")]
-    balloon_device: Option<BalloonDeviceConfig>,
+    /// The balloon device.
+    #[serde(rename = "balloon", skip_serializing_if = "Option::is_none")]
+    pub balloon_device: Option<BalloonDeviceConfig>,
+    /// The block devices.
     #[serde(rename = "drives")]
-    block_devices: Vec<BlockDeviceConfig>,
-    #[serde(rename = "boot-source")]
-    boot_source: BootSourceConfig,
-    #[serde(rename = "logger")]
-    logger: Option<LoggerConfig>,
-    #[serde(rename = "machine-config")]
-    machine_config: Option<VmConfig>,
-    #[serde(rename = "metrics")]
-    metrics: Option<MetricsConfig>,
-    #[serde(rename = "mmds-config")]
-    mmds_config: Option<MmdsConfig>,
+    pub block_devices: Vec<BlockDeviceConfig>,
+    /// The boot source, mandatory in the configuration file.
+    #[serde(rename = "boot-source", skip_serializing_if = "Option::is_none")]
+    pub boot_source: Option<BootSourceConfig>,
+    /// The logger.
+    #[serde(rename = "logger", skip_serializing_if = "Option::is_none")]
+    pub logger: Option<LoggerConfig>,
+    /// The vCPU and memory configuration.
+    #[serde(rename = "machine-config", skip_serializing_if = "Option::is_none")]
+    pub machine_config: Option<VmConfig>,
+    /// The metrics system.
+    #[serde(rename = "metrics", skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<MetricsConfig>,
+    /// The MMDS configuration applying to the shared data store.
+    #[serde(rename = "mmds-config", skip_serializing_if = "Option::is_none")]
+    pub mmds_config: Option<MmdsConfig>,
+    /// The MMDS configurations of the network interfaces with a dedicated data store.
+    #[serde(rename = "mmds-configs", default)]
+    pub mmds_configs: Vec<MmdsConfig>,
+    /// The network interfaces.
     #[serde(rename = "network-interfaces", default)]
-    net_devices: Vec<NetworkInterfaceConfig>,
-    #[serde(rename = "vsock")]
-    vsock_device: Option<VsockDeviceConfig>,
+    pub net_devices: Vec<NetworkInterfaceConfig>,
+    /// A single vsock device, as accepted by earlier versions of the configuration file.
+    #[serde(rename = "vsock", skip_serializing_if = "Option::is_none")]
+    pub vsock_device: Option<VsockDeviceConfig>,
+    /// The vsock devices.
+    #[serde(rename = "vsock-devices", default)]
+    pub vsock_devices: Vec<VsockDeviceConfig>,
+}
+
+impl VmmConfig {
+    /// Returns the configuration of `resource`, in the format accepted by the matching API
+    /// request, or `None` if the resource isn't configured.
+    pub fn resource_config(&self, resource: &ConfiguredResource) -> Option<Value> {
+        use self::ConfiguredResource::*;
+        match resource {
+            BootSource => self.boot_source.as_ref().map(to_value),
+            Drives(drive_id) => select(&self.block_devices, drive_id.as_deref(), |cfg| {
+                cfg.drive_id.as_str()
+            }),
+            Logger => self.logger.as_ref().map(to_value),
+            Metrics => self.metrics.as_ref().map(to_value),
+            NetworkInterfaces(iface_id) => select(&self.net_devices, iface_id.as_deref(), |cfg| {
+                cfg.iface_id.as_str()
+            }),
+            Vsock(vsock_id) => select(&self.vsock_devices, vsock_id.as_deref(), |cfg| {
+                cfg.vsock_id.as_str()
+            }),
+        }
+    }
+}
+
+impl From<&VmResources> for VmmConfig {
+    fn from(resources: &VmResources) -> Self {
+        let mut mmds_configs: Vec<MmdsConfig> =
+            resources.iface_mmds_configs.values().cloned().collect();
+        mmds_configs.sort_unstable_by(|a, b| a.iface_id.cmp(&b.iface_id));
+
+        VmmConfig {
+            balloon_device: resources.balloon.get_config().ok(),
+            block_devices: resources.block.configs(),
+            boot_source: resources.boot_source_config.clone(),
+            logger: resources.logger_config.clone(),
+            machine_config: Some(resources.vm_config.clone()),
+            metrics: resources.metrics_config.clone(),
+            mmds_config: resources.mmds_config.clone(),
+            mmds_configs,
+            net_devices: resources.net_builder.configs(),
+            vsock_device: None,
+            vsock_devices: resources.vsock.configs(),
+        }
+    }
+}
+
+fn to_value<T: Serialize>(cfg: &T) -> Value {
+    serde_json::to_value(cfg).expect("Configurations always serialize to JSON")
+}
+
+// Returns the configuration with the given ID if there is one, or all of them.
+fn select<T: Serialize>(cfgs: &[T], id: Option<&str>, cfg_id: fn(&T) -> &str) -> Option<Value> {
+    match id {
+        Some(id) => cfgs.iter().find(|cfg| cfg_id(cfg) == id).map(to_value),
+        None => Some(to_value(&cfgs)),
+    }
+}
+
+/// A microVM resource, or collection of resources, whose configuration can be read back.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfiguredResource {
+    /// The boot source.
+    BootSource,
+    /// The block device with the given ID, or all of them.
+    Drives(Option<String>),
+    /// The logger.
+    Logger,
+    /// The metrics system.
+    Metrics,
+    /// The network interface with the given ID, or all of them.
+    NetworkInterfaces(Option<String>),
+    /// The vsock device with the given ID, or all of them.
+    Vsock(Option<String>),
+}
+
+impl Display for ConfiguredResource {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        use self::ConfiguredResource::*;
+        match self {
+            BootSource => write!(f, "boot source"),
+            Drives(Some(id)) => write!(f, "drive {:?}", id),
+            Drives(None) => write!(f, "drives"),
+            Logger => write!(f, "logger"),
+            Metrics => write!(f, "metrics system"),
+            NetworkInterfaces(Some(id)) => write!(f, "network interface {:?}", id),
+            NetworkInterfaces(None) => write!(f, "network interfaces"),
+            Vsock(Some(id)) => write!(f, "vsock device {:?}", id),
+            Vsock(None) => write!(f, "vsock devices"),
+        }
+    }
+}
+
+// A configuration built from scratch by `VmResources::set_full_config`, along with the
+// files of the logger and the metrics system when they have to be initialized.
+struct FullConfig {
+    resources: VmResources,
+    logger: Option<(LoggerConfig, File)>,
+    metrics: Option<(MetricsConfig, File)>,
 }
 
 /// A data structure that encapsulates the device configurations
@@ -81,16 +241,28 @@ pub struct VmResources {
     vm_config: VmConfig,
     /// The boot configuration for this microVM.
     boot_config: Option<BootConfig>,
+    /// The boot source configuration `boot_config` was built from.
+    boot_source_config: Option<BootSourceConfig>,
+    /// The configuration the logger was initialized with, if configured through the API or
+    /// the configuration file.
+    logger_config: Option<LoggerConfig>,
+    /// The configuration the metrics system was initialized with.
+    metrics_config: Option<MetricsConfig>,
     /// The block devices.
     pub block: BlockBuilder,
-    /// The vsock device.
+    /// The vsock devices.
     pub vsock: VsockBuilder,
     /// The balloon device.
     pub balloon: BalloonBuilder,
     /// The network devices builder.
     pub net_builder: NetBuilder,
-    /// The configuration for `MmdsNetworkStack`.
+    /// The configuration for `MmdsNetworkStack`, applying to the network interfaces without a
+    /// dedicated MMDS data store.
     pub mmds_config: Option<MmdsConfig>,
+    /// The MMDS configurations of the network interfaces with a dedicated data store.
+    iface_mmds_configs: HashMap<String, MmdsConfig>,
+    /// The MMDS data stores.
+    pub mmds: MmdsStores,
     /// Whether or not to load boot timer device.
     pub boot_timer: bool,
 }
@@ -103,16 +275,20 @@ impl VmResources {
     ) -> std::result::Result<Self, Error> {
         let vmm_config: VmmConfig = serde_json::from_slice::<VmmConfig>(config_json.as_bytes())
             .map_err(|_| Error::InvalidJson)?;
+        // The boot source can only be left out when reading the configuration back.
+        let boot_source = vmm_config.boot_source.ok_or(Error::InvalidJson)?;
 
+        let mut resources: Self = Self::default();
         if let Some(logger) = vmm_config.logger {
-            init_logger(logger, instance_info).map_err(Error::Logger)?;
+            resources
+                .set_logger(logger, instance_info)
+                .map_err(Error::Logger)?;
         }
 
         if let Some(metrics) = vmm_config.metrics {
-            init_metrics(metrics).map_err(Error::Metrics)?;
+            resources.set_metrics(metrics).map_err(Error::Metrics)?;
         }
 
-        let mut resources: Self = Self::default();
         if let Some(machine_config) = vmm_config.machine_config {
             resources
                 .set_vm_config(&machine_config)
@@ -120,7 +296,7 @@ impl VmResources {
         }
 
         resources
-            .set_boot_source(vmm_config.boot_source)
+            .set_boot_source(boot_source)
             .map_err(Error::BootSource)?;
 
         for drive_config in vmm_config.block_devices.into_iter() {
@@ -135,7 +311,11 @@ impl VmResources {
                 .map_err(Error::NetDevice)?;
         }
 
-        if let Some(vsock_config) = vmm_config.vsock_device {
+        for vsock_config in vmm_config
+            .vsock_device
+            .into_iter()
+            .chain(vmm_config.vsock_devices.into_iter())
+        {
             resources
                 .set_vsock_device(vsock_config)
                 .map_err(Error::VsockDevice)?;
@@ -147,7 +327,11 @@ impl VmResources {
                 .map_err(Error::BalloonDevice)?;
         }
 
-        if let Some(mmds_config) = vmm_config.mmds_config {
+        for mmds_config in vmm_config
+            .mmds_config
+            .into_iter()
+            .chain(vmm_config.mmds_configs.into_iter())
+        {
             resources
                 .set_mmds_config(mmds_config)
                 .map_err(Error::MmdsConfig)?;
@@ -156,6 +340,237 @@ impl VmResources {
         Ok(resources)
     }
 
+    /// Replaces the configuration with the one described by `vmm_config`. Every resource is
+    /// validated before any of them is applied: if one of them is invalid, the current
+    /// configuration is kept and the errors of all the invalid resources are returned.
+    ///
+    /// The network interfaces and the vsock devices release their TAP devices and sockets
+    /// while the new configuration is built, since it may reuse them, and acquire them again if
+    /// it is rejected. The contents of the MMDS data stores are carried over. The logger and the
+    /// metrics system can't be reset, so they are only initialized once everything else is
+    /// valid and their files are open, and only if their configuration changed.
+    pub fn set_full_config(
+        &mut self,
+        vmm_config: VmmConfig,
+        instance_info: &InstanceInfo,
+    ) -> std::result::Result<(), FullConfigError> {
+        let net_configs = self.net_builder.configs();
+        let vsock_configs = self.vsock.configs();
+        self.release_devices();
+
+        let full_config = match self.build_full_config(vmm_config) {
+            Ok(full_config) => full_config,
+            Err(errors) => {
+                self.restore_devices(net_configs, vsock_configs);
+                return Err(FullConfigError(errors));
+            }
+        };
+        let mut resources = full_config.resources;
+        // Initializing the logger or the metrics system can only fail if another thread
+        // initialized it since their files were opened.
+        if let Some((logger, log_file)) = full_config.logger {
+            if let Err(err) = init_logger_with_file(&logger, log_file, instance_info) {
+                resources.release_devices();
+                self.restore_devices(net_configs, vsock_configs);
+                return Err(FullConfigError(vec![(
+                    "logger".to_string(),
+                    Error::Logger(err),
+                )]));
+            }
+            resources.logger_config = Some(logger);
+        }
+        if let Some((metrics, metrics_file)) = full_config.metrics {
+            if let Err(err) = init_metrics_with_file(metrics_file) {
+                // The logger stays initialized, but keeps the configuration it was
+                // initialized with.
+                self.logger_config = resources.logger_config.clone();
+                resources.release_devices();
+                self.restore_devices(net_configs, vsock_configs);
+                return Err(FullConfigError(vec![(
+                    "metrics".to_string(),
+                    Error::Metrics(err),
+                )]));
+            }
+            resources.metrics_config = Some(metrics);
+        }
+
+        *self = resources;
+        Ok(())
+    }
+
+    // Builds the resources described by `vmm_config` from scratch, along with the files of the
+    // logger and the metrics system if they have to be initialized. On failure, the errors of
+    // all the invalid resources are returned, and no socket is left behind.
+    fn build_full_config(
+        &self,
+        vmm_config: VmmConfig,
+    ) -> std::result::Result<FullConfig, Vec<(String, Error)>> {
+        let mut resources = Self {
+            logger_config: self.logger_config.clone(),
+            metrics_config: self.metrics_config.clone(),
+            boot_timer: self.boot_timer,
+            ..Default::default()
+        };
+        let mut errors = Vec::new();
+        let mut check = |resource: String, result: std::result::Result<(), Error>| {
+            if let Err(err) = result {
+                errors.push((resource, err));
+            }
+        };
+
+        if let Some(machine_config) = vmm_config.machine_config {
+            check(
+                "machine-config".to_string(),
+                resources
+                    .set_vm_config(&machine_config)
+                    .map_err(Error::VmConfig),
+            );
+        }
+
+        if let Some(boot_source) = vmm_config.boot_source {
+            check(
+                "boot-source".to_string(),
+                resources
+                    .set_boot_source(boot_source)
+                    .map_err(Error::BootSource),
+            );
+        }
+
+        for drive_config in vmm_config.block_devices.into_iter() {
+            check(
+                format!("drives/{}", drive_config.drive_id),
+                resources
+                    .set_block_device(drive_config)
+                    .map_err(Error::BlockDevice),
+            );
+        }
+
+        for net_config in vmm_config.net_devices.into_iter() {
+            check(
+                format!("network-interfaces/{}", net_config.iface_id),
+                resources
+                    .build_net_device(net_config)
+                    .map_err(Error::NetDevice),
+            );
+        }
+
+        for vsock_config in vmm_config
+            .vsock_device
+            .into_iter()
+            .chain(vmm_config.vsock_devices.into_iter())
+        {
+            check(
+                format!("vsock/{}", vsock_config.vsock_id),
+                resources
+                    .set_vsock_device(vsock_config)
+                    .map_err(Error::VsockDevice),
+            );
+        }
+
+        if let Some(balloon_config) = vmm_config.balloon_device {
+            check(
+                "balloon".to_string(),
+                resources
+                    .set_balloon_device(balloon_config)
+                    .map_err(Error::BalloonDevice),
+            );
+        }
+
+        for mmds_config in vmm_config
+            .mmds_config
+            .into_iter()
+            .chain(vmm_config.mmds_configs.into_iter())
+        {
+            check(
+                "mmds/config".to_string(),
+                resources
+                    .set_mmds_config(mmds_config)
+                    .map_err(Error::MmdsConfig),
+            );
+        }
+
+        // The contents of the data stores are copied to the new ones, whose size limits they
+        // have to fit in. A dedicated data store which holds data has to be kept.
+        let stores = std::iter::once((None, self.mmds.shared())).chain(
+            self.mmds
+                .dedicated()
+                .map(|(iface_id, mmds)| (Some(iface_id.as_str()), mmds)),
+        );
+        for (iface_id, mmds) in stores {
+            let mmds = mmds.lock().expect("Poisoned lock");
+            if !mmds.is_initialized() {
+                continue;
+            }
+            check(
+                iface_id.map_or("mmds".to_string(), |iface_id| {
+                    format!("mmds/interfaces/{}", iface_id)
+                }),
+                resources
+                    .mmds
+                    .put_data(iface_id, mmds.data_store_value())
+                    .map_err(Error::MmdsStore),
+            );
+        }
+
+        // Leave the logger and the metrics system alone if their configuration didn't change.
+        let mut logger = None;
+        if let Some(logger_config) = vmm_config.logger {
+            if resources.logger_config.as_ref() != Some(&logger_config) {
+                match open_log_file(&logger_config) {
+                    Ok(log_file) => logger = Some((logger_config, log_file)),
+                    Err(err) => check("logger".to_string(), Err(Error::Logger(err))),
+                }
+            }
+        }
+        let mut metrics = None;
+        if let Some(metrics_config) = vmm_config.metrics {
+            if resources.metrics_config.as_ref() != Some(&metrics_config) {
+                match open_metrics_file(&metrics_config) {
+                    Ok(metrics_file) => metrics = Some((metrics_config, metrics_file)),
+                    Err(err) => check("metrics".to_string(), Err(Error::Metrics(err))),
+                }
+            }
+        }
+
+        if !errors.is_empty() {
+            resources.release_devices();
+            return Err(errors);
+        }
+        Ok(FullConfig {
+            resources,
+            logger,
+            metrics,
+        })
+    }
+
+    // Drops the network interfaces and the vsock devices, releasing their TAP devices and
+    // removing their Unix sockets.
+    fn release_devices(&mut self) {
+        self.net_builder.clear();
+        self.vsock.clear();
+    }
+
+    // Acquires again the network interfaces and the vsock devices released by
+    // `set_full_config`, once the new configuration is rejected.
+    fn restore_devices(
+        &mut self,
+        net_configs: Vec<NetworkInterfaceConfig>,
+        vsock_configs: Vec<VsockDeviceConfig>,
+    ) {
+        for net_config in net_configs.into_iter() {
+            let iface_id = net_config.iface_id.clone();
+            if let Err(err) = self.build_net_device_internal(net_config) {
+                error!("Cannot restore the network interface {}: {}", iface_id, err);
+            }
+        }
+        for vsock_config in vsock_configs.into_iter() {
+            let vsock_id = vsock_config.vsock_id.clone();
+            if let Err(err) = self.vsock.insert(vsock_config) {
+                error!("Cannot restore the vsock device {}: {}", vsock_id, err);
+            }
+        }
+    }
+
     /// Returns a VcpuConfig based on the vm config.
     pub fn vcpu_config(&self) -> VcpuConfig {
         // The unwraps are ok to use because the values are initialized using defaults if not
@@ -288,6 +703,25 @@ impl VmResources {
             kernel_file,
             initrd_file,
         });
+        self.boot_source_config = Some(boot_source_cfg);
+        Ok(())
+    }
+
+    /// Initializes the logger, and keeps its configuration.
+    pub fn set_logger(
+        &mut self,
+        logger_cfg: LoggerConfig,
+        instance_info: &InstanceInfo,
+    ) -> Result<LoggerConfigError> {
+        init_logger(logger_cfg.clone(), instance_info)?;
+        self.logger_config = Some(logger_cfg);
+        Ok(())
+    }
+
+    /// Initializes the metrics system, and keeps its configuration.
+    pub fn set_metrics(&mut self, metrics_cfg: MetricsConfig) -> Result<MetricsConfigError> {
+        init_metrics(metrics_cfg.clone())?;
+        self.metrics_config = Some(metrics_cfg);
         Ok(())
     }
 
@@ -306,16 +740,58 @@ impl VmResources {
         &mut self,
         body: NetworkInterfaceConfig,
     ) -> Result<NetworkInterfaceError> {
-        self.net_builder.build(body).map(|net_device| {
-            // Update `Net` device `MmdsNetworkStack` IPv4 address.
-            match &self.mmds_config {
-                Some(cfg) => cfg.ipv4_addr().map_or((), |ipv4_addr| {
-                    if let Some(mmds_ns) = net_device.lock().expect("Poisoned lock").mmds_ns_mut() {
+        self.build_net_device_internal(body).map(|_| ())
+    }
+
+    /// Builds a network device to be hot-plugged into the running microVM. Unlike
+    /// `build_net_device()`, this never replaces an existing device.
+    pub fn build_net_device_for_hotplug(
+        &mut self,
+        body: NetworkInterfaceConfig,
+    ) -> std::result::Result<Arc<Mutex<Net>>, NetworkInterfaceError> {
+        if self
+            .net_builder
+            .iter()
+            .any(|net| net.lock().expect("Poisoned lock").id() == &body.iface_id)
+        {
+            return Err(NetworkInterfaceError::DeviceIdInUse(body.iface_id));
+        }
+        self.build_net_device_internal(body)
+    }
+
+    /// Returns the traffic statistics of the network device with `iface_id` ID.
+    pub fn net_interface_stats(
+        &self,
+        iface_id: &str,
+    ) -> std::result::Result<NetworkInterfaceStats, NetworkInterfaceError> {
+        self.net_builder.stats(iface_id)
+    }
+
+    /// Forgets the network device with `iface_id` ID.
+    pub fn remove_net_device(&mut self, iface_id: &str) -> Result<NetworkInterfaceError> {
+        self.net_builder.remove(iface_id).map(|_| ())
+    }
+
+    fn build_net_device_internal(
+        &mut self,
+        body: NetworkInterfaceConfig,
+    ) -> std::result::Result<Arc<Mutex<Net>>, NetworkInterfaceError> {
+        let mmds = self.mmds.for_iface(&body.iface_id);
+        let mmds_config = self
+            .iface_mmds_configs
+            .get(&body.iface_id)
+            .or_else(|| self.mmds_config.as_ref());
+        self.net_builder.build(body, mmds).map(|net_device| {
+            // Update `Net` device `MmdsNetworkStack` IPv4 and IPv6 addresses.
+            if let Some(mmds_config) = mmds_config {
+                if let Some(mmds_ns) = net_device.lock().expect("Poisoned lock").mmds_ns_mut() {
+                    if let Some(ipv4_addr) = mmds_config.ipv4_addr() {
                         mmds_ns.set_ipv4_addr(ipv4_addr);
-                    };
-                }),
-                None => (),
-            };
+                    }
+                    mmds_ns.set_ipv6_addr(mmds_config.ipv6_addr());
+                }
+            }
+            net_device
         })
     }
 
@@ -324,7 +800,9 @@ impl VmResources {
         self.vsock.insert(config)
     }
 
-    /// Setter for mmds config.
+    /// Setter for mmds config. A configuration with a network interface ID dedicates a data
+    /// store to that interface, while one without applies to the shared data store and to all
+    /// the interfaces without a dedicated one.
     pub fn set_mmds_config(&mut self, config: MmdsConfig) -> Result<MmdsConfigError> {
         // Check IPv4 address validity.
         let ipv4_addr = match config.ipv4_addr() {
@@ -332,15 +810,65 @@ impl VmResources {
             None => Ok(MmdsNetworkStack::default_ipv4_addr()),
             _ => Err(MmdsConfigError::InvalidIpv4Addr),
         }?;
-
-        // Update existing built network device `MmdsNetworkStack` IPv4 address.
-        for net_device in self.net_builder.iter_mut() {
-            if let Some(mmds_ns) = net_device.lock().expect("Poisoned lock").mmds_ns_mut() {
-                mmds_ns.set_ipv4_addr(ipv4_addr)
+        // Check IPv6 address validity.
+        let ipv6_addr = match config.ipv6_addr() {
+            Some(ipv6_addr) if !is_ipv6_link_local_valid(ipv6_addr) => {
+                return Err(MmdsConfigError::InvalidIpv6Addr)
             }
+            ipv6_addr => ipv6_addr,
+        };
+        // The vsock port isn't tied to any network interface.
+        if config.iface_id().is_some() && config.vsock_port().is_some() {
+            return Err(MmdsConfigError::InvalidVsockPort);
         }
 
-        self.mmds_config = Some(config);
+        match config.iface_id() {
+            Some(iface_id) => {
+                let net_device = self
+                    .net_builder
+                    .iter()
+                    .find(|net| net.lock().expect("Poisoned lock").id() == iface_id)
+                    .ok_or_else(|| MmdsConfigError::InvalidNetworkInterfaceId(iface_id.into()))?;
+                let mut net_device = net_device.lock().expect("Poisoned lock");
+                let mmds_ns = net_device
+                    .mmds_ns_mut()
+                    .ok_or_else(|| MmdsConfigError::InvalidNetworkInterfaceId(iface_id.into()))?;
+
+                let mmds = self.mmds.dedicate(iface_id);
+                {
+                    let mut locked_mmds = mmds.lock().expect("Poisoned lock");
+                    locked_mmds.set_token_mode(config.token_mode());
+                    locked_mmds.set_data_store_limit(config.data_store_limit());
+                }
+                mmds_ns.set_ipv4_addr(ipv4_addr);
+                mmds_ns.set_ipv6_addr(ipv6_addr);
+                mmds_ns.set_mmds(mmds);
+
+                self.iface_mmds_configs.insert(iface_id.to_string(), config);
+            }
+            None => {
+                // Update the `MmdsNetworkStack` IPv4 and IPv6 addresses of the existing built
+                // network devices without a dedicated configuration.
+                for net_device in self.net_builder.iter_mut() {
+                    let mut net_device = net_device.lock().expect("Poisoned lock");
+                    if self.iface_mmds_configs.contains_key(net_device.id()) {
+                        continue;
+                    }
+                    if let Some(mmds_ns) = net_device.mmds_ns_mut() {
+                        mmds_ns.set_ipv4_addr(ipv4_addr);
+                        mmds_ns.set_ipv6_addr(ipv6_addr);
+                    }
+                }
+
+                {
+                    let mut locked_mmds = self.mmds.shared().lock().expect("Poisoned lock");
+                    locked_mmds.set_token_mode(config.token_mode());
+                    locked_mmds.set_data_store_limit(config.data_store_limit());
+                }
+
+                self.mmds_config = Some(config);
+            }
+        }
         Ok(())
     }
 }
@@ -348,6 +876,7 @@ impl VmResources {
 #[cfg(test)]
 mod tests {
     use std::fs::File;
+    use std::net::Ipv4Addr;
     use std::os::linux::fs::MetadataExt;
 
     use super::*;
@@ -355,11 +884,13 @@ mod tests {
     use crate::vmm_config::boot_source::{BootConfig, BootSourceConfig, DEFAULT_KERNEL_CMDLINE};
     use crate::vmm_config::drive::{BlockBuilder, BlockDeviceConfig};
     use crate::vmm_config::machine_config::{CpuFeaturesTemplate, VmConfig, VmConfigError};
+    use crate::vmm_config::mmds::TokenMode;
     use crate::vmm_config::net::{NetBuilder, NetworkInterfaceConfig};
     use crate::vmm_config::vsock::tests::default_config;
     use crate::vmm_config::RateLimiterConfig;
     use crate::vstate::vcpu::VcpuConfig;
     use logger::{LevelFilter, LOGGER};
+    use mmds::data_store::DEFAULT_DATA_STORE_LIMIT;
     use utils::net::mac::MacAddr;
     use utils::tempfile::TempFile;
 
@@ -373,6 +904,7 @@ mod tests {
                 .to_str()
                 .unwrap()
                 .to_string(),
+            unix_socket_path: None,
             guest_mac: Some(MacAddr::parse_str("01:23:45:67:89:0a").unwrap()),
             rx_rate_limiter: Some(RateLimiterConfig::default()),
             tx_rate_limiter: Some(RateLimiterConfig::default()),
@@ -382,7 +914,9 @@ mod tests {
 
     fn default_net_builder() -> NetBuilder {
         let mut net_builder = NetBuilder::new();
-        net_builder.build(default_net_cfg()).unwrap();
+        net_builder
+            .build(default_net_cfg(), Arc::default())
+            .unwrap();
 
         net_builder
     }
@@ -425,11 +959,16 @@ mod tests {
         VmResources {
             vm_config: VmConfig::default(),
             boot_config: Some(default_boot_cfg()),
+            boot_source_config: None,
+            logger_config: None,
+            metrics_config: None,
             block: default_blocks(),
             vsock: Default::default(),
             balloon: Default::default(),
             net_builder: default_net_builder(),
             mmds_config: None,
+            iface_mmds_configs: HashMap::new(),
+            mmds: Default::default(),
             boot_timer: false,
         }
     }
@@ -758,6 +1297,218 @@ mod tests {
         assert!(VmResources::from_json(json.as_str(), &default_instance_info).is_ok());
     }
 
+    #[test]
+    fn test_vmm_config_from_resources() {
+        let kernel_file = TempFile::new().unwrap();
+        let rootfs_file = TempFile::new().unwrap();
+        let instance_info = InstanceInfo {
+            id: "".to_string(),
+            state: "Not started".to_string(),
+            vmm_version: "SOME_VERSION".to_string(),
+            app_name: "".to_string(),
+        };
+
+        let json = format!(
+            r#"{{
+                    "boot-source": {{
+                        "kernel_image_path": "{}",
+                        "boot_args": "console=ttyS0 reboot=k panic=1 pci=off"
+                    }},
+                    "drives": [
+                        {{
+                            "drive_id": "rootfs",
+                            "path_on_host": "{}",
+                            "is_root_device": true,
+                            "is_read_only": false,
+                            "rate_limiter": {{
+                                "bandwidth": {{
+                                    "size": 1000,
+                                    "refill_time": 100
+                                }}
+                            }}
+                        }}
+                    ],
+                    "network-interfaces": [
+                        {{
+                            "iface_id": "netif",
+                            "host_dev_name": "hostname10",
+                            "guest_mac": "06:00:00:00:00:01",
+                            "allow_mmds_requests": true
+                        }}
+                    ],
+                    "machine-config": {{
+                        "vcpu_count": 2,
+                        "mem_size_mib": 1024,
+                        "ht_enabled": false
+                    }},
+                    "mmds-configs": [
+                        {{
+                            "ipv4_address": "169.254.170.2",
+                            "iface_id": "netif"
+                        }}
+                    ]
+            }}"#,
+            kernel_file.as_path().to_str().unwrap(),
+            rootfs_file.as_path().to_str().unwrap(),
+        );
+        let vm_resources = VmResources::from_json(json.as_str(), &instance_info).unwrap();
+        let vmm_config = VmmConfig::from(&vm_resources);
+        assert_eq!(vmm_config.block_devices.len(), 1);
+        assert!(vmm_config.block_devices[0].rate_limiter.is_some());
+        assert_eq!(vmm_config.net_devices.len(), 1);
+        assert_eq!(vmm_config.mmds_configs.len(), 1);
+        assert!(vmm_config.mmds_config.is_none());
+        assert_eq!(
+            vmm_config.resource_config(&ConfiguredResource::NetworkInterfaces(Some(
+                "netif".to_string()
+            ))),
+            Some(serde_json::to_value(&vmm_config.net_devices[0]).unwrap())
+        );
+        assert!(vmm_config
+            .resource_config(&ConfiguredResource::Drives(Some("other".to_string())))
+            .is_none());
+        assert!(vmm_config
+            .resource_config(&ConfiguredResource::Logger)
+            .is_none());
+
+        // The configuration read back reproduces the same resources.
+        let json = serde_json::to_string(&vmm_config).unwrap();
+        drop(vm_resources);
+        let vm_resources = VmResources::from_json(json.as_str(), &instance_info).unwrap();
+        assert_eq!(VmmConfig::from(&vm_resources), vmm_config);
+    }
+
+    #[test]
+    fn test_set_full_config() {
+        let kernel_file = TempFile::new().unwrap();
+        let rootfs_file = TempFile::new().unwrap();
+        let instance_info = InstanceInfo {
+            id: "".to_string(),
+            state: "Not started".to_string(),
+            vmm_version: "SOME_VERSION".to_string(),
+            app_name: "".to_string(),
+        };
+        let mut vm_resources = VmResources {
+            boot_timer: true,
+            ..Default::default()
+        };
+        let mut tmp_sock_file = TempFile::new().unwrap();
+        tmp_sock_file.remove().unwrap();
+        let vsock_config = default_config(&tmp_sock_file);
+        vm_resources.set_vsock_device(vsock_config.clone()).unwrap();
+        vm_resources
+            .mmds
+            .put_data(None, serde_json::json!({"key": "value"}))
+            .unwrap();
+
+        // All the invalid resources are reported, and none of the valid ones is applied.
+        let json = format!(
+            r#"{{
+                    "boot-source": {{
+                        "kernel_image_path": "{}"
+                    }},
+                    "drives": [
+                        {{
+                            "drive_id": "rootfs",
+                            "path_on_host": "/invalid/path",
+                            "is_root_device": true,
+                            "is_read_only": false
+                        }}
+                    ],
+                    "machine-config": {{
+                        "vcpu_count": 0,
+                        "mem_size_mib": 1024,
+                        "ht_enabled": false
+                    }},
+                    "vsock-devices": [
+                        {{
+                            "vsock_id": "vsock2",
+                            "guest_cid": 4,
+                            "uds_path": "{}"
+                        }}
+                    ]
+            }}"#,
+            kernel_file.as_path().to_str().unwrap(),
+            vsock_config.uds_path,
+        );
+        let vmm_config: VmmConfig = serde_json::from_str(&json).unwrap();
+        let err = vm_resources
+            .set_full_config(vmm_config, &instance_info)
+            .unwrap_err();
+        let resources: Vec<&str> = err
+            .0
+            .iter()
+            .map(|(resource, _)| resource.as_str())
+            .collect();
+        assert_eq!(resources, vec!["machine-config", "drives/rootfs"]);
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Invalid microVM configuration: [machine-config] {} [drives/rootfs] {}",
+                VmConfigError::InvalidVcpuCount,
+                DriveError::InvalidBlockDevicePath
+            )
+        );
+        assert!(vm_resources.boot_source_config.is_none());
+        assert_eq!(vm_resources.vm_config().vcpu_count, Some(1));
+        // The socket of the new vsock device is removed, and the current device is restored.
+        assert_eq!(vm_resources.vsock.configs().len(), 1);
+        assert!(vm_resources.vsock.get(&vsock_config.vsock_id).is_some());
+        assert!(std::path::Path::new(&vsock_config.uds_path).exists());
+
+        // The contents of the MMDS data store have to fit in the new one.
+        let json = r#"{
+                "mmds-config": {
+                    "data_store_limit": 1
+                }
+        }"#;
+        let vmm_config: VmmConfig = serde_json::from_str(json).unwrap();
+        let err = vm_resources
+            .set_full_config(vmm_config, &instance_info)
+            .unwrap_err();
+        assert_eq!(err.0.len(), 1);
+        assert_eq!(err.0[0].0, "mmds");
+
+        // A valid configuration replaces the current one.
+        let json = format!(
+            r#"{{
+                    "boot-source": {{
+                        "kernel_image_path": "{}"
+                    }},
+                    "drives": [
+                        {{
+                            "drive_id": "rootfs",
+                            "path_on_host": "{}",
+                            "is_root_device": true,
+                            "is_read_only": false
+                        }}
+                    ],
+                    "machine-config": {{
+                        "vcpu_count": 2,
+                        "mem_size_mib": 1024,
+                        "ht_enabled": false
+                    }}
+            }}"#,
+            kernel_file.as_path().to_str().unwrap(),
+            rootfs_file.as_path().to_str().unwrap(),
+        );
+        let vmm_config: VmmConfig = serde_json::from_str(&json).unwrap();
+        vm_resources
+            .set_full_config(vmm_config, &instance_info)
+            .unwrap();
+        assert!(vm_resources.boot_source_config.is_some());
+        assert_eq!(vm_resources.vm_config().vcpu_count, Some(2));
+        assert_eq!(vm_resources.block.configs().len(), 1);
+        assert!(vm_resources.vsock.configs().is_empty());
+        assert!(!std::path::Path::new(&vsock_config.uds_path).exists());
+        assert_eq!(
+            vm_resources.mmds.get_data(None).unwrap(),
+            serde_json::json!({"key": "value"})
+        );
+        // The boot timer is set on the command line, not in the configuration.
+        assert!(vm_resources.boot_timer);
+    }
+
     #[test]
     fn test_vcpu_config() {
         let vm_resources = default_vm_resources();
@@ -821,6 +1572,8 @@ mod tests {
                 amount_mb: 100,
                 deflate_on_oom: false,
                 stats_polling_interval_s: 0,
+                free_page_reporting: false,
+                auto_size: None,
             })
             .unwrap();
         aux_vm_config.mem_size_mib = Some(90);
@@ -839,17 +1592,24 @@ mod tests {
         let mut vm_resources = VmResources {
             vm_config: VmConfig::default(),
             boot_config: Some(default_boot_cfg()),
+            boot_source_config: None,
+            logger_config: None,
+            metrics_config: None,
             block: default_blocks(),
             vsock: Default::default(),
             balloon: BalloonBuilder::new(),
             net_builder: default_net_builder(),
             mmds_config: None,
+            iface_mmds_configs: HashMap::new(),
+            mmds: Default::default(),
             boot_timer: false,
         };
         let mut new_balloon_cfg = BalloonDeviceConfig {
             amount_mb: 100,
             deflate_on_oom: false,
             stats_polling_interval_s: 0,
+            free_page_reporting: true,
+            auto_size: None,
         };
         assert!(vm_resources.balloon.get().is_none());
         vm_resources
@@ -866,15 +1626,21 @@ mod tests {
             actual_balloon_cfg.stats_polling_interval_s,
             new_balloon_cfg.stats_polling_interval_s
         );
+        assert!(actual_balloon_cfg.free_page_reporting);
 
         vm_resources = VmResources {
             vm_config: VmConfig::default(),
             boot_config: Some(default_boot_cfg()),
+            boot_source_config: None,
+            logger_config: None,
+            metrics_config: None,
             block: default_blocks(),
             vsock: Default::default(),
             balloon: BalloonBuilder::new(),
             net_builder: default_net_builder(),
             mmds_config: None,
+            iface_mmds_configs: HashMap::new(),
+            mmds: Default::default(),
             boot_timer: false,
         };
         new_balloon_cfg.amount_mb = 256;
@@ -951,15 +1717,23 @@ mod tests {
         let mut tmp_sock_file = TempFile::new().unwrap();
         tmp_sock_file.remove().unwrap();
         let new_vsock_cfg = default_config(&tmp_sock_file);
-        assert!(vm_resources.vsock.get().is_none());
+        assert!(vm_resources.vsock.get(&new_vsock_cfg.vsock_id).is_none());
         vm_resources
             .set_vsock_device(new_vsock_cfg.clone())
             .unwrap();
-        let actual_vsock_cfg = vm_resources.vsock.get().unwrap();
+        let actual_vsock_cfg = vm_resources.vsock.get(&new_vsock_cfg.vsock_id).unwrap();
         assert_eq!(
             actual_vsock_cfg.lock().unwrap().id(),
             &new_vsock_cfg.vsock_id
         );
+
+        // Add a second device.
+        let mut tmp_sock_file = TempFile::new().unwrap();
+        tmp_sock_file.remove().unwrap();
+        let mut second_vsock_cfg = default_config(&tmp_sock_file);
+        second_vsock_cfg.vsock_id = "vsock2".to_string();
+        vm_resources.set_vsock_device(second_vsock_cfg).unwrap();
+        assert_eq!(vm_resources.vsock.iter().count(), 2);
     }
 
     #[test]
@@ -973,7 +1747,160 @@ mod tests {
         new_net_device_cfg.host_dev_name = "dummy_path2".to_string();
         assert_eq!(vm_resources.net_builder.len(), 1);
 
-        vm_resources.build_net_device(new_net_device_cfg).unwrap();
+        vm_resources
+            .build_net_device(new_net_device_cfg.clone())
+            .unwrap();
+        assert_eq!(vm_resources.net_builder.len(), 2);
+
+        // Hot-plugged devices cannot replace existing ones.
+        assert_eq!(
+            vm_resources
+                .build_net_device_for_hotplug(new_net_device_cfg)
+                .err()
+                .unwrap()
+                .to_string(),
+            NetworkInterfaceError::DeviceIdInUse("new_net_if".to_string()).to_string()
+        );
         assert_eq!(vm_resources.net_builder.len(), 2);
+
+        vm_resources.remove_net_device("new_net_if").unwrap();
+        assert_eq!(vm_resources.net_builder.len(), 1);
+        assert!(vm_resources.remove_net_device("new_net_if").is_err());
+    }
+
+    #[test]
+    fn test_set_mmds_config() {
+        let mut vm_resources = default_vm_resources();
+        let mut mmds_net_cfg = default_net_cfg();
+        mmds_net_cfg.iface_id = "mmds_net_if".to_string();
+        mmds_net_cfg.guest_mac = Some(MacAddr::parse_str("01:23:45:67:89:0d").unwrap());
+        mmds_net_cfg.allow_mmds_requests = true;
+        vm_resources.build_net_device(mmds_net_cfg).unwrap();
+
+        let mmds_config = |ipv4: &str, iface_id: Option<&str>| MmdsConfig {
+            ipv4_address: Some(ipv4.parse().unwrap()),
+            token_mode: TokenMode::Required,
+            iface_id: iface_id.map(str::to_string),
+            data_store_limit: None,
+            ipv6_address: None,
+            vsock_port: None,
+        };
+        let net_mmds = |vm_resources: &VmResources| {
+            let net = vm_resources
+                .net_builder
+                .iter()
+                .find(|net| net.lock().unwrap().id() == "mmds_net_if")
+                .unwrap()
+                .clone();
+            let net = net.lock().unwrap();
+            let mmds_ns = net.mmds_ns().unwrap();
+            (mmds_ns.ipv4_addr(), mmds_ns.mmds().clone())
+        };
+
+        // Invalid IPv4 address.
+        assert!(vm_resources
+            .set_mmds_config(mmds_config("10.0.0.1", None))
+            .is_err());
+        // Invalid IPv6 address.
+        let mut invalid_cfg = mmds_config("169.254.0.1", None);
+        invalid_cfg.ipv6_address = Some("fd00::1".parse().unwrap());
+        assert_eq!(
+            vm_resources
+                .set_mmds_config(invalid_cfg)
+                .unwrap_err()
+                .to_string(),
+            MmdsConfigError::InvalidIpv6Addr.to_string()
+        );
+        // The vsock port is only valid for the shared configuration.
+        let mut invalid_cfg = mmds_config("169.254.0.1", Some("mmds_net_if"));
+        invalid_cfg.vsock_port = Some(52);
+        assert_eq!(
+            vm_resources
+                .set_mmds_config(invalid_cfg)
+                .unwrap_err()
+                .to_string(),
+            MmdsConfigError::InvalidVsockPort.to_string()
+        );
+        // Unknown interface, and interface not allowing MMDS requests.
+        for iface_id in &["unknown", "net_if1"] {
+            assert_eq!(
+                vm_resources
+                    .set_mmds_config(mmds_config("169.254.0.1", Some(*iface_id)))
+                    .unwrap_err()
+                    .to_string(),
+                MmdsConfigError::InvalidNetworkInterfaceId(iface_id.to_string()).to_string()
+            );
+        }
+
+        // The shared configuration applies to the interfaces without a dedicated one.
+        let mut shared_cfg = mmds_config("169.254.0.1", None);
+        shared_cfg.ipv6_address = Some("fe80::a9fe:a9fe".parse().unwrap());
+        shared_cfg.vsock_port = Some(52);
+        vm_resources.set_mmds_config(shared_cfg).unwrap();
+        let (ipv4_addr, mmds) = net_mmds(&vm_resources);
+        assert_eq!(ipv4_addr, "169.254.0.1".parse::<Ipv4Addr>().unwrap());
+        assert_eq!(
+            vm_resources
+                .net_builder
+                .iter()
+                .find(|net| net.lock().unwrap().id() == "mmds_net_if")
+                .unwrap()
+                .lock()
+                .unwrap()
+                .mmds_ns()
+                .unwrap()
+                .ipv6_addr(),
+            Some("fe80::a9fe:a9fe".parse().unwrap())
+        );
+        assert_eq!(
+            vm_resources.mmds_config.as_ref().unwrap().vsock_port(),
+            Some(52)
+        );
+        assert!(Arc::ptr_eq(&mmds, vm_resources.mmds.shared()));
+        assert_eq!(
+            vm_resources.mmds.shared().lock().unwrap().token_mode(),
+            TokenMode::Required
+        );
+
+        // A dedicated configuration switches the interface to its own data store.
+        let mut dedicated_cfg = mmds_config("169.254.0.2", Some("mmds_net_if"));
+        dedicated_cfg.token_mode = TokenMode::Optional;
+        dedicated_cfg.data_store_limit = Some(1024);
+        vm_resources.set_mmds_config(dedicated_cfg).unwrap();
+        let (ipv4_addr, mmds) = net_mmds(&vm_resources);
+        assert_eq!(ipv4_addr, "169.254.0.2".parse::<Ipv4Addr>().unwrap());
+        let dedicated = vm_resources.mmds.get(Some("mmds_net_if")).unwrap();
+        assert!(Arc::ptr_eq(&mmds, dedicated));
+        assert_eq!(dedicated.lock().unwrap().token_mode(), TokenMode::Optional);
+        assert_eq!(dedicated.lock().unwrap().data_store_limit(), 1024);
+        assert_eq!(
+            vm_resources
+                .mmds
+                .shared()
+                .lock()
+                .unwrap()
+                .data_store_limit(),
+            DEFAULT_DATA_STORE_LIMIT
+        );
+
+        // The shared configuration no longer applies to it, including when rebuilt.
+        vm_resources
+            .set_mmds_config(mmds_config("169.254.0.3", None))
+            .unwrap();
+        assert_eq!(
+            net_mmds(&vm_resources).0,
+            "169.254.0.2".parse::<Ipv4Addr>().unwrap()
+        );
+        let mut mmds_net_cfg = default_net_cfg();
+        mmds_net_cfg.iface_id = "mmds_net_if".to_string();
+        mmds_net_cfg.guest_mac = Some(MacAddr::parse_str("01:23:45:67:89:0d").unwrap());
+        mmds_net_cfg.allow_mmds_requests = true;
+        vm_resources.build_net_device(mmds_net_cfg).unwrap();
+        let (ipv4_addr, mmds) = net_mmds(&vm_resources);
+        assert_eq!(ipv4_addr, "169.254.0.2".parse::<Ipv4Addr>().unwrap());
+        assert!(Arc::ptr_eq(
+            &mmds,
+            vm_resources.mmds.get(Some("mmds_net_if")).unwrap()
+        ));
     }
 }
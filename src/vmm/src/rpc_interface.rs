@@ -13,6 +13,7 @@ use super::{
 };
 use crate::builder::StartMicrovmError;
 use crate::persist::{CreateSnapshotError, LoadSnapshotError};
+use crate::resources::{ConfiguredResource, FullConfigError, VmmConfig};
 use crate::version_map::VERSION_MAP;
 use crate::vmm_config::balloon::{
     BalloonConfigError, BalloonDeviceConfig, BalloonStats, BalloonUpdateConfig,
@@ -24,16 +25,18 @@ use crate::vmm_config::instance_info::InstanceInfo;
 use crate::vmm_config::logger::{LoggerConfig, LoggerConfigError};
 use crate::vmm_config::machine_config::{VmConfig, VmConfigError};
 use crate::vmm_config::metrics::{MetricsConfig, MetricsConfigError};
-use crate::vmm_config::mmds::{MmdsConfig, MmdsConfigError};
+use crate::vmm_config::mmds::{MmdsConfig, MmdsConfigError, MmdsStoreError};
 use crate::vmm_config::net::{
-    NetworkInterfaceConfig, NetworkInterfaceError, NetworkInterfaceUpdateConfig,
+    NetworkInterfaceConfig, NetworkInterfaceError, NetworkInterfaceStats,
+    NetworkInterfaceUpdateConfig,
 };
 use crate::vmm_config::snapshot::{CreateSnapshotParams, LoadSnapshotParams, SnapshotType};
 use crate::vmm_config::vsock::{VsockConfigError, VsockDeviceConfig};
-use crate::vmm_config::{self, RateLimiterUpdate};
-use logger::{info, update_metric_with_elapsed_time, METRICS};
+use crate::vmm_config::RateLimiterUpdate;
+use logger::{info, update_metric_with_elapsed_time, VmEvent, EVENTS, METRICS};
 use polly::event_manager::EventManager;
 use seccomp::BpfProgram;
+use serde_json::Value;
 #[cfg(test)]
 use tests::{
     build_microvm_for_boot, create_snapshot, restore_from_snapshot, MockVmRes as VmResources,
@@ -58,31 +61,53 @@ pub enum VmmAction {
     CreateSnapshot(CreateSnapshotParams),
     /// Get the balloon device configuration.
     GetBalloonConfig,
+    /// Get the contents of the MMDS data store dedicated to the given network interface, or of
+    /// the shared one if no interface is given.
+    GetMmds(Option<String>),
     /// Get the ballon device latest statistics.
     GetBalloonStats,
+    /// Get the traffic statistics of a network interface.
+    GetNetworkInterfaceStats(String),
+    /// Get the configuration of a microVM resource, or of a collection of resources.
+    GetResourceConfig(ConfiguredResource),
     /// Get the configuration of the microVM.
     GetVmConfiguration,
+    /// Get the complete effective configuration of the microVM, in the format accepted by
+    /// `--config-file`.
+    GetFullVmConfig,
     /// Flush the metrics. This action can only be called after the logger has been configured.
     FlushMetrics,
     /// Add a new block device or update one that already exists using the `BlockDeviceConfig` as
     /// input. This action can only be called before the microVM has booted.
     InsertBlockDevice(BlockDeviceConfig),
     /// Add a new network interface config or update one that already exists using the
-    /// `NetworkInterfaceConfig` as input. This action can only be called before the microVM has
-    /// booted.
+    /// `NetworkInterfaceConfig` as input. After the microVM has booted, this action hot-plugs a
+    /// new network interface and cannot update an existing one.
     InsertNetworkDevice(NetworkInterfaceConfig),
     /// Load the microVM state using as input the `LoadSnapshotParams`. This action can only be
     /// called before the microVM has booted. If this action is successful, the loaded microVM will
     /// be in `Paused` state. Should change this state to `Resumed` for the microVM to run.
     LoadSnapshot(LoadSnapshotParams),
+    /// Patch the contents of the MMDS data store selected as in `GetMmds`.
+    PatchMmds(Option<String>, Value),
     /// Pause the guest, by pausing the microVM VCPUs.
     Pause,
+    /// Replace the contents of the MMDS data store selected as in `GetMmds`.
+    PutMmds(Option<String>, Value),
+    /// Remove the network interface with the given ID. After the microVM has booted, this action
+    /// hot-unplugs the interface.
+    RemoveNetworkDevice(String),
     /// Resume the guest, by resuming the microVM VCPUs.
     Resume,
     /// Set the balloon device or update the one that already exists using the
     /// `BalloonDeviceConfig` as input. This action can only be called before the microVM
     /// has booted.
     SetBalloonDevice(BalloonDeviceConfig),
+    /// Replace the whole configuration of the microVM with the one described by the `VmmConfig`
+    /// document, and boot the microVM afterwards if the flag is set. The configuration is only
+    /// applied if all of its resources are valid. This action can only be called before the
+    /// microVM has booted.
+    SetFullVmConfig(VmmConfig, bool),
     /// Set the MMDS configuration.
     SetMmdsConfiguration(MmdsConfig),
     /// Set the vsock device or update the one that already exists using the
@@ -98,6 +123,10 @@ pub enum VmmAction {
     /// driver is listening on the guest end, this can be used to shut down the microVM gracefully.
     #[cfg(target_arch = "x86_64")]
     SendCtrlAltDel,
+    /// Ask the guest to power off and stop the microVM once it did, or once the timeout given
+    /// in milliseconds expired. This action can only be called after the microVM has booted.
+    #[cfg(target_arch = "x86_64")]
+    Shutdown(u64),
     /// Update the balloon size, after microVM start.
     UpdateBalloon(BalloonUpdateConfig),
     /// Update the balloon statistics polling interval, after microVM start.
@@ -105,7 +134,7 @@ pub enum VmmAction {
     /// Update existing block device properties such as `path_on_host` or `rate_limiter`.
     UpdateBlockDevice(BlockDeviceUpdateConfig),
     /// Update a network interface, after microVM start. Currently, the only updatable properties
-    /// are the RX and TX rate limiters.
+    /// are the backing TAP device and the RX and TX rate limiters.
     UpdateNetworkInterface(NetworkInterfaceUpdateConfig),
 }
 
@@ -121,6 +150,8 @@ pub enum VmmActionError {
     /// One of the actions `InsertBlockDevice` or `UpdateBlockDevicePath`
     /// failed because of bad user input.
     DriveConfig(DriveError),
+    /// The action `SetFullVmConfig` failed because of bad user input.
+    FullVmConfig(FullConfigError),
     /// Internal Vmm error.
     InternalVmm(VmmError),
     /// Loading a microVM snapshot failed.
@@ -133,6 +164,8 @@ pub enum VmmActionError {
     MachineConfig(VmConfigError),
     /// The action `ConfigureMetrics` failed because of bad user input.
     Metrics(MetricsConfigError),
+    /// One of the actions `GetMmds`, `PutMmds` or `PatchMmds` failed.
+    Mmds(MmdsStoreError),
     /// The action `SetMmdsConfiguration` failed because of bad user input.
     MmdsConfig(MmdsConfigError),
     /// The action `InsertNetworkDevice` failed because of bad user input.
@@ -143,6 +176,8 @@ pub enum VmmActionError {
     OperationNotSupportedPostBoot,
     /// The requested operation is not supported before starting the microVM.
     OperationNotSupportedPreBoot,
+    /// The action `GetResourceConfig` failed because the resource isn't configured.
+    ResourceNotConfigured(ConfiguredResource),
     /// The action `StartMicroVm` failed because of an internal error.
     StartMicrovm(StartMicrovmError),
     /// The action `SetVsockDevice` failed because of bad user input.
@@ -161,6 +196,7 @@ impl Display for VmmActionError {
                 BootSource(err) => err.to_string(),
                 CreateSnapshot(err) => err.to_string(),
                 DriveConfig(err) => err.to_string(),
+                FullVmConfig(err) => err.to_string(),
                 InternalVmm(err) => format!("Internal Vmm error: {}", err),
                 LoadSnapshot(err) => format!("Load microVM snapshot error: {}", err),
                 LoadSnapshotNotAllowed => {
@@ -170,6 +206,7 @@ impl Display for VmmActionError {
                 Logger(err) => err.to_string(),
                 MachineConfig(err) => err.to_string(),
                 Metrics(err) => err.to_string(),
+                Mmds(err) => err.to_string(),
                 MmdsConfig(err) => err.to_string(),
                 NetworkConfig(err) => err.to_string(),
                 NotSupported(err) => format!("The requested operation is not supported: {}", err),
@@ -181,6 +218,7 @@ impl Display for VmmActionError {
                     "The requested operation is not supported before starting the microVM."
                         .to_string()
                 }
+                ResourceNotConfigured(resource) => format!("The {} is not configured.", resource),
                 StartMicrovm(err) => err.to_string(),
                 // The action `SetVsockDevice` failed because of bad user input.
                 VsockConfig(err) => err.to_string(),
@@ -199,8 +237,16 @@ pub enum VmmData {
     BalloonStats(BalloonStats),
     /// No data is sent on the channel.
     Empty,
+    /// The complete effective configuration of the microVM.
+    FullVmConfig(VmmConfig),
     /// The microVM configuration represented by `VmConfig`.
     MachineConfiguration(VmConfig),
+    /// The contents of a MMDS data store.
+    MmdsValue(Value),
+    /// The traffic statistics of a network interface.
+    NetworkInterfaceStats(NetworkInterfaceStats),
+    /// The configuration of a microVM resource, or of a collection of resources.
+    ResourceConfig(Value),
 }
 
 /// Shorthand result type for external VMM commands.
@@ -282,22 +328,36 @@ impl<'a> PrebootApiController<'a> {
         match request {
             // Supported operations allowed pre-boot.
             ConfigureBootSource(config) => self.set_boot_source(config),
-            ConfigureLogger(logger_cfg) => {
-                vmm_config::logger::init_logger(logger_cfg, &self.instance_info)
-                    .map(|()| VmmData::Empty)
-                    .map_err(VmmActionError::Logger)
-            }
-            ConfigureMetrics(metrics_cfg) => vmm_config::metrics::init_metrics(metrics_cfg)
+            ConfigureLogger(logger_cfg) => self
+                .vm_resources
+                .set_logger(logger_cfg, &self.instance_info)
+                .map(|()| VmmData::Empty)
+                .map_err(VmmActionError::Logger),
+            ConfigureMetrics(metrics_cfg) => self
+                .vm_resources
+                .set_metrics(metrics_cfg)
                 .map(|()| VmmData::Empty)
                 .map_err(VmmActionError::Metrics),
             GetBalloonConfig => self.balloon_config(),
+            GetMmds(iface_id) => get_mmds(&self.vm_resources, iface_id),
+            GetNetworkInterfaceStats(iface_id) => self
+                .vm_resources
+                .net_interface_stats(&iface_id)
+                .map(VmmData::NetworkInterfaceStats)
+                .map_err(VmmActionError::NetworkConfig),
+            GetResourceConfig(resource) => resource_config(&self.vm_resources, resource),
             GetVmConfiguration => Ok(VmmData::MachineConfiguration(
                 self.vm_resources.vm_config().clone(),
             )),
+            GetFullVmConfig => Ok(VmmData::FullVmConfig(VmmConfig::from(&*self.vm_resources))),
             InsertBlockDevice(config) => self.insert_block_device(config),
             InsertNetworkDevice(config) => self.insert_net_device(config),
             LoadSnapshot(config) => self.load_snapshot(&config),
+            PatchMmds(iface_id, value) => patch_mmds(&self.vm_resources, iface_id, value),
+            PutMmds(iface_id, value) => put_mmds(&self.vm_resources, iface_id, value),
+            RemoveNetworkDevice(iface_id) => self.remove_net_device(&iface_id),
             SetBalloonDevice(config) => self.set_balloon_device(config),
+            SetFullVmConfig(config, boot) => self.set_full_vm_config(config, boot),
             SetVsockDevice(config) => self.set_vsock_device(config),
             SetVmConfiguration(config) => self.set_vm_config(config),
             SetMmdsConfiguration(config) => self.set_mmds_config(config),
@@ -313,7 +373,7 @@ impl<'a> PrebootApiController<'a> {
             | UpdateBlockDevice(_)
             | UpdateNetworkInterface(_) => Err(VmmActionError::OperationNotSupportedPreBoot),
             #[cfg(target_arch = "x86_64")]
-            SendCtrlAltDel => Err(VmmActionError::OperationNotSupportedPreBoot),
+            SendCtrlAltDel | Shutdown(_) => Err(VmmActionError::OperationNotSupportedPreBoot),
         }
     }
 
@@ -341,6 +401,14 @@ impl<'a> PrebootApiController<'a> {
             .map_err(VmmActionError::NetworkConfig)
     }
 
+    fn remove_net_device(&mut self, iface_id: &str) -> ActionResult {
+        self.boot_path = true;
+        self.vm_resources
+            .remove_net_device(iface_id)
+            .map(|()| VmmData::Empty)
+            .map_err(VmmActionError::NetworkConfig)
+    }
+
     fn set_balloon_device(&mut self, cfg: BalloonDeviceConfig) -> ActionResult {
         self.boot_path = true;
         self.vm_resources
@@ -357,6 +425,18 @@ impl<'a> PrebootApiController<'a> {
             .map_err(VmmActionError::BootSource)
     }
 
+    fn set_full_vm_config(&mut self, cfg: VmmConfig, boot: bool) -> ActionResult {
+        self.boot_path = true;
+        self.vm_resources
+            .set_full_config(cfg, &self.instance_info)
+            .map_err(VmmActionError::FullVmConfig)?;
+        if boot {
+            self.start_microvm()
+        } else {
+            Ok(VmmData::Empty)
+        }
+    }
+
     fn set_mmds_config(&mut self, cfg: MmdsConfig) -> ActionResult {
         self.boot_path = true;
         self.vm_resources
@@ -391,6 +471,7 @@ impl<'a> PrebootApiController<'a> {
         )
         .map(|vmm| {
             self.built_vmm = Some(vmm);
+            EVENTS.emit(VmEvent::Started);
             VmmData::Empty
         })
         .map_err(VmmActionError::StartMicrovm)
@@ -412,6 +493,7 @@ impl<'a> PrebootApiController<'a> {
             &self.seccomp_filter,
             load_params,
             VERSION_MAP.clone(),
+            &mut self.vm_resources.mmds,
         )
         .and_then(|vmm| {
             let ret = if load_params.resume_vm {
@@ -435,6 +517,39 @@ impl<'a> PrebootApiController<'a> {
     }
 }
 
+// The configuration of the resources can be read back both before and after the microVM boots.
+fn resource_config(vm_resources: &VmResources, resource: ConfiguredResource) -> ActionResult {
+    VmmConfig::from(vm_resources)
+        .resource_config(&resource)
+        .map(VmmData::ResourceConfig)
+        .ok_or(VmmActionError::ResourceNotConfigured(resource))
+}
+
+// The MMDS data stores can be accessed both before and after the microVM boots.
+fn get_mmds(vm_resources: &VmResources, iface_id: Option<String>) -> ActionResult {
+    vm_resources
+        .mmds
+        .get_data(iface_id.as_deref())
+        .map(VmmData::MmdsValue)
+        .map_err(VmmActionError::Mmds)
+}
+
+fn put_mmds(vm_resources: &VmResources, iface_id: Option<String>, value: Value) -> ActionResult {
+    vm_resources
+        .mmds
+        .put_data(iface_id.as_deref(), value)
+        .map(|()| VmmData::Empty)
+        .map_err(VmmActionError::Mmds)
+}
+
+fn patch_mmds(vm_resources: &VmResources, iface_id: Option<String>, value: Value) -> ActionResult {
+    vm_resources
+        .mmds
+        .patch_data(iface_id.as_deref(), value)
+        .map(|()| VmmData::Empty)
+        .map_err(VmmActionError::Mmds)
+}
+
 /// Enables RPC interaction with a running Firecracker VMM.
 pub struct RuntimeApiController {
     vmm: Arc<Mutex<Vmm>>,
@@ -443,7 +558,11 @@ pub struct RuntimeApiController {
 
 impl RuntimeApiController {
     /// Handles the incoming runtime `VmmAction` request and provides a response for it.
-    pub fn handle_request(&mut self, request: VmmAction) -> ActionResult {
+    pub fn handle_request(
+        &mut self,
+        request: VmmAction,
+        event_manager: &mut EventManager,
+    ) -> ActionResult {
         use self::VmmAction::*;
         match request {
             // Supported operations allowed post-boot.
@@ -463,13 +582,31 @@ impl RuntimeApiController {
                 .latest_balloon_stats()
                 .map(VmmData::BalloonStats)
                 .map_err(|e| VmmActionError::BalloonConfig(BalloonConfigError::from(e))),
+            GetMmds(iface_id) => get_mmds(&self.vm_resources, iface_id),
+            GetNetworkInterfaceStats(iface_id) => self
+                .vmm
+                .lock()
+                .expect("Poisoned lock")
+                .net_interface_stats(&iface_id)
+                .map(VmmData::NetworkInterfaceStats)
+                .map_err(|_| {
+                    VmmActionError::NetworkConfig(NetworkInterfaceError::DeviceNotFound(iface_id))
+                }),
+            GetResourceConfig(resource) => resource_config(&self.vm_resources, resource),
             GetVmConfiguration => Ok(VmmData::MachineConfiguration(
                 self.vm_resources.vm_config().clone(),
             )),
+            GetFullVmConfig => Ok(VmmData::FullVmConfig(VmmConfig::from(&self.vm_resources))),
+            InsertNetworkDevice(config) => self.hotplug_net_device(config, event_manager),
+            PatchMmds(iface_id, value) => self.patch_mmds(iface_id, value),
             Pause => self.pause(),
+            PutMmds(iface_id, value) => self.put_mmds(iface_id, value),
+            RemoveNetworkDevice(iface_id) => self.hot_unplug_net_device(&iface_id, event_manager),
             Resume => self.resume(),
             #[cfg(target_arch = "x86_64")]
             SendCtrlAltDel => self.send_ctrl_alt_del(),
+            #[cfg(target_arch = "x86_64")]
+            Shutdown(timeout_ms) => self.shutdown(timeout_ms),
             UpdateBalloon(balloon_update) => self
                 .vmm
                 .lock()
@@ -485,16 +622,18 @@ impl RuntimeApiController {
                 .map(|_| VmmData::Empty)
                 .map_err(|e| VmmActionError::BalloonConfig(BalloonConfigError::from(e))),
             UpdateBlockDevice(new_cfg) => self.update_block_device(new_cfg),
-            UpdateNetworkInterface(netif_update) => self.update_net_rate_limiters(netif_update),
+            UpdateNetworkInterface(netif_update) => {
+                self.update_net_interface(netif_update, event_manager)
+            }
 
             // Operations not allowed post-boot.
             ConfigureBootSource(_)
             | ConfigureLogger(_)
             | ConfigureMetrics(_)
             | InsertBlockDevice(_)
-            | InsertNetworkDevice(_)
             | LoadSnapshot(_)
             | SetBalloonDevice(_)
+            | SetFullVmConfig(..)
             | SetVsockDevice(_)
             | SetMmdsConfiguration(_)
             | SetVmConfiguration(_)
@@ -507,6 +646,28 @@ impl RuntimeApiController {
         Self { vm_resources, vmm }
     }
 
+    /// Replaces the contents of an MMDS data store and answers the guest requests waiting for
+    /// it to change.
+    fn put_mmds(&mut self, iface_id: Option<String>, value: Value) -> ActionResult {
+        let result = put_mmds(&self.vm_resources, iface_id, value)?;
+        self.vmm
+            .lock()
+            .expect("Poisoned lock")
+            .process_mmds_updates();
+        Ok(result)
+    }
+
+    /// Patches the contents of an MMDS data store and answers the guest requests waiting for
+    /// it to change.
+    fn patch_mmds(&mut self, iface_id: Option<String>, value: Value) -> ActionResult {
+        let result = patch_mmds(&self.vm_resources, iface_id, value)?;
+        self.vmm
+            .lock()
+            .expect("Poisoned lock")
+            .process_mmds_updates();
+        Ok(result)
+    }
+
     /// Pauses the microVM by pausing the vCPUs.
     pub fn pause(&mut self) -> ActionResult {
         let pause_start_us = utils::time::get_time_us(utils::time::ClockType::Monotonic);
@@ -520,6 +681,7 @@ impl RuntimeApiController {
         let elapsed_time_us =
             update_metric_with_elapsed_time(&METRICS.latencies_us.vmm_pause_vm, pause_start_us);
         info!("'pause vm' VMM action took {} us.", elapsed_time_us);
+        EVENTS.emit(VmEvent::Paused);
 
         Ok(VmmData::Empty)
     }
@@ -537,6 +699,7 @@ impl RuntimeApiController {
         let elapsed_time_us =
             update_metric_with_elapsed_time(&METRICS.latencies_us.vmm_resume_vm, resume_start_us);
         info!("'resume vm' VMM action took {} us.", elapsed_time_us);
+        EVENTS.emit(VmEvent::Resumed);
 
         Ok(VmmData::Empty)
     }
@@ -565,10 +728,20 @@ impl RuntimeApiController {
             .map_err(VmmActionError::InternalVmm)
     }
 
+    #[cfg(target_arch = "x86_64")]
+    fn shutdown(&mut self, timeout_ms: u64) -> ActionResult {
+        self.vmm
+            .lock()
+            .expect("Poisoned lock")
+            .shutdown(timeout_ms)
+            .map(|()| VmmData::Empty)
+            .map_err(VmmActionError::InternalVmm)
+    }
+
     fn create_snapshot(&mut self, create_params: &CreateSnapshotParams) -> ActionResult {
-        // Diff snapshots are not allowed on uVMs with vsock device.
+        // Diff snapshots are not allowed on uVMs with vsock devices.
         if create_params.snapshot_type == SnapshotType::Diff
-            && self.vm_resources.vsock.get().is_some()
+            && self.vm_resources.vsock.iter().next().is_some()
         {
             return Err(VmmActionError::NotSupported(
                 "Diff snapshots are not allowed on uVMs with vsock device.".to_string(),
@@ -603,6 +776,10 @@ impl RuntimeApiController {
                 );
             }
         }
+        EVENTS.emit(VmEvent::SnapshotCreated {
+            snapshot_path: create_params.snapshot_path.to_string_lossy().into_owned(),
+            mem_file_path: create_params.mem_file_path.to_string_lossy().into_owned(),
+        });
         Ok(VmmData::Empty)
     }
 
@@ -631,34 +808,88 @@ impl RuntimeApiController {
         Ok(VmmData::Empty)
     }
 
-    /// Updates configuration for an emulated net device as described in `new_cfg`.
-    fn update_net_rate_limiters(&mut self, new_cfg: NetworkInterfaceUpdateConfig) -> ActionResult {
+    /// Attaches a new emulated net device, as described in `cfg`, to the running microVM.
+    fn hotplug_net_device(
+        &mut self,
+        cfg: NetworkInterfaceConfig,
+        event_manager: &mut EventManager,
+    ) -> ActionResult {
+        let iface_id = cfg.iface_id.clone();
+        let mut vmm = self.vmm.lock().expect("Poisoned lock");
+        let net = self
+            .vm_resources
+            .build_net_device_for_hotplug(cfg)
+            .map_err(VmmActionError::NetworkConfig)?;
+        if let Err(e) = vmm.hotplug_net_device(net, event_manager) {
+            let _ = self.vm_resources.remove_net_device(&iface_id);
+            return Err(VmmActionError::NetworkConfig(
+                NetworkInterfaceError::DeviceHotplug(e),
+            ));
+        }
+        Ok(VmmData::Empty)
+    }
+
+    /// Detaches the emulated net device with `iface_id` ID from the running microVM.
+    fn hot_unplug_net_device(
+        &mut self,
+        iface_id: &str,
+        event_manager: &mut EventManager,
+    ) -> ActionResult {
         self.vmm
             .lock()
             .expect("Poisoned lock")
-            .update_net_rate_limiters(
+            .hot_unplug_net_device(iface_id, event_manager)
+            .map_err(NetworkInterfaceError::DeviceHotUnplug)
+            .map_err(VmmActionError::NetworkConfig)?;
+        // Devices restored from a snapshot are unknown to the VM resources.
+        let _ = self.vm_resources.remove_net_device(iface_id);
+        Ok(VmmData::Empty)
+    }
+
+    /// Updates configuration for an emulated net device as described in `new_cfg`.
+    fn update_net_interface(
+        &mut self,
+        new_cfg: NetworkInterfaceUpdateConfig,
+        event_manager: &mut EventManager,
+    ) -> ActionResult {
+        let mut vmm = self.vmm.lock().expect("Poisoned lock");
+        // The rate limiters are updated last, as that can't fail half way.
+        if new_cfg.host_dev_name.is_some() || new_cfg.link_up.is_some() {
+            vmm.update_net_connection(
                 &new_cfg.iface_id,
-                RateLimiterUpdate::from(new_cfg.rx_rate_limiter).bandwidth,
-                RateLimiterUpdate::from(new_cfg.rx_rate_limiter).ops,
-                RateLimiterUpdate::from(new_cfg.tx_rate_limiter).bandwidth,
-                RateLimiterUpdate::from(new_cfg.tx_rate_limiter).ops,
+                new_cfg.host_dev_name.as_deref(),
+                new_cfg.link_up,
+                event_manager,
             )
-            .map(|()| VmmData::Empty)
             .map_err(NetworkInterfaceError::DeviceUpdate)
-            .map_err(VmmActionError::NetworkConfig)
+            .map_err(VmmActionError::NetworkConfig)?;
+        }
+        vmm.update_net_rate_limiters(
+            &new_cfg.iface_id,
+            RateLimiterUpdate::from(new_cfg.rx_rate_limiter).bandwidth,
+            RateLimiterUpdate::from(new_cfg.rx_rate_limiter).ops,
+            RateLimiterUpdate::from(new_cfg.tx_rate_limiter).bandwidth,
+            RateLimiterUpdate::from(new_cfg.tx_rate_limiter).ops,
+        )
+        .map(|()| VmmData::Empty)
+        .map_err(NetworkInterfaceError::DeviceUpdate)
+        .map_err(VmmActionError::NetworkConfig)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::resources::Error as ResourcesError;
     use crate::vmm_config::balloon::BalloonBuilder;
     use crate::vmm_config::drive::CacheType;
     use crate::vmm_config::logger::LoggerLevel;
+    use crate::vmm_config::mmds::{MmdsStores, TokenMode};
     use crate::vmm_config::vsock::VsockBuilder;
     use devices::virtio::balloon::{BalloonConfig, Error as BalloonError};
     use devices::virtio::VsockError;
     use seccomp::BpfProgramRef;
+    use serde_json::json;
 
     use std::path::PathBuf;
 
@@ -670,17 +901,20 @@ mod tests {
                 (BootSource(_), BootSource(_)) => true,
                 (CreateSnapshot(_), CreateSnapshot(_)) => true,
                 (DriveConfig(_), DriveConfig(_)) => true,
+                (FullVmConfig(_), FullVmConfig(_)) => true,
                 (InternalVmm(_), InternalVmm(_)) => true,
                 (LoadSnapshot(_), LoadSnapshot(_)) => true,
                 (LoadSnapshotNotAllowed, LoadSnapshotNotAllowed) => true,
                 (Logger(_), Logger(_)) => true,
                 (MachineConfig(_), MachineConfig(_)) => true,
                 (Metrics(_), Metrics(_)) => true,
+                (Mmds(_), Mmds(_)) => true,
                 (MmdsConfig(_), MmdsConfig(_)) => true,
                 (NetworkConfig(_), NetworkConfig(_)) => true,
                 (NotSupported(_), NotSupported(_)) => true,
                 (OperationNotSupportedPostBoot, OperationNotSupportedPostBoot) => true,
                 (OperationNotSupportedPreBoot, OperationNotSupportedPreBoot) => true,
+                (ResourceNotConfigured(_), ResourceNotConfigured(_)) => true,
                 (StartMicrovm(_), StartMicrovm(_)) => true,
                 (VsockConfig(_), VsockConfig(_)) => true,
                 _ => false,
@@ -694,13 +928,19 @@ mod tests {
         vm_config: VmConfig,
         pub balloon: BalloonBuilder,
         pub vsock: VsockBuilder,
+        pub mmds: MmdsStores,
         balloon_config_called: bool,
         balloon_set: bool,
         boot_cfg_set: bool,
         block_set: bool,
+        logger_set: bool,
+        metrics_set: bool,
         vsock_set: bool,
         net_set: bool,
+        net_removed: bool,
+        net_stats_called: bool,
         mmds_set: bool,
+        full_config_set: bool,
         pub boot_timer: bool,
         // when `true`, all self methods are forced to fail
         pub force_errors: bool,
@@ -711,6 +951,17 @@ mod tests {
             &self.vm_config
         }
 
+        #[cfg(target_arch = "x86_64")]
+        pub fn shutdown(&mut self, timeout_ms: u64) -> Result<(), VmmError> {
+            if self.force_errors {
+                return Err(VmmError::I8042Error(
+                    devices::legacy::I8042DeviceError::InternalBufferFull,
+                ));
+            }
+            self.shutdown_timeout_ms = Some(timeout_ms);
+            Ok(())
+        }
+
         pub fn balloon_config(&mut self) -> Result<BalloonConfig, BalloonError> {
             if self.force_errors {
                 return Err(BalloonError::DeviceNotFound);
@@ -751,6 +1002,26 @@ mod tests {
             Ok(())
         }
 
+        pub fn set_logger(
+            &mut self,
+            _: LoggerConfig,
+            _: &InstanceInfo,
+        ) -> Result<(), LoggerConfigError> {
+            if self.force_errors {
+                return Err(LoggerConfigError::InitializationFailure(String::new()));
+            }
+            self.logger_set = true;
+            Ok(())
+        }
+
+        pub fn set_metrics(&mut self, _: MetricsConfig) -> Result<(), MetricsConfigError> {
+            if self.force_errors {
+                return Err(MetricsConfigError::InitializationFailure(String::new()));
+            }
+            self.metrics_set = true;
+            Ok(())
+        }
+
         pub fn set_block_device(&mut self, _: BlockDeviceConfig) -> Result<(), DriveError> {
             if self.force_errors {
                 return Err(DriveError::RootBlockDeviceAlreadyAdded);
@@ -770,6 +1041,32 @@ mod tests {
             Ok(())
         }
 
+        pub fn build_net_device_for_hotplug(
+            &mut self,
+            cfg: NetworkInterfaceConfig,
+        ) -> Result<MockNetDevice, NetworkInterfaceError> {
+            self.build_net_device(cfg).map(|()| MockNetDevice)
+        }
+
+        pub fn remove_net_device(&mut self, iface_id: &str) -> Result<(), NetworkInterfaceError> {
+            if self.force_errors {
+                return Err(NetworkInterfaceError::DeviceNotFound(iface_id.to_string()));
+            }
+            self.net_removed = true;
+            Ok(())
+        }
+
+        pub fn net_interface_stats(
+            &mut self,
+            iface_id: &str,
+        ) -> Result<NetworkInterfaceStats, NetworkInterfaceError> {
+            if self.force_errors {
+                return Err(NetworkInterfaceError::DeviceNotFound(iface_id.to_string()));
+            }
+            self.net_stats_called = true;
+            Ok(NetworkInterfaceStats::default())
+        }
+
         pub fn set_vsock_device(&mut self, _: VsockDeviceConfig) -> Result<(), VsockConfigError> {
             if self.force_errors {
                 return Err(VsockConfigError::CreateVsockDevice(
@@ -787,26 +1084,62 @@ mod tests {
             self.mmds_set = true;
             Ok(())
         }
+
+        pub fn set_full_config(
+            &mut self,
+            _: VmmConfig,
+            _: &InstanceInfo,
+        ) -> Result<(), FullConfigError> {
+            if self.force_errors {
+                return Err(FullConfigError(vec![(
+                    "machine-config".to_string(),
+                    ResourcesError::VmConfig(VmConfigError::InvalidVcpuCount),
+                )]));
+            }
+            self.full_config_set = true;
+            Ok(())
+        }
     }
 
+    impl From<&MockVmRes> for VmmConfig {
+        fn from(_: &MockVmRes) -> Self {
+            VmmConfig::default()
+        }
+    }
+
+    // Mock net device handed from `MockVmRes` to `MockVmm` on hot-plug.
+    pub struct MockNetDevice;
+
     // Mock `Vmm` used for testing.
     #[derive(Debug, Default, PartialEq)]
     pub struct MockVmm {
         pub balloon_config_called: bool,
+        pub hot_unplug_net_device_called: bool,
+        pub hotplug_net_device_called: bool,
         pub latest_balloon_stats_called: bool,
+        pub net_interface_stats_called: bool,
         pub pause_called: bool,
+        pub process_mmds_updates_called: bool,
         pub resume_called: bool,
         #[cfg(target_arch = "x86_64")]
         pub send_ctrl_alt_del_called: bool,
+        #[cfg(target_arch = "x86_64")]
+        pub shutdown_timeout_ms: Option<u64>,
         pub update_balloon_config_called: bool,
         pub update_balloon_stats_config_called: bool,
         pub update_block_device_path_called: bool,
+        pub update_net_host_dev_name_called: bool,
+        pub update_net_link_state_called: bool,
         pub update_net_rate_limiters_called: bool,
         // when `true`, all self methods are forced to fail
         pub force_errors: bool,
     }
 
     impl MockVmm {
+        pub fn process_mmds_updates(&mut self) {
+            self.process_mmds_updates_called = true;
+        }
+
         pub fn resume_vm(&mut self) -> Result<(), VmmError> {
             if self.force_errors {
                 return Err(VmmError::VcpuResume);
@@ -901,6 +1234,61 @@ mod tests {
             self.update_net_rate_limiters_called = true;
             Ok(())
         }
+
+        pub fn update_net_connection(
+            &mut self,
+            _: &str,
+            host_dev_name: Option<&str>,
+            link_up: Option<bool>,
+            _: &mut EventManager,
+        ) -> Result<(), VmmError> {
+            if self.force_errors {
+                return Err(VmmError::DeviceManager(
+                    crate::device_manager::mmio::Error::IncorrectDeviceType,
+                ));
+            }
+            self.update_net_host_dev_name_called = host_dev_name.is_some();
+            self.update_net_link_state_called = link_up.is_some();
+            Ok(())
+        }
+
+        pub fn net_interface_stats(&mut self, _: &str) -> Result<NetworkInterfaceStats, VmmError> {
+            if self.force_errors {
+                return Err(VmmError::DeviceManager(
+                    crate::device_manager::mmio::Error::DeviceNotFound,
+                ));
+            }
+            self.net_interface_stats_called = true;
+            Ok(NetworkInterfaceStats::default())
+        }
+
+        pub fn hotplug_net_device(
+            &mut self,
+            _: MockNetDevice,
+            _: &mut EventManager,
+        ) -> Result<(), VmmError> {
+            if self.force_errors {
+                return Err(VmmError::DeviceManager(
+                    crate::device_manager::mmio::Error::IrqsExhausted,
+                ));
+            }
+            self.hotplug_net_device_called = true;
+            Ok(())
+        }
+
+        pub fn hot_unplug_net_device(
+            &mut self,
+            _: &str,
+            _: &mut EventManager,
+        ) -> Result<(), VmmError> {
+            if self.force_errors {
+                return Err(VmmError::DeviceManager(
+                    crate::device_manager::mmio::Error::DeviceNotFound,
+                ));
+            }
+            self.hot_unplug_net_device_called = true;
+            Ok(())
+        }
     }
 
     // Need to redefine this since the non-test one uses real VmResources
@@ -930,6 +1318,7 @@ mod tests {
         _: BpfProgramRef,
         _: &LoadSnapshotParams,
         _: versionize::VersionMap,
+        _: &mut MmdsStores,
     ) -> Result<Arc<Mutex<Vmm>>, LoadSnapshotError> {
         Ok(Arc::new(Mutex::new(MockVmm::default())))
     }
@@ -1007,6 +1396,109 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_preboot_get_full_vm_config() {
+        let req = VmmAction::GetFullVmConfig;
+        check_preboot_request(req, |result, _| {
+            assert_eq!(result, Ok(VmmData::FullVmConfig(VmmConfig::default())))
+        });
+    }
+
+    #[test]
+    fn test_preboot_set_full_vm_config() {
+        let req = VmmAction::SetFullVmConfig(VmmConfig::default(), false);
+        check_preboot_request(req, |result, vm_res| {
+            assert_eq!(result, Ok(VmmData::Empty));
+            assert!(vm_res.full_config_set)
+        });
+
+        let req = VmmAction::SetFullVmConfig(VmmConfig::default(), false);
+        check_preboot_request_err(
+            req,
+            VmmActionError::FullVmConfig(FullConfigError(Vec::new())),
+        );
+
+        // The microVM is booted after applying the configuration.
+        let mut vm_resources = MockVmRes::default();
+        let mut evmgr = EventManager::new().unwrap();
+        let mut preboot = default_preboot(&mut vm_resources, &mut evmgr);
+        let req = VmmAction::SetFullVmConfig(VmmConfig::default(), true);
+        assert_eq!(preboot.handle_preboot_request(req), Ok(VmmData::Empty));
+        assert!(preboot.built_vmm.is_some());
+    }
+
+    #[test]
+    fn test_preboot_get_resource_config() {
+        let req = VmmAction::GetResourceConfig(ConfiguredResource::Drives(None));
+        check_preboot_request(req, |result, _| {
+            assert_eq!(result, Ok(VmmData::ResourceConfig(json!([]))))
+        });
+
+        let req =
+            VmmAction::GetResourceConfig(ConfiguredResource::Drives(Some(String::from("rootfs"))));
+        check_preboot_request(req, |result, _| {
+            let err = result.unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "The drive \"rootfs\" is not configured.".to_string()
+            );
+        });
+
+        let req = VmmAction::GetResourceConfig(ConfiguredResource::Logger);
+        check_preboot_request(req, |result, _| {
+            assert_eq!(
+                result,
+                Err(VmmActionError::ResourceNotConfigured(
+                    ConfiguredResource::Logger
+                ))
+            )
+        });
+    }
+
+    #[test]
+    fn test_preboot_configure_logger() {
+        let req = VmmAction::ConfigureLogger(LoggerConfig {
+            log_path: PathBuf::new(),
+            level: LoggerLevel::Debug,
+            show_level: false,
+            show_log_origin: false,
+        });
+        check_preboot_request(req, |result, vm_res| {
+            assert_eq!(result, Ok(VmmData::Empty));
+            assert!(vm_res.logger_set)
+        });
+
+        let req = VmmAction::ConfigureLogger(LoggerConfig {
+            log_path: PathBuf::new(),
+            level: LoggerLevel::Debug,
+            show_level: false,
+            show_log_origin: false,
+        });
+        check_preboot_request_err(
+            req,
+            VmmActionError::Logger(LoggerConfigError::InitializationFailure(String::new())),
+        );
+    }
+
+    #[test]
+    fn test_preboot_configure_metrics() {
+        let req = VmmAction::ConfigureMetrics(MetricsConfig {
+            metrics_path: PathBuf::new(),
+        });
+        check_preboot_request(req, |result, vm_res| {
+            assert_eq!(result, Ok(VmmData::Empty));
+            assert!(vm_res.metrics_set)
+        });
+
+        let req = VmmAction::ConfigureMetrics(MetricsConfig {
+            metrics_path: PathBuf::new(),
+        });
+        check_preboot_request_err(
+            req,
+            VmmActionError::Metrics(MetricsConfigError::InitializationFailure(String::new())),
+        );
+    }
+
     #[test]
     fn test_preboot_get_balloon_config() {
         let req = VmmAction::GetBalloonConfig;
@@ -1083,6 +1575,7 @@ mod tests {
         let req = VmmAction::InsertNetworkDevice(NetworkInterfaceConfig {
             iface_id: String::new(),
             host_dev_name: String::new(),
+            unix_socket_path: None,
             guest_mac: None,
             rx_rate_limiter: None,
             tx_rate_limiter: None,
@@ -1096,6 +1589,7 @@ mod tests {
         let req = VmmAction::InsertNetworkDevice(NetworkInterfaceConfig {
             iface_id: String::new(),
             host_dev_name: String::new(),
+            unix_socket_path: None,
             guest_mac: None,
             rx_rate_limiter: None,
             tx_rate_limiter: None,
@@ -1109,12 +1603,55 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_preboot_remove_net_dev() {
+        let req = VmmAction::RemoveNetworkDevice(String::new());
+        check_preboot_request(req, |result, vm_res| {
+            assert_eq!(result, Ok(VmmData::Empty));
+            assert!(vm_res.net_removed)
+        });
+
+        let req = VmmAction::RemoveNetworkDevice(String::new());
+        check_preboot_request_err(
+            req,
+            VmmActionError::NetworkConfig(NetworkInterfaceError::DeviceNotFound(String::new())),
+        );
+    }
+
+    #[test]
+    fn test_preboot_get_net_stats() {
+        let req = VmmAction::GetNetworkInterfaceStats(String::new());
+        check_preboot_request(req, |result, vm_res| {
+            assert_eq!(
+                result,
+                Ok(VmmData::NetworkInterfaceStats(
+                    NetworkInterfaceStats::default()
+                ))
+            );
+            assert!(vm_res.net_stats_called)
+        });
+
+        let req = VmmAction::GetNetworkInterfaceStats(String::new());
+        check_preboot_request_err(
+            req,
+            VmmActionError::NetworkConfig(NetworkInterfaceError::DeviceNotFound(String::new())),
+        );
+    }
+
     #[test]
     fn test_preboot_set_vsock_dev() {
         let req = VmmAction::SetVsockDevice(VsockDeviceConfig {
             vsock_id: String::new(),
             guest_cid: 0,
             uds_path: String::new(),
+            listen_ports: Vec::new(),
+            tcp_listeners: Vec::new(),
+            tcp_endpoints: Vec::new(),
+            persist_connections: false,
+            buf_alloc: None,
+            max_connections: None,
+            conn_request_timeout_ms: None,
+            conn_shutdown_timeout_ms: None,
         });
         check_preboot_request(req, |result, vm_res| {
             assert_eq!(result, Ok(VmmData::Empty));
@@ -1125,6 +1662,14 @@ mod tests {
             vsock_id: String::new(),
             guest_cid: 0,
             uds_path: String::new(),
+            listen_ports: Vec::new(),
+            tcp_listeners: Vec::new(),
+            tcp_endpoints: Vec::new(),
+            persist_connections: false,
+            buf_alloc: None,
+            max_connections: None,
+            conn_request_timeout_ms: None,
+            conn_shutdown_timeout_ms: None,
         });
         check_preboot_request_err(
             req,
@@ -1136,19 +1681,69 @@ mod tests {
 
     #[test]
     fn test_preboot_set_mmds_config() {
-        let req = VmmAction::SetMmdsConfiguration(MmdsConfig { ipv4_address: None });
+        let req = VmmAction::SetMmdsConfiguration(MmdsConfig {
+            ipv4_address: None,
+            token_mode: TokenMode::Optional,
+            iface_id: None,
+            data_store_limit: None,
+            ipv6_address: None,
+            vsock_port: None,
+        });
         check_preboot_request(req, |result, vm_res| {
             assert_eq!(result, Ok(VmmData::Empty));
             assert!(vm_res.mmds_set)
         });
 
-        let req = VmmAction::SetMmdsConfiguration(MmdsConfig { ipv4_address: None });
+        let req = VmmAction::SetMmdsConfiguration(MmdsConfig {
+            ipv4_address: None,
+            token_mode: TokenMode::Optional,
+            iface_id: None,
+            data_store_limit: None,
+            ipv6_address: None,
+            vsock_port: None,
+        });
         check_preboot_request_err(
             req,
             VmmActionError::MmdsConfig(MmdsConfigError::InvalidIpv4Addr),
         );
     }
 
+    #[test]
+    fn test_preboot_mmds() {
+        let mut vm_resources = MockVmRes::default();
+        let mut evmgr = EventManager::new().unwrap();
+        let mut preboot = default_preboot(&mut vm_resources, &mut evmgr);
+
+        let req = VmmAction::GetMmds(None);
+        assert_eq!(
+            preboot.handle_preboot_request(req),
+            Ok(VmmData::MmdsValue(json!({})))
+        );
+        let req = VmmAction::PatchMmds(None, json!({"foo": "bar"}));
+        assert!(preboot.handle_preboot_request(req).is_err());
+        let req = VmmAction::PutMmds(None, json!({"foo": "bar"}));
+        assert_eq!(preboot.handle_preboot_request(req), Ok(VmmData::Empty));
+        let req = VmmAction::PatchMmds(None, json!({"baz": 1}));
+        assert_eq!(preboot.handle_preboot_request(req), Ok(VmmData::Empty));
+        let req = VmmAction::GetMmds(None);
+        assert_eq!(
+            preboot.handle_preboot_request(req),
+            Ok(VmmData::MmdsValue(json!({"foo": "bar", "baz": 1})))
+        );
+
+        // No data store is dedicated to the interface.
+        let req = VmmAction::GetMmds(Some(String::from("eth0")));
+        assert_eq!(
+            preboot.handle_preboot_request(req),
+            Err(VmmActionError::Mmds(MmdsStoreError::NoDedicatedStore(
+                String::from("eth0")
+            )))
+        );
+
+        // Populating the MMDS does not prevent loading a snapshot.
+        assert!(!preboot.boot_path);
+    }
+
     #[cfg(target_arch = "x86_64")]
     #[test]
     fn test_preboot_load_snapshot() {
@@ -1162,6 +1757,8 @@ mod tests {
             mem_file_path: PathBuf::new(),
             enable_diff_snapshots: false,
             resume_vm: false,
+            vsock_reattach: Vec::new(),
+            discard_mmds_data: false,
         });
         // Request should succeed.
         preboot.handle_preboot_request(req).unwrap();
@@ -1175,6 +1772,8 @@ mod tests {
             mem_file_path: PathBuf::new(),
             enable_diff_snapshots: false,
             resume_vm: true,
+            vsock_reattach: Vec::new(),
+            discard_mmds_data: false,
         });
         // Request should succeed.
         preboot.handle_preboot_request(req).unwrap();
@@ -1220,6 +1819,8 @@ mod tests {
         check_preboot_request_err(
             VmmAction::UpdateNetworkInterface(NetworkInterfaceUpdateConfig {
                 iface_id: String::new(),
+                host_dev_name: None,
+                link_up: None,
                 rx_rate_limiter: None,
                 tx_rate_limiter: None,
             }),
@@ -1231,6 +1832,7 @@ mod tests {
                 snapshot_path: PathBuf::new(),
                 mem_file_path: PathBuf::new(),
                 version: None,
+                progress: Default::default(),
             }),
             VmmActionError::OperationNotSupportedPreBoot,
         );
@@ -1239,6 +1841,11 @@ mod tests {
             VmmAction::SendCtrlAltDel,
             VmmActionError::OperationNotSupportedPreBoot,
         );
+        #[cfg(target_arch = "x86_64")]
+        check_preboot_request_err(
+            VmmAction::Shutdown(1000),
+            VmmActionError::OperationNotSupportedPreBoot,
+        );
     }
 
     #[test]
@@ -1292,7 +1899,7 @@ mod tests {
     {
         let vmm = Arc::new(Mutex::new(MockVmm::default()));
         let mut runtime = RuntimeApiController::new(MockVmRes::default(), vmm.clone());
-        let res = runtime.handle_request(request);
+        let res = runtime.handle_request(request, &mut EventManager::new().unwrap());
         check_success(res, &vmm.lock().unwrap());
     }
 
@@ -1303,7 +1910,9 @@ mod tests {
             ..Default::default()
         }));
         let mut runtime = RuntimeApiController::new(MockVmRes::default(), vmm);
-        let err = runtime.handle_request(request).unwrap_err();
+        let err = runtime
+            .handle_request(request, &mut EventManager::new().unwrap())
+            .unwrap_err();
         assert_eq!(err, expected_err);
     }
 
@@ -1318,6 +1927,56 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_runtime_get_full_vm_config() {
+        let req = VmmAction::GetFullVmConfig;
+        check_runtime_request(req, |result, _| {
+            assert_eq!(result, Ok(VmmData::FullVmConfig(VmmConfig::default())));
+        });
+
+        let req = VmmAction::GetResourceConfig(ConfiguredResource::Vsock(None));
+        check_runtime_request(req, |result, _| {
+            assert_eq!(result, Ok(VmmData::ResourceConfig(json!([]))));
+        });
+
+        let req = VmmAction::GetResourceConfig(ConfiguredResource::BootSource);
+        check_runtime_request(req, |result, _| {
+            assert_eq!(
+                result,
+                Err(VmmActionError::ResourceNotConfigured(
+                    ConfiguredResource::BootSource
+                ))
+            );
+        });
+    }
+
+    #[test]
+    fn test_runtime_mmds() {
+        let mut vm_resources = MockVmRes::default();
+        vm_resources.mmds.dedicate("eth0");
+        let vmm = Arc::new(Mutex::new(MockVmm::default()));
+        let mut runtime = RuntimeApiController::new(vm_resources, vmm.clone());
+        let mut evmgr = EventManager::new().unwrap();
+
+        let req = VmmAction::PutMmds(Some(String::from("eth0")), json!({"foo": "bar"}));
+        assert_eq!(runtime.handle_request(req, &mut evmgr), Ok(VmmData::Empty));
+        // Guest requests waiting for a change get a chance to be answered.
+        assert!(vmm.lock().unwrap().process_mmds_updates_called);
+        let req = VmmAction::GetMmds(Some(String::from("eth0")));
+        assert_eq!(
+            runtime.handle_request(req, &mut evmgr),
+            Ok(VmmData::MmdsValue(json!({"foo": "bar"})))
+        );
+        // The shared data store is left untouched.
+        let req = VmmAction::GetMmds(None);
+        assert_eq!(
+            runtime.handle_request(req, &mut evmgr),
+            Ok(VmmData::MmdsValue(json!({})))
+        );
+        let req = VmmAction::PatchMmds(Some(String::from("eth1")), json!({}));
+        assert!(runtime.handle_request(req, &mut evmgr).is_err());
+    }
+
     #[test]
     fn test_runtime_pause() {
         let req = VmmAction::Pause;
@@ -1360,6 +2019,24 @@ mod tests {
         );
     }
 
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_runtime_shutdown() {
+        let req = VmmAction::Shutdown(1000);
+        check_runtime_request(req, |result, vmm| {
+            assert_eq!(result, Ok(VmmData::Empty));
+            assert_eq!(vmm.shutdown_timeout_ms, Some(1000))
+        });
+
+        let req = VmmAction::Shutdown(1000);
+        check_runtime_request_err(
+            req,
+            VmmActionError::InternalVmm(VmmError::I8042Error(
+                devices::legacy::I8042DeviceError::InternalBufferFull,
+            )),
+        );
+    }
+
     #[test]
     fn test_runtime_balloon_config() {
         let req = VmmAction::GetBalloonConfig;
@@ -1454,6 +2131,8 @@ mod tests {
     fn test_runtime_update_net_rate_limiters() {
         let req = VmmAction::UpdateNetworkInterface(NetworkInterfaceUpdateConfig {
             iface_id: String::new(),
+            host_dev_name: None,
+            link_up: None,
             rx_rate_limiter: None,
             tx_rate_limiter: None,
         });
@@ -1464,6 +2143,8 @@ mod tests {
 
         let req = VmmAction::UpdateNetworkInterface(NetworkInterfaceUpdateConfig {
             iface_id: String::new(),
+            host_dev_name: None,
+            link_up: None,
             rx_rate_limiter: None,
             tx_rate_limiter: None,
         });
@@ -1475,6 +2156,138 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_runtime_update_net_host_dev_name() {
+        let req = VmmAction::UpdateNetworkInterface(NetworkInterfaceUpdateConfig {
+            iface_id: String::new(),
+            host_dev_name: Some("tap1".to_string()),
+            link_up: None,
+            rx_rate_limiter: None,
+            tx_rate_limiter: None,
+        });
+        check_runtime_request(req, |result, vmm| {
+            assert_eq!(result, Ok(VmmData::Empty));
+            assert!(vmm.update_net_host_dev_name_called);
+            assert!(vmm.update_net_rate_limiters_called);
+        });
+
+        let req = VmmAction::UpdateNetworkInterface(NetworkInterfaceUpdateConfig {
+            iface_id: String::new(),
+            host_dev_name: Some("tap1".to_string()),
+            link_up: None,
+            rx_rate_limiter: None,
+            tx_rate_limiter: None,
+        });
+        check_runtime_request_err(
+            req,
+            VmmActionError::NetworkConfig(NetworkInterfaceError::DeviceUpdate(
+                VmmError::DeviceManager(crate::device_manager::mmio::Error::IncorrectDeviceType),
+            )),
+        );
+    }
+
+    #[test]
+    fn test_runtime_update_net_link_state() {
+        let req = VmmAction::UpdateNetworkInterface(NetworkInterfaceUpdateConfig {
+            iface_id: String::new(),
+            host_dev_name: None,
+            link_up: Some(false),
+            rx_rate_limiter: None,
+            tx_rate_limiter: None,
+        });
+        check_runtime_request(req, |result, vmm| {
+            assert_eq!(result, Ok(VmmData::Empty));
+            assert!(vmm.update_net_link_state_called);
+            assert!(!vmm.update_net_host_dev_name_called);
+        });
+
+        let req = VmmAction::UpdateNetworkInterface(NetworkInterfaceUpdateConfig {
+            iface_id: String::new(),
+            host_dev_name: None,
+            link_up: Some(false),
+            rx_rate_limiter: None,
+            tx_rate_limiter: None,
+        });
+        check_runtime_request_err(
+            req,
+            VmmActionError::NetworkConfig(NetworkInterfaceError::DeviceUpdate(
+                VmmError::DeviceManager(crate::device_manager::mmio::Error::IncorrectDeviceType),
+            )),
+        );
+    }
+
+    #[test]
+    fn test_runtime_get_net_stats() {
+        let req = VmmAction::GetNetworkInterfaceStats(String::from("foo"));
+        check_runtime_request(req, |result, vmm| {
+            assert_eq!(
+                result,
+                Ok(VmmData::NetworkInterfaceStats(
+                    NetworkInterfaceStats::default()
+                ))
+            );
+            assert!(vmm.net_interface_stats_called)
+        });
+
+        let req = VmmAction::GetNetworkInterfaceStats(String::from("foo"));
+        check_runtime_request_err(
+            req,
+            VmmActionError::NetworkConfig(NetworkInterfaceError::DeviceNotFound(String::from(
+                "foo",
+            ))),
+        );
+    }
+
+    #[test]
+    fn test_runtime_insert_net_dev() {
+        let req = VmmAction::InsertNetworkDevice(NetworkInterfaceConfig {
+            iface_id: String::new(),
+            host_dev_name: String::new(),
+            unix_socket_path: None,
+            guest_mac: None,
+            rx_rate_limiter: None,
+            tx_rate_limiter: None,
+            allow_mmds_requests: false,
+        });
+        check_runtime_request(req, |result, vmm| {
+            assert_eq!(result, Ok(VmmData::Empty));
+            assert!(vmm.hotplug_net_device_called);
+        });
+
+        let req = VmmAction::InsertNetworkDevice(NetworkInterfaceConfig {
+            iface_id: String::new(),
+            host_dev_name: String::new(),
+            unix_socket_path: None,
+            guest_mac: None,
+            rx_rate_limiter: None,
+            tx_rate_limiter: None,
+            allow_mmds_requests: false,
+        });
+        check_runtime_request_err(
+            req,
+            VmmActionError::NetworkConfig(NetworkInterfaceError::DeviceHotplug(
+                VmmError::DeviceManager(crate::device_manager::mmio::Error::IrqsExhausted),
+            )),
+        );
+    }
+
+    #[test]
+    fn test_runtime_remove_net_dev() {
+        let req = VmmAction::RemoveNetworkDevice(String::new());
+        check_runtime_request(req, |result, vmm| {
+            assert_eq!(result, Ok(VmmData::Empty));
+            assert!(vmm.hot_unplug_net_device_called);
+        });
+
+        let req = VmmAction::RemoveNetworkDevice(String::new());
+        check_runtime_request_err(
+            req,
+            VmmActionError::NetworkConfig(NetworkInterfaceError::DeviceHotUnplug(
+                VmmError::DeviceManager(crate::device_manager::mmio::Error::DeviceNotFound),
+            )),
+        );
+    }
+
     #[test]
     fn test_runtime_disallowed() {
         check_runtime_request_err(
@@ -1508,22 +2321,19 @@ mod tests {
             }),
             VmmActionError::OperationNotSupportedPostBoot,
         );
-        check_runtime_request_err(
-            VmmAction::InsertNetworkDevice(NetworkInterfaceConfig {
-                iface_id: String::new(),
-                host_dev_name: String::new(),
-                guest_mac: None,
-                rx_rate_limiter: None,
-                tx_rate_limiter: None,
-                allow_mmds_requests: false,
-            }),
-            VmmActionError::OperationNotSupportedPostBoot,
-        );
         check_runtime_request_err(
             VmmAction::SetVsockDevice(VsockDeviceConfig {
                 vsock_id: String::new(),
                 guest_cid: 0,
                 uds_path: String::new(),
+                listen_ports: Vec::new(),
+                tcp_listeners: Vec::new(),
+                tcp_endpoints: Vec::new(),
+                persist_connections: false,
+                buf_alloc: None,
+                max_connections: None,
+                conn_request_timeout_ms: None,
+                conn_shutdown_timeout_ms: None,
             }),
             VmmActionError::OperationNotSupportedPostBoot,
         );
@@ -1536,23 +2346,44 @@ mod tests {
                 vsock_id: String::new(),
                 guest_cid: 0,
                 uds_path: String::new(),
+                listen_ports: Vec::new(),
+                tcp_listeners: Vec::new(),
+                tcp_endpoints: Vec::new(),
+                persist_connections: false,
+                buf_alloc: None,
+                max_connections: None,
+                conn_request_timeout_ms: None,
+                conn_shutdown_timeout_ms: None,
             }),
             VmmActionError::OperationNotSupportedPostBoot,
         );
         check_runtime_request_err(
-            VmmAction::SetMmdsConfiguration(MmdsConfig { ipv4_address: None }),
+            VmmAction::SetMmdsConfiguration(MmdsConfig {
+                ipv4_address: None,
+                token_mode: TokenMode::Optional,
+                iface_id: None,
+                data_store_limit: None,
+                ipv6_address: None,
+                vsock_port: None,
+            }),
             VmmActionError::OperationNotSupportedPostBoot,
         );
         check_runtime_request_err(
             VmmAction::SetVmConfiguration(VmConfig::default()),
             VmmActionError::OperationNotSupportedPostBoot,
         );
+        check_runtime_request_err(
+            VmmAction::SetFullVmConfig(VmmConfig::default(), false),
+            VmmActionError::OperationNotSupportedPostBoot,
+        );
         check_runtime_request_err(
             VmmAction::LoadSnapshot(LoadSnapshotParams {
                 snapshot_path: PathBuf::new(),
                 mem_file_path: PathBuf::new(),
                 enable_diff_snapshots: false,
                 resume_vm: false,
+                vsock_reattach: Vec::new(),
+                discard_mmds_data: false,
             }),
             VmmActionError::OperationNotSupportedPostBoot,
         );
@@ -1571,6 +2402,8 @@ mod tests {
             mem_file_path: PathBuf::new(),
             enable_diff_snapshots: false,
             resume_vm: false,
+            vsock_reattach: Vec::new(),
+            discard_mmds_data: false,
         });
         let err = preboot.handle_preboot_request(req);
         assert_eq!(
@@ -1601,6 +2434,7 @@ mod tests {
         let req = VmmAction::InsertNetworkDevice(NetworkInterfaceConfig {
             iface_id: String::new(),
             host_dev_name: String::new(),
+            unix_socket_path: None,
             guest_mac: None,
             rx_rate_limiter: None,
             tx_rate_limiter: None,
@@ -1615,13 +2449,28 @@ mod tests {
             vsock_id: String::new(),
             guest_cid: 0,
             uds_path: String::new(),
+            listen_ports: Vec::new(),
+            tcp_listeners: Vec::new(),
+            tcp_endpoints: Vec::new(),
+            persist_connections: false,
+            buf_alloc: None,
+            max_connections: None,
+            conn_request_timeout_ms: None,
+            conn_shutdown_timeout_ms: None,
         });
         verify_load_snap_disallowed_after_boot_resources(req, "SetVsockDevice");
 
         let req = VmmAction::SetVmConfiguration(VmConfig::default());
         verify_load_snap_disallowed_after_boot_resources(req, "SetVmConfiguration");
 
-        let req = VmmAction::SetMmdsConfiguration(MmdsConfig { ipv4_address: None });
+        let req = VmmAction::SetMmdsConfiguration(MmdsConfig {
+            ipv4_address: None,
+            token_mode: TokenMode::Optional,
+            iface_id: None,
+            data_store_limit: None,
+            ipv6_address: None,
+            vsock_port: None,
+        });
         verify_load_snap_disallowed_after_boot_resources(req, "SetMmdsConfiguration");
     }
 }
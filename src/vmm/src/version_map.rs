@@ -6,7 +6,12 @@
 use std::collections::HashMap;
 
 use crate::device_manager::persist::DeviceStates;
+use crate::persist::MicrovmState;
+use devices::virtio::balloon::persist::BalloonState;
 use devices::virtio::block::persist::BlockState;
+use devices::virtio::net::persist::{NetConfigSpaceState, NetState};
+use devices::virtio::vsock::persist::VsockUdsState;
+use mmds::persist::{MmdsNetworkStackState, MmdsState};
 
 use lazy_static::lazy_static;
 use versionize::VersionMap;
@@ -18,7 +23,17 @@ lazy_static! {
     pub static ref VERSION_MAP: VersionMap = {
         let mut version_map = VersionMap::new();
         version_map.new_version().set_type_version(DeviceStates::type_id(), 2);
-        version_map.new_version().set_type_version(BlockState::type_id(), 2);
+        version_map
+            .new_version()
+            .set_type_version(MicrovmState::type_id(), 2)
+            .set_type_version(DeviceStates::type_id(), 3)
+            .set_type_version(BalloonState::type_id(), 2)
+            .set_type_version(BlockState::type_id(), 2)
+            .set_type_version(NetState::type_id(), 2)
+            .set_type_version(NetConfigSpaceState::type_id(), 2)
+            .set_type_version(VsockUdsState::type_id(), 2)
+            .set_type_version(MmdsState::type_id(), 2)
+            .set_type_version(MmdsNetworkStackState::type_id(), 2);
         version_map
     };
 
@@ -5,6 +5,7 @@ use std::fmt;
 use std::sync::{Arc, Mutex};
 
 pub use devices::virtio::balloon::device::BalloonStats;
+pub use devices::virtio::balloon::AutoSizeConfig;
 use devices::virtio::balloon::Error as BalloonError;
 pub use devices::virtio::BALLOON_DEV_ID;
 use devices::virtio::{Balloon, BalloonConfig};
@@ -27,6 +28,10 @@ pub enum BalloonConfigError {
     /// The user polled the statistics of a balloon device that
     /// does not have the statistics enabled.
     StatsNotFound,
+    /// The user enabled automatic sizing without enabling the statistics.
+    AutoSizeStatsDisabled,
+    /// The automatic sizing bounds or watermarks are inconsistent.
+    InvalidAutoSizeConfig,
     /// Failed to create a balloon device.
     CreateFailure(devices::virtio::balloon::Error),
     /// Failed to update the configuration of the ballon device.
@@ -42,6 +47,15 @@ impl fmt::Display for BalloonConfigError {
             InvalidStatsUpdate => write!(f, "Cannot enable/disable the statistics after boot."),
             TooManyPagesRequested => write!(f, "Amount of pages requested is too large."),
             StatsNotFound => write!(f, "Statistics for the balloon device are not enabled"),
+            AutoSizeStatsDisabled => write!(
+                f,
+                "Automatic sizing requires the balloon statistics to be enabled."
+            ),
+            InvalidAutoSizeConfig => write!(
+                f,
+                "Invalid automatic sizing configuration: min_mb must not exceed max_mb, \
+                 step_mb must be positive and low_available_mb must be below high_available_mb."
+            ),
             CreateFailure(e) => write!(f, "Error creating the balloon device: {:?}", e),
             UpdateFailure(e) => write!(
                 f,
@@ -61,6 +75,8 @@ impl From<BalloonError> for BalloonConfigError {
             BalloonError::StatisticsStateChange => Self::InvalidStatsUpdate,
             BalloonError::StatisticsDisabled => Self::StatsNotFound,
             BalloonError::TooManyPagesRequested => Self::TooManyPagesRequested,
+            BalloonError::AutoSizeStatsDisabled => Self::AutoSizeStatsDisabled,
+            BalloonError::InvalidAutoSizeConfig => Self::InvalidAutoSizeConfig,
             e => Self::CreateFailure(e),
         }
     }
@@ -80,6 +96,12 @@ pub struct BalloonDeviceConfig {
     /// Interval in seconds between refreshing statistics.
     #[serde(default)]
     pub stats_polling_interval_s: u16,
+    /// Option to let the guest report free pages, which are then released to the host.
+    #[serde(default)]
+    pub free_page_reporting: bool,
+    /// Optional policy that resizes the balloon based on the guest statistics.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_size: Option<AutoSizeConfig>,
 }
 
 impl From<BalloonConfig> for BalloonDeviceConfig {
@@ -88,6 +110,8 @@ impl From<BalloonConfig> for BalloonDeviceConfig {
             amount_mb: state.amount_mb,
             deflate_on_oom: state.deflate_on_oom,
             stats_polling_interval_s: state.stats_polling_interval_s,
+            free_page_reporting: state.free_page_reporting,
+            auto_size: state.auto_size,
         }
     }
 }
@@ -133,17 +157,18 @@ impl BalloonBuilder {
     /// Inserts a Balloon device in the store.
     /// If an entry already exists, it will overwrite it.
     pub fn set(&mut self, cfg: BalloonDeviceConfig) -> Result<()> {
-        self.inner = Some(Arc::new(Mutex::new(
-            Balloon::new(
-                cfg.amount_mb,
-                cfg.deflate_on_oom,
-                cfg.stats_polling_interval_s,
-                // `restored` flag is false because this code path
-                // is never called by snapshot restore functionality.
-                false,
-            )
-            .map_err(BalloonConfigError::CreateFailure)?,
-        )));
+        let mut balloon = Balloon::new(
+            cfg.amount_mb,
+            cfg.deflate_on_oom,
+            cfg.stats_polling_interval_s,
+            cfg.free_page_reporting,
+            // `restored` flag is false because this code path
+            // is never called by snapshot restore functionality.
+            false,
+        )
+        .map_err(BalloonConfigError::CreateFailure)?;
+        balloon.set_auto_size(cfg.auto_size)?;
+        self.inner = Some(Arc::new(Mutex::new(balloon)));
 
         Ok(())
     }
@@ -171,6 +196,8 @@ pub(crate) mod tests {
             amount_mb: 0,
             deflate_on_oom: false,
             stats_polling_interval_s: 0,
+            free_page_reporting: false,
+            auto_size: None,
         }
     }
 
@@ -189,6 +216,8 @@ pub(crate) mod tests {
             amount_mb: 0,
             deflate_on_oom: false,
             stats_polling_interval_s: 0,
+            free_page_reporting: false,
+            auto_size: None,
         };
         assert_eq!(default_balloon_config, balloon_config);
         let mut builder = BalloonBuilder::new();
@@ -204,18 +233,59 @@ pub(crate) mod tests {
         };
     }
 
+    #[test]
+    fn test_balloon_auto_size() {
+        let auto_size = AutoSizeConfig {
+            min_mb: 0,
+            max_mb: 64,
+            step_mb: 8,
+            low_available_mb: 32,
+            high_available_mb: 128,
+        };
+        let mut balloon_config = BalloonDeviceConfig {
+            auto_size: Some(auto_size),
+            ..default_config()
+        };
+        let mut builder = BalloonBuilder::new();
+
+        // The statistics must be enabled.
+        assert!(matches!(
+            builder.set(balloon_config.clone()),
+            Err(BalloonConfigError::AutoSizeStatsDisabled)
+        ));
+
+        balloon_config.stats_polling_interval_s = 1;
+        balloon_config.auto_size = Some(AutoSizeConfig {
+            low_available_mb: 128,
+            ..auto_size
+        });
+        assert!(matches!(
+            builder.set(balloon_config.clone()),
+            Err(BalloonConfigError::InvalidAutoSizeConfig)
+        ));
+        assert!(builder.get().is_none());
+
+        balloon_config.auto_size = Some(auto_size);
+        builder.set(balloon_config.clone()).unwrap();
+        assert_eq!(builder.get_config().unwrap(), balloon_config);
+    }
+
     #[test]
     fn test_from_balloon_state() {
         let expected_balloon_config = BalloonDeviceConfig {
             amount_mb: 5,
             deflate_on_oom: false,
             stats_polling_interval_s: 3,
+            free_page_reporting: false,
+            auto_size: None,
         };
 
         let actual_balloon_config = BalloonDeviceConfig::from(BalloonConfig {
             amount_mb: 5,
             deflate_on_oom: false,
             stats_polling_interval_s: 3,
+            free_page_reporting: false,
+            auto_size: None,
         });
 
         assert_eq!(expected_balloon_config, actual_balloon_config);
@@ -244,5 +314,11 @@ pub(crate) mod tests {
 
         let err = StatsNotFound;
         let _ = format!("{}{:?}", err, err);
+
+        let err = AutoSizeStatsDisabled;
+        let _ = format!("{}{:?}", err, err);
+
+        let err = InvalidAutoSizeConfig;
+        let _ = format!("{}{:?}", err, err);
     }
 }
@@ -9,13 +9,13 @@ use std::path::PathBuf;
 use std::result;
 use std::sync::{Arc, Mutex};
 
-use super::RateLimiterConfig;
+use super::{rate_limiter_config, RateLimiterConfig};
 use crate::Error as VmmError;
 use devices::virtio::Block;
 
 pub use devices::virtio::CacheType;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 type Result<T> = result::Result<T, DriveError>;
 
@@ -64,7 +64,7 @@ impl Display for DriveError {
 }
 
 /// Use this structure to set up the Block Device before booting the kernel.
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct BlockDeviceConfig {
     /// Unique identifier of the drive.
@@ -89,9 +89,23 @@ pub struct BlockDeviceConfig {
     pub rate_limiter: Option<RateLimiterConfig>,
 }
 
+impl From<&Block> for BlockDeviceConfig {
+    fn from(block: &Block) -> Self {
+        BlockDeviceConfig {
+            drive_id: block.id().clone(),
+            path_on_host: block.file_path().clone(),
+            is_root_device: block.is_root_device(),
+            partuuid: block.partuuid().cloned(),
+            is_read_only: block.is_read_only(),
+            cache_type: block.cache_type(),
+            rate_limiter: rate_limiter_config(block.rate_limiter()),
+        }
+    }
+}
+
 /// Only provided fields will be updated. I.e. if any optional fields
 /// are missing, they will not be updated.
-#[derive(Debug, Default, Deserialize, PartialEq)]
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct BlockDeviceUpdateConfig {
     /// The drive ID, as provided by the user at creation time.
@@ -138,6 +152,14 @@ impl BlockBuilder {
             .position(|b| b.lock().expect("Poisoned lock").id().eq(drive_id))
     }
 
+    /// Returns the configurations of the block devices, reflecting their current state.
+    pub fn configs(&self) -> Vec<BlockDeviceConfig> {
+        self.list
+            .iter()
+            .map(|block| BlockDeviceConfig::from(&*block.lock().expect("Poisoned lock")))
+            .collect()
+    }
+
     /// Inserts a `Block` in the block devices list using the specified configuration.
     /// If a block with the same id already exists, it will overwrite it.
     /// Inserting a secondary root block device will fail.
@@ -267,6 +289,33 @@ mod tests {
             assert_eq!(block.is_read_only(), dummy_block_device.is_read_only);
         }
         assert_eq!(block_devs.get_index_of_drive_id(&dummy_id), Some(0));
+        assert_eq!(block_devs.configs(), vec![dummy_block_device]);
+    }
+
+    #[test]
+    fn test_block_configs() {
+        let dummy_file = TempFile::new().unwrap();
+        let dummy_path = dummy_file.as_path().to_str().unwrap().to_string();
+        let block_device_config = || BlockDeviceConfig {
+            path_on_host: dummy_path.clone(),
+            is_root_device: true,
+            partuuid: Some("0eaa91a0-01".to_string()),
+            cache_type: CacheType::Writeback,
+            is_read_only: true,
+            drive_id: String::from("1"),
+            rate_limiter: Some(RateLimiterConfig {
+                bandwidth: Some(super::super::TokenBucketConfig {
+                    size: 1000,
+                    one_time_burst: Some(2000),
+                    refill_time: 100,
+                }),
+                ops: None,
+            }),
+        };
+
+        let mut block_devs = BlockBuilder::new();
+        block_devs.insert(block_device_config()).unwrap();
+        assert_eq!(block_devs.configs(), vec![block_device_config()]);
     }
 
     #[test]
@@ -4,6 +4,7 @@
 //! Auxiliary module for configuring the logger.
 use serde::{de, Deserialize, Deserializer, Serialize};
 use std::fmt::{Display, Formatter};
+use std::fs::File;
 use std::path::PathBuf;
 
 use super::{open_file_nonblock, FcLineWriter};
@@ -125,9 +126,22 @@ impl Display for LoggerConfigError {
     }
 }
 
-/// Configures the logger as described in `logger_cfg`.
-pub fn init_logger(
-    logger_cfg: LoggerConfig,
+/// Opens the log file described in `logger_cfg`, failing if the logger was already initialized.
+/// The logger itself is left untouched.
+pub fn open_log_file(logger_cfg: &LoggerConfig) -> std::result::Result<File, LoggerConfigError> {
+    if LOGGER.is_initialized() {
+        return Err(LoggerConfigError::InitializationFailure(
+            "Reinitialization of logger not allowed.".to_string(),
+        ));
+    }
+    open_file_nonblock(&logger_cfg.log_path)
+        .map_err(|e| LoggerConfigError::InitializationFailure(e.to_string()))
+}
+
+/// Configures the logger as described in `logger_cfg`, writing to the already opened `log_file`.
+pub fn init_logger_with_file(
+    logger_cfg: &LoggerConfig,
+    log_file: File,
     instance_info: &InstanceInfo,
 ) -> std::result::Result<(), LoggerConfigError> {
     LOGGER
@@ -135,21 +149,26 @@ pub fn init_logger(
         .set_include_origin(logger_cfg.show_log_origin, logger_cfg.show_log_origin)
         .set_include_level(logger_cfg.show_level);
 
-    let writer = FcLineWriter::new(
-        open_file_nonblock(&logger_cfg.log_path)
-            .map_err(|e| LoggerConfigError::InitializationFailure(e.to_string()))?,
-    );
     LOGGER
         .init(
             format!(
                 "Running {} v{}",
                 instance_info.app_name, instance_info.vmm_version
             ),
-            Box::new(writer),
+            Box::new(FcLineWriter::new(log_file)),
         )
         .map_err(|e| LoggerConfigError::InitializationFailure(e.to_string()))
 }
 
+/// Configures the logger as described in `logger_cfg`.
+pub fn init_logger(
+    logger_cfg: LoggerConfig,
+    instance_info: &InstanceInfo,
+) -> std::result::Result<(), LoggerConfigError> {
+    let log_file = open_log_file(&logger_cfg)?;
+    init_logger_with_file(&logger_cfg, log_file, instance_info)
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::{BufRead, BufReader};
@@ -189,7 +208,12 @@ mod tests {
         };
 
         assert!(init_logger(desc.clone(), &default_instance_info).is_ok());
-        assert!(init_logger(desc, &default_instance_info).is_err());
+        assert!(init_logger(desc.clone(), &default_instance_info).is_err());
+        // Once initialized, the logger doesn't open other files.
+        assert_eq!(
+            open_log_file(&desc).unwrap_err().to_string(),
+            "Reinitialization of logger not allowed."
+        );
 
         // Validate logfile works.
         warn!("this is a test");
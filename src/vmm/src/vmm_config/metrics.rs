@@ -3,10 +3,11 @@
 
 //! Auxiliary module for configuring the metrics system.
 use std::fmt::{Display, Formatter};
+use std::fs::File;
 use std::path::PathBuf;
 
 use super::{open_file_nonblock, FcLineWriter};
-use logger::METRICS;
+use logger::{MetricsError, METRICS};
 
 use serde::{Deserialize, Serialize};
 
@@ -33,17 +34,32 @@ impl Display for MetricsConfigError {
     }
 }
 
-/// Configures the metrics as described in `metrics_cfg`.
-pub fn init_metrics(metrics_cfg: MetricsConfig) -> std::result::Result<(), MetricsConfigError> {
-    let writer = FcLineWriter::new(
-        open_file_nonblock(&metrics_cfg.metrics_path)
-            .map_err(|e| MetricsConfigError::InitializationFailure(e.to_string()))?,
-    );
+/// Opens the metrics file described in `metrics_cfg`, failing if the metrics system was
+/// already initialized. The metrics system itself is left untouched.
+pub fn open_metrics_file(
+    metrics_cfg: &MetricsConfig,
+) -> std::result::Result<File, MetricsConfigError> {
+    if METRICS.is_initialized() {
+        return Err(MetricsConfigError::InitializationFailure(
+            MetricsError::AlreadyInitialized.to_string(),
+        ));
+    }
+    open_file_nonblock(&metrics_cfg.metrics_path)
+        .map_err(|e| MetricsConfigError::InitializationFailure(e.to_string()))
+}
+
+/// Configures the metrics to be written to the already opened `metrics_file`.
+pub fn init_metrics_with_file(metrics_file: File) -> std::result::Result<(), MetricsConfigError> {
     METRICS
-        .init(Box::new(writer))
+        .init(Box::new(FcLineWriter::new(metrics_file)))
         .map_err(|e| MetricsConfigError::InitializationFailure(e.to_string()))
 }
 
+/// Configures the metrics as described in `metrics_cfg`.
+pub fn init_metrics(metrics_cfg: MetricsConfig) -> std::result::Result<(), MetricsConfigError> {
+    init_metrics_with_file(open_metrics_file(&metrics_cfg)?)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -64,7 +80,12 @@ mod tests {
         };
 
         assert!(init_metrics(desc.clone()).is_ok());
-        assert!(init_metrics(desc).is_err());
+        assert!(init_metrics(desc.clone()).is_err());
+        // Once initialized, the metrics system doesn't open other files.
+        assert_eq!(
+            open_metrics_file(&desc).unwrap_err().to_string(),
+            "Reinitialization of metrics not allowed."
+        );
     }
 
     #[test]
@@ -1,16 +1,44 @@
 // Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-use serde::{export::Formatter, Deserialize};
+use serde::{export::Formatter, Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt::{Display, Result};
-use std::net::Ipv4Addr;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::sync::{Arc, Mutex};
+
+pub use mmds::data_store::Error as MmdsError;
+use mmds::data_store::{Mmds, DEFAULT_DATA_STORE_LIMIT};
+use serde_json::Value;
+
+pub use mmds::token::TokenMode;
 
 /// Keeps the MMDS configuration.
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct MmdsConfig {
     /// MMDS IPv4 configured address.
     pub ipv4_address: Option<Ipv4Addr>,
+    /// Whether guest requests must present a session token.
+    #[serde(default)]
+    pub token_mode: TokenMode,
+    /// ID of the network interface this configuration applies to. Guest requests received
+    /// on this interface are served from a data store dedicated to it. When missing, the
+    /// configuration applies to the shared data store and to all the interfaces without a
+    /// dedicated one.
+    #[serde(default)]
+    pub iface_id: Option<String>,
+    /// Maximum size, in bytes, of the serialized contents of the data store.
+    #[serde(default)]
+    pub data_store_limit: Option<usize>,
+    /// MMDS IPv6 link-local address. The MMDS is only reachable over IPv6 when one is
+    /// configured.
+    #[serde(default)]
+    pub ipv6_address: Option<Ipv6Addr>,
+    /// Vsock port on which the guest can reach the shared data store, on every vsock device.
+    /// Only valid for the shared configuration.
+    #[serde(default)]
+    pub vsock_port: Option<u32>,
 }
 
 impl MmdsConfig {
@@ -19,6 +47,32 @@ impl MmdsConfig {
     pub fn ipv4_addr(&self) -> Option<Ipv4Addr> {
         self.ipv4_address
     }
+
+    /// Returns the MMDS session token mode.
+    pub fn token_mode(&self) -> TokenMode {
+        self.token_mode
+    }
+
+    /// Returns the ID of the network interface this configuration applies to, if any.
+    pub fn iface_id(&self) -> Option<&str> {
+        self.iface_id.as_deref()
+    }
+
+    /// Returns the maximum size of the data store contents, falling back to the default one.
+    pub fn data_store_limit(&self) -> usize {
+        self.data_store_limit.unwrap_or(DEFAULT_DATA_STORE_LIMIT)
+    }
+
+    /// Returns the MMDS IPv6 address if one was configured.
+    /// Otherwise returns None.
+    pub fn ipv6_addr(&self) -> Option<Ipv6Addr> {
+        self.ipv6_address
+    }
+
+    /// Returns the vsock port on which the MMDS is served, if any.
+    pub fn vsock_port(&self) -> Option<u32> {
+        self.vsock_port
+    }
 }
 
 /// MMDS configuration related errors.
@@ -26,6 +80,12 @@ impl MmdsConfig {
 pub enum MmdsConfigError {
     /// The provided IPv4 address is not link-local valid.
     InvalidIpv4Addr,
+    /// The provided IPv6 address is not link-local valid.
+    InvalidIpv6Addr,
+    /// The network interface does not exist or does not allow MMDS requests.
+    InvalidNetworkInterfaceId(String),
+    /// A vsock port was provided for a network interface configuration.
+    InvalidVsockPort,
 }
 
 impl Display for MmdsConfigError {
@@ -34,6 +94,176 @@ impl Display for MmdsConfigError {
             MmdsConfigError::InvalidIpv4Addr => {
                 write!(f, "The MMDS IPv4 address is not link local.")
             }
+            MmdsConfigError::InvalidIpv6Addr => {
+                write!(f, "The MMDS IPv6 address is not link local.")
+            }
+            MmdsConfigError::InvalidNetworkInterfaceId(iface_id) => write!(
+                f,
+                "The network interface {} does not exist or does not allow MMDS requests.",
+                iface_id
+            ),
+            MmdsConfigError::InvalidVsockPort => write!(
+                f,
+                "The MMDS vsock port can only be set in the configuration without a network \
+                 interface ID."
+            ),
+        }
+    }
+}
+
+/// Errors associated with accessing the MMDS data stores.
+#[derive(Debug)]
+pub enum MmdsStoreError {
+    /// The data store operation failed.
+    DataStore(MmdsError),
+    /// No data store is dedicated to the network interface.
+    NoDedicatedStore(String),
+}
+
+impl Display for MmdsStoreError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            MmdsStoreError::DataStore(err) => write!(f, "{}", err),
+            MmdsStoreError::NoDedicatedStore(iface_id) => write!(
+                f,
+                "No MMDS data store is dedicated to the network interface {}.",
+                iface_id
+            ),
+        }
+    }
+}
+
+/// The MMDS data stores of a microVM: a shared one, serving the guest requests received on
+/// all the network interfaces without a dedicated data store, and the dedicated ones.
+///
+/// Cloning yields handles to the same data stores.
+#[derive(Clone, Default)]
+pub struct MmdsStores {
+    shared: Arc<Mutex<Mmds>>,
+    dedicated: HashMap<String, Arc<Mutex<Mmds>>>,
+}
+
+impl MmdsStores {
+    /// Returns the shared data store.
+    pub fn shared(&self) -> &Arc<Mutex<Mmds>> {
+        &self.shared
+    }
+
+    /// Returns the data store dedicated to `iface_id`, or the shared one if `iface_id` is
+    /// `None`.
+    pub fn get(&self, iface_id: Option<&str>) -> Option<&Arc<Mutex<Mmds>>> {
+        match iface_id {
+            Some(iface_id) => self.dedicated.get(iface_id),
+            None => Some(&self.shared),
         }
     }
+
+    /// Returns the data store serving the guest requests received on `iface_id`.
+    pub fn for_iface(&self, iface_id: &str) -> Arc<Mutex<Mmds>> {
+        self.dedicated.get(iface_id).unwrap_or(&self.shared).clone()
+    }
+
+    /// Returns the data store dedicated to `iface_id`, creating it if needed.
+    pub fn dedicate(&mut self, iface_id: &str) -> Arc<Mutex<Mmds>> {
+        self.dedicated
+            .entry(iface_id.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Returns an iterator over the dedicated data stores and their network interface IDs.
+    pub fn dedicated(&self) -> impl Iterator<Item = (&String, &Arc<Mutex<Mmds>>)> {
+        self.dedicated.iter()
+    }
+
+    /// Returns the contents of the data store selected as in `get`.
+    pub fn get_data(&self, iface_id: Option<&str>) -> std::result::Result<Value, MmdsStoreError> {
+        Ok(self
+            .store(iface_id)?
+            .lock()
+            .expect("Poisoned lock")
+            .data_store_value())
+    }
+
+    /// Replaces the contents of the data store selected as in `get`.
+    pub fn put_data(
+        &self,
+        iface_id: Option<&str>,
+        data: Value,
+    ) -> std::result::Result<(), MmdsStoreError> {
+        self.store(iface_id)?
+            .lock()
+            .expect("Poisoned lock")
+            .put_data(data)
+            .map_err(MmdsStoreError::DataStore)
+    }
+
+    /// Patches the contents of the data store selected as in `get`.
+    pub fn patch_data(
+        &self,
+        iface_id: Option<&str>,
+        data: Value,
+    ) -> std::result::Result<(), MmdsStoreError> {
+        self.store(iface_id)?
+            .lock()
+            .expect("Poisoned lock")
+            .patch_data(data)
+            .map_err(MmdsStoreError::DataStore)
+    }
+
+    fn store(
+        &self,
+        iface_id: Option<&str>,
+    ) -> std::result::Result<&Arc<Mutex<Mmds>>, MmdsStoreError> {
+        self.get(iface_id).ok_or_else(|| {
+            MmdsStoreError::NoDedicatedStore(iface_id.unwrap_or_default().to_string())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_mmds_stores() {
+        let mut stores = MmdsStores::default();
+        stores.put_data(None, json!({"shared": 1})).unwrap();
+
+        // Interfaces without a dedicated store share the same one.
+        assert!(Arc::ptr_eq(&stores.for_iface("eth0"), stores.shared()));
+        assert_eq!(
+            stores.get_data(Some("eth0")).unwrap_err().to_string(),
+            "No MMDS data store is dedicated to the network interface eth0."
+        );
+
+        let dedicated = stores.dedicate("eth0");
+        assert!(Arc::ptr_eq(&dedicated, &stores.dedicate("eth0")));
+        assert!(Arc::ptr_eq(&dedicated, &stores.for_iface("eth0")));
+        assert_eq!(stores.dedicated().count(), 1);
+
+        stores.put_data(Some("eth0"), json!({"a": 1})).unwrap();
+        stores.patch_data(Some("eth0"), json!({"b": 2})).unwrap();
+        assert_eq!(
+            stores.get_data(Some("eth0")).unwrap(),
+            json!({"a": 1, "b": 2})
+        );
+        assert_eq!(stores.get_data(None).unwrap(), json!({"shared": 1}));
+        assert!(stores.get_data(Some("eth1")).is_err());
+
+        // Clones share the data stores.
+        let clone = stores.clone();
+        clone.put_data(Some("eth0"), json!({"c": 3})).unwrap();
+        assert_eq!(stores.get_data(Some("eth0")).unwrap(), json!({"c": 3}));
+
+        stores.dedicate("eth1");
+        assert_eq!(
+            stores
+                .patch_data(Some("eth1"), json!({}))
+                .unwrap_err()
+                .to_string(),
+            MmdsError::NotInitialized.to_string()
+        );
+    }
 }
@@ -8,7 +8,7 @@ use std::os::unix::fs::OpenOptionsExt;
 use std::path::PathBuf;
 
 use libc::O_NONBLOCK;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use rate_limiter::{BucketUpdate, RateLimiter, TokenBucket};
 
@@ -46,11 +46,12 @@ pub mod vsock;
 
 /// A public-facing, stateless structure, holding all the data we need to create a TokenBucket
 /// (live) object.
-#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
 pub struct TokenBucketConfig {
     /// See TokenBucket::size.
     pub size: u64,
     /// See TokenBucket::one_time_burst.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub one_time_burst: Option<u64>,
     /// See TokenBucket::refill_time.
     pub refill_time: u64,
@@ -58,15 +59,45 @@ pub struct TokenBucketConfig {
 
 /// A public-facing, stateless structure, holding all the data we need to create a RateLimiter
 /// (live) object.
-#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct RateLimiterConfig {
     /// Data used to initialize the RateLimiter::bandwidth bucket.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub bandwidth: Option<TokenBucketConfig>,
     /// Data used to initialize the RateLimiter::ops bucket.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub ops: Option<TokenBucketConfig>,
 }
 
+impl From<&TokenBucket> for TokenBucketConfig {
+    fn from(tb: &TokenBucket) -> Self {
+        let one_time_burst = match tb.initial_one_time_burst() {
+            0 => None,
+            one_time_burst => Some(one_time_burst),
+        };
+        TokenBucketConfig {
+            size: tb.capacity(),
+            one_time_burst,
+            refill_time: tb.refill_time_ms(),
+        }
+    }
+}
+
+impl From<&RateLimiter> for RateLimiterConfig {
+    fn from(rl: &RateLimiter) -> Self {
+        RateLimiterConfig {
+            bandwidth: rl.bandwidth().map(TokenBucketConfig::from),
+            ops: rl.ops().map(TokenBucketConfig::from),
+        }
+    }
+}
+
+/// Returns the configuration of a live rate limiter, or `None` if it doesn't limit anything.
+pub(crate) fn rate_limiter_config(rl: &RateLimiter) -> Option<RateLimiterConfig> {
+    Some(RateLimiterConfig::from(rl)).filter(|cfg| *cfg != RateLimiterConfig::default())
+}
+
 /// A public-facing, stateless structure, specifying RateLimiter properties updates.
 pub struct RateLimiterUpdate {
     /// Possible update to the RateLimiter::bandwidth bucket.
@@ -177,6 +208,10 @@ mod tests {
         assert_eq!(rl.ops().unwrap().capacity(), SIZE * 2);
         assert_eq!(rl.ops().unwrap().one_time_burst(), 0);
         assert_eq!(rl.ops().unwrap().refill_time_ms(), REFILL_TIME * 2);
+
+        // The configuration can be read back from the live rate limiter.
+        assert_eq!(rate_limiter_config(&rl), Some(rlconf));
+        assert_eq!(rate_limiter_config(&RateLimiter::default()), None);
     }
 
     #[test]
@@ -6,23 +6,28 @@ use std::fmt;
 use std::result;
 use std::sync::{Arc, Mutex};
 
-use super::RateLimiterConfig;
+use super::{rate_limiter_config, RateLimiterConfig};
 use crate::Error as VmmError;
-use devices::virtio::net::TapError;
+use devices::virtio::net::{HostEndpoint, TapError};
 use devices::virtio::Net;
+use logger::{IncMetric, NetInterfaceMetrics};
+use mmds::data_store::Mmds;
 use utils::net::mac::MacAddr;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 /// This struct represents the strongly typed equivalent of the json body from net iface
 /// related requests.
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct NetworkInterfaceConfig {
     /// ID of the guest network interface.
     pub iface_id: String,
     /// Host level path for the guest network interface.
+    #[serde(default)]
     pub host_dev_name: String,
+    /// Path of a Unix socket peer to exchange frames with, instead of a TAP device.
+    pub unix_socket_path: Option<String>,
     /// Guest MAC address.
     pub guest_mac: Option<MacAddr>,
     /// Rate Limiter for received packages.
@@ -45,13 +50,36 @@ fn default_allow_mmds_requests() -> bool {
     false
 }
 
-/// The data fed into a network iface update request. Currently, only the RX and TX rate limiters
-/// can be updated.
-#[derive(Debug, Deserialize, PartialEq, Clone)]
+impl From<&Net> for NetworkInterfaceConfig {
+    fn from(net: &Net) -> Self {
+        let (host_dev_name, unix_socket_path) = match net.host_endpoint() {
+            HostEndpoint::Tap(name) => (name, None),
+            HostEndpoint::UnixSocket(path) => (String::new(), Some(path)),
+        };
+        NetworkInterfaceConfig {
+            iface_id: net.id().clone(),
+            host_dev_name,
+            unix_socket_path,
+            guest_mac: net.guest_mac().copied(),
+            rx_rate_limiter: rate_limiter_config(net.rx_rate_limiter()),
+            tx_rate_limiter: rate_limiter_config(net.tx_rate_limiter()),
+            allow_mmds_requests: net.mmds_ns().is_some(),
+        }
+    }
+}
+
+/// The data fed into a network iface update request. Currently, only the host TAP device, the
+/// link state and the RX and TX rate limiters can be updated.
+#[derive(Debug, Deserialize, PartialEq, Clone, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct NetworkInterfaceUpdateConfig {
     /// The net iface ID, as provided by the user at iface creation time.
     pub iface_id: String,
+    /// New host TAP device for the interface. Only interfaces already backed by a TAP device
+    /// can be re-pointed.
+    pub host_dev_name: Option<String>,
+    /// New link state reported to the guest.
+    pub link_up: Option<bool>,
     /// New RX rate limiter config. Only provided data will be updated. I.e. if any optional data
     /// is missing, it will not be nullified, but left unchanged.
     pub rx_rate_limiter: Option<RateLimiterConfig>,
@@ -60,6 +88,57 @@ pub struct NetworkInterfaceUpdateConfig {
     pub tx_rate_limiter: Option<RateLimiterConfig>,
 }
 
+/// Traffic statistics of a network interface, accumulated since its creation.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct NetworkInterfaceStats {
+    /// The net iface ID, as provided by the user at iface creation time.
+    pub iface_id: String,
+    /// Number of bytes received by the guest.
+    pub rx_bytes: usize,
+    /// Number of packets received by the guest.
+    pub rx_packets: usize,
+    /// Number of errors while writing received frames to the guest.
+    pub rx_errors: usize,
+    /// Number of times receiving was throttled by the RX rate limiter.
+    pub rx_rate_limiter_throttled: usize,
+    /// Number of frames sent to the guest by the MMDS.
+    pub rx_mmds_frames: usize,
+    /// Number of bytes transmitted by the guest.
+    pub tx_bytes: usize,
+    /// Number of packets transmitted by the guest.
+    pub tx_packets: usize,
+    /// Number of frames transmitted by the guest which were dropped.
+    pub tx_dropped: usize,
+    /// Number of times transmitting was throttled by the TX rate limiter.
+    pub tx_rate_limiter_throttled: usize,
+    /// Number of frames transmitted by the guest which were consumed by the MMDS.
+    pub tx_mmds_frames: usize,
+    /// Number of failed reads from the host backend.
+    pub host_read_errors: usize,
+    /// Number of failed writes to the host backend.
+    pub host_write_errors: usize,
+}
+
+impl NetworkInterfaceStats {
+    pub(crate) fn new(iface_id: &str, metrics: &NetInterfaceMetrics) -> Self {
+        NetworkInterfaceStats {
+            iface_id: iface_id.to_string(),
+            rx_bytes: metrics.rx_bytes_count.count(),
+            rx_packets: metrics.rx_packets_count.count(),
+            rx_errors: metrics.rx_fails.count(),
+            rx_rate_limiter_throttled: metrics.rx_rate_limiter_throttled.count(),
+            rx_mmds_frames: metrics.rx_mmds_frames.count(),
+            tx_bytes: metrics.tx_bytes_count.count(),
+            tx_packets: metrics.tx_packets_count.count(),
+            tx_dropped: metrics.tx_dropped.count(),
+            tx_rate_limiter_throttled: metrics.tx_rate_limiter_throttled.count(),
+            tx_mmds_frames: metrics.tx_mmds_frames.count(),
+            host_read_errors: metrics.tap_read_fails.count(),
+            host_write_errors: metrics.tap_write_fails.count(),
+        }
+    }
+}
+
 /// Errors associated with `NetworkInterfaceConfig`.
 #[derive(Debug)]
 pub enum NetworkInterfaceError {
@@ -69,8 +148,18 @@ pub enum NetworkInterfaceError {
     CreateRateLimiter(std::io::Error),
     /// The MAC address is already in use.
     GuestMacAddressInUse(String),
+    /// Error while attaching the interface to the running microVM.
+    DeviceHotplug(VmmError),
+    /// Error while detaching the interface from the running microVM.
+    DeviceHotUnplug(VmmError),
+    /// The interface ID is already in use.
+    DeviceIdInUse(String),
+    /// The interface ID doesn't match any interface.
+    DeviceNotFound(String),
     /// Error during interface update (patch).
     DeviceUpdate(VmmError),
+    /// Both a TAP device and a Unix socket were specified as the interface backend.
+    MultipleBackends,
     /// Cannot open/create tap device.
     OpenTap(TapError),
 }
@@ -86,7 +175,15 @@ impl fmt::Display for NetworkInterfaceError {
                 "{}",
                 format!("The guest MAC address {} is already in use.", mac_addr)
             ),
+            DeviceHotplug(e) => write!(f, "Error during interface hot-plug: {}", e),
+            DeviceHotUnplug(e) => write!(f, "Error during interface hot-unplug: {}", e),
+            DeviceIdInUse(id) => write!(f, "The interface ID {} is already in use.", id),
+            DeviceNotFound(id) => write!(f, "No interface with ID {} was found.", id),
             DeviceUpdate(e) => write!(f, "Error during interface update (patch): {}", e),
+            MultipleBackends => write!(
+                f,
+                "Only one of host_dev_name and unix_socket_path can be specified."
+            ),
             OpenTap(e) => {
                 // We are propagating the Tap Error. This error can contain
                 // imbricated quotes which would result in an invalid json.
@@ -131,9 +228,22 @@ impl NetBuilder {
         self.net_devices.iter_mut()
     }
 
+    /// Returns the configurations of the network devices, reflecting their current state.
+    pub fn configs(&self) -> Vec<NetworkInterfaceConfig> {
+        self.net_devices
+            .iter()
+            .map(|net| NetworkInterfaceConfig::from(&*net.lock().expect("Poisoned lock")))
+            .collect()
+    }
+
     /// Builds a network device based on a network interface config. Keeps a device reference
-    /// in the builder's internal list.
-    pub fn build(&mut self, netif_config: NetworkInterfaceConfig) -> Result<Arc<Mutex<Net>>> {
+    /// in the builder's internal list. If the interface allows MMDS requests, they are served
+    /// from the `mmds` data store.
+    pub fn build(
+        &mut self,
+        netif_config: NetworkInterfaceConfig,
+        mmds: Arc<Mutex<Mmds>>,
+    ) -> Result<Arc<Mutex<Net>>> {
         let mac_conflict = |net: &Arc<Mutex<Net>>| {
             let net = net.lock().expect("Poisoned lock");
             // Check if another net dev has same MAC.
@@ -160,14 +270,39 @@ impl NetBuilder {
         }
 
         // Add new device.
-        let net = Arc::new(Mutex::new(Self::create_net(netif_config)?));
+        let net = Arc::new(Mutex::new(Self::create_net(netif_config, mmds)?));
         self.net_devices.push(net.clone());
 
         Ok(net)
     }
 
+    /// Removes all the network devices, closing their TAP devices.
+    pub fn clear(&mut self) {
+        self.net_devices.clear();
+    }
+
+    /// Removes the network device with `iface_id` ID from the builder's internal list.
+    pub fn remove(&mut self, iface_id: &str) -> Result<Arc<Mutex<Net>>> {
+        let index = self
+            .net_devices
+            .iter()
+            .position(|net| net.lock().expect("Poisoned lock").id() == iface_id)
+            .ok_or_else(|| NetworkInterfaceError::DeviceNotFound(iface_id.to_string()))?;
+        Ok(self.net_devices.swap_remove(index))
+    }
+
+    /// Returns the traffic statistics of the network device with `iface_id` ID.
+    pub fn stats(&self, iface_id: &str) -> Result<NetworkInterfaceStats> {
+        self.net_devices
+            .iter()
+            .map(|net| net.lock().expect("Poisoned lock"))
+            .find(|net| net.id() == iface_id)
+            .map(|net| NetworkInterfaceStats::new(iface_id, net.metrics()))
+            .ok_or_else(|| NetworkInterfaceError::DeviceNotFound(iface_id.to_string()))
+    }
+
     /// Creates a Net device from a NetworkInterfaceConfig.
-    pub fn create_net(cfg: NetworkInterfaceConfig) -> Result<Net> {
+    pub fn create_net(cfg: NetworkInterfaceConfig, mmds: Arc<Mutex<Mmds>>) -> Result<Net> {
         let rx_rate_limiter = cfg
             .rx_rate_limiter
             .map(super::RateLimiterConfig::try_into)
@@ -178,17 +313,36 @@ impl NetBuilder {
             .map(super::RateLimiterConfig::try_into)
             .transpose()
             .map_err(NetworkInterfaceError::CreateRateLimiter)?;
+        let mmds = if cfg.allow_mmds_requests {
+            Some(mmds)
+        } else {
+            None
+        };
 
         // Create and return the Net device
-        devices::virtio::net::Net::new_with_tap(
-            cfg.iface_id,
-            cfg.host_dev_name.clone(),
-            cfg.guest_mac.as_ref(),
-            rx_rate_limiter.unwrap_or_default(),
-            tx_rate_limiter.unwrap_or_default(),
-            cfg.allow_mmds_requests,
-        )
-        .map_err(NetworkInterfaceError::CreateNetworkDevice)
+        match cfg.unix_socket_path {
+            Some(_) if !cfg.host_dev_name.is_empty() => {
+                Err(NetworkInterfaceError::MultipleBackends)
+            }
+            Some(path) => devices::virtio::net::Net::new_with_unix_socket(
+                cfg.iface_id,
+                path,
+                cfg.guest_mac.as_ref(),
+                rx_rate_limiter.unwrap_or_default(),
+                tx_rate_limiter.unwrap_or_default(),
+                mmds,
+            )
+            .map_err(NetworkInterfaceError::CreateNetworkDevice),
+            None => devices::virtio::net::Net::new_with_tap(
+                cfg.iface_id,
+                cfg.host_dev_name.clone(),
+                cfg.guest_mac.as_ref(),
+                rx_rate_limiter.unwrap_or_default(),
+                tx_rate_limiter.unwrap_or_default(),
+                mmds,
+            )
+            .map_err(NetworkInterfaceError::CreateNetworkDevice),
+        }
     }
 }
 
@@ -212,6 +366,7 @@ mod tests {
         NetworkInterfaceConfig {
             iface_id: String::from(id),
             host_dev_name: String::from(name),
+            unix_socket_path: None,
             guest_mac: Some(MacAddr::parse_str(mac).unwrap()),
             rx_rate_limiter: Some(RateLimiterConfig::default()),
             tx_rate_limiter: Some(RateLimiterConfig::default()),
@@ -224,6 +379,7 @@ mod tests {
             NetworkInterfaceConfig {
                 iface_id: self.iface_id.clone(),
                 host_dev_name: self.host_dev_name.clone(),
+                unix_socket_path: self.unix_socket_path.clone(),
                 guest_mac: self.guest_mac,
                 rx_rate_limiter: None,
                 tx_rate_limiter: None,
@@ -242,21 +398,30 @@ mod tests {
 
         // Test create.
         let netif_1 = create_netif(id_1, host_dev_name_1, guest_mac_1);
-        assert!(net_builder.build(netif_1).is_ok());
+        assert!(net_builder.build(netif_1, Arc::default()).is_ok());
         assert_eq!(net_builder.net_devices.len(), 1);
 
         // Test update mac address (this test does not modify the tap).
         guest_mac_1 = "01:23:45:67:89:0b";
         let netif_1 = create_netif(id_1, host_dev_name_1, guest_mac_1);
 
-        assert!(net_builder.build(netif_1).is_ok());
+        assert!(net_builder.build(netif_1, Arc::default()).is_ok());
         assert_eq!(net_builder.net_devices.len(), 1);
 
         // Test update host_dev_name (the tap will be updated).
         host_dev_name_1 = "dev2";
         let netif_1 = create_netif(id_1, host_dev_name_1, guest_mac_1);
-        assert!(net_builder.build(netif_1).is_ok());
+        assert!(net_builder.build(netif_1, Arc::default()).is_ok());
         assert_eq!(net_builder.net_devices.len(), 1);
+        // Rate limiters without token buckets are read back as missing.
+        assert_eq!(
+            net_builder.configs(),
+            vec![NetworkInterfaceConfig {
+                rx_rate_limiter: None,
+                tx_rate_limiter: None,
+                ..create_netif(id_1, host_dev_name_1, guest_mac_1)
+            }]
+        );
     }
 
     #[test]
@@ -269,7 +434,7 @@ mod tests {
 
         // Adding the first valid network config.
         let netif_1 = create_netif(id_1, host_dev_name_1, guest_mac_1);
-        assert!(net_builder.build(netif_1).is_ok());
+        assert!(net_builder.build(netif_1, Arc::default()).is_ok());
 
         // Error Cases for CREATE
         // Error Case: Add new network config with the same mac as netif_1.
@@ -283,7 +448,11 @@ mod tests {
             guest_mac_1.to_string()
         );
         assert_eq!(
-            net_builder.build(netif_2).err().unwrap().to_string(),
+            net_builder
+                .build(netif_2, Arc::default())
+                .err()
+                .unwrap()
+                .to_string(),
             expected_error
         );
         assert_eq!(net_builder.net_devices.len(), 1);
@@ -291,7 +460,11 @@ mod tests {
         // Error Case: Add new network config with the same dev_host_name as netif_1.
         let netif_2 = create_netif(id_2, host_dev_name_1, guest_mac_2);
         assert_eq!(
-            net_builder.build(netif_2).err().unwrap().to_string(),
+            net_builder
+                .build(netif_2, Arc::default())
+                .err()
+                .unwrap()
+                .to_string(),
             NetworkInterfaceError::CreateNetworkDevice(devices::virtio::net::Error::TapOpen(
                 TapError::IoctlError(std::io::Error::from_raw_os_error(16))
             ))
@@ -301,7 +474,7 @@ mod tests {
 
         // Adding the second valid network config.
         let netif_2 = create_netif(id_2, host_dev_name_2, guest_mac_2);
-        assert!(net_builder.build(netif_2).is_ok());
+        assert!(net_builder.build(netif_2, Arc::default()).is_ok());
 
         // Error Cases for UPDATE
         // Error Case: Update netif_2 mac using the same mac as netif_1.
@@ -311,14 +484,22 @@ mod tests {
             guest_mac_1.to_string()
         );
         assert_eq!(
-            net_builder.build(netif_2).err().unwrap().to_string(),
+            net_builder
+                .build(netif_2, Arc::default())
+                .err()
+                .unwrap()
+                .to_string(),
             expected_error
         );
 
         // Error Case: Update netif_2 dev_host_name using the same dev_host_name as netif_1.
         let netif_2 = create_netif(id_2, host_dev_name_1, guest_mac_2);
         assert_eq!(
-            net_builder.build(netif_2).err().unwrap().to_string(),
+            net_builder
+                .build(netif_2, Arc::default())
+                .err()
+                .unwrap()
+                .to_string(),
             NetworkInterfaceError::CreateNetworkDevice(devices::virtio::net::Error::TapOpen(
                 TapError::IoctlError(std::io::Error::from_raw_os_error(16))
             ))
@@ -326,6 +507,60 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_unix_socket_backend() {
+        let mut net_builder = NetBuilder::new();
+
+        let tmp_file = utils::tempfile::TempFile::new().unwrap();
+        let path = tmp_file.as_path().to_str().unwrap().to_string();
+        drop(tmp_file);
+        let _peer = std::os::unix::net::UnixDatagram::bind(&path).unwrap();
+
+        // Both backends at once are rejected.
+        let mut netif = create_netif("id_1", "dev5", "01:23:45:67:89:0a");
+        netif.unix_socket_path = Some(path.clone());
+        assert_eq!(
+            net_builder
+                .build(netif.clone(), Arc::default())
+                .err()
+                .unwrap()
+                .to_string(),
+            NetworkInterfaceError::MultipleBackends.to_string()
+        );
+        assert!(net_builder.is_empty());
+
+        netif.host_dev_name = String::new();
+        let net = net_builder.build(netif, Arc::default()).unwrap();
+        assert_eq!(
+            net.lock().unwrap().host_endpoint(),
+            devices::virtio::net::HostEndpoint::UnixSocket(path.clone())
+        );
+        assert_eq!(net_builder.len(), 1);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut net_builder = NetBuilder::new();
+        assert!(net_builder.stats("stats_id").is_err());
+
+        let net = net_builder
+            .build(
+                create_netif("stats_id", "dev6", "01:23:45:67:89:0a"),
+                Arc::default(),
+            )
+            .unwrap();
+        net.lock().unwrap().metrics().rx_bytes_count.add(42);
+        net.lock().unwrap().metrics().tx_dropped.inc();
+
+        let stats = net_builder.stats("stats_id").unwrap();
+        assert_eq!(stats.iface_id, "stats_id");
+        assert_eq!(stats.rx_bytes, 42);
+        assert_eq!(stats.tx_dropped, 1);
+        assert_eq!(stats.tx_bytes, 0);
+    }
+
     #[test]
     fn test_error_display() {
         // FIXME: use macro
@@ -340,11 +575,26 @@ mod tests {
             NetworkInterfaceError::DeviceUpdate(VmmError::VcpuExit),
             NetworkInterfaceError::DeviceUpdate(VmmError::VcpuExit)
         );
+        let _ = format!(
+            "{}{:?}",
+            NetworkInterfaceError::MultipleBackends,
+            NetworkInterfaceError::MultipleBackends
+        );
         let _ = format!(
             "{}{:?}",
             NetworkInterfaceError::OpenTap(TapError::InvalidIfname),
             NetworkInterfaceError::OpenTap(TapError::InvalidIfname)
         );
+        let _ = format!(
+            "{}{:?}",
+            NetworkInterfaceError::DeviceHotplug(VmmError::VcpuExit),
+            NetworkInterfaceError::DeviceHotUnplug(VmmError::VcpuExit)
+        );
+        let _ = format!(
+            "{}{:?}",
+            NetworkInterfaceError::DeviceIdInUse(String::new()),
+            NetworkInterfaceError::DeviceNotFound(String::new())
+        );
     }
 
     #[test]
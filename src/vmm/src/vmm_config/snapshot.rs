@@ -4,7 +4,10 @@
 //! Configurations used in the snapshotting context.
 
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 
+use devices::virtio::vsock::persist::VsockConnReattach;
 use serde::{Deserialize, Serialize};
 
 /// The snapshot type options that are available when
@@ -23,6 +26,31 @@ impl Default for SnapshotType {
     }
 }
 
+/// Counts the bytes written while creating a snapshot. Clones share the same
+/// counter, so the progress can be followed from another thread.
+#[derive(Clone, Debug, Default)]
+pub struct SnapshotProgress(Arc<AtomicU64>);
+
+impl SnapshotProgress {
+    /// Records `count` more bytes written.
+    pub fn add(&self, count: u64) {
+        self.0.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Returns the number of bytes written so far.
+    pub fn bytes_written(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// The progress is not part of the configuration, so parameters compare equal
+// regardless of how far they got.
+impl PartialEq for SnapshotProgress {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
 /// Stores the configuration that will be used for creating a snapshot.
 #[derive(Debug, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
@@ -38,6 +66,9 @@ pub struct CreateSnapshotParams {
     /// Optional field for the microVM version. The default
     /// value is the current version.
     pub version: Option<String>,
+    /// Updated with the bytes written to the snapshot files.
+    #[serde(skip)]
+    pub progress: SnapshotProgress,
 }
 
 /// Stores the configuration that will be used for loading a snapshot.
@@ -56,6 +87,43 @@ pub struct LoadSnapshotParams {
     /// is successful.
     #[serde(default)]
     pub resume_vm: bool,
+    /// Host sockets to reattach persisted vsock connections to. Persisted
+    /// connections that don't get reattached are reset.
+    #[serde(default)]
+    pub vsock_reattach: Vec<VsockReattachConfig>,
+    /// When set to true, the MMDS data store saved in the snapshot is
+    /// discarded, instead of replacing the current one.
+    #[serde(default)]
+    pub discard_mmds_data: bool,
+}
+
+/// A host Unix socket to which a persisted vsock connection is reattached,
+/// when loading a snapshot.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct VsockReattachConfig {
+    /// ID of the vsock device the connection belongs to. Only optional
+    /// when the microVM has a single vsock device.
+    #[serde(default)]
+    pub vsock_id: Option<String>,
+    /// The host-side port of the connection.
+    pub host_port: u32,
+    /// The guest-side port of the connection. When not set, the first
+    /// connection on `host_port` that wasn't already reattached is used.
+    pub guest_port: Option<u32>,
+    /// Path to the Unix socket to connect to.
+    pub uds_path: String,
+}
+
+impl From<&VsockReattachConfig> for VsockConnReattach {
+    fn from(cfg: &VsockReattachConfig) -> Self {
+        VsockConnReattach {
+            vsock_id: cfg.vsock_id.clone(),
+            local_port: cfg.host_port,
+            peer_port: cfg.guest_port,
+            uds_path: cfg.uds_path.clone(),
+        }
+    }
 }
 
 /// The microVM state options.
@@ -2,21 +2,35 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use std::fmt;
+use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
 
-use devices::virtio::{Vsock, VsockError, VsockUnixBackend, VsockUnixBackendError};
+use devices::virtio::{
+    Vsock, VsockConnParams, VsockError, VsockUnixBackend, VsockUnixBackendError,
+    VSOCK_BUF_ALLOC_MAX, VSOCK_BUF_ALLOC_MIN, VSOCK_MAX_CONNECTIONS,
+};
 
 use serde::{Deserialize, Serialize};
 
 type MutexVsockUnix = Arc<Mutex<Vsock<VsockUnixBackend>>>;
 
-/// Errors associated with `NetworkInterfaceConfig`.
+/// Errors associated with `VsockDeviceConfig`.
 #[derive(Debug)]
 pub enum VsockConfigError {
     /// Failed to create the backend for the vsock device.
     CreateVsockBackend(VsockUnixBackendError),
     /// Failed to create the vsock device.
     CreateVsockDevice(VsockError),
+    /// A vsock port is mapped to an invalid TCP address.
+    InvalidTcpAddress(String),
+    /// The per-connection buffer size is not a power of two within the supported range.
+    InvalidBufAlloc(u32),
+    /// The maximum number of connections is outside the supported range.
+    InvalidMaxConnections(u32),
+    /// A connection timeout is zero.
+    InvalidConnTimeout,
+    /// The Unix socket path is already used by another vsock device.
+    UdsPathInUse(String),
 }
 
 impl fmt::Display for VsockConfigError {
@@ -27,6 +41,29 @@ impl fmt::Display for VsockConfigError {
                 write!(f, "Cannot create backend for vsock device: {:?}", e)
             }
             CreateVsockDevice(ref e) => write!(f, "Cannot create vsock device: {:?}", e),
+            InvalidTcpAddress(ref addr) => write!(
+                f,
+                "Invalid TCP address for vsock port: {}. Expected <ip>:<port>.",
+                addr
+            ),
+            InvalidBufAlloc(size) => write!(
+                f,
+                "Invalid vsock connection buffer size: {}. Expected a power of two between {} \
+                 and {}.",
+                size, VSOCK_BUF_ALLOC_MIN, VSOCK_BUF_ALLOC_MAX
+            ),
+            InvalidMaxConnections(count) => write!(
+                f,
+                "Invalid maximum number of vsock connections: {}. Expected a value between 1 \
+                 and {}.",
+                count, VSOCK_MAX_CONNECTIONS
+            ),
+            InvalidConnTimeout => write!(f, "Vsock connection timeouts must be greater than 0."),
+            UdsPathInUse(ref path) => write!(
+                f,
+                "The Unix socket path {} is already used by another vsock device.",
+                path
+            ),
         }
     }
 }
@@ -44,54 +81,246 @@ pub struct VsockDeviceConfig {
     pub guest_cid: u32,
     /// Path to local unix socket.
     pub uds_path: String,
+    /// Guest ports that get a dedicated host-side listening socket, at "<uds_path>_listen_<port>".
+    /// Host connections to these sockets are forwarded to the guest port without the
+    /// `CONNECT <port>` handshake.
+    #[serde(default)]
+    pub listen_ports: Vec<u32>,
+    /// Guest ports that host TCP clients can connect to, through a listener bound at the
+    /// given address. No handshake is used over TCP.
+    #[serde(default)]
+    pub tcp_listeners: Vec<VsockTcpPortConfig>,
+    /// Host ports whose guest-initiated connections are forwarded to the TCP endpoint at the
+    /// given address, instead of "<uds_path>_<port>".
+    #[serde(default)]
+    pub tcp_endpoints: Vec<VsockTcpPortConfig>,
+    /// Save live connections in snapshots, so that they can be resumed, or cleanly reset,
+    /// when the snapshot is loaded.
+    #[serde(default)]
+    pub persist_connections: bool,
+    /// The buffer space, in bytes, advertised to the guest for each connection. Must be a
+    /// power of two.
+    #[serde(default)]
+    pub buf_alloc: Option<u32>,
+    /// The maximum number of concurrent connections.
+    #[serde(default)]
+    pub max_connections: Option<u32>,
+    /// The time, in milliseconds, a host-initiated connection waits for the guest to accept it.
+    #[serde(default)]
+    pub conn_request_timeout_ms: Option<u64>,
+    /// The time, in milliseconds, a connection waits for the guest to acknowledge its shutdown
+    /// before being forcefully reset.
+    #[serde(default)]
+    pub conn_shutdown_timeout_ms: Option<u64>,
+}
+
+impl VsockDeviceConfig {
+    fn conn_params(&self) -> Result<VsockConnParams> {
+        let defaults = VsockConnParams::default();
+        let params = VsockConnParams {
+            buf_alloc: self.buf_alloc.unwrap_or(defaults.buf_alloc),
+            request_timeout_ms: self
+                .conn_request_timeout_ms
+                .unwrap_or(defaults.request_timeout_ms),
+            shutdown_timeout_ms: self
+                .conn_shutdown_timeout_ms
+                .unwrap_or(defaults.shutdown_timeout_ms),
+        };
+        if !VsockConnParams::is_valid_buf_alloc(params.buf_alloc) {
+            return Err(VsockConfigError::InvalidBufAlloc(params.buf_alloc));
+        }
+        if params.request_timeout_ms == 0 || params.shutdown_timeout_ms == 0 {
+            return Err(VsockConfigError::InvalidConnTimeout);
+        }
+        Ok(params)
+    }
+
+    fn max_connections(&self) -> Result<usize> {
+        match self.max_connections {
+            None => Ok(VSOCK_MAX_CONNECTIONS),
+            Some(count) if count >= 1 && count as usize <= VSOCK_MAX_CONNECTIONS => {
+                Ok(count as usize)
+            }
+            Some(count) => Err(VsockConfigError::InvalidMaxConnections(count)),
+        }
+    }
+}
+
+/// A vsock port mapped to a host TCP address.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct VsockTcpPortConfig {
+    /// The vsock port.
+    pub port: u32,
+    /// The host TCP address, as `<ip>:<port>`.
+    pub addr: String,
+}
+
+impl VsockTcpPortConfig {
+    fn socket_addr(&self) -> Result<SocketAddr> {
+        self.addr
+            .parse()
+            .map_err(|_| VsockConfigError::InvalidTcpAddress(self.addr.clone()))
+    }
 }
 
 struct VsockAndUnixPath {
     vsock: MutexVsockUnix,
     uds_path: String,
+    listen_ports: Vec<u32>,
 }
 
-/// A builder of Vsock with Unix backend from 'VsockDeviceConfig'.
+impl VsockAndUnixPath {
+    fn remove_sockets(&self) -> Result<()> {
+        for port in self.listen_ports.iter() {
+            std::fs::remove_file(VsockUnixBackend::listen_sock_path(&self.uds_path, *port))
+                .map_err(VsockUnixBackendError::UnixBind)
+                .map_err(VsockConfigError::CreateVsockBackend)?;
+        }
+        std::fs::remove_file(&self.uds_path)
+            .map_err(VsockUnixBackendError::UnixBind)
+            .map_err(VsockConfigError::CreateVsockBackend)
+    }
+
+    fn id(&self) -> String {
+        self.vsock.lock().expect("Poisoned lock").id().to_string()
+    }
+
+    fn config(&self) -> VsockDeviceConfig {
+        let vsock = self.vsock.lock().expect("Poisoned lock");
+        let backend = vsock.backend();
+        let conn_params = backend.conn_params();
+        let port_configs = |ports: Vec<(u32, SocketAddr)>| {
+            ports
+                .into_iter()
+                .map(|(port, addr)| VsockTcpPortConfig {
+                    port,
+                    addr: addr.to_string(),
+                })
+                .collect()
+        };
+        VsockDeviceConfig {
+            vsock_id: vsock.id().to_string(),
+            guest_cid: vsock.cid() as u32,
+            uds_path: self.uds_path.clone(),
+            listen_ports: backend.listen_ports(),
+            tcp_listeners: port_configs(backend.tcp_listeners()),
+            tcp_endpoints: port_configs(backend.tcp_endpoints()),
+            persist_connections: backend.persist_connections(),
+            buf_alloc: Some(conn_params.buf_alloc),
+            max_connections: Some(backend.max_connections() as u32),
+            conn_request_timeout_ms: Some(conn_params.request_timeout_ms),
+            conn_shutdown_timeout_ms: Some(conn_params.shutdown_timeout_ms),
+        }
+    }
+}
+
+/// A builder of Vsock devices with Unix backend from 'VsockDeviceConfig'.
 #[derive(Default)]
 pub struct VsockBuilder {
-    inner: Option<VsockAndUnixPath>,
+    vsock_devices: Vec<VsockAndUnixPath>,
 }
 
 impl VsockBuilder {
     /// Creates an empty Vsock with Unix backend Store.
     pub fn new() -> Self {
-        Self { inner: None }
+        Self {
+            vsock_devices: Vec::new(),
+        }
     }
 
     /// Inserts a Unix backend Vsock in the store.
-    /// If an entry already exists, it will overwrite it.
+    /// If an entry with the same ID already exists, it will overwrite it.
     pub fn insert(&mut self, cfg: VsockDeviceConfig) -> Result<()> {
+        if self
+            .vsock_devices
+            .iter()
+            .any(|entry| entry.uds_path == cfg.uds_path && entry.id() != cfg.vsock_id)
+        {
+            return Err(VsockConfigError::UdsPathInUse(cfg.uds_path));
+        }
+
         // Make sure to drop the old one and remove the socket before creating a new one.
-        if let Some(existing) = self.inner.take() {
-            std::fs::remove_file(existing.uds_path)
-                .map_err(VsockUnixBackendError::UnixBind)
-                .map_err(VsockConfigError::CreateVsockBackend)?;
+        if let Some(index) = self
+            .vsock_devices
+            .iter()
+            .position(|entry| entry.id() == cfg.vsock_id)
+        {
+            self.vsock_devices.swap_remove(index).remove_sockets()?;
         }
-        self.inner = Some(VsockAndUnixPath {
+        self.vsock_devices.push(VsockAndUnixPath {
             uds_path: cfg.uds_path.clone(),
+            listen_ports: cfg.listen_ports.clone(),
             vsock: Arc::new(Mutex::new(Self::create_unixsock_vsock(cfg)?)),
         });
         Ok(())
     }
 
-    /// Provides a reference to the Vsock if present.
-    pub fn get(&self) -> Option<&MutexVsockUnix> {
-        self.inner.as_ref().map(|pair| &pair.vsock)
+    /// Removes all the vsock devices, closing their sockets and removing the Unix ones.
+    pub fn clear(&mut self) {
+        for entry in self.vsock_devices.drain(..) {
+            // Leftover sockets are reported when they get in the way of a new device.
+            let _ = entry.remove_sockets();
+        }
+    }
+
+    /// Returns an immutable iterator over the vsock devices.
+    pub fn iter(&self) -> impl Iterator<Item = &MutexVsockUnix> {
+        self.vsock_devices.iter().map(|entry| &entry.vsock)
+    }
+
+    /// Provides a reference to the Vsock with the given ID, if present.
+    pub fn get(&self, vsock_id: &str) -> Option<&MutexVsockUnix> {
+        self.iter()
+            .find(|vsock| vsock.lock().expect("Poisoned lock").id() == vsock_id)
+    }
+
+    /// Returns the configurations of the vsock devices, reflecting their current state.
+    pub fn configs(&self) -> Vec<VsockDeviceConfig> {
+        self.vsock_devices
+            .iter()
+            .map(VsockAndUnixPath::config)
+            .collect()
     }
 
     /// Creates a Vsock device from a VsockDeviceConfig.
     pub fn create_unixsock_vsock(cfg: VsockDeviceConfig) -> Result<Vsock<VsockUnixBackend>> {
-        let backend = VsockUnixBackend::new(u64::from(cfg.guest_cid), cfg.uds_path)
+        let conn_params = cfg.conn_params()?;
+        let max_connections = cfg.max_connections()?;
+        let mut backend = VsockUnixBackend::new(u64::from(cfg.guest_cid), cfg.uds_path.clone())
             .map_err(VsockConfigError::CreateVsockBackend)?;
+        backend.set_conn_params(conn_params);
+        backend.set_max_connections(max_connections);
+        if let Err(err) = Self::add_port_mappings(&mut backend, &cfg) {
+            // Don't leave behind sockets that would make a retry fail.
+            for port in backend.listen_ports() {
+                let _ = std::fs::remove_file(format!("{}_{}", cfg.uds_path, port));
+            }
+            let _ = std::fs::remove_file(&cfg.uds_path);
+            return Err(err);
+        }
 
-        Ok(Vsock::new(u64::from(cfg.guest_cid), backend)
+        Ok(Vsock::new(cfg.vsock_id, u64::from(cfg.guest_cid), backend)
             .map_err(VsockConfigError::CreateVsockDevice)?)
     }
+
+    fn add_port_mappings(backend: &mut VsockUnixBackend, cfg: &VsockDeviceConfig) -> Result<()> {
+        for port in cfg.listen_ports.iter() {
+            backend
+                .listen_on_port(*port)
+                .map_err(VsockConfigError::CreateVsockBackend)?;
+        }
+        for listener in cfg.tcp_listeners.iter() {
+            backend
+                .listen_on_tcp(listener.port, listener.socket_addr()?)
+                .map_err(VsockConfigError::CreateVsockBackend)?;
+        }
+        for endpoint in cfg.tcp_endpoints.iter() {
+            backend.connect_port_to_tcp(endpoint.port, endpoint.socket_addr()?);
+        }
+        backend.set_persist_connections(cfg.persist_connections);
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -104,6 +333,14 @@ pub(crate) mod tests {
             vsock_id: "vsock".to_string(),
             guest_cid: 3,
             uds_path: tmp_sock_file.as_path().to_str().unwrap().to_string(),
+            listen_ports: Vec::new(),
+            tcp_listeners: Vec::new(),
+            tcp_endpoints: Vec::new(),
+            persist_connections: false,
+            buf_alloc: None,
+            max_connections: None,
+            conn_request_timeout_ms: None,
+            conn_shutdown_timeout_ms: None,
         }
     }
 
@@ -123,14 +360,227 @@ pub(crate) mod tests {
         let mut vsock_config = default_config(&tmp_sock_file);
 
         store.insert(vsock_config.clone()).unwrap();
-        let vsock = store.get().unwrap();
+        let vsock = store.get(&vsock_config.vsock_id).unwrap();
         assert_eq!(vsock.lock().unwrap().id(), &vsock_config.vsock_id);
 
         let new_cid = vsock_config.guest_cid + 1;
         vsock_config.guest_cid = new_cid;
-        store.insert(vsock_config).unwrap();
-        let vsock = store.get().unwrap();
+        store.insert(vsock_config.clone()).unwrap();
+        let vsock = store.get(&vsock_config.vsock_id).unwrap();
         assert_eq!(vsock.lock().unwrap().cid(), new_cid as u64);
+        assert_eq!(store.iter().count(), 1);
+
+        // The defaults are read back explicitly.
+        let defaults = VsockConnParams::default();
+        vsock_config.buf_alloc = Some(defaults.buf_alloc);
+        vsock_config.max_connections = Some(VSOCK_MAX_CONNECTIONS as u32);
+        vsock_config.conn_request_timeout_ms = Some(defaults.request_timeout_ms);
+        vsock_config.conn_shutdown_timeout_ms = Some(defaults.shutdown_timeout_ms);
+        assert_eq!(store.configs(), vec![vsock_config]);
+    }
+
+    #[test]
+    fn test_vsock_insert_multiple() {
+        let mut store = VsockBuilder::new();
+        let mut tmp_sock_file = TempFile::new().unwrap();
+        tmp_sock_file.remove().unwrap();
+        let first_config = default_config(&tmp_sock_file);
+        store.insert(first_config.clone()).unwrap();
+
+        // A second device can't reuse the socket path of the first one.
+        let mut second_config = first_config.clone();
+        second_config.vsock_id = "vsock2".to_string();
+        assert_eq!(
+            store
+                .insert(second_config.clone())
+                .err()
+                .unwrap()
+                .to_string(),
+            VsockConfigError::UdsPathInUse(first_config.uds_path.clone()).to_string()
+        );
+
+        let mut tmp_sock_file = TempFile::new().unwrap();
+        tmp_sock_file.remove().unwrap();
+        second_config.uds_path = tmp_sock_file.as_path().to_str().unwrap().to_string();
+        store.insert(second_config.clone()).unwrap();
+        assert_eq!(store.iter().count(), 2);
+        assert!(store.get(&first_config.vsock_id).is_some());
+        assert!(store.get(&second_config.vsock_id).is_some());
+        assert!(store.get("vsock3").is_none());
+    }
+
+    #[test]
+    fn test_vsock_clear() {
+        let mut store = VsockBuilder::new();
+        let mut tmp_sock_file = TempFile::new().unwrap();
+        tmp_sock_file.remove().unwrap();
+        let mut vsock_config = default_config(&tmp_sock_file);
+        vsock_config.listen_ports = vec![52];
+        store.insert(vsock_config.clone()).unwrap();
+
+        store.clear();
+        assert_eq!(store.iter().count(), 0);
+        assert!(!std::path::Path::new(&vsock_config.uds_path).exists());
+        assert!(!std::path::Path::new(&format!("{}_52", vsock_config.uds_path)).exists());
+
+        // The same sockets can be bound again.
+        store.insert(vsock_config).unwrap();
+        store.clear();
+    }
+
+    #[test]
+    fn test_vsock_conn_params() {
+        let mut tmp_sock_file = TempFile::new().unwrap();
+        tmp_sock_file.remove().unwrap();
+        let mut vsock_config = default_config(&tmp_sock_file);
+
+        let vsock = VsockBuilder::create_unixsock_vsock(vsock_config.clone()).unwrap();
+        assert_eq!(vsock.backend().conn_params(), VsockConnParams::default());
+        assert_eq!(vsock.backend().max_connections(), VSOCK_MAX_CONNECTIONS);
+        drop(vsock);
+        std::fs::remove_file(&vsock_config.uds_path).unwrap();
+
+        vsock_config.buf_alloc = Some(256 * 1024);
+        vsock_config.max_connections = Some(8);
+        vsock_config.conn_request_timeout_ms = Some(100);
+        vsock_config.conn_shutdown_timeout_ms = Some(200);
+        let vsock = VsockBuilder::create_unixsock_vsock(vsock_config.clone()).unwrap();
+        assert_eq!(
+            vsock.backend().conn_params(),
+            VsockConnParams {
+                buf_alloc: 256 * 1024,
+                request_timeout_ms: 100,
+                shutdown_timeout_ms: 200,
+            }
+        );
+        assert_eq!(vsock.backend().max_connections(), 8);
+        drop(vsock);
+        std::fs::remove_file(&vsock_config.uds_path).unwrap();
+
+        // Invalid values are rejected before any socket gets created.
+        let mut invalid_config = vsock_config.clone();
+        invalid_config.buf_alloc = Some(100 * 1024);
+        assert_eq!(
+            VsockBuilder::create_unixsock_vsock(invalid_config)
+                .err()
+                .unwrap()
+                .to_string(),
+            VsockConfigError::InvalidBufAlloc(100 * 1024).to_string()
+        );
+        let mut invalid_config = vsock_config.clone();
+        invalid_config.buf_alloc = Some(VSOCK_BUF_ALLOC_MAX * 2);
+        assert!(VsockBuilder::create_unixsock_vsock(invalid_config).is_err());
+        let mut invalid_config = vsock_config.clone();
+        invalid_config.max_connections = Some(0);
+        assert_eq!(
+            VsockBuilder::create_unixsock_vsock(invalid_config)
+                .err()
+                .unwrap()
+                .to_string(),
+            VsockConfigError::InvalidMaxConnections(0).to_string()
+        );
+        let mut invalid_config = vsock_config.clone();
+        invalid_config.max_connections = Some(VSOCK_MAX_CONNECTIONS as u32 + 1);
+        assert!(VsockBuilder::create_unixsock_vsock(invalid_config).is_err());
+        let mut invalid_config = vsock_config.clone();
+        invalid_config.conn_shutdown_timeout_ms = Some(0);
+        assert_eq!(
+            VsockBuilder::create_unixsock_vsock(invalid_config)
+                .err()
+                .unwrap()
+                .to_string(),
+            VsockConfigError::InvalidConnTimeout.to_string()
+        );
+        assert!(!std::path::Path::new(&vsock_config.uds_path).exists());
+    }
+
+    #[test]
+    fn test_vsock_listen_ports() {
+        let mut store = VsockBuilder::new();
+        let mut tmp_sock_file = TempFile::new().unwrap();
+        tmp_sock_file.remove().unwrap();
+        let mut vsock_config = default_config(&tmp_sock_file);
+        vsock_config.listen_ports = vec![52, 1024];
+
+        store.insert(vsock_config.clone()).unwrap();
+        let port_path = format!("{}_listen_52", vsock_config.uds_path);
+        assert!(std::path::Path::new(&port_path).exists());
+        assert_eq!(
+            store
+                .get(&vsock_config.vsock_id)
+                .unwrap()
+                .lock()
+                .unwrap()
+                .backend()
+                .listen_ports(),
+            vec![52, 1024]
+        );
+
+        // Replacing the device removes the old per-port sockets.
+        vsock_config.listen_ports = vec![1024];
+        store.insert(vsock_config.clone()).unwrap();
+        assert!(!std::path::Path::new(&port_path).exists());
+
+        // Duplicate ports can't be bound twice.
+        let mut tmp_sock_file = TempFile::new().unwrap();
+        tmp_sock_file.remove().unwrap();
+        let mut vsock_config = default_config(&tmp_sock_file);
+        vsock_config.listen_ports = vec![7, 7];
+        assert!(VsockBuilder::create_unixsock_vsock(vsock_config.clone()).is_err());
+        assert!(!std::path::Path::new(&vsock_config.uds_path).exists());
+        assert!(!std::path::Path::new(&format!("{}_listen_7", vsock_config.uds_path)).exists());
+    }
+
+    #[test]
+    fn test_vsock_tcp_ports() {
+        let mut tmp_sock_file = TempFile::new().unwrap();
+        tmp_sock_file.remove().unwrap();
+        let mut vsock_config = default_config(&tmp_sock_file);
+        vsock_config.tcp_listeners = vec![VsockTcpPortConfig {
+            port: 52,
+            addr: "127.0.0.1:0".to_string(),
+        }];
+        vsock_config.tcp_endpoints = vec![VsockTcpPortConfig {
+            port: 1024,
+            addr: "127.0.0.1:8080".to_string(),
+        }];
+
+        let vsock = VsockBuilder::create_unixsock_vsock(vsock_config.clone()).unwrap();
+        assert_eq!(vsock.backend().tcp_listeners()[0].0, 52);
+        assert_eq!(
+            vsock.backend().tcp_endpoints(),
+            vec![(1024, "127.0.0.1:8080".parse().unwrap())]
+        );
+        drop(vsock);
+        std::fs::remove_file(&vsock_config.uds_path).unwrap();
+
+        vsock_config.tcp_endpoints[0].addr = "localhost".to_string();
+        assert_eq!(
+            VsockBuilder::create_unixsock_vsock(vsock_config.clone())
+                .err()
+                .unwrap()
+                .to_string(),
+            VsockConfigError::InvalidTcpAddress("localhost".to_string()).to_string()
+        );
+        assert!(!std::path::Path::new(&vsock_config.uds_path).exists());
+    }
+
+    #[test]
+    fn test_vsock_persist_connections() {
+        let mut tmp_sock_file = TempFile::new().unwrap();
+        tmp_sock_file.remove().unwrap();
+        let mut vsock_config = default_config(&tmp_sock_file);
+
+        let vsock = VsockBuilder::create_unixsock_vsock(vsock_config.clone()).unwrap();
+        assert!(!vsock.backend().persist_connections());
+        drop(vsock);
+        std::fs::remove_file(&vsock_config.uds_path).unwrap();
+
+        vsock_config.persist_connections = true;
+        let vsock = VsockBuilder::create_unixsock_vsock(vsock_config.clone()).unwrap();
+        assert!(vsock.backend().persist_connections());
+        drop(vsock);
+        std::fs::remove_file(&vsock_config.uds_path).unwrap();
     }
 
     #[test]
@@ -146,5 +596,20 @@ pub(crate) mod tests {
             io::Error::from_raw_os_error(0),
         ));
         let _ = format!("{}{:?}", err, err);
+
+        let err = InvalidTcpAddress(String::from("localhost"));
+        let _ = format!("{}{:?}", err, err);
+
+        let err = InvalidBufAlloc(0);
+        let _ = format!("{}{:?}", err, err);
+
+        let err = InvalidMaxConnections(0);
+        let _ = format!("{}{:?}", err, err);
+
+        let err = InvalidConnTimeout;
+        let _ = format!("{}{:?}", err, err);
+
+        let err = UdsPathInUse(String::from("vsock.sock"));
+        let _ = format!("{}{:?}", err, err);
     }
 }
@@ -25,7 +25,7 @@ use crate::{
 };
 use kvm_bindings::{KVM_SYSTEM_EVENT_RESET, KVM_SYSTEM_EVENT_SHUTDOWN};
 use kvm_ioctls::VcpuExit;
-use logger::{error, info, IncMetric, METRICS};
+use logger::{error, info, IncMetric, VmEvent, EVENTS, METRICS};
 use seccomp::{BpfProgram, SeccompFilter};
 use utils::{
     errno,
@@ -294,9 +294,15 @@ impl Vcpu {
                 // Moreover if we allow the vCPU0 thread to finish execution, this might generate a
                 // seccomp failure because musl calls `sigprocmask` as part of `pthread_exit`.
                 // So we pause vCPU0 and send a signal to the emulation thread to stop the VMM.
-                Ok(VcpuEmulation::Stopped) => return self.exit(FC_EXIT_CODE_OK),
+                Ok(VcpuEmulation::Stopped) => {
+                    self.emit_exit_event("Guest shutdown".to_string(), FC_EXIT_CODE_OK);
+                    return self.exit(FC_EXIT_CODE_OK);
+                }
                 // Emulation errors lead to vCPU exit.
-                Err(_) => return self.exit(FC_EXIT_CODE_GENERIC_ERROR),
+                Err(e) => {
+                    self.emit_exit_event(e.to_string(), FC_EXIT_CODE_GENERIC_ERROR);
+                    return self.exit(FC_EXIT_CODE_GENERIC_ERROR);
+                }
             }
         }
 
@@ -420,6 +426,15 @@ impl Vcpu {
         StateMachine::next(Self::exited)
     }
 
+    // Reports to the event stream subscribers why the emulation loop ended.
+    fn emit_exit_event(&self, reason: String, exit_code: u8) {
+        EVENTS.emit(VmEvent::VcpuExit {
+            vcpu: self.kvm_vcpu.index,
+            reason,
+            exit_code: i32::from(exit_code),
+        });
+    }
+
     #[cfg(not(test))]
     // This is the main loop of the `Exited` state.
     fn exited(&mut self) -> StateMachine<Self> {
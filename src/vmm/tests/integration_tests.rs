@@ -17,6 +17,7 @@ use vmm::persist::{snapshot_state_sanity_check, LoadSnapshotError, MicrovmState}
 use vmm::resources::VmResources;
 use vmm::version_map::VERSION_MAP;
 use vmm::vmm_config::boot_source::BootSourceConfig;
+use vmm::vmm_config::mmds::MmdsStores;
 use vmm::vmm_config::snapshot::{CreateSnapshotParams, SnapshotType};
 
 use vmm::utilities::mock_devices::MockSerialInput;
@@ -295,6 +296,7 @@ fn verify_create_snapshot(is_diff: bool) -> (TempFile, TempFile) {
                 snapshot_path: snapshot_file.as_path().to_path_buf(),
                 mem_file_path: memory_file.as_path().to_path_buf(),
                 version: Some(String::from("0.24.0")),
+                progress: Default::default(),
             };
 
             {
@@ -302,6 +304,7 @@ fn verify_create_snapshot(is_diff: bool) -> (TempFile, TempFile) {
                 persist::create_snapshot(&mut locked_vmm, &snapshot_params, VERSION_MAP.clone())
                     .unwrap();
             }
+            assert!(snapshot_params.progress.bytes_written() > 0);
 
             vmm.lock().unwrap().stop(0);
         }
@@ -369,6 +372,8 @@ fn verify_load_snapshot(snapshot_file: TempFile, memory_file: TempFile) {
                 microvm_state,
                 mem,
                 false,
+                Vec::new(),
+                &MmdsStores::default(),
                 &empty_seccomp_filter,
             )
             .unwrap();
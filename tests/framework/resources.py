@@ -95,7 +95,9 @@ class Balloon():
     def create_json(
             amount_mb=None,
             deflate_on_oom=None,
-            stats_polling_interval_s=None
+            stats_polling_interval_s=None,
+            free_page_reporting=None,
+            auto_size=None
     ):
         """Compose the json associated to this type of API request."""
         datax = {}
@@ -109,6 +111,12 @@ class Balloon():
         if stats_polling_interval_s is not None:
             datax['stats_polling_interval_s'] = stats_polling_interval_s
 
+        if free_page_reporting is not None:
+            datax['free_page_reporting'] = free_page_reporting
+
+        if auto_size is not None:
+            datax['auto_size'] = auto_size
+
         return datax
 
 
@@ -373,7 +381,8 @@ class SnapshotLoad():
         )
 
     @staticmethod
-    def create_json(mem_file_path, snapshot_path, diff=False, resume=False):
+    def create_json(mem_file_path, snapshot_path, diff=False, resume=False,
+                    vsock_reattach=None, discard_mmds_data=None):
         """Compose the json associated to this type of API request."""
         datax = {
             'mem_file_path': mem_file_path,
@@ -383,6 +392,10 @@ class SnapshotLoad():
             datax['enable_diff_snapshots'] = True
         if resume:
             datax['resume_vm'] = True
+        if vsock_reattach is not None:
+            datax['vsock_reattach'] = vsock_reattach
+        if discard_mmds_data is not None:
+            datax['discard_mmds_data'] = discard_mmds_data
         return datax
 
 
@@ -545,10 +558,10 @@ class MMDS():
         self._mmds_cfg_url = api_url + self.MMDS_CFG_RESOURCE
         self._api_session = api_session
 
-    def put(self, **args):
+    def put(self, iface_id=None, **args):
         """Send a new MMDS request."""
         return self._api_session.put(
-            "{}".format(self._mmds_cfg_url),
+            self._data_store_url(iface_id),
             json=args['json']
         )
 
@@ -559,19 +572,25 @@ class MMDS():
             json=args['json']
         )
 
-    def patch(self, **args):
+    def patch(self, iface_id=None, **args):
         """Update the details of some MMDS request."""
         return self._api_session.patch(
-            "{}".format(self._mmds_cfg_url),
+            self._data_store_url(iface_id),
             json=args['json']
         )
 
-    def get(self):
+    def get(self, iface_id=None):
         """Get the status of the mmds request."""
         return self._api_session.get(
-            self._mmds_cfg_url
+            self._data_store_url(iface_id)
         )
 
+    def _data_store_url(self, iface_id):
+        """Build the URL of the shared or of a dedicated data store."""
+        if iface_id is None:
+            return self._mmds_cfg_url
+        return "{}/interfaces/{}".format(self._mmds_cfg_url, iface_id)
+
 
 class Network():
     """Facility for handling network configuration for a microvm."""
@@ -702,7 +721,15 @@ class Vsock():
     def create_json(
             vsock_id,
             guest_cid,
-            uds_path):
+            uds_path,
+            listen_ports=None,
+            tcp_listeners=None,
+            tcp_endpoints=None,
+            persist_connections=None,
+            buf_alloc=None,
+            max_connections=None,
+            conn_request_timeout_ms=None,
+            conn_shutdown_timeout_ms=None):
         """Create the json for the vsock specific API request."""
         datax = {
             'vsock_id': vsock_id,
@@ -710,4 +737,28 @@ class Vsock():
             'uds_path': uds_path
         }
 
+        if listen_ports is not None:
+            datax['listen_ports'] = listen_ports
+
+        if tcp_listeners is not None:
+            datax['tcp_listeners'] = tcp_listeners
+
+        if tcp_endpoints is not None:
+            datax['tcp_endpoints'] = tcp_endpoints
+
+        if persist_connections is not None:
+            datax['persist_connections'] = persist_connections
+
+        if buf_alloc is not None:
+            datax['buf_alloc'] = buf_alloc
+
+        if max_connections is not None:
+            datax['max_connections'] = max_connections
+
+        if conn_request_timeout_ms is not None:
+            datax['conn_request_timeout_ms'] = conn_request_timeout_ms
+
+        if conn_shutdown_timeout_ms is not None:
+            datax['conn_shutdown_timeout_ms'] = conn_shutdown_timeout_ms
+
         return datax
@@ -32,12 +32,14 @@ def test_flush_metrics(test_microvm_with_api):
         'api_server',
         'balloon',
         'block',
+        'delete_api_requests',
         'get_api_requests',
         'i8042',
         'latencies_us',
         'logger',
         'mmds',
         'net',
+        'net_interfaces',
         'patch_api_requests',
         'put_api_requests',
         'rtc',